@@ -0,0 +1,51 @@
+#version 300 es
+precision mediump float;
+precision highp int;
+
+struct DitherParams
+{
+    highp float strength;
+    highp float palette_size;
+};
+
+const highp float _bayer[16] = float[](0.0, 8.0, 2.0, 10.0, 12.0, 4.0, 14.0, 6.0, 3.0, 11.0, 1.0, 9.0, 15.0, 7.0, 13.0, 5.0);
+
+uniform DitherParams params;
+
+uniform highp sampler2D SPIRV_Cross_Combinedcolor_mapcolor_sampler;
+uniform highp sampler2D SPIRV_Cross_Combinedpalette_mapcolor_sampler;
+
+in highp vec4 vcolor;
+in highp vec2 vuv;
+layout(location = 0) out highp vec4 target;
+
+highp vec3 gamma(highp vec3 linear)
+{
+    return pow(linear, vec3(0.4545454680919647216796875));
+}
+
+void main()
+{
+    highp vec2 canvas_size = vec2(textureSize(SPIRV_Cross_Combinedcolor_mapcolor_sampler, 0));
+    ivec2 pixel = ivec2(vuv * canvas_size);
+    int index = ((pixel.y % 4) * 4) + (pixel.x % 4);
+    highp float threshold = ((_bayer[index] + 0.5) / 16.0) - 0.5;
+    highp vec4 color = texture(SPIRV_Cross_Combinedcolor_mapcolor_sampler, vuv);
+    highp vec3 wanted = gamma(color.xyz) + vec3(threshold * params.strength);
+    int palette_size = int(params.palette_size);
+    highp vec4 closest = color;
+    highp float closest_distance = 1000.0;
+    for (int i = 0; i < palette_size; i++)
+    {
+        highp vec4 entry = texelFetch(SPIRV_Cross_Combinedpalette_mapcolor_sampler, ivec2(i, 0), 0);
+        highp vec3 difference = gamma(entry.xyz) - wanted;
+        highp float distance = dot(difference, difference);
+        if (distance < closest_distance)
+        {
+            closest = entry;
+            closest_distance = distance;
+        }
+    }
+    target = vec4(closest.xyz, color.w);
+}
+
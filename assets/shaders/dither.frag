@@ -0,0 +1,53 @@
+#version 450
+#extension GL_ARB_separate_shader_objects : enable
+
+layout(location = 0) in vec4 vcolor;
+layout(location = 1) in vec2 vuv;
+
+layout(location = 0) out vec4 target;
+
+layout(set = 0, binding = 0) uniform texture2D color_map;
+layout(set = 0, binding = 1) uniform texture2D palette_map;
+layout(set = 0, binding = 2) uniform sampler color_sampler;
+
+layout(push_constant) uniform DitherParams {
+    float strength;
+    float palette_size;
+} params;
+
+const float bayer[16] = float[](
+    0.0, 8.0, 2.0, 10.0,
+    12.0, 4.0, 14.0, 6.0,
+    3.0, 11.0, 1.0, 9.0,
+    15.0, 7.0, 13.0, 5.0
+);
+
+// Colors are compared in gamma space, so thresholds are spread evenly by eye
+vec3 gamma(vec3 linear) {
+    return pow(linear, vec3(1.0 / 2.2));
+}
+
+void main() {
+    vec2 canvas_size = vec2(textureSize(sampler2D(color_map, color_sampler), 0));
+    ivec2 pixel = ivec2(vuv * canvas_size);
+    int index = (pixel.y % 4) * 4 + (pixel.x % 4);
+    float threshold = (bayer[index] + 0.5) / 16.0 - 0.5;
+
+    vec4 color = texture(sampler2D(color_map, color_sampler), vuv);
+    vec3 wanted = gamma(color.rgb) + threshold * params.strength;
+
+    int palette_size = int(params.palette_size);
+    vec4 closest = color;
+    float closest_distance = 1000.0;
+    for (int i = 0; i < palette_size; i++) {
+        vec4 entry = texelFetch(sampler2D(palette_map, color_sampler), ivec2(i, 0), 0);
+        vec3 difference = gamma(entry.rgb) - wanted;
+        float distance = dot(difference, difference);
+        if (distance < closest_distance) {
+            closest = entry;
+            closest_distance = distance;
+        }
+    }
+
+    target = vec4(closest.rgb, color.a);
+}
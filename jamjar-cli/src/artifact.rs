@@ -0,0 +1,205 @@
+use std::path::{Path, PathBuf};
+
+use crate::{
+    read_manifest, zip_dir, JamjarError, JamjarToml, OverwritePolicy, PathFilter, Platform,
+    Reporter,
+};
+
+/// One of the `[extra_artifacts]` from jamjar.toml, ready to build.
+#[derive(Debug)]
+pub struct ArtifactConfig {
+    pub app_root: Option<PathBuf>,
+    pub app_name: Option<String>,
+    /// The app's Cargo.toml, from `[package]`.
+    pub manifest_path: Option<PathBuf>,
+    pub name: String,
+    pub files: Vec<String>,
+    pub file_name: String,
+    pub output_dir: PathBuf,
+}
+
+impl ArtifactConfig {
+    /// Every extra artifact in the app's jamjar.toml. They go in the same
+    /// output directory as the host platform's package unless they set
+    /// their own.
+    pub fn resolve_all(app_root: Option<PathBuf>) -> Result<Vec<Self>, JamjarError> {
+        let root = app_root.clone().unwrap_or_else(|| PathBuf::from("."));
+        let jamjar_toml = JamjarToml::load(&root)?;
+        let package = jamjar_toml.package_options(Platform::host());
+
+        Ok(jamjar_toml
+            .extra_artifacts
+            .into_iter()
+            .map(|(name, options)| ArtifactConfig {
+                app_root: app_root.clone(),
+                app_name: package.app_name.clone(),
+                manifest_path: package.manifest_path.clone(),
+                file_name: options
+                    .name
+                    .unwrap_or_else(|| format!("{{app_name}}_{}_{{version}}.zip", name)),
+                name,
+                files: options.files,
+                output_dir: options
+                    .output_dir
+                    .or_else(|| package.output_dir.clone())
+                    .unwrap_or_else(|| PathBuf::from("./target/jamjar")),
+            })
+            .collect())
+    }
+
+    pub fn resolve(app_root: Option<PathBuf>, name: &str) -> Result<Self, JamjarError> {
+        let artifacts = Self::resolve_all(app_root)?;
+        let names = artifacts
+            .iter()
+            .map(|artifact| artifact.name.clone())
+            .collect::<Vec<_>>();
+
+        artifacts
+            .into_iter()
+            .find(|artifact| artifact.name == name)
+            .ok_or_else(|| {
+                JamjarError::StringError(format!(
+                    "jamjar.toml has no extra artifact named `{}`. Found: {}",
+                    name,
+                    names.join(", ")
+                ))
+            })
+    }
+}
+
+pub fn build_artifact(config: &ArtifactConfig) -> Result<PathBuf, JamjarError> {
+    build_artifact_with(config, &mut Reporter::Print)
+}
+
+pub fn build_artifact_with(
+    config: &ArtifactConfig,
+    reporter: &mut Reporter,
+) -> Result<PathBuf, JamjarError> {
+    let cwd = match config.app_root {
+        Some(ref path) => path.canonicalize().map_err(|e| {
+            JamjarError::io(
+                e,
+                &format!(
+                    "The input directory '{}' could not be found.",
+                    path.display()
+                ),
+            )
+        })?,
+        None => std::env::current_dir()
+            .map_err(|e| JamjarError::io(e, "Failed to get current directory."))?,
+    };
+
+    let (_, manifest) = read_manifest(&cwd, config.manifest_path.as_deref())?;
+
+    let app_name = config
+        .app_name
+        .to_owned()
+        .unwrap_or_else(|| manifest.package.name.clone());
+
+    let temp_dir = tempfile::tempdir()
+        .map_err(|e| JamjarError::io(e, "Failed to create temporary directory."))?;
+
+    reporter.line(&format!("Collecting files for {}:", config.name));
+    for pattern in &config.files {
+        let paths = glob_files(&cwd, pattern)?;
+        if paths.is_empty() {
+            return Err(JamjarError::StringError(format!(
+                "`{}` in extra artifact `{}` matched no files",
+                pattern, config.name
+            )));
+        }
+
+        // The files are flattened into the archive, so names must be unique
+        for path in paths {
+            let file_name = path.file_name().unwrap();
+            let staged_path = temp_dir.as_ref().join(file_name);
+            if staged_path.exists() {
+                return Err(JamjarError::StringError(format!(
+                    "More than one file in extra artifact `{}` is named `{}`",
+                    config.name,
+                    file_name.to_string_lossy()
+                )));
+            }
+
+            reporter.line(&format!(
+                "  {}",
+                path.strip_prefix(&cwd).unwrap_or(&path).display()
+            ));
+            std::fs::copy(&path, &staged_path).map_err(|e| {
+                JamjarError::io(e, &format!("Failed to copy '{}'.", path.display()))
+            })?;
+        }
+    }
+
+    std::fs::create_dir_all(&config.output_dir)
+        .map_err(|e| JamjarError::io(e, "Failed to create output directory."))?;
+
+    let output_path = config.output_dir.join(
+        config
+            .file_name
+            .replace("{app_name}", &app_name)
+            .replace("{version}", &manifest.package.version),
+    );
+
+    reporter.line("Compressing files to output");
+    zip_dir(
+        temp_dir.as_ref(),
+        &output_path,
+        OverwritePolicy::Overwrite,
+        &PathFilter::default(),
+        reporter,
+    )
+}
+
+/// The files matching `pattern`, relative to `root`, in sorted order.
+fn glob_files(root: &Path, pattern: &str) -> Result<Vec<PathBuf>, JamjarError> {
+    let components = pattern
+        .split(['/', '\\'])
+        .filter(|component| !component.is_empty() && *component != ".");
+
+    let mut paths = vec![root.to_owned()];
+    for component in components {
+        let mut matches = vec![];
+        for dir in paths {
+            if !component.contains(['*', '?']) {
+                let path = dir.join(component);
+                if path.exists() {
+                    matches.push(path);
+                }
+                continue;
+            }
+
+            let entries = match std::fs::read_dir(&dir) {
+                Ok(entries) => entries,
+                Err(_) => continue,
+            };
+
+            for entry in entries {
+                let entry = entry?;
+                if wildcard_match(component, &entry.file_name().to_string_lossy()) {
+                    matches.push(entry.path());
+                }
+            }
+        }
+        paths = matches;
+    }
+
+    paths.retain(|path| path.is_file());
+    paths.sort();
+    Ok(paths)
+}
+
+pub(crate) fn wildcard_match(pattern: &str, name: &str) -> bool {
+    fn matches(pattern: &[char], name: &[char]) -> bool {
+        match pattern.split_first() {
+            None => name.is_empty(),
+            Some(('*', rest)) => (0..=name.len()).any(|i| matches(rest, &name[i..])),
+            Some(('?', rest)) => !name.is_empty() && matches(rest, &name[1..]),
+            Some((c, rest)) => name.first() == Some(c) && matches(rest, &name[1..]),
+        }
+    }
+
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let name = name.chars().collect::<Vec<_>>();
+    matches(&pattern, &name)
+}
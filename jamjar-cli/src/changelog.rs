@@ -0,0 +1,64 @@
+use std::path::Path;
+
+use crate::{JamjarError, Reporter};
+
+pub const CHANGES_FILE: &str = "CHANGES.txt";
+
+/// The notes under the `## ` heading for `version` in a markdown changelog,
+/// without the heading itself. The heading only has to start with the
+/// version, so `## 0.4.0 - 2021-04-18` matches `0.4.0`, but `## 0.4.01`
+/// doesn't.
+pub fn changelog_section(markdown: &str, version: &str) -> Option<String> {
+    fn heading(line: &str) -> Option<&str> {
+        line.strip_prefix("## ").map(str::trim)
+    }
+
+    let matches = |line: &str| match heading(line).and_then(|text| text.strip_prefix(version)) {
+        Some(rest) => !rest.starts_with(|c: char| c.is_alphanumeric() || c == '.'),
+        None => false,
+    };
+
+    let mut lines = markdown.lines();
+    lines.find(|line| matches(line))?;
+
+    let section = lines
+        .take_while(|line| heading(line).is_none())
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    Some(section.trim().to_owned())
+}
+
+/// Read the changes for `version` from the changelog at `path`. A missing
+/// section is an error if `required`, or a warning otherwise.
+pub(crate) fn read_changes(
+    path: &Path,
+    version: &str,
+    required: bool,
+    reporter: &mut Reporter,
+) -> Result<Option<String>, JamjarError> {
+    let markdown = std::fs::read_to_string(path).map_err(|e| {
+        JamjarError::io(
+            e,
+            &format!("Failed to read changelog '{}'.", path.display()),
+        )
+    })?;
+
+    match changelog_section(&markdown, version) {
+        Some(changes) => Ok(Some(changes)),
+        None if required => Err(JamjarError::StringError(format!(
+            "{} has no `## {}` section, and `require_changelog` is set",
+            path.display(),
+            version
+        ))),
+        None => {
+            reporter.line(&format!(
+                "Warning: {} has no `## {}` section, so {} won't be included.",
+                path.display(),
+                version,
+                CHANGES_FILE
+            ));
+            Ok(None)
+        }
+    }
+}
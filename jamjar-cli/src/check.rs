@@ -0,0 +1,145 @@
+use std::path::{Path, PathBuf};
+
+use jamjar::resource_keys::{verify_keys, KeyMismatches, KeyMode};
+
+use crate::{asset_filter, filter::slashed, JamjarError, JamjarToml, PathFilter, Reporter};
+
+/// One of the `[[resources]]` from jamjar.toml, with its keys loaded.
+#[derive(Debug)]
+pub struct ResourceCheck {
+    pub dir: PathBuf,
+    pub keys: Vec<String>,
+    pub ignore: Vec<String>,
+    pub mode: KeyMode,
+    /// `dir` relative to the app root.
+    pub rel_dir: PathBuf,
+    /// Which assets ship with the app. Files that don't ship don't need
+    /// keys.
+    pub assets: PathFilter,
+}
+
+impl ResourceCheck {
+    pub fn resolve_all(app_root: Option<PathBuf>) -> Result<Vec<Self>, JamjarError> {
+        let root = app_root.unwrap_or_else(|| PathBuf::from("."));
+        let jamjar_toml = JamjarToml::load(&root)?;
+        let assets = asset_filter(&root, &jamjar_toml)?;
+
+        jamjar_toml
+            .resources
+            .into_iter()
+            .map(|options| {
+                let keys = match (options.keys, &options.enum_file) {
+                    (Some(keys), _) => keys,
+                    (None, Some(enum_file)) => enum_keys(enum_file)?,
+                    (None, None) => {
+                        return Err(JamjarError::StringError(format!(
+                            "[[resources]] entry for '{}' in jamjar.toml needs `keys` or an `enum_file`",
+                            options.dir.display()
+                        )))
+                    }
+                };
+
+                let rel_dir = options
+                    .dir
+                    .strip_prefix(&root)
+                    .unwrap_or(&options.dir)
+                    .to_owned();
+                Ok(ResourceCheck {
+                    dir: options.dir,
+                    keys,
+                    ignore: options.ignore,
+                    mode: options.mode.unwrap_or_default(),
+                    rel_dir,
+                    assets: assets.clone(),
+                })
+            })
+            .collect()
+    }
+}
+
+pub fn check_resources(checks: &[ResourceCheck]) -> Result<(), JamjarError> {
+    check_resources_with(checks, &mut Reporter::Print)
+}
+
+/// Verify every directory, reporting each one, and fail with every
+/// mismatch found.
+pub fn check_resources_with(
+    checks: &[ResourceCheck],
+    reporter: &mut Reporter,
+) -> Result<(), JamjarError> {
+    let mut failures = vec![];
+
+    for check in checks {
+        let mut files = file_names(&check.dir)?;
+        files.retain(|name| check.assets.includes(&slashed(&check.rel_dir.join(name))));
+        match verify_keys(check.mode, &check.keys, &files, &check.ignore) {
+            Ok(()) => reporter.line(&format!(
+                "{}: {} keys ok",
+                check.dir.display(),
+                check.keys.len()
+            )),
+            Err(mismatches) => {
+                reporter.line(&format!("{}: failed", check.dir.display()));
+                failures.push((check.dir.clone(), mismatches));
+            }
+        }
+    }
+
+    match failures.is_empty() {
+        true => Ok(()),
+        false => Err(JamjarError::ResourceMismatch { dirs: failures }),
+    }
+}
+
+pub(crate) fn describe_mismatches(dirs: &[(PathBuf, KeyMismatches)]) -> String {
+    let mut description = String::new();
+    for (dir, mismatches) in dirs {
+        description.push_str(&format!("\n  {}:", dir.display()));
+        for mismatch in &mismatches.0 {
+            description.push_str(&format!("\n    {}", mismatch));
+        }
+    }
+    description
+}
+
+/// The names of the files directly inside `dir`, in sorted order, like
+/// `resource_list!` and `jamjar::codegen` see them.
+fn file_names(dir: &Path) -> Result<Vec<String>, JamjarError> {
+    let entries = std::fs::read_dir(dir).map_err(|e| {
+        JamjarError::io(
+            e,
+            &format!("Could not read resource directory '{}'.", dir.display()),
+        )
+    })?;
+
+    let mut names = vec![];
+    for entry in entries {
+        let entry = entry?;
+        if entry.path().is_file() {
+            names.push(entry.file_name().to_string_lossy().into_owned());
+        }
+    }
+    names.sort();
+    Ok(names)
+}
+
+/// The variants of the enum in a generated files enum module.
+fn enum_keys(path: &Path) -> Result<Vec<String>, JamjarError> {
+    let source = std::fs::read_to_string(path)
+        .map_err(|e| JamjarError::io(e, &format!("Could not read '{}'.", path.display())))?;
+
+    let body = source
+        .find("enum ")
+        .map(|start| &source[start..])
+        .and_then(|rest| Some(&rest[rest.find('{')? + 1..rest.find('}')?]))
+        .ok_or_else(|| {
+            JamjarError::StringError(format!("No enum found in '{}'", path.display()))
+        })?;
+
+    Ok(body
+        .split(',')
+        .map(str::trim)
+        .filter(|variant| !variant.is_empty())
+        .map(str::to_owned)
+        .collect())
+}
@@ -0,0 +1,1205 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+use jamjar::resource_keys::KeyMode;
+use serde::{
+    de::{DeserializeOwned, Error as _},
+    ser::SerializeMap,
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+use toml::{value::Table, Value};
+
+use crate::JamjarError;
+
+pub const JAMJAR_TOML: &str = "jamjar.toml";
+
+const ROOT_KEYS: &[&str] = &[
+    "package",
+    "web",
+    "dist",
+    "extra_artifacts",
+    "release",
+    "resources",
+    "asset_exclude",
+];
+const PACKAGE_KEYS: &[&str] = &[
+    "app_name",
+    "bin_name",
+    "output_dir",
+    "icon_path",
+    "features",
+    "default_features",
+    "all_features",
+    "format",
+    "appimage_fallback",
+    "build_info",
+    "launch_helpers",
+    "controls",
+    "pre_build",
+    "post_build",
+    "smoke_test",
+    "release_feed",
+    "size_report",
+    "icon_sizes",
+    "changelog",
+    "require_changelog",
+    "locked",
+    "offline",
+    "toolchain",
+    "target",
+    "manifest_path",
+    "on_existing",
+    "zip_exclude",
+    "zip_include_only",
+    "keep_symbols",
+    "resources",
+    "third_party_notices",
+    "strict_notices",
+    "bundle_id",
+    "sign_identity",
+    "entitlements",
+    "notarize",
+    "macos",
+    "windows",
+    "linux",
+];
+/// Keys for `[package.<platform>]` and Cargo.toml metadata. There's no
+/// `target`, as the target picks the platform.
+pub(crate) const PLATFORM_KEYS: &[&str] = &[
+    "app_name",
+    "bin_name",
+    "output_dir",
+    "icon_path",
+    "features",
+    "default_features",
+    "all_features",
+    "format",
+    "appimage_fallback",
+    "build_info",
+    "launch_helpers",
+    "controls",
+    "pre_build",
+    "post_build",
+    "smoke_test",
+    "release_feed",
+    "size_report",
+    "icon_sizes",
+    "changelog",
+    "require_changelog",
+    "locked",
+    "offline",
+    "toolchain",
+    "manifest_path",
+    "on_existing",
+    "zip_exclude",
+    "zip_include_only",
+    "keep_symbols",
+    "resources",
+    "third_party_notices",
+    "strict_notices",
+    "bundle_id",
+    "sign_identity",
+    "entitlements",
+    "notarize",
+];
+pub(crate) const WEB_KEYS: &[&str] = &[
+    "app_name",
+    "bin_name",
+    "output_dir",
+    "features",
+    "default_features",
+    "all_features",
+    "bypass_spirv_cross",
+    "spirv_cross",
+    "debug",
+    "names_section",
+    "emit_html",
+    "html_filename",
+    "public_path",
+    "size_report",
+    "locked",
+    "offline",
+    "toolchain",
+    "manifest_path",
+    "wasm_compat",
+    "third_party_notices",
+    "strict_notices",
+];
+
+const DIST_KEYS: &[&str] = &["name", "kind"];
+const ARTIFACT_KEYS: &[&str] = &["files", "name", "output_dir"];
+pub(crate) const RELEASE_KEYS: &[&str] = &["require_clean_git", "lock_timeout"];
+const RESOURCE_KEYS: &[&str] = &["dir", "keys", "enum_file", "ignore", "mode"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Platform {
+    MacOS,
+    Windows,
+    Linux,
+}
+
+impl Platform {
+    pub const ALL: &'static [Platform] = &[Platform::MacOS, Platform::Windows, Platform::Linux];
+
+    /// The platform a target triple like `x86_64-pc-windows-gnu` builds
+    /// for, if it's one that can be packaged.
+    pub fn from_target(triple: &str) -> Option<Self> {
+        if triple.contains("-windows") {
+            Some(Platform::Windows)
+        } else if triple.contains("-apple-darwin") {
+            Some(Platform::MacOS)
+        } else if triple.contains("-linux") {
+            Some(Platform::Linux)
+        } else {
+            None
+        }
+    }
+
+    pub fn host() -> Self {
+        #[cfg(windows)]
+        {
+            Platform::Windows
+        }
+        #[cfg(target_os = "macos")]
+        {
+            Platform::MacOS
+        }
+        #[cfg(all(unix, not(target_os = "macos")))]
+        {
+            Platform::Linux
+        }
+    }
+
+    /// The short name used in artifact filenames.
+    pub fn name(self) -> &'static str {
+        match self {
+            Platform::MacOS => "macos",
+            Platform::Windows => "win",
+            Platform::Linux => "linux",
+        }
+    }
+
+    /// The name of this platform's section in jamjar.toml.
+    pub fn section(self) -> &'static str {
+        match self {
+            Platform::MacOS => "macos",
+            Platform::Windows => "windows",
+            Platform::Linux => "linux",
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    #[default]
+    Zip,
+    /// A single double-clickable executable. Linux only.
+    AppImage,
+    /// A `bin` and `share` tree that can be extracted into `~/.local`.
+    /// Linux only.
+    #[serde(rename = "tar.gz")]
+    TarGz,
+    /// A disk image with the `.app` on it, which keeps its permissions
+    /// however it's opened. macOS only.
+    Dmg,
+}
+
+impl std::str::FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "zip" => Ok(OutputFormat::Zip),
+            "appimage" => Ok(OutputFormat::AppImage),
+            "tar.gz" | "targz" => Ok(OutputFormat::TarGz),
+            "dmg" => Ok(OutputFormat::Dmg),
+            _ => Err(format!(
+                "unknown output format `{}`, expected `zip`, `appimage`, `tar.gz` or `dmg`",
+                s
+            )),
+        }
+    }
+}
+
+/// Which browsers a web build has to run in.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CompatLevel {
+    /// Browsers with the WebAssembly features Rust uses by default.
+    #[default]
+    Modern,
+    /// Also browsers with only the first version of WebAssembly, like
+    /// Safari before 15. Costs some size and speed.
+    Legacy,
+}
+
+impl std::str::FromStr for CompatLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "modern" => Ok(CompatLevel::Modern),
+            "legacy" => Ok(CompatLevel::Legacy),
+            _ => Err(format!(
+                "unknown compatibility level `{}`, expected `modern` or `legacy`",
+                s
+            )),
+        }
+    }
+}
+
+/// What to do when a package with the same name is already in the output
+/// directory, usually because the version wasn't bumped. Packages from
+/// `jamjar dist` default to `Error` instead.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OverwritePolicy {
+    /// Replace it.
+    #[default]
+    Overwrite,
+    /// Fail, before building anything.
+    Error,
+    /// Keep it, and add `-1`, `-2`, etc. to the new package's name.
+    Rename,
+}
+
+impl std::str::FromStr for OverwritePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "overwrite" => Ok(OverwritePolicy::Overwrite),
+            "error" => Ok(OverwritePolicy::Error),
+            "rename" => Ok(OverwritePolicy::Rename),
+            _ => Err(format!(
+                "unknown policy `{}`, expected `overwrite`, `error`, or `rename`",
+                s
+            )),
+        }
+    }
+}
+
+/// Where web builds get the spirv_cross wrapper scripts from.
+///
+/// In jamjar.toml this is `"embedded"`, `"skip"`, or `{ path = "dir" }`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SpirvCrossMode {
+    /// Use the copies built into jamjar.
+    Embedded,
+    /// Copy `spirv_cross_wrapper_glsl.js` and `.wasm` from this directory.
+    #[serde(rename = "path")]
+    FromPath(PathBuf),
+    /// Don't include them, for apps built with `bypass_spirv_cross`.
+    Skip,
+}
+
+// toml can't serialize enum variants with data, so `{ path = "dir" }` is
+// written out by hand.
+impl Serialize for SpirvCrossMode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            SpirvCrossMode::Embedded => serializer.serialize_str("embedded"),
+            SpirvCrossMode::Skip => serializer.serialize_str("skip"),
+            SpirvCrossMode::FromPath(path) => {
+                let mut map = serializer.serialize_map(Some(1))?;
+                map.serialize_entry("path", path)?;
+                map.end()
+            }
+        }
+    }
+}
+
+impl std::str::FromStr for SpirvCrossMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "embedded" => Ok(SpirvCrossMode::Embedded),
+            "skip" => Ok(SpirvCrossMode::Skip),
+            "" => Err("expected `embedded`, `skip`, or a directory path".to_owned()),
+            path => Ok(SpirvCrossMode::FromPath(PathBuf::from(path))),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExitExpectation {
+    /// The app must exit successfully before the timeout.
+    #[default]
+    Clean,
+    /// The app may also still be running at the timeout, in which case
+    /// it's killed. Useful for apps with no way to exit on their own.
+    Survive,
+}
+
+/// Launch the packaged app before archiving it, to check that it runs.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct SmokeTest {
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// How long to wait for the app, in seconds.
+    #[serde(
+        default = "SmokeTest::default_timeout",
+        deserialize_with = "SmokeTest::deserialize_timeout"
+    )]
+    pub timeout: f64,
+    #[serde(default)]
+    pub expect_exit: ExitExpectation,
+}
+
+impl SmokeTest {
+    fn default_timeout() -> f64 {
+        10.
+    }
+
+    /// The timeout becomes a `Duration`, which can't be negative, NaN, or
+    /// too large for a `u64` of seconds.
+    fn deserialize_timeout<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+        let timeout = f64::deserialize(deserializer)?;
+        match timeout > 0. && timeout < u64::MAX as f64 {
+            true => Ok(timeout),
+            false => Err(D::Error::custom(format!(
+                "smoke test timeout must be a positive number of seconds, not {}",
+                timeout
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
+pub struct PackageOptions {
+    pub app_name: Option<String>,
+    pub bin_name: Option<String>,
+    pub output_dir: Option<PathBuf>,
+    pub icon_path: Option<PathBuf>,
+    pub features: Option<Vec<String>>,
+    pub default_features: Option<bool>,
+    pub all_features: Option<bool>,
+    pub format: Option<OutputFormat>,
+    /// Make a tar.gz instead of failing if an AppImage was asked for but
+    /// `appimagetool` isn't installed.
+    pub appimage_fallback: Option<bool>,
+    pub build_info: Option<bool>,
+    pub launch_helpers: Option<bool>,
+    /// Describes the app's controls in the README added by `launch_helpers`.
+    pub controls: Option<String>,
+    /// Shell commands to run in the app root before building.
+    pub pre_build: Option<Vec<String>>,
+    /// Shell commands to run in the app root after packaging.
+    pub post_build: Option<Vec<String>>,
+    pub smoke_test: Option<SmokeTest>,
+    /// Write `latest.json` and `releases.json` describing the package.
+    pub release_feed: Option<bool>,
+    /// Write a breakdown of what's taking up space in the package.
+    pub size_report: Option<bool>,
+    /// The sizes of the icons installed in the `hicolor` theme on Linux.
+    pub icon_sizes: Option<Vec<u32>>,
+    /// A markdown changelog to take the current version's notes from.
+    pub changelog: Option<PathBuf>,
+    /// Fail if the changelog has no section for the current version.
+    pub require_changelog: Option<bool>,
+    /// Pass `--locked` to cargo, so Cargo.lock is never updated.
+    pub locked: Option<bool>,
+    /// Pass `--offline` to cargo, so it never uses the network.
+    pub offline: Option<bool>,
+    /// The Rust toolchain to build with, like `1.74.0`. Defaults to the
+    /// one rustup picks for the app.
+    pub toolchain: Option<String>,
+    /// The target triple to build for, like `x86_64-pc-windows-gnu`, to
+    /// package for another platform.
+    pub target: Option<String>,
+    /// The app's Cargo.toml, relative to the app root, if it's not in the
+    /// app root itself.
+    pub manifest_path: Option<PathBuf>,
+    /// What to do if the package already exists.
+    pub on_existing: Option<OverwritePolicy>,
+    /// Files to leave out of zip packages, as patterns relative to the
+    /// package root. These are added to the defaults, which leave out
+    /// `.DS_Store`, `Thumbs.db`, and `*.pdb`.
+    pub zip_exclude: Option<Vec<String>>,
+    /// If set, only files matching one of these go in zip packages.
+    pub zip_include_only: Option<Vec<String>>,
+    /// Leave `*.pdb` debug symbols in, unless `zip_exclude` leaves them out.
+    pub keep_symbols: Option<bool>,
+    /// Files and directories to ship alongside the executable, relative to
+    /// the app root (or to Cargo.toml, in its metadata). They go in
+    /// `Contents/Resources` in macOS apps.
+    pub resources: Option<Vec<PathBuf>>,
+    /// Write the licenses of every crate in the app to
+    /// `THIRD-PARTY-NOTICES.txt` in the package.
+    pub third_party_notices: Option<bool>,
+    /// Fail, instead of warning, if a crate's license needs reviewing.
+    pub strict_notices: Option<bool>,
+    /// The macOS bundle identifier, like `com.example.mygame`.
+    pub bundle_id: Option<String>,
+    /// The identity to sign macOS apps with, as passed to `codesign`, or
+    /// `-` to sign them ad-hoc.
+    pub sign_identity: Option<String>,
+    /// An entitlements plist to sign macOS apps with.
+    pub entitlements: Option<PathBuf>,
+    /// The `notarytool` keychain profile to notarize macOS apps with.
+    pub notarize: Option<String>,
+}
+
+impl PackageOptions {
+    /// Fill in any options not set here from `fallback`.
+    pub fn or(self, fallback: PackageOptions) -> PackageOptions {
+        PackageOptions {
+            app_name: self.app_name.or(fallback.app_name),
+            bin_name: self.bin_name.or(fallback.bin_name),
+            output_dir: self.output_dir.or(fallback.output_dir),
+            icon_path: self.icon_path.or(fallback.icon_path),
+            features: self.features.or(fallback.features),
+            default_features: self.default_features.or(fallback.default_features),
+            all_features: self.all_features.or(fallback.all_features),
+            format: self.format.or(fallback.format),
+            appimage_fallback: self.appimage_fallback.or(fallback.appimage_fallback),
+            build_info: self.build_info.or(fallback.build_info),
+            launch_helpers: self.launch_helpers.or(fallback.launch_helpers),
+            controls: self.controls.or(fallback.controls),
+            pre_build: self.pre_build.or(fallback.pre_build),
+            post_build: self.post_build.or(fallback.post_build),
+            smoke_test: self.smoke_test.or(fallback.smoke_test),
+            release_feed: self.release_feed.or(fallback.release_feed),
+            size_report: self.size_report.or(fallback.size_report),
+            icon_sizes: self.icon_sizes.or(fallback.icon_sizes),
+            changelog: self.changelog.or(fallback.changelog),
+            require_changelog: self.require_changelog.or(fallback.require_changelog),
+            locked: self.locked.or(fallback.locked),
+            offline: self.offline.or(fallback.offline),
+            toolchain: self.toolchain.or(fallback.toolchain),
+            target: self.target.or(fallback.target),
+            manifest_path: self.manifest_path.or(fallback.manifest_path),
+            on_existing: self.on_existing.or(fallback.on_existing),
+            zip_exclude: self.zip_exclude.or(fallback.zip_exclude),
+            zip_include_only: self.zip_include_only.or(fallback.zip_include_only),
+            keep_symbols: self.keep_symbols.or(fallback.keep_symbols),
+            resources: self.resources.or(fallback.resources),
+            third_party_notices: self.third_party_notices.or(fallback.third_party_notices),
+            strict_notices: self.strict_notices.or(fallback.strict_notices),
+            bundle_id: self.bundle_id.or(fallback.bundle_id),
+            sign_identity: self.sign_identity.or(fallback.sign_identity),
+            entitlements: self.entitlements.or(fallback.entitlements),
+            notarize: self.notarize.or(fallback.notarize),
+        }
+    }
+
+    fn relative_to(self, root: &Path) -> PackageOptions {
+        PackageOptions {
+            output_dir: self.output_dir.map(|path| root.join(path)),
+            icon_path: self.icon_path.map(|path| root.join(path)),
+            changelog: self.changelog.map(|path| root.join(path)),
+            entitlements: self.entitlements.map(|path| root.join(path)),
+            resources: self
+                .resources
+                .map(|paths| paths.into_iter().map(|path| root.join(path)).collect()),
+            ..self
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
+pub struct WebBuildOptions {
+    pub app_name: Option<String>,
+    pub bin_name: Option<String>,
+    pub output_dir: Option<PathBuf>,
+    pub features: Option<Vec<String>>,
+    pub default_features: Option<bool>,
+    pub all_features: Option<bool>,
+    pub bypass_spirv_cross: Option<bool>,
+    pub spirv_cross: Option<SpirvCrossMode>,
+    pub debug: Option<bool>,
+    pub names_section: Option<bool>,
+    pub emit_html: Option<bool>,
+    pub html_filename: Option<String>,
+    pub public_path: Option<String>,
+    pub size_report: Option<bool>,
+    pub locked: Option<bool>,
+    pub offline: Option<bool>,
+    pub toolchain: Option<String>,
+    pub manifest_path: Option<PathBuf>,
+    pub wasm_compat: Option<CompatLevel>,
+    pub third_party_notices: Option<bool>,
+    pub strict_notices: Option<bool>,
+}
+
+impl WebBuildOptions {
+    /// Fill in any options not set here from `fallback`.
+    pub fn or(self, fallback: WebBuildOptions) -> WebBuildOptions {
+        let spirv_cross = self.spirv_cross_mode().or(fallback.spirv_cross_mode());
+
+        WebBuildOptions {
+            app_name: self.app_name.or(fallback.app_name),
+            bin_name: self.bin_name.or(fallback.bin_name),
+            output_dir: self.output_dir.or(fallback.output_dir),
+            features: self.features.or(fallback.features),
+            default_features: self.default_features.or(fallback.default_features),
+            all_features: self.all_features.or(fallback.all_features),
+            bypass_spirv_cross: self.bypass_spirv_cross.or(fallback.bypass_spirv_cross),
+            spirv_cross,
+            debug: self.debug.or(fallback.debug),
+            names_section: self.names_section.or(fallback.names_section),
+            emit_html: self.emit_html.or(fallback.emit_html),
+            html_filename: self.html_filename.or(fallback.html_filename),
+            public_path: self.public_path.or(fallback.public_path),
+            size_report: self.size_report.or(fallback.size_report),
+            locked: self.locked.or(fallback.locked),
+            offline: self.offline.or(fallback.offline),
+            toolchain: self.toolchain.or(fallback.toolchain),
+            manifest_path: self.manifest_path.or(fallback.manifest_path),
+            wasm_compat: self.wasm_compat.or(fallback.wasm_compat),
+            third_party_notices: self.third_party_notices.or(fallback.third_party_notices),
+            strict_notices: self.strict_notices.or(fallback.strict_notices),
+        }
+    }
+
+    /// `spirv_cross`, or else the mode `bypass_spirv_cross` asks for. This is
+    /// worked out for each source of options before they're merged, so that
+    /// a `bypass_spirv_cross` flag isn't overridden by `spirv_cross` from
+    /// jamjar.toml.
+    fn spirv_cross_mode(&self) -> Option<SpirvCrossMode> {
+        match (&self.spirv_cross, self.bypass_spirv_cross) {
+            (Some(mode), _) => Some(mode.clone()),
+            (None, Some(true)) => Some(SpirvCrossMode::Skip),
+            (None, Some(false)) => Some(SpirvCrossMode::Embedded),
+            (None, None) => None,
+        }
+    }
+
+    fn relative_to(self, root: &Path) -> WebBuildOptions {
+        WebBuildOptions {
+            output_dir: self.output_dir.map(|path| root.join(path)),
+            spirv_cross: self.spirv_cross.map(|mode| match mode {
+                SpirvCrossMode::FromPath(path) => SpirvCrossMode::FromPath(root.join(path)),
+                mode => mode,
+            }),
+            ..self
+        }
+    }
+}
+
+/// An archive of files from the app, like a soundtrack, built alongside the
+/// main package without involving cargo.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ExtraArtifactOptions {
+    /// Paths relative to the app root. `*` and `?` match any characters
+    /// within one directory or file name.
+    pub files: Vec<String>,
+    /// The archive's file name, with `{app_name}` and `{version}` filled in.
+    pub name: Option<String>,
+    pub output_dir: Option<PathBuf>,
+}
+
+impl ExtraArtifactOptions {
+    fn relative_to(self, root: &Path) -> ExtraArtifactOptions {
+        ExtraArtifactOptions {
+            output_dir: self.output_dir.map(|path| root.join(path)),
+            ..self
+        }
+    }
+}
+
+/// Checks that apply to every package and web build.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ReleaseOptions {
+    /// Refuse to build if the app's git working tree has uncommitted
+    /// changes, unless `--allow-dirty` is passed.
+    pub require_clean_git: Option<bool>,
+    /// How many seconds to wait for another jamjar run writing to the same
+    /// output directory to finish.
+    pub lock_timeout: Option<u64>,
+}
+
+/// A directory of resources for `jamjar check` to compare with the keys
+/// the app uses for them.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ResourceCheckOptions {
+    pub dir: PathBuf,
+    pub keys: Option<Vec<String>>,
+    /// A generated files enum (from `jamjar::codegen`) to take the keys
+    /// from, if `keys` isn't set.
+    pub enum_file: Option<PathBuf>,
+    /// Patterns for files that don't need a key, where `*` and `?` match
+    /// any characters.
+    #[serde(default)]
+    pub ignore: Vec<String>,
+    /// How keys are paired with files, as passed to `map_resources_with`.
+    pub mode: Option<KeyMode>,
+}
+
+impl ResourceCheckOptions {
+    fn relative_to(self, root: &Path) -> ResourceCheckOptions {
+        ResourceCheckOptions {
+            dir: root.join(self.dir),
+            enum_file: self.enum_file.map(|path| root.join(path)),
+            ..self
+        }
+    }
+}
+
+// Only a handful of these are made, when jamjar.toml is read
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone, PartialEq)]
+pub enum DistOptions {
+    Package(PackageOptions),
+    Web(WebBuildOptions),
+}
+
+/// One build run by `jamjar dist`. Options not set here fall back to the
+/// `[package]` or `[web]` sections.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DistEntry {
+    pub name: String,
+    pub options: DistOptions,
+}
+
+/// The contents of an app's `jamjar.toml`.
+///
+/// The `[package]` section holds defaults for every platform, which can be
+/// overridden by `[package.macos]`, `[package.windows]`, and
+/// `[package.linux]`. The `[web]` section configures web builds.
+///
+/// Each `[[dist]]` entry has a `kind` (`"package"` or `"web"`), an optional
+/// `name`, and any of the options for that kind.
+///
+/// Each table in `[extra_artifacts]` describes another archive to build,
+/// under its own name.
+///
+/// The `[release]` section holds checks for every build.
+///
+/// Each `[[resources]]` entry is a directory for `jamjar check` to verify.
+///
+/// `asset_exclude` lists patterns, relative to the app root, for assets
+/// that never ship or get checked, like source art.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct JamjarToml {
+    pub package: PackageOptions,
+    pub platforms: HashMap<Platform, PackageOptions>,
+    pub web: WebBuildOptions,
+    pub dist: Vec<DistEntry>,
+    pub extra_artifacts: BTreeMap<String, ExtraArtifactOptions>,
+    pub release: ReleaseOptions,
+    pub resources: Vec<ResourceCheckOptions>,
+    pub asset_exclude: Vec<String>,
+}
+
+impl JamjarToml {
+    /// Load `jamjar.toml` from the app root, if there is one. Relative
+    /// paths inside it are resolved against the app root.
+    pub fn load(app_root: &Path) -> Result<Self, JamjarError> {
+        let path = app_root.join(JAMJAR_TOML);
+        if !path.exists() {
+            return Ok(JamjarToml::default());
+        }
+
+        let source = std::fs::read_to_string(&path)
+            .map_err(|e| JamjarError::io(e, "Could not read jamjar.toml."))?;
+
+        let JamjarToml {
+            package,
+            platforms,
+            web,
+            dist,
+            extra_artifacts,
+            release,
+            resources,
+            asset_exclude,
+        } = Self::parse(&source)?;
+
+        Ok(JamjarToml {
+            package: package.relative_to(app_root),
+            platforms: platforms
+                .into_iter()
+                .map(|(platform, options)| (platform, options.relative_to(app_root)))
+                .collect(),
+            web: web.relative_to(app_root),
+            dist: dist
+                .into_iter()
+                .map(|entry| DistEntry {
+                    options: match entry.options {
+                        DistOptions::Package(options) => {
+                            DistOptions::Package(options.relative_to(app_root))
+                        }
+                        DistOptions::Web(options) => {
+                            DistOptions::Web(options.relative_to(app_root))
+                        }
+                    },
+                    ..entry
+                })
+                .collect(),
+            extra_artifacts: extra_artifacts
+                .into_iter()
+                .map(|(name, options)| (name, options.relative_to(app_root)))
+                .collect(),
+            release,
+            resources: resources
+                .into_iter()
+                .map(|options| options.relative_to(app_root))
+                .collect(),
+            asset_exclude,
+        })
+    }
+
+    pub fn parse(source: &str) -> Result<Self, JamjarError> {
+        let mut root: Table = toml::from_str(source)?;
+        strip_unknown_keys("", &mut root, ROOT_KEYS);
+
+        let mut package = take_table(&mut root, "package")?.unwrap_or_default();
+        strip_unknown_keys("package", &mut package, PACKAGE_KEYS);
+
+        let mut platforms = HashMap::new();
+        for &platform in Platform::ALL {
+            if let Some(table) = take_table(&mut package, platform.section())? {
+                let section = format!("package.{}", platform.section());
+                platforms.insert(
+                    platform,
+                    section_from_table(&section, table, PLATFORM_KEYS)?,
+                );
+            }
+        }
+
+        let package = section_from_table("package", package, PACKAGE_KEYS)?;
+
+        let web = match take_table(&mut root, "web")? {
+            Some(table) => section_from_table("web", table, WEB_KEYS)?,
+            None => WebBuildOptions::default(),
+        };
+
+        let dist = match root.remove("dist") {
+            None => vec![],
+            Some(Value::Array(entries)) => entries
+                .into_iter()
+                .enumerate()
+                .map(|(i, entry)| match entry {
+                    Value::Table(table) => dist_entry_from_table(i, table),
+                    _ => Err(JamjarError::StringError(
+                        "`dist` in jamjar.toml must be an array of tables, e.g. `[[dist]]`"
+                            .to_owned(),
+                    )),
+                })
+                .collect::<Result<_, _>>()?,
+            Some(_) => {
+                return Err(JamjarError::StringError(
+                    "`dist` in jamjar.toml must be an array of tables, e.g. `[[dist]]`".to_owned(),
+                ))
+            }
+        };
+
+        let mut extra_artifacts = BTreeMap::new();
+        for (name, table) in take_table(&mut root, "extra_artifacts")?.unwrap_or_default() {
+            let section = format!("extra_artifacts.{}", name);
+            let table = match table {
+                Value::Table(table) => table,
+                _ => {
+                    return Err(JamjarError::StringError(format!(
+                        "`{}` in jamjar.toml must be a table",
+                        section
+                    )))
+                }
+            };
+            let options = section_from_table(&section, table, ARTIFACT_KEYS)?;
+            extra_artifacts.insert(name, options);
+        }
+
+        let release = match take_table(&mut root, "release")? {
+            Some(table) => section_from_table("release", table, RELEASE_KEYS)?,
+            None => ReleaseOptions::default(),
+        };
+
+        let resources_error = || {
+            JamjarError::StringError(
+                "`resources` in jamjar.toml must be an array of tables, e.g. `[[resources]]`"
+                    .to_owned(),
+            )
+        };
+        let resources = match root.remove("resources") {
+            None => vec![],
+            Some(Value::Array(entries)) => entries
+                .into_iter()
+                .enumerate()
+                .map(|(i, entry)| match entry {
+                    Value::Table(table) => {
+                        section_from_table(&format!("resources.{}", i), table, RESOURCE_KEYS)
+                    }
+                    _ => Err(resources_error()),
+                })
+                .collect::<Result<_, _>>()?,
+            Some(_) => return Err(resources_error()),
+        };
+
+        let asset_exclude = match root.remove("asset_exclude") {
+            None => vec![],
+            Some(value) => value.try_into().map_err(|_| {
+                JamjarError::StringError(
+                    "`asset_exclude` in jamjar.toml must be an array of strings".to_owned(),
+                )
+            })?,
+        };
+
+        Ok(JamjarToml {
+            package,
+            platforms,
+            web,
+            dist,
+            extra_artifacts,
+            release,
+            resources,
+            asset_exclude,
+        })
+    }
+
+    /// The package options for the given platform, with its section taking
+    /// precedence over the top-level `[package]` values.
+    pub fn package_options(&self, platform: Platform) -> PackageOptions {
+        let platform_options = self.platforms.get(&platform).cloned().unwrap_or_default();
+        platform_options.or(self.package.clone())
+    }
+}
+
+/// Options from the `[package.metadata.jamjar]` table in the app's
+/// Cargo.toml, used for anything the command line and jamjar.toml don't
+/// set. Package options go straight in the table, and web options in a
+/// `web` table inside it:
+///
+/// ```toml
+/// [package.metadata.jamjar]
+/// app_name = "My Game"
+/// bundle_id = "com.example.mygame"
+///
+/// [package.metadata.jamjar.web]
+/// public_path = "/games/mygame/"
+/// ```
+///
+/// Relative paths are resolved against the directory Cargo.toml is in.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ManifestOptions {
+    pub package: PackageOptions,
+    pub web: WebBuildOptions,
+}
+
+/// Keys in `[package.metadata.jamjar]` that are read elsewhere.
+const METADATA_KEYS: &[&str] = &["copyright", "desktop_categories", "web"];
+
+impl ManifestOptions {
+    /// Load the options from the Cargo.toml at `manifest_path`. If it can't
+    /// be read, there are none: the build reports that itself.
+    pub fn load(manifest_path: &Path) -> Result<Self, JamjarError> {
+        let source = match std::fs::read_to_string(manifest_path) {
+            Ok(source) => source,
+            Err(_) => return Ok(ManifestOptions::default()),
+        };
+
+        let manifest_dir = manifest_path.parent().unwrap_or_else(|| Path::new("."));
+        let ManifestOptions { package, web } = Self::parse(&source)?;
+        Ok(ManifestOptions {
+            package: package.relative_to(manifest_dir),
+            web: web.relative_to(manifest_dir),
+        })
+    }
+
+    pub fn parse(source: &str) -> Result<Self, JamjarError> {
+        let root: Table = toml::from_str(source)?;
+        let mut metadata = match root
+            .get("package")
+            .and_then(|package| package.get("metadata"))
+            .and_then(|metadata| metadata.get("jamjar"))
+        {
+            Some(Value::Table(table)) => table.clone(),
+            _ => return Ok(ManifestOptions::default()),
+        };
+
+        let web = match metadata.remove("web") {
+            None => WebBuildOptions::default(),
+            Some(Value::Table(mut table)) => {
+                strip_unknown_keys_in(
+                    "Cargo.toml",
+                    "package.metadata.jamjar.web",
+                    &mut table,
+                    WEB_KEYS,
+                );
+                Value::Table(table).try_into()?
+            }
+            Some(_) => {
+                return Err(JamjarError::StringError(
+                    "`web` in [package.metadata.jamjar] of Cargo.toml must be a table".to_owned(),
+                ))
+            }
+        };
+
+        let keys = [PLATFORM_KEYS, METADATA_KEYS].concat();
+        strip_unknown_keys_in(
+            "Cargo.toml",
+            "package.metadata.jamjar",
+            &mut metadata,
+            &keys,
+        );
+        for key in METADATA_KEYS {
+            metadata.remove(*key);
+        }
+
+        Ok(ManifestOptions {
+            package: Value::Table(metadata).try_into()?,
+            web,
+        })
+    }
+}
+
+fn dist_entry_from_table(index: usize, mut table: Table) -> Result<DistEntry, JamjarError> {
+    let section = format!("dist.{}", index);
+    let take_string = |table: &mut Table, key: &str| match table.remove(key) {
+        None => Ok(None),
+        Some(Value::String(value)) => Ok(Some(value)),
+        Some(_) => Err(JamjarError::StringError(format!(
+            "`{}` in [[dist]] entry {} of jamjar.toml must be a string",
+            key, index
+        ))),
+    };
+
+    let kind = take_string(&mut table, "kind")?;
+    let name = take_string(&mut table, "name")?;
+
+    let options = match kind.as_deref() {
+        Some("package") => {
+            let keys = [DIST_KEYS, &["target"], PLATFORM_KEYS].concat();
+            DistOptions::Package(section_from_table(&section, table, &keys)?)
+        }
+        Some("web") => {
+            let keys = [DIST_KEYS, WEB_KEYS].concat();
+            DistOptions::Web(section_from_table(&section, table, &keys)?)
+        }
+        _ => {
+            return Err(JamjarError::StringError(format!(
+                "[[dist]] entry {} of jamjar.toml needs a `kind` of \"package\" or \"web\"",
+                index
+            )))
+        }
+    };
+
+    Ok(DistEntry {
+        name: name.unwrap_or_else(|| format!("{}-{}", kind.unwrap(), index)),
+        options,
+    })
+}
+
+fn take_table(table: &mut Table, key: &str) -> Result<Option<Table>, JamjarError> {
+    match table.remove(key) {
+        None => Ok(None),
+        Some(Value::Table(table)) => Ok(Some(table)),
+        Some(_) => Err(JamjarError::StringError(format!(
+            "`{}` in jamjar.toml must be a table",
+            key
+        ))),
+    }
+}
+
+fn strip_unknown_keys(section: &str, table: &mut Table, valid_keys: &[&str]) {
+    strip_unknown_keys_in(JAMJAR_TOML, section, table, valid_keys)
+}
+
+fn strip_unknown_keys_in(file: &str, section: &str, table: &mut Table, valid_keys: &[&str]) {
+    let unknown_keys = table
+        .keys()
+        .filter(|key| !valid_keys.contains(&key.as_str()))
+        .cloned()
+        .collect::<Vec<_>>();
+
+    for key in unknown_keys {
+        let location = match section {
+            "" => "at the top level".to_owned(),
+            section => format!("in [{}]", section),
+        };
+        let suggestion = match closest_key(&key, valid_keys) {
+            Some(closest) => format!(" Did you mean `{}`?", closest),
+            None => String::new(),
+        };
+        eprintln!(
+            "Warning: ignoring unknown key `{}` {} of {}.{} Valid keys are: {}",
+            key,
+            location,
+            file,
+            suggestion,
+            valid_keys.join(", ")
+        );
+        table.remove(&key);
+    }
+}
+
+/// The valid key most likely meant by a misspelled `key`, if any are close.
+fn closest_key<'a>(key: &str, valid_keys: &[&'a str]) -> Option<&'a str> {
+    fn edit_distance(a: &str, b: &str) -> usize {
+        let b = b.chars().collect::<Vec<_>>();
+        let mut row = (0..=b.len()).collect::<Vec<_>>();
+        for (i, ca) in a.chars().enumerate() {
+            let mut diagonal = row[0];
+            row[0] = i + 1;
+            for (j, &cb) in b.iter().enumerate() {
+                let substitution = diagonal + (ca != cb) as usize;
+                diagonal = row[j + 1];
+                row[j + 1] = substitution.min(row[j] + 1).min(row[j + 1] + 1);
+            }
+        }
+        row[b.len()]
+    }
+
+    valid_keys
+        .iter()
+        .map(|&valid| (edit_distance(key, valid), valid))
+        .filter(|&(distance, _)| distance <= 2)
+        .min()
+        .map(|(_, valid)| valid)
+}
+
+/// Deserialize a section, after warning about and removing any keys that
+/// aren't in `valid_keys`. Unknown keys are never an error, so the option
+/// structs don't deny them themselves.
+fn section_from_table<T: DeserializeOwned>(
+    section: &str,
+    mut table: Table,
+    valid_keys: &[&str],
+) -> Result<T, JamjarError> {
+    strip_unknown_keys(section, &mut table, valid_keys);
+    Ok(Value::Table(table).try_into()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn platform_sections_override_package() {
+        let toml = JamjarToml::parse(
+            r#"
+            [package]
+            app_name = "Game"
+            features = ["a"]
+
+            [package.windows]
+            features = ["b"]
+            "#,
+        )
+        .unwrap();
+
+        let windows = toml.package_options(Platform::Windows);
+        assert_eq!(windows.app_name.as_deref(), Some("Game"));
+        assert_eq!(windows.features, Some(vec!["b".to_owned()]));
+
+        let linux = toml.package_options(Platform::Linux);
+        assert_eq!(linux.app_name.as_deref(), Some("Game"));
+        assert_eq!(linux.features, Some(vec!["a".to_owned()]));
+    }
+
+    #[test]
+    fn target_is_only_read_from_the_top_of_package() {
+        let toml = JamjarToml::parse(
+            r#"
+            [package]
+            target = "x86_64-pc-windows-gnu"
+
+            [package.windows]
+            target = "i686-pc-windows-gnu"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            toml.package.target.as_deref(),
+            Some("x86_64-pc-windows-gnu")
+        );
+        assert_eq!(toml.platforms[&Platform::Windows].target, None);
+
+        let metadata = ManifestOptions::parse(
+            r#"
+            [package.metadata.jamjar]
+            target = "x86_64-pc-windows-gnu"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(metadata.package.target, None);
+    }
+
+    #[test]
+    fn spirv_cross_is_resolved_per_layer_before_merging() {
+        let flags = WebBuildOptions {
+            bypass_spirv_cross: Some(true),
+            ..Default::default()
+        };
+        let toml = WebBuildOptions {
+            spirv_cross: Some(SpirvCrossMode::Embedded),
+            ..Default::default()
+        };
+        assert_eq!(
+            flags.or(toml.clone()).spirv_cross,
+            Some(SpirvCrossMode::Skip)
+        );
+
+        let flags = WebBuildOptions {
+            spirv_cross: Some(SpirvCrossMode::Embedded),
+            bypass_spirv_cross: Some(true),
+            ..Default::default()
+        };
+        let metadata = WebBuildOptions {
+            bypass_spirv_cross: Some(true),
+            ..Default::default()
+        };
+        assert_eq!(
+            flags.or(toml).or(metadata).spirv_cross,
+            Some(SpirvCrossMode::Embedded)
+        );
+    }
+
+    #[test]
+    fn smoke_test_timeouts_must_be_positive() {
+        let parse = |timeout: &str| {
+            JamjarToml::parse(&format!("[package.smoke_test]\ntimeout = {}", timeout))
+                .map(|toml| toml.package_options(Platform::Linux).smoke_test)
+        };
+
+        assert_eq!(parse("2.5").unwrap().unwrap().timeout, 2.5);
+        for timeout in &["0", "-1.0", "nan", "inf", "1e30"] {
+            assert!(parse(timeout).is_err(), "{} was accepted", timeout);
+        }
+    }
+
+    #[test]
+    fn unknown_keys_are_ignored() {
+        let toml = JamjarToml::parse(
+            r#"
+            colour = "red"
+
+            [package]
+            app_nmae = "Typo"
+            format = "zip"
+
+            [package.macos]
+            windows = { app_name = "Nested" }
+            icon_path = "icon.png"
+
+            [web]
+            spirv = "skip"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(toml.package.app_name, None);
+        assert_eq!(toml.package.format, Some(OutputFormat::Zip));
+        let macos = &toml.platforms[&Platform::MacOS];
+        assert_eq!(macos.app_name, None);
+        assert_eq!(macos.icon_path, Some(PathBuf::from("icon.png")));
+        assert_eq!(toml.web, WebBuildOptions::default());
+    }
+
+    #[test]
+    fn platform_sections_must_be_tables() {
+        assert!(JamjarToml::parse("[package]\nlinux = 1").is_err());
+    }
+
+    #[test]
+    fn closest_key_suggests_near_misses() {
+        assert_eq!(closest_key("app_nmae", PACKAGE_KEYS), Some("app_name"));
+        assert_eq!(closest_key("completely_wrong", PACKAGE_KEYS), None);
+    }
+}
@@ -0,0 +1,277 @@
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::{
+    build_artifact_with, git_dirty, lockfile_hash, manifest_file, package_app_with, rustc_version,
+    target_dir, web_build_with, ArtifactConfig, ConfigSource, DistOptions, JamjarError, JamjarToml,
+    PackageConfig, Platform, Reporter, ResolvedConfig, WebBuildConfig,
+};
+
+#[derive(Debug)]
+pub enum TaskConfig {
+    Package(Box<PackageConfig>),
+    Web(WebBuildConfig),
+    Artifact(ArtifactConfig),
+}
+
+impl TaskConfig {
+    /// Build even if `[release]` requires a clean git working tree and it
+    /// isn't.
+    pub fn allow_dirty(&mut self) {
+        match self {
+            TaskConfig::Package(config) => {
+                config.require_clean_git = false;
+                config.resolved.allow_dirty();
+            }
+            TaskConfig::Web(config) => {
+                config.require_clean_git = false;
+                config.resolved.allow_dirty();
+            }
+            TaskConfig::Artifact(_) => (),
+        }
+    }
+
+    /// The merged options for a package or web build.
+    pub fn resolved(&self) -> Option<&ResolvedConfig> {
+        match self {
+            TaskConfig::Package(config) => Some(&config.resolved),
+            TaskConfig::Web(config) => Some(&config.resolved),
+            TaskConfig::Artifact(_) => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct BuildTask {
+    pub name: String,
+    pub config: TaskConfig,
+}
+
+#[derive(Debug)]
+pub struct PackageReport {
+    pub name: String,
+    pub output_path: PathBuf,
+    pub duration: Duration,
+    /// Whether the git working tree had uncommitted changes, or `None` if
+    /// the app isn't in a git repository.
+    pub dirty: Option<bool>,
+    /// The changelog section included in a package as `CHANGES.txt`.
+    pub changes: Option<String>,
+    /// The SHA-256 of Cargo.lock, to tell whether two builds used the same
+    /// dependencies.
+    pub lockfile_hash: Option<String>,
+    /// The output of `rustc --version --verbose` for the toolchain the
+    /// build used. `None` for extra artifacts.
+    pub rustc_version: Option<String>,
+    /// The options the build used, for reproducing it. `None` for extra
+    /// artifacts.
+    pub config: Option<ResolvedConfig>,
+}
+
+/// The app root and kind of target of a build.
+type ConflictKey = (PathBuf, &'static str);
+
+impl BuildTask {
+    /// Build the task list from the `[[dist]]` entries in the app's
+    /// jamjar.toml, followed by any `[extra_artifacts]`.
+    pub fn resolve_dist(app_root: Option<PathBuf>) -> Result<Vec<BuildTask>, JamjarError> {
+        let root = app_root.clone().unwrap_or_else(|| PathBuf::from("."));
+        let jamjar_toml = JamjarToml::load(&root)?;
+
+        if jamjar_toml.dist.is_empty() {
+            return Err(JamjarError::StringError(
+                "jamjar.toml has no [[dist]] entries to build".to_owned(),
+            ));
+        }
+
+        let artifacts = ArtifactConfig::resolve_all(app_root.clone())?
+            .into_iter()
+            .map(|config| BuildTask {
+                name: config.name.clone(),
+                config: TaskConfig::Artifact(config),
+            });
+
+        let builds = jamjar_toml
+            .dist
+            .into_iter()
+            .enumerate()
+            .map(|(index, entry)| {
+                let source = ConfigSource::File(format!("[[dist]] entry {}", index));
+                let config = match entry.options {
+                    DistOptions::Package(options) => {
+                        TaskConfig::Package(Box::new(PackageConfig::resolve_release(
+                            app_root.clone(),
+                            options,
+                            source,
+                            Platform::host(),
+                        )?))
+                    }
+                    DistOptions::Web(options) => TaskConfig::Web(WebBuildConfig::resolve_from(
+                        app_root.clone(),
+                        options,
+                        source,
+                    )?),
+                };
+
+                Ok(BuildTask {
+                    name: entry.name,
+                    config,
+                })
+            })
+            .collect::<Result<Vec<_>, JamjarError>>()?;
+
+        Ok(builds.into_iter().chain(artifacts).collect())
+    }
+
+    /// Tasks that share a cargo target directory and target can't overlap,
+    /// since each would overwrite the binary the other is packaging.
+    fn conflict_key(&self) -> ConflictKey {
+        let target = match &self.config {
+            TaskConfig::Package(_) => "native",
+            TaskConfig::Web(_) => "wasm32",
+            TaskConfig::Artifact(_) => "artifact",
+        };
+        (target_dir(&self.manifest_file()), target)
+    }
+
+    fn app_root(&self) -> PathBuf {
+        let app_root = match &self.config {
+            TaskConfig::Package(config) => &config.app_root,
+            TaskConfig::Web(config) => &config.app_root,
+            TaskConfig::Artifact(config) => &config.app_root,
+        };
+
+        let app_root = app_root.clone().unwrap_or_else(|| PathBuf::from("."));
+        app_root.canonicalize().unwrap_or(app_root)
+    }
+
+    fn toolchain_version(&self) -> Option<String> {
+        let toolchain = match &self.config {
+            TaskConfig::Package(config) => &config.toolchain,
+            TaskConfig::Web(config) => &config.toolchain,
+            TaskConfig::Artifact(_) => return None,
+        };
+
+        rustc_version(&self.app_root(), toolchain.as_deref()).ok()
+    }
+
+    fn manifest_file(&self) -> PathBuf {
+        let manifest_path = match &self.config {
+            TaskConfig::Package(config) => &config.manifest_path,
+            TaskConfig::Web(config) => &config.manifest_path,
+            TaskConfig::Artifact(config) => &config.manifest_path,
+        };
+
+        manifest_file(&self.app_root(), manifest_path.as_deref())
+    }
+
+    fn run(&self) -> (Result<PathBuf, JamjarError>, String) {
+        let mut reporter = Reporter::Capture(String::new());
+        if let Some(resolved) = self.config.resolved() {
+            reporter.line(&format!("Configuration:\n{}", resolved));
+        }
+        let result = match &self.config {
+            TaskConfig::Package(config) => package_app_with(config, &mut reporter),
+            TaskConfig::Web(config) => web_build_with(config, &mut reporter),
+            TaskConfig::Artifact(config) => build_artifact_with(config, &mut reporter),
+        };
+        (result, reporter.into_log())
+    }
+}
+
+/// Run each task, with up to `jobs` running at once. Tasks that would
+/// clobber each other's builds are run one after another.
+///
+/// The output of each task is printed in one piece when it finishes. The
+/// results are in the same order as `tasks`.
+pub fn package_all(tasks: Vec<BuildTask>, jobs: usize) -> Vec<Result<PackageReport, JamjarError>> {
+    let task_count = tasks.len();
+
+    let mut groups: Vec<(ConflictKey, Vec<(usize, BuildTask)>)> = vec![];
+    for (index, task) in tasks.into_iter().enumerate() {
+        let key = task.conflict_key();
+        match groups.iter_mut().find(|(group_key, _)| *group_key == key) {
+            Some((_, group)) => group.push((index, task)),
+            None => groups.push((key, vec![(index, task)])),
+        }
+    }
+
+    let worker_count = jobs.max(1).min(groups.len());
+    let queue = Arc::new(Mutex::new(
+        groups
+            .into_iter()
+            .map(|(_, group)| group)
+            .collect::<VecDeque<_>>(),
+    ));
+    let results = Arc::new(Mutex::new(
+        (0..task_count).map(|_| None).collect::<Vec<_>>(),
+    ));
+    let print_lock = Arc::new(Mutex::new(()));
+
+    let workers = (0..worker_count)
+        .map(|_| {
+            let queue = queue.clone();
+            let results = results.clone();
+            let print_lock = print_lock.clone();
+
+            std::thread::spawn(move || loop {
+                let group = match queue.lock().unwrap().pop_front() {
+                    Some(group) => group,
+                    None => break,
+                };
+
+                for (index, task) in group {
+                    println!("Started: {}", task.name);
+
+                    let start = Instant::now();
+                    let (result, log) = task.run();
+                    let duration = start.elapsed();
+                    let dirty = git_dirty(&task.app_root());
+                    let manifest_file = task.manifest_file();
+                    let manifest_dir = manifest_file.parent().unwrap_or_else(|| Path::new("."));
+                    let lockfile_hash = lockfile_hash(manifest_dir);
+                    let rustc_version = task.toolchain_version();
+                    let config = task.config.resolved().cloned();
+                    let changes = match (&result, &task.config) {
+                        (Ok(_), TaskConfig::Package(config)) => config.changes(),
+                        _ => None,
+                    };
+
+                    {
+                        let _lock = print_lock.lock().unwrap();
+                        println!("\n==> {} ({:.1}s)", task.name, duration.as_secs_f32());
+                        print!("{}", log);
+                        match &result {
+                            Ok(path) => println!("==> {}: created {}", task.name, path.display()),
+                            Err(e) => println!("==> {}: failed: {}", task.name, e),
+                        }
+                    }
+
+                    results.lock().unwrap()[index] =
+                        Some(result.map(|output_path| PackageReport {
+                            name: task.name,
+                            output_path,
+                            duration,
+                            dirty,
+                            changes,
+                            lockfile_hash,
+                            rustc_version,
+                            config,
+                        }));
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+
+    for worker in workers {
+        worker.join().expect("A build task panicked");
+    }
+
+    let results = std::mem::take(&mut *results.lock().unwrap());
+    results
+        .into_iter()
+        .map(|result| result.expect("Every task should have run"))
+        .collect()
+}
@@ -0,0 +1,147 @@
+use std::io::Read;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{JamjarError, Reporter};
+
+pub const LATEST_FILE: &str = "latest.json";
+pub const HISTORY_FILE: &str = "releases.json";
+
+/// A packaged release, as written to `latest.json` and `releases.json` by
+/// `package_app` when `release_feed` is set.
+///
+/// `latest.json` holds the most recent release, and `releases.json` holds
+/// every release packaged into the same output directory, oldest first.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Release {
+    pub app_name: String,
+    /// The version from Cargo.toml.
+    pub version: String,
+    /// The platform's short name, as used in artifact filenames (`macos`,
+    /// `win`, or `linux`).
+    pub platform: String,
+    /// The artifact's file name, relative to the feed.
+    pub file_name: String,
+    /// The artifact's size in bytes.
+    pub size: u64,
+    /// The artifact's SHA-256 digest, as lowercase hex.
+    pub sha256: String,
+    /// When the artifact was packaged, in UTC, e.g. `2021-04-18T15:30:00Z`.
+    pub timestamp: String,
+}
+
+impl Release {
+    /// Describe the artifact at `path`, packaged now.
+    pub fn describe(
+        app_name: &str,
+        version: &str,
+        platform: &str,
+        path: &Path,
+    ) -> Result<Self, JamjarError> {
+        let mut file = std::fs::File::open(path)
+            .map_err(|e| JamjarError::io(e, &format!("Failed to open '{}'.", path.display())))?;
+
+        let mut hasher = Sha256::new();
+        let mut size = 0;
+        let mut buffer = vec![0; 64 * 1024];
+        loop {
+            let count = file.read(&mut buffer)?;
+            if count == 0 {
+                break;
+            }
+            hasher.update(&buffer[..count]);
+            size += count as u64;
+        }
+
+        let sha256 = hasher
+            .finalize()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+
+        Ok(Release {
+            app_name: app_name.to_owned(),
+            version: version.to_owned(),
+            platform: platform.to_owned(),
+            file_name: path.file_name().unwrap().to_string_lossy().into_owned(),
+            size,
+            sha256,
+            timestamp: iso_timestamp(SystemTime::now()),
+        })
+    }
+}
+
+/// Write `release` to `latest.json` in `output_dir`, and add it to the
+/// history in `releases.json`. A release already in the history with the
+/// same version and platform is replaced.
+pub fn write_release_feed(
+    output_dir: &Path,
+    release: &Release,
+    reporter: &mut Reporter,
+) -> Result<(), JamjarError> {
+    let history_path = output_dir.join(HISTORY_FILE);
+    let mut history = match std::fs::read_to_string(&history_path) {
+        Ok(json) => serde_json::from_str::<Vec<Release>>(&json).map_err(|e| {
+            JamjarError::StringError(format!(
+                "Could not read '{}': {}",
+                history_path.display(),
+                e
+            ))
+        })?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => vec![],
+        Err(e) => return Err(JamjarError::io(e, "Could not read release history.")),
+    };
+
+    history.retain(|old| old.version != release.version || old.platform != release.platform);
+    history.push(release.clone());
+
+    reporter.line(&format!("Writing {} and {}", LATEST_FILE, HISTORY_FILE));
+    write_json(&output_dir.join(LATEST_FILE), release)?;
+    write_json(&history_path, &history)?;
+
+    Ok(())
+}
+
+fn write_json<T: Serialize + ?Sized>(path: &Path, value: &T) -> Result<(), JamjarError> {
+    let mut json = serde_json::to_string_pretty(value)
+        .map_err(|e| JamjarError::StringError(format!("Failed to serialize release: {}", e)))?;
+    json.push('\n');
+
+    std::fs::write(path, json)
+        .map_err(|e| JamjarError::io(e, &format!("Failed to write '{}'.", path.display())))
+}
+
+/// Format `time` as an ISO 8601 UTC timestamp, to the second.
+fn iso_timestamp(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0);
+
+    let days = (secs / 86400) as i64;
+    let rem = secs % 86400;
+
+    // Howard Hinnant's days-to-civil algorithm
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
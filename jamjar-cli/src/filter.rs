@@ -0,0 +1,94 @@
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::{artifact::wildcard_match, JamjarError, JamjarToml};
+
+/// The allowlist of assets to ship, in the app root. If it exists, assets
+/// must match one of its `include` patterns.
+pub const ASSET_MANIFEST: &str = "asset_manifest.toml";
+
+/// Which files in a directory are kept, e.g. when it's zipped.
+///
+/// Patterns are matched against paths relative to the directory, with `/`
+/// between components on every platform. `*` and `?` match within one
+/// component. A pattern with no `/` matches the file name in any
+/// directory, so `*.pdb` leaves out every `.pdb` file.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct PathFilter {
+    pub exclude: Vec<String>,
+    /// If set, files must also match one of these.
+    pub include_only: Option<Vec<String>>,
+}
+
+impl PathFilter {
+    /// OS clutter, and debug symbols unless `keep_symbols` is set.
+    pub fn default_zip_exclude(keep_symbols: bool) -> Vec<String> {
+        let mut exclude = vec![".DS_Store".to_owned(), "Thumbs.db".to_owned()];
+        if !keep_symbols {
+            exclude.push("*.pdb".to_owned());
+        }
+        exclude
+    }
+
+    /// Whether the file at `rel_path` is kept.
+    pub fn includes(&self, rel_path: &str) -> bool {
+        let matches = |pattern: &String| pattern_match(pattern, rel_path);
+        let included = match &self.include_only {
+            Some(patterns) => patterns.iter().any(matches),
+            None => true,
+        };
+        included && !self.exclude.iter().any(matches)
+    }
+}
+
+fn pattern_match(pattern: &str, rel_path: &str) -> bool {
+    let pattern = pattern.trim_start_matches("./");
+    if !pattern.contains('/') {
+        let name = rel_path.rsplit('/').next().unwrap_or(rel_path);
+        return wildcard_match(pattern, name);
+    }
+
+    let path = rel_path.split('/').collect::<Vec<_>>();
+    let pattern = pattern.split('/').collect::<Vec<_>>();
+    pattern.len() == path.len()
+        && pattern
+            .iter()
+            .zip(&path)
+            .all(|(pattern, name)| wildcard_match(pattern, name))
+}
+
+/// `path` with `/` between its components, as patterns expect.
+pub(crate) fn slashed(path: &Path) -> String {
+    path.components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AssetManifest {
+    include: Vec<String>,
+}
+
+/// Which of the app's assets ship with it: everything but `asset_exclude`
+/// from jamjar.toml, and only what's in `asset_manifest.toml` if there is
+/// one. Paths are relative to the app root, like `assets/sprites/hero.psd`.
+pub fn asset_filter(app_root: &Path, jamjar_toml: &JamjarToml) -> Result<PathFilter, JamjarError> {
+    let manifest_path = app_root.join(ASSET_MANIFEST);
+    let include_only = match manifest_path.is_file() {
+        true => {
+            let source = std::fs::read_to_string(&manifest_path)
+                .map_err(|e| JamjarError::io(e, "Could not read asset_manifest.toml."))?;
+            let manifest: AssetManifest = toml::from_str(&source)?;
+            Some(manifest.include)
+        }
+        false => None,
+    };
+
+    Ok(PathFilter {
+        exclude: jamjar_toml.asset_exclude.clone(),
+        include_only,
+    })
+}
@@ -1,10 +1,16 @@
+// JamjarError is only made when a build fails, so boxing its bigger
+// variants to shrink it wouldn't save anything
+#![allow(clippy::result_large_err)]
+
 use std::io::Error as IOError;
 use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::time::Duration;
 
 use handlebars::{Handlebars, TemplateRenderError};
 use image::ImageError;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use thiserror::Error;
 use toml::de::Error as TomlError;
 use zip::{
@@ -12,6 +18,45 @@ use zip::{
     write::{FileOptions, ZipWriter},
 };
 
+mod artifact;
+mod changelog;
+mod check;
+mod config;
+mod dist;
+mod feed;
+mod filter;
+mod lock;
+mod notices;
+mod report;
+mod resolved;
+mod size;
+mod toolchain;
+mod winres;
+
+pub use artifact::{build_artifact, build_artifact_with, ArtifactConfig};
+pub use changelog::{changelog_section, CHANGES_FILE};
+pub use check::{check_resources, check_resources_with, ResourceCheck};
+pub use config::{
+    CompatLevel, DistEntry, DistOptions, ExitExpectation, ExtraArtifactOptions, JamjarToml,
+    ManifestOptions, OutputFormat, OverwritePolicy, PackageOptions, Platform, ReleaseOptions,
+    ResourceCheckOptions, SmokeTest, SpirvCrossMode, WebBuildOptions, JAMJAR_TOML,
+};
+pub use dist::{package_all, BuildTask, PackageReport, TaskConfig};
+pub use feed::{write_release_feed, Release};
+pub use filter::{asset_filter, PathFilter, ASSET_MANIFEST};
+
+use filter::slashed;
+pub use lock::{OutputLock, DEFAULT_LOCK_TIMEOUT, LOCK_FILE};
+pub use notices::NOTICES_FILE;
+
+use notices::NoticesBuild;
+pub use report::Reporter;
+pub use resolved::{resolve_config, ConfigSource, ResolvedConfig, ResolvedValue};
+pub use size::{write_size_report, SizeEntry, SizeReport};
+pub use toolchain::rustc_version;
+
+use toolchain::use_toolchain;
+
 #[derive(Debug, Error)]
 pub enum JamjarError {
     #[error("an IO error occurred")]
@@ -32,6 +77,16 @@ pub enum JamjarError {
         cause: TemplateRenderError,
     },
 
+    #[error(
+        "template `{template}` uses `{variable}`, which was not provided. Available variables are: {}",
+        .available.join(", ")
+    )]
+    TemplateVariableError {
+        template: &'static str,
+        variable: String,
+        available: Vec<String>,
+    },
+
     #[error("failed to decode icon image")]
     ImageError(#[from] ImageError),
 
@@ -43,6 +98,41 @@ pub enum JamjarError {
 
     #[error("an error occurred: {0}")]
     StringError(String),
+
+    #[error("hook `{command}` failed:\n{output}")]
+    HookError { command: String, output: String },
+
+    #[error("smoke test failed: {reason}\n{output}")]
+    SmokeTestError { reason: String, output: String },
+
+    #[error(
+        "the git working tree has uncommitted changes (pass --allow-dirty to build anyway):\n  {}",
+        .files.join("\n  ")
+    )]
+    DirtyWorkingTree { files: Vec<String> },
+
+    #[error(
+        "`locked` is set, but there's no Cargo.lock in `{}` or above it. Run `cargo generate-lockfile` and commit the result.",
+        .manifest_dir.display()
+    )]
+    MissingLockfile { manifest_dir: PathBuf },
+
+    #[error(
+        "`{}` already exists. Bump the version in Cargo.toml, or set `on_existing` to `rename` or `overwrite`.",
+        .path.display()
+    )]
+    PackageExists { path: PathBuf },
+
+    #[error("timed out waiting for `{}`, held by another jamjar run ({holder})", .path.display())]
+    LockTimeout { path: PathBuf, holder: String },
+
+    #[error(
+        "resource files don't match their keys:{}",
+        check::describe_mismatches(.dirs)
+    )]
+    ResourceMismatch {
+        dirs: Vec<(PathBuf, jamjar::resource_keys::KeyMismatches)>,
+    },
 }
 
 impl JamjarError {
@@ -54,13 +144,121 @@ impl JamjarError {
     }
 }
 
+/// Render a template in strict mode, so that a variable missing from the
+/// context is an error rather than an empty string.
+///
+/// `html` templates have their variables HTML-escaped.
+fn render_template<T: Serialize>(
+    name: &'static str,
+    template: &str,
+    context: &T,
+    html: bool,
+) -> Result<String, JamjarError> {
+    let mut hb = Handlebars::new();
+    hb.set_strict_mode(true);
+    if !html {
+        hb.register_escape_fn(handlebars::no_escape);
+    }
+
+    hb.render_template(template, context).map_err(|cause| {
+        let variable = match &cause {
+            TemplateRenderError::RenderError(e) if e.desc.contains("strict mode") => {
+                e.desc.split('"').nth(1).map(str::to_owned)
+            }
+            _ => None,
+        };
+
+        match variable {
+            Some(variable) => {
+                let available = handlebars::to_json(context)
+                    .as_object()
+                    .map(|fields| fields.keys().cloned().collect())
+                    .unwrap_or_default();
+                JamjarError::TemplateVariableError {
+                    template: name,
+                    variable,
+                    available,
+                }
+            }
+            None => JamjarError::TemplateError { cause },
+        }
+    })
+}
+
+/// The icon sizes installed on Linux, unless `icon_sizes` is set.
+pub const DEFAULT_ICON_SIZES: &[u32] = &[16, 24, 32, 48, 64, 128, 256, 512];
+
+/// Desktops don't use `hicolor` icons outside of this range.
+const ICON_SIZE_RANGE: std::ops::RangeInclusive<u32> = 16..=512;
+
 #[derive(Debug)]
 pub struct PackageConfig {
     pub app_root: Option<PathBuf>,
     pub app_name: Option<String>,
+    /// The binary to package, for crates with more than one. Defaults to
+    /// the one named after the package.
+    pub bin_name: Option<String>,
     pub output_dir: PathBuf,
     pub icon_path: Option<PathBuf>,
     pub features: Vec<String>,
+    pub default_features: bool,
+    pub all_features: bool,
+    pub format: OutputFormat,
+    pub appimage_fallback: bool,
+    pub build_info: bool,
+    pub launch_helpers: bool,
+    pub controls: Option<String>,
+    pub pre_build: Vec<String>,
+    pub post_build: Vec<String>,
+    pub smoke_test: Option<SmokeTest>,
+    /// Fail before building if the git working tree is dirty.
+    pub require_clean_git: bool,
+    /// How long to wait for another jamjar run using the output directory.
+    pub lock_timeout: Duration,
+    pub release_feed: bool,
+    pub size_report: bool,
+    /// The sizes of the `hicolor` icons in Linux packages.
+    pub icon_sizes: Vec<u32>,
+    pub changelog: Option<PathBuf>,
+    pub require_changelog: bool,
+    pub locked: bool,
+    pub offline: bool,
+    /// The Rust toolchain to build with, or `None` to let rustup pick
+    /// (e.g. from the app's `rust-toolchain.toml`).
+    pub toolchain: Option<String>,
+    /// The target triple to build for, or `None` for the host.
+    pub target: Option<String>,
+    /// The app's Cargo.toml, relative to `app_root`. Defaults to the one
+    /// in `app_root`.
+    pub manifest_path: Option<PathBuf>,
+    pub on_existing: OverwritePolicy,
+    pub keep_symbols: bool,
+    /// Files and directories copied in next to the executable, or into
+    /// `Contents/Resources` on macOS.
+    pub resources: Vec<PathBuf>,
+    /// Include `THIRD-PARTY-NOTICES.txt`, listing the crates in the app
+    /// and their licenses.
+    pub third_party_notices: bool,
+    /// Fail if a crate's license can't be read, instead of listing it for
+    /// review.
+    pub strict_notices: bool,
+    /// Which files go in zip packages.
+    pub zip_filter: PathFilter,
+    /// Which assets are bundled, from `asset_exclude` and
+    /// `asset_manifest.toml`.
+    pub asset_filter: PathFilter,
+    /// The macOS bundle identifier, or `None` to make one from the app
+    /// name with `default_bundle_id`.
+    pub bundle_id: Option<String>,
+    /// Sign macOS apps with this `codesign` identity, or ad-hoc if it's `-`.
+    pub sign_identity: Option<String>,
+    /// An entitlements plist to sign macOS apps with.
+    pub entitlements: Option<PathBuf>,
+    /// The keychain profile to notarize macOS apps with, which needs them
+    /// signed with a Developer ID.
+    pub notarize: Option<String>,
+    /// The options above, and where each came from.
+    pub resolved: ResolvedConfig,
 }
 
 #[derive(Debug)]
@@ -70,17 +268,361 @@ pub struct WebBuildConfig {
     pub bin_name: Option<String>,
     pub output_dir: PathBuf,
     pub features: Vec<String>,
-    pub bypass_spirv_cross: bool,
+    pub default_features: bool,
+    pub all_features: bool,
+    pub spirv_cross: SpirvCrossMode,
     pub debug: bool,
+    pub names_section: bool,
+    /// Which browsers the build has to run in.
+    pub wasm_compat: CompatLevel,
+    pub third_party_notices: bool,
+    pub strict_notices: bool,
+    /// The page to write, or `None` to only print a snippet for an
+    /// existing page.
+    pub html_filename: Option<String>,
+    /// Where the page loads the JS and wasm from, ending in `/`.
+    pub public_path: String,
+    /// Fail before building if the git working tree is dirty.
+    pub require_clean_git: bool,
+    /// How long to wait for another jamjar run using the output directory.
+    pub lock_timeout: Duration,
+    pub size_report: bool,
+    pub locked: bool,
+    pub offline: bool,
+    /// The Rust toolchain to build with, or `None` to let rustup pick
+    /// (e.g. from the app's `rust-toolchain.toml`).
+    pub toolchain: Option<String>,
+    /// The app's Cargo.toml, relative to `app_root`. Defaults to the one
+    /// in `app_root`.
+    pub manifest_path: Option<PathBuf>,
+    /// The options above, and where each came from.
+    pub resolved: ResolvedConfig,
+}
+
+impl PackageConfig {
+    /// Build a config from explicitly provided options, falling back to the
+    /// app's jamjar.toml (platform section first) and then the defaults.
+    pub fn resolve(
+        app_root: Option<PathBuf>,
+        options: PackageOptions,
+        platform: Platform,
+    ) -> Result<Self, JamjarError> {
+        Self::resolve_from(app_root, options, ConfigSource::Flag, platform)
+    }
+
+    /// Like `resolve`, but with `options` coming from `source` instead of
+    /// the command line.
+    pub fn resolve_from(
+        app_root: Option<PathBuf>,
+        options: PackageOptions,
+        source: ConfigSource,
+        platform: Platform,
+    ) -> Result<Self, JamjarError> {
+        Self::resolve_with(
+            app_root,
+            options,
+            source,
+            platform,
+            OverwritePolicy::default(),
+        )
+    }
+
+    /// Like `resolve_from`, for a package built for release by `jamjar
+    /// dist`. Unless `on_existing` is set, an existing package is never
+    /// replaced.
+    pub(crate) fn resolve_release(
+        app_root: Option<PathBuf>,
+        options: PackageOptions,
+        source: ConfigSource,
+        platform: Platform,
+    ) -> Result<Self, JamjarError> {
+        Self::resolve_with(app_root, options, source, platform, OverwritePolicy::Error)
+    }
+
+    fn resolve_with(
+        app_root: Option<PathBuf>,
+        options: PackageOptions,
+        source: ConfigSource,
+        platform: Platform,
+        default_on_existing: OverwritePolicy,
+    ) -> Result<Self, JamjarError> {
+        let root = app_root.clone().unwrap_or_else(|| PathBuf::from("."));
+        let jamjar_toml = JamjarToml::load(&root)?;
+
+        // A target picks the platform section it's building for, so it can
+        // only come from the options or the top of `[package]`
+        let target = options
+            .target
+            .clone()
+            .or_else(|| jamjar_toml.package.target.clone());
+        let platform = match &target {
+            Some(target) => Platform::from_target(target).ok_or_else(|| {
+                JamjarError::StringError(format!(
+                    "`{}` isn't a Windows, macOS, or Linux target, so it can't be packaged",
+                    target
+                ))
+            })?,
+            None => platform,
+        };
+
+        let platform_options = jamjar_toml
+            .platforms
+            .get(&platform)
+            .cloned()
+            .unwrap_or_default();
+        let manifest_path = options
+            .manifest_path
+            .clone()
+            .or_else(|| jamjar_toml.package_options(platform).manifest_path);
+        let metadata = ManifestOptions::load(&manifest_file(&root, manifest_path.as_deref()))?;
+        let layers = [
+            resolved::layer(source, &options)?,
+            resolved::layer(
+                ConfigSource::File(format!("[package.{}]", platform.section())),
+                &platform_options,
+            )?,
+            resolved::layer(
+                ConfigSource::File("[package]".to_owned()),
+                &jamjar_toml.package,
+            )?,
+            resolved::layer(
+                ConfigSource::Manifest("[package.metadata.jamjar]".to_owned()),
+                &metadata.package,
+            )?,
+        ];
+        let options = options
+            .or(jamjar_toml.package_options(platform))
+            .or(metadata.package);
+        let keep_symbols = options.keep_symbols.unwrap_or(false);
+
+        let icon_sizes = options
+            .icon_sizes
+            .unwrap_or_else(|| DEFAULT_ICON_SIZES.to_vec());
+        if let Some(size) = icon_sizes
+            .iter()
+            .find(|size| !ICON_SIZE_RANGE.contains(size))
+        {
+            return Err(JamjarError::StringError(format!(
+                "icon size {} is out of range: `icon_sizes` should be from {} to {}",
+                size,
+                ICON_SIZE_RANGE.start(),
+                ICON_SIZE_RANGE.end()
+            )));
+        }
+
+        if let Some(bundle_id) = options
+            .bundle_id
+            .as_ref()
+            .filter(|bundle_id| !valid_bundle_id(bundle_id))
+        {
+            return Err(JamjarError::StringError(format!(
+                "`{}` is not a valid bundle ID: it should only have letters, digits, `-` and `.`, like `com.example.mygame`",
+                bundle_id
+            )));
+        }
+
+        let mut config = PackageConfig {
+            app_root,
+            app_name: options.app_name,
+            bin_name: options.bin_name,
+            output_dir: options
+                .output_dir
+                .unwrap_or_else(|| PathBuf::from("./target/jamjar")),
+            icon_path: options.icon_path,
+            features: options.features.unwrap_or_default(),
+            default_features: options.default_features.unwrap_or(true),
+            all_features: options.all_features.unwrap_or(false),
+            format: options.format.unwrap_or_default(),
+            appimage_fallback: options.appimage_fallback.unwrap_or(false),
+            build_info: options.build_info.unwrap_or(false),
+            launch_helpers: options.launch_helpers.unwrap_or(false),
+            controls: options.controls,
+            pre_build: options.pre_build.unwrap_or_default(),
+            post_build: options.post_build.unwrap_or_default(),
+            smoke_test: options.smoke_test,
+            require_clean_git: jamjar_toml.release.require_clean_git.unwrap_or(false),
+            lock_timeout: jamjar_toml
+                .release
+                .lock_timeout
+                .map(Duration::from_secs)
+                .unwrap_or(DEFAULT_LOCK_TIMEOUT),
+            release_feed: options.release_feed.unwrap_or(false),
+            size_report: options.size_report.unwrap_or(false),
+            icon_sizes,
+            changelog: options.changelog,
+            require_changelog: options.require_changelog.unwrap_or(false),
+            locked: options.locked.unwrap_or(false),
+            offline: options.offline.unwrap_or(false),
+            toolchain: options.toolchain,
+            target,
+            manifest_path: options.manifest_path,
+            on_existing: options.on_existing.unwrap_or(default_on_existing),
+            keep_symbols,
+            resources: options.resources.unwrap_or_default(),
+            third_party_notices: options.third_party_notices.unwrap_or(false),
+            strict_notices: options.strict_notices.unwrap_or(false),
+            zip_filter: PathFilter {
+                exclude: PathFilter::default_zip_exclude(keep_symbols)
+                    .into_iter()
+                    .chain(options.zip_exclude.unwrap_or_default())
+                    .collect(),
+                include_only: options.zip_include_only,
+            },
+            asset_filter: asset_filter(&root, &jamjar_toml)?,
+            bundle_id: options.bundle_id,
+            sign_identity: options.sign_identity,
+            entitlements: options.entitlements,
+            notarize: options.notarize,
+            resolved: ResolvedConfig::default(),
+        };
+        config.resolved = ResolvedConfig::for_package(&config, &layers, &jamjar_toml.release)?;
+        Ok(config)
+    }
+
+    /// The platform being packaged for: the target's, or the host's.
+    pub fn platform(&self) -> Platform {
+        self.target
+            .as_deref()
+            .and_then(Platform::from_target)
+            .unwrap_or_else(Platform::host)
+    }
+
+    /// The changelog section for the app's current version, if `changelog`
+    /// is set and has one.
+    pub fn changes(&self) -> Option<String> {
+        let root = self.app_root.clone().unwrap_or_else(|| PathBuf::from("."));
+        let (_, manifest) = read_manifest(&root, self.manifest_path.as_deref()).ok()?;
+        let markdown = std::fs::read_to_string(self.changelog.as_ref()?).ok()?;
+        changelog_section(&markdown, &manifest.package.version)
+    }
+}
+
+impl WebBuildConfig {
+    /// Build a config from explicitly provided options, falling back to the
+    /// app's jamjar.toml and then the defaults.
+    pub fn resolve(
+        app_root: Option<PathBuf>,
+        options: WebBuildOptions,
+    ) -> Result<Self, JamjarError> {
+        Self::resolve_from(app_root, options, ConfigSource::Flag)
+    }
+
+    /// Like `resolve`, but with `options` coming from `source` instead of
+    /// the command line.
+    pub fn resolve_from(
+        app_root: Option<PathBuf>,
+        options: WebBuildOptions,
+        source: ConfigSource,
+    ) -> Result<Self, JamjarError> {
+        let root = app_root.clone().unwrap_or_else(|| PathBuf::from("."));
+        let jamjar_toml = JamjarToml::load(&root)?;
+        let manifest_path = options
+            .manifest_path
+            .clone()
+            .or_else(|| jamjar_toml.web.manifest_path.clone());
+        let metadata = ManifestOptions::load(&manifest_file(&root, manifest_path.as_deref()))?;
+        let layers = [
+            resolved::layer(source, &options)?,
+            resolved::layer(ConfigSource::File("[web]".to_owned()), &jamjar_toml.web)?,
+            resolved::layer(
+                ConfigSource::Manifest("[package.metadata.jamjar.web]".to_owned()),
+                &metadata.web,
+            )?,
+        ];
+        let options = options.or(jamjar_toml.web).or(metadata.web);
+
+        let mut config = WebBuildConfig {
+            app_root,
+            app_name: options.app_name,
+            bin_name: options.bin_name,
+            output_dir: options
+                .output_dir
+                .unwrap_or_else(|| PathBuf::from("./target/jamjar_web")),
+            features: options.features.unwrap_or_default(),
+            default_features: options.default_features.unwrap_or(true),
+            all_features: options.all_features.unwrap_or(false),
+            spirv_cross: match (options.spirv_cross, options.bypass_spirv_cross) {
+                (Some(mode), _) => mode,
+                (None, Some(true)) => SpirvCrossMode::Skip,
+                (None, _) => SpirvCrossMode::Embedded,
+            },
+            debug: options.debug.unwrap_or(false),
+            names_section: options.names_section.or(options.debug).unwrap_or(false),
+            wasm_compat: options.wasm_compat.unwrap_or_default(),
+            third_party_notices: options.third_party_notices.unwrap_or(false),
+            strict_notices: options.strict_notices.unwrap_or(false),
+            html_filename: match options.emit_html.unwrap_or(true) {
+                true => Some(
+                    options
+                        .html_filename
+                        .unwrap_or_else(|| "index.html".to_owned()),
+                ),
+                false => None,
+            },
+            public_path: match options.public_path {
+                Some(path) if path.is_empty() => "./".to_owned(),
+                Some(path) if path.ends_with('/') => path,
+                Some(path) => format!("{}/", path),
+                None => "./".to_owned(),
+            },
+            require_clean_git: jamjar_toml.release.require_clean_git.unwrap_or(false),
+            lock_timeout: jamjar_toml
+                .release
+                .lock_timeout
+                .map(Duration::from_secs)
+                .unwrap_or(DEFAULT_LOCK_TIMEOUT),
+            size_report: options.size_report.unwrap_or(false),
+            locked: options.locked.unwrap_or(false),
+            offline: options.offline.unwrap_or(false),
+            toolchain: options.toolchain,
+            manifest_path: options.manifest_path,
+            resolved: ResolvedConfig::default(),
+        };
+        config.resolved = ResolvedConfig::for_web(&config, &layers, &jamjar_toml.release)?;
+        Ok(config)
+    }
+}
+
+/// A bundle identifier made from the app's name, like `com.jamjar.mygame`
+/// for "My Game". Anything but ASCII letters and digits is left out, and a
+/// leading digit gets `app` put before it.
+pub fn default_bundle_id(app_name: &str) -> String {
+    let mut name = app_name
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .collect::<String>()
+        .to_ascii_lowercase();
+
+    if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+        name.insert_str(0, "app");
+    }
+
+    format!("com.jamjar.{}", name)
+}
+
+fn valid_bundle_id(bundle_id: &str) -> bool {
+    !bundle_id.is_empty()
+        && bundle_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.')
 }
 
 struct AppConfig<'a> {
     app_root: &'a Path,
+    manifest_path: &'a Path,
+    release_dir: &'a Path,
     app_name: &'a str,
     exe_name: &'a str,
     version: &'a str,
     bundle_id: &'a str,
     icon_path: &'a Path,
+    icon_sizes: &'a [u32],
+    copyright: Option<&'a str>,
+    desktop_categories: &'a [String],
+    asset_filter: &'a PathFilter,
+    resources: &'a [PathBuf],
+    sign_identity: Option<&'a str>,
+    entitlements: Option<&'a Path>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -88,15 +630,405 @@ struct CargoManifest {
     package: CargoManifestPackage,
 }
 
+/// The path to the app's Cargo.toml: `manifest_path` relative to the app
+/// root, or the one in the app root if it's not set. `manifest_path` can
+/// also be the directory containing it.
+pub(crate) fn manifest_file(app_root: &Path, manifest_path: Option<&Path>) -> PathBuf {
+    match manifest_path {
+        Some(path) if app_root.join(path).is_dir() => app_root.join(path).join("Cargo.toml"),
+        Some(path) => app_root.join(path),
+        None => app_root.join("Cargo.toml"),
+    }
+}
+
+/// Find and parse the app's Cargo.toml, returning its path too.
+pub(crate) fn read_manifest(
+    app_root: &Path,
+    manifest_path: Option<&Path>,
+) -> Result<(PathBuf, CargoManifest), JamjarError> {
+    let path = manifest_file(app_root, manifest_path);
+
+    let manifest_toml = std::fs::read_to_string(&path).map_err(|e| {
+        JamjarError::io(
+            e,
+            &format!(
+                "Could not read Cargo.toml at '{}' (the app root is '{}').",
+                path.display(),
+                app_root.display()
+            ),
+        )
+    })?;
+
+    let manifest = toml::from_str::<CargoManifest>(&manifest_toml)
+        .map_err(|e| JamjarError::TomlError { cause: e })?;
+
+    Ok((path, manifest))
+}
+
+/// Fail if the app has no binary called `bin_name`, listing the ones it
+/// does have. Cargo finds binaries in `src/bin` as well as `[[bin]]`
+/// sections, so it's asked rather than reading Cargo.toml.
+fn check_bin(
+    app_root: &Path,
+    manifest_path: &Path,
+    bin_name: &str,
+    toolchain: Option<&str>,
+) -> Result<(), JamjarError> {
+    #[derive(Deserialize)]
+    struct Metadata {
+        packages: Vec<MetadataPackage>,
+    }
+
+    #[derive(Deserialize)]
+    struct MetadataPackage {
+        manifest_path: PathBuf,
+        targets: Vec<MetadataTarget>,
+    }
+
+    #[derive(Deserialize)]
+    struct MetadataTarget {
+        name: String,
+        kind: Vec<String>,
+    }
+
+    let mut cmd = Command::new("cargo");
+    cmd.current_dir(app_root)
+        .arg("metadata")
+        .arg("--no-deps")
+        .arg("--format-version")
+        .arg("1")
+        .arg("--manifest-path")
+        .arg(manifest_path);
+    use_toolchain(&mut cmd, toolchain);
+
+    let output = cmd
+        .output()
+        .map_err(|e| JamjarError::io(e, "Failed to run cargo metadata."))?;
+    if !output.status.success() {
+        return Err(JamjarError::StringError(format!(
+            "`cargo metadata` failed:\n{}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    let metadata: Metadata = serde_json::from_slice(&output.stdout).map_err(|e| {
+        JamjarError::StringError(format!("Could not read `cargo metadata` output: {}", e))
+    })?;
+
+    let manifest_path = manifest_path.canonicalize()?;
+    let bins = metadata
+        .packages
+        .iter()
+        .filter(|package| {
+            package.manifest_path.canonicalize().ok().as_ref() == Some(&manifest_path)
+        })
+        .flat_map(|package| &package.targets)
+        .filter(|target| target.kind.iter().any(|kind| kind == "bin"))
+        .map(|target| target.name.as_str())
+        .collect::<Vec<_>>();
+
+    match bins.contains(&bin_name) {
+        true => Ok(()),
+        false if bins.is_empty() => Err(JamjarError::StringError(format!(
+            "there's no binary called `{}`, and the app has no binaries at all",
+            bin_name
+        ))),
+        false => Err(JamjarError::StringError(format!(
+            "there's no binary called `{}`. The app's binaries are: {}",
+            bin_name,
+            bins.join(", ")
+        ))),
+    }
+}
+
+/// The directory cargo builds the app into. That's next to Cargo.lock,
+/// which is in the workspace root if the app is part of a workspace.
+pub(crate) fn target_dir(manifest_path: &Path) -> PathBuf {
+    let manifest_dir = manifest_path.parent().unwrap_or_else(|| Path::new("."));
+    let build_root = find_lockfile(manifest_dir)
+        .and_then(|lockfile| lockfile.parent().map(Path::to_owned))
+        .unwrap_or_else(|| manifest_dir.to_owned());
+    build_root.join("target")
+}
+
 #[derive(Debug, Deserialize)]
 struct CargoManifestPackage {
     name: String,
     version: String,
+    #[serde(default)]
+    authors: Vec<String>,
+    license: Option<String>,
+    #[serde(default)]
+    metadata: CargoManifestMetadata,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct CargoManifestMetadata {
+    #[serde(default)]
+    jamjar: JamjarMetadata,
+}
+
+/// The `[package.metadata.jamjar]` section of Cargo.toml.
+#[derive(Debug, Default, Deserialize)]
+struct JamjarMetadata {
+    copyright: Option<String>,
+    desktop_categories: Option<Vec<String>>,
+}
+
+impl CargoManifestPackage {
+    /// The authors' names, without their email addresses.
+    fn author_names(&self) -> Option<String> {
+        let names = self
+            .authors
+            .iter()
+            .map(|author| match author.find('<') {
+                Some(index) => author[..index].trim(),
+                None => author.trim(),
+            })
+            .filter(|name| !name.is_empty())
+            .collect::<Vec<_>>();
+
+        match names.is_empty() {
+            true => None,
+            false => Some(names.join(", ")),
+        }
+    }
+
+    /// The freedesktop.org categories for the `.desktop` file, `Game` by
+    /// default.
+    fn desktop_categories(&self) -> Vec<String> {
+        match &self.metadata.jamjar.desktop_categories {
+            Some(categories) => categories.clone(),
+            None => vec!["Game".to_owned()],
+        }
+    }
+
+    /// The copyright from `[package.metadata.jamjar]`, or one made from the
+    /// authors (and license) if that's not set.
+    fn copyright(&self) -> Option<String> {
+        if let Some(copyright) = &self.metadata.jamjar.copyright {
+            return Some(copyright.to_owned());
+        }
+
+        let authors = self.author_names()?;
+        Some(match &self.license {
+            Some(license) => format!("Copyright \u{a9} {}. Licensed under {}.", authors, license),
+            None => format!("Copyright \u{a9} {}", authors),
+        })
+    }
+}
+
+/// Build details passed to the app through environment variables, to be
+/// read by `jamjar::build_info!()`.
+#[derive(Debug)]
+struct BuildInfo<'a> {
+    version: &'a str,
+    git_hash: String,
+    dirty: bool,
+    timestamp: u64,
+    profile: &'a str,
+}
+
+impl<'a> BuildInfo<'a> {
+    fn collect(app_root: &Path, version: &'a str, profile: &'a str) -> Self {
+        let git = |args: &[&str]| {
+            Command::new("git")
+                .current_dir(app_root)
+                .args(args)
+                .output()
+                .ok()
+                .filter(|output| output.status.success())
+                .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_owned())
+        };
+
+        let git_hash = git(&["rev-parse", "--short", "HEAD"]).unwrap_or_else(|| "unknown".into());
+        let dirty = git_dirty(app_root).unwrap_or(false);
+
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|time| time.as_secs())
+            .unwrap_or(0);
+
+        BuildInfo {
+            version,
+            git_hash,
+            dirty,
+            timestamp,
+            profile,
+        }
+    }
+
+    fn apply(&self, cmd: &mut Command) {
+        cmd.env("JAMJAR_BUILD_VERSION", self.version)
+            .env("JAMJAR_BUILD_GIT_HASH", &self.git_hash)
+            .env("JAMJAR_BUILD_DIRTY", self.dirty.to_string())
+            .env("JAMJAR_BUILD_TIMESTAMP", self.timestamp.to_string())
+            .env("JAMJAR_BUILD_PROFILE", self.profile);
+    }
+}
+
+/// The files with uncommitted changes in the app's git working tree, or
+/// `None` if it's not in a git repository.
+pub fn git_dirty_files(app_root: &Path) -> Option<Vec<String>> {
+    let output = Command::new("git")
+        .current_dir(app_root)
+        .args(["status", "--porcelain"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+
+    // Each line is a two letter status, a space, then the path
+    Some(
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter(|line| line.len() > 3)
+            .map(|line| line[3..].to_owned())
+            .collect(),
+    )
+}
+
+/// Whether the app's git working tree has uncommitted changes, or `None` if
+/// it's not in a git repository.
+pub fn git_dirty(app_root: &Path) -> Option<bool> {
+    git_dirty_files(app_root).map(|files| !files.is_empty())
+}
+
+/// The app's Cargo.lock, which is in the workspace root if it's part of a
+/// workspace.
+fn find_lockfile(manifest_dir: &Path) -> Option<PathBuf> {
+    manifest_dir
+        .ancestors()
+        .map(|dir| dir.join("Cargo.lock"))
+        .find(|path| path.is_file())
+}
+
+/// The SHA-256 of the Cargo.lock for the manifest in `manifest_dir`, or
+/// `None` if it doesn't have one. Builds with the same hash were built
+/// from the same dependencies.
+pub fn lockfile_hash(manifest_dir: &Path) -> Option<String> {
+    let lockfile = std::fs::read(find_lockfile(manifest_dir)?).ok()?;
+    Some(
+        Sha256::digest(&lockfile)
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect(),
+    )
+}
+
+/// Check the toolchain is installed before building, and log exactly which
+/// one it is.
+fn report_toolchain(
+    app_root: &Path,
+    toolchain: Option<&str>,
+    reporter: &mut Reporter,
+) -> Result<(), JamjarError> {
+    let version = rustc_version(app_root, toolchain)?;
+    reporter.line(&format!(
+        "Rust toolchain:\n  {}",
+        version.replace('\n', "\n  ")
+    ));
+    Ok(())
+}
+
+/// Fail if `locked` is set and there's no Cargo.lock. Cargo would fail too,
+/// but not until it had tried to write a new one.
+fn check_lockfile(manifest_path: &Path, locked: bool) -> Result<(), JamjarError> {
+    let manifest_dir = manifest_path.parent().unwrap_or_else(|| Path::new("."));
+    match locked && find_lockfile(manifest_dir).is_none() {
+        true => Err(JamjarError::MissingLockfile {
+            manifest_dir: manifest_dir.to_owned(),
+        }),
+        false => Ok(()),
+    }
+}
+
+/// Fail if `notarize` is set but the app can't be notarized, rather than
+/// finding out after it's built.
+fn check_notarize(config: &PackageConfig) -> Result<(), JamjarError> {
+    if config.notarize.is_none() {
+        return Ok(());
+    }
+
+    if Platform::host() != Platform::MacOS || config.platform() != Platform::MacOS {
+        return Err(JamjarError::StringError(
+            "`notarize` only works when packaging on macOS, for macOS".to_owned(),
+        ));
+    }
+
+    match config.sign_identity.as_deref() {
+        None | Some("-") => Err(JamjarError::StringError(
+            "`notarize` needs the app signed with a Developer ID: set `sign_identity` too"
+                .to_owned(),
+        )),
+        Some(_) => Ok(()),
+    }
+}
+
+/// Fail if the app can't be packaged for `platform` from this one. macOS
+/// apps need `iconutil`, which only macOS has.
+fn check_cross_platform(platform: Platform) -> Result<(), JamjarError> {
+    match platform {
+        Platform::MacOS if Platform::host() != Platform::MacOS => Err(JamjarError::StringError(
+            "macOS apps can only be packaged on macOS".to_owned(),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// The error for a failed cross-compile, with rustc's own errors. A target
+/// that isn't installed fails with "can't find crate for `std`", so that
+/// gets a hint on fixing it.
+fn target_build_error(target: &str, stderr: &[u8]) -> JamjarError {
+    let stderr = String::from_utf8_lossy(stderr);
+    let errors = stderr
+        .lines()
+        .filter(|line| line.starts_with("error") || line.contains("may not be installed"))
+        .map(str::trim)
+        .collect::<Vec<_>>();
+
+    let mut message = format!("cargo failed to build for `{}`", target);
+    if !errors.is_empty() {
+        message.push_str(":\n  ");
+        message.push_str(&errors.join("\n  "));
+    }
+    if stderr.contains("may not be installed") {
+        message.push_str(&format!(
+            "\nInstall the target with `rustup target add {}`.",
+            target
+        ));
+    }
+    JamjarError::StringError(message)
+}
+
+/// Fail if the working tree is dirty and `require_clean` is set. This is
+/// run before anything is built, so it fails fast.
+fn check_clean_git(
+    app_root: &Path,
+    require_clean: bool,
+    reporter: &mut Reporter,
+) -> Result<(), JamjarError> {
+    if !require_clean {
+        return Ok(());
+    }
+
+    match git_dirty_files(app_root) {
+        None => {
+            reporter.line("Not in a git repository, so skipping the clean working tree check.");
+            Ok(())
+        }
+        Some(files) if files.is_empty() => Ok(()),
+        Some(files) => Err(JamjarError::DirtyWorkingTree { files }),
+    }
 }
 
 pub fn package_app(config: &PackageConfig) -> Result<PathBuf, JamjarError> {
-    use std::fs::File;
+    package_app_with(config, &mut Reporter::Print)
+}
 
+pub fn package_app_with(
+    config: &PackageConfig,
+    reporter: &mut Reporter,
+) -> Result<PathBuf, JamjarError> {
     let cwd = match config.app_root {
         Some(ref path) => path.canonicalize().map_err(|e| {
             JamjarError::io(
@@ -111,237 +1043,1939 @@ pub fn package_app(config: &PackageConfig) -> Result<PathBuf, JamjarError> {
             .map_err(|e| JamjarError::io(e, "Failed to get current directory."))?,
     };
 
-    println!("App is at: {}", cwd.display());
-
-    println!("Compiling app for release:");
-    {
-        let mut cmd = Command::new("cargo");
-        cmd.current_dir(&cwd).arg("build").arg("--release");
-
-        if !config.features.is_empty() {
-            cmd.arg("--features");
-            cmd.args(config.features.iter());
-        }
+    reporter.line(&format!("App is at: {}", cwd.display()));
 
-        let output = cmd.output()?;
+    let (manifest_path, manifest) = read_manifest(&cwd, config.manifest_path.as_deref())?;
+    if config.manifest_path.is_some() {
+        reporter.line(&format!("Cargo.toml is at: {}", manifest_path.display()));
+    }
 
-        print!("{}", String::from_utf8_lossy(&output.stdout));
-        eprint!("{}", String::from_utf8_lossy(&output.stderr));
+    check_clean_git(&cwd, config.require_clean_git, reporter)?;
+    check_lockfile(&manifest_path, config.locked)?;
+    check_notarize(config)?;
+    report_toolchain(&cwd, config.toolchain.as_deref(), reporter)?;
 
-        if !output.status.success() {
-            return Err(JamjarError::ExternalCommandError("cargo"));
-        }
+    let platform = config.platform();
+    check_cross_platform(platform)?;
+    if let Some(target) = &config.target {
+        reporter.line(&format!("Building for {} ({})", target, platform.name()));
     }
 
-    let manifest_toml = {
-        let manifest_path = cwd.join("Cargo.toml");
-        std::fs::read_to_string(&manifest_path)
-            .map_err(|e| JamjarError::io(e, "Could not read Cargo.toml."))?
+    // A cross-compiled exe usually can't run here
+    let smoke_test = match &config.smoke_test {
+        Some(_) if platform != Platform::host() => {
+            reporter.line(&format!(
+                "Skipping smoke test: can't run a {} app on {}",
+                platform.name(),
+                Platform::host().name()
+            ));
+            None
+        }
+        smoke_test => smoke_test.as_ref(),
     };
 
-    let manifest = toml::from_str::<CargoManifest>(&manifest_toml)
-        .map_err(|e| JamjarError::TomlError { cause: e })?;
+    std::fs::create_dir_all(&config.output_dir)
+        .map_err(|e| JamjarError::io(e, "Failed to create output directory."))?;
+    let _lock = OutputLock::acquire(&config.output_dir, config.lock_timeout, reporter)?;
 
     let app_name = config
         .app_name
         .to_owned()
         .unwrap_or_else(|| manifest.package.name.clone());
-    let exe_name = manifest.package.name;
 
-    let icon_path = match config.icon_path {
-        Some(ref path) => path.to_owned(),
-        None => cwd.join("icon.png"),
+    // Falling back is opt-in, so a CI job asking for an AppImage never
+    // quietly gets something else.
+    let format = match config.format {
+        OutputFormat::AppImage if platform == Platform::Linux && !appimagetool_installed() => {
+            match config.appimage_fallback {
+                true => {
+                    reporter.line(&format!(
+                        "`appimagetool` was not found, so a tar.gz will be made instead. {}",
+                        APPIMAGETOOL_HELP
+                    ));
+                    OutputFormat::TarGz
+                }
+                false => {
+                    reporter.line(&format!(
+                        "`appimagetool` was not found. {} Or set `appimage_fallback` to make a tar.gz instead.",
+                        APPIMAGETOOL_HELP
+                    ));
+                    return Err(JamjarError::ExternalCommandError("appimagetool"));
+                }
+            }
+        }
+        format => format,
     };
 
-    println!(
-        "App name is: {}\nVersion is: {}\nIcon path is: {}",
-        app_name,
-        manifest.package.version,
-        icon_path.display(),
-    );
+    // Checked before building too, so a forgotten version bump fails fast.
+    // It's checked again when the package is moved into place.
+    let package_path = config.output_dir.join(package_file_name(
+        format,
+        platform,
+        &app_name,
+        &manifest.package.version,
+    ));
+    let expected_path = available_path(&package_path, config.on_existing)?;
+    if expected_path != package_path {
+        reporter.line(&format!(
+            "{} already exists, so the package will be {}",
+            package_path.display(),
+            expected_path.display()
+        ));
+    }
 
-    std::fs::create_dir_all(&config.output_dir)
-        .map_err(|e| JamjarError::io(e, "Failed to create output directory."))?;
+    // Checked before building, so a missing section fails fast
+    let changes = match &config.changelog {
+        Some(path) => changelog::read_changes(
+            path,
+            &manifest.package.version,
+            config.require_changelog,
+            reporter,
+        )?,
+        None => None,
+    };
 
-    let platform = {
-        #[cfg(windows)]
-        {
-            "win"
-        }
-        #[cfg(target_os = "macos")]
-        {
-            "macos"
-        }
-        #[cfg(all(unix, not(target_os = "macos")))]
-        {
-            "linux"
+    // Collected before building too, so strict_notices fails fast
+    let notices = match config.third_party_notices {
+        true => Some(notices::third_party_notices(
+            &NoticesBuild {
+                app_root: &cwd,
+                manifest_path: &manifest_path,
+                target: config.target.as_deref(),
+                features: &config.features,
+                default_features: config.default_features,
+                all_features: config.all_features,
+                locked: config.locked,
+                offline: config.offline,
+                toolchain: config.toolchain.as_deref(),
+            },
+            &format!("{} {}", app_name, manifest.package.version),
+            config.strict_notices,
+            reporter,
+        )?),
+        false => None,
+    };
+
+    if let Some(resource) = config
+        .resources
+        .iter()
+        .find(|resource| !cwd.join(resource).exists())
+    {
+        return Err(JamjarError::StringError(format!(
+            "the resource '{}' was not found in the app root",
+            resource.display()
+        )));
+    }
+
+    let exe_name = match &config.bin_name {
+        Some(bin_name) => {
+            check_bin(&cwd, &manifest_path, bin_name, config.toolchain.as_deref())?;
+            bin_name.clone()
         }
+        None => manifest.package.name.clone(),
     };
 
-    let output_path = config.output_dir.join(format!(
-        "{}_{}_{}.zip",
-        app_name, platform, manifest.package.version
-    ));
+    let hook_env = HookEnv {
+        app_root: &cwd,
+        app_name: &app_name,
+        version: &manifest.package.version,
+        platform,
+    };
 
-    let temp_dir = tempfile::tempdir()
-        .map_err(|e| JamjarError::io(e, "Failed to create temporary directory."))?;
+    for hook in &config.pre_build {
+        hook_env.run("pre_build", hook, None, reporter)?;
+    }
+
+    let copyright = manifest.package.copyright();
+    let bundle_id = config
+        .bundle_id
+        .clone()
+        .unwrap_or_else(|| default_bundle_id(&app_name));
+    let desktop_categories = manifest.package.desktop_categories();
+
+    let icon_path = match config.icon_path {
+        Some(ref path) => path.to_owned(),
+        None => cwd.join("icon.png"),
+    };
 
-    println!("Creating macOS app");
+    let target_dir = target_dir(&manifest_path);
+    let release_dir = match &config.target {
+        Some(target) => target_dir.join(target).join("release"),
+        None => target_dir.join("release"),
+    };
 
     let app_config = AppConfig {
         app_root: &cwd,
+        manifest_path: &manifest_path,
+        release_dir: &release_dir,
         app_name: &app_name,
         exe_name: &exe_name,
         version: &manifest.package.version,
-        bundle_id: &app_name,
+        bundle_id: &bundle_id,
         icon_path: &icon_path,
+        icon_sizes: &config.icon_sizes,
+        copyright: copyright.as_deref(),
+        desktop_categories: &desktop_categories,
+        asset_filter: &config.asset_filter,
+        resources: &config.resources,
+        sign_identity: config.sign_identity.as_deref(),
+        entitlements: config.entitlements.as_deref(),
     };
 
-    let _app_path = create_macos_app(&app_config, temp_dir.as_ref())?;
-
-    println!("Compressing app to output");
-    let mut output_file = File::create(&output_path)
-        .map_err(|e| JamjarError::io(e, "Failed to create output file."))?;
+    let resources = match platform {
+        Platform::Windows => {
+            let target = match &config.target {
+                Some(target) => target.clone(),
+                None => toolchain::host_target(&cwd, config.toolchain.as_deref())?,
+            };
+            let company = manifest.package.author_names();
+            let windows_config = WindowsAppConfig {
+                app: &app_config,
+                target: &target,
+                company: company.as_deref(),
+            };
+            Some(write_resources(&windows_config, reporter)?)
+        }
+        _ => None,
+    };
 
-    let mut zipper = ZipWriter::new(&mut output_file);
-    let mut dirs = vec![temp_dir.as_ref().to_owned()];
+    reporter.line("Compiling app for release:");
+    {
+        // Resources are linked into the exe alone with `cargo rustc`, rather
+        // than through RUSTFLAGS, which would rebuild every dependency and
+        // pass the resource file to each of their links too.
+        let subcommand = match resources {
+            Some(_) => "rustc",
+            None => "build",
+        };
 
-    while let Some(dir) = dirs.pop() {
-        for entry in std::fs::read_dir(dir)? {
-            use std::io::Write;
+        let mut cmd = Command::new("cargo");
+        cmd.current_dir(&cwd).arg(subcommand).arg("--release");
+        cmd.arg("--manifest-path").arg(&manifest_path);
+        cmd.args(lock_args(config.locked, config.offline));
+        use_toolchain(&mut cmd, config.toolchain.as_deref());
 
-            let entry = entry?;
-            let path = entry.path();
+        if let Some(target) = &config.target {
+            cmd.arg("--target").arg(target);
+        }
 
-            if entry.file_type()?.is_file() {
-                let rel_path = path.strip_prefix(&temp_dir).unwrap().to_owned();
-                zipper.start_file(
-                    rel_path.to_string_lossy(),
-                    FileOptions::default().unix_permissions(0o755),
-                )?;
-                let contents = std::fs::read(path)?;
-                zipper.write_all(&contents)?;
-            } else {
-                dirs.push(path);
-            }
+        if resources.is_some() {
+            cmd.arg("--bin").arg(&exe_name);
+        } else if let Some(bin_name) = &config.bin_name {
+            cmd.arg("--bin").arg(bin_name);
         }
-    }
 
-    zipper.finish()?;
+        cmd.args(feature_args(
+            &config.features,
+            config.default_features,
+            config.all_features,
+        ));
 
-    Ok(output_path)
-}
+        if config.build_info {
+            let build_info = BuildInfo::collect(&cwd, &manifest.package.version, "release");
+            reporter.line(&format!("Build info: {:?}", build_info));
+            build_info.apply(&mut cmd);
+        }
 
-fn create_macos_app(config: &AppConfig, destination: &Path) -> Result<PathBuf, JamjarError> {
-    use std::os::unix::fs::PermissionsExt;
+        if let Some(resource_path) = &resources {
+            cmd.arg("--")
+                .arg(format!("-Clink-arg={}", resource_path.display()));
+        }
 
-    let AppConfig {
-        app_root,
-        app_name,
-        exe_name,
-        version,
-        bundle_id,
-        icon_path,
-    } = config;
+        let output = cmd.output()?;
 
-    let app_path = destination.join(format!("{}.app", app_name));
-    let contents_path = app_path.join("Contents");
-    let macos_path = contents_path.join("MacOS");
-    let resources_path = contents_path.join("Resources");
-    let plist_path = contents_path.join("Info.plist");
-    let app_exe_path = macos_path.join(app_name);
-    let app_icons_path = resources_path.join("Icon.icns");
+        reporter.command_output(&output);
 
-    std::fs::create_dir_all(&macos_path)?;
-    std::fs::create_dir_all(&resources_path)?;
-    std::fs::create_dir_all(&contents_path)?;
+        if !output.status.success() {
+            return Err(match &config.target {
+                Some(target) => target_build_error(target, &output.stderr),
+                None => JamjarError::ExternalCommandError("cargo"),
+            });
+        }
+    }
 
-    // Info.plist
+    reporter.line(&format!(
+        "App name is: {}\nVersion is: {}\nIcon path is: {}",
+        app_name,
+        manifest.package.version,
+        icon_path.display(),
+    ));
+
+    let temp_dir = tempfile::tempdir()
+        .map_err(|e| JamjarError::io(e, "Failed to create temporary directory."))?;
+
+    let output_path = match format {
+        OutputFormat::Zip if platform == Platform::MacOS => {
+            stage_macos_app(
+                config,
+                &app_config,
+                temp_dir.as_ref(),
+                smoke_test,
+                (changes.as_deref(), notices.as_deref()),
+                reporter,
+            )?;
+
+            reporter.line("Compressing app to output");
+            zip_dir(
+                temp_dir.as_ref(),
+                &package_path,
+                config.on_existing,
+                &config.zip_filter,
+                reporter,
+            )?
+        }
+        OutputFormat::Zip => {
+            let exe_path = match platform {
+                Platform::Windows => {
+                    reporter.line("Creating Windows app folder");
+                    create_windows_app(&app_config, temp_dir.as_ref(), reporter)?
+                }
+                _ => {
+                    reporter.line("Creating app folder");
+                    let exe_path = create_app_folder(&app_config, temp_dir.as_ref(), reporter)?;
+                    add_linux_desktop_files(&app_config, exe_path.parent().unwrap(), reporter)?;
+                    exe_path
+                }
+            };
+            let app_dir = exe_path.parent().unwrap();
+
+            if let Some(smoke_test) = smoke_test {
+                run_smoke_test(&exe_path, smoke_test, reporter)?;
+            }
+
+            if config.launch_helpers {
+                reporter.line("Adding launch helpers");
+                write_launch_helpers(
+                    &app_config,
+                    app_dir,
+                    &exe_path,
+                    platform == Platform::Linux,
+                    config.controls.as_deref(),
+                )?;
+            }
+
+            if let Some(changes) = &changes {
+                write_changes(app_dir, changes)?;
+            }
+
+            if let Some(notices) = &notices {
+                write_notices(app_dir, notices)?;
+            }
+
+            reporter.line("Compressing app to output");
+            zip_dir(
+                temp_dir.as_ref(),
+                &package_path,
+                config.on_existing,
+                &config.zip_filter,
+                reporter,
+            )?
+        }
+        OutputFormat::AppImage => {
+            if platform != Platform::Linux {
+                return Err(JamjarError::StringError(
+                    "AppImages can only be created when packaging for Linux".to_owned(),
+                ));
+            }
+
+            reporter.line("Creating AppDir");
+            let app_dir = create_linux_app_dir(&app_config, temp_dir.as_ref(), reporter)?;
+
+            if let Some(smoke_test) = smoke_test {
+                run_smoke_test(&app_dir.join("AppRun"), smoke_test, reporter)?;
+            }
+
+            if let Some(changes) = &changes {
+                write_changes(&app_dir, changes)?;
+            }
+
+            if let Some(notices) = &notices {
+                write_notices(&app_dir, notices)?;
+            }
+
+            reporter.line("Running appimagetool");
+            write_atomically(&package_path, config.on_existing, |partial_path| {
+                run_appimagetool(&app_dir, partial_path, reporter)
+            })?
+        }
+        OutputFormat::TarGz => {
+            if platform != Platform::Linux {
+                return Err(JamjarError::StringError(
+                    "tar.gz packages can only be created when packaging for Linux".to_owned(),
+                ));
+            }
+
+            reporter.line("Creating Linux app folder");
+            let exe_path = create_linux_app(&app_config, temp_dir.as_ref(), reporter)?;
+            let folder_path = temp_dir.as_ref().join(&app_name);
+
+            if let Some(smoke_test) = smoke_test {
+                run_smoke_test(&exe_path, smoke_test, reporter)?;
+            }
+
+            if config.launch_helpers {
+                reporter.line("Adding launch helpers");
+                write_launch_helpers(
+                    &app_config,
+                    &folder_path,
+                    &exe_path,
+                    false,
+                    config.controls.as_deref(),
+                )?;
+            }
+
+            if let Some(changes) = &changes {
+                write_changes(&folder_path, changes)?;
+            }
+
+            if let Some(notices) = &notices {
+                write_notices(&folder_path, notices)?;
+            }
+
+            reporter.line("Compressing app to output");
+            write_atomically(&package_path, config.on_existing, |partial_path| {
+                tar_dir_to(
+                    temp_dir.as_ref(),
+                    partial_path,
+                    &config.zip_filter,
+                    reporter,
+                )
+            })?
+        }
+        OutputFormat::Dmg => {
+            if platform != Platform::MacOS {
+                return Err(JamjarError::StringError(
+                    "DMGs can only be created when packaging for macOS".to_owned(),
+                ));
+            }
+
+            stage_macos_app(
+                config,
+                &app_config,
+                temp_dir.as_ref(),
+                smoke_test,
+                (changes.as_deref(), notices.as_deref()),
+                reporter,
+            )?;
+
+            reporter.line("Running hdiutil");
+            write_atomically(&package_path, config.on_existing, |partial_path| {
+                run_hdiutil(&app_name, temp_dir.as_ref(), partial_path, reporter)
+            })?
+        }
+    };
+
+    if config.release_feed {
+        let release = Release::describe(
+            &app_name,
+            &manifest.package.version,
+            platform.name(),
+            &output_path,
+        )?;
+        write_release_feed(&config.output_dir, &release, reporter)?;
+    }
+
+    if config.size_report {
+        let report = SizeReport::of_archive(&output_path)?;
+        let report_path =
+            config
+                .output_dir
+                .join(format!("{}_{}.size.json", app_name, platform.name()));
+        write_size_report(report, &report_path, reporter)?;
+    }
+
+    for hook in &config.post_build {
+        hook_env.run("post_build", hook, Some(&output_path), reporter)?;
+    }
+
+    Ok(output_path)
+}
+
+fn write_notices(dir: &Path, notices: &str) -> Result<(), JamjarError> {
+    std::fs::write(dir.join(NOTICES_FILE), notices)
+        .map_err(|e| JamjarError::io(e, &format!("Failed to write {}.", NOTICES_FILE)))
+}
+
+fn write_changes(dir: &Path, changes: &str) -> Result<(), JamjarError> {
+    let mut contents = changes.to_owned();
+    contents.push('\n');
+    std::fs::write(dir.join(CHANGES_FILE), contents)
+        .map_err(|e| JamjarError::io(e, "Failed to write CHANGES.txt."))
+}
+
+/// Details of the build passed to `pre_build` and `post_build` hooks.
+struct HookEnv<'a> {
+    app_root: &'a Path,
+    app_name: &'a str,
+    version: &'a str,
+    platform: Platform,
+}
+
+impl<'a> HookEnv<'a> {
+    fn run(
+        &self,
+        stage: &str,
+        hook: &str,
+        artifact: Option<&Path>,
+        reporter: &mut Reporter,
+    ) -> Result<(), JamjarError> {
+        reporter.line(&format!("Running {} hook: {}", stage, hook));
+
+        let mut cmd = if cfg!(windows) {
+            let mut cmd = Command::new("cmd");
+            cmd.arg("/C").arg(hook);
+            cmd
+        } else {
+            let mut cmd = Command::new("sh");
+            cmd.arg("-c").arg(hook);
+            cmd
+        };
+
+        cmd.current_dir(self.app_root)
+            .env("JAMJAR_APP_NAME", self.app_name)
+            .env("JAMJAR_VERSION", self.version)
+            .env("JAMJAR_PLATFORM", self.platform.name());
+
+        if let Some(artifact) = artifact {
+            cmd.env("JAMJAR_ARTIFACT", artifact);
+        }
+
+        let output = cmd
+            .output()
+            .map_err(|e| JamjarError::io(e, &format!("Failed to run hook `{}`.", hook)))?;
+
+        if !output.status.success() {
+            return Err(JamjarError::HookError {
+                command: hook.to_owned(),
+                output: format!(
+                    "{}{}",
+                    String::from_utf8_lossy(&output.stdout),
+                    String::from_utf8_lossy(&output.stderr)
+                ),
+            });
+        }
+
+        reporter.command_output(&output);
+
+        Ok(())
+    }
+}
+
+/// Launch the staged executable from its own directory, and wait for it to
+/// exit or for the timeout.
+fn run_smoke_test(
+    exe_path: &Path,
+    smoke_test: &SmokeTest,
+    reporter: &mut Reporter,
+) -> Result<(), JamjarError> {
+    use std::io::Read;
+    use std::process::Stdio;
+    use std::time::Instant;
+
+    reporter.line(&format!(
+        "Running smoke test: {} {}",
+        exe_path.display(),
+        smoke_test.args.join(" ")
+    ));
+
+    let mut child = Command::new(exe_path)
+        .current_dir(exe_path.parent().unwrap())
+        .args(&smoke_test.args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| JamjarError::io(e, "Failed to launch the app for the smoke test."))?;
+
+    // Read output on other threads so a chatty app can't fill the pipes
+    // and block.
+    fn read_all<R: Read + Send + 'static>(mut pipe: Option<R>) -> std::thread::JoinHandle<String> {
+        std::thread::spawn(move || {
+            let mut output = Vec::new();
+            if let Some(pipe) = pipe.as_mut() {
+                let _ = pipe.read_to_end(&mut output);
+            }
+            String::from_utf8_lossy(&output).into_owned()
+        })
+    }
+    let stdout = read_all(child.stdout.take());
+    let stderr = read_all(child.stderr.take());
+
+    let timeout = Duration::from_secs_f64(smoke_test.timeout);
+    let start = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break Some(status);
+        }
+        if start.elapsed() >= timeout {
+            let _ = child.kill();
+            let _ = child.wait();
+            break None;
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    };
+
+    let output = format!(
+        "{}{}",
+        stdout.join().unwrap_or_default(),
+        stderr.join().unwrap_or_default()
+    );
+
+    let failure = match (status, smoke_test.expect_exit) {
+        (Some(status), _) if status.success() => None,
+        (Some(status), _) => Some(format!("the app exited with {}", status)),
+        (None, ExitExpectation::Survive) => None,
+        (None, ExitExpectation::Clean) => Some(format!(
+            "the app was still running after {}s",
+            smoke_test.timeout
+        )),
+    };
+
+    match failure {
+        Some(reason) => Err(JamjarError::SmokeTestError { reason, output }),
+        None => {
+            reporter.line("Smoke test passed");
+            Ok(())
+        }
+    }
+}
+
+/// The Windows-only details of packaging an app, alongside its `AppConfig`.
+struct WindowsAppConfig<'a> {
+    app: &'a AppConfig<'a>,
+    /// The target triple the exe is built for, which decides the format the
+    /// resources are written in.
+    target: &'a str,
+    company: Option<&'a str>,
+}
+
+/// Write the Windows resources for the exe into the target directory,
+/// returning the path to link them from.
+///
+/// The resources are a VERSIONINFO, and the app's icon if it has one.
+/// They're written directly rather than through a resource compiler: as a
+/// `.res` file for MSVC, and as a COFF object for other linkers. They are
+/// named after a hash of their contents, so that cargo sees a new link
+/// argument and relinks when they change.
+fn write_resources(
+    config: &WindowsAppConfig,
+    reporter: &mut Reporter,
+) -> Result<PathBuf, JamjarError> {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+
+    let app = config.app;
+
+    let icon = match app.icon_path.is_file() {
+        true => Some(windows_icon(app.icon_path)?),
+        false => {
+            reporter.line(&format!(
+                "No icon at {}, so the exe has the default icon",
+                app.icon_path.display()
+            ));
+            None
+        }
+    };
+
+    let version_info = winres::VersionInfo {
+        version: app.version,
+        app_name: app.app_name,
+        exe_name: app.exe_name,
+        company: config.company,
+        copyright: app.copyright,
+    };
+    let resources = winres::resources(icon.as_deref(), &version_info);
+
+    let (resources, extension) = match config.target.ends_with("-msvc") {
+        true => (winres::res_file(&resources), "res"),
+        false => (winres::coff_object(&resources, config.target)?, "o"),
+    };
+
+    let hash = {
+        let mut hasher = DefaultHasher::new();
+        resources.hash(&mut hasher);
+        hasher.finish()
+    };
+
+    let out_dir = target_dir(app.manifest_path).join("jamjar");
+    std::fs::create_dir_all(&out_dir)
+        .map_err(|e| JamjarError::io(e, "Failed to create resource directory."))?;
+
+    let path = out_dir.join(format!("resources-{:016x}.{}", hash, extension));
+    if !path.exists() {
+        reporter.line("Writing Windows resources");
+        std::fs::write(&path, &resources)
+            .map_err(|e| JamjarError::io(e, "Failed to write Windows resources."))?;
+    }
+
+    Ok(path)
+}
+
+/// The sizes in the icon embedded in Windows exes.
+const WINDOWS_ICON_SIZES: &[u32] = &[16, 24, 32, 48, 64, 256];
+
+/// Render the icon at each of `WINDOWS_ICON_SIZES`, as PNGs, which Windows
+/// has supported in icons since Vista.
+fn windows_icon(icon_path: &Path) -> Result<Vec<(u32, Vec<u8>)>, JamjarError> {
+    use image::{imageops::FilterType, ImageOutputFormat};
+
+    let image_bytes = std::fs::read(icon_path)?;
+    let image = image::load_from_memory(&image_bytes)?;
+
+    let mut pngs = vec![];
+    for &size in WINDOWS_ICON_SIZES {
+        let mut png = vec![];
+        image
+            .resize_exact(size, size, FilterType::CatmullRom)
+            .write_to(&mut png, ImageOutputFormat::Png)?;
+        pngs.push((size, png));
+    }
+
+    Ok(pngs)
+}
+
+/// The cargo flags that stop it from updating Cargo.lock or using the
+/// network.
+fn lock_args(locked: bool, offline: bool) -> Vec<&'static str> {
+    let mut args = vec![];
+
+    if locked {
+        args.push("--locked");
+    }
+
+    if offline {
+        args.push("--offline");
+    }
+
+    args
+}
+
+/// The cargo flags selecting which features to build with. Features are
+/// passed as a single comma-separated `--features` argument.
+fn feature_args(features: &[String], default_features: bool, all_features: bool) -> Vec<String> {
+    let mut args = vec![];
+
+    if all_features {
+        args.push("--all-features".to_owned());
+    }
+
+    if !default_features {
+        args.push("--no-default-features".to_owned());
+    }
+
+    if !features.is_empty() {
+        args.push("--features".to_owned());
+        args.push(features.join(","));
+    }
+
+    args
+}
+
+/// The name of the package for this version, before any `on_existing`
+/// renaming.
+fn package_file_name(
+    format: OutputFormat,
+    platform: Platform,
+    app_name: &str,
+    version: &str,
+) -> String {
+    match format {
+        OutputFormat::Zip => format!("{}_{}_{}.zip", app_name, platform.name(), version),
+        OutputFormat::AppImage => format!("{}_linux_{}.AppImage", app_name, version),
+        OutputFormat::TarGz => format!("{}_linux_{}.tar.gz", app_name, version),
+        OutputFormat::Dmg => format!("{}_macos_{}.dmg", app_name, version),
+    }
+}
+
+/// `path` with `-{n}` added before its extension, or `path` itself for 0.
+fn numbered_path(path: &Path, n: usize) -> PathBuf {
+    if n == 0 {
+        return path.to_owned();
+    }
+
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let name = match file_name.strip_suffix(".tar.gz") {
+        Some(stem) => format!("{}-{}.tar.gz", stem, n),
+        None => {
+            let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+            match path.extension() {
+                Some(extension) => format!("{}-{}.{}", stem, n, extension.to_string_lossy()),
+                None => format!("{}-{}", stem, n),
+            }
+        }
+    };
+    path.with_file_name(name)
+}
+
+/// Where a package meant for `path` would go right now, following
+/// `policy`.
+fn available_path(path: &Path, policy: OverwritePolicy) -> Result<PathBuf, JamjarError> {
+    match policy {
+        OverwritePolicy::Overwrite => Ok(path.to_owned()),
+        OverwritePolicy::Error if path.exists() => Err(JamjarError::PackageExists {
+            path: path.to_owned(),
+        }),
+        OverwritePolicy::Error => Ok(path.to_owned()),
+        OverwritePolicy::Rename => Ok((0..)
+            .map(|n| numbered_path(path, n))
+            .find(|path| !path.exists())
+            .unwrap()),
+    }
+}
+
+/// Zip up the files in `source_dir` that `filter` includes. The archive is
+/// written next to `output_path` with a `.partial` extension, and only
+/// moved into place once it's complete. Returns where it ended up.
+fn zip_dir(
+    source_dir: &Path,
+    output_path: &Path,
+    policy: OverwritePolicy,
+    filter: &PathFilter,
+    reporter: &mut Reporter,
+) -> Result<PathBuf, JamjarError> {
+    write_atomically(output_path, policy, |partial_path| {
+        zip_dir_to(source_dir, partial_path, filter, reporter)
+    })
+}
+
+/// Call `write` with a temporary path next to `path`, then move what it
+/// wrote into place, following `policy` if something's already there.
+/// If it fails, the partial file is removed, so nothing half-written is
+/// left with the final name.
+fn write_atomically<F>(
+    path: &Path,
+    policy: OverwritePolicy,
+    write: F,
+) -> Result<PathBuf, JamjarError>
+where
+    F: FnOnce(&Path) -> Result<(), JamjarError>,
+{
+    let mut partial_name = path.file_name().unwrap_or_default().to_owned();
+    partial_name.push(".partial");
+    let partial_path = path.with_file_name(partial_name);
+
+    if let Err(e) = write(&partial_path) {
+        let _ = std::fs::remove_file(&partial_path);
+        return Err(e);
+    }
+
+    let result = move_into_place(&partial_path, path, policy);
+    if result.is_err() {
+        let _ = std::fs::remove_file(&partial_path);
+    }
+    result
+}
+
+/// Move `partial_path` to `path`. Unless `policy` is `Overwrite`, this
+/// never replaces a file, even one that appeared during the build.
+fn move_into_place(
+    partial_path: &Path,
+    path: &Path,
+    policy: OverwritePolicy,
+) -> Result<PathBuf, JamjarError> {
+    let move_error = |e, path: &Path| {
+        JamjarError::io(
+            e,
+            &format!("Failed to move '{}' into place.", path.display()),
+        )
+    };
+
+    if policy == OverwritePolicy::Overwrite {
+        std::fs::rename(partial_path, path).map_err(|e| move_error(e, path))?;
+        return Ok(path.to_owned());
+    }
+
+    for n in 0.. {
+        let candidate = numbered_path(path, n);
+
+        // A hard link fails if the file exists, so nothing can slip in
+        // between checking and moving. Filesystems without hard links fall
+        // back to checking first.
+        let linked = match std::fs::hard_link(partial_path, &candidate) {
+            Ok(()) => true,
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => false,
+            Err(_) if candidate.exists() => false,
+            Err(_) => {
+                std::fs::rename(partial_path, &candidate).map_err(|e| move_error(e, &candidate))?;
+                return Ok(candidate);
+            }
+        };
+
+        if linked {
+            let _ = std::fs::remove_file(partial_path);
+            return Ok(candidate);
+        }
+        if policy == OverwritePolicy::Error {
+            return Err(JamjarError::PackageExists { path: candidate });
+        }
+    }
+
+    unreachable!()
+}
+
+fn zip_dir_to(
+    source_dir: &Path,
+    output_path: &Path,
+    filter: &PathFilter,
+    reporter: &mut Reporter,
+) -> Result<(), JamjarError> {
+    use std::fs::File;
+
+    let mut output_file = File::create(output_path)
+        .map_err(|e| JamjarError::io(e, "Failed to create output file."))?;
+
+    let mut zipper = ZipWriter::new(&mut output_file);
+    let mut dirs = vec![source_dir.to_owned()];
+
+    while let Some(dir) = dirs.pop() {
+        for entry in std::fs::read_dir(dir)? {
+            use std::io::Write;
+
+            let entry = entry?;
+            let path = entry.path();
+
+            if entry.file_type()?.is_file() {
+                let rel_path = path.strip_prefix(source_dir).unwrap().to_owned();
+                let slashed = slashed(&rel_path);
+                if !filter.includes(&slashed) {
+                    reporter.line(&format!("Leaving out: {}", slashed));
+                    continue;
+                }
+
+                zipper.start_file(slashed, zip_file_options(&entry)?)?;
+                let contents = std::fs::read(path)?;
+                zipper.write_all(&contents)?;
+            } else {
+                dirs.push(path);
+            }
+        }
+    }
+
+    zipper.finish()?;
+
+    Ok(())
+}
+
+/// Write the files in `source_dir` that `filter` includes to a gzipped
+/// tarball. Files keep whether they're executable, but not their owners or
+/// times, so the same build always makes the same archive.
+fn tar_dir_to(
+    source_dir: &Path,
+    output_path: &Path,
+    filter: &PathFilter,
+    reporter: &mut Reporter,
+) -> Result<(), JamjarError> {
+    use flate2::{write::GzEncoder, Compression};
+    use tar::{Builder, HeaderMode};
+
+    let output_file = std::fs::File::create(output_path)
+        .map_err(|e| JamjarError::io(e, "Failed to create output file."))?;
+
+    let mut tarball = Builder::new(GzEncoder::new(output_file, Compression::default()));
+    tarball.mode(HeaderMode::Deterministic);
+
+    let mut files = vec![];
+    let mut dirs = vec![source_dir.to_owned()];
+    while let Some(dir) = dirs.pop() {
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            match path.is_dir() {
+                true => dirs.push(path),
+                false => files.push(path),
+            }
+        }
+    }
+    files.sort();
+
+    for path in files {
+        let slashed = slashed(path.strip_prefix(source_dir).unwrap());
+        if !filter.includes(&slashed) {
+            reporter.line(&format!("Leaving out: {}", slashed));
+            continue;
+        }
+        tarball.append_path_with_name(&path, slashed)?;
+    }
+
+    tarball.into_inner()?.finish()?;
+
+    Ok(())
+}
+
+/// Options for zipping `entry`. On Unix, it keeps its permissions, so
+/// executables stay executable. Elsewhere there are none to keep, and the
+/// zip's defaults are used.
+fn zip_file_options(entry: &std::fs::DirEntry) -> Result<FileOptions, JamjarError> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        let mode = entry.metadata()?.permissions().mode();
+        Ok(FileOptions::default().unix_permissions(mode))
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = entry;
+        Ok(FileOptions::default())
+    }
+}
+
+/// Create the macOS app in `destination`, along with everything that ships
+/// next to it, then smoke test and notarize it if asked to. Zips and DMGs
+/// both hold what's left in `destination`. `texts` are the changes and
+/// third-party notices, if there are any.
+fn stage_macos_app(
+    config: &PackageConfig,
+    app_config: &AppConfig,
+    destination: &Path,
+    smoke_test: Option<&SmokeTest>,
+    texts: (Option<&str>, Option<&str>),
+    reporter: &mut Reporter,
+) -> Result<(), JamjarError> {
+    reporter.line("Creating macOS app");
+    let app_path = create_macos_app(app_config, destination, reporter)?;
+
+    if let Some(smoke_test) = smoke_test {
+        let exe_path = app_path.join("Contents/MacOS").join(app_config.app_name);
+        run_smoke_test(&exe_path, smoke_test, reporter)?;
+    }
+
+    if config.launch_helpers {
+        reporter.line("Adding launch helpers");
+        write_launch_helpers(
+            app_config,
+            destination,
+            &app_path,
+            false,
+            config.controls.as_deref(),
+        )?;
+    }
+
+    let (changes, notices) = texts;
+    if let Some(changes) = changes {
+        write_changes(destination, changes)?;
+    }
+
+    if let Some(notices) = notices {
+        write_notices(destination, notices)?;
+    }
+
+    if let Some(profile) = &config.notarize {
+        notarize_app(&app_path, profile, reporter)?;
+    }
+
+    Ok(())
+}
+
+fn create_macos_app(
+    config: &AppConfig,
+    destination: &Path,
+    reporter: &mut Reporter,
+) -> Result<PathBuf, JamjarError> {
+    let AppConfig {
+        app_name,
+        exe_name,
+        version,
+        bundle_id,
+        icon_path,
+        copyright,
+        ..
+    } = config;
+
+    let app_path = destination.join(format!("{}.app", app_name));
+    let contents_path = app_path.join("Contents");
+    let macos_path = contents_path.join("MacOS");
+    let resources_path = contents_path.join("Resources");
+    let plist_path = contents_path.join("Info.plist");
+    let app_exe_path = macos_path.join(app_name);
+    let app_icons_path = resources_path.join("Icon.icns");
+
+    std::fs::create_dir_all(&macos_path)?;
+    std::fs::create_dir_all(&resources_path)?;
+    std::fs::create_dir_all(&contents_path)?;
+
+    // Info.plist
+    #[derive(Serialize)]
+    struct InfoPlist<'a> {
+        app_name: &'a str,
+        version: &'a str,
+        bundle_id: &'a str,
+        copyright: Option<&'a str>,
+    }
+
+    let template = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/templates/Info.plist"));
+    let context = InfoPlist {
+        app_name,
+        version,
+        bundle_id,
+        copyright: *copyright,
+    };
+
+    let info_plist = render_template("Info.plist", template, &context, true)?;
+
+    std::fs::write(&plist_path, &info_plist)
+        .map_err(|e| JamjarError::io(e, "Failed to write Info.plist."))?;
+
+    // Icons
+    {
+        reporter.line("Creating icon set:");
+
+        let temp_icons_dir = tempfile::tempdir()?;
+        let temp_icons_dir = temp_icons_dir
+            .as_ref()
+            .join(format!("{}.iconset", app_name));
+        std::fs::create_dir(&temp_icons_dir)?;
+
+        let sizes = &[
+            (16, "icon_16x16.png"),
+            (32, "icon_16x16@2x.png"),
+            (32, "icon_32x32.png"),
+            (64, "icon_32x32@2x.png"),
+            (128, "icon_128x128.png"),
+            (256, "icon_128x128@2x.png"),
+            (256, "icon_256x256.png"),
+            (512, "icon_256x256@2x.png"),
+            (512, "icon_512x512.png"),
+            (1024, "icon_512x512@2x.png"),
+        ];
+
+        render_icon_sizes(icon_path, &temp_icons_dir, sizes, reporter)?;
+
+        reporter.line("Running iconutil");
+        let output = Command::new("iconutil")
+            .arg("-c")
+            .arg("icns")
+            .arg(temp_icons_dir)
+            .arg("--output")
+            .arg(&app_icons_path)
+            .output()?;
+
+        reporter.command_output(&output);
+
+        if !output.status.success() {
+            return Err(JamjarError::ExternalCommandError("iconutil"));
+        }
+    }
+
+    copy_resources(config, &resources_path, reporter)?;
+
+    // Executable
+    copy_exe(config, exe_name, &app_exe_path)?;
+    make_executable(&app_exe_path)?;
+
+    // Signing has to come last, as any later change to the bundle breaks
+    // the signature
+    if let Some(identity) = config.sign_identity {
+        match identity {
+            "-" => reporter.line("Signing app ad-hoc"),
+            _ => reporter.line(&format!("Signing app as {}", identity)),
+        }
+        codesign(&app_exe_path, identity, config.entitlements, reporter)?;
+        codesign(&app_path, identity, config.entitlements, reporter)?;
+    }
+
+    Ok(app_path)
+}
+
+/// Sign `path` with the hardened runtime, which notarization requires.
+fn codesign(
+    path: &Path,
+    identity: &str,
+    entitlements: Option<&Path>,
+    reporter: &mut Reporter,
+) -> Result<(), JamjarError> {
+    let mut cmd = Command::new("codesign");
+    cmd.arg("--force")
+        .arg("--options")
+        .arg("runtime")
+        .arg("--sign")
+        .arg(identity);
+
+    if let Some(entitlements) = entitlements {
+        cmd.arg("--entitlements").arg(entitlements);
+    }
+
+    let output = cmd
+        .arg(path)
+        .output()
+        .map_err(|e| JamjarError::io(e, "Failed to run codesign."))?;
+
+    reporter.command_output(&output);
+
+    if !output.status.success() {
+        return Err(JamjarError::StringError(format!(
+            "codesign could not sign '{}':\n{}",
+            path.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Deserialize)]
+struct NotaryResult {
+    status: String,
+}
+
+/// Submit a signed app to Apple's notary service, and staple the ticket to
+/// it once it's accepted so it passes Gatekeeper offline.
+fn notarize_app(
+    app_path: &Path,
+    profile: &str,
+    reporter: &mut Reporter,
+) -> Result<(), JamjarError> {
+    // The package itself can't be submitted, as the app has to be stapled
+    // before it's zipped
+    let submission_dir = tempfile::tempdir()
+        .map_err(|e| JamjarError::io(e, "Failed to create temporary directory."))?;
+    let submission_path = submission_dir.as_ref().join("notarize.zip");
+
+    reporter.line("Zipping app for notarization");
+    let output = Command::new("ditto")
+        .arg("-c")
+        .arg("-k")
+        .arg("--keepParent")
+        .arg(app_path)
+        .arg(&submission_path)
+        .output()
+        .map_err(|e| JamjarError::io(e, "Failed to run ditto."))?;
+
+    reporter.command_output(&output);
+
+    if !output.status.success() {
+        return Err(JamjarError::ExternalCommandError("ditto"));
+    }
+
+    reporter.line("Submitting app for notarization, which can take a few minutes");
+    let output = Command::new("xcrun")
+        .arg("notarytool")
+        .arg("submit")
+        .arg(&submission_path)
+        .arg("--keychain-profile")
+        .arg(profile)
+        .arg("--wait")
+        .arg("--output-format")
+        .arg("json")
+        .output()
+        .map_err(|e| JamjarError::io(e, "Failed to run notarytool."))?;
+
+    let accepted = match serde_json::from_slice::<NotaryResult>(&output.stdout) {
+        Ok(result) => result.status == "Accepted",
+        Err(_) => false,
+    };
+    if !output.status.success() || !accepted {
+        return Err(JamjarError::StringError(format!(
+            "notarization failed:\n{}{}",
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
+        )));
+    }
+
+    reporter.line("Stapling notarization ticket");
+    let output = Command::new("xcrun")
+        .arg("stapler")
+        .arg("staple")
+        .arg(app_path)
+        .output()
+        .map_err(|e| JamjarError::io(e, "Failed to run stapler."))?;
+
+    reporter.command_output(&output);
+
+    if !output.status.success() {
+        return Err(JamjarError::ExternalCommandError("stapler"));
+    }
+
+    Ok(())
+}
+
+/// Resize the icon to each size, saving it to each path under `dir`.
+fn render_icon_sizes<P: AsRef<Path>>(
+    icon_path: &Path,
+    dir: &Path,
+    sizes: &[(u32, P)],
+    reporter: &mut Reporter,
+) -> Result<(), JamjarError> {
+    use image::imageops::FilterType;
+
+    let image_bytes = std::fs::read(icon_path)?;
+    let image = image::load_from_memory(&image_bytes)?;
+
+    for (size, path) in sizes {
+        let path = path.as_ref();
+        let dest_path = dir.join(path);
+        if let Some(parent) = dest_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let resized_image = image.resize_exact(*size, *size, FilterType::CatmullRom);
+        resized_image.save(&dest_path)?;
+        reporter.line(&format!("  Resized to {}", path.display()));
+    }
+
+    Ok(())
+}
+
+/// Render the icon into the `hicolor` theme under `prefix`, at
+/// `share/icons/hicolor/{size}x{size}/apps/{exe_name}.png`.
+fn write_hicolor_icons(
+    config: &AppConfig,
+    prefix: &Path,
+    reporter: &mut Reporter,
+) -> Result<(), JamjarError> {
+    reporter.line("Creating hicolor icons:");
+
+    let sizes = config
+        .icon_sizes
+        .iter()
+        .map(|&size| {
+            let path = format!(
+                "share/icons/hicolor/{0}x{0}/apps/{1}.png",
+                size, config.exe_name
+            );
+            (size, path)
+        })
+        .collect::<Vec<_>>();
+
+    render_icon_sizes(config.icon_path, prefix, &sizes, reporter)
+}
+
+fn write_desktop_entry(config: &AppConfig, path: &Path) -> Result<(), JamjarError> {
     #[derive(Serialize)]
-    struct InfoPlist<'a> {
+    struct DesktopEntry<'a> {
+        app_name: &'a str,
+        exe_name: &'a str,
+        categories: String,
+    }
+
+    let context = DesktopEntry {
+        app_name: config.app_name,
+        exe_name: config.exe_name,
+        categories: config
+            .desktop_categories
+            .iter()
+            .map(|category| format!("{};", category))
+            .collect(),
+    };
+
+    let template = include_str!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/templates/app.desktop"
+    ));
+    let desktop_entry = render_template("app.desktop", template, &context, false)?;
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, &desktop_entry)
+        .map_err(|e| JamjarError::io(e, "Failed to write .desktop file."))
+}
+
+/// Add a `share` folder to a zipped Linux app, laid out so it can be merged
+/// into `~/.local/share` (or `/usr/share`) to install the icons and menu
+/// entry. Apps without an icon are left alone.
+fn add_linux_desktop_files(
+    config: &AppConfig,
+    folder_path: &Path,
+    reporter: &mut Reporter,
+) -> Result<(), JamjarError> {
+    if !config.icon_path.is_file() {
+        reporter.line(&format!(
+            "No icon at {}, so no desktop files were added",
+            config.icon_path.display()
+        ));
+        return Ok(());
+    }
+
+    write_hicolor_icons(config, folder_path, reporter)?;
+    write_desktop_entry(
+        config,
+        &folder_path
+            .join("share/applications")
+            .join(format!("{}.desktop", config.exe_name)),
+    )
+}
+
+/// Copy the release build of `exe_file` from the target directory.
+fn copy_exe(config: &AppConfig, exe_file: &str, destination: &Path) -> Result<(), JamjarError> {
+    let exe_path = config.release_dir.join(exe_file);
+    std::fs::copy(&exe_path, destination).map_err(|e| {
+        JamjarError::io(
+            e,
+            &format!(
+                "Could not copy the built executable '{}' (Cargo.toml is at '{}', and the app root is '{}').",
+                exe_path.display(),
+                config.manifest_path.display(),
+                config.app_root.display()
+            ),
+        )
+    })?;
+    Ok(())
+}
+
+/// Copy the executable and resources into an `{app_name}` folder,
+/// returning the executable's path.
+fn create_app_folder(
+    config: &AppConfig,
+    destination: &Path,
+    reporter: &mut Reporter,
+) -> Result<PathBuf, JamjarError> {
+    let folder_path = destination.join(config.app_name);
+    let app_exe_path = folder_path.join(config.exe_name);
+    std::fs::create_dir_all(&folder_path)?;
+
+    copy_exe(config, config.exe_name, &app_exe_path)?;
+    make_executable(&app_exe_path)?;
+    copy_resources(config, &folder_path, reporter)?;
+
+    Ok(app_exe_path)
+}
+
+/// Lay out an `{app_name}` folder that can be extracted over `~/.local`
+/// (or `/usr/local`) to install the app, returning the executable's path:
+///
+/// ```text
+/// {app_name}/
+///     bin/{exe_name}
+///     share/{exe_name}/{resources}
+///     share/applications/{exe_name}.desktop
+///     share/icons/hicolor/{size}x{size}/apps/{exe_name}.png
+/// ```
+fn create_linux_app(
+    config: &AppConfig,
+    destination: &Path,
+    reporter: &mut Reporter,
+) -> Result<PathBuf, JamjarError> {
+    let folder_path = destination.join(config.app_name);
+    let bin_path = folder_path.join("bin");
+    let app_exe_path = bin_path.join(config.exe_name);
+    std::fs::create_dir_all(&bin_path)?;
+
+    copy_exe(config, config.exe_name, &app_exe_path)?;
+    make_executable(&app_exe_path)?;
+    copy_resources(
+        config,
+        &folder_path.join("share").join(config.exe_name),
+        reporter,
+    )?;
+    add_linux_desktop_files(config, &folder_path, reporter)?;
+
+    Ok(app_exe_path)
+}
+
+/// Copy the `.exe` into an `{app_name}` folder as `{app_name}.exe`, with
+/// the resources next to it, returning its path. The icon and version info
+/// are already in the exe, linked in from `write_resources`.
+fn create_windows_app(
+    config: &AppConfig,
+    destination: &Path,
+    reporter: &mut Reporter,
+) -> Result<PathBuf, JamjarError> {
+    let folder_path = destination.join(config.app_name);
+    let app_exe_path = folder_path.join(format!("{}.exe", config.app_name));
+    std::fs::create_dir_all(&folder_path)?;
+
+    copy_exe(config, &format!("{}.exe", config.exe_name), &app_exe_path)?;
+    copy_resources(config, &folder_path, reporter)?;
+
+    Ok(app_exe_path)
+}
+
+/// Let everyone run the file at `path`. Other platforms have no such
+/// permission, so this does nothing there.
+fn make_executable(path: &Path) -> Result<(), JamjarError> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        let mut perms = std::fs::metadata(path)?.permissions();
+        perms.set_mode(0o755);
+        std::fs::set_permissions(path, perms)?;
+    }
+
+    #[cfg(not(unix))]
+    let _ = path;
+
+    Ok(())
+}
+
+/// Write a README (and a `run.sh`, if `run_script` is set) next to the app.
+fn write_launch_helpers(
+    config: &AppConfig,
+    app_dir: &Path,
+    app_file: &Path,
+    run_script: bool,
+    controls: Option<&str>,
+) -> Result<(), JamjarError> {
+    #[derive(Serialize)]
+    struct LaunchHelpers<'a> {
         app_name: &'a str,
+        exe_name: &'a str,
+        exe_file: &'a str,
         version: &'a str,
-        bundle_id: &'a str,
+        controls: Option<&'a str>,
+        run_script: bool,
     }
 
-    let template = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/templates/Info.plist"));
-    let context = InfoPlist {
-        app_name,
-        version,
-        bundle_id,
+    let exe_file = app_file.file_name().unwrap().to_string_lossy();
+    let context = LaunchHelpers {
+        app_name: config.app_name,
+        exe_name: config.exe_name,
+        exe_file: &exe_file,
+        version: config.version,
+        controls,
+        run_script,
     };
 
-    let hb = Handlebars::new();
-    let info_plist = hb
-        .render_template(&template, &context)
-        .map_err(|e| JamjarError::TemplateError { cause: e })?;
+    if run_script {
+        let run_script_path = app_dir.join("run.sh");
+        let template = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/templates/run.sh"));
+        let script = render_template("run.sh", template, &context, false)?;
 
-    std::fs::write(&plist_path, &info_plist)
-        .map_err(|e| JamjarError::io(e, "Failed to write Info.plist."))?;
+        std::fs::write(&run_script_path, &script)
+            .map_err(|e| JamjarError::io(e, "Failed to write run.sh."))?;
+
+        make_executable(&run_script_path)?;
+    }
+
+    let template = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/templates/README.txt"));
+    let readme = render_template("README.txt", template, &context, false)?;
+
+    std::fs::write(app_dir.join("README.txt"), &readme)
+        .map_err(|e| JamjarError::io(e, "Failed to write README.txt."))?;
+
+    Ok(())
+}
+
+/// Stage an AppDir following the AppImage spec:
+///
+/// ```text
+/// {app_name}.AppDir/
+///     AppRun
+///     {exe_name}.desktop
+///     {exe_name}.png
+///     .DirIcon
+///     usr/bin/{exe_name}
+///     usr/share/{exe_name}/assets/
+///     usr/share/{exe_name}/{resources}
+///     usr/share/icons/hicolor/{size}x{size}/apps/{exe_name}.png
+/// ```
+fn create_linux_app_dir(
+    config: &AppConfig,
+    destination: &Path,
+    reporter: &mut Reporter,
+) -> Result<PathBuf, JamjarError> {
+    let AppConfig {
+        app_root,
+        app_name,
+        exe_name,
+        icon_path,
+        ..
+    } = config;
+
+    let app_dir_path = destination.join(format!("{}.AppDir", app_name));
+    let bin_path = app_dir_path.join("usr/bin");
+    let share_path = app_dir_path.join("usr/share").join(exe_name);
+    let app_run_path = app_dir_path.join("AppRun");
+    let desktop_path = app_dir_path.join(format!("{}.desktop", exe_name));
+    let icon_dest_path = app_dir_path.join(format!("{}.png", exe_name));
+    let app_exe_path = bin_path.join(exe_name);
+
+    std::fs::create_dir_all(&bin_path)?;
+    std::fs::create_dir_all(&share_path)?;
+
+    #[derive(Serialize)]
+    struct AppRunScript<'a> {
+        app_name: &'a str,
+        exe_name: &'a str,
+    }
+
+    let context = AppRunScript { app_name, exe_name };
+
+    // AppRun
+    let template = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/templates/AppRun"));
+    let app_run = render_template("AppRun", template, &context, false)?;
+
+    std::fs::write(&app_run_path, &app_run)
+        .map_err(|e| JamjarError::io(e, "Failed to write AppRun."))?;
+
+    // .desktop
+    write_desktop_entry(config, &desktop_path)?;
 
     // Icons
+    write_hicolor_icons(config, &app_dir_path.join("usr"), reporter)?;
     {
-        println!("Creating icon set:");
-
-        let temp_icons_dir = tempfile::tempdir()?;
-        let temp_icons_dir = temp_icons_dir
-            .as_ref()
-            .join(format!("{}.iconset", app_name));
-        std::fs::create_dir(&temp_icons_dir)?;
+        use image::imageops::FilterType;
 
         let image_bytes = std::fs::read(icon_path)?;
         let image = image::load_from_memory(&image_bytes)?;
+        let resized_image = image.resize_exact(256, 256, FilterType::CatmullRom);
+        resized_image.save(&icon_dest_path)?;
+        std::fs::copy(&icon_dest_path, app_dir_path.join(".DirIcon"))?;
+    }
 
-        let sizes = &[
-            ((16, 16), "icon_16x16.png"),
-            ((32, 32), "icon_16x16@2x.png"),
-            ((32, 32), "icon_32x32.png"),
-            ((64, 64), "icon_32x32@2x.png"),
-            ((128, 128), "icon_128x128.png"),
-            ((256, 256), "icon_128x128@2x.png"),
-            ((256, 256), "icon_256x256.png"),
-            ((512, 512), "icon_256x256@2x.png"),
-            ((512, 512), "icon_512x512.png"),
-            ((1024, 1024), "icon_512x512@2x.png"),
-        ];
+    // Assets
+    let assets_path = app_root.join("assets");
+    if assets_path.is_dir() {
+        reporter.line("Bundling assets");
+        let mut copied = Copied::default();
+        copy_dir(
+            &assets_path,
+            &share_path.join("assets"),
+            Path::new("assets"),
+            config.asset_filter,
+            false,
+            &mut copied,
+        )?;
+        copied.report_excluded(config.asset_filter, reporter);
+    }
+    copy_resources(config, &share_path, reporter)?;
+
+    // Executable
+    copy_exe(config, exe_name, &app_exe_path)?;
+
+    make_executable(&app_exe_path)?;
+    make_executable(&app_run_path)?;
 
-        for &((width, height), filename) in sizes {
-            use image::imageops::FilterType;
+    Ok(app_dir_path)
+}
+
+/// The files copied by `copy_dir`, and the files it left out.
+#[derive(Debug, Default)]
+struct Copied {
+    /// Where each copied file is, as the filter saw it.
+    files: Vec<PathBuf>,
+    excluded_files: usize,
+    excluded_bytes: u64,
+    /// Symlinks and hidden files that weren't copied.
+    skipped: Vec<PathBuf>,
+}
 
-            let resized_image = image.resize_exact(width, height, FilterType::CatmullRom);
-            resized_image.save(temp_icons_dir.join(filename))?;
-            println!("  Resized to {}", filename);
+impl Copied {
+    fn report_excluded(&self, filter: &PathFilter, reporter: &mut Reporter) {
+        if self.excluded_files > 0 {
+            let reason = match filter.include_only {
+                Some(_) => format!(
+                    "matching `asset_exclude` or missing from {}",
+                    ASSET_MANIFEST
+                ),
+                None => "matching `asset_exclude`".to_owned(),
+            };
+            reporter.line(&format!(
+                "  Left out {} files ({} bytes) {}",
+                self.excluded_files, self.excluded_bytes, reason
+            ));
         }
+        for path in &self.skipped {
+            reporter.line(&format!(
+                "  Skipped {}, as it's a symlink or hidden",
+                slashed(path)
+            ));
+        }
+    }
+}
 
-        println!("Running iconutil");
-        let output = Command::new("iconutil")
-            .arg("-c")
-            .arg("icns")
-            .arg(temp_icons_dir)
-            .arg("--output")
-            .arg(&app_icons_path)
-            .output()?;
+/// Symlinks might point outside the app, and hidden files are things like
+/// `.DS_Store` and `.gitkeep`, so resources leave both out.
+fn skip_entry(entry: &std::fs::DirEntry) -> Result<bool, JamjarError> {
+    Ok(entry.file_type()?.is_symlink() || entry.file_name().to_string_lossy().starts_with('.'))
+}
 
-        print!("{}", String::from_utf8_lossy(&output.stdout));
-        eprint!("{}", String::from_utf8_lossy(&output.stderr));
+/// Copy the files in `source` that `filter` includes, noting them and the
+/// rest in `copied`. `rel_path` is where `source` is, as `filter` sees it.
+/// If `skip_hidden` is set, symlinks and hidden files are left out too.
+fn copy_dir(
+    source: &Path,
+    destination: &Path,
+    rel_path: &Path,
+    filter: &PathFilter,
+    skip_hidden: bool,
+    copied: &mut Copied,
+) -> Result<(), JamjarError> {
+    std::fs::create_dir_all(destination)?;
+
+    for entry in std::fs::read_dir(source)? {
+        let entry = entry?;
+        let dest_path = destination.join(entry.file_name());
+        let entry_rel_path = rel_path.join(entry.file_name());
+
+        if skip_hidden && skip_entry(&entry)? {
+            copied.skipped.push(entry_rel_path);
+        } else if entry.file_type()?.is_dir() {
+            copy_dir(
+                &entry.path(),
+                &dest_path,
+                &entry_rel_path,
+                filter,
+                skip_hidden,
+                copied,
+            )?;
+        } else if filter.includes(&slashed(&entry_rel_path)) {
+            std::fs::copy(entry.path(), dest_path)?;
+            copied.files.push(entry_rel_path);
+        } else {
+            copied.excluded_files += 1;
+            copied.excluded_bytes += entry.metadata()?.len();
+        }
+    }
 
-        if !output.status.success() {
-            return Err(JamjarError::ExternalCommandError("iconutil"));
+    Ok(())
+}
+
+/// Copy each of the `resources` into `destination`, keeping their names.
+/// Directories are copied with everything in them but symlinks, hidden
+/// files and `asset_exclude` matches. `asset_manifest.toml` only lists
+/// assets, so it doesn't apply.
+fn copy_resources(
+    config: &AppConfig,
+    destination: &Path,
+    reporter: &mut Reporter,
+) -> Result<(), JamjarError> {
+    if config.resources.is_empty() {
+        return Ok(());
+    }
+
+    reporter.line("Bundling resources:");
+    let filter = PathFilter {
+        exclude: config.asset_filter.exclude.clone(),
+        include_only: None,
+    };
+    let mut copied = Copied::default();
+
+    for resource in config.resources {
+        let source = config.app_root.join(resource);
+        let metadata = std::fs::symlink_metadata(&source).map_err(|e| {
+            JamjarError::io(
+                e,
+                &format!("The resource '{}' could not be found.", source.display()),
+            )
+        })?;
+        let name = match source.file_name() {
+            Some(name) => name,
+            None => {
+                return Err(JamjarError::StringError(format!(
+                    "the resource '{}' should name a file or directory in the app",
+                    resource.display()
+                )))
+            }
+        };
+        let rel_path = source.strip_prefix(config.app_root).unwrap_or(resource);
+
+        if metadata.file_type().is_symlink() {
+            reporter.line(&format!(
+                "  Skipped {}, as it's a symlink",
+                rel_path.display()
+            ));
+        } else if metadata.is_dir() {
+            copy_dir(
+                &source,
+                &destination.join(name),
+                rel_path,
+                &filter,
+                true,
+                &mut copied,
+            )?;
+        } else {
+            std::fs::create_dir_all(destination)?;
+            std::fs::copy(&source, destination.join(name))?;
+            copied.files.push(rel_path.to_owned());
         }
     }
 
-    // Executable
-    let exe_path = app_root.join(format!("target/release/{}", exe_name));
-    std::fs::copy(&exe_path, &app_exe_path)?;
+    for file in &copied.files {
+        reporter.line(&format!("  {}", slashed(file)));
+    }
+    copied.report_excluded(&filter, reporter);
 
-    let mut perms = std::fs::metadata(&app_exe_path)?.permissions();
-    perms.set_mode(0o755);
-    std::fs::set_permissions(&app_exe_path, perms)?;
+    Ok(())
+}
 
-    Ok(app_path)
+const APPIMAGETOOL_HELP: &str = "Download it from \
+    https://github.com/AppImage/AppImageKit/releases, make it executable, \
+    and put it on your PATH.";
+
+fn appimagetool_installed() -> bool {
+    match Command::new("appimagetool").arg("--version").output() {
+        Err(e) => e.kind() != std::io::ErrorKind::NotFound,
+        Ok(_) => true,
+    }
+}
+
+fn run_appimagetool(
+    app_dir: &Path,
+    output_path: &Path,
+    reporter: &mut Reporter,
+) -> Result<(), JamjarError> {
+    let output = Command::new("appimagetool")
+        .env("ARCH", "x86_64")
+        .arg(app_dir)
+        .arg(output_path)
+        .output()
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => JamjarError::StringError(format!(
+                "`appimagetool` was not found. {}",
+                APPIMAGETOOL_HELP
+            )),
+            _ => JamjarError::io(e, "Failed to run appimagetool."),
+        })?;
+
+    reporter.command_output(&output);
+
+    if !output.status.success() {
+        return Err(JamjarError::ExternalCommandError("appimagetool"));
+    }
+
+    Ok(())
+}
+
+/// Make a compressed disk image named `volume_name` holding the contents of
+/// `source_dir`.
+fn run_hdiutil(
+    volume_name: &str,
+    source_dir: &Path,
+    output_path: &Path,
+    reporter: &mut Reporter,
+) -> Result<(), JamjarError> {
+    // hdiutil adds `.dmg` to any path that doesn't end with it, so the image
+    // is made elsewhere and copied over
+    let image_dir = tempfile::tempdir()
+        .map_err(|e| JamjarError::io(e, "Failed to create temporary directory."))?;
+    let image_path = image_dir.as_ref().join(format!("{}.dmg", volume_name));
+
+    let output = Command::new("hdiutil")
+        .arg("create")
+        .arg("-volname")
+        .arg(volume_name)
+        .arg("-srcfolder")
+        .arg(source_dir)
+        .arg("-format")
+        .arg("UDZO")
+        .arg(&image_path)
+        .output()
+        .map_err(|e| JamjarError::io(e, "Failed to run hdiutil."))?;
+
+    reporter.command_output(&output);
+
+    if !output.status.success() {
+        return Err(JamjarError::StringError(format!(
+            "hdiutil could not create the disk image:\n{}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    std::fs::copy(&image_path, output_path)
+        .map_err(|e| JamjarError::io(e, "Failed to copy the disk image to the output."))?;
+    Ok(())
+}
+
+/// The post-MVP wasm features that `wasm_compat = "legacy"` turns off.
+const LEGACY_WASM_FEATURES: &str =
+    "-bulk-memory,-multivalue,-nontrapping-fptoint,-reference-types,-sign-ext";
+
+/// wasm-opt passes to rewrite the features out of the standard library,
+/// which comes prebuilt with them on.
+const LEGACY_WASM_OPT_PASSES: &[&str] = &[
+    "--signext-lowering",
+    "--llvm-memory-copy-fill-lowering",
+    "--llvm-nontrapping-fptoint-lowering",
+];
+
+/// Add the flags that turn the features off to a legacy build, keeping any
+/// rustflags the user already has.
+///
+/// Cargo takes rustflags from only one place: `CARGO_ENCODED_RUSTFLAGS`,
+/// then `RUSTFLAGS`, then any `target` rustflags in its config, then
+/// `build.rustflags`. If one of the variables is set, the flags are added
+/// to it. Otherwise they're passed with `--config` on the key the config
+/// uses, where cargo joins them onto the user's.
+fn add_legacy_rustflags(cmd: &mut Command, app_root: &Path) {
+    let target_feature = format!("target-feature={}", LEGACY_WASM_FEATURES);
+
+    if let Some(existing) = std::env::var_os("CARGO_ENCODED_RUSTFLAGS") {
+        let mut flags = existing;
+        if !flags.is_empty() {
+            flags.push("\x1f");
+        }
+        flags.push(format!("-C\x1f{}", target_feature));
+        cmd.env("CARGO_ENCODED_RUSTFLAGS", flags);
+        return;
+    }
+
+    if let Ok(existing) = std::env::var("RUSTFLAGS") {
+        if !existing.trim().is_empty() {
+            cmd.env(
+                "RUSTFLAGS",
+                format!("{} -C {}", existing.trim(), target_feature),
+            );
+            return;
+        }
+    }
+
+    let (build_flags, target_flags) = configured_rustflags(app_root);
+    let key = match build_flags && !target_flags {
+        true => "build.rustflags",
+        false => "target.wasm32-unknown-unknown.rustflags",
+    };
+    cmd.arg("--config")
+        .arg(format!("{}=[\"-C\", \"{}\"]", key, target_feature));
+}
+
+/// Whether cargo's config for a build in `app_root` sets `build.rustflags`,
+/// and whether it sets any `target` rustflags that could apply to wasm.
+/// The config is in `.cargo/config.toml` (or `.cargo/config`) in the app
+/// root and each directory above it, and in the cargo home directory.
+fn configured_rustflags(app_root: &Path) -> (bool, bool) {
+    let cargo_home = std::env::var_os("CARGO_HOME")
+        .map(PathBuf::from)
+        .or_else(|| {
+            std::env::var_os("HOME")
+                .or_else(|| std::env::var_os("USERPROFILE"))
+                .map(|home| Path::new(&home).join(".cargo"))
+        });
+
+    let config_dirs = app_root
+        .ancestors()
+        .map(|dir| dir.join(".cargo"))
+        .chain(cargo_home);
+
+    let mut build_flags = std::env::var_os("CARGO_BUILD_RUSTFLAGS").is_some();
+    let mut target_flags =
+        std::env::var_os("CARGO_TARGET_WASM32_UNKNOWN_UNKNOWN_RUSTFLAGS").is_some();
+
+    for dir in config_dirs {
+        let config = ["config.toml", "config"]
+            .iter()
+            .find_map(|name| std::fs::read_to_string(dir.join(name)).ok())
+            .and_then(|config| toml::from_str::<toml::Value>(&config).ok());
+        let config = match config {
+            Some(config) => config,
+            None => continue,
+        };
+
+        if let Some(build) = config.get("build") {
+            build_flags |= build.get("rustflags").is_some();
+        }
+
+        // `cfg(...)` keys aren't evaluated, so any of them counts
+        if let Some(targets) = config.get("target").and_then(toml::Value::as_table) {
+            target_flags |= targets.iter().any(|(target, table)| {
+                (target == "wasm32-unknown-unknown" || target.starts_with("cfg("))
+                    && table.get("rustflags").is_some()
+            });
+        }
+    }
+
+    (build_flags, target_flags)
 }
 
 pub fn web_build(config: &WebBuildConfig) -> Result<PathBuf, JamjarError> {
+    web_build_with(config, &mut Reporter::Print)
+}
+
+pub fn web_build_with(
+    config: &WebBuildConfig,
+    reporter: &mut Reporter,
+) -> Result<PathBuf, JamjarError> {
     let cwd = match config.app_root {
         Some(ref path) => path.canonicalize().map_err(|e| {
             JamjarError::io(
@@ -356,14 +2990,7 @@ pub fn web_build(config: &WebBuildConfig) -> Result<PathBuf, JamjarError> {
             .map_err(|e| JamjarError::io(e, "Failed to get current directory."))?,
     };
 
-    let manifest_toml = {
-        let manifest_path = cwd.join("Cargo.toml");
-        std::fs::read_to_string(&manifest_path)
-            .map_err(|e| JamjarError::io(e, "Could not read Cargo.toml."))?
-    };
-
-    let manifest = toml::from_str::<CargoManifest>(&manifest_toml)
-        .map_err(|e| JamjarError::TomlError { cause: e })?;
+    let (manifest_path, manifest) = read_manifest(&cwd, config.manifest_path.as_deref())?;
 
     let app_name = config
         .app_name
@@ -371,44 +2998,82 @@ pub fn web_build(config: &WebBuildConfig) -> Result<PathBuf, JamjarError> {
         .unwrap_or_else(|| manifest.package.name.clone());
 
     let final_bin_name = config.bin_name.as_ref().unwrap_or(&manifest.package.name);
+    let mut written_files = vec![
+        format!("{}_bg.wasm", final_bin_name),
+        format!("{}.js", final_bin_name),
+    ];
+
+    check_clean_git(&cwd, config.require_clean_git, reporter)?;
+    check_lockfile(&manifest_path, config.locked)?;
+    report_toolchain(&cwd, config.toolchain.as_deref(), reporter)?;
+
+    let notices = match config.third_party_notices {
+        true => Some(notices::third_party_notices(
+            &NoticesBuild {
+                app_root: &cwd,
+                manifest_path: &manifest_path,
+                target: Some("wasm32-unknown-unknown"),
+                features: &config.features,
+                default_features: config.default_features,
+                all_features: config.all_features,
+                locked: config.locked,
+                offline: config.offline,
+                toolchain: config.toolchain.as_deref(),
+            },
+            &format!("{} {}", app_name, manifest.package.version),
+            config.strict_notices,
+            reporter,
+        )?),
+        false => None,
+    };
 
     std::fs::create_dir_all(&config.output_dir)
         .map_err(|e| JamjarError::io(e, "Failed to create output directory."))?;
+    let _lock = OutputLock::acquire(&config.output_dir, config.lock_timeout, reporter)?;
 
     let profile = if config.debug { "debug" } else { "release" };
-    println!("Compiling app for {}:", profile);
+    reporter.line(&format!("Compiling app for {}:", profile));
     {
         let mut cmd = Command::new("cargo");
-        cmd.current_dir(&cwd)
-            .arg("build")
-            .arg(if config.debug { "" } else { "--release" })
-            .arg("--target")
-            .arg("wasm32-unknown-unknown");
+        cmd.current_dir(&cwd).arg("build");
+        cmd.arg("--manifest-path").arg(&manifest_path);
+
+        if !config.debug {
+            cmd.arg("--release");
+        }
+
+        cmd.args(lock_args(config.locked, config.offline));
+        use_toolchain(&mut cmd, config.toolchain.as_deref());
+
+        cmd.arg("--target").arg("wasm32-unknown-unknown");
+
+        if config.wasm_compat == CompatLevel::Legacy {
+            add_legacy_rustflags(&mut cmd, &cwd);
+        }
 
         if let Some(bin_name) = &config.bin_name {
             cmd.arg("--bin");
             cmd.arg(bin_name);
         }
 
-        if !config.features.is_empty() {
-            cmd.arg("--features");
-            cmd.args(config.features.iter());
-        }
+        cmd.args(feature_args(
+            &config.features,
+            config.default_features,
+            config.all_features,
+        ));
 
         let output = cmd.output()?;
 
-        print!("{}", String::from_utf8_lossy(&output.stdout));
-        eprint!("{}", String::from_utf8_lossy(&output.stderr));
+        reporter.command_output(&output);
 
         if !output.status.success() {
             return Err(JamjarError::ExternalCommandError("cargo"));
         }
     }
 
-    println!("Running wasm-bindgen:");
+    reporter.line("Running wasm-bindgen:");
     {
-        let mut wasm_path = cwd.clone();
-        wasm_path.push("target");
+        let mut wasm_path = target_dir(&manifest_path);
         wasm_path.push("wasm32-unknown-unknown");
         wasm_path.push(profile);
         wasm_path.push(format!("{}.wasm", &final_bin_name));
@@ -420,69 +3085,354 @@ pub fn web_build(config: &WebBuildConfig) -> Result<PathBuf, JamjarError> {
             .arg(&config.output_dir)
             .arg("--web");
 
+        // Keeping the DWARF sections lets browser devtools map the wasm
+        // back to Rust source lines.
+        if config.debug {
+            cmd.arg("--debug").arg("--keep-debug");
+        }
+
+        if !config.names_section {
+            cmd.arg("--remove-name-section");
+        }
+
         let output = cmd.output()?;
 
-        print!("{}", String::from_utf8_lossy(&output.stdout));
-        eprint!("{}", String::from_utf8_lossy(&output.stderr));
+        reporter.command_output(&output);
 
         if !output.status.success() {
-            return Err(JamjarError::ExternalCommandError("cargo"));
+            return Err(JamjarError::ExternalCommandError("wasm-bindgen"));
+        }
+    }
+
+    if config.debug && config.wasm_compat == CompatLevel::Legacy {
+        reporter.line("Debug builds skip wasm-opt, so the standard library's newer wasm features are left in. Use a release build to test in older browsers.");
+    }
+
+    if !config.debug {
+        reporter.line("Running wasm-opt:");
+
+        let wasm_path = config
+            .output_dir
+            .join(format!("{}_bg.wasm", &final_bin_name));
+
+        let mut cmd = Command::new("wasm-opt");
+        cmd.current_dir(&cwd)
+            .arg("-Oz")
+            .arg(&wasm_path)
+            .arg("-o")
+            .arg(&wasm_path);
+
+        if config.names_section {
+            cmd.arg("--debuginfo");
+        }
+
+        // These run after -Oz, so it can't bring the features back
+        if config.wasm_compat == CompatLevel::Legacy {
+            cmd.args(LEGACY_WASM_OPT_PASSES);
+        }
+
+        match cmd.output() {
+            Ok(output) => {
+                reporter.command_output(&output);
+
+                if !output.status.success() {
+                    return Err(JamjarError::ExternalCommandError("wasm-opt"));
+                }
+            }
+            Err(e)
+                if e.kind() == std::io::ErrorKind::NotFound
+                    && config.wasm_compat == CompatLevel::Legacy =>
+            {
+                return Err(JamjarError::StringError(
+                    "`wasm_compat = \"legacy\"` needs wasm-opt, which was not found. Install binaryen to fix this.".to_owned(),
+                ));
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                reporter.line("  wasm-opt was not found, so the output is unoptimized. Install binaryen to fix this.");
+            }
+            Err(e) => return Err(JamjarError::io(e, "Failed to run wasm-opt.")),
         }
     }
 
-    println!("Creating index.html:");
     {
-        // index.html
         #[derive(Serialize)]
         struct IndexHtml<'a> {
             app_name: &'a str,
             bin_name: &'a str,
+            panic_hook: bool,
+            public_path: &'a str,
+            spirv_cross: bool,
         }
 
-        let no_spirv_template =
-            include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/templates/index.html"));
-        let spirv_template = include_str!(concat!(
-            env!("CARGO_MANIFEST_DIR"),
-            "/templates/index_spirv.html"
-        ));
-        let template = if config.bypass_spirv_cross {
-            no_spirv_template
-        } else {
-            spirv_template
-        };
-
         let context = IndexHtml {
             app_name: &app_name,
-            bin_name: &final_bin_name,
+            bin_name: final_bin_name,
+            panic_hook: config.debug,
+            public_path: &config.public_path,
+            spirv_cross: config.spirv_cross != SpirvCrossMode::Skip,
         };
 
-        let hb = Handlebars::new();
-        let html = hb
-            .render_template(&template, &context)
-            .map_err(|e| JamjarError::TemplateError { cause: e })?;
+        match &config.html_filename {
+            Some(html_filename) => {
+                reporter.line(&format!("Creating {}:", html_filename));
+
+                let no_spirv_template =
+                    include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/templates/index.html"));
+                let spirv_template = include_str!(concat!(
+                    env!("CARGO_MANIFEST_DIR"),
+                    "/templates/index_spirv.html"
+                ));
+                let (template_name, template) = match config.spirv_cross {
+                    SpirvCrossMode::Skip => ("index.html", no_spirv_template),
+                    _ => ("index_spirv.html", spirv_template),
+                };
+
+                let html = render_template(template_name, template, &context, true)?;
+
+                // Only this one file is written, so the output directory can
+                // be part of an existing site
+                std::fs::write(config.output_dir.join(html_filename), &html).map_err(|e| {
+                    JamjarError::io(e, &format!("Failed to write {}", html_filename))
+                })?;
+                written_files.push(html_filename.clone());
+            }
+            None => {
+                let template = include_str!(concat!(
+                    env!("CARGO_MANIFEST_DIR"),
+                    "/templates/embed_snippet.html"
+                ));
+                let snippet = render_template("embed_snippet.html", template, &context, true)?;
+
+                reporter.line("Skipping HTML. To start the app, add this to your page:");
+                for line in snippet.lines() {
+                    reporter.line(&format!("  {}", line));
+                }
+            }
+        }
+    }
+
+    const SPIRV_JS: &str = "spirv_cross_wrapper_glsl.js";
+    const SPIRV_WASM: &str = "spirv_cross_wrapper_glsl.wasm";
+
+    let spirv_files = match &config.spirv_cross {
+        SpirvCrossMode::Embedded => Some((
+            include_bytes!("../ext/spirv_cross/spirv_cross_wrapper_glsl.js").to_vec(),
+            include_bytes!("../ext/spirv_cross/spirv_cross_wrapper_glsl.wasm").to_vec(),
+        )),
+        SpirvCrossMode::FromPath(dir) => {
+            let read = |name: &str| {
+                std::fs::read(dir.join(name)).map_err(|e| {
+                    JamjarError::io(
+                        e,
+                        &format!("Could not read {} from '{}'.", name, dir.display()),
+                    )
+                })
+            };
+            Some((read(SPIRV_JS)?, read(SPIRV_WASM)?))
+        }
+        SpirvCrossMode::Skip => None,
+    };
+
+    if let Some((spirv_js, spirv_wasm)) = spirv_files {
+        reporter.line("Copying spirv_cross scripts:");
+
+        for (name, contents) in &[(SPIRV_JS, spirv_js), (SPIRV_WASM, spirv_wasm)] {
+            let written = write_if_changed(&config.output_dir.join(name), contents)
+                .map_err(|e| JamjarError::io(e, &format!("Failed to write {}.", name)))?;
+            if !written {
+                reporter.line(&format!("  {} is unchanged", name));
+            }
+            written_files.push(name.to_string());
+        }
+    }
+
+    if let Some(notices) = &notices {
+        write_if_changed(&config.output_dir.join(NOTICES_FILE), notices.as_bytes())
+            .map_err(|e| JamjarError::io(e, &format!("Failed to write {}.", NOTICES_FILE)))?;
+        written_files.push(NOTICES_FILE.to_owned());
+    }
+
+    if config.size_report {
+        let report = SizeReport::of_files(
+            &format!("{} (web)", app_name),
+            &config.output_dir,
+            &written_files,
+            reporter,
+        )?;
+        let report_path = config
+            .output_dir
+            .join(format!("{}_web.size.json", app_name));
+        write_size_report(report, &report_path, reporter)?;
+    }
 
-        let mut index_path = config.output_dir.clone();
-        index_path.push("index.html");
+    Ok(config.output_dir.clone())
+}
 
-        std::fs::write(&index_path, &html)
-            .map_err(|e| JamjarError::io(e, "Failed to write index.html"))?;
+/// Write the file unless it already has these contents, so that its
+/// modification time only changes when it does. Returns whether it wrote.
+fn write_if_changed(path: &Path, contents: &[u8]) -> Result<bool, IOError> {
+    if let Ok(existing) = std::fs::read(path) {
+        if existing == contents {
+            return Ok(false);
+        }
     }
 
-    let spirv_js = include_str!("../ext/spirv_cross/spirv_cross_wrapper_glsl.js");
-    let spirv_wasm = include_bytes!("../ext/spirv_cross/spirv_cross_wrapper_glsl.wasm");
+    std::fs::write(path, contents)?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    if !config.bypass_spirv_cross {
-        println!("Copying spirv_cross scripts:");
+    fn features(names: &[&str]) -> Vec<String> {
+        names.iter().map(|&name| name.to_owned()).collect()
+    }
 
-        let mut js_path = config.output_dir.clone();
-        js_path.push("spirv_cross_wrapper_glsl.js");
+    #[test]
+    fn default_features_add_no_flags() {
+        assert!(feature_args(&[], true, false).is_empty());
+    }
 
-        let mut wasm_path = config.output_dir.clone();
-        wasm_path.push("spirv_cross_wrapper_glsl.wasm");
+    #[test]
+    fn features_are_joined_into_one_argument() {
+        assert_eq!(
+            feature_args(&features(&["web-render", "dep/feature"]), true, false),
+            ["--features", "web-render,dep/feature"]
+        );
+    }
 
-        std::fs::write(&js_path, spirv_js)?;
-        std::fs::write(&wasm_path, spirv_wasm)?;
+    #[test]
+    fn default_and_all_features_can_be_toggled() {
+        assert_eq!(
+            feature_args(&features(&["audio"]), false, false),
+            ["--no-default-features", "--features", "audio"]
+        );
+        assert_eq!(feature_args(&[], true, true), ["--all-features"]);
     }
 
-    Ok(config.output_dir.clone())
+    /// An app root with a jamjar.toml, and a Cargo.toml in `game`.
+    fn app_root(jamjar_toml: &str, metadata: &str) -> tempfile::TempDir {
+        let root = tempfile::tempdir().unwrap();
+        std::fs::write(root.path().join(config::JAMJAR_TOML), jamjar_toml).unwrap();
+        std::fs::create_dir(root.path().join("game")).unwrap();
+        std::fs::write(
+            root.path().join("game/Cargo.toml"),
+            format!(
+                "[package]\nname = \"game\"\nversion = \"0.1.0\"\n\n[package.metadata.jamjar]\n{}",
+                metadata
+            ),
+        )
+        .unwrap();
+        root
+    }
+
+    #[test]
+    fn package_options_come_from_flags_then_jamjar_toml_then_metadata() {
+        let root = app_root(
+            r#"
+            [package]
+            manifest_path = "game"
+            app_name = "Toml"
+            bin_name = "toml"
+            "#,
+            r#"
+            app_name = "Metadata"
+            bin_name = "metadata"
+            bundle_id = "com.example.metadata"
+            "#,
+        );
+
+        let flags = PackageOptions {
+            app_name: Some("Flag".to_owned()),
+            ..Default::default()
+        };
+        let config = PackageConfig::resolve_from(
+            Some(root.path().to_owned()),
+            flags,
+            ConfigSource::Flag,
+            Platform::Linux,
+        )
+        .unwrap();
+
+        assert_eq!(config.app_name.as_deref(), Some("Flag"));
+        assert_eq!(config.bin_name.as_deref(), Some("toml"));
+        assert_eq!(config.bundle_id.as_deref(), Some("com.example.metadata"));
+        assert_eq!(config.format, OutputFormat::Zip);
+        assert!(!config.launch_helpers);
+    }
+
+    #[test]
+    fn web_options_come_from_flags_then_jamjar_toml_then_metadata() {
+        let root = app_root(
+            r#"
+            [web]
+            manifest_path = "game"
+            app_name = "Toml"
+            bin_name = "toml"
+            "#,
+            r#"
+            [package.metadata.jamjar.web]
+            app_name = "Metadata"
+            bin_name = "metadata"
+            public_path = "/metadata/"
+            "#,
+        );
+
+        let flags = WebBuildOptions {
+            app_name: Some("Flag".to_owned()),
+            ..Default::default()
+        };
+        let config =
+            WebBuildConfig::resolve_from(Some(root.path().to_owned()), flags, ConfigSource::Flag)
+                .unwrap();
+
+        assert_eq!(config.app_name.as_deref(), Some("Flag"));
+        assert_eq!(config.bin_name.as_deref(), Some("toml"));
+        assert_eq!(config.public_path, "/metadata/");
+        assert!(!config.debug);
+    }
+
+    #[test]
+    fn metadata_paths_are_relative_to_cargo_toml() {
+        let root = app_root(
+            "[package]\nmanifest_path = \"game\"",
+            "icon_path = \"icon.png\"\nresources = [\"data\"]\nchangelog = \"CHANGES.md\"",
+        );
+
+        let config = PackageConfig::resolve_from(
+            Some(root.path().to_owned()),
+            PackageOptions::default(),
+            ConfigSource::Flag,
+            Platform::Linux,
+        )
+        .unwrap();
+
+        let game = root.path().join("game");
+        assert_eq!(config.icon_path, Some(game.join("icon.png")));
+        assert_eq!(config.changelog, Some(game.join("CHANGES.md")));
+        assert_eq!(config.resources, [game.join("data")]);
+    }
+
+    #[test]
+    fn bundle_ids_keep_only_ascii_letters_and_digits() {
+        assert_eq!(default_bundle_id("My Game"), "com.jamjar.mygame");
+        assert_eq!(default_bundle_id("Café-Racer 2"), "com.jamjar.cafracer2");
+        assert_eq!(default_bundle_id("ゲーム"), "com.jamjar.app");
+        assert_eq!(default_bundle_id(""), "com.jamjar.app");
+    }
+
+    #[test]
+    fn bundle_ids_never_start_with_a_digit() {
+        assert_eq!(default_bundle_id("2048"), "com.jamjar.app2048");
+        assert_eq!(default_bundle_id("3D Tetris"), "com.jamjar.app3dtetris");
+        assert_eq!(default_bundle_id("٣ Kings"), "com.jamjar.kings");
+    }
+
+    #[test]
+    fn default_bundle_ids_are_valid() {
+        for name in &["My Game", "Café-Racer 2", "ゲーム", "2048", ""] {
+            assert!(valid_bundle_id(&default_bundle_id(name)), "{}", name);
+        }
+    }
 }
@@ -0,0 +1,141 @@
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::{JamjarError, Reporter};
+
+pub const LOCK_FILE: &str = ".jamjar.lock";
+
+/// How long to wait for another run, unless `lock_timeout` is set.
+pub const DEFAULT_LOCK_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// How long to wait between attempts to take a held lock.
+const RETRY_PERIOD: Duration = Duration::from_millis(250);
+
+/// An advisory lock on an output directory, so that jamjar runs writing to
+/// the same place take turns. It's released when dropped.
+///
+/// The lock file holds the pid and start time of the run holding it. A lock
+/// left behind by a run that died is taken over.
+#[derive(Debug)]
+pub struct OutputLock {
+    path: PathBuf,
+}
+
+impl OutputLock {
+    /// Lock `dir`, waiting up to `timeout` for any other run holding it to
+    /// finish. The directory must already exist.
+    pub fn acquire(
+        dir: &Path,
+        timeout: Duration,
+        reporter: &mut Reporter,
+    ) -> Result<Self, JamjarError> {
+        let path = dir.join(LOCK_FILE);
+        let start = Instant::now();
+        let mut waiting = false;
+
+        loop {
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(mut file) => {
+                    let started = SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .map(|duration| duration.as_secs())
+                        .unwrap_or(0);
+                    write!(file, "pid={}\nstarted={}\n", std::process::id(), started)
+                        .map_err(|e| JamjarError::io(e, "Failed to write lock file."))?;
+
+                    return Ok(OutputLock { path });
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => (),
+                Err(e) => {
+                    return Err(JamjarError::io(
+                        e,
+                        &format!("Failed to create '{}'.", path.display()),
+                    ))
+                }
+            }
+
+            // The holder may have finished since the open failed
+            let holder = match std::fs::read_to_string(&path) {
+                Ok(contents) => contents,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(JamjarError::io(e, "Failed to read lock file.")),
+            };
+            let pid = holder
+                .lines()
+                .find_map(|line| line.strip_prefix("pid="))
+                .and_then(|pid| pid.trim().parse::<u32>().ok());
+
+            // A lock file that's still being written won't have a pid yet,
+            // so only one with a pid can be known to be stale
+            if let Some(pid) = pid {
+                if !process_alive(pid) {
+                    // Another run may have replaced it in the meantime
+                    if std::fs::read_to_string(&path).ok().as_deref() == Some(holder.as_str()) {
+                        reporter.line(&format!(
+                            "Removing stale lock left by process {} in {}",
+                            pid,
+                            dir.display()
+                        ));
+                        let _ = std::fs::remove_file(&path);
+                    }
+                    continue;
+                }
+            }
+
+            if start.elapsed() >= timeout {
+                return Err(JamjarError::LockTimeout {
+                    path,
+                    holder: holder.trim().replace('\n', ", "),
+                });
+            }
+
+            if !waiting {
+                waiting = true;
+                reporter.line(&format!(
+                    "Waiting for another jamjar run to finish with {}",
+                    dir.display()
+                ));
+            }
+
+            std::thread::sleep(RETRY_PERIOD);
+        }
+    }
+}
+
+impl Drop for OutputLock {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Whether a process with this pid is running. If there's no way to tell,
+/// it's assumed to be, so a live lock is never broken.
+fn process_alive(pid: u32) -> bool {
+    if pid == std::process::id() {
+        return true;
+    }
+
+    if cfg!(target_os = "linux") {
+        return Path::new("/proc").join(pid.to_string()).exists();
+    }
+
+    if cfg!(windows) {
+        return Command::new("tasklist")
+            .args(["/FI", &format!("PID eq {}", pid), "/NH"])
+            .output()
+            .map(|output| String::from_utf8_lossy(&output.stdout).contains(&pid.to_string()))
+            .unwrap_or(true);
+    }
+
+    // `kill -0` only checks the process exists, without sending a signal
+    Command::new("kill")
+        .arg("-0")
+        .arg(pid.to_string())
+        .stderr(Stdio::null())
+        .status()
+        .map(|status| status.success())
+        .unwrap_or(true)
+}
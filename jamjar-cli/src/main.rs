@@ -2,7 +2,10 @@ use std::path::PathBuf;
 
 use structopt::StructOpt;
 
-use jamjar_cli::{PackageConfig, WebBuildConfig};
+use jamjar_cli::{
+    ArtifactConfig, BuildTask, CompatLevel, OutputFormat, OverwritePolicy, PackageConfig,
+    PackageOptions, Platform, ResourceCheck, SpirvCrossMode, WebBuildConfig, WebBuildOptions,
+};
 
 /// Package the app into an archive for distribution.
 #[derive(StructOpt)]
@@ -15,10 +18,14 @@ struct PackageCmd {
     #[structopt(long = "name", short = "n")]
     app_name: Option<String>,
 
-    /// The directory to put the packaged archive into.
-    #[structopt(long = "output_dir", short = "o", default_value = "./target/jamjar")]
+    /// The name of the binary to package, for crates with more than one. Defaults to the one named after the package.
+    #[structopt(long = "bin_name", short = "b")]
+    bin_name: Option<String>,
+
+    /// The directory to put the packaged archive into. Defaults to `./target/jamjar`.
+    #[structopt(long = "output_dir", short = "o")]
     #[structopt(parse(from_os_str))]
-    output_dir: PathBuf,
+    output_dir: Option<PathBuf>,
 
     /// The icon image to use for the app. Defaults to `icon.png` in the app root.
     #[structopt(long = "icon_path", short = "i")]
@@ -28,6 +35,123 @@ struct PackageCmd {
     /// Space-separated list of features to activate.
     #[structopt(long = "features")]
     features: Vec<String>,
+
+    /// Don't activate the `default` feature.
+    #[structopt(long)]
+    no_default_features: bool,
+
+    /// Activate all available features.
+    #[structopt(long)]
+    all_features: bool,
+
+    /// The output format: `zip`, `appimage` or `tar.gz` (Linux only), or `dmg` (macOS only).
+    /// Defaults to `zip`.
+    #[structopt(long = "format", short = "f")]
+    format: Option<OutputFormat>,
+
+    /// Shorthand for `--format appimage`.
+    #[structopt(long, conflicts_with = "format")]
+    appimage: bool,
+
+    /// Make a tar.gz instead of failing if `appimagetool` isn't installed.
+    #[structopt(long)]
+    appimage_fallback: bool,
+
+    /// Pass the version, git hash, and build time to the app, for use with `jamjar::build_info!()`.
+    #[structopt(long)]
+    build_info: bool,
+
+    /// Include a README, and on Linux a `run.sh` script, alongside the app.
+    #[structopt(long)]
+    launch_helpers: bool,
+
+    /// Write `latest.json` and `releases.json` describing the package to the output directory.
+    #[structopt(long)]
+    release_feed: bool,
+
+    /// Print the largest files in the package, and write a breakdown next to it.
+    #[structopt(long)]
+    size_report: bool,
+
+    /// A markdown changelog to copy the current version's section from, into `CHANGES.txt`.
+    #[structopt(long = "changelog")]
+    #[structopt(parse(from_os_str))]
+    changelog: Option<PathBuf>,
+
+    /// Pass `--locked` to cargo, failing instead of updating Cargo.lock.
+    #[structopt(long)]
+    locked: bool,
+
+    /// Pass `--offline` to cargo, failing instead of using the network.
+    #[structopt(long)]
+    offline: bool,
+
+    /// The Rust toolchain to build with, like `1.74.0` or `nightly-2024-01-01`. Defaults to the app's `rust-toolchain.toml`, if it has one.
+    #[structopt(long)]
+    toolchain: Option<String>,
+
+    /// The target triple to cross-compile for, like `x86_64-pc-windows-gnu`. The target must be installed with `rustup target add`.
+    #[structopt(long)]
+    target: Option<String>,
+
+    /// The app's Cargo.toml, relative to the app root. Defaults to the one in the app root.
+    #[structopt(long = "manifest-path")]
+    #[structopt(parse(from_os_str))]
+    manifest_path: Option<PathBuf>,
+
+    /// What to do if the package already exists: `error`, `rename`, or `overwrite`. Defaults to `overwrite`.
+    #[structopt(long = "on-existing")]
+    on_existing: Option<OverwritePolicy>,
+
+    /// Leave files matching this pattern out of zip packages, as well as the defaults. Can be repeated.
+    #[structopt(long = "zip-exclude")]
+    zip_exclude: Vec<String>,
+
+    /// Only put files matching this pattern in zip packages. Can be repeated.
+    #[structopt(long = "zip-include-only")]
+    zip_include_only: Vec<String>,
+
+    /// Don't leave `.pdb` debug symbols out of zip packages.
+    #[structopt(long)]
+    keep_symbols: bool,
+
+    /// A file or directory to ship with the app, relative to the app root. Can be repeated.
+    #[structopt(long = "resource")]
+    #[structopt(parse(from_os_str))]
+    resources: Vec<PathBuf>,
+
+    /// Write `THIRD-PARTY-NOTICES.txt`, listing every crate in the app and its license.
+    #[structopt(long)]
+    third_party_notices: bool,
+
+    /// Fail if a crate's license needs reviewing, instead of listing it in the notices.
+    #[structopt(long)]
+    strict_notices: bool,
+
+    /// The macOS bundle identifier, like `com.example.mygame`. Defaults to one made from the app name.
+    #[structopt(long = "bundle-id")]
+    bundle_id: Option<String>,
+
+    /// Sign the macOS app with this identity from the keychain, or pass `-` to sign it ad-hoc.
+    #[structopt(long = "sign-identity")]
+    sign_identity: Option<String>,
+
+    /// An entitlements plist to sign the macOS app with. Only used with `--sign-identity`.
+    #[structopt(long)]
+    #[structopt(parse(from_os_str))]
+    entitlements: Option<PathBuf>,
+
+    /// Notarize the macOS app with this keychain profile, as saved by `xcrun notarytool store-credentials`.
+    #[structopt(long)]
+    notarize: Option<String>,
+
+    /// Build even if `require_clean_git` is set in jamjar.toml and there are uncommitted changes.
+    #[structopt(long)]
+    allow_dirty: bool,
+
+    /// Print the configuration, with where each value came from, instead of building.
+    #[structopt(long)]
+    print_config: bool,
 }
 
 /// Create a web build of the app for testing or distrubution.
@@ -45,26 +169,130 @@ struct WebBuildCmd {
     #[structopt(long = "bin_name", short = "b")]
     bin_name: Option<String>,
 
-    /// The directory to put the packaged archive into.
-    #[structopt(
-        long = "output_dir",
-        short = "o",
-        default_value = "./target/jamjar_web"
-    )]
+    /// The directory to put the packaged archive into. Defaults to `./target/jamjar_web`.
+    #[structopt(long = "output_dir", short = "o")]
     #[structopt(parse(from_os_str))]
-    output_dir: PathBuf,
+    output_dir: Option<PathBuf>,
 
     /// Space-separated list of features to activate.
     #[structopt(long = "features")]
     features: Vec<String>,
 
+    /// Don't activate the `default` feature.
+    #[structopt(long)]
+    no_default_features: bool,
+
+    /// Activate all available features.
+    #[structopt(long)]
+    all_features: bool,
+
     /// Use this flag to skip packaging spirv_cross scripts.
     #[structopt(long)]
     bypass_spirv_cross: bool,
 
-    /// Build with the debug profile instead of release.
+    /// Where to get the spirv_cross scripts: `embedded`, `skip`, or a directory containing them.
+    #[structopt(long)]
+    spirv_cross: Option<SpirvCrossMode>,
+
+    /// Build with the debug profile instead of release, keeping debug info for browser devtools.
     #[structopt(long)]
     debug: bool,
+
+    /// Keep function names in release builds, for readable stack traces.
+    #[structopt(long)]
+    names_section: bool,
+
+    /// Which browsers to support: `modern` (the default), or `legacy` to also run in ones without post-MVP WebAssembly features.
+    #[structopt(long = "wasm-compat")]
+    wasm_compat: Option<CompatLevel>,
+
+    /// Write `THIRD-PARTY-NOTICES.txt`, listing every crate in the app and its license.
+    #[structopt(long)]
+    third_party_notices: bool,
+
+    /// Fail if a crate's license needs reviewing, instead of listing it in the notices.
+    #[structopt(long)]
+    strict_notices: bool,
+
+    /// Don't write an HTML page, and print a snippet to add to an existing one instead.
+    #[structopt(long)]
+    no_html: bool,
+
+    /// The name of the HTML page to write. Defaults to `index.html`.
+    #[structopt(long = "html_filename")]
+    html_filename: Option<String>,
+
+    /// The path or URL the page loads the JS and wasm files from. Defaults to `./`.
+    #[structopt(long = "public_path")]
+    public_path: Option<String>,
+
+    /// Print the size of each output file, and write a breakdown to the output directory.
+    #[structopt(long)]
+    size_report: bool,
+
+    /// Pass `--locked` to cargo, failing instead of updating Cargo.lock.
+    #[structopt(long)]
+    locked: bool,
+
+    /// Pass `--offline` to cargo, failing instead of using the network.
+    #[structopt(long)]
+    offline: bool,
+
+    /// The Rust toolchain to build with, like `1.74.0` or `nightly-2024-01-01`. Defaults to the app's `rust-toolchain.toml`, if it has one.
+    #[structopt(long)]
+    toolchain: Option<String>,
+
+    /// The app's Cargo.toml, relative to the app root. Defaults to the one in the app root.
+    #[structopt(long = "manifest-path")]
+    #[structopt(parse(from_os_str))]
+    manifest_path: Option<PathBuf>,
+
+    /// Build even if `require_clean_git` is set in jamjar.toml and there are uncommitted changes.
+    #[structopt(long)]
+    allow_dirty: bool,
+
+    /// Print the configuration, with where each value came from, instead of building.
+    #[structopt(long)]
+    print_config: bool,
+}
+
+/// Run every build listed in the `[[dist]]` entries of jamjar.toml.
+#[derive(StructOpt)]
+struct DistCmd {
+    /// The path to the root of your app. Defaults to current directory.
+    #[structopt(parse(from_os_str))]
+    app_root: Option<PathBuf>,
+
+    /// The maximum number of builds to run at once. Defaults to 2.
+    #[structopt(long = "jobs", short = "j")]
+    jobs: Option<usize>,
+
+    /// Build even if `require_clean_git` is set in jamjar.toml and there are uncommitted changes.
+    #[structopt(long)]
+    allow_dirty: bool,
+
+    /// Print the configuration, with where each value came from, instead of building.
+    #[structopt(long)]
+    print_config: bool,
+}
+
+/// Build one of the `[extra_artifacts]` from jamjar.toml.
+#[derive(StructOpt)]
+struct ArtifactCmd {
+    /// The name of the artifact.
+    name: String,
+
+    /// The path to the root of your app. Defaults to current directory.
+    #[structopt(parse(from_os_str))]
+    app_root: Option<PathBuf>,
+}
+
+/// Check that the resource directories listed in jamjar.toml match their keys.
+#[derive(StructOpt)]
+struct CheckCmd {
+    /// The path to the root of your app. Defaults to current directory.
+    #[structopt(parse(from_os_str))]
+    app_root: Option<PathBuf>,
 }
 
 /// A simple, opinionated tool for packaging Rust apps (mostly game jam games) for different platforms
@@ -72,6 +300,23 @@ struct WebBuildCmd {
 enum JamjarCommand {
     Package(PackageCmd),
     Web(WebBuildCmd),
+    Dist(DistCmd),
+    Artifact(ArtifactCmd),
+    Check(CheckCmd),
+}
+
+fn some_list<T>(items: Vec<T>) -> Option<Vec<T>> {
+    match items.is_empty() {
+        true => None,
+        false => Some(items),
+    }
+}
+
+fn some_flag(flag: bool) -> Option<bool> {
+    match flag {
+        true => Some(true),
+        false => None,
+    }
 }
 
 fn main() {
@@ -79,6 +324,9 @@ fn main() {
     match cmd {
         JamjarCommand::Package(build_cmd) => package(build_cmd),
         JamjarCommand::Web(web_build_cmd) => web_build(web_build_cmd),
+        JamjarCommand::Dist(dist_cmd) => dist(dist_cmd),
+        JamjarCommand::Artifact(artifact_cmd) => artifact(artifact_cmd),
+        JamjarCommand::Check(check_cmd) => check(check_cmd),
     }
 }
 
@@ -86,28 +334,134 @@ fn package(build_cmd: PackageCmd) {
     let PackageCmd {
         app_root,
         app_name,
+        bin_name,
         output_dir,
         icon_path,
         features,
+        no_default_features,
+        all_features,
+        format,
+        appimage,
+        appimage_fallback,
+        build_info,
+        launch_helpers,
+        release_feed,
+        size_report,
+        changelog,
+        locked,
+        offline,
+        toolchain,
+        target,
+        manifest_path,
+        on_existing,
+        zip_exclude,
+        zip_include_only,
+        keep_symbols,
+        resources,
+        third_party_notices,
+        strict_notices,
+        bundle_id,
+        sign_identity,
+        entitlements,
+        notarize,
+        allow_dirty,
+        print_config,
     } = build_cmd;
 
-    let config = PackageConfig {
-        app_root,
+    let options = PackageOptions {
         app_name,
+        bin_name,
         output_dir,
         icon_path,
-        features,
+        features: some_list(features),
+        default_features: some_flag(no_default_features).map(|_| false),
+        all_features: some_flag(all_features),
+        format: format.or_else(|| some_flag(appimage).map(|_| OutputFormat::AppImage)),
+        appimage_fallback: some_flag(appimage_fallback),
+        build_info: some_flag(build_info),
+        launch_helpers: some_flag(launch_helpers),
+        controls: None,
+        pre_build: None,
+        post_build: None,
+        smoke_test: None,
+        release_feed: some_flag(release_feed),
+        size_report: some_flag(size_report),
+        icon_sizes: None,
+        changelog,
+        require_changelog: None,
+        locked: some_flag(locked),
+        offline: some_flag(offline),
+        toolchain,
+        target,
+        manifest_path,
+        on_existing,
+        zip_exclude: some_list(zip_exclude),
+        zip_include_only: some_list(zip_include_only),
+        keep_symbols: some_flag(keep_symbols),
+        resources: some_list(resources),
+        third_party_notices: some_flag(third_party_notices),
+        strict_notices: some_flag(strict_notices),
+        bundle_id,
+        sign_identity,
+        entitlements,
+        notarize,
+    };
+
+    let mut config = match PackageConfig::resolve(app_root.clone(), options, Platform::host()) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Packaging failed: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    if allow_dirty {
+        config.require_clean_git = false;
+        config.resolved.allow_dirty();
+    }
+
+    if print_config {
+        print!("{}", config.resolved);
+        return;
+    }
+
+    let artifacts = match ArtifactConfig::resolve_all(app_root) {
+        Ok(artifacts) => artifacts,
+        Err(e) => {
+            eprintln!("Packaging failed: {}", e);
+            std::process::exit(1);
+        }
     };
 
     match jamjar_cli::package_app(&config) {
         Ok(path) => {
             println!("Release created at: {}", path.display());
+
+            let root = config
+                .app_root
+                .clone()
+                .unwrap_or_else(|| PathBuf::from("."));
+            if jamjar_cli::git_dirty(&root) == Some(true) {
+                println!("Note: it was built from a git working tree with uncommitted changes.");
+            }
         }
         Err(e) => {
             eprintln!("Packaging failed: {}", e);
             std::process::exit(1);
         }
     }
+
+    for artifact in &artifacts {
+        match jamjar_cli::build_artifact(artifact) {
+            Ok(path) => {
+                println!("{} created at: {}", artifact.name, path.display());
+            }
+            Err(e) => {
+                eprintln!("Packaging {} failed: {}", artifact.name, e);
+                std::process::exit(1);
+            }
+        }
+    }
 }
 
 fn web_build(web_build_cmd: WebBuildCmd) {
@@ -117,20 +471,69 @@ fn web_build(web_build_cmd: WebBuildCmd) {
         bin_name,
         output_dir,
         features,
+        no_default_features,
+        all_features,
         bypass_spirv_cross,
+        spirv_cross,
         debug,
+        names_section,
+        wasm_compat,
+        third_party_notices,
+        strict_notices,
+        no_html,
+        html_filename,
+        public_path,
+        size_report,
+        locked,
+        offline,
+        toolchain,
+        manifest_path,
+        allow_dirty,
+        print_config,
     } = web_build_cmd;
 
-    let config = WebBuildConfig {
-        app_root,
+    let options = WebBuildOptions {
         app_name,
         bin_name,
         output_dir,
-        features,
-        bypass_spirv_cross,
-        debug,
+        features: some_list(features),
+        default_features: some_flag(no_default_features).map(|_| false),
+        all_features: some_flag(all_features),
+        bypass_spirv_cross: some_flag(bypass_spirv_cross),
+        spirv_cross,
+        debug: some_flag(debug),
+        names_section: some_flag(names_section),
+        emit_html: some_flag(no_html).map(|_| false),
+        html_filename,
+        public_path,
+        size_report: some_flag(size_report),
+        locked: some_flag(locked),
+        offline: some_flag(offline),
+        toolchain,
+        manifest_path,
+        wasm_compat,
+        third_party_notices: some_flag(third_party_notices),
+        strict_notices: some_flag(strict_notices),
+    };
+
+    let mut config = match WebBuildConfig::resolve(app_root, options) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Packaging failed: {}", e);
+            std::process::exit(1);
+        }
     };
 
+    if allow_dirty {
+        config.require_clean_git = false;
+        config.resolved.allow_dirty();
+    }
+
+    if print_config {
+        print!("{}", config.resolved);
+        return;
+    }
+
     match jamjar_cli::web_build(&config) {
         Ok(path) => {
             println!("Built for web. Host here to test: {}", path.display());
@@ -141,3 +544,114 @@ fn web_build(web_build_cmd: WebBuildCmd) {
         }
     }
 }
+
+fn dist(dist_cmd: DistCmd) {
+    let DistCmd {
+        app_root,
+        jobs,
+        allow_dirty,
+        print_config,
+    } = dist_cmd;
+
+    let mut tasks = match BuildTask::resolve_dist(app_root) {
+        Ok(tasks) => tasks,
+        Err(e) => {
+            eprintln!("Packaging failed: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    if allow_dirty {
+        for task in &mut tasks {
+            task.config.allow_dirty();
+        }
+    }
+
+    if print_config {
+        for (i, task) in tasks.iter().enumerate() {
+            if let Some(resolved) = task.config.resolved() {
+                if i > 0 {
+                    println!();
+                }
+                println!("# {}", task.name);
+                print!("{}", resolved);
+            }
+        }
+        return;
+    }
+
+    let names = tasks
+        .iter()
+        .map(|task| task.name.clone())
+        .collect::<Vec<_>>();
+    let results = jamjar_cli::package_all(tasks, jobs.unwrap_or(2));
+
+    println!("\nSummary:");
+    let mut failed = false;
+    for (name, result) in names.iter().zip(results) {
+        match result {
+            Ok(report) => println!(
+                "  {}: {} ({:.1}s){}",
+                name,
+                report.output_path.display(),
+                report.duration.as_secs_f32(),
+                match report.dirty {
+                    Some(true) => " [dirty]",
+                    _ => "",
+                }
+            ),
+            Err(e) => {
+                failed = true;
+                println!("  {}: failed: {}", name, e);
+            }
+        }
+    }
+
+    if failed {
+        std::process::exit(1);
+    }
+}
+
+fn artifact(artifact_cmd: ArtifactCmd) {
+    let ArtifactCmd { name, app_root } = artifact_cmd;
+
+    let config = match ArtifactConfig::resolve(app_root, &name) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Packaging failed: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    match jamjar_cli::build_artifact(&config) {
+        Ok(path) => {
+            println!("{} created at: {}", name, path.display());
+        }
+        Err(e) => {
+            eprintln!("Packaging failed: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn check(check_cmd: CheckCmd) {
+    let CheckCmd { app_root } = check_cmd;
+
+    let checks = match ResourceCheck::resolve_all(app_root) {
+        Ok(checks) => checks,
+        Err(e) => {
+            eprintln!("Check failed: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    if checks.is_empty() {
+        println!("No [[resources]] to check in jamjar.toml.");
+        return;
+    }
+
+    if let Err(e) = jamjar_cli::check_resources(&checks) {
+        eprintln!("Check failed: {}", e);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,461 @@
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::toolchain::host_target;
+use crate::{
+    feature_args, lock_args, lockfile_hash, target_dir, use_toolchain, JamjarError, Reporter,
+};
+
+pub const NOTICES_FILE: &str = "THIRD-PARTY-NOTICES.txt";
+
+/// Which build of the app the notices are for, since the dependencies
+/// change with the target and features.
+pub(crate) struct NoticesBuild<'a> {
+    pub app_root: &'a Path,
+    pub manifest_path: &'a Path,
+    /// The target triple, or `None` for the host.
+    pub target: Option<&'a str>,
+    pub features: &'a [String],
+    pub default_features: bool,
+    pub all_features: bool,
+    pub locked: bool,
+    pub offline: bool,
+    pub toolchain: Option<&'a str>,
+}
+
+/// A crate that ships in the app.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CrateNotice {
+    name: String,
+    version: String,
+    /// The SPDX license expression, with any old-style `/` turned into
+    /// `OR`.
+    license: Option<String>,
+    /// Why the license needs checking by hand, if it does.
+    review: Option<String>,
+    /// The name and contents of each license file in the crate's source.
+    texts: Vec<(String, String)>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Metadata {
+    packages: Vec<MetadataPackage>,
+    workspace_members: Vec<String>,
+    resolve: Option<MetadataResolve>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MetadataPackage {
+    id: String,
+    name: String,
+    version: String,
+    license: Option<String>,
+    license_file: Option<PathBuf>,
+    manifest_path: PathBuf,
+    targets: Vec<MetadataTarget>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MetadataTarget {
+    kind: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MetadataResolve {
+    root: Option<String>,
+    nodes: Vec<MetadataNode>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MetadataNode {
+    id: String,
+    deps: Vec<MetadataDep>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MetadataDep {
+    pkg: String,
+    dep_kinds: Vec<MetadataDepKind>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MetadataDepKind {
+    kind: Option<String>,
+}
+
+/// The contents of `THIRD-PARTY-NOTICES.txt` for the build: every crate
+/// compiled into the app, grouped by license, then their license texts.
+///
+/// Crates without a usable license field are listed for review, which
+/// fails instead if `strict`. The crates are cached by Cargo.lock, so this
+/// only runs `cargo metadata` when the dependencies or build change.
+pub(crate) fn third_party_notices(
+    build: &NoticesBuild,
+    title: &str,
+    strict: bool,
+    reporter: &mut Reporter,
+) -> Result<String, JamjarError> {
+    let target = match build.target {
+        Some(target) => target.to_owned(),
+        None => host_target(build.app_root, build.toolchain)?,
+    };
+
+    let cache_path = || {
+        cache_key(build, &target).map(|key| {
+            target_dir(build.manifest_path)
+                .join("jamjar/notices")
+                .join(format!("{}.json", key))
+        })
+    };
+
+    let cached = cache_path()
+        .and_then(|path| std::fs::read(path).ok())
+        .and_then(|json| serde_json::from_slice::<Vec<CrateNotice>>(&json).ok());
+
+    let crates = match cached {
+        Some(crates) => {
+            reporter.line("Using cached third-party licenses");
+            crates
+        }
+        None => {
+            reporter.line("Collecting third-party licenses");
+            let crates = collect_crates(build, &target)?;
+
+            // cargo may have just updated Cargo.lock, so the key is worked
+            // out again for what was actually collected
+            if let Some(path) = &cache_path() {
+                // The cache only saves time, so failing to write it is fine
+                let _ = std::fs::create_dir_all(path.parent().unwrap())
+                    .and_then(|_| std::fs::write(path, serde_json::to_vec(&crates)?));
+            }
+            crates
+        }
+    };
+
+    let needs_review = crates
+        .iter()
+        .filter_map(|krate| {
+            krate
+                .review
+                .as_ref()
+                .map(|reason| format!("{} {}: {}", krate.name, krate.version, reason))
+        })
+        .collect::<Vec<_>>();
+
+    if !needs_review.is_empty() {
+        if strict {
+            return Err(JamjarError::StringError(format!(
+                "{} crates need their licenses reviewed, and `strict_notices` is set:\n  {}",
+                needs_review.len(),
+                needs_review.join("\n  ")
+            )));
+        }
+
+        reporter.line(&format!(
+            "Warning: {} crates need their licenses reviewed:",
+            needs_review.len()
+        ));
+        for line in &needs_review {
+            reporter.line(&format!("  {}", line));
+        }
+    }
+
+    reporter.line(&format!(
+        "  {} third-party crates listed in {}",
+        crates.len(),
+        NOTICES_FILE
+    ));
+
+    Ok(render_notices(title, &crates, &needs_review))
+}
+
+/// A hash of Cargo.lock and everything else that changes which crates are
+/// built, or `None` if there's no Cargo.lock to go by. Cargo.toml is
+/// included, since Cargo.lock isn't updated for a new dependency until
+/// cargo next runs.
+fn cache_key(build: &NoticesBuild, target: &str) -> Option<String> {
+    let manifest_dir = build.manifest_path.parent()?;
+    let mut hasher = Sha256::new();
+    hasher.update(lockfile_hash(manifest_dir)?);
+    hasher.update(std::fs::read(build.manifest_path).ok()?);
+    hasher.update(target);
+    for arg in feature_args(build.features, build.default_features, build.all_features) {
+        hasher.update(b"\0");
+        hasher.update(arg);
+    }
+    Some(
+        hasher
+            .finalize()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect(),
+    )
+}
+
+fn collect_crates(build: &NoticesBuild, target: &str) -> Result<Vec<CrateNotice>, JamjarError> {
+    let mut cmd = Command::new("cargo");
+    cmd.current_dir(build.app_root)
+        .arg("metadata")
+        .arg("--format-version")
+        .arg("1")
+        .arg("--manifest-path")
+        .arg(build.manifest_path)
+        .arg("--filter-platform")
+        .arg(target);
+    cmd.args(lock_args(build.locked, build.offline));
+    cmd.args(feature_args(
+        build.features,
+        build.default_features,
+        build.all_features,
+    ));
+    use_toolchain(&mut cmd, build.toolchain);
+
+    let output = cmd
+        .output()
+        .map_err(|e| JamjarError::io(e, "Failed to run cargo metadata."))?;
+
+    if !output.status.success() {
+        return Err(JamjarError::StringError(format!(
+            "`cargo metadata` failed:\n{}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    let metadata: Metadata = serde_json::from_slice(&output.stdout).map_err(|e| {
+        JamjarError::StringError(format!("Could not read `cargo metadata` output: {}", e))
+    })?;
+
+    let packages = metadata
+        .packages
+        .iter()
+        .map(|package| (package.id.as_str(), package))
+        .collect::<HashMap<_, _>>();
+    let resolve = metadata.resolve.as_ref().ok_or_else(|| {
+        JamjarError::StringError("`cargo metadata` didn't resolve the dependencies".to_owned())
+    })?;
+
+    let manifest_path = build.manifest_path.canonicalize()?;
+    let root = match &resolve.root {
+        Some(root) => root.as_str(),
+        None => metadata
+            .packages
+            .iter()
+            .find(|package| {
+                package.manifest_path.canonicalize().ok() == Some(manifest_path.clone())
+            })
+            .map(|package| package.id.as_str())
+            .ok_or_else(|| {
+                JamjarError::StringError(format!(
+                    "`cargo metadata` didn't list the app at '{}'",
+                    build.manifest_path.display()
+                ))
+            })?,
+    };
+
+    let nodes = resolve
+        .nodes
+        .iter()
+        .map(|node| (node.id.as_str(), node))
+        .collect::<HashMap<_, _>>();
+
+    // Only normal dependencies end up in the binary. Build scripts and
+    // proc macros run at compile time, so neither do theirs.
+    let mut shipped = HashSet::new();
+    let mut queue = VecDeque::from(vec![root]);
+    while let Some(id) = queue.pop_front() {
+        let node = match nodes.get(id) {
+            Some(node) => node,
+            None => continue,
+        };
+        for dep in &node.deps {
+            let normal = dep.dep_kinds.iter().any(|kind| kind.kind.is_none());
+            let proc_macro = match packages.get(dep.pkg.as_str()) {
+                Some(package) => package
+                    .targets
+                    .iter()
+                    .any(|target| target.kind.iter().any(|kind| kind == "proc-macro")),
+                None => false,
+            };
+            if normal && !proc_macro && shipped.insert(dep.pkg.as_str()) {
+                queue.push_back(&dep.pkg);
+            }
+        }
+    }
+
+    let workspace = metadata
+        .workspace_members
+        .iter()
+        .map(String::as_str)
+        .collect::<HashSet<_>>();
+
+    let mut crates = shipped
+        .into_iter()
+        .filter(|id| !workspace.contains(id))
+        .filter_map(|id| packages.get(id))
+        .map(|package| crate_notice(package))
+        .collect::<Vec<_>>();
+    crates.sort_by(|a, b| (&a.name, &a.version).cmp(&(&b.name, &b.version)));
+
+    Ok(crates)
+}
+
+fn crate_notice(package: &MetadataPackage) -> CrateNotice {
+    let license = package
+        .license
+        .as_deref()
+        .map(|license| license.replace('/', " OR "));
+
+    let review = match &license {
+        Some(license) if valid_expression(license) => None,
+        Some(_) => Some(format!(
+            "nonstandard license `{}`",
+            package.license.as_deref().unwrap_or_default()
+        )),
+        None => match &package.license_file {
+            Some(file) => Some(format!("no license field, see {}", file.display())),
+            None => Some("no license field".to_owned()),
+        },
+    };
+
+    let crate_dir = package
+        .manifest_path
+        .parent()
+        .unwrap_or_else(|| Path::new("."));
+
+    CrateNotice {
+        name: package.name.clone(),
+        version: package.version.clone(),
+        license: license.filter(|_| review.is_none()),
+        review,
+        texts: license_texts(crate_dir, package.license_file.as_deref()),
+    }
+}
+
+/// Whether `expression` looks like an SPDX license expression, like
+/// `MIT OR Apache-2.0` or `(MIT AND BSD-3-Clause) WITH LLVM-exception`.
+fn valid_expression(expression: &str) -> bool {
+    let tokens = expression.replace('(', " ( ").replace(')', " ) ");
+    let identifier = |token: &str| {
+        token
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.' || c == '+')
+    };
+
+    // Identifiers and operators have to alternate, like `MIT OR GPL v2`
+    // doesn't
+    let mut expect_identifier = true;
+    let mut depth = 0;
+    for token in tokens.split_whitespace() {
+        match (token, expect_identifier) {
+            ("(", true) => depth += 1,
+            (")", false) if depth > 0 => depth -= 1,
+            ("AND" | "OR" | "WITH", false) => expect_identifier = true,
+            ("AND" | "OR" | "WITH" | "(" | ")", _) => return false,
+            (_, true) if identifier(token) => expect_identifier = false,
+            _ => return false,
+        }
+    }
+
+    !expect_identifier && depth == 0
+}
+
+/// The license files in a crate's source, like `LICENSE-MIT` or `COPYING`,
+/// plus its `license-file` if that's named something else.
+fn license_texts(crate_dir: &Path, license_file: Option<&Path>) -> Vec<(String, String)> {
+    let mut paths = std::fs::read_dir(crate_dir)
+        .map(|entries| {
+            entries
+                .filter_map(Result::ok)
+                .map(|entry| entry.path())
+                .filter(|path| {
+                    let name = path
+                        .file_name()
+                        .unwrap_or_default()
+                        .to_string_lossy()
+                        .to_uppercase();
+                    path.is_file()
+                        && ["LICENSE", "LICENCE", "COPYING", "NOTICE"]
+                            .iter()
+                            .any(|prefix| name.starts_with(prefix))
+                })
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+
+    if let Some(file) = license_file {
+        let path = crate_dir.join(file);
+        if path.is_file() && !paths.contains(&path) {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+
+    paths
+        .into_iter()
+        .filter_map(|path| {
+            let text = std::fs::read(&path).ok()?;
+            let name = path.file_name()?.to_string_lossy().into_owned();
+            Some((name, String::from_utf8_lossy(&text).trim().to_owned()))
+        })
+        .collect()
+}
+
+fn render_notices(title: &str, crates: &[CrateNotice], needs_review: &[String]) -> String {
+    fn heading(text: &mut String, title: &str, underline: char) {
+        text.push_str(title);
+        text.push('\n');
+        text.push_str(&underline.to_string().repeat(title.chars().count()));
+        text.push_str("\n\n");
+    }
+
+    let mut by_license = BTreeMap::<&str, Vec<&CrateNotice>>::new();
+    for krate in crates {
+        if let Some(license) = &krate.license {
+            by_license.entry(license).or_default().push(krate);
+        }
+    }
+
+    let mut text = String::new();
+    heading(
+        &mut text,
+        &format!("Third-party software in {}", title),
+        '=',
+    );
+    text.push_str("This software includes the following crates, grouped by license.\n\n");
+
+    for (license, crates) in &by_license {
+        heading(&mut text, license, '-');
+        for krate in crates {
+            text.push_str(&format!("{} {}\n", krate.name, krate.version));
+        }
+        text.push('\n');
+    }
+
+    if !needs_review.is_empty() {
+        heading(&mut text, "Needs review", '-');
+        for line in needs_review {
+            text.push_str(line);
+            text.push('\n');
+        }
+        text.push('\n');
+    }
+
+    heading(&mut text, "License texts", '=');
+    for krate in crates {
+        for (name, license_text) in &krate.texts {
+            heading(
+                &mut text,
+                &format!("{} {} ({})", krate.name, krate.version, name),
+                '-',
+            );
+            text.push_str(license_text);
+            text.push_str("\n\n");
+        }
+    }
+
+    text
+}
@@ -0,0 +1,47 @@
+use std::process::Output;
+
+/// Where progress messages and the output of external commands go.
+///
+/// Tasks that run concurrently capture their output, so that it can be
+/// printed in one piece instead of interleaving with other tasks.
+#[derive(Debug)]
+pub enum Reporter {
+    Print,
+    Capture(String),
+}
+
+impl Reporter {
+    pub fn line(&mut self, line: &str) {
+        match self {
+            Reporter::Print => println!("{}", line),
+            Reporter::Capture(log) => {
+                log.push_str(line);
+                log.push('\n');
+            }
+        }
+    }
+
+    /// Report the stdout and stderr of a finished command.
+    pub fn command_output(&mut self, output: &Output) {
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        match self {
+            Reporter::Print => {
+                print!("{}", stdout);
+                eprint!("{}", stderr);
+            }
+            Reporter::Capture(log) => {
+                log.push_str(&stdout);
+                log.push_str(&stderr);
+            }
+        }
+    }
+
+    /// The captured output, if any.
+    pub fn into_log(self) -> String {
+        match self {
+            Reporter::Print => String::new(),
+            Reporter::Capture(log) => log,
+        }
+    }
+}
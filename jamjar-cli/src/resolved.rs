@@ -0,0 +1,298 @@
+use std::fmt;
+use std::path::PathBuf;
+
+use serde::Serialize;
+use toml::{value::Table, Value};
+
+use crate::config::{PLATFORM_KEYS, RELEASE_KEYS, WEB_KEYS};
+use crate::{
+    DistOptions, JamjarError, PackageConfig, PackageOptions, Platform, ReleaseOptions,
+    WebBuildConfig, WebBuildOptions,
+};
+
+/// Where the value of an option came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigSource {
+    /// Nothing set it.
+    Default,
+    /// A section of jamjar.toml, like `[package.linux]`.
+    File(String),
+    /// A section of Cargo.toml, like `[package.metadata.jamjar]`.
+    Manifest(String),
+    /// A command line flag.
+    Flag,
+}
+
+impl fmt::Display for ConfigSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigSource::Default => write!(f, "default"),
+            ConfigSource::File(section) => write!(f, "jamjar.toml {}", section),
+            ConfigSource::Manifest(section) => write!(f, "Cargo.toml {}", section),
+            ConfigSource::Flag => write!(f, "flag"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResolvedValue {
+    pub key: String,
+    /// `None` if the option is unset, like `icon_path` when the app uses
+    /// `icon.png`.
+    pub value: Option<Value>,
+    pub source: ConfigSource,
+}
+
+/// Every option a build uses, after merging the command line, jamjar.toml,
+/// and the defaults, along with where each one came from.
+///
+/// Printing it with `Display` gives a valid jamjar.toml with a comment on
+/// each line saying where the value came from.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ResolvedConfig {
+    /// `package` or `web`, followed by `release`.
+    pub sections: Vec<(String, Vec<ResolvedValue>)>,
+}
+
+/// The options that came from one place, in the order they're checked.
+pub(crate) type Layer = (ConfigSource, Table);
+
+pub(crate) fn layer<T: Serialize>(source: ConfigSource, options: &T) -> Result<Layer, JamjarError> {
+    match to_value(options)? {
+        Value::Table(table) => Ok((source, table)),
+        _ => unreachable!("Options should serialize to a table"),
+    }
+}
+
+fn to_value<T: Serialize>(value: &T) -> Result<Value, JamjarError> {
+    Value::try_from(value)
+        .map_err(|e| JamjarError::StringError(format!("could not write out configuration: {}", e)))
+}
+
+impl ResolvedConfig {
+    pub(crate) fn for_package(
+        config: &PackageConfig,
+        layers: &[Layer],
+        release: &ReleaseOptions,
+    ) -> Result<Self, JamjarError> {
+        let effective = PackageOptions {
+            app_name: config.app_name.clone(),
+            bin_name: config.bin_name.clone(),
+            output_dir: Some(config.output_dir.clone()),
+            icon_path: config.icon_path.clone(),
+            features: Some(config.features.clone()),
+            default_features: Some(config.default_features),
+            all_features: Some(config.all_features),
+            format: Some(config.format),
+            appimage_fallback: Some(config.appimage_fallback),
+            build_info: Some(config.build_info),
+            launch_helpers: Some(config.launch_helpers),
+            controls: config.controls.clone(),
+            pre_build: Some(config.pre_build.clone()),
+            post_build: Some(config.post_build.clone()),
+            smoke_test: config.smoke_test.clone(),
+            release_feed: Some(config.release_feed),
+            size_report: Some(config.size_report),
+            icon_sizes: Some(config.icon_sizes.clone()),
+            changelog: config.changelog.clone(),
+            require_changelog: Some(config.require_changelog),
+            locked: Some(config.locked),
+            offline: Some(config.offline),
+            toolchain: config.toolchain.clone(),
+            target: config.target.clone(),
+            manifest_path: config.manifest_path.clone(),
+            on_existing: Some(config.on_existing),
+            zip_exclude: Some(config.zip_filter.exclude.clone()),
+            zip_include_only: config.zip_filter.include_only.clone(),
+            keep_symbols: Some(config.keep_symbols),
+            resources: Some(config.resources.clone()),
+            third_party_notices: Some(config.third_party_notices),
+            strict_notices: Some(config.strict_notices),
+            bundle_id: config.bundle_id.clone(),
+            sign_identity: config.sign_identity.clone(),
+            entitlements: config.entitlements.clone(),
+            notarize: config.notarize.clone(),
+        };
+
+        Ok(ResolvedConfig {
+            sections: vec![
+                section(
+                    "package",
+                    &effective,
+                    &[&["target"], PLATFORM_KEYS].concat(),
+                    layers,
+                    &[("zip_exclude", "keep_symbols")],
+                )?,
+                release_section(config.require_clean_git, config.lock_timeout, release)?,
+            ],
+        })
+    }
+
+    pub(crate) fn for_web(
+        config: &WebBuildConfig,
+        layers: &[Layer],
+        release: &ReleaseOptions,
+    ) -> Result<Self, JamjarError> {
+        // `bypass_spirv_cross` and `emit_html` only feed into other options,
+        // so they're shown as the source of those.
+        let effective = WebBuildOptions {
+            app_name: config.app_name.clone(),
+            bin_name: config.bin_name.clone(),
+            output_dir: Some(config.output_dir.clone()),
+            features: Some(config.features.clone()),
+            default_features: Some(config.default_features),
+            all_features: Some(config.all_features),
+            bypass_spirv_cross: None,
+            spirv_cross: Some(config.spirv_cross.clone()),
+            debug: Some(config.debug),
+            names_section: Some(config.names_section),
+            emit_html: None,
+            html_filename: config.html_filename.clone(),
+            public_path: Some(config.public_path.clone()),
+            size_report: Some(config.size_report),
+            locked: Some(config.locked),
+            offline: Some(config.offline),
+            toolchain: config.toolchain.clone(),
+            manifest_path: config.manifest_path.clone(),
+            wasm_compat: Some(config.wasm_compat),
+            third_party_notices: Some(config.third_party_notices),
+            strict_notices: Some(config.strict_notices),
+        };
+        let keys = WEB_KEYS
+            .iter()
+            .copied()
+            .filter(|&key| key != "bypass_spirv_cross" && key != "emit_html")
+            .collect::<Vec<_>>();
+        let derived = [
+            ("spirv_cross", "bypass_spirv_cross"),
+            ("names_section", "debug"),
+            ("html_filename", "emit_html"),
+        ];
+
+        Ok(ResolvedConfig {
+            sections: vec![
+                section("web", &effective, &keys, layers, &derived)?,
+                release_section(config.require_clean_git, config.lock_timeout, release)?,
+            ],
+        })
+    }
+
+    /// Record that `--allow-dirty` turned off `require_clean_git`.
+    pub fn allow_dirty(&mut self) {
+        let values = self
+            .sections
+            .iter_mut()
+            .filter(|(name, _)| name == "release")
+            .flat_map(|(_, values)| values);
+        for value in values {
+            if value.key == "require_clean_git" && value.value == Some(Value::Boolean(true)) {
+                value.value = Some(Value::Boolean(false));
+                value.source = ConfigSource::Flag;
+            }
+        }
+    }
+}
+
+/// Resolve the options for a build without running it, the same way the
+/// build itself would.
+pub fn resolve_config(
+    app_root: Option<PathBuf>,
+    options: DistOptions,
+    platform: Platform,
+) -> Result<ResolvedConfig, JamjarError> {
+    match options {
+        DistOptions::Package(options) => {
+            Ok(PackageConfig::resolve(app_root, options, platform)?.resolved)
+        }
+        DistOptions::Web(options) => Ok(WebBuildConfig::resolve(app_root, options)?.resolved),
+    }
+}
+
+fn release_section(
+    require_clean_git: bool,
+    lock_timeout: std::time::Duration,
+    release: &ReleaseOptions,
+) -> Result<(String, Vec<ResolvedValue>), JamjarError> {
+    let effective = ReleaseOptions {
+        require_clean_git: Some(require_clean_git),
+        lock_timeout: Some(lock_timeout.as_secs()),
+    };
+    let layers = [layer(ConfigSource::File("[release]".to_owned()), release)?];
+    section("release", &effective, RELEASE_KEYS, &layers, &[])
+}
+
+/// Pair each effective value with the first layer that set it, or the
+/// layer that set the option it was `derived` from.
+fn section<T: Serialize>(
+    name: &str,
+    effective: &T,
+    keys: &[&str],
+    layers: &[Layer],
+    derived: &[(&str, &str)],
+) -> Result<(String, Vec<ResolvedValue>), JamjarError> {
+    let (_, mut effective) = layer(ConfigSource::Default, effective)?;
+    let source_of = |key: &str| {
+        layers
+            .iter()
+            .find(|(_, table)| table.contains_key(key))
+            .map(|(source, _)| source.clone())
+    };
+
+    let values = keys
+        .iter()
+        .map(|&key| {
+            let derived_from = derived
+                .iter()
+                .find(|&&(derived_key, _)| derived_key == key)
+                .and_then(|&(_, from)| source_of(from));
+            ResolvedValue {
+                key: key.to_owned(),
+                value: effective.remove(key),
+                source: source_of(key)
+                    .or(derived_from)
+                    .unwrap_or(ConfigSource::Default),
+            }
+        })
+        .collect();
+
+    Ok((name.to_owned(), values))
+}
+
+/// A value on one line, with tables inline.
+fn inline(value: &Value) -> String {
+    match value {
+        Value::Array(items) => {
+            let items = items.iter().map(inline).collect::<Vec<_>>();
+            format!("[{}]", items.join(", "))
+        }
+        Value::Table(table) if table.is_empty() => "{}".to_owned(),
+        Value::Table(table) => {
+            let entries = table
+                .iter()
+                .map(|(key, value)| format!("{} = {}", key, inline(value)))
+                .collect::<Vec<_>>();
+            format!("{{ {} }}", entries.join(", "))
+        }
+        value => value.to_string(),
+    }
+}
+
+impl fmt::Display for ResolvedConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, (name, values)) in self.sections.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            writeln!(f, "[{}]", name)?;
+            for value in values {
+                match &value.value {
+                    Some(inner) => {
+                        writeln!(f, "{} = {}  # {}", value.key, inline(inner), value.source)?
+                    }
+                    None => writeln!(f, "# {} is not set  # {}", value.key, value.source)?,
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,315 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::process::Command;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{JamjarError, Reporter};
+
+/// How many of the biggest files and directories are printed.
+const TOP_COUNT: usize = 10;
+
+/// How many items `twiggy` is asked for in each wasm file.
+const WASM_ITEM_COUNT: usize = 50;
+
+/// The size of one file, directory, or wasm item in a `SizeReport`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SizeEntry {
+    pub path: String,
+    /// The size in the artifact, after compression.
+    pub compressed: u64,
+    pub uncompressed: u64,
+    /// The change in `compressed` since the previous report, if there was
+    /// one. Entries that are new since then count from zero.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delta: Option<i64>,
+}
+
+/// What's taking up space in a build, written as JSON next to it when
+/// `size_report` is set.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SizeReport {
+    pub artifact: String,
+    pub compressed: u64,
+    pub uncompressed: u64,
+    /// The change in `compressed` since the previous report, if there was
+    /// one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delta: Option<i64>,
+    /// Every file, biggest first.
+    pub files: Vec<SizeEntry>,
+    /// The total for each directory, biggest first.
+    pub directories: Vec<SizeEntry>,
+    /// The biggest functions and data in each wasm file, biggest first, if
+    /// `twiggy` is installed.
+    #[serde(default)]
+    pub wasm_items: Vec<SizeEntry>,
+}
+
+impl SizeReport {
+    /// Break down an archive by its entries. Anything other than a zip,
+    /// like an AppImage, is reported as a single file.
+    pub fn of_archive(path: &Path) -> Result<Self, JamjarError> {
+        let name = file_name(path);
+
+        if path.extension() != Some("zip".as_ref()) {
+            let size = file_size(path)?;
+            return Ok(Self::from_files(
+                name.clone(),
+                vec![(name, size, size)],
+                vec![],
+            ));
+        }
+
+        let file = std::fs::File::open(path)
+            .map_err(|e| JamjarError::io(e, &format!("Failed to open '{}'.", path.display())))?;
+        let mut archive = zip::ZipArchive::new(file)?;
+
+        let mut files = vec![];
+        for i in 0..archive.len() {
+            let entry = archive.by_index(i)?;
+            if !entry.is_dir() {
+                files.push((
+                    entry.name().replace('\\', "/"),
+                    entry.compressed_size(),
+                    entry.size(),
+                ));
+            }
+        }
+
+        Ok(Self::from_files(name, files, vec![]))
+    }
+
+    /// Report on loose files in `dir`, like a web build. Wasm files are
+    /// broken down further with `twiggy`, if it's installed.
+    pub fn of_files<S: AsRef<str>>(
+        artifact: &str,
+        dir: &Path,
+        files: &[S],
+        reporter: &mut Reporter,
+    ) -> Result<Self, JamjarError> {
+        let mut sizes = vec![];
+        let mut wasm_items = vec![];
+        let mut twiggy_missing = false;
+
+        for file in files {
+            let file = file.as_ref();
+            let path = dir.join(file);
+            let size = file_size(&path)?;
+            sizes.push((file.to_owned(), size, size));
+
+            if file.ends_with(".wasm") && !twiggy_missing {
+                match twiggy_top(&path)? {
+                    Some(items) => wasm_items.extend(
+                        items
+                            .into_iter()
+                            .map(|(name, size)| (format!("{}: {}", file, name), size, size)),
+                    ),
+                    None => {
+                        twiggy_missing = true;
+                        reporter.line("  twiggy was not found, so wasm files aren't broken down. Install twiggy to fix this.");
+                    }
+                }
+            }
+        }
+
+        Ok(Self::from_files(artifact.to_owned(), sizes, wasm_items))
+    }
+
+    fn from_files(
+        artifact: String,
+        files: Vec<(String, u64, u64)>,
+        wasm_items: Vec<(String, u64, u64)>,
+    ) -> Self {
+        let mut directories = BTreeMap::<String, (u64, u64)>::new();
+        for (path, compressed, uncompressed) in &files {
+            let mut parts = path.split('/').collect::<Vec<_>>();
+            parts.pop();
+
+            for depth in 1..=parts.len() {
+                let dir = format!("{}/", parts[..depth].join("/"));
+                let total = directories.entry(dir).or_default();
+                total.0 += compressed;
+                total.1 += uncompressed;
+            }
+        }
+
+        let entries = |list: Vec<(String, u64, u64)>| {
+            let mut list = list
+                .into_iter()
+                .map(|(path, compressed, uncompressed)| SizeEntry {
+                    path,
+                    compressed,
+                    uncompressed,
+                    delta: None,
+                })
+                .collect::<Vec<_>>();
+            list.sort_by(|a, b| b.compressed.cmp(&a.compressed).then(a.path.cmp(&b.path)));
+            list
+        };
+
+        SizeReport {
+            artifact,
+            compressed: files.iter().map(|file| file.1).sum(),
+            uncompressed: files.iter().map(|file| file.2).sum(),
+            delta: None,
+            files: entries(files),
+            directories: entries(
+                directories
+                    .into_iter()
+                    .map(|(path, (compressed, uncompressed))| (path, compressed, uncompressed))
+                    .collect(),
+            ),
+            wasm_items: entries(wasm_items),
+        }
+    }
+
+    /// Fill in every `delta` by comparing with an earlier report.
+    pub fn compare_with(&mut self, previous: &SizeReport) {
+        fn compare(entries: &mut [SizeEntry], previous: &[SizeEntry]) {
+            for entry in entries {
+                let old = previous
+                    .iter()
+                    .find(|old| old.path == entry.path)
+                    .map_or(0, |old| old.compressed);
+                entry.delta = Some(entry.compressed as i64 - old as i64);
+            }
+        }
+
+        self.delta = Some(self.compressed as i64 - previous.compressed as i64);
+        compare(&mut self.files, &previous.files);
+        compare(&mut self.directories, &previous.directories);
+        compare(&mut self.wasm_items, &previous.wasm_items);
+    }
+}
+
+/// Compare `report` with the one at `report_path` from the previous build,
+/// if there is one, then print the biggest entries and replace it.
+pub fn write_size_report(
+    mut report: SizeReport,
+    report_path: &Path,
+    reporter: &mut Reporter,
+) -> Result<SizeReport, JamjarError> {
+    // An unreadable old report just means there's nothing to compare with
+    if let Ok(json) = std::fs::read_to_string(report_path) {
+        if let Ok(previous) = serde_json::from_str::<SizeReport>(&json) {
+            report.compare_with(&previous);
+        }
+    }
+
+    reporter.line(&format!(
+        "Size of {}: {} ({} uncompressed){}",
+        report.artifact,
+        human_size(report.compressed),
+        human_size(report.uncompressed),
+        human_delta(report.delta),
+    ));
+
+    let sections = [
+        ("files", &report.files),
+        ("directories", &report.directories),
+        ("wasm items", &report.wasm_items),
+    ];
+    for (title, entries) in &sections {
+        if entries.len() <= 1 {
+            continue;
+        }
+
+        reporter.line(&format!("  Largest {}:", title));
+        for entry in entries.iter().take(TOP_COUNT) {
+            reporter.line(&format!(
+                "    {:>10}  {}{}",
+                human_size(entry.compressed),
+                entry.path,
+                human_delta(entry.delta),
+            ));
+        }
+    }
+
+    let mut json = serde_json::to_string_pretty(&report)
+        .map_err(|e| JamjarError::StringError(format!("Failed to serialize size report: {}", e)))?;
+    json.push('\n');
+
+    std::fs::write(report_path, json).map_err(|e| {
+        JamjarError::io(e, &format!("Failed to write '{}'.", report_path.display()))
+    })?;
+    reporter.line(&format!("Size report written to {}", report_path.display()));
+
+    Ok(report)
+}
+
+/// The biggest items in a wasm file according to `twiggy top`, or `None`
+/// if twiggy isn't installed.
+fn twiggy_top(path: &Path) -> Result<Option<Vec<(String, u64)>>, JamjarError> {
+    #[derive(Deserialize)]
+    struct Item {
+        name: String,
+        shallow_size: u64,
+    }
+
+    let output = Command::new("twiggy")
+        .arg("top")
+        .arg("-n")
+        .arg(WASM_ITEM_COUNT.to_string())
+        .arg("-f")
+        .arg("json")
+        .arg(path)
+        .output();
+
+    let output = match output {
+        Ok(output) => output,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(JamjarError::io(e, "Failed to run twiggy.")),
+    };
+
+    if !output.status.success() {
+        return Err(JamjarError::ExternalCommandError("twiggy"));
+    }
+
+    let items = serde_json::from_slice::<Vec<Item>>(&output.stdout).map_err(|e| {
+        JamjarError::StringError(format!("Could not read the output of twiggy: {}", e))
+    })?;
+
+    Ok(Some(
+        items
+            .into_iter()
+            .map(|item| (item.name, item.shallow_size))
+            .collect(),
+    ))
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.display().to_string())
+}
+
+fn file_size(path: &Path) -> Result<u64, JamjarError> {
+    std::fs::metadata(path)
+        .map(|metadata| metadata.len())
+        .map_err(|e| JamjarError::io(e, &format!("Failed to read '{}'.", path.display())))
+}
+
+fn human_size(bytes: u64) -> String {
+    const UNITS: &[&str] = &["B", "KB", "MB", "GB"];
+
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1000. && unit + 1 < UNITS.len() {
+        size /= 1000.;
+        unit += 1;
+    }
+
+    match unit {
+        0 => format!("{} B", bytes),
+        _ => format!("{:.1} {}", size, UNITS[unit]),
+    }
+}
+
+fn human_delta(delta: Option<i64>) -> String {
+    match delta {
+        None | Some(0) => String::new(),
+        Some(delta) if delta > 0 => format!(" (+{})", human_size(delta as u64)),
+        Some(delta) => format!(" (-{})", human_size(delta.unsigned_abs())),
+    }
+}
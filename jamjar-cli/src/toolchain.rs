@@ -0,0 +1,69 @@
+use std::path::Path;
+use std::process::Command;
+
+use crate::JamjarError;
+
+/// Build with `toolchain`, if it's set. Otherwise rustup picks as usual,
+/// from the app's `rust-toolchain.toml` or the default toolchain.
+pub(crate) fn use_toolchain(cmd: &mut Command, toolchain: Option<&str>) {
+    if let Some(toolchain) = toolchain {
+        cmd.env("RUSTUP_TOOLCHAIN", toolchain);
+    }
+}
+
+/// The output of `rustc --version --verbose` for the toolchain builds of
+/// the app will use. Fails if `toolchain` is set but isn't installed.
+pub fn rustc_version(app_root: &Path, toolchain: Option<&str>) -> Result<String, JamjarError> {
+    if toolchain.is_some() && !rustup_installed() {
+        return Err(JamjarError::StringError(
+            "`toolchain` is set, but rustup was not found to switch to it".to_owned(),
+        ));
+    }
+
+    let mut cmd = Command::new("rustc");
+    cmd.current_dir(app_root).arg("--version").arg("--verbose");
+    use_toolchain(&mut cmd, toolchain);
+
+    // Newer versions of rustup install missing toolchains on first use,
+    // which would hide a typo behind a long download
+    cmd.env("RUSTUP_AUTO_INSTALL", "0");
+
+    let output = cmd
+        .output()
+        .map_err(|e| JamjarError::io(e, "Failed to run rustc."))?;
+
+    if !output.status.success() {
+        return Err(match toolchain {
+            Some(toolchain) => JamjarError::StringError(format!(
+                "the Rust toolchain `{0}` is not installed. Install it with `rustup toolchain install {0}`.\n{1}",
+                toolchain,
+                String::from_utf8_lossy(&output.stderr)
+                    .lines()
+                    .next()
+                    .unwrap_or_default()
+            )),
+            None => JamjarError::ExternalCommandError("rustc"),
+        });
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_owned())
+}
+
+/// The target triple the toolchain builds for by default.
+pub fn host_target(app_root: &Path, toolchain: Option<&str>) -> Result<String, JamjarError> {
+    let version = rustc_version(app_root, toolchain)?;
+    version
+        .lines()
+        .find_map(|line| line.strip_prefix("host: "))
+        .map(|host| host.trim().to_owned())
+        .ok_or_else(|| {
+            JamjarError::StringError("`rustc --version --verbose` didn't say the host".to_owned())
+        })
+}
+
+fn rustup_installed() -> bool {
+    match Command::new("rustup").arg("--version").output() {
+        Err(e) => e.kind() != std::io::ErrorKind::NotFound,
+        Ok(_) => true,
+    }
+}
@@ -0,0 +1,564 @@
+use crate::JamjarError;
+
+const RT_ICON: u16 = 3;
+const RT_GROUP_ICON: u16 = 14;
+const RT_VERSION: u16 = 16;
+
+/// US English, which the version info's translation says too.
+const LANGUAGE: u16 = 0x0409;
+
+/// Moveable, pure and discardable, as `rc` writes them. Windows has
+/// ignored these since it went 32-bit.
+const MEMORY_FLAGS: u16 = 0x1030;
+
+/// The version info shown in the exe's properties.
+pub struct VersionInfo<'a> {
+    pub version: &'a str,
+    pub app_name: &'a str,
+    pub exe_name: &'a str,
+    pub company: Option<&'a str>,
+    pub copyright: Option<&'a str>,
+}
+
+/// One resource, with a numeric type and ID, as all of jamjar's are.
+pub struct Resource {
+    kind: u16,
+    id: u16,
+    data: Vec<u8>,
+}
+
+/// The resources for an exe: the icon, made of a group of `(size, png)`
+/// images, if there is one, then the version info. They're sorted by type
+/// and then ID, which the resource directory in an object file needs.
+pub fn resources(icon: Option<&[(u32, Vec<u8>)]>, version_info: &VersionInfo) -> Vec<Resource> {
+    let mut resources = vec![];
+
+    if let Some(images) = icon {
+        // GRPICONDIR: reserved, type (1 for icons), and the number of images,
+        // then a GRPICONDIRENTRY pointing at each RT_ICON resource by ID.
+        let mut group = vec![];
+        for field in &[0_u16, 1, images.len() as u16] {
+            group.extend_from_slice(&field.to_le_bytes());
+        }
+
+        for (index, (size, png)) in images.iter().enumerate() {
+            let id = index as u16 + 1;
+
+            // A width and height of 256 is written as 0
+            let side = (size % 256) as u8;
+            group.extend_from_slice(&[side, side, 0, 0]);
+            group.extend_from_slice(&1_u16.to_le_bytes());
+            group.extend_from_slice(&32_u16.to_le_bytes());
+            group.extend_from_slice(&(png.len() as u32).to_le_bytes());
+            group.extend_from_slice(&id.to_le_bytes());
+
+            resources.push(Resource {
+                kind: RT_ICON,
+                id,
+                data: png.clone(),
+            });
+        }
+
+        resources.push(Resource {
+            kind: RT_GROUP_ICON,
+            id: 1,
+            data: group,
+        });
+    }
+
+    resources.push(Resource {
+        kind: RT_VERSION,
+        id: 1,
+        data: version_info.to_bytes(),
+    });
+
+    resources
+}
+
+/// A `.res` file holding `resources`, which the MSVC linker takes as it is.
+pub fn res_file(resources: &[Resource]) -> Vec<u8> {
+    let mut res = vec![];
+
+    // An empty resource comes first, marking the file as 32-bit
+    write_res_entry(&mut res, 0, 0, 0, 0, &[]);
+
+    for resource in resources {
+        write_res_entry(
+            &mut res,
+            resource.kind,
+            resource.id,
+            MEMORY_FLAGS,
+            LANGUAGE,
+            &resource.data,
+        );
+    }
+
+    res
+}
+
+fn write_res_entry(res: &mut Vec<u8>, kind: u16, id: u16, flags: u16, language: u16, data: &[u8]) {
+    // The header's size is fixed, since the type and name are both numbers
+    res.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    res.extend_from_slice(&32_u32.to_le_bytes());
+    for field in &[0xffff, kind, 0xffff, id] {
+        res.extend_from_slice(&field.to_le_bytes());
+    }
+
+    // The data version, flags, language, version and characteristics
+    res.extend_from_slice(&0_u32.to_le_bytes());
+    res.extend_from_slice(&flags.to_le_bytes());
+    res.extend_from_slice(&language.to_le_bytes());
+    res.extend_from_slice(&[0; 8]);
+
+    res.extend_from_slice(data);
+    pad(res, 4);
+}
+
+/// A COFF object file with a `.rsrc` section holding `resources`, for
+/// linkers that don't take `.res` files, like MinGW's. Returns an error if
+/// there's no COFF machine type for the `target` triple's architecture.
+pub fn coff_object(resources: &[Resource], target: &str) -> Result<Vec<u8>, JamjarError> {
+    // The machine type, and the relocation type for an address relative
+    // to the start of the image, which is what resource directories hold.
+    let (machine, relocation_type) = match target.split('-').next() {
+        Some("x86_64") => (0x8664_u16, 3_u16),
+        Some("i386") | Some("i586") | Some("i686") => (0x14c, 7),
+        Some("aarch64") => (0xaa64, 2),
+        _ => {
+            return Err(JamjarError::StringError(format!(
+                "can't embed the icon and version info in an exe for `{}`",
+                target
+            )))
+        }
+    };
+
+    let section = resource_section(resources);
+    let relocations = &section.relocations;
+
+    let section_start = 20 + 40;
+    let relocations_start = section_start + section.data.len();
+    let symbols_start = relocations_start + 10 * relocations.len();
+
+    let mut object = vec![];
+
+    // IMAGE_FILE_HEADER, for one section and one symbol
+    object.extend_from_slice(&machine.to_le_bytes());
+    object.extend_from_slice(&1_u16.to_le_bytes());
+    object.extend_from_slice(&0_u32.to_le_bytes());
+    object.extend_from_slice(&(symbols_start as u32).to_le_bytes());
+    object.extend_from_slice(&1_u32.to_le_bytes());
+    object.extend_from_slice(&[0; 4]);
+
+    // IMAGE_SECTION_HEADER, for initialized read-only data
+    object.extend_from_slice(b".rsrc\0\0\0");
+    object.extend_from_slice(&[0; 8]);
+    object.extend_from_slice(&(section.data.len() as u32).to_le_bytes());
+    object.extend_from_slice(&(section_start as u32).to_le_bytes());
+    object.extend_from_slice(&(relocations_start as u32).to_le_bytes());
+    object.extend_from_slice(&0_u32.to_le_bytes());
+    object.extend_from_slice(&(relocations.len() as u16).to_le_bytes());
+    object.extend_from_slice(&0_u16.to_le_bytes());
+    object.extend_from_slice(&0x4000_0040_u32.to_le_bytes());
+
+    object.extend_from_slice(&section.data);
+
+    // Each relocation is against the section's symbol, the only one
+    for &offset in relocations {
+        object.extend_from_slice(&offset.to_le_bytes());
+        object.extend_from_slice(&0_u32.to_le_bytes());
+        object.extend_from_slice(&relocation_type.to_le_bytes());
+    }
+
+    // The section's symbol, which is static and has no aux records
+    object.extend_from_slice(b".rsrc\0\0\0");
+    object.extend_from_slice(&0_u32.to_le_bytes());
+    object.extend_from_slice(&1_u16.to_le_bytes());
+    object.extend_from_slice(&0_u16.to_le_bytes());
+    object.extend_from_slice(&[3, 0]);
+
+    // An empty string table, which is just its own size
+    object.extend_from_slice(&4_u32.to_le_bytes());
+
+    Ok(object)
+}
+
+/// The contents of a `.rsrc` section, and the offsets of the data addresses
+/// in it that need relocating.
+struct ResourceSection {
+    data: Vec<u8>,
+    relocations: Vec<u32>,
+}
+
+/// Lay out `resources` as a resource directory: a table of types, a table
+/// of IDs for each type, and a table of languages for each ID, all before
+/// the data entries that point at the data itself.
+fn resource_section(resources: &[Resource]) -> ResourceSection {
+    const SUBDIRECTORY: u32 = 0x8000_0000;
+
+    let directory_len = |entries: usize| 16 + 8 * entries as u32;
+
+    let mut kinds = resources
+        .iter()
+        .map(|resource| resource.kind)
+        .collect::<Vec<_>>();
+    kinds.dedup();
+    let count = |kind: u16| {
+        resources
+            .iter()
+            .filter(|resource| resource.kind == kind)
+            .count()
+    };
+
+    let mut type_directories = vec![];
+    let mut offset = directory_len(kinds.len());
+    for &kind in &kinds {
+        type_directories.push(offset);
+        offset += directory_len(count(kind));
+    }
+
+    let language_directories = offset;
+    let data_entries = language_directories + directory_len(1) * resources.len() as u32;
+
+    let mut data_offsets = vec![];
+    let mut offset = data_entries + 16 * resources.len() as u32;
+    for resource in resources {
+        data_offsets.push(offset);
+        offset += resource.data.len() as u32;
+        offset = (offset + 7) & !7;
+    }
+
+    let mut data = vec![];
+    write_directory(
+        &mut data,
+        kinds
+            .iter()
+            .zip(&type_directories)
+            .map(|(&kind, &offset)| (kind, SUBDIRECTORY | offset)),
+    );
+
+    for &kind in &kinds {
+        write_directory(
+            &mut data,
+            resources
+                .iter()
+                .enumerate()
+                .filter(|(_, resource)| resource.kind == kind)
+                .map(|(index, resource)| {
+                    let offset = language_directories + directory_len(1) * index as u32;
+                    (resource.id, SUBDIRECTORY | offset)
+                }),
+        );
+    }
+
+    for index in 0..resources.len() as u32 {
+        write_directory(
+            &mut data,
+            std::iter::once((LANGUAGE, data_entries + 16 * index)),
+        );
+    }
+
+    // IMAGE_RESOURCE_DATA_ENTRY: the data's address, which the linker
+    // relocates, its size, its code page and a reserved field.
+    let mut relocations = vec![];
+    for (resource, &offset) in resources.iter().zip(&data_offsets) {
+        relocations.push(data.len() as u32);
+        data.extend_from_slice(&offset.to_le_bytes());
+        data.extend_from_slice(&(resource.data.len() as u32).to_le_bytes());
+        data.extend_from_slice(&[0; 8]);
+    }
+
+    for resource in resources {
+        data.extend_from_slice(&resource.data);
+        pad(&mut data, 8);
+    }
+
+    ResourceSection { data, relocations }
+}
+
+/// IMAGE_RESOURCE_DIRECTORY, with `entries` of IDs and offsets. Its
+/// characteristics, timestamp and version are all zero.
+fn write_directory(data: &mut Vec<u8>, entries: impl Iterator<Item = (u16, u32)>) {
+    let entries = entries.collect::<Vec<_>>();
+    data.extend_from_slice(&[0; 12]);
+    data.extend_from_slice(&0_u16.to_le_bytes());
+    data.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+    for (id, offset) in entries {
+        data.extend_from_slice(&u32::from(id).to_le_bytes());
+        data.extend_from_slice(&offset.to_le_bytes());
+    }
+}
+
+impl VersionInfo<'_> {
+    /// The `VS_VERSIONINFO` structure, with the same strings an `.rc` file
+    /// would usually give.
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut strings = vec![];
+        if let Some(company) = self.company {
+            strings.push(("CompanyName", company.to_owned()));
+        }
+        strings.push(("FileDescription", self.app_name.to_owned()));
+        strings.push(("FileVersion", self.version.to_owned()));
+        if let Some(copyright) = self.copyright {
+            strings.push(("LegalCopyright", copyright.to_owned()));
+        }
+        strings.push(("OriginalFilename", format!("{}.exe", self.exe_name)));
+        strings.push(("ProductName", self.app_name.to_owned()));
+        strings.push(("ProductVersion", self.version.to_owned()));
+
+        let strings = strings
+            .iter()
+            .map(|(key, value)| block(key, Value::Text(value), &[]))
+            .collect::<Vec<_>>();
+
+        // US English, in UTF-16 (code page 1200)
+        let string_table = block("040904b0", Value::None, &strings);
+        let string_file_info = block("StringFileInfo", Value::None, &[string_table]);
+        let translation = block("Translation", Value::Binary(&[0x09, 0x04, 0xb0, 0x04]), &[]);
+        let var_file_info = block("VarFileInfo", Value::None, &[translation]);
+
+        block(
+            "VS_VERSION_INFO",
+            Value::Binary(&self.fixed_file_info()),
+            &[string_file_info, var_file_info],
+        )
+    }
+
+    /// `VS_FIXEDFILEINFO`, for a Windows app. The version is four numbers,
+    /// so pre-release and build tags are dropped.
+    fn fixed_file_info(&self) -> Vec<u8> {
+        let mut numbers = self
+            .version
+            .split(['-', '+'])
+            .next()
+            .unwrap_or("")
+            .split('.')
+            .map(|part| part.parse::<u32>().unwrap_or(0).min(0xffff))
+            .collect::<Vec<_>>();
+        numbers.resize(4, 0);
+        let most_significant = numbers[0] << 16 | numbers[1];
+        let least_significant = numbers[2] << 16 | numbers[3];
+
+        let fields = [
+            0xfeef_04bd, // The signature
+            0x0001_0000, // The structure's version
+            most_significant,
+            least_significant,
+            most_significant,
+            least_significant,
+            0x3f,        // Which flags are valid
+            0,           // The flags
+            0x0004_0004, // VOS_NT_WINDOWS32
+            1,           // VFT_APP
+            0,           // The subtype
+            0,           // The date, which is never set
+            0,
+        ];
+
+        fields
+            .iter()
+            .flat_map(|field: &u32| field.to_le_bytes())
+            .collect()
+    }
+}
+
+enum Value<'a> {
+    None,
+    Text(&'a str),
+    Binary(&'a [u8]),
+}
+
+/// One block of version info: its length, its value's length and type, its
+/// key, then its value and children, each aligned to four bytes. A text
+/// value's length is in UTF-16 units, including the terminator.
+fn block(key: &str, value: Value, children: &[Vec<u8>]) -> Vec<u8> {
+    let (value_len, text, value) = match value {
+        Value::None => (0, true, vec![]),
+        Value::Text(text) => {
+            let value = utf16(text);
+            (value.len() / 2, true, value)
+        }
+        Value::Binary(bytes) => (bytes.len(), false, bytes.to_vec()),
+    };
+
+    // The length is filled in at the end
+    let mut block = vec![0, 0];
+    block.extend_from_slice(&(value_len as u16).to_le_bytes());
+    block.extend_from_slice(&u16::from(text).to_le_bytes());
+    block.extend_from_slice(&utf16(key));
+    pad(&mut block, 4);
+    block.extend_from_slice(&value);
+
+    for child in children {
+        pad(&mut block, 4);
+        block.extend_from_slice(child);
+    }
+
+    let len = block.len() as u16;
+    block[..2].copy_from_slice(&len.to_le_bytes());
+    block
+}
+
+/// `text` in little-endian UTF-16, with a terminator.
+fn utf16(text: &str) -> Vec<u8> {
+    text.encode_utf16()
+        .chain(std::iter::once(0))
+        .flat_map(u16::to_le_bytes)
+        .collect()
+}
+
+/// Pad `data` with zeros to a multiple of `alignment`, a power of two.
+fn pad(data: &mut Vec<u8>, alignment: usize) {
+    while data.len() & (alignment - 1) != 0 {
+        data.push(0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn u16_at(data: &[u8], offset: usize) -> u16 {
+        u16::from_le_bytes([data[offset], data[offset + 1]])
+    }
+
+    fn u32_at(data: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes([
+            data[offset],
+            data[offset + 1],
+            data[offset + 2],
+            data[offset + 3],
+        ])
+    }
+
+    fn version_info() -> VersionInfo<'static> {
+        VersionInfo {
+            version: "1.2.3-beta.1",
+            app_name: "Gâme \"Deluxe\"",
+            exe_name: "game",
+            company: Some("Someone"),
+            copyright: None,
+        }
+    }
+
+    fn icon() -> Vec<(u32, Vec<u8>)> {
+        vec![(16, vec![1; 5]), (256, vec![2; 12])]
+    }
+
+    #[test]
+    fn res_files_list_each_resource_after_an_empty_one() {
+        let icon = icon();
+        let res = res_file(&resources(Some(&icon), &version_info()));
+
+        let mut entries = vec![];
+        let mut offset = 0;
+        while offset < res.len() {
+            let data_len = u32_at(&res, offset) as usize;
+            let header_len = u32_at(&res, offset + 4) as usize;
+            assert_eq!(header_len, 32);
+            entries.push((
+                u16_at(&res, offset + 10),
+                u16_at(&res, offset + 14),
+                res[offset + header_len..offset + header_len + data_len].to_vec(),
+            ));
+            offset += (header_len + data_len + 3) & !3;
+        }
+        assert_eq!(offset, res.len());
+
+        let kinds = entries
+            .iter()
+            .map(|(kind, id, _)| (*kind, *id))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            [
+                (0, 0),
+                (RT_ICON, 1),
+                (RT_ICON, 2),
+                (RT_GROUP_ICON, 1),
+                (RT_VERSION, 1)
+            ]
+        );
+        assert_eq!(entries[2].2, vec![2; 12]);
+
+        // The group's second entry is the 256px image, with its size as 0
+        let group = &entries[3].2;
+        assert_eq!(u16_at(group, 4), 2);
+        assert_eq!(&group[20..22], &[0, 0]);
+        assert_eq!(u32_at(group, 28), 12);
+        assert_eq!(u16_at(group, 32), 2);
+    }
+
+    #[test]
+    fn version_info_has_the_fixed_info_and_strings() {
+        let bytes = version_info().to_bytes();
+
+        assert_eq!(u16_at(&bytes, 0) as usize, bytes.len());
+        assert_eq!(u16_at(&bytes, 2), 52);
+        assert_eq!(&bytes[6..38], &utf16("VS_VERSION_INFO")[..]);
+
+        // The key ends at 38, so the fixed info starts at 40
+        assert_eq!(u32_at(&bytes, 40), 0xfeef_04bd);
+        assert_eq!(u32_at(&bytes, 48), 1 << 16 | 2);
+        assert_eq!(u32_at(&bytes, 52), 3 << 16);
+
+        let contains = |text: &[u8]| bytes.windows(text.len()).any(|window| window == text);
+        assert!(contains(&utf16("Gâme \"Deluxe\"")));
+        assert!(contains(&utf16("game.exe")));
+        assert!(contains(&utf16("1.2.3-beta.1")));
+        assert!(!contains(&utf16("LegalCopyright")));
+    }
+
+    #[test]
+    fn object_files_point_each_resource_at_its_data() {
+        let icon = icon();
+        let resources = resources(Some(&icon), &version_info());
+        let object = coff_object(&resources, "x86_64-pc-windows-gnu").unwrap();
+
+        assert_eq!(u16_at(&object, 0), 0x8664);
+        let section_len = u32_at(&object, 36) as usize;
+        let section_start = u32_at(&object, 40) as usize;
+        let relocations_start = u32_at(&object, 44) as usize;
+        let relocation_count = u16_at(&object, 52) as usize;
+        let section = &object[section_start..section_start + section_len];
+
+        let relocations = (0..relocation_count)
+            .map(|index| u32_at(&object, relocations_start + 10 * index) as usize)
+            .collect::<Vec<_>>();
+
+        // Follow type -> ID -> language -> data entry for each resource
+        let entry = |directory: usize, index: usize| {
+            let entries = directory + 16 + 8 * index;
+            (u32_at(section, entries), u32_at(section, entries + 4))
+        };
+        let mut found = 0;
+        for type_index in 0..u16_at(section, 14) as usize {
+            let (kind, types) = entry(0, type_index);
+            let ids = (types & 0x7fff_ffff) as usize;
+            for id_index in 0..u16_at(section, ids + 14) as usize {
+                let (id, languages) = entry(ids, id_index);
+                let (language, data_entry) = entry((languages & 0x7fff_ffff) as usize, 0);
+                assert_eq!(language, u32::from(LANGUAGE));
+
+                let data_entry = data_entry as usize;
+                assert!(relocations.contains(&data_entry));
+                let offset = u32_at(section, data_entry) as usize;
+                let len = u32_at(section, data_entry + 4) as usize;
+
+                let resource = &resources[found];
+                assert_eq!((kind, id), (resource.kind.into(), resource.id.into()));
+                assert_eq!(&section[offset..offset + len], &resource.data[..]);
+                found += 1;
+            }
+        }
+        assert_eq!(found, resources.len());
+        assert_eq!(relocations.len(), resources.len());
+    }
+
+    #[test]
+    fn object_files_need_a_known_architecture() {
+        let resources = resources(None, &version_info());
+        assert!(coff_object(&resources, "i686-pc-windows-gnu").is_ok());
+        assert!(coff_object(&resources, "aarch64-pc-windows-gnullvm").is_ok());
+        assert!(coff_object(&resources, "mips-pc-windows-gnu").is_err());
+    }
+}
@@ -0,0 +1,6 @@
+#!/bin/sh
+cd "$(dirname "$0")" || exit 1
+if [ ! -x "./{{ exe_name }}" ]; then
+    chmod +x "./{{ exe_name }}"
+fi
+exec "./{{ exe_name }}" "$@"
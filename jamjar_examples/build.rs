@@ -2,11 +2,13 @@ fn main() {
     jamjar::codegen::create_data_structs(&[("assets/config.toml", "src/gen/config.rs", "Config")])
         .unwrap();
 
-    jamjar::codegen::create_files_enums(&[
+    let files_enums = [
         ("assets/images", "src/gen/images.rs", "Image"),
         ("assets/audio", "src/gen/audio.rs", "Audio"),
-    ])
-    .unwrap();
+    ];
+    jamjar::codegen::check_files_keys(jamjar::resource_keys::KeyMode::Strict, &files_enums)
+        .unwrap();
+    jamjar::codegen::create_files_enums(&files_enums).unwrap();
 
     jamjar::codegen::create_data_enums(&[
         ("assets/numbers.yaml", "src/gen/numbers.rs", "Number"),
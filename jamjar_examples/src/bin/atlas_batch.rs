@@ -0,0 +1,75 @@
+// NOTE: This example uses threads, so it only runs natively.
+
+const SPRITE_COUNT: usize = 200;
+const WORKER_COUNT: usize = 4;
+
+fn main() {
+    use jamjar::{
+        atlas::{image::ImageAtlas, Atlas},
+        timing::RealClock,
+    };
+
+    let sources = vec![
+        jamjar::resource!("assets/images/blit.png"),
+        jamjar::resource!("assets/images/bubble.png"),
+        jamjar::resource!("assets/images/heart.png"),
+        jamjar::resource!("assets/images/star.png"),
+        jamjar::resource!("assets/images/target.png"),
+    ];
+
+    let decode = |i: usize| {
+        let bytes = &sources[i % sources.len()];
+        image::load_from_memory(bytes).unwrap().to_rgba8()
+    };
+
+    let mut clock = RealClock::new_now();
+
+    let mut atlas = ImageAtlas::<str>::with_size([8192, 8192]);
+    for i in 0..SPRITE_COUNT {
+        atlas.insert((i.to_string(), decode(i)));
+    }
+    let sequential_secs = clock.update();
+
+    let mut atlas = ImageAtlas::<str>::with_size([8192, 8192]);
+    let batch = atlas.begin_batch();
+    let workers = (0..WORKER_COUNT)
+        .map(|worker| {
+            let batch = batch.clone();
+            let sources = sources.clone();
+            std::thread::spawn(move || {
+                for i in (worker..SPRITE_COUNT).step_by(WORKER_COUNT) {
+                    let bytes = &sources[i % sources.len()];
+                    let image = image::load_from_memory(bytes).unwrap().to_rgba8();
+                    batch.submit(i.to_string(), image);
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    std::mem::drop(batch);
+
+    atlas.finish_batch().unwrap();
+    for worker in workers {
+        worker.join().unwrap();
+    }
+    let batched_secs = clock.update();
+
+    assert!((0..SPRITE_COUNT).all(|i| atlas.contains(&i.to_string())));
+    let ([x, y], [w, h]) = atlas.get("0").unwrap().pixels;
+
+    println!(
+        "Loaded {} sprites:\n  sequentially in {:.1}ms\n  batched across {} threads in {:.1}ms",
+        SPRITE_COUNT,
+        sequential_secs * 1000.,
+        WORKER_COUNT,
+        batched_secs * 1000.,
+    );
+    println!("Sprite \"0\" is {}x{} at ({}, {})", w, h, x, y);
+
+    // The pivot and boxes come from regions.toml. (A heart.toml sidecar
+    // would also work, but it would need ignoring in the images directory.)
+    let heart_path = concat!(env!("CARGO_MANIFEST_DIR"), "/assets/images/heart.png");
+    let regions_path = concat!(env!("CARGO_MANIFEST_DIR"), "/assets/regions.toml");
+    atlas.insert_path("heart".to_owned(), heart_path).unwrap();
+    atlas.load_extras_file(regions_path).unwrap();
+    println!("Heart extras: {:?}", atlas.fetch_extras("heart"));
+}
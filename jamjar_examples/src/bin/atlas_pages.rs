@@ -0,0 +1,136 @@
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen(start)]
+pub fn wasm_main() {
+    main();
+}
+
+const PAGE_SIZE: u32 = 512;
+const TILE_SIZE: u32 = 32;
+const COLUMNS: u32 = 20;
+const ROWS: u32 = 15;
+
+/// A tile with a unique color, so that any tile drawn from the wrong page
+/// or place breaks the gradient across the grid.
+fn tile(index: u32) -> image::RgbaImage {
+    let column = index % COLUMNS;
+    let row = index / COLUMNS;
+    let r = (column * 255 / (COLUMNS - 1)) as u8;
+    let g = (row * 255 / (ROWS - 1)) as u8;
+
+    image::RgbaImage::from_fn(TILE_SIZE, TILE_SIZE, |x, y| {
+        let edge = x == 0 || y == 0 || x == TILE_SIZE - 1 || y == TILE_SIZE - 1;
+        match edge {
+            true => image::Rgba([r / 2, g / 2, 64, 255]),
+            false => image::Rgba([r, g, 128, 255]),
+        }
+    })
+}
+
+fn main() {
+    use jamjar::{
+        atlas::{image::ImageAtlas, Atlas},
+        draw::{
+            backend,
+            groove::{DrawContext, Sprite},
+            CanvasConfig,
+        },
+        windowing,
+    };
+
+    jamjar::logging::init_logging();
+
+    let resolution = [COLUMNS * TILE_SIZE, ROWS * TILE_SIZE];
+
+    let (window, event_loop) =
+        windowing::window_and_event_loop("Atlas Pages Test", resolution).unwrap();
+
+    // 300 tiles don't fit on one 512x512 page, so they spill onto a second
+    let mut atlas = ImageAtlas::<str>::paged([PAGE_SIZE, PAGE_SIZE], 2);
+    for i in 0..COLUMNS * ROWS {
+        atlas.insert((i.to_string(), tile(i)));
+    }
+    assert_eq!(atlas.page_count(), 2);
+
+    let pages = atlas.compile_pages();
+    let tiles = (0..COLUMNS * ROWS)
+        .map(|i| {
+            let region = atlas.fetch(&i.to_string());
+            let pos = [
+                (i % COLUMNS * TILE_SIZE) as f32,
+                (i / COLUMNS * TILE_SIZE) as f32,
+            ];
+            Sprite::new(region, pos)
+        })
+        .collect::<Vec<_>>();
+
+    let canvas_config = CanvasConfig::pixel_scaled(resolution);
+    let mut context =
+        DrawContext::<backend::Whatever>::with_pages(&window, canvas_config, pages).unwrap();
+
+    let mut interleaved = false;
+    let mut report_stats = true;
+
+    jamjar::jprintln!(
+        r#"Press:
+Space. To toggle between drawing each page's tiles together,
+       and alternating between pages"#
+    );
+
+    event_loop.run(move |event, _, control_flow| {
+        use windowing::event::{ElementState, Event, VirtualKeyCode, WindowEvent};
+
+        match event {
+            Event::WindowEvent { event, .. } => match event {
+                WindowEvent::CloseRequested => {
+                    *control_flow = windowing::event_loop::ControlFlow::Exit
+                }
+                WindowEvent::Resized(dims) => {
+                    context.resolution_changed(dims.into());
+                }
+                WindowEvent::ScaleFactorChanged {
+                    scale_factor,
+                    new_inner_size,
+                } => {
+                    context.scale_factor_changed(scale_factor, (*new_inner_size).into());
+                }
+                WindowEvent::KeyboardInput { input, .. } => {
+                    if let (ElementState::Pressed, Some(VirtualKeyCode::Space)) =
+                        (input.state, input.virtual_keycode)
+                    {
+                        interleaved = !interleaved;
+                        report_stats = true;
+                        window.request_redraw();
+                    }
+                }
+                _ => (),
+            },
+            Event::RedrawRequested(_) => {
+                let mut ren = context.start_rendering([0., 0., 0., 1.]);
+
+                match interleaved {
+                    false => ren.sprites(tiles.iter().copied()),
+                    true => {
+                        let (first, second): (Vec<&Sprite>, Vec<&Sprite>) =
+                            tiles.iter().partition(|sprite| sprite.page == 0);
+                        let both = first.len().min(second.len());
+                        for (a, b) in first.iter().zip(&second) {
+                            ren.sprite(**a);
+                            ren.sprite(**b);
+                        }
+                        for sprite in first[both..].iter().chain(&second[both..]) {
+                            ren.sprite(**sprite);
+                        }
+                    }
+                }
+
+                std::mem::drop(ren);
+
+                if report_stats {
+                    report_stats = false;
+                    jamjar::jprintln!("{:?}", context.stats());
+                }
+            }
+            _ => (),
+        }
+    });
+}
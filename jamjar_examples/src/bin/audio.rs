@@ -8,9 +8,13 @@ fn main() {
     use jamjar_examples::gen::{data::VOLUMES, Audio};
 
     use jamjar::{
-        audio::{AudioState, Mixer, Sound, Track},
+        audio::{
+            AudioState, Echo, FadeCurve, Limiter, Mixer, MixerOptions, ResampleQuality, Sound,
+            Track,
+        },
         resource,
         timing::{RealClock, RealTimestamp},
+        windowing::{RedrawPolicy, RedrawScheduler},
     };
 
     jamjar::logging::init_logging();
@@ -23,32 +27,116 @@ fn main() {
         &jamjar::resource_list!("assets/audio"),
     );
 
-    let mut mixer = Mixer::new(audio_library, Some(VOLUMES.clone()));
+    // Latency from the output buffer alone is `buffer_size / sample_rate`.
+    // With the device's default buffer, players have reported ~120ms
+    // between a key press and the sound. With 256 frames at 48kHz, the
+    // buffer adds 5.3ms, printed as the buffer latency of the first sound.
+    // Pass `--default-buffer` to leave the buffer size to the device, to
+    // compare by ear.
+    //
+    // Pass `--low-end` to trade sound quality and memory for CPU, and
+    // compare the CPU use printed every few seconds. Pass `--limit` to
+    // keep sounds mashed together from clipping.
+    let low_end = std::env::args().any(|arg| arg == "--low-end");
+    let limit = std::env::args().any(|arg| arg == "--limit");
+    let default_buffer = std::env::args().any(|arg| arg == "--default-buffer");
+    let mixer_options = MixerOptions {
+        sample_rate: Some(48000),
+        buffer_size: Some(256).filter(|_| !default_buffer),
+        resample_quality: match low_end {
+            true => ResampleQuality::Half,
+            false => ResampleQuality::Full,
+        },
+        predecode_under: Some(std::time::Duration::from_secs(2)).filter(|_| low_end),
+        measure_cpu: true,
+        limiter: Some(Limiter::default()).filter(|_| limit),
+        detect_silence: Some(std::time::Duration::from_secs(2)),
+        ..MixerOptions::default()
+    };
+
+    // Opening the device can take a few hundred milliseconds, so on native
+    // it's opened up front. Pass `--cold` to open it on the first key press
+    // instead, to compare. Browsers need a key press first either way.
+    let cold = cfg!(target_arch = "wasm32") || std::env::args().any(|arg| arg == "--cold");
+
+    #[cfg(not(target_arch = "wasm32"))]
+    let mut mixer = match cold {
+        true => Mixer::new(audio_library, Some(VOLUMES.clone()), mixer_options),
+        false => Mixer::new_prewarmed(audio_library, Some(VOLUMES.clone()), mixer_options),
+    };
+    #[cfg(target_arch = "wasm32")]
+    let mut mixer = Mixer::new(audio_library, Some(VOLUMES.clone()), mixer_options);
+    mixer.name_keys_with_debug();
+
     let mut clock = RealClock::new_now();
+    let mut first_sound = true;
     let mut time_at_change = RealTimestamp::zero();
     let mut track_toggle = false;
+    let mut echo = false;
+    #[cfg(not(target_arch = "wasm32"))]
+    let mut time_at_cpu_report = RealTimestamp::zero();
+
+    let frame_rate = jamjar::windowing::refresh_rate(&window).unwrap_or(60) as f32;
+
+    // Nothing moves until the mixer is started, so sleep until there's input
+    let mut redraw = RedrawScheduler::new(RedrawPolicy::OnEvent);
 
     event_loop.run(move |event, _, control_flow| {
-        use jamjar::windowing::event::{ElementState, Event, WindowEvent};
+        use jamjar::windowing::event::{ElementState, Event, VirtualKeyCode, WindowEvent};
+
+        redraw.handle_event(&event);
 
         match event {
+            // Everything sent to the mixer until `commit_frame` is applied
+            // together, with the sound played on a key press starting at
+            // the volumes set in the same frame
+            Event::NewEvents(_) => mixer.begin_frame(),
             Event::WindowEvent { event, .. } => match event {
                 WindowEvent::CloseRequested => {
                     *control_flow = jamjar::windowing::event_loop::ControlFlow::Exit
                 }
                 WindowEvent::KeyboardInput { input, .. } => {
                     if let ElementState::Pressed = input.state {
-                        if mixer.initialized() {
+                        if mixer.initialized() && input.virtual_keycode == Some(VirtualKeyCode::E) {
+                            echo = !echo;
+                            jamjar::jprintln!("Echo: {}", echo);
+                        } else if input.virtual_keycode == Some(VirtualKeyCode::D) {
+                            jamjar::jprintln!("{:#?}", mixer.diagnose());
+                        } else {
+                            let mut timer = RealClock::new_now();
+                            if !mixer.initialized() {
+                                mixer.init();
+                            }
+                            redraw.set_policy(RedrawPolicy::AtMost(frame_rate));
+
                             time_at_change = clock.now();
                             track_toggle = !track_toggle;
 
-                            mixer.play_sound(Sound {
-                                key: Audio::Chime,
-                                volume: 1.0,
-                                speed: 1.0,
-                            });
-                        } else {
-                            mixer.init();
+                            mixer.play_sound(Sound::new(Audio::Chime));
+
+                            if first_sound {
+                                first_sound = false;
+
+                                // Queries send the frame so far ahead of them,
+                                // so this returns once the sound has started
+                                let config = mixer.output_config();
+                                jamjar::jprintln!(
+                                    "First sound started after {:.1}ms ({})",
+                                    timer.update() * 1000.,
+                                    if cold { "cold" } else { "prewarmed" }
+                                );
+
+                                if let Some(config) = config {
+                                    let buffer_latency_ms = config.buffer_size.map(|size| {
+                                        size as f64 * 1000. / config.sample_rate as f64
+                                    });
+                                    jamjar::jprintln!(
+                                        "Output config: {:?} (buffer latency: {:?}ms)",
+                                        config,
+                                        buffer_latency_ms
+                                    );
+                                }
+                            }
                         }
                     }
                 }
@@ -57,8 +145,10 @@ fn main() {
             Event::MainEventsCleared => {
                 clock.update();
 
-                let fade_in = clock.since(time_at_change).min(1.0) as f32;
-                let fade_out = 1.0 - fade_in;
+                // Equal-power curves avoid the dip in loudness halfway through
+                let progress = clock.since(time_at_change).min(1.0) as f32;
+                let fade_in = FadeCurve::EqualPower.fade_in(progress);
+                let fade_out = FadeCurve::EqualPower.fade_out(progress);
                 let volume0 = if track_toggle { fade_out } else { fade_in };
                 let volume1 = if track_toggle { fade_in } else { fade_out };
 
@@ -68,28 +158,51 @@ fn main() {
                         track_volume: 1.0,
                         tracks: &[
                             Track {
-                                key: Audio::Groove,
                                 volume: volume0,
                                 playing: volume0 > 0.0,
-                                looping: true,
+                                echo: match echo {
+                                    true => Some(Echo {
+                                        delay: std::time::Duration::from_millis(375),
+                                        feedback: 0.4,
+                                        mix: 0.5,
+                                    }),
+                                    false => None,
+                                },
                                 feedback_rate: Some(std::time::Duration::from_secs_f64(60. / 80.)),
+                                ..Track::new(Audio::Groove)
                             },
                             Track {
-                                key: Audio::Duelling,
                                 volume: volume1,
                                 playing: volume1 > 0.0,
                                 looping: false,
-                                feedback_rate: None,
+                                ..Track::new(Audio::Duelling)
                             },
                         ],
                     });
                 }
+                mixer.commit_frame();
 
                 for feedback in mixer.feedback() {
                     jamjar::jprintln!("Got feedback from mixer track {}!!!", feedback);
                 }
 
-                window.request_redraw();
+                for event in mixer.events() {
+                    jamjar::jprintln!("Mixer event: {:?}", event);
+                }
+
+                #[cfg(not(target_arch = "wasm32"))]
+                if mixer.initialized() && clock.since(time_at_cpu_report) >= 5.0 {
+                    time_at_cpu_report = clock.now();
+                    if let Some(busy) = mixer.cpu_estimate() {
+                        jamjar::jprintln!(
+                            "Audio sources took {:.2}ms per second{}",
+                            busy.as_secs_f64() * 1000.,
+                            if low_end { " (low-end)" } else { "" }
+                        );
+                    }
+                }
+
+                redraw.update(&window, control_flow);
             }
             Event::RedrawRequested(_) => {}
             _ => (),
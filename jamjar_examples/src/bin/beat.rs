@@ -0,0 +1,122 @@
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen(start)]
+pub fn wasm_main() {
+    main();
+}
+
+const GROOVE_BPM: f32 = 80.;
+const FLASH_SECS: f64 = 0.15;
+
+fn main() {
+    use jamjar_examples::gen::{data::VOLUMES, Audio};
+
+    use jamjar::{
+        audio::{AudioState, Mixer, MixerEvent, MixerOptions, Track},
+        draw::{backend, groove::DrawContext, CanvasConfig},
+        timing::{RealClock, RealTimestamp},
+        windowing::{self, RedrawPolicy, RedrawScheduler},
+    };
+
+    jamjar::logging::init_logging();
+
+    let resolution = [256, 256];
+
+    let (window, event_loop) = windowing::window_and_event_loop("Beat Test", resolution).unwrap();
+
+    let mut context = DrawContext::<backend::Whatever>::new(
+        &window,
+        CanvasConfig::pixel_scaled(resolution),
+        image::RgbaImage::new(1024, 1024),
+    )
+    .unwrap();
+
+    let audio_library =
+        jamjar::resources::map_audio_resources(Audio::ALL, &jamjar::resource_list!("assets/audio"));
+
+    let mut mixer = Mixer::new(
+        audio_library,
+        Some(VOLUMES.clone()),
+        MixerOptions::default(),
+    );
+    mixer.set_track_bpm(0, GROOVE_BPM, std::time::Duration::from_secs(0));
+
+    let mut clock = RealClock::new_now();
+    let mut last_beat = RealTimestamp::zero();
+    let mut playing = true;
+    let mut speed = 1.0;
+
+    // While minimized, drawing stops but the music keeps getting updates
+    let mut redraw = RedrawScheduler::new(RedrawPolicy::Continuous);
+
+    jamjar::jprintln!(
+        "Press any key to start the music, and again to pause it. Press S to slow it down."
+    );
+
+    event_loop.run(move |event, _, control_flow| {
+        use windowing::event::{ElementState, Event, VirtualKeyCode, WindowEvent};
+
+        redraw.handle_event(&event);
+
+        match event {
+            Event::WindowEvent { event, .. } => match event {
+                WindowEvent::CloseRequested => {
+                    *control_flow = windowing::event_loop::ControlFlow::Exit
+                }
+                WindowEvent::Resized(dims) => {
+                    context.resolution_changed(dims.into());
+                }
+                WindowEvent::ScaleFactorChanged {
+                    scale_factor,
+                    new_inner_size,
+                } => {
+                    context.scale_factor_changed(scale_factor, (*new_inner_size).into());
+                }
+                WindowEvent::KeyboardInput { input, .. } => {
+                    if let ElementState::Pressed = input.state {
+                        if !mixer.initialized() {
+                            mixer.init();
+                        } else if input.virtual_keycode == Some(VirtualKeyCode::S) {
+                            speed = if speed < 1.0 { 1.0 } else { 0.5 };
+                        } else {
+                            playing = !playing;
+                        }
+                    }
+                }
+                _ => (),
+            },
+            Event::MainEventsCleared => {
+                clock.update();
+                redraw.update(&window, control_flow);
+
+                if let Some(visibility) = redraw.take_visibility_change() {
+                    jamjar::jprintln!("Window is now {:?}", visibility);
+                }
+
+                if mixer.initialized() {
+                    mixer.update_state(AudioState {
+                        sound_volume: 1.0,
+                        track_volume: 1.0,
+                        tracks: &[Track {
+                            playing,
+                            speed,
+                            ..Track::new(Audio::Groove)
+                        }],
+                    });
+                }
+
+                for event in mixer.events() {
+                    if let MixerEvent::Beat { beat_number, .. } = event {
+                        jamjar::jprintln!("Beat {}: {:?}", beat_number, mixer.beat_clock(0));
+                        last_beat = clock.now();
+                    }
+                }
+            }
+            Event::RedrawRequested(_) => {
+                let flash = 1. - (clock.since(last_beat) / FLASH_SECS).min(1.) as f32;
+                let color = [0.1 + 0.8 * flash, 0.1 + 0.5 * flash, 0.2, 1.];
+                context.start_rendering(color);
+            }
+            _ => (),
+        }
+    });
+}
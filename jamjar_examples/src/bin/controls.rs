@@ -0,0 +1,193 @@
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen(start)]
+pub fn wasm_main() {
+    main();
+}
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+enum Action {
+    Left,
+    Right,
+    Jump,
+}
+
+const ACTIONS: &[Action] = &[Action::Left, Action::Right, Action::Jump];
+
+#[cfg(not(target_arch = "wasm32"))]
+const CONTROLS_PATH: &str = "controls.yaml";
+
+fn default_controls() -> jamjar::input::ActionMap<Action> {
+    use jamjar::input::{ActionMap, AxisDirection, Binding, GamepadAxis, GamepadButton, Key};
+
+    ActionMap::with_bindings(vec![
+        (Action::Left, Binding::Key(Key::A)),
+        (Action::Left, Binding::Key(Key::Left)),
+        (
+            Action::Left,
+            Binding::GamepadAxis(GamepadAxis::LeftStickX, AxisDirection::Negative),
+        ),
+        (
+            Action::Left,
+            Binding::GamepadButton(GamepadButton::DPadLeft),
+        ),
+        (Action::Right, Binding::Key(Key::D)),
+        (Action::Right, Binding::Key(Key::Right)),
+        (
+            Action::Right,
+            Binding::GamepadAxis(GamepadAxis::LeftStickX, AxisDirection::Positive),
+        ),
+        (
+            Action::Right,
+            Binding::GamepadButton(GamepadButton::DPadRight),
+        ),
+        (Action::Jump, Binding::Key(Key::Space)),
+        (Action::Jump, Binding::GamepadButton(GamepadButton::South)),
+    ])
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn load_controls() -> jamjar::input::ActionMap<Action> {
+    std::fs::read_to_string(CONTROLS_PATH)
+        .ok()
+        .and_then(|source| serde_yaml::from_str(&source).ok())
+        .unwrap_or_else(default_controls)
+}
+
+#[cfg(target_arch = "wasm32")]
+fn load_controls() -> jamjar::input::ActionMap<Action> {
+    default_controls()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn save_controls(controls: &jamjar::input::ActionMap<Action>) {
+    let source = serde_yaml::to_string(controls).unwrap();
+    std::fs::write(CONTROLS_PATH, source).unwrap();
+}
+
+#[cfg(target_arch = "wasm32")]
+fn save_controls(_controls: &jamjar::input::ActionMap<Action>) {}
+
+fn main() {
+    use jamjar::{
+        atlas::font::FontAtlas,
+        draw::{
+            backend,
+            groove::DrawContext,
+            text::{TextPainter, TextStyle},
+            CanvasConfig,
+        },
+        font::Font,
+        input::{Binding, InputState, Key},
+        windowing,
+    };
+
+    jamjar::logging::init_logging();
+
+    let resolution = [256, 128];
+
+    let (window, event_loop) =
+        windowing::window_and_event_loop("Controls Test", resolution).unwrap();
+
+    let font = Font::new(jamjar::resource!("assets/fonts/chocolate_11.ttf").to_vec());
+
+    let mut font_atlas = FontAtlas::with_size([1024, 1024]).with_pixel_snap();
+
+    let mut context = DrawContext::<backend::Whatever>::new(
+        &window,
+        CanvasConfig::pixel_scaled(resolution),
+        image::RgbaImage::new(1024, 1024),
+    )
+    .unwrap();
+
+    let mut input = InputState::new();
+    let mut controls = load_controls();
+    let mut text_painter = TextPainter::new();
+    let mut selected = 0;
+    let mut player_x = 128.;
+    let mut jumps = 0;
+
+    jamjar::jprintln!("Up/Down to pick an action, Enter to rebind it, Backspace to reset.");
+
+    event_loop.run(move |event, _, control_flow| {
+        use windowing::event::{Event, WindowEvent};
+
+        input.handle_event(&event);
+
+        match event {
+            Event::WindowEvent { event, .. } => match event {
+                WindowEvent::CloseRequested => {
+                    *control_flow = windowing::event_loop::ControlFlow::Exit
+                }
+                WindowEvent::Resized(dims) => {
+                    context.resolution_changed(dims.into());
+                }
+                WindowEvent::ScaleFactorChanged {
+                    scale_factor,
+                    new_inner_size,
+                } => {
+                    context.scale_factor_changed(scale_factor, (*new_inner_size).into());
+                }
+                _ => (),
+            },
+            Event::MainEventsCleared => {
+                if let Some(binding) = input.take_captured() {
+                    let action = ACTIONS[selected];
+                    if binding != Binding::Key(Key::Escape) {
+                        controls.rebind(action, binding);
+                        save_controls(&controls);
+                    }
+                    jamjar::jprintln!("{:?} is bound to {:?}", action, controls.bindings(&action));
+                } else if !input.capturing() {
+                    if input.binding_pressed(Binding::Key(Key::Up)) {
+                        selected = (selected + ACTIONS.len() - 1) % ACTIONS.len();
+                    }
+                    if input.binding_pressed(Binding::Key(Key::Down)) {
+                        selected = (selected + 1) % ACTIONS.len();
+                    }
+                    if input.binding_pressed(Binding::Key(Key::Return)) {
+                        input.start_capture();
+                    }
+                    if input.binding_pressed(Binding::Key(Key::Back)) {
+                        controls = default_controls();
+                        save_controls(&controls);
+                    }
+
+                    player_x += controls.axis(&input, &Action::Left, &Action::Right) * 2.;
+                    player_x = player_x.max(0.).min(248.);
+
+                    if controls.pressed(&input, &Action::Jump) {
+                        jumps += 1;
+                    }
+                }
+
+                input.finish_frame();
+                window.request_redraw();
+            }
+            Event::RedrawRequested(_) => {
+                let ren = context.start_rendering([0.1, 0.1, 0.2, 1.]);
+
+                for (i, action) in ACTIONS.iter().enumerate() {
+                    let cursor = if i == selected { ">" } else { " " };
+                    let bindings = match (i == selected, input.capturing()) {
+                        (true, true) => "press a key or button...".to_owned(),
+                        _ => format!("{:?}", controls.bindings(action)),
+                    };
+                    let label = format!("{} {:?}: {}", cursor, action, bindings);
+                    let y = 16. + 16. * i as f32;
+                    text_painter.draw_text(&font, &label, [8., y], 11., [1., 1., 1., 1.]);
+                }
+
+                let jumped = format!("Jumps: {}", jumps);
+                text_painter.draw_text(&font, &jumped, [8., 80.], 11., [1., 1., 1., 1.]);
+
+                // The player moves by fractions of a pixel, so snap it to keep it sharp
+                let player_style = TextStyle::new(11., [1., 0.8, 0.2, 1.]).pixel_snapped();
+                text_painter.draw_styled(&font, "@", [player_x, 100.], player_style);
+                text_painter.finish(ren, &mut font_atlas);
+            }
+            _ => (),
+        }
+    });
+}
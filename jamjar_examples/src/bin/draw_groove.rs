@@ -7,13 +7,16 @@ pub fn wasm_main() {
 fn main() {
     use jamjar::{
         atlas::{Atlas, FontImageAtlas},
+        debug::Overlay,
         draw::{
             backend,
             groove::{DrawContext, Sprite},
-            CanvasConfig, CanvasMode,
+            shapes,
+            text::TextPainter,
+            CanvasConfig, CanvasMode, Dither, Palette, PresentMode, Space,
         },
         font::Font,
-        windowing,
+        windowing::{self, RedrawPolicy, RedrawScheduler},
     };
 
     jamjar::logging::init_logging();
@@ -42,6 +45,27 @@ fn main() {
         DrawContext::<backend::Whatever>::new(&window, canvas_config, atlas_image.clone()).unwrap();
 
     let mut clock = jamjar::timing::RealClock::new_now();
+    let mut text_painter = TextPainter::new();
+    let mut overlay = Overlay::new();
+    let mut redraw = RedrawScheduler::new(RedrawPolicy::Continuous);
+    let mut frames_drawn = 0;
+    let mut present_mode = PresentMode::Vsync;
+
+    // Each palette with the dither strength that suits it
+    let palettes: [(&str, Palette, f32); 4] = [
+        ("1-bit", Palette::one_bit(), 1.),
+        ("Game Boy", Palette::game_boy(), 0.35),
+        ("CGA", Palette::cga(), 0.5),
+        ("PICO-8", Palette::pico8(), 0.2),
+    ];
+    let mut palette_index = None;
+    let mut dither_scale: f32 = 1.;
+
+    // Don't assume 60Hz: plenty of monitors are faster
+    let refresh_rate = windowing::refresh_rate(&window);
+    for monitor in windowing::monitors(&event_loop) {
+        jamjar::jprintln!("Monitor: {:?}", monitor);
+    }
 
     jamjar::jprintln!(
         r#"Press:
@@ -50,12 +74,25 @@ fn main() {
 3. For pixel scaling
 4. For free scaling
 
-0. To toggle between Direct and Intermediate modes"#
+0. To toggle between Direct and Intermediate modes
+
+M. To switch between redrawing continuously (gameplay), at most 20fps,
+   and only on input (menus)
+
+V. To switch between vsync, no vsync, and adaptive vsync
+
+D. To cycle through dithered palettes
+[ and ]. To weaken or strengthen the dithering
+
+F3. To toggle the debug overlay"#
     );
 
     event_loop.run(move |event, _, control_flow| {
         use windowing::event::{ElementState, Event, VirtualKeyCode, WindowEvent};
 
+        redraw.handle_event(&event);
+        overlay.handle_event(&event);
+
         match event {
             Event::WindowEvent { event, .. } => match event {
                 WindowEvent::CloseRequested => {
@@ -73,6 +110,7 @@ fn main() {
                 WindowEvent::KeyboardInput { input, .. } => {
                     if let ElementState::Pressed = input.state {
                         let mut mode = canvas_config.canvas_mode;
+                        let mut dither_changed = false;
 
                         match input.virtual_keycode {
                             Some(VirtualKeyCode::Key0) => {
@@ -93,9 +131,58 @@ fn main() {
                             Some(VirtualKeyCode::Key4) => {
                                 canvas_config = CanvasConfig::default();
                             }
+                            Some(VirtualKeyCode::M) => {
+                                redraw.set_policy(match redraw.policy() {
+                                    RedrawPolicy::Continuous => RedrawPolicy::AtMost(20.),
+                                    RedrawPolicy::AtMost(_) => RedrawPolicy::OnEvent,
+                                    RedrawPolicy::OnEvent => RedrawPolicy::Continuous,
+                                });
+                                jamjar::jprintln!("Redraw policy: {:?}", redraw.policy());
+                            }
+                            Some(VirtualKeyCode::V) => {
+                                present_mode = match present_mode {
+                                    PresentMode::Vsync => PresentMode::NoVsync,
+                                    PresentMode::NoVsync => PresentMode::Adaptive,
+                                    PresentMode::Adaptive => PresentMode::Vsync,
+                                };
+                                context.set_present_mode(present_mode);
+                                jamjar::jprintln!("Asked for present mode: {:?}", present_mode);
+                            }
+                            Some(VirtualKeyCode::D) => {
+                                palette_index = match palette_index {
+                                    None => Some(0),
+                                    Some(i) if i + 1 < palettes.len() => Some(i + 1),
+                                    Some(_) => None,
+                                };
+                                dither_changed = true;
+                            }
+                            Some(VirtualKeyCode::LBracket) => {
+                                dither_scale = (dither_scale - 0.25).max(0.);
+                                dither_changed = true;
+                            }
+                            Some(VirtualKeyCode::RBracket) => {
+                                dither_scale = (dither_scale + 0.25).min(2.);
+                                dither_changed = true;
+                            }
                             _ => (),
                         }
 
+                        if dither_changed {
+                            let dither = palette_index.map(|i| {
+                                let (name, palette, strength) = &palettes[i];
+                                jamjar::jprintln!(
+                                    "Dithering to {} at {:.2}",
+                                    name,
+                                    strength * dither_scale
+                                );
+                                Dither::new(palette.clone(), strength * dither_scale)
+                            });
+                            if dither.is_none() {
+                                jamjar::jprintln!("Dithering off");
+                            }
+                            context.set_dither(dither);
+                        }
+
                         canvas_config.canvas_mode = mode;
                         context.set_canvas_config(canvas_config);
                         jamjar::jprintln!("Canvas config changed: {:?}", canvas_config);
@@ -104,14 +191,29 @@ fn main() {
                 _ => (),
             },
             Event::MainEventsCleared => {
-                clock.update();
-                window.request_redraw();
+                let dt = clock.update();
+                overlay.record_frame(dt);
+                redraw.update(&window, control_flow);
             }
             Event::RedrawRequested(_) => {
                 let t = (clock.secs() % 8.) as f32 / 8.;
+                frames_drawn += 1;
 
+                // The scene pans back and forth, but the HUD stays put
+                let pan = (clock.secs() as f32 * 0.5).sin() * 128.;
+                context.set_camera([pan, 0.]);
+
+                let present = context.present_mode().unwrap_or(present_mode);
+                let stats = context.stats();
                 let mut ren = context.start_rendering([0.2, 0., 0.4, 1.]);
 
+                ren.sprite(shapes::gradient_rect(
+                    atlas.images.fetch("white"),
+                    ([-128., 0.], [768., 256.]),
+                    [0.2, 0., 0.4, 1.],
+                    [0.05, 0., 0.1, 1.],
+                ));
+
                 for i in 0..8 {
                     let ii = i as f32 / 8.;
                     let it = (t + ii) % 1.;
@@ -161,9 +263,45 @@ fn main() {
                     [3., 3.],
                 ));
 
-                let glyph = font.test_glyph('H', [100., 100.]);
-                ren.glyphs(vec![glyph], [1., 0., 1., 1.]);
-                ren.finish_with_text(&mut atlas.fonts, None);
+                text_painter.set_space(Space::World);
+                text_painter.draw_text(
+                    &font,
+                    "Hello, jamjar!",
+                    [100., 100.],
+                    11.,
+                    [1., 0., 1., 1.],
+                );
+
+                ren.set_space(Space::Screen);
+                ren.sprite(Sprite::sized(
+                    atlas.images.fetch("white"),
+                    [4., 232.],
+                    [0., 0., 0., 0.6],
+                    [240., 20.],
+                ));
+
+                let refresh = match refresh_rate {
+                    Some(rate) => format!("{}Hz", rate),
+                    None => "?Hz".to_owned(),
+                };
+
+                text_painter.set_space(Space::Screen);
+                text_painter.draw_text(
+                    &font,
+                    &format!("Frames: {}  {} {:?}", frames_drawn, refresh, present),
+                    [8., 236.],
+                    11.,
+                    [1., 1., 1., 1.],
+                );
+
+                overlay.draw(
+                    &mut ren,
+                    &mut text_painter,
+                    &font,
+                    atlas.images.fetch("white"),
+                    &[&stats],
+                );
+                text_painter.finish(ren, &mut atlas.fonts);
             }
             _ => (),
         }
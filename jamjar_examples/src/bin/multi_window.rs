@@ -0,0 +1,138 @@
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen(start)]
+pub fn wasm_main() {
+    main();
+}
+
+const ATLAS_SIZE: u32 = 4096;
+const DEBUG_SIZE: u32 = 512;
+
+fn main() {
+    use std::collections::HashMap;
+
+    use jamjar::{
+        atlas::{Atlas, FontImageAtlas},
+        draw::{
+            backend,
+            groove::{DrawContext, Sprite},
+            CanvasConfig, Region,
+        },
+        windowing::{self, WindowSet},
+    };
+
+    jamjar::logging::init_logging();
+
+    let resolution = [256, 256];
+
+    let (window, event_loop) =
+        windowing::window_and_event_loop("Multi-Window Test", resolution).unwrap();
+
+    let white_img = image::load_from_memory(&jamjar::resource!("assets/images/white.png"))
+        .unwrap()
+        .to_rgba8();
+    let bubble_img = image::load_from_memory(&jamjar::resource!("assets/images/bubble.png"))
+        .unwrap()
+        .to_rgba8();
+
+    let mut atlas_image = image::RgbaImage::new(ATLAS_SIZE, ATLAS_SIZE);
+    let mut atlas = FontImageAtlas::new([ATLAS_SIZE, ATLAS_SIZE], 1024);
+    atlas.images.insert(("white".to_owned(), white_img));
+    atlas.images.insert(("bubble".to_owned(), bubble_img));
+    atlas.compile_into(&mut atlas_image);
+
+    let mut contexts = HashMap::new();
+    contexts.insert(
+        window.id(),
+        DrawContext::<backend::Whatever>::new(
+            &window,
+            CanvasConfig::pixel_scaled(resolution),
+            atlas_image.clone(),
+        )
+        .unwrap(),
+    );
+
+    let mut windows = WindowSet::new(window);
+    let mut clock = jamjar::timing::RealClock::new_now();
+
+    jamjar::jprintln!("Press D to open the atlas debug window.");
+
+    event_loop.run(move |event, event_loop, control_flow| {
+        use windowing::event::{ElementState, Event, VirtualKeyCode, WindowEvent};
+
+        if let Some((closed, window)) = windows.handle_event(&event, control_flow) {
+            contexts.remove(&closed);
+            drop(window);
+        }
+
+        match event {
+            Event::WindowEvent { window_id, event } => match event {
+                WindowEvent::Resized(dims) => {
+                    if let Some(context) = contexts.get_mut(&window_id) {
+                        context.resolution_changed(dims.into());
+                    }
+                }
+                WindowEvent::ScaleFactorChanged {
+                    scale_factor,
+                    new_inner_size,
+                } => {
+                    if let Some(context) = contexts.get_mut(&window_id) {
+                        context.scale_factor_changed(scale_factor, (*new_inner_size).into());
+                    }
+                }
+                WindowEvent::KeyboardInput { input, .. } => {
+                    if let (ElementState::Pressed, Some(VirtualKeyCode::D)) =
+                        (input.state, input.virtual_keycode)
+                    {
+                        let id = windows
+                            .create(event_loop, "Atlas Debug", [DEBUG_SIZE, DEBUG_SIZE])
+                            .unwrap();
+                        let context = DrawContext::<backend::Whatever>::new(
+                            windows.get(id).unwrap(),
+                            CanvasConfig::pixel_scaled([DEBUG_SIZE, DEBUG_SIZE]),
+                            atlas_image.clone(),
+                        )
+                        .unwrap();
+                        contexts.insert(id, context);
+                    }
+                }
+                _ => (),
+            },
+            Event::MainEventsCleared => {
+                clock.update();
+                windows.request_redraw_all();
+            }
+            Event::RedrawRequested(window_id) => {
+                let context = match contexts.get_mut(&window_id) {
+                    Some(context) => context,
+                    None => return,
+                };
+
+                if windows.is_primary(window_id) {
+                    let t = clock.secs() as f32;
+                    let mut ren = context.start_rendering([0.1, 0.1, 0.2, 1.]);
+                    let x = 112. + (t * 2.).cos() * 64.;
+                    let y = 112. + (t * 2.).sin() * 64.;
+                    ren.sprite(Sprite::scaled(
+                        atlas.images.fetch("bubble"),
+                        [x, y],
+                        [1., 1., 1., 1.],
+                        [2., 2.],
+                    ));
+                } else {
+                    // Show the top-left corner of the atlas, where the
+                    // images are packed.
+                    let visible = DEBUG_SIZE as f32 / ATLAS_SIZE as f32;
+                    let mut ren = context.start_rendering([0.3, 0., 0.3, 1.]);
+                    ren.sprite(Sprite::new(
+                        Region::new(
+                            ([0, 0], [DEBUG_SIZE, DEBUG_SIZE]),
+                            ([0., 0.], [visible, visible]),
+                        ),
+                        [0., 0.],
+                    ));
+                }
+            }
+            _ => (),
+        }
+    });
+}
@@ -0,0 +1,82 @@
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen(start)]
+pub fn wasm_main() {
+    main();
+}
+
+fn main() {
+    use jamjar_examples::gen::data::{MUSIC, VOLUMES};
+
+    use jamjar::{
+        audio::{AudioState, Mixer, MixerOptions, MusicDirector},
+        windowing::{RedrawPolicy, RedrawScheduler},
+    };
+
+    jamjar::logging::init_logging();
+
+    let (window, event_loop) =
+        jamjar::windowing::window_and_event_loop("Music Test", [512, 256]).unwrap();
+
+    let audio_library = jamjar::resources::map_audio_resources(
+        jamjar_examples::gen::Audio::ALL,
+        &jamjar::resource_list!("assets/audio"),
+    );
+
+    let mut mixer = Mixer::new(
+        audio_library,
+        Some(VOLUMES.clone()),
+        MixerOptions::default(),
+    );
+    let mut music = MusicDirector::new(MUSIC.clone(), 0);
+
+    // Nothing is drawn, so only wake up for input
+    let mut redraw = RedrawScheduler::new(RedrawPolicy::OnEvent);
+
+    jamjar::jprintln!("Press any key to start, then 1, 2, or 3 to change the music.");
+
+    event_loop.run(move |event, _, control_flow| {
+        use jamjar::windowing::event::{ElementState, Event, VirtualKeyCode, WindowEvent};
+
+        redraw.handle_event(&event);
+
+        match event {
+            Event::WindowEvent { event, .. } => match event {
+                WindowEvent::CloseRequested => {
+                    *control_flow = jamjar::windowing::event_loop::ControlFlow::Exit
+                }
+                WindowEvent::KeyboardInput { input, .. } => {
+                    if let ElementState::Pressed = input.state {
+                        let state = match input.virtual_keycode {
+                            Some(VirtualKeyCode::Key1) => Some("calm"),
+                            Some(VirtualKeyCode::Key2) => Some("tense"),
+                            Some(VirtualKeyCode::Key3) => Some("duel"),
+                            _ => None,
+                        };
+
+                        if !mixer.initialized() {
+                            mixer.init();
+                            music.set_state("calm", &mut mixer);
+                        } else if let Some(state) = state {
+                            music.set_state(state, &mut mixer);
+                            jamjar::jprintln!("Music state: {}", state);
+                        }
+                    }
+                }
+                _ => (),
+            },
+            Event::MainEventsCleared => {
+                if mixer.initialized() {
+                    mixer.update_state(AudioState {
+                        sound_volume: 1.0,
+                        track_volume: 1.0,
+                        tracks: &music.tracks(),
+                    });
+                }
+
+                redraw.update(&window, control_flow);
+            }
+            Event::RedrawRequested(_) => {}
+            _ => (),
+        }
+    });
+}
@@ -0,0 +1,155 @@
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen(start)]
+pub fn wasm_main() {
+    main();
+}
+
+fn main() {
+    use jamjar::{
+        atlas::{Atlas, FontImageAtlas},
+        draw::{
+            backend,
+            groove::DrawContext,
+            particles::{Curve, EmitterConfig},
+            CanvasConfig, Particles, ScreenEffects,
+        },
+        windowing,
+    };
+
+    jamjar::logging::init_logging();
+
+    let resolution = [512, 256];
+
+    let (window, event_loop) =
+        windowing::window_and_event_loop("Particles Test", resolution).unwrap();
+
+    let white_img = image::load_from_memory(&jamjar::resource!("assets/images/white.png"))
+        .unwrap()
+        .to_rgba8();
+    let bubble_img = image::load_from_memory(&jamjar::resource!("assets/images/bubble.png"))
+        .unwrap()
+        .to_rgba8();
+
+    let mut atlas_image = image::RgbaImage::new(4096, 4096);
+    let mut atlas = FontImageAtlas::new([4096, 4096], 1024);
+    atlas.images.insert(("white".to_owned(), white_img));
+    atlas.images.insert(("bubble".to_owned(), bubble_img));
+    atlas.compile_into(&mut atlas_image);
+
+    let canvas_config = CanvasConfig::pixel_scaled(resolution);
+    let mut context =
+        DrawContext::<backend::Whatever>::new(&window, canvas_config, atlas_image).unwrap();
+
+    let mut fountain = Particles::new(
+        EmitterConfig {
+            rate: 1500.,
+            lifetime: (1.5, 2.5),
+            speed: (150., 250.),
+            direction: -std::f32::consts::FRAC_PI_2,
+            spread: 0.3,
+            gravity: [0., 200.],
+            size: Curve::Linear(0.25, 0.05),
+            alpha: Curve::Keys(vec![(0., 0.), (0.1, 1.), (0.7, 1.), (1., 0.)]),
+            tint: [0.4, 0.7, 1., 1.],
+            frames: vec![atlas.images.fetch("bubble")],
+        },
+        [128., 240.],
+        4000,
+    )
+    .with_seed(7);
+
+    let mut confetti = Particles::new(
+        EmitterConfig {
+            rate: 0.,
+            lifetime: (1., 3.),
+            speed: (20., 200.),
+            direction: 0.,
+            spread: std::f32::consts::PI,
+            gravity: [0., 60.],
+            size: Curve::Constant(0.25),
+            alpha: Curve::Linear(1., 0.),
+            tint: [1., 0.8, 0.2, 1.],
+            frames: vec![atlas.images.fetch("white")],
+        },
+        [384., 128.],
+        2000,
+    )
+    .with_seed(42);
+
+    context.install_screen_effects(ScreenEffects::new(), atlas.images.fetch("white"));
+    let mut faded = false;
+
+    let mut clock = jamjar::timing::RealClock::new_now();
+    let mut frames = 0;
+    let mut last_report = 0.;
+
+    jamjar::jprintln!("Press Space for a burst of confetti, or F to fade in and out.");
+
+    event_loop.run(move |event, _, control_flow| {
+        use windowing::event::{ElementState, Event, VirtualKeyCode, WindowEvent};
+
+        match event {
+            Event::WindowEvent { event, .. } => match event {
+                WindowEvent::CloseRequested => {
+                    *control_flow = windowing::event_loop::ControlFlow::Exit
+                }
+                WindowEvent::Resized(dims) => {
+                    context.resolution_changed(dims.into());
+                }
+                WindowEvent::ScaleFactorChanged {
+                    scale_factor,
+                    new_inner_size,
+                } => {
+                    context.scale_factor_changed(scale_factor, (*new_inner_size).into());
+                }
+                WindowEvent::KeyboardInput { input, .. } => {
+                    if input.state == ElementState::Pressed
+                        && input.virtual_keycode == Some(VirtualKeyCode::Space)
+                    {
+                        confetti.burst(1000);
+
+                        let effects = context.screen_effects().unwrap();
+                        effects.shake(6., 0.4, 20.);
+                        effects.flash([1., 1., 1., 0.6], 0.2);
+                    }
+
+                    if input.state == ElementState::Pressed
+                        && input.virtual_keycode == Some(VirtualKeyCode::F)
+                    {
+                        let effects = context.screen_effects().unwrap();
+                        match faded {
+                            false => effects.fade_to([0., 0., 0., 1.], 1.),
+                            true => effects.fade_from([0., 0., 0., 1.], 1.),
+                        }
+                        faded = !faded;
+                    }
+                }
+                _ => (),
+            },
+            Event::MainEventsCleared => {
+                let dt = clock.update() as f32;
+                fountain.update(dt);
+                confetti.update(dt);
+                context.screen_effects().unwrap().update(dt);
+                window.request_redraw();
+
+                frames += 1;
+                if clock.secs() - last_report >= 1. {
+                    jamjar::jprintln!(
+                        "{} fps, {} particles",
+                        frames,
+                        fountain.len() + confetti.len()
+                    );
+                    frames = 0;
+                    last_report = clock.secs();
+                }
+            }
+            Event::RedrawRequested(_) => {
+                let mut ren = context.start_rendering([0.05, 0., 0.1, 1.]);
+                fountain.draw(&mut ren);
+                confetti.draw(&mut ren);
+            }
+            _ => (),
+        }
+    });
+}
@@ -7,6 +7,10 @@ fn main() {
         eprintln!("{:#?}", &&**NUMERI);
         eprintln!("config = {:#?}", &&**CONFIG);
 
+        for error in jamjar::reloading::current_errors() {
+            eprintln!("Still using old data for {}: {}", error.file, error.message);
+        }
+
         eprintln!("You can edit assets/numbers.yaml or assets/numeri.toml and it'll live reload.");
 
         let wait = 5;
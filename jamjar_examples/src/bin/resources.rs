@@ -1,17 +1,41 @@
 use jamjar_examples::gen::Image;
 
-use jamjar::{resource, resource_list};
+use jamjar::{
+    resource, resource_list,
+    resources::{KeyMode, ResourceMap},
+};
 
 fn main() {
     let heart = include_bytes!("../../assets/images/heart.png");
     let star = include_bytes!("../../assets/images/star.png");
     let target = include_bytes!("../../assets/images/target.png");
 
-    let images = jamjar::resources::map_resources(Image::ALL, &resource_list!("assets/images"));
+    jamjar::resources::verify_resources(
+        KeyMode::Strict,
+        Image::ALL,
+        &resource_list!("assets/images"),
+        &[] as &[&str],
+    )
+    .unwrap_or_else(|e| panic!("{}", e));
+
+    let images = jamjar::resources::map_resources_with(
+        KeyMode::Strict,
+        Image::ALL,
+        &resource_list!("assets/images"),
+    )
+    .unwrap();
 
     assert_eq!(&*images[&Image::Heart], heart);
     assert_eq!(&*images[&Image::Star], star);
     assert_eq!(&*images[&Image::Target], target);
 
+    let named = ResourceMap::new(
+        KeyMode::Normalized,
+        jamjar::resources::stringly_map_resources(&resource_list!("assets/images")),
+    )
+    .unwrap();
+
+    assert_eq!(&**named.get("Heart").unwrap(), heart);
+
     eprintln!("Resources loaded ok!");
 }
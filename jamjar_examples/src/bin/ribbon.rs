@@ -0,0 +1,110 @@
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen(start)]
+pub fn wasm_main() {
+    main();
+}
+
+fn main() {
+    use jamjar::{
+        atlas::{Atlas, FontImageAtlas},
+        draw::{
+            backend,
+            groove::DrawContext,
+            particles::Curve,
+            ribbon::{Join, RibbonConfig},
+            CanvasConfig, Ribbon,
+        },
+        input::InputState,
+        windowing,
+    };
+
+    jamjar::logging::init_logging();
+
+    let resolution = [512, 256];
+
+    let (window, event_loop) = windowing::window_and_event_loop("Ribbon Test", resolution).unwrap();
+
+    let white_img = image::load_from_memory(&jamjar::resource!("assets/images/white.png"))
+        .unwrap()
+        .to_rgba8();
+
+    let mut atlas_image = image::RgbaImage::new(1024, 1024);
+    let mut atlas = FontImageAtlas::new([1024, 1024], 256);
+    atlas.images.insert(("white".to_owned(), white_img));
+    atlas.compile_into(&mut atlas_image);
+
+    let canvas_config = CanvasConfig::pixel_scaled(resolution);
+    let mut context =
+        DrawContext::<backend::Whatever>::new(&window, canvas_config, atlas_image).unwrap();
+
+    let mut input = InputState::new();
+    input.set_canvas(&window, canvas_config);
+
+    let mut ribbon = Ribbon::new(
+        RibbonConfig {
+            region: atlas.images.fetch("white"),
+            tint: [0.4, 0.8, 1., 1.],
+            width: Curve::Linear(12., 0.),
+            alpha: Curve::Keys(vec![(0., 1.), (0.5, 0.8), (1., 0.)]),
+            max_age: Some(0.4),
+            max_length: Some(300.),
+            join: Join::Round,
+        },
+        128,
+    );
+
+    let mut clock = jamjar::timing::RealClock::new_now();
+
+    jamjar::jprintln!(
+        "Move the mouse to draw a trail. Press J to switch between round and miter joins."
+    );
+
+    event_loop.run(move |event, _, control_flow| {
+        use windowing::event::{ElementState, Event, VirtualKeyCode, WindowEvent};
+
+        input.handle_event(&event);
+
+        match event {
+            Event::WindowEvent { event, .. } => match event {
+                WindowEvent::CloseRequested => {
+                    *control_flow = windowing::event_loop::ControlFlow::Exit
+                }
+                WindowEvent::Resized(dims) => {
+                    context.resolution_changed(dims.into());
+                }
+                WindowEvent::ScaleFactorChanged {
+                    scale_factor,
+                    new_inner_size,
+                } => {
+                    context.scale_factor_changed(scale_factor, (*new_inner_size).into());
+                }
+                WindowEvent::KeyboardInput { input, .. } => {
+                    if input.state == ElementState::Pressed
+                        && input.virtual_keycode == Some(VirtualKeyCode::J)
+                    {
+                        ribbon.config.join = match ribbon.config.join {
+                            Join::Round => Join::Miter,
+                            Join::Miter => Join::Round,
+                        };
+                        jamjar::jprintln!("{:?} joins", ribbon.config.join);
+                    }
+                }
+                _ => (),
+            },
+            Event::MainEventsCleared => {
+                let dt = clock.update() as f32;
+                ribbon.update(dt);
+                if let Some(pos) = input.mouse_position() {
+                    ribbon.push(pos);
+                }
+                input.finish_frame();
+                window.request_redraw();
+            }
+            Event::RedrawRequested(_) => {
+                let mut ren = context.start_rendering([0.05, 0., 0.1, 1.]);
+                ribbon.draw(&mut ren);
+            }
+            _ => (),
+        }
+    });
+}
@@ -0,0 +1,108 @@
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen(start)]
+pub fn wasm_main() {
+    main();
+}
+
+const MAX_NAME_LENGTH: usize = 16;
+
+fn main() {
+    use jamjar::{
+        atlas::font::FontAtlas,
+        draw::{backend, groove::DrawContext, text::TextPainter, CanvasConfig},
+        font,
+        input::{InputState, TextInputEvent},
+        windowing,
+    };
+
+    jamjar::logging::init_logging();
+
+    let resolution = [256, 128];
+
+    let (window, event_loop) =
+        windowing::window_and_event_loop("Text Input Test", resolution).unwrap();
+
+    let font =
+        font::load_font(&jamjar::resource_list!("assets/fonts"), "chocolate_11.ttf").unwrap();
+
+    let mut font_atlas = FontAtlas::with_size([1024, 1024]);
+
+    let mut context = DrawContext::<backend::Whatever>::new(
+        &window,
+        CanvasConfig::pixel_scaled(resolution),
+        image::RgbaImage::new(1024, 1024),
+    )
+    .unwrap();
+
+    let mut input = InputState::new();
+    let mut text_painter = TextPainter::new();
+    let mut name = String::new();
+
+    jamjar::jprintln!("Click the window or press Enter to edit your name.");
+
+    event_loop.run(move |event, _, control_flow| {
+        use windowing::event::{ElementState, Event, VirtualKeyCode, WindowEvent};
+
+        input.handle_event(&event);
+
+        match event {
+            Event::WindowEvent { event, .. } => match event {
+                WindowEvent::CloseRequested => {
+                    *control_flow = windowing::event_loop::ControlFlow::Exit
+                }
+                WindowEvent::Resized(dims) => {
+                    context.resolution_changed(dims.into());
+                }
+                WindowEvent::ScaleFactorChanged {
+                    scale_factor,
+                    new_inner_size,
+                } => {
+                    context.scale_factor_changed(scale_factor, (*new_inner_size).into());
+                }
+                WindowEvent::MouseInput {
+                    state: ElementState::Pressed,
+                    ..
+                } => {
+                    input.start_text_input();
+                }
+                WindowEvent::KeyboardInput { input: key, .. } => {
+                    if key.state == ElementState::Pressed
+                        && key.virtual_keycode == Some(VirtualKeyCode::Return)
+                    {
+                        if input.text_input_active() {
+                            input.stop_text_input();
+                            jamjar::jprintln!("Name entered: {}", name);
+                        } else {
+                            input.start_text_input();
+                        }
+                    }
+                }
+                _ => (),
+            },
+            Event::MainEventsCleared => {
+                for event in input.text_input() {
+                    match event {
+                        TextInputEvent::Char(ch) if name.chars().count() < MAX_NAME_LENGTH => {
+                            name.push(ch)
+                        }
+                        TextInputEvent::Backspace => {
+                            name.pop();
+                        }
+                        _ => (),
+                    }
+                }
+
+                window.request_redraw();
+            }
+            Event::RedrawRequested(_) => {
+                let cursor = if input.text_input_active() { "_" } else { "" };
+                let label = format!("Name: {}{}", name, cursor);
+
+                let ren = context.start_rendering([0.1, 0.1, 0.2, 1.]);
+                text_painter.draw_text(&font, &label, [16., 56.], 11., [1., 1., 1., 1.]);
+                text_painter.finish(ren, &mut font_atlas);
+            }
+            _ => (),
+        }
+    });
+}
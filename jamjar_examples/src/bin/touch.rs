@@ -0,0 +1,127 @@
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen(start)]
+pub fn wasm_main() {
+    main();
+}
+
+fn main() {
+    use jamjar::{
+        atlas::{image::ImageAtlas, Atlas},
+        draw::{
+            backend,
+            groove::{DrawContext, Sprite},
+            CanvasConfig, Space,
+        },
+        input::{Binding, InputState, MouseButton, PointerId},
+        windowing,
+    };
+
+    jamjar::logging::init_logging();
+
+    let resolution = [512, 256];
+
+    let (window, event_loop) = windowing::window_and_event_loop("Touch Test", resolution).unwrap();
+
+    let bubble_img = image::load_from_memory(&jamjar::resource!("assets/images/bubble.png"))
+        .unwrap()
+        .to_rgba8();
+
+    let mut atlas_image = image::RgbaImage::new(1024, 1024);
+    let mut atlas = ImageAtlas::<str>::with_size([1024, 1024]);
+    atlas.insert(("bubble".to_owned(), bubble_img));
+    atlas.compile_into(&mut atlas_image);
+
+    let canvas_config = CanvasConfig::set_scaled(resolution);
+    let mut context =
+        DrawContext::<backend::Whatever>::new(&window, canvas_config, atlas_image).unwrap();
+
+    let mut input = InputState::new();
+    input.set_canvas(&window, canvas_config);
+
+    let mut camera = [0., 0.];
+    let mut zoom = 1.;
+    let mut pointers = vec![];
+
+    jamjar::jprintln!(
+        "Touch (or click) to draw circles. Drag with two fingers to pan, and pinch to resize."
+    );
+
+    event_loop.run(move |event, _, control_flow| {
+        use windowing::event::{Event, WindowEvent};
+
+        input.handle_event(&event);
+
+        match event {
+            Event::WindowEvent { event, .. } => match event {
+                WindowEvent::CloseRequested => {
+                    *control_flow = windowing::event_loop::ControlFlow::Exit
+                }
+                WindowEvent::Resized(dims) => {
+                    context.resolution_changed(dims.into());
+                }
+                WindowEvent::ScaleFactorChanged {
+                    scale_factor,
+                    new_inner_size,
+                } => {
+                    context.scale_factor_changed(scale_factor, (*new_inner_size).into());
+                }
+                _ => (),
+            },
+            Event::MainEventsCleared => {
+                if let Some([dx, dy]) = input.two_finger_pan() {
+                    camera = [camera[0] - dx, camera[1] - dy];
+                }
+                if let (Some(delta), Some(distance)) = (input.pinch_delta(), input.pinch_distance())
+                {
+                    if distance > 0. {
+                        zoom = (zoom * distance / (distance - delta)).max(0.25).min(4.);
+                    }
+                }
+                if input.binding_pressed(Binding::Mouse(MouseButton::Left)) {
+                    jamjar::jprintln!("Pressed at {:?}", input.mouse_position());
+                }
+
+                pointers = input.pointers().to_vec();
+                input.finish_frame();
+                window.request_redraw();
+            }
+            Event::RedrawRequested(_) => {
+                context.set_camera(camera);
+                let mut ren = context.start_rendering([0.1, 0.1, 0.2, 1.]);
+
+                // A grid in the world, to show the camera moving
+                for x in 0..16 {
+                    for y in 0..8 {
+                        ren.sprite(Sprite::scaled(
+                            atlas.fetch("bubble"),
+                            [x as f32 * 32. + 8., y as f32 * 32. + 8.],
+                            [0.3, 0.3, 0.5, 1.],
+                            [1., 1.],
+                        ));
+                    }
+                }
+
+                ren.set_space(Space::Screen);
+                for pointer in &pointers {
+                    let color = match pointer.id {
+                        PointerId::Mouse => [1., 1., 1., 1.],
+                        PointerId::Touch(id) => {
+                            let hue = (id % 6) as f32 / 6.;
+                            [hue, (hue + 0.33) % 1., (hue + 0.66) % 1., 1.]
+                        }
+                    };
+                    let size = 48. * zoom;
+                    let [x, y] = pointer.position;
+
+                    ren.sprite(Sprite::scaled(
+                        atlas.fetch("bubble"),
+                        [x - size / 2., y - size / 2.],
+                        color,
+                        [size / 16., size / 16.],
+                    ));
+                }
+            }
+            _ => (),
+        }
+    });
+}
@@ -18,5 +18,7 @@ jamjar::static_data_mod! {
         static CONFIG: Config = load_config("assets/config.toml");
 
         static VOLUMES: HashMap<Audio, f32> = load_volumes("assets/volumes.yaml");
+
+        static MUSIC: jamjar::audio::MusicSpec<Audio> = load_music("assets/music.toml");
     }
 }
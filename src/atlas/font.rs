@@ -1,12 +1,109 @@
+use std::collections::HashMap;
+
 use image::RgbaImage;
 use rusttype::gpu_cache::Cache;
 
 use crate::{atlas::Atlas, draw::GlyphRegion, font::Glyph};
 
+pub trait OutlineAtlas {
+    fn fetch_outline(&self, key: &Glyph) -> Option<GlyphRegion>;
+}
+
+/// There wasn't room for the outline space asked for in
+/// `FontAtlas::with_outline_space`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutlineSpaceError {
+    pub requested: u32,
+    pub available: u32,
+}
+
+impl std::fmt::Display for OutlineSpaceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "Can't reserve {} pixels for outlines in an atlas area {} pixels high",
+            self.requested, self.available
+        )
+    }
+}
+
+impl std::error::Error for OutlineSpaceError {}
+
+/// Subpixel offsets are told apart to the nearest tenth of a pixel, the
+/// same as the glyph cache's position tolerance.
+const SUBPIXEL_STEPS: f32 = 10.;
+
+type OutlineKey = (usize, u16, u32, u32, u32, [u8; 2]);
+
+fn outline_key(glyph: &Glyph, thickness: u32, pixel_snap: bool) -> OutlineKey {
+    let scale = glyph.glyph.scale();
+    let offset = match pixel_snap {
+        true => [0, 0],
+        false => {
+            let pos = glyph.glyph.position();
+            let step = |v: f32| ((v - v.floor()) * SUBPIXEL_STEPS).round() as u8;
+            [step(pos.x), step(pos.y)]
+        }
+    };
+    (
+        glyph.font_id,
+        glyph.glyph.id().0,
+        scale.x.to_bits(),
+        scale.y.to_bits(),
+        thickness,
+        offset,
+    )
+}
+
+fn rasterize_outline(glyph: &Glyph, thickness: u32) -> Option<([u32; 2], Vec<u8>)> {
+    let bounds = glyph.glyph.pixel_bounding_box()?;
+    let t = thickness as i32;
+    let (w, h) = (bounds.width(), bounds.height());
+
+    let mut coverage = vec![0_u8; (w * h) as usize];
+    glyph.glyph.draw(|x, y, v| {
+        coverage[(y as i32 * w + x as i32) as usize] = (v * 255.) as u8;
+    });
+
+    let (ow, oh) = (w + 2 * t, h + 2 * t);
+    let mut dilated = vec![0_u8; (ow * oh) as usize];
+    for y in 0..h {
+        for x in 0..w {
+            let alpha = coverage[(y * w + x) as usize];
+            if alpha == 0 {
+                continue;
+            }
+
+            for dy in -t..=t {
+                for dx in -t..=t {
+                    if dx * dx + dy * dy <= t * t {
+                        let i = ((y + t + dy) * ow + (x + t + dx)) as usize;
+                        dilated[i] = dilated[i].max(alpha);
+                    }
+                }
+            }
+        }
+    }
+
+    Some(([ow as u32, oh as u32], dilated))
+}
+
 pub struct FontAtlas {
     glyph_cache: Cache<'static>,
     backing_image_size: [u32; 2],
     available_area: ([u32; 2], [u32; 2]),
+    outline_area: ([u32; 2], [u32; 2]),
+    outline_regions: HashMap<OutlineKey, ([u32; 2], [u32; 2])>,
+    outline_queue: Vec<(OutlineKey, Glyph)>,
+    outline_shelf: ([u32; 2], u32),
+    /// The latest generation seen of each font.
+    font_generations: HashMap<usize, u32>,
+    /// The id each font's glyphs are cached under. An invalidated font gets
+    /// a new one, so its old glyphs are never fetched again and the glyph
+    /// cache reuses their space.
+    cache_ids: HashMap<usize, usize>,
+    next_cache_id: usize,
+    pixel_snap: bool,
     modified: bool,
 }
 
@@ -24,19 +121,149 @@ impl FontAtlas {
         backing_size: [u32; 2],
     ) -> Self {
         FontAtlas {
-            glyph_cache: Cache::builder()
-                .dimensions(size[0], size[1])
-                .position_tolerance(0.1)
-                .scale_tolerance(0.1)
-                .pad_glyphs(true)
-                .multithread(true)
-                .build(),
+            glyph_cache: Self::glyph_cache(size, false),
             backing_image_size: backing_size,
             available_area: (topleft, size),
+            outline_area: (topleft, [0, 0]),
+            outline_regions: Default::default(),
+            outline_queue: vec![],
+            outline_shelf: ([0, 0], 0),
+            font_generations: Default::default(),
+            cache_ids: Default::default(),
+            next_cache_id: 0,
+            pixel_snap: false,
             modified: true,
         }
     }
 
+    /// Reserve a strip of `outline_height` pixels at the bottom of the
+    /// available area for outlined glyphs. Without this, outlines have
+    /// nowhere to go and `fetch_outline` always returns `None`.
+    ///
+    /// Fails if that wouldn't leave any room for the glyphs themselves.
+    pub fn with_outline_space(self, outline_height: u32) -> Result<Self, OutlineSpaceError> {
+        let ([x, y], [w, h]) = self.available_area;
+        if outline_height >= h {
+            return Err(OutlineSpaceError {
+                requested: outline_height,
+                available: h,
+            });
+        }
+
+        let glyph_height = h - outline_height;
+
+        Ok(FontAtlas {
+            glyph_cache: Self::glyph_cache([w, glyph_height], self.pixel_snap),
+            available_area: ([x, y], [w, glyph_height]),
+            outline_area: ([x, y + glyph_height], [w, outline_height]),
+            ..self
+        })
+    }
+
+    /// Rasterize each glyph once and reuse it at any subpixel offset,
+    /// instead of rasterizing it again for each one. This is meant for text
+    /// drawn with `TextStyle::pixel_snap`: glyphs that aren't snapped can be
+    /// drawn up to a pixel away from where they were laid out.
+    pub fn with_pixel_snap(self) -> Self {
+        let (_, size) = self.available_area;
+        FontAtlas {
+            glyph_cache: Self::glyph_cache(size, true),
+            pixel_snap: true,
+            ..self
+        }
+    }
+
+    fn glyph_cache(size: [u32; 2], pixel_snap: bool) -> Cache<'static> {
+        // A tolerance of 1 pixel treats every subpixel offset as the same
+        let position_tolerance = match pixel_snap {
+            true => 1.0,
+            false => 0.1,
+        };
+
+        Cache::builder()
+            .dimensions(size[0], size[1])
+            .position_tolerance(position_tolerance)
+            .scale_tolerance(0.1)
+            .pad_glyphs(true)
+            .multithread(true)
+            .build()
+    }
+
+    fn allocate_outline(&mut self, size: [u32; 2]) -> Option<[u32; 2]> {
+        let ([ox, oy], [ow, oh]) = self.outline_area;
+        let ([cx, cy], shelf_height) = &mut self.outline_shelf;
+
+        // One pixel of padding to avoid bleeding between outlines
+        let [w, h] = [size[0] + 1, size[1] + 1];
+
+        if *cx + w > ow {
+            *cx = 0;
+            *cy += *shelf_height;
+            *shelf_height = 0;
+        }
+
+        if *cx + w > ow || *cy + h > oh {
+            return None;
+        }
+
+        let pos = [ox + *cx, oy + *cy];
+        *cx += w;
+        *shelf_height = std::cmp::max(*shelf_height, h);
+
+        Some(pos)
+    }
+
+    fn pack_outlines(&mut self, queue: &[(OutlineKey, Glyph)], dest: &mut RgbaImage) -> bool {
+        for (key, glyph) in queue {
+            if self.outline_regions.contains_key(key) {
+                continue;
+            }
+
+            let thickness = key.4;
+            let ([w, h], data) = match rasterize_outline(glyph, thickness) {
+                Some(raster) => raster,
+                None => continue,
+            };
+
+            let [x, y] = match self.allocate_outline([w, h]) {
+                Some(pos) => pos,
+                None => return false,
+            };
+
+            for dy in 0..h {
+                for dx in 0..w {
+                    let alpha = data[(dy * w + dx) as usize];
+                    dest.put_pixel(x + dx, y + dy, [255, 255, 255, alpha].into());
+                }
+            }
+
+            self.outline_regions.insert(*key, ([x, y], [w, h]));
+        }
+
+        true
+    }
+
+    /// Throw away the glyphs cached for a font, e.g. because it's been
+    /// replaced. This is done automatically when glyphs from a newer
+    /// generation of a font are inserted.
+    ///
+    /// Other fonts' glyphs are kept. The space used by this font's old
+    /// glyphs is reused once the atlas fills up.
+    pub fn invalidate_font(&mut self, font_id: usize) {
+        self.cache_ids.insert(font_id, self.next_cache_id);
+        self.next_cache_id += 1;
+        self.outline_regions.retain(|key, _| key.0 != font_id);
+        self.modified = true;
+    }
+
+    fn cache_id(&mut self, font_id: usize) -> usize {
+        let next_cache_id = &mut self.next_cache_id;
+        *self.cache_ids.entry(font_id).or_insert_with(|| {
+            *next_cache_id += 1;
+            *next_cache_id - 1
+        })
+    }
+
     pub fn compile(&mut self) -> RgbaImage {
         let [bw, bh] = self.backing_image_size;
         let mut atlas = RgbaImage::new(bw, bh);
@@ -47,8 +274,22 @@ impl FontAtlas {
 
 impl Atlas<Glyph, Glyph, Option<GlyphRegion>, RgbaImage> for FontAtlas {
     fn insert(&mut self, insertion: Glyph) {
-        self.glyph_cache
-            .queue_glyph(insertion.font_id, insertion.glyph);
+        let generation = self
+            .font_generations
+            .entry(insertion.font_id)
+            .or_insert(insertion.generation);
+        if insertion.generation > *generation {
+            *generation = insertion.generation;
+            self.invalidate_font(insertion.font_id);
+        }
+
+        if let Some(thickness) = insertion.outline {
+            let key = outline_key(&insertion, thickness, self.pixel_snap);
+            self.outline_queue.push((key, insertion.clone()));
+        }
+
+        let cache_id = self.cache_id(insertion.font_id);
+        self.glyph_cache.queue_glyph(cache_id, insertion.glyph);
     }
 
     fn fetch(&self, key: &Glyph) -> Option<GlyphRegion> {
@@ -59,10 +300,10 @@ impl Atlas<Glyph, Glyph, Option<GlyphRegion>, RgbaImage> for FontAtlas {
         let off_u = ax as f32 / bw as f32;
         let off_v = ay as f32 / bh as f32;
 
-        let scale = key.glyph.scale();
-        let ascent = key.glyph.font().v_metrics(scale).ascent;
+        let ascent = key.metrics().ascent;
 
-        let coords = self.glyph_cache.rect_for(key.font_id, &key.glyph).unwrap();
+        let cache_id = *self.cache_ids.get(&key.font_id)?;
+        let coords = self.glyph_cache.rect_for(cache_id, &key.glyph).unwrap();
 
         coords.map(|(uv_rect, px_rect)| {
             use rusttype::Point;
@@ -110,6 +351,23 @@ impl Atlas<Glyph, Glyph, Option<GlyphRegion>, RgbaImage> for FontAtlas {
             })
             .unwrap();
 
+        let outline_queue = std::mem::take(&mut self.outline_queue);
+        let outlines_missing = outline_queue
+            .iter()
+            .any(|(key, _)| !self.outline_regions.contains_key(key));
+
+        if outlines_missing {
+            // When the outline space fills up, evict everything and keep
+            // only the outlines queued since the last compile.
+            if !self.pack_outlines(&outline_queue, dest) {
+                self.outline_regions.clear();
+                self.outline_shelf = ([0, 0], 0);
+                self.pack_outlines(&outline_queue, dest);
+            }
+
+            upload_required = true;
+        }
+
         self.modified = true;
         upload_required
     }
@@ -118,3 +376,79 @@ impl Atlas<Glyph, Glyph, Option<GlyphRegion>, RgbaImage> for FontAtlas {
         self.modified
     }
 }
+
+impl OutlineAtlas for FontAtlas {
+    fn fetch_outline(&self, key: &Glyph) -> Option<GlyphRegion> {
+        let thickness = key.outline?;
+        let ([x, y], [w, h]) =
+            *self
+                .outline_regions
+                .get(&outline_key(key, thickness, self.pixel_snap))?;
+        let bounds = key.glyph.pixel_bounding_box()?;
+
+        let [bw, bh] = self.backing_image_size;
+        let [bw, bh] = [bw as f32, bh as f32];
+        let t = thickness as f32;
+
+        let ascent = key.metrics().ascent;
+
+        Some(GlyphRegion {
+            pos: [bounds.min.x as f32 - t, bounds.min.y as f32 - t + ascent],
+            size: [w as f32, h as f32],
+            uv: (
+                [x as f32 / bw, y as f32 / bh],
+                [w as f32 / bw, h as f32 / bh],
+            ),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::font::tests::test_font;
+
+    #[test]
+    fn glyphs_sit_on_the_baseline() {
+        let font = test_font();
+        let mut atlas = FontAtlas::with_size([256, 256]);
+        let mut dest = RgbaImage::new(256, 256);
+
+        let glyphs = font.layout("Hg", [10., 20.], 22.);
+        for glyph in &glyphs {
+            atlas.insert(glyph.clone());
+        }
+        atlas.compile_into(&mut dest);
+
+        let baseline = 20. + font.metrics(22.).ascent;
+        let bottom = |glyph: &Glyph| {
+            let region = atlas.fetch(glyph).unwrap();
+            region.pos[1] + region.size[1]
+        };
+
+        assert!((bottom(&glyphs[0]) - baseline).abs() <= 1.);
+        assert!(bottom(&glyphs[1]) > baseline + 1.);
+    }
+
+    #[test]
+    fn invalidating_a_font_keeps_other_fonts_glyphs() {
+        let (old, new) = (test_font(), test_font());
+        let mut atlas = FontAtlas::with_size([256, 256]);
+        let mut dest = RgbaImage::new(256, 256);
+
+        let old_glyphs = old.layout("Hg", [10., 20.], 22.);
+        let new_glyphs = new.layout("Hg", [10., 20.], 22.);
+        for glyph in old_glyphs.iter().chain(&new_glyphs) {
+            atlas.insert(glyph.clone());
+        }
+        atlas.compile_into(&mut dest);
+        let kept = atlas.fetch(&new_glyphs[0]).unwrap();
+
+        atlas.invalidate_font(old_glyphs[0].font_id);
+        assert_eq!(atlas.fetch(&new_glyphs[0]), Some(kept));
+
+        atlas.insert(old_glyphs[0].clone());
+        atlas.compile_into(&mut dest);
+        assert!(atlas.fetch(&old_glyphs[0]).is_some());
+    }
+}
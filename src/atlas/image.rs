@@ -1,24 +1,392 @@
 use std::borrow::Borrow;
+use std::cell::Cell;
 use std::collections::HashMap;
-use std::hash::Hash;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
 
-use image::RgbaImage;
+use image::{GenericImageView, RgbaImage};
+use serde::{Deserialize, Serialize};
 use texture_packer::{TexturePacker, TexturePackerConfig};
 
-use crate::{atlas::Atlas, draw::Region};
+use crate::{
+    atlas::Atlas,
+    draw::{Orientation, Region},
+};
+
+mod import;
+
+pub use self::import::ImportError;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackError<K> {
+    pub key: K,
+    pub size: [u32; 2],
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MergeError {
+    /// There's no space left for the incoming atlas image, of this size.
+    NoSpace([u32; 2]),
+    /// These region keys, after namespacing, are already in the atlas.
+    /// Nothing is merged if there are any.
+    Collision(Vec<String>),
+}
+
+/// A name for a region that, unlike the `Region` itself, doesn't change when
+/// the atlas is packed differently, so it can be saved (e.g. in a replay)
+/// and looked up again with `ImageAtlas::resolve`.
+///
+/// It's a hash of the region's key, with integers hashed as fixed-width
+/// little-endian bytes, so the same key gets the same ID in every session
+/// and on every platform, whatever order images were inserted in. The key
+/// is hashed through its `Hash` impl, though, and the standard library's
+/// impls for strings, slices and tuples could change in a later Rust
+/// release, so only save IDs across builds made with the same compiler.
+/// If two keys in an atlas ever hash the same, neither of them has an ID,
+/// rather than one resolving to the other's region.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct RegionId(pub u64);
+
+impl RegionId {
+    fn of<K: Hash + ?Sized>(key: &K) -> Self {
+        let mut hasher = Fnv1a::default();
+        key.hash(&mut hasher);
+        RegionId(hasher.finish())
+    }
+}
+
+/// FNV-1a, used instead of the standard library's hasher because its
+/// algorithm and keys are fixed, rather than random for each session.
+///
+/// `Hash` writes integers in native byte order, and `usize`s (including
+/// lengths and enum discriminants) at native width, so they're all widened
+/// to 64 bits and written little-endian here instead.
+struct Fnv1a(u64);
+
+macro_rules! write_le {
+    ($($method:ident: $int:ty as $wide:ty),* $(,)?) => {
+        $(
+            fn $method(&mut self, i: $int) {
+                self.write(&(i as $wide).to_le_bytes());
+            }
+        )*
+    };
+}
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Fnv1a(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for Fnv1a {
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= byte as u64;
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    write_le! {
+        write_u16: u16 as u16,
+        write_u32: u32 as u32,
+        write_u64: u64 as u64,
+        write_u128: u128 as u128,
+        write_usize: usize as u64,
+        write_i16: i16 as i16,
+        write_i32: i32 as i32,
+        write_i64: i64 as i64,
+        write_i128: i128 as i128,
+        write_isize: isize as i64,
+    }
+}
+
+/// How a region's pixels need to be blended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegionClass {
+    /// Every pixel is fully opaque.
+    Opaque,
+    /// Every pixel is either fully opaque or fully transparent.
+    Cutout,
+    /// Some pixels are partially transparent.
+    Translucent,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RegionMeta {
+    pub class: RegionClass,
+    /// The tightest box (`(topleft, size)`, in pixels relative to the
+    /// region, the right way up) containing every fully opaque pixel, if
+    /// there are any.
+    pub opaque_bounds: Option<([u32; 2], [u32; 2])>,
+}
+
+impl RegionMeta {
+    fn of<I>(image: &I) -> Self
+    where
+        I: GenericImageView<Pixel = image::Rgba<u8>>,
+    {
+        let mut opaque = true;
+        let mut cutout = true;
+        let mut bounds: Option<([u32; 2], [u32; 2])> = None;
+
+        for (x, y, pixel) in image.pixels() {
+            match pixel[3] {
+                255 => {
+                    let ([x0, y0], [x1, y1]) = bounds.unwrap_or(([x, y], [x, y]));
+                    bounds = Some(([x0.min(x), y0.min(y)], [x1.max(x), y1.max(y)]));
+                }
+                0 => opaque = false,
+                _ => {
+                    opaque = false;
+                    cutout = false;
+                }
+            }
+        }
+
+        let class = match (opaque, cutout) {
+            (true, _) => RegionClass::Opaque,
+            (false, true) => RegionClass::Cutout,
+            (false, false) => RegionClass::Translucent,
+        };
+
+        RegionMeta {
+            class,
+            opaque_bounds: bounds
+                .map(|([x0, y0], [x1, y1])| ([x0, y0], [x1 - x0 + 1, y1 - y0 + 1])),
+        }
+    }
+
+    /// Turn bounds measured on a region as stored, with the given size,
+    /// the right way up.
+    fn upright(self, orientation: Orientation, stored_size: [u32; 2]) -> Self {
+        let size = [stored_size[0] as f32, stored_size[1] as f32];
+        let inverse = orientation.inverse();
+        let opaque_bounds = self.opaque_bounds.map(|([x, y], [w, h])| {
+            let [x0, y0] = inverse.map_point([x as f32, y as f32], size);
+            let [x1, y1] = inverse.map_point([(x + w) as f32, (y + h) as f32], size);
+            (
+                [x0.min(x1) as u32, y0.min(y1) as u32],
+                [(x1 - x0).abs() as u32, (y1 - y0).abs() as u32],
+            )
+        });
+
+        RegionMeta {
+            opaque_bounds,
+            ..self
+        }
+    }
+}
+
+/// A named rectangle within a region, e.g. a hitbox.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct NamedRect {
+    pub name: String,
+    pub pos: [f32; 2],
+    pub size: [f32; 2],
+}
+
+/// Where a trimmed region was cut from its original image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Trim {
+    /// The top-left of the region within the original image.
+    pub offset: [u32; 2],
+    /// The size of the original image, before trimming.
+    pub source_size: [u32; 2],
+}
+
+/// Extra data that artists attach to a region, like its pivot point and
+/// collision boxes.
+///
+/// Coordinates are in pixels relative to the top-left of the region's own
+/// image, so they stay the same wherever the region is packed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegionExtras {
+    pub pivot: [f32; 2],
+    pub boxes: Vec<NamedRect>,
+    /// For regions imported trimmed, where they were trimmed from. The
+    /// pivot and boxes have already been moved to match.
+    pub trim: Option<Trim>,
+}
+
+/// The extras as written in a sidecar file, where everything is optional.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ExtrasSource {
+    pivot: Option<[f32; 2]>,
+    #[serde(default)]
+    boxes: Vec<NamedRect>,
+}
+
+impl ExtrasSource {
+    fn resolve(self, size: [u32; 2]) -> RegionExtras {
+        let [w, h] = size;
+        RegionExtras {
+            pivot: self.pivot.unwrap_or([w as f32 / 2., h as f32 / 2.]),
+            boxes: self.boxes,
+            trim: None,
+        }
+    }
+}
+
+impl RegionExtras {
+    /// The extras for a region with no sidecar: a centered pivot and no
+    /// boxes.
+    pub fn centered(size: [u32; 2]) -> Self {
+        ExtrasSource::default().resolve(size)
+    }
+
+    /// Parse a sidecar file for an image of the given size, like:
+    ///
+    /// ```toml
+    /// pivot = [16, 30]
+    ///
+    /// [[boxes]]
+    /// name = "hurt"
+    /// pos = [8, 4]
+    /// size = [16, 26]
+    /// ```
+    ///
+    /// If `pivot` is left out, it's the center of the image.
+    pub fn parse(source: &str, size: [u32; 2]) -> Result<Self, String> {
+        let source = toml::from_str::<ExtrasSource>(source).map_err(|e| e.to_string())?;
+        Ok(source.resolve(size))
+    }
+}
+
+#[derive(Debug)]
+pub enum LoadError<K> {
+    Io(PathBuf, std::io::Error),
+    Image(PathBuf, image::ImageError),
+    /// A sidecar or extras file couldn't be parsed.
+    Extras(PathBuf, String),
+    Pack(PackError<K>),
+}
+
+impl<K> std::fmt::Display for LoadError<K> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            LoadError::Io(path, e) => write!(f, "Failed to read {}: {}", path.display(), e),
+            LoadError::Image(path, e) => write!(f, "Failed to decode {}: {}", path.display(), e),
+            LoadError::Extras(path, e) => write!(f, "Failed to parse {}: {}", path.display(), e),
+            LoadError::Pack(e) => write!(
+                f,
+                "Not enough space in the atlas for an image of size {:?}",
+                e.size
+            ),
+        }
+    }
+}
+
+/// What the packer is given in place of each image. It only needs the size
+/// to place it, so there's no need for it to hold a copy of the pixels.
+#[derive(Debug, Clone, Copy)]
+struct Footprint {
+    width: u32,
+    height: u32,
+}
+
+impl Footprint {
+    fn of(image: &RgbaImage) -> Self {
+        Footprint {
+            width: image.width(),
+            height: image.height(),
+        }
+    }
+}
+
+impl texture_packer::texture::Texture for Footprint {
+    type Pixel = image::Rgba<u8>;
+
+    fn width(&self) -> u32 {
+        self.width
+    }
+
+    fn height(&self) -> u32 {
+        self.height
+    }
+
+    fn get(&self, _x: u32, _y: u32) -> Option<Self::Pixel> {
+        Some(image::Rgba([0, 0, 0, 255]))
+    }
+
+    fn set(&mut self, _x: u32, _y: u32, _val: Self::Pixel) {}
+}
+
+struct Entry {
+    region: Region,
+    /// The image to copy into the atlas, unless the region is from a
+    /// pre-made atlas.
+    image: Option<RgbaImage>,
+    meta: Cell<Option<RegionMeta>>,
+    extras: Option<RegionExtras>,
+    /// The size of the space packed for the region, which can be bigger
+    /// than the region if a smaller image was inserted over it.
+    slot: [u32; 2],
+}
+
+impl Entry {
+    fn pre_made(region: Region) -> Self {
+        Entry {
+            region,
+            image: None,
+            meta: Cell::new(None),
+            extras: None,
+            slot: region.pixels.1,
+        }
+    }
+}
+
+/// A handle for submitting images to an `ImageAtlas` from other threads.
+///
+/// Images are packed as they arrive by whichever thread calls
+/// `ImageAtlas::pack_pending` or `ImageAtlas::finish_batch`, so decoding
+/// can overlap with packing.
+pub struct AtlasBatch<K> {
+    sender: Sender<(K, RgbaImage)>,
+}
+
+impl<K> Clone for AtlasBatch<K> {
+    fn clone(&self) -> Self {
+        AtlasBatch {
+            sender: self.sender.clone(),
+        }
+    }
+}
+
+impl<K> AtlasBatch<K> {
+    pub fn submit(&self, key: K, image: RgbaImage) {
+        // The atlas may have given up on the batch, in which case there's
+        // nothing useful to do with the image.
+        let _ = self.sender.send((key, image));
+    }
+}
 
 pub struct ImageAtlas<'a, K>
 where
     K: ToOwned + Eq + Hash + ?Sized,
     K::Owned: Clone + Eq + Hash,
 {
-    regions: HashMap<K::Owned, Region>,
-    source_images: HashMap<K::Owned, RgbaImage>,
-    packer: TexturePacker<'a, RgbaImage>,
+    entries: HashMap<K::Owned, Entry>,
+    /// How many keys have each ID. Only IDs with one can be resolved.
+    ids: HashMap<RegionId, usize>,
+    packers: Vec<TexturePacker<'a, Footprint>>,
+    max_pages: usize,
+    next_pack_id: usize,
     pre_made_atlas: Option<RgbaImage>,
+    merged_atlases: Vec<(usize, [u32; 2], RgbaImage)>,
     backing_image_size: [u32; 2],
     available_area: ([u32; 2], [u32; 2]),
     modified: bool,
+    generation: u64,
+    moved: Vec<K::Owned>,
+    batch: Option<Receiver<(K::Owned, RgbaImage)>>,
+    batch_errors: Vec<PackError<K::Owned>>,
 }
 
 impl<'a, K> ImageAtlas<'a, K>
@@ -51,13 +419,33 @@ where
         backing_size: [u32; 2],
     ) -> Self {
         ImageAtlas {
-            regions: Default::default(),
-            source_images: Default::default(),
-            packer: TexturePacker::new_skyline(Self::config(size)),
+            entries: Default::default(),
+            ids: Default::default(),
+            packers: vec![TexturePacker::new_skyline(Self::config(size))],
+            max_pages: 1,
+            next_pack_id: 0,
             pre_made_atlas: None,
+            merged_atlases: vec![],
             backing_image_size: backing_size,
             available_area: (topleft, size),
             modified: true,
+            generation: 0,
+            moved: vec![],
+            batch: None,
+            batch_errors: vec![],
+        }
+    }
+
+    /// An atlas that starts a new `page_size` page whenever an image
+    /// doesn't fit on the existing ones, up to `max_pages` pages. Each
+    /// region's `page` says which one it's on, and `compile_pages` builds
+    /// an image for each.
+    pub fn paged(page_size: [u32; 2], max_pages: usize) -> Self {
+        assert!(max_pages > 0, "An atlas needs at least one page");
+
+        ImageAtlas {
+            max_pages,
+            ..Self::with_size(page_size)
         }
     }
 
@@ -69,7 +457,9 @@ where
         let [bw, bh] = backing_size;
 
         let mut packer = TexturePacker::new_skyline(Self::config([bw, bh]));
-        packer.pack_own(String::new(), atlas_image.clone()).unwrap();
+        packer
+            .pack_own(String::new(), Footprint::of(&atlas_image))
+            .unwrap();
         let frame = packer.get_frame("").unwrap().frame;
 
         assert!(
@@ -77,14 +467,29 @@ where
             "Oops, that's not how I thought this worked"
         );
 
+        let mut ids = HashMap::new();
+        for key in regions.keys() {
+            *ids.entry(RegionId::of(key)).or_insert(0) += 1;
+        }
+
         ImageAtlas {
-            regions,
-            source_images: Default::default(),
-            packer,
+            entries: regions
+                .into_iter()
+                .map(|(key, region)| (key, Entry::pre_made(region)))
+                .collect(),
+            ids,
+            packers: vec![packer],
+            max_pages: 1,
+            next_pack_id: 0,
             pre_made_atlas: Some(atlas_image),
+            merged_atlases: vec![],
             backing_image_size: [bw, bh],
             available_area: ([0, 0], [bw, bh]),
             modified: true,
+            generation: 0,
+            moved: vec![],
+            batch: None,
+            batch_errors: vec![],
         }
     }
 
@@ -94,60 +499,523 @@ where
         self.compile_into(&mut atlas);
         atlas
     }
-}
 
-impl<'a, K> Atlas<(K::Owned, RgbaImage), K, Region, RgbaImage> for ImageAtlas<'a, K>
-where
-    K: ToOwned + Eq + Hash + ?Sized,
-    K::Owned: Clone + Eq + Hash,
-{
-    fn insert(&mut self, (key, image): (K::Owned, RgbaImage)) {
-        let string_key = self.source_images.len().to_string();
-        self.packer
-            .pack_own(string_key.clone(), image.clone())
-            .unwrap();
-        let texture_packer::Rect { x, y, w, h } = self.packer.get_frame(&string_key).unwrap().frame;
+    /// An image of each page, in order.
+    pub fn compile_pages(&mut self) -> Vec<RgbaImage> {
+        let [bw, bh] = self.backing_image_size;
+        let pages = (0..self.packers.len())
+            .map(|page| {
+                let mut image = RgbaImage::new(bw, bh);
+                self.compile_page_into(page, &mut image);
+                image
+            })
+            .collect();
+
+        self.modified = false;
+        pages
+    }
 
+    /// How many pages have something on them. This is always at least 1.
+    pub fn page_count(&self) -> usize {
+        self.packers.len()
+    }
+
+    fn compile_page_into(&self, page: usize, dest: &mut RgbaImage) {
+        use image::GenericImage;
+
+        let ([ax, ay], _) = self.available_area;
+        if let Some(pre_made_atlas) = self.pre_made_atlas.as_ref().filter(|_| page == 0) {
+            dest.copy_from(pre_made_atlas, ax, ay).unwrap();
+        }
+
+        for (merged_page, [x, y], atlas_image) in &self.merged_atlases {
+            if *merged_page == page {
+                dest.copy_from(atlas_image, *x, *y).unwrap();
+            }
+        }
+
+        for entry in self
+            .entries
+            .values()
+            .filter(|entry| entry.region.page == page)
+        {
+            // If there's no image, this region must be from a pre-made atlas
+            if let Some(image) = &entry.image {
+                let [x, y] = entry.region.pixels.0;
+
+                // Clear anything left over from a bigger image
+                let [sw, sh] = entry.slot;
+                if [image.width(), image.height()] != entry.slot {
+                    dest.copy_from(&RgbaImage::new(sw, sh), x, y).unwrap();
+                }
+
+                dest.copy_from(image, x, y).unwrap();
+            }
+        }
+    }
+
+    /// Find space for `footprint` on the first page with room, starting a
+    /// new page if there are none. Returns the page it went on.
+    fn pack(&mut self, key: String, footprint: Footprint) -> Option<usize> {
+        for (page, packer) in self.packers.iter_mut().enumerate() {
+            if packer.pack_own(key.clone(), footprint).is_ok() {
+                return Some(page);
+            }
+        }
+
+        if self.packers.len() < self.max_pages {
+            let (_, size) = self.available_area;
+            let mut packer = TexturePacker::new_skyline(Self::config(size));
+            if packer.pack_own(key, footprint).is_ok() {
+                self.packers.push(packer);
+                return Some(self.packers.len() - 1);
+            }
+        }
+
+        None
+    }
+
+    /// The region for pixels at `topleft` in the backing image.
+    fn region_at(&self, topleft: [u32; 2], size: [u32; 2], page: usize) -> Region {
+        let [x, y] = topleft;
+        let [w, h] = size;
         let [bw, bh] = self.backing_image_size;
         let [bw, bh] = [bw as f32, bh as f32];
-        let ([ax, ay], _) = self.available_area;
 
-        let region = Region {
-            pixels: ([x + ax, y + ay], [w, h]),
-            uv: (
-                [(ax + x) as f32 / bw, (ay + y) as f32 / bh],
-                [w as f32 / bw, h as f32 / bh],
-            ),
+        let uv = (
+            [x as f32 / bw, y as f32 / bh],
+            [w as f32 / bw, h as f32 / bh],
+        );
+        Region {
+            page,
+            ..Region::new(([x, y], [w, h]), uv)
+        }
+    }
+
+    /// Insert an image, or replace the image for a key that's already in
+    /// the atlas (e.g. when it's hot-reloaded). A replacement that fits in
+    /// the space packed for the old image is drawn there. One that doesn't
+    /// is packed somewhere new, leaving the old space unused.
+    ///
+    /// A replacement keeps the key's extras. If the key's region changes,
+    /// it's listed by `take_moved`.
+    pub fn try_insert(
+        &mut self,
+        key: K::Owned,
+        image: RgbaImage,
+    ) -> Result<(), PackError<K::Owned>> {
+        let size = [image.width(), image.height()];
+
+        let reusable = self
+            .entries
+            .get(key.borrow())
+            .filter(|entry| entry.image.is_some())
+            .map(|entry| (entry.region.pixels.0, entry.region.page, entry.slot))
+            .filter(|(_, _, [sw, sh])| size[0] <= *sw && size[1] <= *sh);
+
+        let (region, slot) = match reusable {
+            Some((topleft, page, slot)) => (self.region_at(topleft, size, page), slot),
+            None => {
+                let string_key = self.next_pack_id.to_string();
+                let page = match self.pack(string_key.clone(), Footprint::of(&image)) {
+                    Some(page) => page,
+                    None => return Err(PackError { key, size }),
+                };
+                self.next_pack_id += 1;
+
+                let texture_packer::Rect { x, y, w, h } =
+                    self.packers[page].get_frame(&string_key).unwrap().frame;
+                let ([ax, ay], _) = self.available_area;
+
+                (self.region_at([x + ax, y + ay], [w, h], page), [w, h])
+            }
+        };
+
+        self.assign_id(&key);
+        let meta = RegionMeta::of(&image);
+
+        // Reloading an image keeps its pivot and boxes
+        let (extras, moved) = match self.entries.get(key.borrow()) {
+            Some(previous) => (previous.extras.clone(), previous.region != region),
+            None => (None, false),
         };
 
-        self.regions.insert(key.clone(), region);
-        self.source_images.insert(key, image);
+        if moved {
+            if !self.moved.contains(&key) {
+                self.moved.push(key.clone());
+            }
+            self.generation += 1;
+        }
+        self.entries.insert(
+            key,
+            Entry {
+                region,
+                image: Some(image),
+                meta: Cell::new(Some(meta)),
+                extras,
+                slot,
+            },
+        );
         self.modified = true;
+
+        Ok(())
     }
 
-    fn fetch(&self, key: &K) -> Region {
-        self.regions[key]
+    /// The keys whose regions have moved or changed size since the last
+    /// call, because they were inserted again. Anything holding on to their
+    /// old regions should fetch them again.
+    pub fn take_moved(&mut self) -> Vec<K::Owned> {
+        std::mem::take(&mut self.moved)
     }
 
-    fn compile_into(&mut self, dest: &mut RgbaImage) -> bool {
-        use image::GenericImage;
+    /// Counts the times a region has moved or changed size, so cached
+    /// regions can be checked cheaply.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Insert an image along with its extras.
+    pub fn try_insert_with_extras(
+        &mut self,
+        key: K::Owned,
+        image: RgbaImage,
+        extras: RegionExtras,
+    ) -> Result<(), PackError<K::Owned>> {
+        self.try_insert(key.clone(), image)?;
+        self.set_extras(key.borrow(), extras);
+        Ok(())
+    }
+
+    /// Load and insert an image file. If there's a sidecar file next to
+    /// it with the same name and a `.toml` extension (e.g. `player.toml`
+    /// for `player.png`), its extras are attached to the region.
+    pub fn insert_path<P: AsRef<Path>>(
+        &mut self,
+        key: K::Owned,
+        path: P,
+    ) -> Result<(), LoadError<K::Owned>> {
+        let path = path.as_ref();
+        let image = image::open(path)
+            .map_err(|e| LoadError::Image(path.to_owned(), e))?
+            .to_rgba8();
+
+        let sidecar_path = path.with_extension("toml");
+        let extras = match std::fs::read_to_string(&sidecar_path) {
+            Ok(source) => Some(
+                RegionExtras::parse(&source, [image.width(), image.height()])
+                    .map_err(|e| LoadError::Extras(sidecar_path, e))?,
+            ),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => return Err(LoadError::Io(sidecar_path, e)),
+        };
+
+        self.try_insert(key.clone(), image)
+            .map_err(LoadError::Pack)?;
+
+        // The sidecar replaces any earlier extras, even if it's been removed
+        self.entries.get_mut(key.borrow()).unwrap().extras = extras;
+        Ok(())
+    }
+
+    /// Replace the extras for a region already in the atlas.
+    pub fn set_extras(&mut self, key: &K, extras: RegionExtras) {
+        self.entries
+            .get_mut(key)
+            .expect("No region in the atlas for the given key")
+            .extras = Some(extras);
+    }
+
+    /// The pivot and boxes for `key`, or a centered pivot and no boxes if
+    /// it has none.
+    pub fn fetch_extras(&self, key: &K) -> RegionExtras {
+        let entry = &self.entries[key];
+        match &entry.extras {
+            Some(extras) => extras.clone(),
+            None => RegionExtras::centered(entry.region.size()),
+        }
+    }
+
+    pub fn contains(&self, key: &K) -> bool {
+        self.entries.contains_key(key)
+    }
+
+    pub fn get(&self, key: &K) -> Option<&Region> {
+        self.entries.get(key).map(|entry| &entry.region)
+    }
+
+    /// The ID to save in place of the region for `key`, if it's in the
+    /// atlas and no other key has the same ID.
+    pub fn region_id(&self, key: &K) -> Option<RegionId> {
+        let id = RegionId::of(key);
+        if self.ids.get(&id) == Some(&1) && self.entries.contains_key(key) {
+            Some(id)
+        } else {
+            None
+        }
+    }
+
+    /// The current region for a saved `RegionId`, if its key is in the
+    /// atlas.
+    pub fn resolve(&self, id: RegionId) -> Option<&Region> {
+        self.get(self.key_for_id(id)?.borrow())
+    }
+
+    /// The key a saved `RegionId` stands for, if it's in the atlas.
+    ///
+    /// This searches the atlas's keys, so it's best to resolve saved IDs
+    /// once when they're loaded rather than every frame.
+    pub fn key_for_id(&self, id: RegionId) -> Option<&K::Owned> {
+        if self.ids.get(&id) != Some(&1) {
+            return None;
+        }
+        self.entries.keys().find(|key| RegionId::of(*key) == id)
+    }
+
+    /// Count `key` towards its ID, if it's not already in the atlas. Once
+    /// more than one key has an ID, none of them keep it, whichever came
+    /// first.
+    fn assign_id(&mut self, key: &K::Owned) {
+        if !self.entries.contains_key(key.borrow()) {
+            *self.ids.entry(RegionId::of(key)).or_insert(0) += 1;
+        }
+    }
+
+    /// How the region for `key` needs to be blended. For regions from a
+    /// pre-made atlas, this is worked out the first time it's asked for.
+    pub fn fetch_meta(&self, key: &K) -> RegionMeta {
+        let entry = &self.entries[key];
+        if let Some(meta) = entry.meta.get() {
+            return meta;
+        }
 
+        let ([x, y], [w, h]) = entry.region.pixels;
+        let page = entry.region.page;
         let ([ax, ay], _) = self.available_area;
-        if let Some(pre_made_atlas) = &self.pre_made_atlas {
-            dest.copy_from(pre_made_atlas, ax, ay).unwrap();
+
+        let pre_made = self
+            .pre_made_atlas
+            .as_ref()
+            .filter(|_| page == 0)
+            .map(|image| ([ax, ay], image));
+        let merged = self
+            .merged_atlases
+            .iter()
+            .filter(|(merged_page, _, _)| *merged_page == page)
+            .map(|(_, [ox, oy], image)| ([*ox, *oy], image));
+
+        let meta = pre_made
+            .into_iter()
+            .chain(merged)
+            .find(|([ox, oy], image)| {
+                x >= *ox && y >= *oy && x + w <= ox + image.width() && y + h <= oy + image.height()
+            })
+            .map(|([ox, oy], image)| RegionMeta::of(&image.view(x - ox, y - oy, w, h)))
+            .expect("Region is not from an image in the atlas")
+            .upright(entry.region.orientation, [w, h]);
+
+        entry.meta.set(Some(meta));
+        meta
+    }
+
+    /// Cut a sprite sheet into `cell_size` cells, left to right then top to
+    /// bottom, and insert each one under the key returned by `key_for` for
+    /// its index. Returns the keys in order.
+    pub fn insert_grid<F>(
+        &mut self,
+        sheet: &RgbaImage,
+        cell_size: [u32; 2],
+        mut key_for: F,
+    ) -> Vec<K::Owned>
+    where
+        F: FnMut(usize) -> K::Owned,
+    {
+        use image::GenericImageView;
+
+        let [cw, ch] = cell_size;
+        let columns = sheet.width() / cw;
+        let rows = sheet.height() / ch;
+
+        let mut keys = Vec::with_capacity((columns * rows) as usize);
+        for row in 0..rows {
+            for column in 0..columns {
+                let key = key_for(keys.len());
+                let cell = sheet.view(column * cw, row * ch, cw, ch).to_image();
+                self.insert((key.clone(), cell));
+                keys.push(key);
+            }
         }
 
-        for (key, region) in self.regions.iter() {
-            let image = self.source_images.get(key.borrow());
+        keys
+    }
+
+    /// Start accepting images from other threads. The returned handle can
+    /// be cloned and sent to worker threads.
+    pub fn begin_batch(&mut self) -> AtlasBatch<K::Owned> {
+        assert!(self.batch.is_none(), "A batch is already in progress");
+
+        let (sender, receiver) = mpsc::channel();
+        self.batch = Some(receiver);
+        AtlasBatch { sender }
+    }
+
+    /// Pack any batched images that have arrived so far, without waiting
+    /// for more. Returns how many were packed.
+    pub fn pack_pending(&mut self) -> usize {
+        let pending = match &self.batch {
+            Some(receiver) => receiver.try_iter().collect::<Vec<_>>(),
+            None => return 0,
+        };
 
-            // If there's no image, this region must be from the pre-made atlas
-            if let Some(image) = image {
-                dest.copy_from(image, region.pixels.0[0], region.pixels.0[1])
-                    .unwrap();
+        let count = pending.len();
+        for (key, image) in pending {
+            if let Err(e) = self.try_insert(key, image) {
+                self.batch_errors.push(e);
             }
         }
 
-        self.modified = false;
+        count
+    }
+
+    /// Pack batched images as they arrive until every `AtlasBatch` handle
+    /// has been dropped, then report any that didn't fit.
+    pub fn finish_batch(&mut self) -> Result<(), Vec<PackError<K::Owned>>> {
+        if let Some(receiver) = self.batch.take() {
+            for (key, image) in receiver {
+                if let Err(e) = self.try_insert(key, image) {
+                    self.batch_errors.push(e);
+                }
+            }
+        }
+
+        match self.batch_errors.is_empty() {
+            true => Ok(()),
+            false => Err(std::mem::take(&mut self.batch_errors)),
+        }
+    }
+}
+
+impl<'a> ImageAtlas<'a, str> {
+    /// Pack another pre-made atlas (e.g. from a mod) into this one as a
+    /// single image. Its regions are moved to where it was placed, and
+    /// their keys are prefixed with `"{namespace}/"`.
+    pub fn merge_premade(
+        &mut self,
+        atlas_image: RgbaImage,
+        regions: HashMap<String, Region>,
+        namespace: &str,
+    ) -> Result<(), MergeError> {
+        let regions = regions
+            .into_iter()
+            .map(|(key, region)| (format!("{}/{}", namespace, key), region))
+            .collect::<Vec<_>>();
+
+        let mut collisions = regions
+            .iter()
+            .map(|(key, _)| key)
+            .filter(|key| self.entries.contains_key(*key))
+            .cloned()
+            .collect::<Vec<_>>();
+        if !collisions.is_empty() {
+            collisions.sort();
+            return Err(MergeError::Collision(collisions));
+        }
+
+        let size = [atlas_image.width(), atlas_image.height()];
+        let packer_key = format!("premade:{}", self.merged_atlases.len());
+        let page = self
+            .pack(packer_key.clone(), Footprint::of(&atlas_image))
+            .ok_or(MergeError::NoSpace(size))?;
+
+        let frame = self.packers[page].get_frame(&packer_key).unwrap().frame;
+        let ([ax, ay], _) = self.available_area;
+        let [ox, oy] = [frame.x + ax, frame.y + ay];
+
+        let [bw, bh] = self.backing_image_size;
+        let [bw, bh] = [bw as f32, bh as f32];
+
+        for (key, region) in regions {
+            let ([x, y], [w, h]) = region.pixels;
+            let [x, y] = [x + ox, y + oy];
+            let region = Region {
+                pixels: ([x, y], [w, h]),
+                uv: (
+                    [x as f32 / bw, y as f32 / bh],
+                    [w as f32 / bw, h as f32 / bh],
+                ),
+                page,
+                orientation: region.orientation,
+            };
+            self.assign_id(&key);
+            self.entries.insert(key, Entry::pre_made(region));
+        }
+
+        self.merged_atlases.push((page, [ox, oy], atlas_image));
+        self.modified = true;
+
+        Ok(())
+    }
+
+    /// Load one file of extras for many regions, with a table for each
+    /// region key, like:
+    ///
+    /// ```toml
+    /// [player_idle]
+    /// pivot = [16, 30]
+    ///
+    /// [[player_idle.boxes]]
+    /// name = "hurt"
+    /// pos = [8, 4]
+    /// size = [16, 26]
+    /// ```
+    ///
+    /// Only regions already in the atlas are updated. Returns the keys in
+    /// the file that weren't found.
+    pub fn load_extras_file<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+    ) -> Result<Vec<String>, LoadError<String>> {
+        let path = path.as_ref();
+        let source =
+            std::fs::read_to_string(path).map_err(|e| LoadError::Io(path.to_owned(), e))?;
+        let sources = toml::from_str::<HashMap<String, ExtrasSource>>(&source)
+            .map_err(|e| LoadError::Extras(path.to_owned(), e.to_string()))?;
+
+        let mut missing = vec![];
+        for (key, source) in sources {
+            match self.entries.get_mut(&key) {
+                Some(entry) => entry.extras = Some(source.resolve(entry.region.size())),
+                None => missing.push(key),
+            }
+        }
+
+        missing.sort();
+        Ok(missing)
+    }
+}
+
+impl<'a, K> Atlas<(K::Owned, RgbaImage), K, Region, RgbaImage> for ImageAtlas<'a, K>
+where
+    K: ToOwned + Eq + Hash + ?Sized,
+    K::Owned: Clone + Eq + Hash,
+{
+    fn insert(&mut self, (key, image): (K::Owned, RgbaImage)) {
+        if self.try_insert(key, image).is_err() {
+            panic!("Not enough space in the atlas for the inserted image");
+        }
+    }
+
+    fn fetch(&self, key: &K) -> Region {
+        self.get(key)
+            .copied()
+            .expect("No region in the atlas for the given key")
+    }
+
+    /// Copy the first page into `dest`. Use `compile_pages` for atlases
+    /// with more than one: until then, the atlas still counts as modified,
+    /// since the other pages haven't been compiled.
+    fn compile_into(&mut self, dest: &mut RgbaImage) -> bool {
+        self.compile_page_into(0, dest);
+        self.modified = self.page_count() > 1;
 
         true
     }
@@ -156,3 +1024,191 @@ where
         self.modified
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solid(size: [u32; 2], color: [u8; 4]) -> RgbaImage {
+        RgbaImage::from_pixel(size[0], size[1], image::Rgba(color))
+    }
+
+    const RED: [u8; 4] = [255, 0, 0, 255];
+    const GREEN: [u8; 4] = [0, 255, 0, 255];
+    const BLUE: [u8; 4] = [0, 0, 255, 255];
+
+    #[test]
+    fn region_ids_are_the_same_on_every_platform() {
+        #[derive(Hash)]
+        enum Sprite {
+            Player(usize),
+            Enemy,
+        }
+
+        // Pinned, so a change to the hashing that would break saved IDs
+        // shows up here
+        assert_eq!(RegionId::of("player"), RegionId(0x98e3_3875_1854_c60d));
+
+        // `usize`s, lengths and discriminants hash as 64-bit little-endian
+        let mut wide = Fnv1a::default();
+        0_u64.hash(&mut wide);
+        7_u64.hash(&mut wide);
+        assert_eq!(RegionId::of(&Sprite::Player(7)), RegionId(wide.finish()));
+        assert_ne!(
+            RegionId::of(&Sprite::Player(0)),
+            RegionId::of(&Sprite::Enemy)
+        );
+        assert_eq!(RegionId::of(&[1_u8][..]), {
+            let mut wide = Fnv1a::default();
+            wide.write(&1_u64.to_le_bytes());
+            wide.write(&[1]);
+            RegionId(wide.finish())
+        });
+    }
+
+    #[test]
+    fn colliding_region_ids_resolve_to_nothing() {
+        /// A key that hashes the same whatever it is.
+        #[derive(Debug, Clone, PartialEq, Eq)]
+        struct Clash(u8);
+
+        impl Hash for Clash {
+            fn hash<H: Hasher>(&self, _: &mut H) {}
+        }
+
+        let mut atlas = ImageAtlas::<Clash>::with_size([64, 64]);
+        atlas.insert((Clash(1), solid([8, 8], RED)));
+        let id = atlas.region_id(&Clash(1)).unwrap();
+        assert_eq!(atlas.resolve(id), atlas.get(&Clash(1)));
+
+        atlas.insert((Clash(2), solid([8, 8], GREEN)));
+        assert_eq!(atlas.region_id(&Clash(1)), None);
+        assert_eq!(atlas.region_id(&Clash(2)), None);
+        assert_eq!(atlas.resolve(id), None);
+
+        // Reinserting a key doesn't take the ID back
+        atlas.insert((Clash(1), solid([8, 8], BLUE)));
+        assert_eq!(atlas.resolve(id), None);
+    }
+
+    #[test]
+    fn reinserting_keeps_a_region_id() {
+        let mut atlas = ImageAtlas::<str>::with_size([64, 64]);
+        atlas.insert(("a".to_owned(), solid([8, 8], RED)));
+        let id = atlas.region_id("a").unwrap();
+
+        atlas.insert(("a".to_owned(), solid([16, 16], GREEN)));
+        assert_eq!(atlas.region_id("a"), Some(id));
+        assert_eq!(atlas.resolve(id), atlas.get("a"));
+    }
+
+    /// A 16x8 pre-made atlas with a green region `a` and blue region `b`.
+    fn mod_atlas() -> (RgbaImage, HashMap<String, Region>) {
+        let mut image = solid([16, 8], GREEN);
+        image::imageops::replace(&mut image, &solid([8, 8], BLUE), 8, 0);
+
+        let region = |x: u32| Region::new(([x, 0], [8, 8]), ([x as f32 / 16., 0.], [0.5, 1.]));
+        let regions = vec![("a".to_owned(), region(0)), ("b".to_owned(), region(8))];
+        (image, regions.into_iter().collect())
+    }
+
+    #[test]
+    fn merged_regions_are_rebased() {
+        let mut atlas = ImageAtlas::<str>::with_size([64, 64]);
+        atlas.insert(("base".to_owned(), solid([8, 8], RED)));
+
+        let (image, regions) = mod_atlas();
+        atlas
+            .merge_premade(image.clone(), regions.clone(), "mod")
+            .unwrap();
+        atlas.merge_premade(image, regions, "dlc").unwrap();
+
+        let compiled = atlas.compile();
+        for namespace in &["mod", "dlc"] {
+            let a = atlas.fetch(&format!("{}/a", namespace));
+            let b = atlas.fetch(&format!("{}/b", namespace));
+            let ([ax, ay], _) = a.pixels;
+            assert_eq!(b.pixels, ([ax + 8, ay], [8, 8]));
+
+            for region in &[a, b] {
+                let ([x, y], [w, h]) = region.pixels;
+                let expected_uv = (
+                    [x as f32 / 64., y as f32 / 64.],
+                    [w as f32 / 64., h as f32 / 64.],
+                );
+                assert_eq!(region.uv, expected_uv);
+            }
+            assert_eq!(compiled.get_pixel(ax, ay).0, GREEN);
+            assert_eq!(compiled.get_pixel(ax + 8, ay).0, BLUE);
+        }
+
+        let ([x, y], _) = atlas.fetch("base").pixels;
+        assert_eq!(compiled.get_pixel(x, y).0, RED);
+    }
+
+    #[test]
+    fn merge_collisions_are_all_reported() {
+        let mut atlas = ImageAtlas::<str>::with_size([64, 64]);
+        let (image, regions) = mod_atlas();
+        atlas
+            .merge_premade(image.clone(), regions.clone(), "mod")
+            .unwrap();
+
+        let before = atlas.fetch("mod/a");
+        assert_eq!(
+            atlas.merge_premade(image, regions, "mod"),
+            Err(MergeError::Collision(vec![
+                "mod/a".to_owned(),
+                "mod/b".to_owned()
+            ]))
+        );
+        assert_eq!(atlas.fetch("mod/a"), before);
+    }
+
+    #[test]
+    fn reinserting_keeps_extras() {
+        let mut atlas = ImageAtlas::<str>::with_size([64, 64]);
+        let extras = RegionExtras::parse("pivot = [1.0, 2.0]", [8, 8]).unwrap();
+        atlas
+            .try_insert_with_extras("a".to_owned(), solid([8, 8], RED), extras.clone())
+            .unwrap();
+
+        atlas
+            .try_insert("a".to_owned(), solid([8, 8], GREEN))
+            .unwrap();
+        assert_eq!(atlas.fetch_extras("a"), extras);
+    }
+
+    #[test]
+    fn reloads_never_draw_over_other_regions() {
+        let mut atlas = ImageAtlas::<str>::with_size([64, 64]);
+        atlas.insert(("a".to_owned(), solid([8, 8], RED)));
+        atlas.insert(("b".to_owned(), solid([8, 8], BLUE)));
+        let mut compiled = atlas.compile();
+
+        let filled_with = |image: &RgbaImage, region: Region, color: [u8; 4]| {
+            let ([x, y], [w, h]) = region.pixels;
+            (x..x + w).all(|x| (y..y + h).all(|y| image.get_pixel(x, y).0 == color))
+        };
+
+        for &size in &[[4, 4], [12, 12], [6, 6], [16, 8]] {
+            let before = atlas.fetch("a");
+            atlas
+                .try_insert("a".to_owned(), solid(size, GREEN))
+                .unwrap();
+            atlas.compile_into(&mut compiled);
+
+            let after = atlas.fetch("a");
+            assert_eq!(after.size(), size);
+            assert!(filled_with(&compiled, after, GREEN));
+            assert!(filled_with(&compiled, atlas.fetch("b"), BLUE));
+
+            let moved = atlas.take_moved();
+            let expected_moved: Vec<String> = match after == before {
+                true => vec![],
+                false => vec!["a".to_owned()],
+            };
+            assert_eq!(moved, expected_moved);
+        }
+    }
+}
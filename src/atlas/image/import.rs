@@ -0,0 +1,410 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use image::RgbaImage;
+use serde::de::{self, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::Deserialize;
+
+use super::{ImageAtlas, NamedRect, RegionExtras, Trim};
+use crate::draw::{Orientation, Region};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImportError {
+    Json(String),
+    /// The frame with this name isn't entirely within the atlas image.
+    OutOfBounds(String),
+    /// More than one frame has this name.
+    Duplicate(String),
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ImportError::Json(e) => write!(f, "Failed to parse atlas JSON: {}", e),
+            ImportError::OutOfBounds(name) => {
+                write!(f, "Frame `{}` is outside of the atlas image", name)
+            }
+            ImportError::Duplicate(name) => write!(f, "There is more than one frame `{}`", name),
+        }
+    }
+}
+
+impl std::error::Error for ImportError {}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+struct Rect {
+    x: u32,
+    y: u32,
+    w: u32,
+    h: u32,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+struct Size {
+    w: u32,
+    h: u32,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+struct Point {
+    x: f32,
+    y: f32,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Frame {
+    /// Only present when the frames are exported as a list.
+    filename: Option<String>,
+    /// The frame's size is as drawn, so rotated frames take up `h` by `w`
+    /// in the atlas.
+    frame: Rect,
+    #[serde(default)]
+    rotated: bool,
+    sprite_source_size: Option<Rect>,
+    source_size: Option<Size>,
+    /// TexturePacker's pivot, as a fraction of the source size.
+    pivot: Option<Point>,
+}
+
+/// The frames in the order they were exported, from either a hash of
+/// frames by name or a list of frames with a `filename` each.
+#[derive(Debug)]
+struct Frames(Vec<(String, Frame)>);
+
+impl<'de> Deserialize<'de> for Frames {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct FramesVisitor;
+
+        impl<'de> Visitor<'de> for FramesVisitor {
+            type Value = Frames;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a map or list of frames")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Frames, A::Error> {
+                let mut frames = vec![];
+                while let Some(entry) = map.next_entry::<String, Frame>()? {
+                    frames.push(entry);
+                }
+                Ok(Frames(frames))
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Frames, A::Error> {
+                let mut frames = vec![];
+                while let Some(frame) = seq.next_element::<Frame>()? {
+                    let name = frame
+                        .filename
+                        .clone()
+                        .ok_or_else(|| de::Error::missing_field("filename"))?;
+                    frames.push((name, frame));
+                }
+                Ok(Frames(frames))
+            }
+        }
+
+        deserializer.deserialize_any(FramesVisitor)
+    }
+}
+
+/// An Aseprite slice. Each key applies from its frame onwards.
+#[derive(Debug, Deserialize)]
+struct Slice {
+    name: String,
+    keys: Vec<SliceKey>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SliceKey {
+    frame: usize,
+    bounds: Rect,
+    /// Relative to the top-left of `bounds`.
+    pivot: Option<Point>,
+}
+
+impl Slice {
+    fn key_for(&self, frame: usize) -> Option<&SliceKey> {
+        self.keys
+            .iter()
+            .filter(|key| key.frame <= frame)
+            .max_by_key(|key| key.frame)
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Meta {
+    #[serde(default)]
+    slices: Vec<Slice>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Sheet {
+    frames: Frames,
+    #[serde(default)]
+    meta: Meta,
+}
+
+impl<'a> ImageAtlas<'a, str> {
+    /// Load an atlas exported by TexturePacker in its JSON (Hash or Array)
+    /// format, keyed by frame name. Pivots are read if they were exported,
+    /// and rotated frames are left rotated, with `Orientation::Rot90`.
+    ///
+    /// Trimmed frames keep only their trimmed pixels, with their extras
+    /// moved to match and the trim recorded in `RegionExtras::trim`.
+    pub fn from_texture_packer_json(image: RgbaImage, json: &str) -> Result<Self, ImportError> {
+        Self::from_json_sheet(image, json)
+    }
+
+    /// Load a sprite sheet exported by Aseprite as JSON (in either the Hash
+    /// or Array layout), keyed by frame filename.
+    ///
+    /// Slices become boxes on every frame they're keyed on, and the first
+    /// slice with a pivot sets each frame's pivot. Trimmed frames work the
+    /// same as in `from_texture_packer_json`.
+    pub fn from_aseprite_json(image: RgbaImage, json: &str) -> Result<Self, ImportError> {
+        Self::from_json_sheet(image, json)
+    }
+
+    /// Both tools write the same format, apart from where the pivot goes.
+    fn from_json_sheet(image: RgbaImage, json: &str) -> Result<Self, ImportError> {
+        let sheet =
+            serde_json::from_str::<Sheet>(json).map_err(|e| ImportError::Json(e.to_string()))?;
+
+        let size = [image.width(), image.height()];
+        let [bw, bh] = [size[0] as f32, size[1] as f32];
+
+        let mut regions = HashMap::with_capacity(sheet.frames.0.len());
+        let mut extras = Vec::with_capacity(sheet.frames.0.len());
+
+        for (index, (name, frame)) in sheet.frames.0.into_iter().enumerate() {
+            let Rect { x, y, w, h } = frame.frame;
+            let (orientation, [pw, ph]) = match frame.rotated {
+                true => (Orientation::Rot90, [h, w]),
+                false => (Orientation::Upright, [w, h]),
+            };
+            if x.saturating_add(pw) > size[0] || y.saturating_add(ph) > size[1] {
+                return Err(ImportError::OutOfBounds(name));
+            }
+
+            let offset = frame
+                .sprite_source_size
+                .map(|rect| [rect.x, rect.y])
+                .unwrap_or([0, 0]);
+            let source_size = frame
+                .source_size
+                .map(|size| [size.w, size.h])
+                .unwrap_or([w, h]);
+            let trim = match offset == [0, 0] && source_size == [w, h] {
+                true => None,
+                false => Some(Trim {
+                    offset,
+                    source_size,
+                }),
+            };
+
+            // Everything below is in the untrimmed image until it's moved
+            let [sw, sh] = [source_size[0] as f32, source_size[1] as f32];
+            let keys = sheet
+                .meta
+                .slices
+                .iter()
+                .filter_map(|slice| Some((slice, slice.key_for(index)?)))
+                .collect::<Vec<_>>();
+
+            let slice_pivot = keys.iter().find_map(|(_, key)| {
+                let pivot = key.pivot?;
+                Some([key.bounds.x as f32 + pivot.x, key.bounds.y as f32 + pivot.y])
+            });
+            let pivot = slice_pivot
+                .or_else(|| frame.pivot.map(|pivot| [pivot.x * sw, pivot.y * sh]))
+                .unwrap_or([sw / 2., sh / 2.]);
+
+            let [ox, oy] = [offset[0] as f32, offset[1] as f32];
+            let region_extras = RegionExtras {
+                pivot: [pivot[0] - ox, pivot[1] - oy],
+                boxes: keys
+                    .iter()
+                    .map(|(slice, key)| NamedRect {
+                        name: slice.name.clone(),
+                        pos: [key.bounds.x as f32 - ox, key.bounds.y as f32 - oy],
+                        size: [key.bounds.w as f32, key.bounds.h as f32],
+                    })
+                    .collect(),
+                trim,
+            };
+
+            let region = Region {
+                pixels: ([x, y], [pw, ph]),
+                uv: (
+                    [x as f32 / bw, y as f32 / bh],
+                    [pw as f32 / bw, ph as f32 / bh],
+                ),
+                page: 0,
+                orientation,
+            };
+
+            if regions.insert(name.clone(), region).is_some() {
+                return Err(ImportError::Duplicate(name));
+            }
+            extras.push((name, region_extras));
+        }
+
+        let mut atlas = Self::pre_made(image, regions, size);
+        for (name, region_extras) in extras {
+            atlas.set_extras(&name, region_extras);
+        }
+
+        Ok(atlas)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::atlas::Atlas;
+
+    /// An image where every pixel is a different color, so that a region
+    /// in the wrong place compiles to the wrong pixels.
+    fn gradient(size: [u32; 2]) -> RgbaImage {
+        RgbaImage::from_fn(size[0], size[1], |x, y| {
+            image::Rgba([x as u8 * 16, y as u8 * 16, 128, 255])
+        })
+    }
+
+    /// Every region compiles back to the pixels it was imported from.
+    fn assert_round_trips(atlas: &mut ImageAtlas<str>, image: &RgbaImage, keys: &[&str]) {
+        let compiled = atlas.compile();
+        for key in keys {
+            let ([x, y], [w, h]) = atlas.fetch(key).pixels;
+            for (px, py) in (x..x + w).flat_map(|px| (y..y + h).map(move |py| (px, py))) {
+                assert_eq!(
+                    compiled.get_pixel(px, py),
+                    image.get_pixel(px, py),
+                    "{}",
+                    key
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn texture_packer_sheets_round_trip() {
+        let image = gradient([16, 16]);
+        let json = include_str!("fixtures/texture_packer.json");
+        let mut atlas = ImageAtlas::from_texture_packer_json(image.clone(), json).unwrap();
+
+        let hero = atlas.fetch("hero.png");
+        assert_eq!(hero.pixels, ([0, 0], [8, 8]));
+        assert_eq!(hero.orientation, Orientation::Upright);
+        assert_eq!(atlas.fetch_extras("hero.png").pivot, [4., 8.]);
+        assert_eq!(atlas.fetch_extras("hero.png").trim, None);
+
+        // Trimmed frames keep their pivot where it was in the whole image
+        let coin = atlas.fetch("coin.png");
+        assert_eq!(coin.pixels, ([8, 0], [4, 6]));
+        let coin_extras = atlas.fetch_extras("coin.png");
+        assert_eq!(coin_extras.pivot, [2., 3.]);
+        assert_eq!(
+            coin_extras.trim,
+            Some(Trim {
+                offset: [2, 1],
+                source_size: [8, 8],
+            })
+        );
+
+        let sword = atlas.fetch("sword.png");
+        assert_eq!(sword.pixels, ([0, 8], [12, 4]));
+        assert_eq!(sword.orientation, Orientation::Rot90);
+        assert_eq!(sword.size(), [4, 12]);
+        assert_eq!(atlas.fetch_extras("sword.png").pivot, [2., 6.]);
+
+        assert_round_trips(&mut atlas, &image, &["hero.png", "coin.png", "sword.png"]);
+    }
+
+    #[test]
+    fn aseprite_sheets_round_trip() {
+        let image = gradient([16, 8]);
+        let json = include_str!("fixtures/aseprite.json");
+        let mut atlas = ImageAtlas::from_aseprite_json(image.clone(), json).unwrap();
+
+        assert_eq!(atlas.fetch("walk 0.aseprite").pixels, ([0, 0], [8, 8]));
+        assert_eq!(
+            atlas.fetch_extras("walk 0.aseprite"),
+            RegionExtras {
+                pivot: [4., 8.],
+                boxes: vec![
+                    NamedRect {
+                        name: "feet".to_owned(),
+                        pos: [2., 6.],
+                        size: [4., 2.],
+                    },
+                    NamedRect {
+                        name: "hitbox".to_owned(),
+                        pos: [1., 1.],
+                        size: [6., 7.],
+                    },
+                ],
+                trim: None,
+            }
+        );
+
+        // Slice keys carry on until the next key, and move with the trim
+        assert_eq!(atlas.fetch("walk 1.aseprite").pixels, ([8, 0], [6, 7]));
+        assert_eq!(
+            atlas.fetch_extras("walk 1.aseprite"),
+            RegionExtras {
+                pivot: [3., 7.],
+                boxes: vec![
+                    NamedRect {
+                        name: "feet".to_owned(),
+                        pos: [1., 5.],
+                        size: [4., 2.],
+                    },
+                    NamedRect {
+                        name: "hitbox".to_owned(),
+                        pos: [1., 0.],
+                        size: [5., 7.],
+                    },
+                ],
+                trim: Some(Trim {
+                    offset: [1, 1],
+                    source_size: [8, 8],
+                }),
+            }
+        );
+
+        assert_round_trips(&mut atlas, &image, &["walk 0.aseprite", "walk 1.aseprite"]);
+    }
+
+    #[test]
+    fn bad_sheets_are_reported() {
+        let image = || gradient([16, 16]);
+        let frame = |name: &str, x: u32| {
+            format!(
+                r#"{{"filename": "{}", "frame": {{"x": {}, "y": 0, "w": 8, "h": 8}}}}"#,
+                name, x
+            )
+        };
+        let sheet = |frames: &[String]| format!(r#"{{"frames": [{}]}}"#, frames.join(","));
+
+        let duplicate = sheet(&[frame("a", 0), frame("a", 8)]);
+        assert_eq!(
+            ImageAtlas::from_aseprite_json(image(), &duplicate).err(),
+            Some(ImportError::Duplicate("a".to_owned()))
+        );
+
+        let outside = sheet(&[frame("a", 12)]);
+        assert_eq!(
+            ImageAtlas::from_aseprite_json(image(), &outside).err(),
+            Some(ImportError::OutOfBounds("a".to_owned()))
+        );
+
+        let error = ImageAtlas::from_texture_packer_json(image(), "{}")
+            .err()
+            .unwrap();
+        assert!(matches!(error, ImportError::Json(_)));
+        let error: Box<dyn std::error::Error> = Box::new(error);
+        assert!(error.to_string().starts_with("Failed to parse atlas JSON"));
+    }
+}
@@ -1,21 +1,94 @@
 use std::{
     borrow::Cow,
     cmp::Eq,
-    collections::HashMap,
+    collections::{HashMap, HashSet},
+    fmt::Debug,
     hash::Hash,
     io::Cursor,
-    sync::{Arc, Mutex},
+    panic::AssertUnwindSafe,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
     thread::JoinHandle,
     time::Duration,
 };
 
 #[cfg(not(target_arch = "wasm32"))]
-use std::sync::mpsc::{self, Receiver, Sender};
+use std::{
+    collections::VecDeque,
+    sync::mpsc::{self, Receiver, RecvTimeoutError, Sender},
+    time::Instant,
+};
+
+use rodio::{Decoder, OutputStream, Sample, Sink, Source};
+use serde::Serialize;
+
+mod backend;
+mod beat;
+mod channels;
+mod cpu;
+mod diagnosis;
+mod echo;
+pub mod fade;
+mod limiter;
+pub mod loudness;
+pub mod music;
+mod quality;
+mod snapshot;
+mod sniff;
+mod status;
+pub mod testing;
+pub mod tone;
 
-use rodio::{Decoder, OutputStream, OutputStreamHandle, Sink, Source};
+pub use self::beat::BeatClock;
+pub use self::channels::{ChannelPolicy, MonoPlacement};
+pub use self::diagnosis::AudioDiagnosis;
+pub use self::echo::Echo;
+pub use self::fade::FadeCurve;
+pub use self::limiter::Limiter;
+pub use self::loudness::{analyze_volumes, LoudnessCache, VolumeTarget};
+pub use self::music::{MusicDirector, MusicSpec};
+pub use self::quality::ResampleQuality;
+pub use self::snapshot::{MixerSnapshot, TrackSnapshot};
+pub use self::status::TrackStatus;
+pub use self::tone::{Adsr, Tone, Wave};
+
+use self::{
+    backend::{AudioBackend, DeviceBackend, MasterBus, RodioBackend},
+    beat::{TrackClock, Tracked},
+    channels::ChannelMap,
+    cpu::Timed,
+    diagnosis::SilenceMeter,
+    echo::{EchoControl, Echoing},
+    fade::Fade,
+    quality::{Decoded, Pcm, PcmSource, Reduced},
+    sniff::{ogg_duration, sniff_container},
+    status::{Finishing, SlotStatus},
+};
+
+#[cfg(not(target_arch = "wasm32"))]
+use self::{cpu::CpuMeter, snapshot::SNAPSHOT_PERIOD};
 
 pub const MAX_TRACKS: usize = 16;
 
+/// The slowest a track can play. Anything slower is paused instead.
+const MIN_SPEED: f32 = 0.01;
+
+/// How often (in audio time) fading sources re-check their level.
+const FADE_PERIOD: Duration = Duration::from_millis(5);
+
+/// How often (in audio time) scheduled sounds check if they're cancelled.
+const CANCEL_PERIOD: Duration = Duration::from_millis(5);
+
+/// How often (in audio time) sounds pick up changes to their volume.
+const VOLUME_PERIOD: Duration = Duration::from_millis(5);
+
+/// How many sinks are made as soon as the output opens, so the first
+/// tracks to start don't have to wait for theirs.
+const IDLE_SINKS: usize = 4;
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct AudioBytes(Arc<Cow<'static, [u8]>>);
 
@@ -34,6 +107,14 @@ impl AsRef<[u8]> for AudioBytes {
 pub type AudioLibrary<K> = HashMap<K, AudioBytes>;
 pub type AudioVolumes<K> = HashMap<K, f32>;
 
+/// Where to find the audio for one entry of a library loaded with
+/// `Mixer::load_library_from`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AudioSource {
+    Path(PathBuf),
+    Bytes(AudioBytes),
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct Sound<K> {
     pub key: K,
@@ -41,6 +122,30 @@ pub struct Sound<K> {
     pub speed: f32,
 }
 
+impl<K> Sound<K> {
+    /// A sound at full volume and normal speed.
+    pub fn new(key: K) -> Self {
+        Sound {
+            key,
+            volume: 1.0,
+            speed: 1.0,
+        }
+    }
+}
+
+/// A sound played with `Mixer::play_sound_after`, which can be cancelled
+/// before or while it plays.
+#[derive(Debug, Clone)]
+pub struct SoundHandle {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl SoundHandle {
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
 // TODO: Consider how you might force a restart of a non-looping track?
 #[derive(Debug, Clone, PartialEq)]
 pub struct Track<K: Clone> {
@@ -48,16 +153,177 @@ pub struct Track<K: Clone> {
     pub volume: f32,
     pub playing: bool,
     pub looping: bool,
+    /// How fast the track plays, where 1.0 is normal speed. This changes
+    /// the pitch too, and can be changed while the track plays. A speed of
+    /// 0.0 or less pauses the track.
+    ///
+    /// Beats and fades are in the track's own time, so they speed up and
+    /// slow down with it.
+    pub speed: f32,
+    /// An echo on the track, which can be changed while it plays.
+    pub echo: Option<Echo>,
     pub feedback_rate: Option<Duration>,
+    /// Tracks in the same group that start in the same state update begin
+    /// playing together, so layers of the same piece stay in phase.
+    pub sync_group: Option<u32>,
+}
+
+impl<K: Clone> Track<K> {
+    /// A track that's playing and looping at full volume and normal speed,
+    /// with no echo, feedback or sync group. Set any other fields with
+    /// struct update syntax, e.g. `Track { looping: false, ..Track::new(key) }`.
+    pub fn new(key: K) -> Self {
+        Track {
+            key,
+            volume: 1.0,
+            playing: true,
+            looping: true,
+            speed: 1.0,
+            echo: None,
+            feedback_rate: None,
+            sync_group: None,
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct AudioState<'a, K: Clone> {
+    /// The volume of every sound and tone, including ones already playing.
     pub sound_volume: f32,
     pub track_volume: f32,
     pub tracks: &'a [Track<K>],
 }
 
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct MixerOptions {
+    pub sample_rate: Option<u32>,
+
+    /// The output buffer size in frames. Smaller buffers lower latency, but
+    /// can crackle on slow machines. It's clamped to what the device
+    /// supports, and `MixerEvent::OutputConfigFallback` is sent if it isn't
+    /// used as given.
+    pub buffer_size: Option<u32>,
+
+    /// The curve used by fades that don't specify their own.
+    pub fade_curve: FadeCurve,
+
+    /// How sources are mapped onto the output's channels.
+    pub channel_policy: ChannelPolicy,
+
+    /// Audio longer than this is reported in `MixerEvent::LibraryProblems`,
+    /// since every sound and track is decoded from memory. Leave it as
+    /// `None` to allow any length.
+    pub max_duration: Option<Duration>,
+
+    /// How far back `Mixer::snapshot_history` goes. While this is set, the
+    /// audio thread takes a snapshot every 100ms. This isn't supported on
+    /// wasm, where the history is always empty.
+    pub snapshot_history: Option<Duration>,
+
+    /// Lower this to try to save CPU on slow machines, at the cost of sound
+    /// quality. See `ResampleQuality::Half`.
+    pub resample_quality: ResampleQuality,
+
+    /// Audio no longer than this is decoded when the library is set, and
+    /// played from memory from then on, instead of being decoded every
+    /// time it plays. Decoded audio takes about 350KB per second (for
+    /// 44.1kHz stereo), so this is best kept to sound effects and short
+    /// loops.
+    pub predecode_under: Option<Duration>,
+
+    /// Time how long sources take to produce their samples, for
+    /// `Mixer::cpu_estimate`. This adds a little overhead of its own, and
+    /// isn't supported on wasm.
+    pub measure_cpu: bool,
+
+    /// Limit the final mix, so that many loud sounds at once don't clip.
+    /// Everything is mixed by jamjar before it reaches the device while
+    /// this is set.
+    pub limiter: Option<Limiter>,
+
+    /// How long the output has to be silent before `Mixer::output_is_silent`
+    /// says so. Like `limiter`, this mixes everything in jamjar, so the
+    /// final mix can be listened to.
+    pub detect_silence: Option<Duration>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct OutputConfig {
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub buffer_size: Option<u32>,
+}
+
+/// Something wrong with an entry in the library, found when it's set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LibraryProblem {
+    /// The audio is zero bytes long.
+    Empty,
+    /// The first few bytes don't look like any format the decoders know.
+    UnknownFormat,
+    /// The audio is longer than `MixerOptions::max_duration`. Only formats
+    /// whose length can be found without decoding them are checked.
+    TooLong(Duration),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MixerEvent {
+    OutputConfigFallback {
+        requested: MixerOptions,
+        actual: Option<OutputConfig>,
+    },
+    /// The library from the `load_library_from` call with this id is live.
+    LibraryLoaded { id: usize },
+    /// The volumes from the `normalize_library` call with this id are live.
+    LibraryNormalized { id: usize },
+    /// A file couldn't be read while loading a library. The rest of the
+    /// library is still loaded, without this entry.
+    LibraryLoadFailed {
+        id: usize,
+        path: PathBuf,
+        error: String,
+    },
+    /// The output stream couldn't be opened, or stopped working. Nothing
+    /// plays until `Mixer::reopen_output` opens it again.
+    OutputError { error: String },
+    /// The output stream was reopened by `Mixer::reopen_output`. Tracks
+    /// have been restarted on it, and any sounds that were playing are cut.
+    OutputReopened { config: Option<OutputConfig> },
+    /// A track with a tempo set by `Mixer::set_track_bpm` reached a beat.
+    /// `beat_number` counts the beats since the track started.
+    ///
+    /// This is sent when the beat is mixed, which is ahead of when it's
+    /// heard by the output buffer's latency (see `Mixer::output_config`).
+    /// It's then seen the next time `Mixer::events` is called, so polling
+    /// once per frame adds up to a frame of delay on top of that.
+    Beat { index: usize, beat_number: u64 },
+    /// The track in this slot started, paused, finished, or was removed.
+    /// See `Mixer::track_status`.
+    TrackStatusChanged { index: usize, status: TrackStatus },
+    /// The audio for this key (as named by `Mixer::name_keys_with`)
+    /// couldn't be decoded, so it wasn't played. `container` is a guess at
+    /// the format from the first few bytes, like `"mp4"` or `"ogg/opus"`.
+    ///
+    /// Enable the `audio-symphonia` feature for AAC, ALAC, and ADPCM. Opus
+    /// isn't supported: none of the decoders, symphonia included, can
+    /// decode it, so it's always reported here as `"ogg/opus"`.
+    ///
+    /// This is only sent the first time the key is played. After that it's
+    /// skipped until the library is replaced.
+    UnsupportedFormat {
+        key: String,
+        container: &'static str,
+    },
+    /// Entries in a library passed to `Mixer::new`, `update_library` or
+    /// `load_library_from` that are unlikely to play, or too long to
+    /// decode in memory. Keys are named by `Mixer::name_keys_with`, and
+    /// sorted. The library is still used, so these will fail to play or
+    /// play slowly.
+    LibraryProblems {
+        problems: Vec<(String, LibraryProblem)>,
+    },
+}
+
 #[derive(Debug, Clone, PartialEq)]
 struct StateUpdate<K: Clone> {
     pub sound_volume: f32,
@@ -65,14 +331,75 @@ struct StateUpdate<K: Clone> {
     pub tracks: [Option<Track<K>>; MAX_TRACKS],
 }
 
+impl<K: Clone> StateUpdate<K> {
+    fn new(state: AudioState<K>) -> Self {
+        let mut tracks = [
+            None, None, None, None, None, None, None, None, None, None, None, None, None, None,
+            None, None,
+        ];
+        for i in 0..MAX_TRACKS {
+            tracks[i] = state.tracks.get(i).cloned();
+        }
+        StateUpdate {
+            sound_volume: state.sound_volume,
+            track_volume: state.track_volume,
+            tracks,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 enum AudioCmd<K: Clone> {
     Quit,
     Prewarm,
     State(StateUpdate<K>),
     PlaySound(Sound<K>),
+    PlaySoundAfter {
+        sound: Sound<K>,
+        delay: Duration,
+        cancelled: Arc<AtomicBool>,
+    },
+    PlayTone(Tone),
     UpdateLibrary(AudioLibrary<K>, bool),
+    LibraryLoaded {
+        id: usize,
+        library: AudioLibrary<K>,
+        restart_tracks: bool,
+    },
     UpdateVolumes(AudioVolumes<K>),
+    NameKeys(fn(&K) -> String),
+    LibraryNormalized {
+        id: usize,
+        volumes: AudioVolumes<K>,
+    },
+    Fade {
+        index: usize,
+        level: f32,
+        duration: Duration,
+        curve: Option<FadeCurve>,
+    },
+    /// Everything sent between `Mixer::begin_frame` and `commit_frame`.
+    Frame(Vec<AudioCmd<K>>),
+
+    #[cfg(not(target_arch = "wasm32"))]
+    ReopenOutput(Sender<Result<Option<OutputConfig>, String>>),
+    #[cfg(not(target_arch = "wasm32"))]
+    QueryOutputConfig(Sender<Option<OutputConfig>>),
+    #[cfg(not(target_arch = "wasm32"))]
+    QuerySnapshot(Sender<MixerSnapshot<K>>),
+    #[cfg(not(target_arch = "wasm32"))]
+    QueryDiagnosis(Sender<AudioDiagnosis>),
+    #[cfg(not(target_arch = "wasm32"))]
+    QuerySnapshotHistory(Sender<Vec<(Duration, MixerSnapshot<K>)>>),
+}
+
+impl<K: Clone> AudioCmd<K> {
+    fn starts_sound(&self) -> bool {
+        matches!(
+            self,
+            AudioCmd::PlaySound(_) | AudioCmd::PlaySoundAfter { .. } | AudioCmd::PlayTone(_)
+        )
+    }
 }
 
 pub struct Mixer<K: 'static + Clone + Send + Eq + Hash> {
@@ -85,6 +412,16 @@ pub struct Mixer<K: 'static + Clone + Send + Eq + Hash> {
     _thread: Option<JoinHandle<()>>,
     initialized: bool,
     feedback_buffer: Arc<Mutex<Vec<usize>>>,
+    events_buffer: Arc<Mutex<Vec<MixerEvent>>>,
+    clocks: Vec<Arc<Mutex<TrackClock>>>,
+    statuses: Vec<Arc<Mutex<SlotStatus>>>,
+    next_load_id: usize,
+    silence: Option<Arc<SilenceMeter>>,
+    /// Commands queued since `begin_frame`, if a frame is open.
+    frame: Option<Vec<AudioCmd<K>>>,
+
+    #[cfg(not(target_arch = "wasm32"))]
+    cpu_meter: Option<CpuMeter>,
 }
 
 impl<K: 'static + Clone + Send + Eq + Hash> Drop for Mixer<K> {
@@ -99,19 +436,52 @@ impl<K: 'static + Clone + Send + Eq + Hash> Drop for Mixer<K> {
 }
 
 impl<K: 'static + Clone + Send + Eq + Hash> Mixer<K> {
-    pub fn new(audio_library: AudioLibrary<K>, audio_volumes: Option<AudioVolumes<K>>) -> Self {
+    pub fn new(
+        audio_library: AudioLibrary<K>,
+        audio_volumes: Option<AudioVolumes<K>>,
+        options: MixerOptions,
+    ) -> Self {
         let audio_volumes = audio_volumes.unwrap_or_default();
         let feedback_buffer = Arc::new(Mutex::new(Vec::new()));
         let feedback_buffer_ref = Arc::clone(&feedback_buffer);
+        let events_buffer = Arc::new(Mutex::new(Vec::new()));
+        let events_buffer_ref = Arc::clone(&events_buffer);
+        let clocks = (0..MAX_TRACKS)
+            .map(|_| Arc::new(Mutex::new(TrackClock::default())))
+            .collect::<Vec<_>>();
+        let clocks_ref = clocks.clone();
+        let statuses = (0..MAX_TRACKS)
+            .map(|_| Arc::new(Mutex::new(SlotStatus::default())))
+            .collect::<Vec<_>>();
+        let statuses_ref = statuses.clone();
+        let silence = options
+            .detect_silence
+            .map(|window| Arc::new(SilenceMeter::new(window)));
+        let silence_ref = silence.clone();
 
         #[cfg(not(target_arch = "wasm32"))]
         {
+            let cpu_meter = match options.measure_cpu {
+                true => Some(CpuMeter::new()),
+                false => None,
+            };
+            let cpu_time = cpu_meter.as_ref().map(CpuMeter::total);
             let (sender, receiver) = mpsc::channel();
 
             let _thread = {
                 let thread = std::thread::spawn(move || {
-                    let mut speaker =
-                        Speaker::new(receiver, audio_library, audio_volumes, feedback_buffer_ref);
+                    let mut speaker = Speaker::new(
+                        receiver,
+                        audio_library,
+                        audio_volumes,
+                        options,
+                        feedback_buffer_ref,
+                        events_buffer_ref,
+                        clocks_ref,
+                        statuses_ref,
+                        cpu_time,
+                        silence_ref,
+                    );
                     while speaker.listen() {}
                 });
                 Some(thread)
@@ -122,21 +492,62 @@ impl<K: 'static + Clone + Send + Eq + Hash> Mixer<K> {
                 _thread,
                 initialized: false,
                 feedback_buffer,
+                events_buffer,
+                clocks,
+                statuses,
+                next_load_id: 0,
+                silence,
+                frame: None,
+                cpu_meter,
             }
         }
 
         #[cfg(target_arch = "wasm32")]
         {
-            let speaker = Speaker::new(audio_library, audio_volumes, feedback_buffer_ref);
+            let speaker = Speaker::new(
+                audio_library,
+                audio_volumes,
+                options,
+                feedback_buffer_ref,
+                events_buffer_ref,
+                clocks_ref,
+                statuses_ref,
+                None,
+                silence_ref,
+            );
             Mixer {
                 speaker,
                 _thread: None,
                 initialized: false,
                 feedback_buffer,
+                events_buffer,
+                clocks,
+                statuses,
+                next_load_id: 0,
+                silence,
+                frame: None,
             }
         }
     }
 
+    /// Like `new`, but the output stream is opened on the audio thread
+    /// straight away, rather than by `init`, so the first sound doesn't
+    /// have to wait for the device. Call this while something else is
+    /// loading. The mixer starts out initialized.
+    ///
+    /// Browsers only allow audio to start after a user gesture, so on the
+    /// web, use `new` and call `init` from an input event instead.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn new_prewarmed(
+        audio_library: AudioLibrary<K>,
+        audio_volumes: Option<AudioVolumes<K>>,
+        options: MixerOptions,
+    ) -> Self {
+        let mut mixer = Self::new(audio_library, audio_volumes, options);
+        mixer.init();
+        mixer
+    }
+
     pub fn initialized(&self) -> bool {
         self.initialized
     }
@@ -150,34 +561,224 @@ impl<K: 'static + Clone + Send + Eq + Hash> Mixer<K> {
 
     pub fn quit(self) {}
 
-    pub fn update_state(&mut self, state: AudioState<K>) {
-        let mut tracks = [
-            None, None, None, None, None, None, None, None, None, None, None, None, None, None,
-            None, None,
-        ];
-        for i in 0..MAX_TRACKS {
-            tracks[i] = state.tracks.get(i).cloned();
-        }
-        let state = StateUpdate {
-            sound_volume: state.sound_volume,
-            track_volume: state.track_volume,
-            tracks,
+    /// Start queueing this frame's commands, to send together with
+    /// `commit_frame`. The audio thread applies all of the frame's state,
+    /// volume and library changes before starting any of its sounds,
+    /// whatever order they were called in, so a sound always plays at the
+    /// frame's volumes. This is the recommended way to drive the mixer:
+    /// call it at the start of each frame, and `commit_frame` at the end.
+    ///
+    /// Queries like `output_config` send whatever has been queued so far
+    /// first, so they see it.
+    pub fn begin_frame(&mut self) {
+        assert!(
+            self.frame.is_none(),
+            "`begin_frame` was called again before `commit_frame`"
+        );
+        self.frame = Some(vec![]);
+    }
+
+    /// Send everything queued since `begin_frame`, to be applied at once.
+    /// Does nothing if no frame was begun.
+    pub fn commit_frame(&mut self) {
+        self.flush_frame();
+        self.frame = None;
+    }
+
+    fn flush_frame(&mut self) {
+        let cmds = match &mut self.frame {
+            Some(frame) if !frame.is_empty() => std::mem::take(frame),
+            _ => return,
         };
-        self.send(AudioCmd::State(state))
+        self.unchecked_send(AudioCmd::Frame(cmds));
+    }
+
+    pub fn update_state(&mut self, state: AudioState<K>) {
+        self.send(AudioCmd::State(StateUpdate::new(state)))
     }
 
     pub fn play_sound(&mut self, sound: Sound<K>) {
         self.send(AudioCmd::PlaySound(sound))
     }
 
+    /// Play a sound once `delay` has passed. The delay is timed on the
+    /// audio thread, so it's accurate to the sample rather than the frame.
+    pub fn play_sound_after(&mut self, sound: Sound<K>, delay: Duration) -> SoundHandle {
+        let cancelled = Arc::new(AtomicBool::new(false));
+        self.send(AudioCmd::PlaySoundAfter {
+            sound,
+            delay,
+            cancelled: Arc::clone(&cancelled),
+        });
+        SoundHandle { cancelled }
+    }
+
+    /// Synthesize and play a tone directly on the audio thread.
+    pub fn play_tone(&mut self, tone: Tone) {
+        self.send(AudioCmd::PlayTone(tone))
+    }
+
     pub fn update_library(&mut self, library: AudioLibrary<K>, restart_tracks: bool) {
         self.send(AudioCmd::UpdateLibrary(library, restart_tracks))
     }
 
+    /// Replace the library without blocking the calling thread. Files are
+    /// read on a helper thread, and the returned id is reported in a
+    /// `MixerEvent::LibraryLoaded` once the new library is live.
+    ///
+    /// On wasm there are no threads, so this loads the library immediately.
+    pub fn load_library_from(
+        &mut self,
+        sources: Vec<(K, AudioSource)>,
+        restart_tracks: bool,
+    ) -> usize {
+        let id = self.next_load_id;
+        self.next_load_id += 1;
+
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let sender = self.sender.clone();
+            let events_buffer = Arc::clone(&self.events_buffer);
+            std::thread::spawn(move || {
+                let library = load_library(id, sources, &events_buffer);
+
+                // If the mixer has already quit, there's nothing to update
+                let _ = sender.send(AudioCmd::LibraryLoaded {
+                    id,
+                    library,
+                    restart_tracks,
+                });
+            });
+        }
+
+        #[cfg(target_arch = "wasm32")]
+        {
+            let library = load_library(id, sources, &self.events_buffer);
+            self.unchecked_send(AudioCmd::LibraryLoaded {
+                id,
+                library,
+                restart_tracks,
+            });
+        }
+
+        id
+    }
+
+    /// Replace the per-key volumes. This applies straight away to tracks
+    /// and to sounds that are already playing.
     pub fn update_volumes(&mut self, volumes: AudioVolumes<K>) {
         self.send(AudioCmd::UpdateVolumes(volumes))
     }
 
+    /// Set how keys are written in `MixerEvent`s and `diagnose`. Until this
+    /// is called, keys are only named by their type. Any problems found in
+    /// the library are reported again with the new names.
+    ///
+    /// This can be called before `init`.
+    pub fn name_keys_with(&mut self, name: fn(&K) -> String) {
+        self.flush_frame();
+        self.unchecked_send(AudioCmd::NameKeys(name));
+    }
+
+    /// Name keys in `MixerEvent`s and `diagnose` by their `Debug` output.
+    /// See `name_keys_with`.
+    pub fn name_keys_with_debug(&mut self)
+    where
+        K: Debug,
+    {
+        self.name_keys_with(|key| format!("{:?}", key));
+    }
+
+    /// Measure every entry in `library` on a helper thread, and turn each up
+    /// or down to bring it to `target`. The returned id is reported in a
+    /// `MixerEvent::LibraryNormalized` once they're live.
+    ///
+    /// These gains are applied on top of the volumes from `update_volumes`,
+    /// so hand-tuned volumes still adjust the normalized levels. Calling
+    /// this again replaces the gains from the previous call.
+    ///
+    /// If `cache_path` is given, measurements are cached there by content,
+    /// so only new or changed entries are decoded on later runs.
+    ///
+    /// On wasm there are no threads, so this measures the library immediately.
+    pub fn normalize_library(
+        &mut self,
+        library: AudioLibrary<K>,
+        target: VolumeTarget,
+        cache_path: Option<PathBuf>,
+    ) -> usize {
+        let id = self.next_load_id;
+        self.next_load_id += 1;
+
+        let analyze = move || {
+            let mut cache = cache_path.map(LoudnessCache::load);
+            let volumes = analyze_volumes(&library, target, cache.as_mut());
+            if let Some(cache) = &mut cache {
+                // The cache only saves time, so failing to write it is fine
+                let _ = cache.save();
+            }
+            volumes
+        };
+
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let sender = self.sender.clone();
+            std::thread::spawn(move || {
+                let volumes = analyze();
+
+                // If the mixer has already quit, there's nothing to update
+                let _ = sender.send(AudioCmd::LibraryNormalized { id, volumes });
+            });
+        }
+
+        #[cfg(target_arch = "wasm32")]
+        {
+            let volumes = analyze();
+            self.unchecked_send(AudioCmd::LibraryNormalized { id, volumes });
+        }
+
+        id
+    }
+
+    /// Fade the track in the given slot up to full volume. This is applied
+    /// on top of the track's own volume, and persists across state updates.
+    pub fn fade_in(&mut self, index: usize, duration: Duration, curve: Option<FadeCurve>) {
+        self.fade_to(index, 1.0, duration, curve)
+    }
+
+    /// Fade the track in the given slot down to silence. The track keeps
+    /// playing (silently) until it is stopped or removed.
+    pub fn fade_out(&mut self, index: usize, duration: Duration, curve: Option<FadeCurve>) {
+        self.fade_to(index, 0.0, duration, curve)
+    }
+
+    pub fn fade_to(
+        &mut self,
+        index: usize,
+        level: f32,
+        duration: Duration,
+        curve: Option<FadeCurve>,
+    ) {
+        assert!(index < MAX_TRACKS, "Track index out of range");
+        self.send(AudioCmd::Fade {
+            index,
+            level,
+            duration,
+            curve,
+        })
+    }
+
+    pub fn crossfade(
+        &mut self,
+        from_index: usize,
+        to_index: usize,
+        duration: Duration,
+        curve: Option<FadeCurve>,
+    ) {
+        self.fade_out(from_index, duration, curve);
+        self.fade_in(to_index, duration, curve);
+    }
+
     pub fn feedback(&mut self) -> impl Iterator<Item = usize> {
         let items = {
             let indiana_jones = Vec::new();
@@ -188,12 +789,185 @@ impl<K: 'static + Clone + Send + Eq + Hash> Mixer<K> {
         items.into_iter()
     }
 
+    pub fn events(&mut self) -> impl Iterator<Item = MixerEvent> {
+        let items = {
+            let mut buffer = self.events_buffer.lock().unwrap();
+            std::mem::take(&mut *buffer)
+        };
+
+        items.into_iter()
+    }
+
+    /// The output configuration actually negotiated with the device, or
+    /// `None` if the output stream hasn't been opened yet.
+    pub fn output_config(&mut self) -> Option<OutputConfig> {
+        self.flush_frame();
+
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let (sender, receiver) = mpsc::channel();
+            self.unchecked_send(AudioCmd::QueryOutputConfig(sender));
+            receiver.recv().ok().flatten()
+        }
+
+        #[cfg(target_arch = "wasm32")]
+        {
+            self.speaker.output_config
+        }
+    }
+
+    /// The whole state of the mixer as the audio thread sees it, for
+    /// working out why something sounds wrong. Print it, or serialize it to
+    /// compare with another.
+    ///
+    /// Returns `None` if the audio thread has stopped.
+    pub fn debug_snapshot(&mut self) -> Option<MixerSnapshot<K>>
+    where
+        K: Debug + Serialize,
+    {
+        self.flush_frame();
+
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let (sender, receiver) = mpsc::channel();
+            self.unchecked_send(AudioCmd::QuerySnapshot(sender));
+            let mut snapshot = receiver.recv().ok()?;
+            snapshot.sort_by_debug();
+            Some(snapshot)
+        }
+
+        #[cfg(target_arch = "wasm32")]
+        {
+            let mut snapshot = self.speaker.snapshot();
+            snapshot.sort_by_debug();
+            Some(snapshot)
+        }
+    }
+
+    /// The snapshots kept over the last `MixerOptions::snapshot_history`,
+    /// oldest first, each with how long ago it was taken. This is empty if
+    /// the option isn't set.
+    pub fn snapshot_history(&mut self) -> Vec<(Duration, MixerSnapshot<K>)>
+    where
+        K: Debug + Serialize,
+    {
+        self.flush_frame();
+
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let (sender, receiver) = mpsc::channel();
+            self.unchecked_send(AudioCmd::QuerySnapshotHistory(sender));
+            let mut history = receiver.recv().unwrap_or_default();
+            for (_, snapshot) in &mut history {
+                snapshot.sort_by_debug();
+            }
+            history
+        }
+
+        #[cfg(target_arch = "wasm32")]
+        {
+            vec![]
+        }
+    }
+
+    /// How much time the audio output spent pulling samples from sounds,
+    /// tones, and tracks, per second since the last call (or since the
+    /// mixer was made). This covers decoding and effects, but not rodio's
+    /// own mixing and resampling. It's `None` unless
+    /// `MixerOptions::measure_cpu` is set.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn cpu_estimate(&mut self) -> Option<Duration> {
+        self.cpu_meter.as_mut().map(CpuMeter::estimate)
+    }
+
+    /// Whether nothing louder than a 16-bit sample's worth has reached the
+    /// output for `MixerOptions::detect_silence`. This is always `false`
+    /// without that option.
+    ///
+    /// Silence is measured as the output plays, so an output that has
+    /// stopped asking for audio altogether isn't caught. Check
+    /// `diagnose` for that.
+    pub fn output_is_silent(&self) -> bool {
+        self.silence.as_ref().map_or(false, |meter| meter.silent())
+    }
+
+    /// Everything that might stop the game from being heard, for a debug
+    /// screen or a bug report. This includes the audio thread having
+    /// stopped.
+    pub fn diagnose(&mut self) -> AudioDiagnosis {
+        self.flush_frame();
+
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let (sender, receiver) = mpsc::channel();
+            self.unchecked_send(AudioCmd::QueryDiagnosis(sender));
+            receiver
+                .recv()
+                .unwrap_or_else(|_| AudioDiagnosis::thread_stopped())
+        }
+
+        #[cfg(target_arch = "wasm32")]
+        {
+            self.speaker.diagnosis()
+        }
+    }
+
+    /// Set the tempo of the track at `index`, with its first beat `offset`
+    /// into the audio. The track will then send `MixerEvent::Beat` events.
+    pub fn set_track_bpm(&mut self, index: usize, bpm: f32, offset: Duration) {
+        self.clocks[index].lock().unwrap().set_tempo(bpm, offset);
+    }
+
+    /// Where the track at `index` is, if it's playing and has a tempo set.
+    /// This follows the audio, so it stops while the track is paused.
+    pub fn beat_clock(&self, index: usize) -> Option<BeatClock> {
+        self.clocks[index].lock().unwrap().beat_clock()
+    }
+
+    /// What the track in the given slot is actually doing. Unlike
+    /// `Track::playing`, this shows when a non-looping track has run out.
+    /// Changes are also sent as `MixerEvent::TrackStatusChanged`.
+    pub fn track_status(&self, index: usize) -> TrackStatus {
+        self.statuses[index].lock().unwrap().status()
+    }
+
+    /// Close the output stream and open a new one on the current default
+    /// device, e.g. after the user switches headphones. Playing tracks are
+    /// rebuilt on the new stream.
+    ///
+    /// Returns the new stream's config, or why no stream could be opened.
+    /// Either way, the result is also sent as a `MixerEvent`.
+    pub fn reopen_output(&mut self) -> Result<Option<OutputConfig>, String> {
+        assert!(
+            self.initialized,
+            "Mixer must have `init()` called before playing sound"
+        );
+        self.flush_frame();
+
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let (sender, receiver) = mpsc::channel();
+            self.unchecked_send(AudioCmd::ReopenOutput(sender));
+            receiver
+                .recv()
+                .unwrap_or_else(|_| Err("The audio thread has stopped.".to_owned()))
+        }
+
+        #[cfg(target_arch = "wasm32")]
+        {
+            self.speaker.reopen_output()
+        }
+    }
+
     fn send(&mut self, cmd: AudioCmd<K>) {
         assert!(
             self.initialized || matches!(cmd, AudioCmd::Prewarm),
             "Mixer must have `init()` called before playing sound"
         );
-        self.unchecked_send(cmd);
+        match &mut self.frame {
+            Some(frame) => frame.push(cmd),
+            None => self.unchecked_send(cmd),
+        }
     }
 
     fn unchecked_send(&mut self, cmd: AudioCmd<K>) {
@@ -207,35 +981,95 @@ impl<K: 'static + Clone + Send + Eq + Hash> Mixer<K> {
     }
 }
 
+/// A sound or tone that may still be playing, so that its volume can be
+/// changed along with `sound_volume` and the library's volumes.
+struct LiveSound<K> {
+    key: Option<K>,
+    volume: f32,
+    level: Arc<Mutex<f32>>,
+}
+
+impl<K> LiveSound<K> {
+    /// Whether its source still holds on to the level.
+    fn playing(&self) -> bool {
+        Arc::strong_count(&self.level) > 1
+    }
+}
+
 struct Speaker<K: Clone + Send + Eq + Hash> {
     #[cfg(not(target_arch = "wasm32"))]
     receiver: Receiver<AudioCmd<K>>,
 
-    context: Option<(OutputStream, OutputStreamHandle)>,
+    backend: Option<Box<dyn AudioBackend>>,
+    options: MixerOptions,
+    output_config: Option<OutputConfig>,
     sound_volume: f32,
     track_volume: f32,
     library: AudioLibrary<K>,
     volumes: AudioVolumes<K>,
+
+    /// Gains from `Mixer::normalize_library`, applied on top of `volumes`.
+    normalized: AudioVolumes<K>,
+
     tracks: [Option<Track<K>>; MAX_TRACKS],
     sinks: [Option<Sink>; MAX_TRACKS],
+    live_sounds: Vec<LiveSound<K>>,
+    fades: Vec<Arc<Mutex<Fade>>>,
+    echoes: Vec<Arc<Mutex<EchoControl>>>,
+    clocks: Vec<Arc<Mutex<TrackClock>>>,
+    statuses: Vec<Arc<Mutex<SlotStatus>>>,
     feedback_buffer: Arc<Mutex<Vec<usize>>>,
+    events_buffer: Arc<Mutex<Vec<MixerEvent>>>,
+
+    /// Keys that have failed to decode since the library was last set.
+    undecodable: Mutex<HashSet<K>>,
+
+    /// Sinks made ahead of time for new tracks.
+    idle_sinks: Mutex<Vec<Sink>>,
+
+    /// Audio decoded when the library was set, from
+    /// `MixerOptions::predecode_under`.
+    pcm: HashMap<K, Arc<Pcm>>,
+
+    /// Where sources add up the time they take, if it's being measured.
+    cpu_time: Option<Arc<AtomicU64>>,
+
+    /// Listens to the final mix, if `MixerOptions::detect_silence` is set.
+    silence: Option<Arc<SilenceMeter>>,
+
+    /// The last audio that failed to decode, for `Mixer::diagnose`.
+    last_decode_error: Mutex<Option<String>>,
+    /// How keys are written in events, from `Mixer::name_keys_with`.
+    key_name: fn(&K) -> String,
+
+    #[cfg(not(target_arch = "wasm32"))]
+    history: VecDeque<(Instant, MixerSnapshot<K>)>,
 }
 
-impl<K: Clone + Send + Eq + Hash> Speaker<K> {
+impl<K: 'static + Clone + Send + Eq + Hash> Speaker<K> {
     pub fn new(
         #[cfg(not(target_arch = "wasm32"))] receiver: Receiver<AudioCmd<K>>,
         library: AudioLibrary<K>,
         volumes: AudioVolumes<K>,
+        options: MixerOptions,
         feedback_buffer: Arc<Mutex<Vec<usize>>>,
+        events_buffer: Arc<Mutex<Vec<MixerEvent>>>,
+        clocks: Vec<Arc<Mutex<TrackClock>>>,
+        statuses: Vec<Arc<Mutex<SlotStatus>>>,
+        cpu_time: Option<Arc<AtomicU64>>,
+        silence: Option<Arc<SilenceMeter>>,
     ) -> Self {
-        Speaker {
+        let mut speaker = Speaker {
             #[cfg(not(target_arch = "wasm32"))]
             receiver,
-            context: None,
+            backend: None,
+            options,
+            output_config: None,
             sound_volume: 1.0,
             track_volume: 1.0,
             library,
             volumes,
+            normalized: AudioVolumes::default(),
             tracks: [
                 None, None, None, None, None, None, None, None, None, None, None, None, None, None,
                 None, None,
@@ -244,21 +1078,192 @@ impl<K: Clone + Send + Eq + Hash> Speaker<K> {
                 None, None, None, None, None, None, None, None, None, None, None, None, None, None,
                 None, None,
             ],
+            live_sounds: vec![],
+            fades: (0..MAX_TRACKS)
+                .map(|_| Arc::new(Mutex::new(Fade::steady(1.0))))
+                .collect(),
+            echoes: (0..MAX_TRACKS)
+                .map(|_| Arc::new(Mutex::new(EchoControl::default())))
+                .collect(),
+            clocks,
+            statuses,
             feedback_buffer,
-        }
+            events_buffer,
+            undecodable: Mutex::new(HashSet::new()),
+            idle_sinks: Mutex::new(vec![]),
+            pcm: HashMap::new(),
+            cpu_time,
+            silence,
+            last_decode_error: Mutex::new(None),
+            key_name: |_| std::any::type_name::<K>().to_owned(),
+            #[cfg(not(target_arch = "wasm32"))]
+            history: VecDeque::new(),
+        };
+        speaker.check_library();
+        speaker.predecode();
+        speaker
     }
 
     fn warm(&mut self) {
-        if self.context.is_none() {
-            let context = OutputStream::try_default().unwrap();
-            self.context = Some(context);
+        if self.backend.is_none() {
+            match open_output_stream(&self.options, &self.events_buffer) {
+                Ok((backend, output_config)) => self.set_backend(backend, output_config),
+                Err(error) => {
+                    let mut buffer = self.events_buffer.lock().unwrap();
+                    buffer.push(MixerEvent::OutputError { error });
+                }
+            }
+        }
+    }
+
+    fn reopen_output(&mut self) -> Result<Option<OutputConfig>, String> {
+        // The old stream has to be closed before the device will give us
+        // a new one on some platforms.
+        self.sinks = [
+            None, None, None, None, None, None, None, None, None, None, None, None, None, None,
+            None, None,
+        ];
+        self.idle_sinks.lock().unwrap().clear();
+        self.backend = None;
+
+        match open_output_stream(&self.options, &self.events_buffer) {
+            Ok((backend, output_config)) => {
+                self.set_backend(backend, output_config);
+
+                let mut buffer = self.events_buffer.lock().unwrap();
+                buffer.push(MixerEvent::OutputReopened {
+                    config: output_config,
+                });
+                Ok(output_config)
+            }
+            Err(error) => {
+                self.output_config = None;
+                let mut buffer = self.events_buffer.lock().unwrap();
+                buffer.push(MixerEvent::OutputError {
+                    error: error.clone(),
+                });
+                Err(error)
+            }
         }
     }
 
+    /// Switch to a new backend, rebuilding every track's sink against it.
+    /// Sinks from an old backend would keep playing at its sample rate.
+    fn set_backend(&mut self, backend: Box<dyn AudioBackend>, output_config: Option<OutputConfig>) {
+        let requested = self.options;
+        let actual_rate = output_config.map(|config| config.sample_rate);
+        let actual_buffer = output_config.and_then(|config| config.buffer_size);
+        let rate_fallback = requested.sample_rate.is_some() && requested.sample_rate != actual_rate;
+        let buffer_fallback =
+            requested.buffer_size.is_some() && requested.buffer_size != actual_buffer;
+
+        if rate_fallback || buffer_fallback {
+            let mut buffer = self.events_buffer.lock().unwrap();
+            buffer.push(MixerEvent::OutputConfigFallback {
+                requested,
+                actual: output_config,
+            });
+        }
+
+        let limiter = self.options.limiter;
+        let backend: Box<dyn AudioBackend> = match (limiter, &self.silence) {
+            (None, None) => backend,
+            (limiter, silence) => Box::new(MasterBus::new(
+                backend,
+                output_config,
+                limiter,
+                silence.clone(),
+            )),
+        };
+
+        *self.idle_sinks.lock().unwrap() = (0..IDLE_SINKS).map(|_| backend.new_sink()).collect();
+        self.backend = Some(backend);
+        self.output_config = output_config;
+        self.restart_all_tracks();
+    }
+
     #[cfg(not(target_arch = "wasm32"))]
     pub fn listen(&mut self) -> bool {
-        let cmd = self.receiver.recv().unwrap();
-        self.process(cmd)
+        let history = match self.options.snapshot_history {
+            Some(history) => history,
+            None => {
+                let cmd = self.receiver.recv().unwrap();
+                return self.process(cmd);
+            }
+        };
+
+        // Wake up between commands too, so the history keeps going while
+        // nothing is sent
+        let running = match self.receiver.recv_timeout(SNAPSHOT_PERIOD) {
+            Ok(cmd) => self.process(cmd),
+            Err(RecvTimeoutError::Timeout) => true,
+            Err(RecvTimeoutError::Disconnected) => false,
+        };
+        self.record_history(history);
+        running
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn record_history(&mut self, history: Duration) {
+        let due = match self.history.back() {
+            Some((taken, _)) => taken.elapsed() >= SNAPSHOT_PERIOD,
+            None => true,
+        };
+        if due {
+            let snapshot = self.snapshot();
+            self.history.push_back((Instant::now(), snapshot));
+        }
+
+        while let Some((taken, _)) = self.history.front() {
+            if taken.elapsed() <= history {
+                break;
+            }
+            self.history.pop_front();
+        }
+    }
+
+    fn snapshot(&self) -> MixerSnapshot<K> {
+        let key_volumes = self
+            .volumes
+            .iter()
+            .map(|(key, &volume)| (key.clone(), volume))
+            .collect::<Vec<_>>();
+
+        let tracks = self
+            .tracks
+            .iter()
+            .enumerate()
+            .filter_map(|(index, track)| {
+                let track = track.as_ref()?;
+                let fade_level = self.fades[index].lock().unwrap().level();
+                Some(TrackSnapshot {
+                    index,
+                    key: track.key.clone(),
+                    playing: track.playing,
+                    looping: track.looping,
+                    volume: track.volume,
+                    speed: track.speed,
+                    status: self.statuses[index].lock().unwrap().status(),
+                    effective_volume: self.volume_of_track(track) * fade_level,
+                    position: self.clocks[index].lock().unwrap().position(),
+                })
+            })
+            .collect();
+
+        let mut live_counts = HashMap::<Option<K>, usize>::new();
+        for sound in self.live_sounds.iter().filter(|sound| sound.playing()) {
+            *live_counts.entry(sound.key.clone()).or_default() += 1;
+        }
+        let live_sounds = live_counts.into_iter().collect::<Vec<_>>();
+
+        MixerSnapshot {
+            output_config: self.output_config,
+            sound_volume: self.sound_volume,
+            track_volume: self.track_volume,
+            key_volumes,
+            tracks,
+            live_sounds,
+        }
     }
 
     pub fn process(&mut self, cmd: AudioCmd<K>) -> bool {
@@ -268,56 +1273,313 @@ impl<K: Clone + Send + Eq + Hash> Speaker<K> {
             AudioCmd::State(audio_state) => {
                 self.sound_volume = audio_state.sound_volume;
                 self.track_volume = audio_state.track_volume;
+                self.update_live_sounds();
                 self.update_tracks(audio_state.tracks);
             }
             AudioCmd::PlaySound(sound) => self.play_sound(&sound),
+            AudioCmd::PlaySoundAfter {
+                sound,
+                delay,
+                cancelled,
+            } => self.play_sound_after(&sound, delay, cancelled),
+            AudioCmd::PlayTone(tone) => {
+                if self.backend.is_some() {
+                    let source = self.fit_channels(tone.source());
+                    let level = self.start_live_sound(None, 1.0);
+                    let source = Timed::new(with_level(source, level), self.cpu_time.clone());
+                    self.backend.as_ref().unwrap().play_raw(Box::new(source));
+                }
+            }
             AudioCmd::UpdateLibrary(library, restart) => {
-                self.library = library;
+                self.set_library(library);
                 if restart {
                     self.restart_all_tracks();
                 }
             }
-            AudioCmd::UpdateVolumes(volumes) => {
-                self.volumes = volumes;
-                for track in self.tracks.iter().zip(self.sinks.iter()) {
-                    if let (Some(track), Some(sink)) = track {
-                        let track_specific_volume = *self.volumes.get(&track.key).unwrap_or(&1.0);
-                        let volume = track_specific_volume * self.track_volume * track.volume;
-                        sink.set_volume(volume);
-                    }
+            AudioCmd::LibraryLoaded {
+                id,
+                library,
+                restart_tracks,
+            } => {
+                self.set_library(library);
+                if restart_tracks {
+                    self.restart_all_tracks();
                 }
+
+                let mut buffer = self.events_buffer.lock().unwrap();
+                buffer.push(MixerEvent::LibraryLoaded { id });
+            }
+            AudioCmd::UpdateVolumes(volumes) => self.set_volumes(volumes),
+            AudioCmd::NameKeys(name) => {
+                self.key_name = name;
+                self.check_library();
+            }
+            AudioCmd::LibraryNormalized { id, volumes } => {
+                self.normalized = volumes;
+                self.apply_volumes();
+
+                let mut buffer = self.events_buffer.lock().unwrap();
+                buffer.push(MixerEvent::LibraryNormalized { id });
+            }
+            AudioCmd::Fade {
+                index,
+                level,
+                duration,
+                curve,
+            } => {
+                let curve = curve.unwrap_or(self.options.fade_curve);
+                let mut fade = self.fades[index].lock().unwrap();
+                fade.retarget(level, duration, curve);
+            }
+            AudioCmd::Frame(cmds) => {
+                let (sounds, changes): (Vec<_>, Vec<_>) =
+                    cmds.into_iter().partition(AudioCmd::starts_sound);
+                for cmd in changes.into_iter().chain(sounds) {
+                    self.process(cmd);
+                }
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            AudioCmd::ReopenOutput(reply) => {
+                let _ = reply.send(self.reopen_output());
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            AudioCmd::QueryOutputConfig(reply) => {
+                let _ = reply.send(self.output_config);
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            AudioCmd::QuerySnapshot(reply) => {
+                let _ = reply.send(self.snapshot());
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            AudioCmd::QueryDiagnosis(reply) => {
+                let _ = reply.send(self.diagnosis());
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            AudioCmd::QuerySnapshotHistory(reply) => {
+                let now = Instant::now();
+                let history = self
+                    .history
+                    .iter()
+                    .map(|(taken, snapshot)| (now - *taken, snapshot.clone()))
+                    .collect();
+                let _ = reply.send(history);
             }
         }
         true
     }
 
-    fn play_sound(&self, sound: &Sound<K>) {
-        let sound_specific_volume = *self.volumes.get(&sound.key).unwrap_or(&1.0);
-        let volume = sound_specific_volume * self.sound_volume * sound.volume;
+    fn set_library(&mut self, library: AudioLibrary<K>) {
+        self.library = library;
+        self.undecodable.lock().unwrap().clear();
+        self.check_library();
+        self.predecode();
+    }
 
-        let audio_bytes = self.library.get(&sound.key);
-        if let Some(audio_bytes) = audio_bytes {
-            let cursor = Cursor::new(audio_bytes.clone());
-            let source = Decoder::new(cursor)
-                .unwrap()
-                .amplify(volume)
-                .speed(sound.speed)
-                .convert_samples();
-            if let Some((_, handle)) = self.context.as_ref() {
-                handle.play_raw(source).unwrap();
+    /// Decode anything short enough for `MixerOptions::predecode_under`.
+    /// Anything that fails is left to be reported when it's played.
+    fn predecode(&mut self) {
+        self.pcm.clear();
+
+        let max = match self.options.predecode_under {
+            Some(max) => max,
+            None => return,
+        };
+
+        for (key, audio_bytes) in &self.library {
+            let short = audio_duration(audio_bytes).map_or(false, |length| length <= max);
+            if !short {
+                continue;
+            }
+            if let Some(pcm) = Pcm::decode(audio_bytes) {
+                self.pcm.insert(key.clone(), Arc::new(pcm));
+            }
+        }
+    }
+
+    /// The audio for `key` as samples, at the configured quality.
+    fn decoded(&self, key: &K, audio_bytes: &AudioBytes) -> Option<impl Source<Item = f32> + Send> {
+        let source = match self.pcm.get(key) {
+            Some(pcm) => Decoded::Pcm(PcmSource::new(pcm)),
+            None => Decoded::Stream(decode(audio_bytes)?),
+        };
+        Some(Reduced::new(source, self.options.resample_quality))
+    }
+
+    /// Report any entries in the library that won't play well. This only
+    /// reads headers, so it's quick even for large libraries.
+    fn check_library(&self) {
+        let mut problems = vec![];
+
+        for (key, audio_bytes) in &self.library {
+            let bytes = audio_bytes.as_ref();
+            let problem = match sniff_container(bytes) {
+                "empty" => Some(LibraryProblem::Empty),
+                "unknown" => Some(LibraryProblem::UnknownFormat),
+                _ => self
+                    .options
+                    .max_duration
+                    .and_then(|max| audio_duration(audio_bytes).filter(|&length| length > max))
+                    .map(LibraryProblem::TooLong),
+            };
+
+            if let Some(problem) = problem {
+                problems.push(((self.key_name)(key), problem));
             }
         }
+
+        if !problems.is_empty() {
+            problems.sort_by(|a, b| a.0.cmp(&b.0));
+            let mut buffer = self.events_buffer.lock().unwrap();
+            buffer.push(MixerEvent::LibraryProblems { problems });
+        }
+    }
+
+    /// The volume of a sound or track: its group's volume from `AudioState`
+    /// (`sound_volume` or `track_volume`), times its key's volume from the
+    /// `AudioVolumes` and its normalized gain, times the volume it was played
+    /// with. Every volume is worked out here, so that sounds and tracks can't
+    /// drift apart.
+    fn mixed_volume(&self, group_volume: f32, key: Option<&K>, volume: f32) -> f32 {
+        let key_volume = |volumes: &AudioVolumes<K>| {
+            key.and_then(|key| volumes.get(key)).copied().unwrap_or(1.0)
+        };
+        group_volume * key_volume(&self.volumes) * key_volume(&self.normalized) * volume
+    }
+
+    fn volume_of_track(&self, track: &Track<K>) -> f32 {
+        self.mixed_volume(self.track_volume, Some(&track.key), track.volume)
+    }
+
+    /// Register a sound that's about to play, returning the level to play
+    /// it at, which follows any changes to the volumes.
+    fn start_live_sound(&mut self, key: Option<&K>, volume: f32) -> Arc<Mutex<f32>> {
+        self.live_sounds.retain(LiveSound::playing);
+
+        let level = self.mixed_volume(self.sound_volume, key, volume);
+        let level = Arc::new(Mutex::new(level));
+        self.live_sounds.push(LiveSound {
+            key: key.cloned(),
+            volume,
+            level: Arc::clone(&level),
+        });
+        level
+    }
+
+    fn update_live_sounds(&mut self) {
+        self.live_sounds.retain(LiveSound::playing);
+
+        for sound in &self.live_sounds {
+            let level = self.mixed_volume(self.sound_volume, sound.key.as_ref(), sound.volume);
+            *sound.level.lock().unwrap() = level;
+        }
+    }
+
+    fn sound_source(&mut self, sound: &Sound<K>) -> Option<impl Source<Item = f32> + Send> {
+        let audio_bytes = self.playable_bytes(&sound.key)?;
+        let source = match self.decoded(&sound.key, audio_bytes) {
+            Some(source) => source,
+            None => {
+                self.report_undecodable(&sound.key, audio_bytes);
+                return None;
+            }
+        };
+
+        let source = self.fit_channels(source);
+        let level = self.start_live_sound(Some(&sound.key), sound.volume);
+        let source = with_level(source, level).speed(sound.speed);
+        Some(Timed::new(source, self.cpu_time.clone()))
+    }
+
+    /// The audio for `key`, unless it's missing or has already failed to
+    /// decode.
+    fn playable_bytes(&self, key: &K) -> Option<&AudioBytes> {
+        if self.undecodable.lock().unwrap().contains(key) {
+            return None;
+        }
+        self.library.get(key)
+    }
+
+    /// Remember that `key` can't be decoded, reporting it the first time.
+    fn report_undecodable(&self, key: &K, audio_bytes: &AudioBytes) {
+        if self.undecodable.lock().unwrap().insert(key.clone()) {
+            let key = (self.key_name)(key);
+            let container = sniff_container(audio_bytes.as_ref());
+            *self.last_decode_error.lock().unwrap() =
+                Some(format!("{} (looks like {})", key, container));
+
+            let mut buffer = self.events_buffer.lock().unwrap();
+            buffer.push(MixerEvent::UnsupportedFormat { key, container });
+        }
+    }
+
+    fn diagnosis(&self) -> AudioDiagnosis {
+        AudioDiagnosis {
+            thread_running: true,
+            device_present: diagnosis::device_present(),
+            stream_open: self.backend.is_some(),
+            output_config: self.output_config,
+            sound_volume: self.sound_volume,
+            track_volume: self.track_volume,
+            live_sinks: self.sinks.iter().flatten().count(),
+            live_sounds: self
+                .live_sounds
+                .iter()
+                .filter(|sound| sound.playing())
+                .count(),
+            last_decode_error: self.last_decode_error.lock().unwrap().clone(),
+            output_silent: self.silence.as_ref().map(|meter| meter.silent()),
+        }
+    }
+
+    /// Convert the source to the output's channel count, according to the
+    /// channel policy. Sources are left as they are until the output is
+    /// open, or with `ChannelPolicy::Passthrough`.
+    fn fit_channels<S: Source<Item = f32>>(&self, source: S) -> ChannelMap<S> {
+        let channels = source.channels();
+        match (self.options.channel_policy, self.output_config) {
+            (ChannelPolicy::Remap { mono }, Some(config)) => {
+                ChannelMap::new(source, config.channels, mono)
+            }
+            _ => ChannelMap::new(source, channels, MonoPlacement::FrontPair),
+        }
+    }
+
+    fn play_sound(&mut self, sound: &Sound<K>) {
+        if let (Some(source), Some(backend)) = (self.sound_source(sound), self.backend.as_ref()) {
+            backend.play_raw(Box::new(source));
+        }
+    }
+
+    fn play_sound_after(&mut self, sound: &Sound<K>, delay: Duration, cancelled: Arc<AtomicBool>) {
+        if let (Some(source), Some(backend)) = (self.sound_source(sound), self.backend.as_ref()) {
+            let source =
+                source
+                    .delay(delay)
+                    .stoppable()
+                    .periodic_access(CANCEL_PERIOD, move |source| {
+                        if cancelled.load(Ordering::Relaxed) {
+                            source.stop();
+                        }
+                    });
+            backend.play_raw(Box::new(source));
+        }
     }
 
     fn update_tracks(&mut self, tracks: [Option<Track<K>>; MAX_TRACKS]) {
+        let mut synced = vec![];
+
         for i in 0..MAX_TRACKS {
+            let echo = tracks[i].as_ref().and_then(|track| track.echo);
+            self.echoes[i].lock().unwrap().set(echo);
+
             match (&self.tracks[i], &tracks[i]) {
                 (None, None) => (),
                 (Some(_), None) => {
                     self.sinks[i] = None;
+                    self.clocks[i].lock().unwrap().stop();
                 }
                 (None, Some(track)) => {
-                    self.sinks[i] = self.create_sink(track, i);
+                    self.sinks[i] = self.create_synced_sink(track, i, &mut synced);
                 }
                 (Some(old), Some(new)) => {
                     if new.key == old.key && new.feedback_rate == old.feedback_rate {
@@ -325,40 +1587,76 @@ impl<K: Clone + Send + Eq + Hash> Speaker<K> {
                             self.keep_sink_looping(new, i);
                         }
 
-                        let sink = self.sinks[i].as_mut().unwrap();
+                        // Tracks without a sink couldn't be played
+                        let volume = self.volume_of_track(new);
+                        if let Some(sink) = self.sinks[i].as_mut() {
+                            sink.set_speed(new.speed.max(MIN_SPEED));
 
-                        if new.playing {
-                            sink.play();
-                        } else {
-                            sink.pause();
-                        }
+                            if advancing(new) {
+                                sink.play();
+                            } else {
+                                sink.pause();
+                            }
 
-                        let track_specific_volume = *self.volumes.get(&new.key).unwrap_or(&1.0);
-                        let volume = track_specific_volume * self.track_volume * new.volume;
-                        sink.set_volume(volume);
+                            sink.set_volume(volume);
+                        }
                     } else {
-                        self.sinks[i] = self.create_sink(new, i);
+                        self.sinks[i] = self.create_synced_sink(new, i, &mut synced);
                     }
                 }
             }
         }
 
+        // Start every synced track at once, after the slow part of creating
+        // them is over
+        for i in synced {
+            if let Some(sink) = &self.sinks[i] {
+                sink.play();
+            }
+        }
+
         self.tracks = tracks;
+        self.update_statuses();
+    }
+
+    /// Like `create_sink`, but tracks in a sync group are left paused, and
+    /// their index added to `synced` to be started later.
+    fn create_synced_sink(
+        &self,
+        track: &Track<K>,
+        sink_index: usize,
+        synced: &mut Vec<usize>,
+    ) -> Option<Sink> {
+        if track.sync_group.is_none() || !advancing(track) {
+            return self.create_sink(track, sink_index);
+        }
+
+        synced.push(sink_index);
+        let paused = Track {
+            playing: false,
+            ..track.clone()
+        };
+        self.create_sink(&paused, sink_index)
     }
 
     fn create_sink(&self, track: &Track<K>, sink_index: usize) -> Option<Sink> {
-        let track_specific_volume = *self.volumes.get(&track.key).unwrap_or(&1.0);
-        let volume = track_specific_volume * self.track_volume * track.volume;
+        let volume = self.volume_of_track(track);
 
-        let audio_bytes = self
-            .library
-            .get(&track.key)
-            .expect("Failed to look up audio for given key");
+        self.clocks[sink_index].lock().unwrap().stop();
+        let generation = self.statuses[sink_index].lock().unwrap().restart();
 
-        if let Some((_, handle)) = self.context.as_ref() {
-            let sink = Sink::try_new(handle).unwrap();
+        let audio_bytes = self.playable_bytes(&track.key)?;
+
+        if let Some(backend) = self.backend.as_ref() {
+            let sink = self
+                .idle_sinks
+                .lock()
+                .unwrap()
+                .pop()
+                .unwrap_or_else(|| backend.new_sink());
             sink.set_volume(volume);
-            if !track.playing {
+            sink.set_speed(track.speed.max(MIN_SPEED));
+            if !advancing(track) {
                 sink.pause();
             }
 
@@ -367,9 +1665,22 @@ impl<K: Clone + Send + Eq + Hash> Speaker<K> {
                 false => 1,
             };
 
+            // Looping tracks never finish
+            let generation = match track.looping {
+                true => None,
+                false => Some(generation),
+            };
+
             for _ in 0..source_count {
-                let cursor = Cursor::new(audio_bytes.clone());
-                let source = Decoder::new(cursor).unwrap();
+                let source =
+                    match self.track_source(&track.key, audio_bytes, sink_index, generation) {
+                        Some(source) => source,
+                        None => {
+                            self.report_undecodable(&track.key, audio_bytes);
+                            return None;
+                        }
+                    };
+
                 match track.feedback_rate {
                     Some(rate) => {
                         let feedback_buffer = Arc::clone(&self.feedback_buffer);
@@ -388,19 +1699,85 @@ impl<K: Clone + Send + Eq + Hash> Speaker<K> {
     }
 
     fn keep_sink_looping(&mut self, track: &Track<K>, sink_index: usize) {
-        let sink = self.sinks[sink_index].as_mut().unwrap();
-        let audio_bytes = self
-            .library
-            .get(&track.key)
-            .expect("Failed to look up audio for given key");
+        let (sink, audio_bytes) = match (&self.sinks[sink_index], self.playable_bytes(&track.key)) {
+            (Some(sink), Some(audio_bytes)) => (sink, audio_bytes),
+            _ => return,
+        };
 
+        // Audio that can't be decoded was reported when the sink was made
         while sink.len() < 2 {
-            let cursor = Cursor::new(audio_bytes.clone());
-            let source = Decoder::new(cursor).unwrap();
-            sink.append(source);
+            match self.track_source(&track.key, audio_bytes, sink_index, None) {
+                Some(source) => sink.append(source),
+                None => break,
+            }
+        }
+    }
+
+    /// The source for a track. If `finish_generation` is given, the slot is
+    /// marked as finished when the source runs out.
+    fn track_source(
+        &self,
+        key: &K,
+        audio_bytes: &AudioBytes,
+        sink_index: usize,
+        finish_generation: Option<u64>,
+    ) -> Option<impl Source<Item = f32> + Send> {
+        let source = self.decoded(key, audio_bytes)?;
+
+        // Only the last source of a track rings out, since a looping
+        // track's next source carries on the echo instead
+        let ring_out = finish_generation.is_some();
+
+        let source = Echoing::new(
+            Finishing::new(
+                Tracked::new(
+                    with_fade(self.fit_channels(source), &self.fades[sink_index]),
+                    &self.clocks[sink_index],
+                    &self.events_buffer,
+                    sink_index,
+                ),
+                &self.statuses[sink_index],
+                finish_generation,
+                &self.events_buffer,
+                sink_index,
+            ),
+            &self.echoes[sink_index],
+            ring_out,
+        );
+        Some(Timed::new(source, self.cpu_time.clone()))
+    }
+
+    /// Bring each slot's status in line with its track and sink, sending
+    /// events for any that changed.
+    fn update_statuses(&self) {
+        for i in 0..MAX_TRACKS {
+            let status = match (&self.tracks[i], &self.sinks[i]) {
+                (Some(track), Some(_)) if advancing(track) => TrackStatus::Playing,
+                (Some(_), Some(_)) => TrackStatus::Paused,
+                _ => TrackStatus::Empty,
+            };
+
+            if self.statuses[i].lock().unwrap().set(status) {
+                let mut buffer = self.events_buffer.lock().unwrap();
+                buffer.push(MixerEvent::TrackStatusChanged { index: i, status });
+            }
         }
     }
 
+    fn set_volumes(&mut self, volumes: AudioVolumes<K>) {
+        self.volumes = volumes;
+        self.apply_volumes();
+    }
+
+    fn apply_volumes(&mut self) {
+        for track in self.tracks.iter().zip(self.sinks.iter()) {
+            if let (Some(track), Some(sink)) = track {
+                sink.set_volume(self.volume_of_track(track));
+            }
+        }
+        self.update_live_sounds();
+    }
+
     fn restart_all_tracks(&mut self) {
         self.sinks = [
             None, None, None, None, None, None, None, None, None, None, None, None, None, None,
@@ -411,5 +1788,237 @@ impl<K: Clone + Send + Eq + Hash> Speaker<K> {
                 self.sinks[i] = self.create_sink(track, i);
             }
         }
+        self.update_statuses();
+    }
+}
+
+/// Whether the track should be moving: it's playing, at a speed fast
+/// enough to hear.
+fn advancing<K: Clone>(track: &Track<K>) -> bool {
+    track.playing && track.speed >= MIN_SPEED
+}
+
+fn load_library<K: Eq + Hash>(
+    id: usize,
+    sources: Vec<(K, AudioSource)>,
+    events_buffer: &Mutex<Vec<MixerEvent>>,
+) -> AudioLibrary<K> {
+    let mut library = AudioLibrary::with_capacity(sources.len());
+
+    for (key, source) in sources {
+        match source {
+            AudioSource::Bytes(bytes) => {
+                library.insert(key, bytes);
+            }
+            AudioSource::Path(path) => match std::fs::read(&path) {
+                Ok(bytes) => {
+                    library.insert(key, AudioBytes::new(Cow::Owned(bytes)));
+                }
+                Err(e) => {
+                    let mut buffer = events_buffer.lock().unwrap();
+                    buffer.push(MixerEvent::LibraryLoadFailed {
+                        id,
+                        path,
+                        error: e.to_string(),
+                    });
+                }
+            },
+        }
+    }
+
+    library
+}
+
+/// Decode to `f32`, the format everything is mixed in. Returns `None` if
+/// none of the enabled decoders support the format.
+fn decode(audio_bytes: &AudioBytes) -> Option<Unpanicking<impl Source<Item = f32> + Send>> {
+    if audio_bytes.as_ref().is_empty() {
+        return None;
+    }
+
+    // Some decoders panic on malformed data rather than returning an
+    // error, which would take the audio thread down with them
+    let cursor = Cursor::new(audio_bytes.clone());
+    let decoder = std::panic::catch_unwind(AssertUnwindSafe(|| Decoder::new(cursor))).ok()?;
+    Some(Unpanicking::new(decoder.ok()?.convert_samples()))
+}
+
+/// A source that ends, rather than unwinding into whichever thread is
+/// playing it, if decoding panics partway through.
+struct Unpanicking<S> {
+    source: S,
+    panicked: bool,
+}
+
+impl<S> Unpanicking<S> {
+    fn new(source: S) -> Self {
+        Unpanicking {
+            source,
+            panicked: false,
+        }
+    }
+
+    /// Whether the source was cut short by a panic.
+    fn panicked(&self) -> bool {
+        self.panicked
+    }
+}
+
+impl<S: Iterator> Iterator for Unpanicking<S> {
+    type Item = S::Item;
+
+    fn next(&mut self) -> Option<S::Item> {
+        if self.panicked {
+            return None;
+        }
+
+        let source = &mut self.source;
+        match std::panic::catch_unwind(AssertUnwindSafe(|| source.next())) {
+            Ok(sample) => sample,
+            Err(_) => {
+                self.panicked = true;
+                None
+            }
+        }
+    }
+}
+
+impl<S: Source> Source for Unpanicking<S>
+where
+    S::Item: Sample,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        match self.panicked {
+            true => Some(0),
+            false => self.source.current_frame_len(),
+        }
+    }
+
+    fn channels(&self) -> u16 {
+        self.source.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.source.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.source.total_duration()
+    }
+}
+
+/// How long the audio is, if that can be found without decoding all of it.
+fn audio_duration(audio_bytes: &AudioBytes) -> Option<Duration> {
+    ogg_duration(audio_bytes.as_ref()).or_else(|| decode(audio_bytes)?.total_duration())
+}
+
+fn with_fade<S>(source: S, fade: &Arc<Mutex<Fade>>) -> impl Source<Item = S::Item> + Send
+where
+    S: Source + Send + 'static,
+    S::Item: Sample + Send,
+{
+    let fade = Arc::clone(fade);
+    let level = fade.lock().unwrap().level();
+
+    source
+        .amplify(level)
+        .periodic_access(FADE_PERIOD, move |amplify| {
+            let level = fade.lock().unwrap().advance(FADE_PERIOD);
+            amplify.set_factor(level);
+        })
+}
+
+/// Play the source at the level, following any changes to it.
+fn with_level<S>(source: S, level: Arc<Mutex<f32>>) -> impl Source<Item = S::Item> + Send
+where
+    S: Source + Send + 'static,
+    S::Item: Sample + Send,
+{
+    let initial = *level.lock().unwrap();
+
+    source
+        .amplify(initial)
+        .periodic_access(VOLUME_PERIOD, move |amplify| {
+            amplify.set_factor(*level.lock().unwrap());
+        })
+}
+
+/// Open the default output device with the requested options, falling
+/// back to its default config (or any device that works) if they can't be
+/// used.
+fn open_output_stream(
+    options: &MixerOptions,
+    events: &Arc<Mutex<Vec<MixerEvent>>>,
+) -> Result<(Box<dyn AudioBackend>, Option<OutputConfig>), String> {
+    use rodio::cpal::{
+        traits::{DeviceTrait, HostTrait},
+        BufferSize, SampleRate, StreamConfig, SupportedBufferSize,
+    };
+
+    let device = rodio::cpal::default_host().default_output_device();
+
+    if let Some(device) = device.as_ref() {
+        let config = match options.sample_rate {
+            Some(sample_rate) => device
+                .supported_output_configs()
+                .ok()
+                .and_then(|configs| {
+                    configs
+                        .filter(|range| {
+                            range.min_sample_rate().0 <= sample_rate
+                                && sample_rate <= range.max_sample_rate().0
+                        })
+                        .max_by_key(|range| range.channels() == 2)
+                })
+                .map(|range| range.with_sample_rate(SampleRate(sample_rate))),
+            None if options.buffer_size.is_some() => device.default_output_config().ok(),
+            None => None,
+        };
+
+        if let Some(config) = config {
+            let mut output_config = OutputConfig {
+                sample_rate: config.sample_rate().0,
+                channels: config.channels(),
+                buffer_size: None,
+            };
+
+            if let Some(buffer_size) = options.buffer_size {
+                let buffer_size = match *config.buffer_size() {
+                    SupportedBufferSize::Range { min, max } => buffer_size.clamp(min, max),
+                    SupportedBufferSize::Unknown => buffer_size,
+                };
+                let stream_config = StreamConfig {
+                    channels: config.channels(),
+                    sample_rate: config.sample_rate(),
+                    buffer_size: BufferSize::Fixed(buffer_size),
+                };
+
+                if let Ok(backend) = DeviceBackend::new(
+                    device,
+                    &stream_config,
+                    config.sample_format(),
+                    Arc::clone(events),
+                ) {
+                    output_config.buffer_size = Some(buffer_size);
+                    return Ok((Box::new(backend), Some(output_config)));
+                }
+            }
+
+            if let Ok((stream, handle)) = OutputStream::try_from_device_config(device, config) {
+                let backend = RodioBackend::new(stream, handle);
+                return Ok((Box::new(backend), Some(output_config)));
+            }
+        }
     }
+
+    let output_config = device
+        .and_then(|device| device.default_output_config().ok())
+        .map(|config| OutputConfig {
+            sample_rate: config.sample_rate().0,
+            channels: config.channels(),
+            buffer_size: None,
+        });
+
+    let (stream, handle) = OutputStream::try_default().map_err(|err| err.to_string())?;
+    Ok((Box::new(RodioBackend::new(stream, handle)), output_config))
 }
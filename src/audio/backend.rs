@@ -0,0 +1,308 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use rodio::{
+    cpal::{
+        self,
+        traits::{DeviceTrait, StreamTrait},
+        BuildStreamError, FromSample, SampleFormat, SizedSample, StreamConfig,
+    },
+    dynamic_mixer::{self, DynamicMixer, DynamicMixerController},
+    queue::SourcesQueueOutput,
+    OutputStream, OutputStreamHandle, Sink, Source,
+};
+
+use super::{
+    diagnosis::{Metered, SilenceMeter},
+    limiter::{Limited, Limiter},
+    MixerEvent, OutputConfig,
+};
+
+/// Where the speaker sends its sources.
+pub(crate) trait AudioBackend {
+    fn new_sink(&self) -> Sink;
+    fn play_raw(&self, source: Box<dyn Source<Item = f32> + Send>);
+}
+
+pub(crate) struct RodioBackend {
+    _stream: OutputStream,
+    handle: OutputStreamHandle,
+}
+
+impl RodioBackend {
+    pub fn new(stream: OutputStream, handle: OutputStreamHandle) -> Self {
+        RodioBackend {
+            _stream: stream,
+            handle,
+        }
+    }
+}
+
+impl AudioBackend for RodioBackend {
+    fn new_sink(&self) -> Sink {
+        let (sink, output) = idle_sink();
+        self.handle.play_raw(output).unwrap();
+        sink
+    }
+
+    fn play_raw(&self, source: Box<dyn Source<Item = f32> + Send>) {
+        self.handle.play_raw(source).unwrap();
+    }
+}
+
+/// Plays through a stream opened on the device directly, for settings
+/// rodio doesn't expose, like a fixed buffer size.
+pub(crate) struct DeviceBackend {
+    _stream: cpal::Stream,
+    controller: Arc<DynamicMixerController<f32>>,
+}
+
+impl DeviceBackend {
+    /// Errors while the stream is playing are sent as
+    /// `MixerEvent::OutputError`.
+    pub fn new(
+        device: &cpal::Device,
+        config: &StreamConfig,
+        format: SampleFormat,
+        events: Arc<Mutex<Vec<MixerEvent>>>,
+    ) -> Result<Self, String> {
+        let (controller, mix) = dynamic_mixer::mixer::<f32>(config.channels, config.sample_rate.0);
+        let stream = match format {
+            SampleFormat::F32 => build_stream::<f32>(device, config, mix, events),
+            SampleFormat::I16 => build_stream::<i16>(device, config, mix, events),
+            SampleFormat::U16 => build_stream::<u16>(device, config, mix, events),
+            _ => Err(BuildStreamError::StreamConfigNotSupported),
+        }
+        .map_err(|err| err.to_string())?;
+        stream.play().map_err(|err| err.to_string())?;
+
+        Ok(DeviceBackend {
+            _stream: stream,
+            controller,
+        })
+    }
+}
+
+fn build_stream<T>(
+    device: &cpal::Device,
+    config: &StreamConfig,
+    mut mix: DynamicMixer<f32>,
+    events: Arc<Mutex<Vec<MixerEvent>>>,
+) -> Result<cpal::Stream, BuildStreamError>
+where
+    T: SizedSample + FromSample<f32>,
+{
+    device.build_output_stream(
+        config,
+        move |data: &mut [T], _| {
+            for sample in data {
+                *sample = mix.next().map(T::from_sample).unwrap_or(T::EQUILIBRIUM);
+            }
+        },
+        move |err| {
+            events.lock().unwrap().push(MixerEvent::OutputError {
+                error: err.to_string(),
+            });
+        },
+        None,
+    )
+}
+
+impl AudioBackend for DeviceBackend {
+    fn new_sink(&self) -> Sink {
+        let (sink, output) = idle_sink();
+        self.controller.add(output);
+        sink
+    }
+
+    fn play_raw(&self, source: Box<dyn Source<Item = f32> + Send>) {
+        self.controller.add(source);
+    }
+}
+
+/// Mixes everything into a `DynamicMixer`, to be pulled from directly
+/// rather than by an output device.
+pub(crate) struct CaptureBackend {
+    controller: Arc<DynamicMixerController<f32>>,
+}
+
+impl CaptureBackend {
+    pub fn new(controller: Arc<DynamicMixerController<f32>>) -> Self {
+        CaptureBackend { controller }
+    }
+}
+
+impl AudioBackend for CaptureBackend {
+    fn new_sink(&self) -> Sink {
+        let (sink, output) = idle_sink();
+        self.controller.add(output);
+        sink
+    }
+
+    fn play_raw(&self, source: Box<dyn Source<Item = f32> + Send>) {
+        self.controller.add(source);
+    }
+}
+
+/// Mixes everything into one source before it reaches `output`, so the
+/// whole mix can be limited or measured at once.
+pub(crate) struct MasterBus {
+    controller: Arc<DynamicMixerController<f32>>,
+    _output: Box<dyn AudioBackend>,
+}
+
+impl MasterBus {
+    /// Mix at the output's format, or CD quality stereo if it's unknown.
+    /// At least one of `limiter` and `meter` should be given, since they
+    /// keep the mix going while it's empty.
+    pub fn new(
+        output: Box<dyn AudioBackend>,
+        output_config: Option<OutputConfig>,
+        limiter: Option<Limiter>,
+        meter: Option<Arc<SilenceMeter>>,
+    ) -> Self {
+        let (channels, sample_rate) = match output_config {
+            Some(config) => (config.channels, config.sample_rate),
+            None => (2, 44100),
+        };
+        let (controller, mix) = dynamic_mixer::mixer::<f32>(channels, sample_rate);
+
+        let mix: Box<dyn Source<Item = f32> + Send> = match limiter {
+            Some(limiter) => Box::new(Limited::new(mix, limiter)),
+            None => Box::new(mix),
+        };
+        match meter {
+            Some(meter) => output.play_raw(Box::new(Metered::new(mix, meter))),
+            None => output.play_raw(mix),
+        }
+
+        MasterBus {
+            controller,
+            _output: output,
+        }
+    }
+}
+
+impl AudioBackend for MasterBus {
+    fn new_sink(&self) -> Sink {
+        let (sink, output) = idle_sink();
+        self.controller.add(output);
+        sink
+    }
+
+    fn play_raw(&self, source: Box<dyn Source<Item = f32> + Send>) {
+        self.controller.add(source);
+    }
+}
+
+/// The most samples `QueueFrames` reads ahead.
+const READ_AHEAD: usize = 512;
+
+/// A sink, and the output to add to a mixer to hear it.
+fn idle_sink() -> (Sink, QueueFrames) {
+    let (sink, output) = Sink::new_idle();
+    (sink, QueueFrames::new(output))
+}
+
+/// Reads a sink's queue ahead in whole frames, so that each run of frames
+/// it reports has the format of the source it came from.
+///
+/// The queue reports the format of the source it's on until it's asked for
+/// the next sample, so a mixer reading it would take the first samples of
+/// each source in the format of the one before. When those differ, like
+/// the queue's mono silence before a 6-channel track, the track's channels
+/// end up in the wrong speakers.
+pub(crate) struct QueueFrames {
+    queue: SourcesQueueOutput<f32>,
+    frames: Vec<f32>,
+    position: usize,
+    channels: u16,
+    sample_rate: u32,
+    /// The first sample of the next frame, and its format, if that's
+    /// different from the frames before it.
+    carried: Option<(f32, u16, u32)>,
+}
+
+impl QueueFrames {
+    fn new(queue: SourcesQueueOutput<f32>) -> Self {
+        let mut frames = QueueFrames {
+            queue,
+            frames: Vec::with_capacity(READ_AHEAD),
+            position: 0,
+            channels: 1,
+            sample_rate: 44100,
+            carried: None,
+        };
+        frames.read_ahead();
+        frames
+    }
+
+    /// The next sample, with the format of the source it came from.
+    fn pull(&mut self) -> Option<(f32, u16, u32)> {
+        let sample = self.queue.next()?;
+        Some((sample, self.queue.channels(), self.queue.sample_rate()))
+    }
+
+    /// Read frames until the format changes or `READ_AHEAD` is reached.
+    fn read_ahead(&mut self) {
+        self.frames.clear();
+        self.position = 0;
+
+        let mut next = self.carried.take().or_else(|| self.pull());
+        while let Some((sample, channels, sample_rate)) = next {
+            let format = (channels, sample_rate);
+            if !self.frames.is_empty() && format != (self.channels, self.sample_rate) {
+                self.carried = Some((sample, channels, sample_rate));
+                return;
+            }
+
+            self.channels = channels;
+            self.sample_rate = sample_rate;
+            self.frames.push(sample);
+            for _ in 1..channels {
+                match self.queue.next() {
+                    Some(sample) => self.frames.push(sample),
+                    None => return,
+                }
+            }
+
+            if self.frames.len() + channels as usize > READ_AHEAD {
+                return;
+            }
+            next = self.pull();
+        }
+    }
+}
+
+impl Iterator for QueueFrames {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let sample = *self.frames.get(self.position)?;
+        self.position += 1;
+
+        // Read on now, so the next run's format is known before it starts
+        if self.position == self.frames.len() {
+            self.read_ahead();
+        }
+        Some(sample)
+    }
+}
+
+impl Source for QueueFrames {
+    fn current_frame_len(&self) -> Option<usize> {
+        Some(self.frames.len() - self.position)
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
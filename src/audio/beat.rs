@@ -0,0 +1,206 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use rodio::{Sample, Source};
+
+use super::MixerEvent;
+
+/// How often (in audio time) a track's clock is updated.
+const CLOCK_PERIOD: Duration = Duration::from_millis(1);
+
+/// Where a track is in its current play-through, in beats.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BeatClock {
+    /// How far into the audio the track is. This resets when a looping
+    /// track loops.
+    pub position: Duration,
+    /// The beat at `position`, counting from zero at the track's offset.
+    /// Negative before the offset.
+    pub beat: f64,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct TrackClock {
+    position: Option<Duration>,
+    tempo: Option<(f32, Duration)>,
+    last_beat: Option<i64>,
+    beats_heard: u64,
+}
+
+impl TrackClock {
+    pub fn set_tempo(&mut self, bpm: f32, offset: Duration) {
+        self.tempo = Some((bpm, offset));
+
+        // The beat the track is already in has been heard, or skipped past,
+        // so only the next one under the new tempo fires
+        self.last_beat = self.current_beat();
+    }
+
+    pub fn stop(&mut self) {
+        self.position = None;
+        self.last_beat = None;
+        self.beats_heard = 0;
+    }
+
+    pub fn position(&self) -> Option<Duration> {
+        self.position
+    }
+
+    pub fn beat_clock(&self) -> Option<BeatClock> {
+        let position = self.position?;
+        let (bpm, offset) = self.tempo?;
+        let beat = (position.as_secs_f64() - offset.as_secs_f64()) * bpm as f64 / 60.;
+        Some(BeatClock { position, beat })
+    }
+
+    /// The index of the beat at the current position, or `None` before the
+    /// first one.
+    fn current_beat(&self) -> Option<i64> {
+        let beat = self.beat_clock()?.beat;
+        if beat < 0. {
+            return None;
+        }
+        Some(beat.floor() as i64)
+    }
+
+    /// Move to a new position, returning the number of the beat crossed, if
+    /// one was.
+    fn advance(&mut self, position: Duration) -> Option<u64> {
+        self.position = Some(position);
+
+        let beat = self.current_beat()?;
+        if self.last_beat == Some(beat) {
+            return None;
+        }
+
+        self.last_beat = Some(beat);
+        let beat_number = self.beats_heard;
+        self.beats_heard += 1;
+        Some(beat_number)
+    }
+}
+
+/// Counts the samples played from a track's source, to keep its
+/// `TrackClock` in time with the audio.
+pub(crate) struct Tracked<S> {
+    source: S,
+    clock: Arc<Mutex<TrackClock>>,
+    events_buffer: Arc<Mutex<Vec<MixerEvent>>>,
+    index: usize,
+    samples: u64,
+    samples_per_update: u64,
+}
+
+impl<S: Source> Tracked<S>
+where
+    S::Item: Sample,
+{
+    pub fn new(
+        source: S,
+        clock: &Arc<Mutex<TrackClock>>,
+        events_buffer: &Arc<Mutex<Vec<MixerEvent>>>,
+        index: usize,
+    ) -> Self {
+        let samples_per_second = source.sample_rate() as u64 * source.channels() as u64;
+        let samples_per_update =
+            (samples_per_second as f64 * CLOCK_PERIOD.as_secs_f64()).max(1.) as u64;
+
+        Tracked {
+            source,
+            clock: Arc::clone(clock),
+            events_buffer: Arc::clone(events_buffer),
+            index,
+            samples: 0,
+            samples_per_update,
+        }
+    }
+
+    fn update_clock(&self) {
+        let samples_per_second = self.source.sample_rate() as f64 * self.source.channels() as f64;
+        let position = Duration::from_secs_f64(self.samples as f64 / samples_per_second);
+
+        let beat = self.clock.lock().unwrap().advance(position);
+        if let Some(beat_number) = beat {
+            let mut buffer = self.events_buffer.lock().unwrap();
+            buffer.push(MixerEvent::Beat {
+                index: self.index,
+                beat_number,
+            });
+        }
+    }
+}
+
+impl<S: Source> Iterator for Tracked<S>
+where
+    S::Item: Sample,
+{
+    type Item = S::Item;
+
+    fn next(&mut self) -> Option<S::Item> {
+        // The first sample of each source restarts the clock, so a looping
+        // track's position goes back to zero each time round.
+        if self.samples % self.samples_per_update == 0 {
+            self.update_clock();
+        }
+
+        let sample = self.source.next()?;
+        self.samples += 1;
+        Some(sample)
+    }
+}
+
+impl<S: Source> Source for Tracked<S>
+where
+    S::Item: Sample,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        self.source.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.source.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.source.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.source.total_duration()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(secs: f64) -> Duration {
+        Duration::from_secs_f64(secs)
+    }
+
+    #[test]
+    fn changing_tempo_mid_beat_fires_no_duplicate() {
+        let mut clock = TrackClock::default();
+        clock.set_tempo(60., secs(0.));
+        assert_eq!(clock.advance(secs(0.5)), Some(0));
+        assert_eq!(clock.advance(secs(1.2)), Some(1));
+
+        // Still in beat 1
+        clock.set_tempo(60., secs(0.));
+        assert_eq!(clock.advance(secs(1.3)), None);
+
+        // Now in beat 2 (2.6 beats in), which started before the change
+        clock.set_tempo(120., secs(0.));
+        assert_eq!(clock.advance(secs(1.4)), None);
+        assert_eq!(clock.advance(secs(1.5)), Some(2));
+    }
+
+    #[test]
+    fn a_tempo_set_before_the_first_beat_fires_it() {
+        let mut clock = TrackClock::default();
+        clock.advance(secs(0.5));
+        clock.set_tempo(60., secs(1.));
+        assert_eq!(clock.advance(secs(0.9)), None);
+        assert_eq!(clock.advance(secs(1.)), Some(0));
+    }
+}
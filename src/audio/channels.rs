@@ -0,0 +1,250 @@
+use std::time::Duration;
+
+use rodio::Source;
+
+/// How decoded sources are fitted to the output device's channels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChannelPolicy {
+    /// Let rodio convert, which repeats or drops channels in order. On
+    /// surround outputs, this can put stereo sources in the wrong speakers.
+    Passthrough,
+    /// Map each source channel to the output channel for the same speaker,
+    /// folding any the output lacks into the nearest ones it has.
+    Remap { mono: MonoPlacement },
+}
+
+impl Default for ChannelPolicy {
+    fn default() -> Self {
+        ChannelPolicy::Remap {
+            mono: MonoPlacement::FrontPair,
+        }
+    }
+}
+
+/// Where mono sources are played on outputs with more than one channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MonoPlacement {
+    /// Both front speakers, at full volume.
+    FrontPair,
+    /// The center speaker, or the front pair if there isn't one.
+    Center,
+}
+
+/// Speaker positions, in the order channels are interleaved for each
+/// channel count (following WAVE/cpal conventions).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Speaker {
+    FrontLeft,
+    FrontRight,
+    Center,
+    Lfe,
+    BackLeft,
+    BackRight,
+    SideLeft,
+    SideRight,
+    Other,
+}
+
+fn layout(channels: u16) -> Vec<Speaker> {
+    use Speaker::*;
+
+    let known: &[Speaker] = match channels {
+        1 => &[Center],
+        2 => &[FrontLeft, FrontRight],
+        3 => &[FrontLeft, FrontRight, Center],
+        4 => &[FrontLeft, FrontRight, BackLeft, BackRight],
+        5 => &[FrontLeft, FrontRight, Center, BackLeft, BackRight],
+        6 => &[FrontLeft, FrontRight, Center, Lfe, BackLeft, BackRight],
+        _ => &[
+            FrontLeft, FrontRight, Center, Lfe, BackLeft, BackRight, SideLeft, SideRight,
+        ],
+    };
+
+    (0..channels as usize)
+        .map(|i| known.get(i).copied().unwrap_or(Other))
+        .collect()
+}
+
+/// The gain from each input channel to each output channel, as
+/// `matrix[output * input_channels + input]`.
+fn mix_matrix(input: u16, output: u16, mono: MonoPlacement) -> Vec<f32> {
+    use Speaker::*;
+
+    const FOLD: f32 = std::f32::consts::FRAC_1_SQRT_2;
+
+    let inputs = layout(input);
+    let outputs = layout(output);
+    let mut matrix = vec![0.; inputs.len() * outputs.len()];
+
+    if input == output {
+        for i in 0..inputs.len() {
+            matrix[i * inputs.len() + i] = 1.;
+        }
+        return matrix;
+    }
+
+    let position = |speaker| outputs.iter().position(|&s| s == speaker);
+    let mut set = |speaker, i: usize, gain| {
+        if let Some(o) = position(speaker) {
+            matrix[o * inputs.len() + i] = gain;
+            true
+        } else {
+            false
+        }
+    };
+
+    if output == 1 {
+        let audible = inputs.iter().filter(|&&s| s != Lfe).count().max(1);
+        for (i, &speaker) in inputs.iter().enumerate() {
+            if speaker != Lfe {
+                set(Center, i, 1. / audible as f32);
+            }
+        }
+        return matrix;
+    }
+
+    if input == 1 {
+        if mono == MonoPlacement::FrontPair || !set(Center, 0, 1.) {
+            set(FrontLeft, 0, 1.);
+            set(FrontRight, 0, 1.);
+        }
+        return matrix;
+    }
+
+    for (i, &speaker) in inputs.iter().enumerate() {
+        if set(speaker, i, 1.) {
+            continue;
+        }
+
+        match speaker {
+            Center => {
+                set(FrontLeft, i, FOLD);
+                set(FrontRight, i, FOLD);
+            }
+            BackLeft | SideLeft => {
+                let _ = set(SideLeft, i, 1.) || set(BackLeft, i, 1.) || set(FrontLeft, i, FOLD);
+            }
+            BackRight | SideRight => {
+                let _ = set(SideRight, i, 1.) || set(BackRight, i, 1.) || set(FrontRight, i, FOLD);
+            }
+            _ => (),
+        }
+    }
+
+    matrix
+}
+
+/// Converts a source to a different channel count by speaker position.
+/// When the channel counts already match, samples are passed straight
+/// through without mixing.
+///
+/// NOTE: The mapping is chosen from the source's channel count when it's
+/// created, so sources that change channel count partway through aren't
+/// remapped correctly.
+pub(crate) struct ChannelMap<S> {
+    source: S,
+    input: u16,
+    output: u16,
+    matrix: Vec<f32>,
+    frame: Vec<f32>,
+    next_channel: u16,
+}
+
+impl<S: Source<Item = f32>> ChannelMap<S> {
+    pub fn new(source: S, output: u16, mono: MonoPlacement) -> Self {
+        let input = source.channels();
+        ChannelMap {
+            matrix: mix_matrix(input, output, mono),
+            frame: vec![0.; input as usize],
+            source,
+            input,
+            output,
+            next_channel: output,
+        }
+    }
+}
+
+impl<S: Source<Item = f32>> Iterator for ChannelMap<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.input == self.output {
+            return self.source.next();
+        }
+
+        if self.next_channel == self.output {
+            for sample in &mut self.frame {
+                *sample = self.source.next()?;
+            }
+            self.next_channel = 0;
+        }
+
+        let start = self.next_channel as usize * self.input as usize;
+        let gains = &self.matrix[start..start + self.input as usize];
+        self.next_channel += 1;
+
+        Some(gains.iter().zip(&self.frame).map(|(g, s)| g * s).sum())
+    }
+}
+
+impl<S: Source<Item = f32>> Source for ChannelMap<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        if self.input == self.output {
+            return self.source.current_frame_len();
+        }
+
+        self.source
+            .current_frame_len()
+            .map(|len| len / self.input as usize * self.output as usize)
+    }
+
+    fn channels(&self) -> u16 {
+        self.output
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.source.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.source.total_duration()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The gains into `output` channel `o`, one per input channel.
+    fn gains_into(matrix: &[f32], input: u16, o: usize) -> &[f32] {
+        &matrix[o * input as usize..(o + 1) * input as usize]
+    }
+
+    #[test]
+    fn stereo_goes_to_the_front_pair_of_5_1() {
+        let matrix = mix_matrix(2, 6, MonoPlacement::FrontPair);
+        assert_eq!(gains_into(&matrix, 2, 0), &[1., 0.]);
+        assert_eq!(gains_into(&matrix, 2, 1), &[0., 1.]);
+        for o in 2..6 {
+            assert_eq!(gains_into(&matrix, 2, o), &[0., 0.], "output {}", o);
+        }
+    }
+
+    #[test]
+    fn mono_goes_to_the_front_pair() {
+        let matrix = mix_matrix(1, 6, MonoPlacement::FrontPair);
+        assert_eq!(matrix, vec![1., 1., 0., 0., 0., 0.]);
+    }
+
+    #[test]
+    fn mono_goes_to_the_center() {
+        let matrix = mix_matrix(1, 6, MonoPlacement::Center);
+        assert_eq!(matrix, vec![0., 0., 1., 0., 0., 0.]);
+    }
+
+    #[test]
+    fn mono_goes_to_the_front_pair_without_a_center() {
+        let matrix = mix_matrix(1, 2, MonoPlacement::Center);
+        assert_eq!(matrix, vec![1., 1.]);
+    }
+}
@@ -0,0 +1,125 @@
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+use std::time::{Duration, Instant};
+
+use rodio::{Sample, Source};
+
+/// How many samples a source times before adding them to the total.
+const FLUSH_SAMPLES: u32 = 1024;
+
+/// Adds up the time the audio output spends pulling samples from sources,
+/// for `Mixer::cpu_estimate`.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug)]
+pub(crate) struct CpuMeter {
+    busy_nanos: Arc<AtomicU64>,
+    last: (Instant, u64),
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl CpuMeter {
+    pub fn new() -> Self {
+        CpuMeter {
+            busy_nanos: Arc::new(AtomicU64::new(0)),
+            last: (Instant::now(), 0),
+        }
+    }
+
+    /// The total for sources to add to.
+    pub fn total(&self) -> Arc<AtomicU64> {
+        Arc::clone(&self.busy_nanos)
+    }
+
+    /// The time spent per second since the last call.
+    pub fn estimate(&mut self) -> Duration {
+        let now = Instant::now();
+        let busy = self.busy_nanos.load(Ordering::Relaxed);
+        let (then, before) = std::mem::replace(&mut self.last, (now, busy));
+
+        let elapsed = (now - then).as_secs_f64();
+        if elapsed <= 0. {
+            return Duration::ZERO;
+        }
+        Duration::from_secs_f64(busy.saturating_sub(before) as f64 / 1e9 / elapsed)
+    }
+}
+
+/// A source that adds the time taken by each of its samples to `total`,
+/// if there is one.
+pub(crate) struct Timed<S> {
+    source: S,
+    total: Option<Arc<AtomicU64>>,
+    pending_nanos: u64,
+    pending_samples: u32,
+}
+
+impl<S> Timed<S> {
+    pub fn new(source: S, total: Option<Arc<AtomicU64>>) -> Self {
+        Timed {
+            source,
+            total,
+            pending_nanos: 0,
+            pending_samples: 0,
+        }
+    }
+
+    fn flush(&mut self) {
+        if let Some(total) = &self.total {
+            total.fetch_add(self.pending_nanos, Ordering::Relaxed);
+        }
+        self.pending_nanos = 0;
+        self.pending_samples = 0;
+    }
+}
+
+impl<S: Source> Iterator for Timed<S>
+where
+    S::Item: Sample,
+{
+    type Item = S::Item;
+
+    fn next(&mut self) -> Option<S::Item> {
+        if self.total.is_none() {
+            return self.source.next();
+        }
+
+        let start = Instant::now();
+        let sample = self.source.next();
+        self.pending_nanos += start.elapsed().as_nanos() as u64;
+        self.pending_samples += 1;
+
+        if self.pending_samples >= FLUSH_SAMPLES || sample.is_none() {
+            self.flush();
+        }
+        sample
+    }
+}
+
+impl<S: Source> Source for Timed<S>
+where
+    S::Item: Sample,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        self.source.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.source.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.source.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.source.total_duration()
+    }
+}
+
+impl<S> Drop for Timed<S> {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
@@ -0,0 +1,174 @@
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+use std::time::Duration;
+
+use rodio::Source;
+use serde::Serialize;
+
+use super::OutputConfig;
+
+/// What might be stopping the game being heard, from `Mixer::diagnose`.
+/// Worth showing on a debug screen, or attaching to bug reports.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AudioDiagnosis {
+    /// Whether the mixer's audio thread is still running. If it has
+    /// stopped (e.g. it panicked), nothing can be heard, and only
+    /// `device_present` is checked; the rest are left empty.
+    pub thread_running: bool,
+    /// Whether the system has a default output device at all.
+    pub device_present: bool,
+    /// Whether the mixer has opened its output stream, which happens in
+    /// `Mixer::init`.
+    pub stream_open: bool,
+    pub output_config: Option<OutputConfig>,
+    pub sound_volume: f32,
+    pub track_volume: f32,
+    /// How many tracks have something to play.
+    pub live_sinks: usize,
+    /// How many sounds and tones are still playing.
+    pub live_sounds: usize,
+    /// The most recent audio that couldn't be decoded, as the key (in
+    /// `Debug` format) and a guess at its format.
+    pub last_decode_error: Option<String>,
+    /// Whether `Mixer::output_is_silent`, or `None` without
+    /// `MixerOptions::detect_silence`.
+    pub output_silent: Option<bool>,
+}
+
+impl AudioDiagnosis {
+    /// The diagnosis when the audio thread can't be asked for one.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn thread_stopped() -> Self {
+        AudioDiagnosis {
+            thread_running: false,
+            device_present: device_present(),
+            stream_open: false,
+            output_config: None,
+            sound_volume: 0.,
+            track_volume: 0.,
+            live_sinks: 0,
+            live_sounds: 0,
+            last_decode_error: None,
+            output_silent: None,
+        }
+    }
+}
+
+pub(crate) fn device_present() -> bool {
+    use rodio::cpal::traits::HostTrait;
+
+    rodio::cpal::default_host()
+        .default_output_device()
+        .is_some()
+}
+
+/// Samples no louder than this count as silence. It's below the quietest
+/// non-zero 16-bit sample, so fading echoes don't count as sound.
+const SILENCE_THRESHOLD: f32 = 1. / 32768.;
+
+/// How many samples the tap counts before updating the meter.
+const METER_FLUSH_SAMPLES: u64 = 1024;
+
+/// How long the final mix has been silent, kept up to date by `Metered`.
+#[derive(Debug)]
+pub(crate) struct SilenceMeter {
+    window: Duration,
+    /// In microseconds of audio, or `u64::MAX` if nothing has been heard
+    /// since the output opened.
+    silent_for: AtomicU64,
+}
+
+impl SilenceMeter {
+    pub fn new(window: Duration) -> Self {
+        SilenceMeter {
+            window,
+            silent_for: AtomicU64::new(u64::MAX),
+        }
+    }
+
+    /// Forget what's been heard, e.g. when a new output opens.
+    pub fn reset(&self) {
+        self.silent_for.store(u64::MAX, Ordering::Relaxed);
+    }
+
+    pub fn silent(&self) -> bool {
+        let silent_for = self.silent_for.load(Ordering::Relaxed);
+        silent_for == u64::MAX || Duration::from_micros(silent_for) >= self.window
+    }
+}
+
+/// A tap on the final mix for a `SilenceMeter`. It carries on with silence
+/// when its source runs dry, like `Limited`, so silence can be measured.
+pub(crate) struct Metered<S> {
+    source: S,
+    meter: Arc<SilenceMeter>,
+    samples_per_second: f64,
+    heard: bool,
+    silent_samples: u64,
+    pending: u64,
+}
+
+impl<S: Source<Item = f32>> Metered<S> {
+    pub fn new(source: S, meter: Arc<SilenceMeter>) -> Self {
+        let samples_per_second = source.sample_rate() as f64 * source.channels().max(1) as f64;
+        meter.reset();
+        Metered {
+            source,
+            meter,
+            samples_per_second,
+            heard: false,
+            silent_samples: 0,
+            pending: 0,
+        }
+    }
+
+    fn flush(&mut self) {
+        let silent_for = match self.heard {
+            true => (self.silent_samples as f64 / self.samples_per_second * 1e6) as u64,
+            false => u64::MAX,
+        };
+        self.meter.silent_for.store(silent_for, Ordering::Relaxed);
+        self.pending = 0;
+    }
+}
+
+impl<S: Source<Item = f32>> Iterator for Metered<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let sample = self.source.next().unwrap_or(0.);
+
+        if sample.abs() > SILENCE_THRESHOLD {
+            self.heard = true;
+            self.silent_samples = 0;
+        } else {
+            self.silent_samples += 1;
+        }
+
+        self.pending += 1;
+        if self.pending >= METER_FLUSH_SAMPLES {
+            self.flush();
+        }
+        Some(sample)
+    }
+}
+
+impl<S: Source<Item = f32>> Source for Metered<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        self.source.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.source.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
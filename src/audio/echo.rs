@@ -0,0 +1,324 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use rodio::Source;
+
+/// How often (in audio time) echoing sources check for new settings.
+const CHECK_PERIOD: Duration = Duration::from_millis(5);
+
+/// How long the echo takes to ramp to new settings, or out and back in when
+/// its delay changes.
+const RAMP_TIME: Duration = Duration::from_millis(20);
+
+/// Any more feedback than this and the echo would never die away.
+const MAX_FEEDBACK: f32 = 0.95;
+
+/// The longest delay, to keep the buffer a sensible size.
+const MAX_DELAY: Duration = Duration::from_secs(5);
+
+/// The tail of a finished track stops once its echoes are this quiet.
+const TAIL_FLOOR: f32 = 0.001;
+
+/// The longest a finished track's tail can ring for.
+const MAX_TAIL: Duration = Duration::from_secs(10);
+
+/// A single feedback delay on a track, for a bit of space around it.
+///
+/// The echo is mixed before the track's volume is applied, so it's muted
+/// with the track. When a non-looping track finishes, its echo rings out
+/// until it's inaudible, unless the track is removed first.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Echo {
+    /// The time between repeats, up to 5 seconds. This is in the track's
+    /// own time, so it changes with `Track::speed`.
+    pub delay: Duration,
+    /// How much of each repeat is fed into the next, from 0.0 (a single
+    /// repeat) to 0.95 (a long tail).
+    pub feedback: f32,
+    /// The volume of the repeats, relative to the track.
+    pub mix: f32,
+}
+
+impl Echo {
+    fn delay(&self) -> Duration {
+        self.delay.min(MAX_DELAY)
+    }
+
+    fn feedback(&self) -> f32 {
+        self.feedback.clamp(0., MAX_FEEDBACK)
+    }
+
+    fn mix(&self) -> f32 {
+        self.mix.max(0.)
+    }
+
+    /// How long the repeats take to die away once the track stops.
+    pub fn tail(&self) -> Duration {
+        let feedback = self.feedback();
+        let repeats = match feedback {
+            f if f <= TAIL_FLOOR => 1.,
+            f => (TAIL_FLOOR.ln() / f.ln()).ceil() + 1.,
+        };
+
+        self.delay().mul_f32(repeats).min(MAX_TAIL)
+    }
+}
+
+/// A ring buffer holding one delay's worth of interleaved samples.
+#[derive(Debug)]
+struct DelayLine {
+    buffer: Vec<f32>,
+    position: usize,
+}
+
+impl DelayLine {
+    fn new(delay: Duration, sample_rate: u32, channels: u16) -> Self {
+        DelayLine {
+            buffer: vec![0.; line_len(delay, sample_rate, channels)],
+            position: 0,
+        }
+    }
+
+    /// Push a sample in, returning the one from a delay ago.
+    fn process(&mut self, input: f32, feedback: f32) -> f32 {
+        let delayed = self.buffer[self.position];
+        self.buffer[self.position] = input + delayed * feedback;
+        self.position = (self.position + 1) % self.buffer.len();
+        delayed
+    }
+}
+
+fn line_len(delay: Duration, sample_rate: u32, channels: u16) -> usize {
+    let frames = (delay.as_secs_f64() * sample_rate as f64).round().max(1.) as usize;
+    frames * channels.max(1) as usize
+}
+
+/// The echo settings for one track slot, shared with every source it plays.
+#[derive(Debug, Default)]
+pub(crate) struct EchoControl {
+    echo: Option<Echo>,
+    /// The sample rate and channels of the slot's latest source.
+    format: Option<(u32, u16)>,
+    /// A line for a changed delay, for the playing source to switch to.
+    /// `Some(None)` switches the echo off.
+    next_line: Option<Option<DelayLine>>,
+    /// The line from the source that just ended, so a looping track's echo
+    /// carries on into the next time round.
+    parked: Option<DelayLine>,
+}
+
+impl EchoControl {
+    /// Change the settings. This runs on the speaker's side, so the line
+    /// for a new delay is allocated here rather than while mixing.
+    pub fn set(&mut self, echo: Option<Echo>) {
+        let old_delay = self.echo.map(|echo| echo.delay());
+        let new_delay = echo.map(|echo| echo.delay());
+        self.echo = echo;
+
+        if old_delay != new_delay {
+            self.parked = None;
+
+            // Until there's a source, there's no format to make a line for,
+            // but then the first source makes its own
+            if let Some((rate, channels)) = self.format {
+                self.next_line =
+                    Some(echo.map(|echo| DelayLine::new(echo.delay(), rate, channels)));
+            }
+        }
+    }
+}
+
+/// Adds a track's `Echo` to its source, following changes made through its
+/// slot's `EchoControl`.
+pub(crate) struct Echoing<S> {
+    source: S,
+    control: Arc<Mutex<EchoControl>>,
+    echo: Option<Echo>,
+    line: Option<DelayLine>,
+    /// A line waiting to be switched to once the echo has ramped out.
+    next_line: Option<Option<DelayLine>>,
+    started: bool,
+    /// Whether to ring out when the source ends.
+    ring_out: bool,
+    /// The samples left in the tail, once the source has ended.
+    tail: Option<usize>,
+
+    feedback: f32,
+    mix: f32,
+    /// Ramps the whole echo out and back in around a change of delay.
+    gain: f32,
+    ramp_step: f32,
+    samples: usize,
+    samples_per_check: usize,
+}
+
+impl<S: Source<Item = f32>> Echoing<S> {
+    /// `ring_out` should be false for looping tracks, where the next source
+    /// carries on the echo instead.
+    pub fn new(source: S, control: &Arc<Mutex<EchoControl>>, ring_out: bool) -> Self {
+        let sample_rate = source.sample_rate();
+        let channels = source.channels();
+        let samples_per_second = sample_rate as f32 * channels as f32;
+
+        let (echo, line) = {
+            let mut control = control.lock().unwrap();
+            control.format = Some((sample_rate, channels));
+            let line = control
+                .echo
+                .map(|echo| DelayLine::new(echo.delay(), sample_rate, channels));
+            (control.echo, line)
+        };
+
+        Echoing {
+            source,
+            control: Arc::clone(control),
+            echo,
+            line,
+            next_line: None,
+            started: false,
+            ring_out,
+            tail: None,
+            feedback: echo.map_or(0., |echo| echo.feedback()),
+            mix: echo.map_or(0., |echo| echo.mix()),
+            gain: 1.,
+            ramp_step: 1. / (samples_per_second * RAMP_TIME.as_secs_f32()).max(1.),
+            samples: 0,
+            samples_per_check: (samples_per_second * CHECK_PERIOD.as_secs_f32()).max(1.) as usize,
+        }
+    }
+
+    fn check_control(&mut self) {
+        let mut control = self.control.lock().unwrap();
+        self.echo = control.echo;
+
+        if !self.started {
+            self.started = true;
+
+            // Pick up where the last time round left off
+            if let (Some(parked), Some(line)) = (control.parked.take(), &self.line) {
+                if parked.buffer.len() == line.buffer.len() {
+                    self.line = Some(parked);
+                }
+            }
+        }
+
+        if let Some(line) = control.next_line.take() {
+            // A line made before this source's format was known won't fit
+            let fits = match (&line, self.echo) {
+                (Some(line), Some(echo)) => {
+                    line.buffer.len() == line_len(echo.delay(), self.sample_rate(), self.channels())
+                }
+                _ => true,
+            };
+            if fits {
+                self.next_line = Some(line);
+            }
+        }
+    }
+
+    /// Leave the line for the next source of a looping track.
+    fn park(&mut self) {
+        if let Some(line) = self.line.take() {
+            let mut control = self.control.lock().unwrap();
+            if control.next_line.is_none() && self.next_line.is_none() {
+                control.parked = Some(line);
+            }
+        }
+    }
+
+    fn process(&mut self, input: f32) -> f32 {
+        let (target_feedback, target_mix) = match self.echo {
+            Some(echo) => (echo.feedback(), echo.mix()),
+            None => (0., 0.),
+        };
+        self.feedback = approach(self.feedback, target_feedback, self.ramp_step);
+        self.mix = approach(self.mix, target_mix, self.ramp_step);
+
+        if self.next_line.is_some() && (self.line.is_none() || self.gain <= 0.) {
+            self.line = self.next_line.take().unwrap();
+            self.gain = 0.;
+        }
+
+        let line = match &mut self.line {
+            Some(line) => line,
+            None => return input,
+        };
+
+        let target_gain = if self.next_line.is_some() { 0. } else { 1. };
+        self.gain = approach(self.gain, target_gain, self.ramp_step);
+
+        let delayed = line.process(input, self.feedback);
+        input + delayed * self.mix * self.gain
+    }
+}
+
+fn approach(value: f32, target: f32, step: f32) -> f32 {
+    if value < target {
+        (value + step).min(target)
+    } else {
+        (value - step).max(target)
+    }
+}
+
+impl<S: Source<Item = f32>> Iterator for Echoing<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.samples % self.samples_per_check == 0 {
+            self.check_control();
+        }
+        self.samples += 1;
+
+        if let Some(remaining) = &mut self.tail {
+            if *remaining == 0 {
+                return None;
+            }
+            *remaining -= 1;
+            return Some(self.process(0.));
+        }
+
+        match self.source.next() {
+            Some(sample) => Some(self.process(sample)),
+            None if self.ring_out && self.line.is_some() => {
+                let tail = self.echo.map_or(Duration::from_secs(0), |echo| echo.tail());
+                let samples_per_second = self.sample_rate() as f64 * self.channels() as f64;
+                let remaining = (tail.as_secs_f64() * samples_per_second) as usize;
+
+                // Finish on a whole frame
+                let channels = self.channels().max(1) as usize;
+                let remaining = remaining - remaining % channels;
+
+                self.tail = Some(remaining);
+                self.next()
+            }
+            None => {
+                self.park();
+                None
+            }
+        }
+    }
+}
+
+impl<S: Source<Item = f32>> Source for Echoing<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        match self.tail {
+            Some(remaining) => Some(remaining),
+            None => self.source.current_frame_len(),
+        }
+    }
+
+    fn channels(&self) -> u16 {
+        self.source.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.source.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        match self.ring_out && self.line.is_some() {
+            true => None,
+            false => self.source.total_duration(),
+        }
+    }
+}
@@ -0,0 +1,171 @@
+use std::time::Duration;
+
+use serde::Deserialize;
+
+/// The quietest an `Exponential` fade gets before snapping to silence.
+const EXPONENTIAL_FLOOR_DB: f32 = -60.;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FadeCurve {
+    #[default]
+    Linear,
+    /// Keeps the summed power of a crossfade constant, avoiding the dip in
+    /// loudness at the midpoint of a linear crossfade.
+    EqualPower,
+    /// Linear in decibels, which tends to sound smoother for long fades.
+    Exponential,
+}
+
+impl FadeCurve {
+    /// The gain of a fade in from silence, at progress `t` from 0 to 1.
+    pub fn fade_in(self, t: f32) -> f32 {
+        let t = t.clamp(0., 1.);
+        match self {
+            FadeCurve::Linear => t,
+            FadeCurve::EqualPower => (t * std::f32::consts::FRAC_PI_2).sin(),
+            FadeCurve::Exponential => match t {
+                t if t <= 0. => 0.,
+                t => 10_f32.powf(EXPONENTIAL_FLOOR_DB * (1. - t) / 20.),
+            },
+        }
+    }
+
+    /// The gain of a fade out to silence, at progress `t` from 0 to 1.
+    pub fn fade_out(self, t: f32) -> f32 {
+        self.fade_in(1. - t)
+    }
+
+    /// The gain partway through a fade from one level to another. A fade
+    /// up follows the shape of `fade_in`, and a fade down the shape of
+    /// `fade_out`, so that the two halves of a crossfade mirror each other.
+    pub fn blend(self, from: f32, to: f32, t: f32) -> f32 {
+        if to >= from {
+            from + (to - from) * self.fade_in(t)
+        } else {
+            to + (from - to) * self.fade_out(t)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Fade {
+    from: f32,
+    to: f32,
+    elapsed: Duration,
+    duration: Duration,
+    curve: FadeCurve,
+}
+
+impl Fade {
+    pub fn steady(level: f32) -> Self {
+        Fade {
+            from: level,
+            to: level,
+            elapsed: Duration::from_secs(0),
+            duration: Duration::from_secs(0),
+            curve: FadeCurve::Linear,
+        }
+    }
+
+    pub fn level(&self) -> f32 {
+        if self.elapsed >= self.duration {
+            return self.to;
+        }
+
+        let t = self.elapsed.as_secs_f32() / self.duration.as_secs_f32();
+        self.curve.blend(self.from, self.to, t)
+    }
+
+    pub fn advance(&mut self, delta: Duration) -> f32 {
+        self.elapsed += delta;
+        self.level()
+    }
+
+    /// Start a new fade towards `to`, beginning from the current level.
+    pub fn retarget(&mut self, to: f32, duration: Duration, curve: FadeCurve) {
+        *self = Fade {
+            from: self.level(),
+            to,
+            elapsed: Duration::from_secs(0),
+            duration,
+            curve,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CURVES: &[FadeCurve] = &[
+        FadeCurve::Linear,
+        FadeCurve::EqualPower,
+        FadeCurve::Exponential,
+    ];
+
+    fn steps() -> impl Iterator<Item = f32> {
+        (0..=20).map(|i| i as f32 / 20.)
+    }
+
+    #[test]
+    fn fades_reach_their_ends() {
+        for &curve in CURVES {
+            assert_eq!(curve.fade_in(0.), 0.);
+            assert_eq!(curve.fade_in(1.), 1.);
+            assert_eq!(curve.blend(0.25, 0.75, 0.), 0.25);
+            assert_eq!(curve.blend(0.25, 0.75, 1.), 0.75);
+            assert_eq!(curve.blend(0.75, 0.25, 0.), 0.75);
+            assert_eq!(curve.blend(0.75, 0.25, 1.), 0.25);
+        }
+    }
+
+    #[test]
+    fn fades_are_monotonic() {
+        for &curve in CURVES {
+            let up = steps()
+                .map(|t| curve.blend(0.2, 0.8, t))
+                .collect::<Vec<_>>();
+            let down = steps()
+                .map(|t| curve.blend(0.8, 0.2, t))
+                .collect::<Vec<_>>();
+            assert!(up.windows(2).all(|w| w[0] <= w[1]), "{:?}", curve);
+            assert!(down.windows(2).all(|w| w[0] >= w[1]), "{:?}", curve);
+            assert!(up.iter().chain(&down).all(|&g| (0.2..=0.8).contains(&g)));
+        }
+    }
+
+    #[test]
+    fn fading_to_the_same_level_holds_it() {
+        for &curve in CURVES {
+            for t in steps() {
+                assert!((curve.blend(0.5, 0.5, t) - 0.5).abs() < 1e-6, "{:?}", curve);
+            }
+        }
+    }
+
+    #[test]
+    fn crossfades() {
+        for t in steps() {
+            let linear = FadeCurve::Linear;
+            let sum = linear.blend(1., 0., t) + linear.blend(0., 1., t);
+            assert!((sum - 1.).abs() < 1e-6);
+
+            let equal = FadeCurve::EqualPower;
+            let power = equal.blend(1., 0., t).powi(2) + equal.blend(0., 1., t).powi(2);
+            assert!((power - 1.).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn retargeting_starts_from_the_current_level() {
+        let mut fade = Fade::steady(1.);
+        fade.retarget(0., Duration::from_secs(1), FadeCurve::Linear);
+        let halfway = fade.advance(Duration::from_millis(500));
+        assert!((halfway - 0.5).abs() < 1e-6);
+
+        fade.retarget(1., Duration::from_secs(1), FadeCurve::Linear);
+        assert!((fade.level() - 0.5).abs() < 1e-6);
+        assert_eq!(fade.advance(Duration::from_secs(2)), 1.);
+    }
+}
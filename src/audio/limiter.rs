@@ -0,0 +1,113 @@
+use std::time::Duration;
+
+use rodio::Source;
+
+/// Keeps the final mix under 0dBFS, so many sounds landing at once get
+/// quieter instead of clipping.
+///
+/// Below `threshold` the mix is left alone. Above it, peaks are squashed
+/// smoothly towards -0.1dBFS, and the gain recovers over `release` once
+/// they pass. There's no lookahead, so a sudden peak is turned down on the
+/// sample it arrives, which can soften the attack of very loud hits.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Limiter {
+    /// Where the limiting starts, in dBFS (e.g. `-6.0`). It's kept between
+    /// `-60.0` and `-1.0`.
+    pub threshold: f32,
+    /// How long the gain takes to recover after a peak.
+    pub release: Duration,
+}
+
+impl Default for Limiter {
+    fn default() -> Self {
+        Limiter {
+            threshold: -6.,
+            release: Duration::from_millis(150),
+        }
+    }
+}
+
+/// The level peaks level off at (-0.1dBFS), as a gain.
+const CEILING: f32 = 0.989;
+
+/// The range of thresholds, in dBFS. The highest leaves the knee room to
+/// bend.
+const THRESHOLD_RANGE: (f32, f32) = (-60., -1.);
+
+/// A source passed through a `Limiter`. It carries on with silence when
+/// its source runs dry, since the mix it limits can always have more
+/// sounds added to it.
+pub(crate) struct Limited<S> {
+    source: S,
+    threshold: f32,
+    decay: f32,
+    envelope: f32,
+}
+
+impl<S: Source<Item = f32>> Limited<S> {
+    pub fn new(source: S, limiter: Limiter) -> Self {
+        let (min, max) = THRESHOLD_RANGE;
+        let threshold_db = match limiter.threshold.is_nan() {
+            true => Limiter::default().threshold,
+            false => limiter.threshold.clamp(min, max),
+        };
+        let threshold = 10_f32.powf(threshold_db / 20.);
+
+        // The envelope falls to 1/e of a peak after `release`
+        let samples_per_second = source.sample_rate() as f32 * source.channels().max(1) as f32;
+        let release_samples = limiter.release.as_secs_f32() * samples_per_second;
+        let decay = match release_samples {
+            samples if samples >= 1. => (-1. / samples).exp(),
+            _ => 0.,
+        };
+
+        Limited {
+            source,
+            threshold,
+            decay,
+            envelope: 0.,
+        }
+    }
+
+    /// The gain for the current envelope. Above the threshold, the level
+    /// follows a tanh curve that meets it with the same slope and levels
+    /// off at the ceiling.
+    fn gain(&self) -> f32 {
+        let (level, threshold) = (self.envelope, self.threshold);
+        if level <= threshold {
+            return 1.;
+        }
+
+        let headroom = CEILING - threshold;
+        let limited = threshold + headroom * ((level - threshold) / headroom).tanh();
+        limited / level
+    }
+}
+
+impl<S: Source<Item = f32>> Iterator for Limited<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let sample = self.source.next().unwrap_or(0.);
+        self.envelope = sample.abs().max(self.envelope * self.decay);
+        Some(sample * self.gain())
+    }
+}
+
+impl<S: Source<Item = f32>> Source for Limited<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        self.source.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.source.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
@@ -0,0 +1,225 @@
+use std::{
+    collections::{HashMap, HashSet},
+    hash::Hash,
+    io::Cursor,
+    path::{Path, PathBuf},
+};
+
+use rodio::{Decoder, Source};
+
+use super::{AudioBytes, AudioLibrary, AudioVolumes};
+
+/// The most a quiet sound will be turned up, so near-silent entries aren't
+/// amplified into noise. (+12dB)
+const MAX_GAIN: f32 = 4.;
+
+/// Loudness is measured over blocks this long, like LUFS.
+const BLOCK_SECONDS: f32 = 0.4;
+
+/// Blocks quieter than this (-70dB) are ignored, so silence at the start or
+/// end of a sound doesn't make it seem quieter than it is.
+const SILENCE_GATE: f32 = 1e-7;
+
+/// The level that `analyze_volumes` brings each entry to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VolumeTarget {
+    /// The loudest sample, in dBFS (e.g. `-1.0`).
+    Peak(f32),
+    /// The average loudness, in dBFS (e.g. `-18.0`). Entries are never
+    /// turned up so far that they'd clip.
+    Loudness(f32),
+}
+
+/// How loud a sound is, as linear amplitudes from 0 to 1.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Loudness {
+    pub peak: f32,
+    /// The RMS of every 400ms block that isn't silent.
+    ///
+    /// NOTE: This is a rough stand-in for LUFS, without the frequency
+    /// weighting, so bass-heavy sounds will measure a little loud.
+    pub loudness: f32,
+}
+
+fn db_to_gain(db: f32) -> f32 {
+    10_f32.powf(db / 20.)
+}
+
+impl Loudness {
+    /// Decode and measure a sound, or `None` if it can't be decoded.
+    pub fn measure(audio_bytes: &AudioBytes) -> Option<Self> {
+        let source = Decoder::new(Cursor::new(audio_bytes.clone())).ok()?;
+        let block_len =
+            (source.sample_rate() as f32 * source.channels() as f32 * BLOCK_SECONDS) as usize;
+        let block_len = block_len.max(1);
+
+        let mut peak = 0_f32;
+        let mut blocks = vec![];
+        let mut block_energy = 0.;
+        let mut block_samples = 0;
+
+        for sample in source.convert_samples::<f32>() {
+            peak = peak.max(sample.abs());
+            block_energy += sample * sample;
+            block_samples += 1;
+
+            if block_samples == block_len {
+                blocks.push(block_energy / block_samples as f32);
+                block_energy = 0.;
+                block_samples = 0;
+            }
+        }
+
+        if block_samples > 0 {
+            blocks.push(block_energy / block_samples as f32);
+        }
+
+        let audible = blocks
+            .into_iter()
+            .filter(|&energy| energy > SILENCE_GATE)
+            .collect::<Vec<_>>();
+
+        let loudness = match audible.len() {
+            0 => 0.,
+            n => (audible.iter().sum::<f32>() / n as f32).sqrt(),
+        };
+
+        Some(Loudness { peak, loudness })
+    }
+
+    /// The volume multiplier that brings this sound to `target`.
+    pub fn gain_for(&self, target: VolumeTarget) -> f32 {
+        if self.peak <= 0. {
+            return 1.;
+        }
+
+        let gain = match target {
+            VolumeTarget::Peak(db) => db_to_gain(db) / self.peak,
+            VolumeTarget::Loudness(db) if self.loudness > 0. => {
+                (db_to_gain(db) / self.loudness).min(1. / self.peak)
+            }
+            VolumeTarget::Loudness(_) => 1.,
+        };
+
+        gain.min(MAX_GAIN)
+    }
+}
+
+/// Measurements from previous runs, keyed by a hash of the audio's bytes,
+/// so unchanged files don't need decoding again.
+///
+/// Only the sounds measured since the cache was loaded are saved, so entries
+/// for files that have since changed or been removed don't pile up.
+///
+/// The file is a line of `hash peak loudness` for each sound.
+#[derive(Debug, Clone, Default)]
+pub struct LoudnessCache {
+    path: Option<PathBuf>,
+    entries: HashMap<u64, Loudness>,
+    used: HashSet<u64>,
+    modified: bool,
+}
+
+impl LoudnessCache {
+    /// Load the cache from `path`. A missing or unreadable file just gives
+    /// an empty cache.
+    pub fn load<P: AsRef<Path>>(path: P) -> Self {
+        let path = path.as_ref();
+        let entries = std::fs::read_to_string(path)
+            .map(|contents| contents.lines().filter_map(parse_line).collect())
+            .unwrap_or_default();
+
+        LoudnessCache {
+            path: Some(path.to_owned()),
+            entries,
+            used: HashSet::new(),
+            modified: false,
+        }
+    }
+
+    pub fn measure(&mut self, audio_bytes: &AudioBytes) -> Option<Loudness> {
+        let hash = content_hash(audio_bytes.as_ref());
+        self.used.insert(hash);
+        if let Some(loudness) = self.entries.get(&hash) {
+            return Some(*loudness);
+        }
+
+        let loudness = Loudness::measure(audio_bytes)?;
+        self.entries.insert(hash, loudness);
+        self.modified = true;
+        Some(loudness)
+    }
+
+    /// Write the cache back to the file it was loaded from, if anything new
+    /// was measured or any old entries went unused.
+    pub fn save(&mut self) -> std::io::Result<()> {
+        let used = &self.used;
+        let before = self.entries.len();
+        self.entries.retain(|hash, _| used.contains(hash));
+        self.modified |= self.entries.len() != before;
+
+        let path = match (&self.path, self.modified) {
+            (Some(path), true) => path,
+            _ => return Ok(()),
+        };
+
+        let mut lines = self
+            .entries
+            .iter()
+            .map(|(hash, loudness)| {
+                format!("{:016x} {} {}\n", hash, loudness.peak, loudness.loudness)
+            })
+            .collect::<Vec<_>>();
+        lines.sort();
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, lines.concat())?;
+
+        self.modified = false;
+        Ok(())
+    }
+}
+
+fn parse_line(line: &str) -> Option<(u64, Loudness)> {
+    let mut parts = line.split_whitespace();
+    let hash = u64::from_str_radix(parts.next()?, 16).ok()?;
+    let peak = parts.next()?.parse().ok()?;
+    let loudness = parts.next()?.parse().ok()?;
+    Some((hash, Loudness { peak, loudness }))
+}
+
+/// FNV-1a, which (unlike `DefaultHasher`) is stable between Rust versions,
+/// so the cache stays valid.
+fn content_hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Work out a volume multiplier for every entry in the library that brings
+/// it to `target`. Entries that can't be decoded are left out.
+///
+/// To use these with `Mixer::update_volumes` alongside hand-tuned volumes,
+/// multiply the two together first, or use `Mixer::normalize_library`,
+/// which does that for you.
+///
+/// This decodes every entry, so it can be slow for big libraries. Pass a
+/// `cache` to skip entries that were measured before, and save it afterwards.
+pub fn analyze_volumes<K: Clone + Eq + Hash>(
+    library: &AudioLibrary<K>,
+    target: VolumeTarget,
+    mut cache: Option<&mut LoudnessCache>,
+) -> AudioVolumes<K> {
+    library
+        .iter()
+        .filter_map(|(key, audio_bytes)| {
+            let loudness = match cache.as_mut() {
+                Some(cache) => cache.measure(audio_bytes),
+                None => Loudness::measure(audio_bytes),
+            }?;
+            Some((key.clone(), loudness.gain_for(target)))
+        })
+        .collect()
+}
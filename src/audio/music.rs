@@ -0,0 +1,185 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Debug,
+    hash::Hash,
+    ops::Range,
+    time::Duration,
+};
+
+use serde::{Deserialize, Deserializer};
+
+use super::{FadeCurve, Mixer, Track, MAX_TRACKS};
+
+fn default_fade() -> f32 {
+    1.
+}
+
+/// The fade becomes a `Duration`, which can't be infinite, NaN, or too
+/// large for a `u64` of seconds. Negative fades are treated as no fade.
+fn fade_in_secs<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f32, D::Error> {
+    use serde::de::Error;
+
+    let fade = f32::deserialize(deserializer)?;
+    if fade.is_finite() && fade < u64::MAX as f32 {
+        Ok(fade)
+    } else {
+        Err(D::Error::custom(format!(
+            "music fade must be a finite number of seconds, not {}",
+            fade
+        )))
+    }
+}
+
+/// A piece of music made of layers that play together, and the volume of
+/// each layer in each of the game's states.
+///
+/// Can be deserialized (e.g. with `static_data_mod!`) from TOML like:
+///
+/// ```toml
+/// fade = 2.0
+/// curve = "equal_power"
+///
+/// [layers]
+/// drums = "Drums"
+/// strings = "Strings"
+///
+/// [states.explore]
+/// strings = 1.0
+///
+/// [states.combat]
+/// drums = 1.0
+/// strings = 0.3
+/// ```
+///
+/// where `fade` is in seconds, and layers left out of a state are silent.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(bound(deserialize = "K: Deserialize<'de>"))]
+pub struct MusicSpec<K> {
+    /// The track key for each layer, by name.
+    pub layers: BTreeMap<String, K>,
+    pub states: HashMap<String, HashMap<String, f32>>,
+
+    /// How long it takes to move between states, in seconds.
+    #[serde(default = "default_fade", deserialize_with = "fade_in_secs")]
+    pub fade: f32,
+
+    /// The curve to move between states with, or the mixer's default.
+    #[serde(default)]
+    pub curve: Option<FadeCurve>,
+}
+
+/// Plays the layers of a `MusicSpec` in a run of track slots, and fades
+/// them to the right volumes when the game changes state.
+///
+/// Put `tracks()` in the `AudioState` at `slots()`. The layers are started
+/// in sync, and keep playing silently while they're not needed, so they
+/// stay in phase. The fades run on the audio thread, so nothing needs to
+/// be updated each frame.
+#[derive(Debug, Clone)]
+pub struct MusicDirector<K> {
+    spec: MusicSpec<K>,
+    first_slot: usize,
+    state: Option<String>,
+}
+
+impl<K: 'static + Clone + Send + Eq + Hash> MusicDirector<K> {
+    /// A director using the track slots from `first_slot` onwards, one for
+    /// each layer.
+    pub fn new(spec: MusicSpec<K>, first_slot: usize) -> Self {
+        assert!(
+            first_slot + spec.layers.len() <= MAX_TRACKS,
+            "Not enough track slots for every music layer"
+        );
+
+        MusicDirector {
+            spec,
+            first_slot,
+            state: None,
+        }
+    }
+
+    pub fn spec(&self) -> &MusicSpec<K> {
+        &self.spec
+    }
+
+    pub fn slots(&self) -> Range<usize> {
+        self.first_slot..self.first_slot + self.spec.layers.len()
+    }
+
+    /// The current state, if one has been set.
+    pub fn state(&self) -> Option<&str> {
+        self.state.as_deref()
+    }
+
+    /// The tracks for each layer, in slot order. Their volume is left at
+    /// 1.0, since the fades set the actual level of each layer.
+    pub fn tracks(&self) -> Vec<Track<K>> {
+        self.spec
+            .layers
+            .values()
+            .map(|key| Track {
+                sync_group: Some(self.first_slot as u32),
+                ..Track::new(key.clone())
+            })
+            .collect()
+    }
+
+    /// Fade every layer to its volume in `state`. The first state set is
+    /// applied immediately, so call this before the tracks start playing.
+    ///
+    /// Returns `false`, and changes nothing, if there is no such state.
+    pub fn set_state(&mut self, state: &str, mixer: &mut Mixer<K>) -> bool {
+        // A spec built by hand rather than parsed might have a fade that
+        // can't be a `Duration`, in which case the state changes at once
+        let duration = match self.state {
+            Some(_) if self.spec.fade.is_finite() && self.spec.fade < u64::MAX as f32 => {
+                Duration::from_secs_f32(self.spec.fade.max(0.))
+            }
+            _ => Duration::from_secs(0),
+        };
+        self.set_state_over(state, duration, mixer)
+    }
+
+    /// Like `set_state`, but with a different fade duration.
+    pub fn set_state_over(
+        &mut self,
+        state: &str,
+        duration: Duration,
+        mixer: &mut Mixer<K>,
+    ) -> bool {
+        let levels = match self.spec.states.get(state) {
+            Some(levels) => levels,
+            None => return false,
+        };
+
+        for (i, layer) in self.spec.layers.keys().enumerate() {
+            let level = levels.get(layer).copied().unwrap_or(0.);
+            mixer.fade_to(self.first_slot + i, level, duration, self.spec.curve);
+        }
+
+        self.state = Some(state.to_owned());
+        true
+    }
+}
+
+#[cfg(all(test, feature = "toml"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fades_must_be_finite() {
+        let parse = |fade: &str| {
+            toml::from_str::<MusicSpec<String>>(&format!(
+                "fade = {}\nlayers = {{}}\nstates = {{}}",
+                fade
+            ))
+            .map(|spec| spec.fade)
+        };
+
+        assert_eq!(parse("2.5").unwrap(), 2.5);
+        assert_eq!(parse("-1.0").unwrap(), -1.);
+        for fade in &["inf", "-inf", "nan", "1e30"] {
+            assert!(parse(fade).is_err(), "{} was accepted", fade);
+        }
+    }
+}
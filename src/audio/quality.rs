@@ -0,0 +1,272 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use rodio::Source;
+
+use super::{decode, AudioBytes};
+
+/// How much work goes into each sample after decoding. Only worth
+/// lowering on machines that struggle to keep up with several tracks.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ResampleQuality {
+    /// Play everything at its own sample rate.
+    #[default]
+    Full,
+    /// Halve the sample rate of audio at 32kHz and above, filtering it
+    /// first to keep aliasing down. This loses everything above a quarter
+    /// of the original rate (11kHz for 44.1kHz audio), and softens the
+    /// highs below that.
+    ///
+    /// Fades and echoes then handle half as many samples, but the output
+    /// resamples everything back up to the device's rate, and decoding
+    /// isn't any cheaper, so whether this saves CPU depends on the
+    /// machine. Check `Mixer::cpu_estimate` with and without it. See
+    /// `MixerOptions::predecode_under` for cheaper decoding.
+    Half,
+}
+
+/// Sources below this rate are left alone by `ResampleQuality::Half`.
+const HALF_MIN_RATE: u32 = 32000;
+
+/// A whole sound decoded ahead of time, shared by every copy playing.
+#[derive(Debug)]
+pub(crate) struct Pcm {
+    channels: u16,
+    sample_rate: u32,
+    samples: Vec<f32>,
+}
+
+impl Pcm {
+    /// Returns `None` if the audio can't be decoded, or decoding it
+    /// panics partway through.
+    pub fn decode(audio_bytes: &AudioBytes) -> Option<Self> {
+        let mut source = decode(audio_bytes)?;
+        let (channels, sample_rate) = (source.channels(), source.sample_rate());
+        let samples = source.by_ref().collect();
+        if source.panicked() {
+            return None;
+        }
+
+        Some(Pcm {
+            channels,
+            sample_rate,
+            samples,
+        })
+    }
+}
+
+pub(crate) struct PcmSource {
+    pcm: Arc<Pcm>,
+    position: usize,
+}
+
+impl PcmSource {
+    pub fn new(pcm: &Arc<Pcm>) -> Self {
+        PcmSource {
+            pcm: Arc::clone(pcm),
+            position: 0,
+        }
+    }
+}
+
+impl Iterator for PcmSource {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let sample = self.pcm.samples.get(self.position).copied();
+        self.position += 1;
+        sample
+    }
+}
+
+impl Source for PcmSource {
+    fn current_frame_len(&self) -> Option<usize> {
+        Some(self.pcm.samples.len().saturating_sub(self.position))
+    }
+
+    fn channels(&self) -> u16 {
+        self.pcm.channels
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.pcm.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        let frames = self.pcm.samples.len() / self.pcm.channels.max(1) as usize;
+        Some(Duration::from_secs_f64(
+            frames as f64 / self.pcm.sample_rate as f64,
+        ))
+    }
+}
+
+/// Audio decoded as it plays, or ahead of time.
+pub(crate) enum Decoded<S> {
+    Stream(S),
+    Pcm(PcmSource),
+}
+
+impl<S: Source<Item = f32>> Iterator for Decoded<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        match self {
+            Decoded::Stream(source) => source.next(),
+            Decoded::Pcm(source) => source.next(),
+        }
+    }
+}
+
+impl<S: Source<Item = f32>> Source for Decoded<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        match self {
+            Decoded::Stream(source) => source.current_frame_len(),
+            Decoded::Pcm(source) => source.current_frame_len(),
+        }
+    }
+
+    fn channels(&self) -> u16 {
+        match self {
+            Decoded::Stream(source) => source.channels(),
+            Decoded::Pcm(source) => source.channels(),
+        }
+    }
+
+    fn sample_rate(&self) -> u32 {
+        match self {
+            Decoded::Stream(source) => source.sample_rate(),
+            Decoded::Pcm(source) => source.sample_rate(),
+        }
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        match self {
+            Decoded::Stream(source) => source.total_duration(),
+            Decoded::Pcm(source) => source.total_duration(),
+        }
+    }
+}
+
+/// A source played at the given quality. The format is checked once, at
+/// the start, so this assumes it doesn't change, which holds for every
+/// decoder rodio has.
+pub(crate) struct Reduced<S> {
+    source: S,
+    halved: bool,
+    channels: usize,
+    /// The second frame of the last pair read, which is blended into the
+    /// next output frame too.
+    previous: Vec<f32>,
+    /// The output frame being played, and how much of it has been.
+    frame: Vec<f32>,
+    played: usize,
+}
+
+impl<S: Source<Item = f32>> Reduced<S> {
+    pub fn new(source: S, quality: ResampleQuality) -> Self {
+        let halved = quality == ResampleQuality::Half && source.sample_rate() >= HALF_MIN_RATE;
+        let channels = source.channels().max(1) as usize;
+        Reduced {
+            source,
+            halved,
+            channels,
+            previous: vec![0.; channels],
+            frame: vec![],
+            played: 0,
+        }
+    }
+
+    /// Read the next two frames, and low-pass filter them (weighting the
+    /// frames 1:2:1 with the one before) into one. This takes out
+    /// everything at the original Nyquist frequency, and most of what's
+    /// near it, so less of it aliases when every other frame is dropped.
+    fn next_frame(&mut self) -> bool {
+        self.frame.clear();
+        self.played = 0;
+
+        for _ in 0..self.channels {
+            match self.source.next() {
+                Some(sample) => self.frame.push(sample),
+                None => return false,
+            }
+        }
+        for channel in 0..self.channels {
+            let odd = self.source.next().unwrap_or(0.);
+            let even = self.frame[channel];
+            self.frame[channel] = 0.25 * self.previous[channel] + 0.5 * even + 0.25 * odd;
+            self.previous[channel] = odd;
+        }
+        true
+    }
+}
+
+impl<S: Source<Item = f32>> Iterator for Reduced<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if !self.halved {
+            return self.source.next();
+        }
+
+        if self.played == self.frame.len() && !self.next_frame() {
+            return None;
+        }
+        self.played += 1;
+        Some(self.frame[self.played - 1])
+    }
+}
+
+impl<S: Source<Item = f32>> Source for Reduced<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        match self.halved {
+            true => None,
+            false => self.source.current_frame_len(),
+        }
+    }
+
+    fn channels(&self) -> u16 {
+        self.source.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        match self.halved {
+            true => self.source.sample_rate() / 2,
+            false => self.source.sample_rate(),
+        }
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.source.total_duration()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rodio::buffer::SamplesBuffer;
+
+    /// The peak of a mono sine at `freq`, halved.
+    fn halved_peak(freq: f32) -> f32 {
+        let rate = 44100;
+        let samples = (0..rate)
+            .map(|i| (i as f32 / rate as f32 * freq * std::f32::consts::TAU).sin())
+            .collect::<Vec<_>>();
+        let source = SamplesBuffer::new(1, rate, samples);
+        let reduced = Reduced::new(source, ResampleQuality::Half);
+        assert_eq!(reduced.sample_rate(), rate / 2);
+
+        // Skip the start, where the filter is still filling up
+        reduced.skip(100).fold(0., |peak, s: f32| peak.max(s.abs()))
+    }
+
+    #[test]
+    fn halving_keeps_low_frequencies() {
+        assert!(halved_peak(441.) > 0.99);
+    }
+
+    #[test]
+    fn halving_filters_what_would_alias() {
+        // At 22050Hz, this would alias to 2050Hz at full strength
+        assert!(halved_peak(20000.) < 0.05);
+    }
+}
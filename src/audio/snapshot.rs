@@ -0,0 +1,133 @@
+use std::fmt::{self, Debug};
+use std::time::Duration;
+
+use serde::Serialize;
+
+use super::{OutputConfig, TrackStatus};
+
+/// How often the speaker adds to its history, when
+/// `MixerOptions::snapshot_history` is set.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) const SNAPSHOT_PERIOD: Duration = Duration::from_millis(100);
+
+/// Everything the mixer is doing at one moment, from `Mixer::debug_snapshot`
+/// or `Mixer::snapshot_history`. Printing it with `Display` gives a
+/// readable summary.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MixerSnapshot<K> {
+    /// `None` until the output stream is opened by `Mixer::init`.
+    pub output_config: Option<OutputConfig>,
+    pub sound_volume: f32,
+    pub track_volume: f32,
+    /// The volume of each key in the library's `AudioVolumes`, sorted by
+    /// the key's `Debug` output.
+    pub key_volumes: Vec<(K, f32)>,
+    /// Every slot with a track in it. The rest are empty.
+    pub tracks: Vec<TrackSnapshot<K>>,
+    /// How many sounds are still playing for each key, sorted like
+    /// `key_volumes`. Tones are counted under `None`.
+    pub live_sounds: Vec<(Option<K>, usize)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TrackSnapshot<K> {
+    pub index: usize,
+    pub key: K,
+    /// What the `Track` asks for.
+    pub playing: bool,
+    pub looping: bool,
+    pub volume: f32,
+    pub speed: f32,
+    /// What the slot is actually doing.
+    pub status: TrackStatus,
+    /// The volume it's heard at, after the track volume, the key's volume,
+    /// and any fade.
+    pub effective_volume: f32,
+    /// How far into the audio it is, in the track's own time. This goes
+    /// back to zero when a looping track loops.
+    pub position: Option<Duration>,
+}
+
+impl<K: Debug> MixerSnapshot<K> {
+    /// Sort the keys by their `Debug` output. The audio thread doesn't
+    /// need keys to be `Debug`, so this is done when the snapshot arrives.
+    pub(crate) fn sort_by_debug(&mut self) {
+        fn sort<T: Debug, V>(items: &mut Vec<(T, V)>) {
+            items.sort_by_cached_key(|(key, _)| format!("{:?}", key));
+        }
+
+        sort(&mut self.key_volumes);
+        sort(&mut self.live_sounds);
+    }
+}
+
+impl<K: Debug> fmt::Display for MixerSnapshot<K> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.output_config {
+            Some(config) => {
+                write!(
+                    f,
+                    "output: {} Hz, {} channels",
+                    config.sample_rate, config.channels
+                )?;
+                if let Some(buffer_size) = config.buffer_size {
+                    write!(f, ", {} frame buffer", buffer_size)?;
+                }
+            }
+            None => write!(f, "output: not open")?,
+        }
+
+        write!(
+            f,
+            "\nsound volume: {:.2}, track volume: {:.2}",
+            self.sound_volume, self.track_volume
+        )?;
+
+        if !self.key_volumes.is_empty() {
+            write!(f, "\nkey volumes:")?;
+            for (key, volume) in &self.key_volumes {
+                write!(f, "\n  {:?}: {:.2}", key, volume)?;
+            }
+        }
+
+        write!(f, "\ntracks:")?;
+        if self.tracks.is_empty() {
+            write!(f, " none")?;
+        }
+        for track in &self.tracks {
+            let requested = match (track.playing, track.looping) {
+                (true, true) => "playing, looping",
+                (true, false) => "playing",
+                (false, true) => "stopped, looping",
+                (false, false) => "stopped",
+            };
+            write!(
+                f,
+                "\n  [{}] {:?}: {:?} (asked for {}), volume {:.2} -> {:.2}, speed {:.2}",
+                track.index,
+                track.key,
+                track.status,
+                requested,
+                track.volume,
+                track.effective_volume,
+                track.speed
+            )?;
+            if let Some(position) = track.position {
+                write!(f, ", at {:.3}s", position.as_secs_f64())?;
+            }
+        }
+
+        write!(f, "\nlive sounds:")?;
+        if self.live_sounds.is_empty() {
+            write!(f, " none")?;
+        }
+        for (key, count) in &self.live_sounds {
+            match key {
+                Some(key) => write!(f, "\n  {:?}: {}", key, count)?,
+                None => write!(f, "\n  (tones): {}", count)?,
+            }
+        }
+
+        Ok(())
+    }
+}
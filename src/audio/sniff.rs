@@ -0,0 +1,77 @@
+use std::{convert::TryInto, time::Duration};
+
+/// Guess the container (and for Ogg, the codec) of some encoded audio from
+/// its first few bytes. This is only for reporting problems, so it's a guess
+/// rather than a full parse.
+pub(crate) fn sniff_container(bytes: &[u8]) -> &'static str {
+    if bytes.is_empty() {
+        return "empty";
+    }
+
+    let starts = |magic: &[u8]| bytes.starts_with(magic);
+    let at = |offset: usize, magic: &[u8]| bytes.get(offset..offset + magic.len()) == Some(magic);
+
+    if starts(b"OggS") {
+        // The first page holds the codec's identification header
+        let first_page = &bytes[..bytes.len().min(128)];
+        let contains = |magic: &[u8]| first_page.windows(magic.len()).any(|w| w == magic);
+
+        return if contains(b"OpusHead") {
+            "ogg/opus"
+        } else if contains(b"\x01vorbis") {
+            "ogg/vorbis"
+        } else if contains(b"FLAC") {
+            "ogg/flac"
+        } else {
+            "ogg"
+        };
+    }
+
+    match bytes {
+        _ if starts(b"RIFF") && at(8, b"WAVE") => "wav",
+        _ if starts(b"FORM") && (at(8, b"AIFF") || at(8, b"AIFC")) => "aiff",
+        _ if starts(b"fLaC") => "flac",
+        _ if starts(b"caff") => "caf",
+        _ if at(4, b"ftyp") => "mp4",
+        _ if starts(b"\x1a\x45\xdf\xa3") => "webm",
+        _ if starts(b"ID3") => "mp3",
+        // Both ADTS and MP3 frames start with a sync word, but only ADTS
+        // has the layer bits set to zero
+        [0xff, second, ..] if second & 0xf6 == 0xf0 => "aac",
+        [0xff, second, ..] if second & 0xe0 == 0xe0 => "mp3",
+        _ => "unknown",
+    }
+}
+
+/// The length of Ogg Vorbis or Opus audio, from the granule position of its
+/// last page. Unlike asking the decoder, this doesn't decode anything.
+pub(crate) fn ogg_duration(bytes: &[u8]) -> Option<Duration> {
+    if !bytes.starts_with(b"OggS") {
+        return None;
+    }
+
+    let first_page = &bytes[..bytes.len().min(128)];
+    let find =
+        |haystack: &[u8], magic: &[u8]| haystack.windows(magic.len()).position(|w| w == magic);
+
+    let sample_rate = if find(first_page, b"OpusHead").is_some() {
+        // Opus granule positions are always at 48kHz
+        48000
+    } else {
+        let header = find(first_page, b"\x01vorbis")?;
+        let rate = first_page.get(header + 12..header + 16)?;
+        u32::from_le_bytes(rate.try_into().ok()?)
+    };
+
+    // Pages are at most ~64KB, so the last one starts within that of the end
+    let tail_start = bytes.len().saturating_sub(65536);
+    let last_page = tail_start + bytes[tail_start..].windows(4).rposition(|w| w == b"OggS")?;
+    let granule = bytes.get(last_page + 6..last_page + 14)?;
+    let samples = u64::from_le_bytes(granule.try_into().ok()?);
+
+    if sample_rate == 0 || samples == u64::MAX {
+        return None;
+    }
+
+    Some(Duration::from_secs_f64(samples as f64 / sample_rate as f64))
+}
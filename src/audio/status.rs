@@ -0,0 +1,137 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use rodio::{Sample, Source};
+use serde::Serialize;
+
+use super::MixerEvent;
+
+/// What a track slot is actually doing, as opposed to what its `Track` asks
+/// for.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum TrackStatus {
+    Playing,
+    Paused,
+    /// A non-looping track played to the end. It stays finished until the
+    /// track is replaced or removed.
+    Finished,
+    /// There's no track in the slot (or the mixer isn't initialized).
+    #[default]
+    Empty,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct SlotStatus {
+    status: TrackStatus,
+    /// Counts the sinks created for the slot, so a source from an old sink
+    /// can't mark a new one as finished.
+    generation: u64,
+    finished_generation: Option<u64>,
+}
+
+impl SlotStatus {
+    pub fn status(&self) -> TrackStatus {
+        self.status
+    }
+
+    /// Start tracking a new sink, returning its generation.
+    pub fn restart(&mut self) -> u64 {
+        self.generation += 1;
+        self.generation
+    }
+
+    /// Update the status from the speaker's side, returning whether it
+    /// changed. A finished track stays finished until it's restarted or
+    /// removed.
+    pub fn set(&mut self, status: TrackStatus) -> bool {
+        let finished = self.finished_generation == Some(self.generation);
+        if self.status == status || (finished && status != TrackStatus::Empty) {
+            return false;
+        }
+
+        self.status = status;
+        true
+    }
+
+    fn finish(&mut self, generation: u64) -> bool {
+        if generation != self.generation || self.status == TrackStatus::Empty {
+            return false;
+        }
+
+        self.status = TrackStatus::Finished;
+        self.finished_generation = Some(generation);
+        true
+    }
+}
+
+/// Marks a slot as finished when its source runs out, unless it's looping.
+pub(crate) struct Finishing<S> {
+    source: S,
+    slot: Option<(Arc<Mutex<SlotStatus>>, u64)>,
+    events_buffer: Arc<Mutex<Vec<MixerEvent>>>,
+    index: usize,
+}
+
+impl<S> Finishing<S> {
+    /// `generation` is `None` for looping tracks, which never finish.
+    pub fn new(
+        source: S,
+        slot: &Arc<Mutex<SlotStatus>>,
+        generation: Option<u64>,
+        events_buffer: &Arc<Mutex<Vec<MixerEvent>>>,
+        index: usize,
+    ) -> Self {
+        Finishing {
+            source,
+            slot: generation.map(|generation| (Arc::clone(slot), generation)),
+            events_buffer: Arc::clone(events_buffer),
+            index,
+        }
+    }
+}
+
+impl<S: Source> Iterator for Finishing<S>
+where
+    S::Item: Sample,
+{
+    type Item = S::Item;
+
+    fn next(&mut self) -> Option<S::Item> {
+        let sample = self.source.next();
+
+        if sample.is_none() {
+            if let Some((slot, generation)) = self.slot.take() {
+                if slot.lock().unwrap().finish(generation) {
+                    let mut buffer = self.events_buffer.lock().unwrap();
+                    buffer.push(MixerEvent::TrackStatusChanged {
+                        index: self.index,
+                        status: TrackStatus::Finished,
+                    });
+                }
+            }
+        }
+
+        sample
+    }
+}
+
+impl<S: Source> Source for Finishing<S>
+where
+    S::Item: Sample,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        self.source.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.source.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.source.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.source.total_duration()
+    }
+}
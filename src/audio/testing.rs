@@ -0,0 +1,529 @@
+//! Render mixer commands offline, without an audio device.
+
+use std::fmt::Debug;
+use std::hash::Hash;
+use std::time::Duration;
+
+use super::{
+    backend::CaptureBackend, AudioCmd, AudioLibrary, AudioState, AudioVolumes, FadeCurve,
+    MixerOptions, OutputConfig, Sound, Speaker, StateUpdate, Tone, Track,
+};
+
+pub const SAMPLE_RATE: u32 = 44100;
+
+/// Output is interleaved stereo.
+pub const CHANNELS: u16 = 2;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MixerCommand<K: Clone + Eq + Hash> {
+    State {
+        sound_volume: f32,
+        track_volume: f32,
+        tracks: Vec<Track<K>>,
+    },
+    PlaySound(Sound<K>),
+    PlaySoundAfter(Sound<K>, Duration),
+    PlayTone(Tone),
+    UpdateLibrary(AudioLibrary<K>, bool),
+    UpdateVolumes(AudioVolumes<K>),
+    Fade {
+        index: usize,
+        level: f32,
+        duration: Duration,
+        curve: Option<FadeCurve>,
+    },
+    /// Render this much audio before processing the next command.
+    Wait(Duration),
+    /// Simulate the output device changing to this config, as if
+    /// `Mixer::reopen_output` was called. Output is still rendered at
+    /// `SAMPLE_RATE` with `CHANNELS`.
+    SwitchOutput(OutputConfig),
+}
+
+/// The index into the rendered samples of the first sample at time `t`.
+pub fn sample_index(t: Duration) -> usize {
+    (t.as_secs_f64() * SAMPLE_RATE as f64).round() as usize * CHANNELS as usize
+}
+
+/// Run the commands through a mixer with the given library, and return the
+/// first `duration` of its output.
+pub fn render_commands<K>(
+    library: AudioLibrary<K>,
+    commands: Vec<MixerCommand<K>>,
+    duration: Duration,
+) -> Vec<f32>
+where
+    K: 'static + Clone + Send + Eq + Hash,
+{
+    render_commands_with(library, commands, duration, MixerOptions::default())
+}
+
+/// Like `render_commands`, but with a mixer made with `options`, e.g. to
+/// hear the effect of a `Limiter`. The output's format is unaffected.
+pub fn render_commands_with<K>(
+    library: AudioLibrary<K>,
+    commands: Vec<MixerCommand<K>>,
+    duration: Duration,
+    options: MixerOptions,
+) -> Vec<f32>
+where
+    K: 'static + Clone + Send + Eq + Hash,
+{
+    use rodio::source::UniformSourceIterator;
+    use std::sync::{Arc, Mutex};
+
+    let capture =
+        |config: OutputConfig| {
+            let (controller, mixer) =
+                rodio::dynamic_mixer::mixer::<f32>(config.channels, config.sample_rate);
+            let mixer: Box<dyn Iterator<Item = f32>> = Box::new(
+                UniformSourceIterator::<_, f32>::new(mixer, CHANNELS, SAMPLE_RATE),
+            );
+            (Box::new(CaptureBackend::new(controller)), mixer)
+        };
+
+    let default_config = OutputConfig {
+        sample_rate: SAMPLE_RATE,
+        channels: CHANNELS,
+        buffer_size: None,
+    };
+    let (backend, mut mixer) = capture(default_config);
+
+    #[cfg(not(target_arch = "wasm32"))]
+    let (_, receiver) = std::sync::mpsc::channel();
+
+    let mut speaker = Speaker::new(
+        #[cfg(not(target_arch = "wasm32"))]
+        receiver,
+        library,
+        AudioVolumes::new(),
+        options,
+        Arc::new(Mutex::new(Vec::new())),
+        Arc::new(Mutex::new(Vec::new())),
+        (0..super::MAX_TRACKS)
+            .map(|_| Arc::new(Mutex::new(Default::default())))
+            .collect(),
+        (0..super::MAX_TRACKS)
+            .map(|_| Arc::new(Mutex::new(Default::default())))
+            .collect(),
+        None,
+        None,
+    );
+    speaker.set_backend(backend, Some(default_config));
+
+    let end = sample_index(duration);
+    let mut output = Vec::with_capacity(end);
+    let render_until =
+        |mixer: &mut Box<dyn Iterator<Item = f32>>, output: &mut Vec<f32>, until: usize| {
+            while output.len() < until {
+                output.push(mixer.next().unwrap_or(0.));
+            }
+        };
+
+    let mut elapsed = Duration::from_secs(0);
+    for command in commands {
+        let cmd = match command {
+            MixerCommand::Wait(wait) => {
+                elapsed += wait;
+                render_until(&mut mixer, &mut output, sample_index(elapsed).min(end));
+                continue;
+            }
+            MixerCommand::SwitchOutput(config) => {
+                let (backend, new_mixer) = capture(config);
+                mixer = new_mixer;
+                speaker.set_backend(backend, Some(config));
+                continue;
+            }
+            MixerCommand::State {
+                sound_volume,
+                track_volume,
+                tracks,
+            } => AudioCmd::State(StateUpdate::new(AudioState {
+                sound_volume,
+                track_volume,
+                tracks: &tracks,
+            })),
+            MixerCommand::PlaySound(sound) => AudioCmd::PlaySound(sound),
+            MixerCommand::PlaySoundAfter(sound, delay) => AudioCmd::PlaySoundAfter {
+                sound,
+                delay,
+                cancelled: Default::default(),
+            },
+            MixerCommand::PlayTone(tone) => AudioCmd::PlayTone(tone),
+            MixerCommand::UpdateLibrary(library, restart) => {
+                AudioCmd::UpdateLibrary(library, restart)
+            }
+            MixerCommand::UpdateVolumes(volumes) => AudioCmd::UpdateVolumes(volumes),
+            MixerCommand::Fade {
+                index,
+                level,
+                duration,
+                curve,
+            } => AudioCmd::Fade {
+                index,
+                level,
+                duration,
+                curve,
+            },
+        };
+        speaker.process(cmd);
+    }
+
+    render_until(&mut mixer, &mut output, end);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::{Adsr, ChannelPolicy, Limiter, MonoPlacement, Wave};
+
+    const BEEP: &str = "beep";
+
+    /// A square wave at a constant level, so its peak is its volume.
+    fn square(volume: f32) -> Tone {
+        Tone {
+            wave: Wave::Square,
+            ..sine(volume)
+        }
+    }
+
+    /// A sine wave at a constant level.
+    fn sine(volume: f32) -> Tone {
+        Tone {
+            wave: Wave::Sine,
+            freq: 441.,
+            duration: Duration::from_secs(1),
+            envelope: Adsr {
+                attack: Duration::from_secs(0),
+                decay: Duration::from_secs(0),
+                sustain: 1.,
+                release: Duration::from_secs(0),
+            },
+            volume,
+            ..Default::default()
+        }
+    }
+
+    fn library() -> AudioLibrary<&'static str> {
+        let mut library = AudioLibrary::new();
+        library.insert(BEEP, square(0.5).to_audio_bytes());
+        library
+    }
+
+    fn beep() -> Sound<&'static str> {
+        Sound::new(BEEP)
+    }
+
+    fn peak(samples: &[f32]) -> f32 {
+        samples.iter().fold(0., |peak, s| s.abs().max(peak))
+    }
+
+    #[test]
+    fn renders_silence_without_commands() {
+        let duration = Duration::from_millis(250);
+        let output = render_commands(library(), vec![], duration);
+        assert_eq!(output.len(), sample_index(duration));
+        assert_eq!(peak(&output), 0.);
+    }
+
+    #[test]
+    fn plays_sounds_from_the_library() {
+        let commands = vec![MixerCommand::PlaySound(beep())];
+        let output = render_commands(library(), commands, Duration::from_millis(250));
+        assert!((peak(&output) - 0.5).abs() < 0.01, "peak {}", peak(&output));
+    }
+
+    #[test]
+    fn waits_before_later_commands() {
+        let wait = Duration::from_millis(100);
+        let commands = vec![MixerCommand::Wait(wait), MixerCommand::PlaySound(beep())];
+        let output = render_commands(library(), commands, Duration::from_millis(250));
+        let (before, after) = output.split_at(sample_index(wait));
+        assert_eq!(peak(before), 0.);
+        assert!(peak(after) > 0.4);
+    }
+
+    #[test]
+    fn delays_sounds() {
+        let delay = Duration::from_millis(100);
+        let commands = vec![MixerCommand::PlaySoundAfter(beep(), delay)];
+        let output = render_commands(library(), commands, Duration::from_millis(250));
+        let (before, after) = output.split_at(sample_index(delay));
+        assert_eq!(peak(before), 0.);
+        assert!(peak(after) > 0.4);
+    }
+
+    #[test]
+    fn sound_volume_scales_sounds() {
+        let commands = vec![
+            MixerCommand::State {
+                sound_volume: 0.5,
+                track_volume: 1.,
+                tracks: vec![],
+            },
+            MixerCommand::PlaySound(beep()),
+        ];
+        let output = render_commands(library(), commands, Duration::from_millis(250));
+        assert!(
+            (peak(&output) - 0.25).abs() < 0.01,
+            "peak {}",
+            peak(&output)
+        );
+    }
+
+    /// The peak before `change` and the peak from a little after it, once
+    /// the new level has been picked up.
+    fn peaks_around(output: &[f32], change: Duration) -> (f32, f32) {
+        let settled = change + Duration::from_millis(20);
+        (
+            peak(&output[..sample_index(change)]),
+            peak(&output[sample_index(settled)..]),
+        )
+    }
+
+    #[test]
+    fn volume_changes_reach_playing_sounds() {
+        let change = Duration::from_millis(100);
+        let mut volumes = AudioVolumes::new();
+        volumes.insert(BEEP, 0.5);
+        let commands = vec![
+            MixerCommand::PlaySound(beep()),
+            MixerCommand::Wait(change),
+            MixerCommand::UpdateVolumes(volumes),
+        ];
+        let output = render_commands(library(), commands, Duration::from_millis(250));
+        let (before, after) = peaks_around(&output, change);
+        assert!((before - 0.5).abs() < 0.01, "peak before {}", before);
+        assert!((after - 0.25).abs() < 0.01, "peak after {}", after);
+    }
+
+    #[test]
+    fn sound_volume_changes_reach_playing_sounds() {
+        let change = Duration::from_millis(100);
+        let commands = vec![
+            MixerCommand::PlaySound(Sound {
+                volume: 0.5,
+                ..beep()
+            }),
+            MixerCommand::Wait(change),
+            MixerCommand::State {
+                sound_volume: 0.5,
+                track_volume: 1.,
+                tracks: vec![],
+            },
+        ];
+        let output = render_commands(library(), commands, Duration::from_millis(250));
+        let (before, after) = peaks_around(&output, change);
+        assert!((before - 0.25).abs() < 0.01, "peak before {}", before);
+        assert!((after - 0.125).abs() < 0.01, "peak after {}", after);
+    }
+
+    #[test]
+    fn volume_changes_reach_playing_tracks() {
+        let change = Duration::from_millis(100);
+        let tracks = vec![Track::new(BEEP)];
+        let mut volumes = AudioVolumes::new();
+        volumes.insert(BEEP, 0.5);
+        let commands = vec![
+            MixerCommand::State {
+                sound_volume: 1.,
+                track_volume: 1.,
+                tracks: tracks.clone(),
+            },
+            MixerCommand::Wait(change),
+            MixerCommand::UpdateVolumes(volumes),
+            MixerCommand::State {
+                sound_volume: 1.,
+                track_volume: 0.5,
+                tracks,
+            },
+        ];
+        let output = render_commands(library(), commands, Duration::from_millis(250));
+        let (before, after) = peaks_around(&output, change);
+        assert!((before - 0.5).abs() < 0.01, "peak before {}", before);
+        assert!((after - 0.125).abs() < 0.01, "peak after {}", after);
+    }
+
+    /// Tones are mono, so each should come out the same on both channels.
+    fn assert_mono_upmixes_evenly(mono: MonoPlacement) {
+        let options = MixerOptions {
+            channel_policy: ChannelPolicy::Remap { mono },
+            ..Default::default()
+        };
+        let commands = vec![MixerCommand::PlaySound(beep())];
+        let output = render_commands_with(library(), commands, Duration::from_millis(250), options);
+        assert!(peak(&output) > 0.4, "peak {}", peak(&output));
+        for (i, frame) in output.chunks(CHANNELS as usize).enumerate() {
+            assert_eq!(frame[0], frame[1], "frame {}", i);
+        }
+    }
+
+    #[test]
+    fn mono_sounds_upmix_to_both_front_channels() {
+        assert_mono_upmixes_evenly(MonoPlacement::FrontPair);
+    }
+
+    #[test]
+    fn mono_sounds_upmix_to_both_channels_without_a_center() {
+        assert_mono_upmixes_evenly(MonoPlacement::Center);
+    }
+
+    #[test]
+    fn key_volumes_multiply_with_track_volumes() {
+        let mut volumes = AudioVolumes::new();
+        volumes.insert(BEEP, 0.5);
+        let commands = vec![
+            MixerCommand::UpdateVolumes(volumes),
+            MixerCommand::State {
+                sound_volume: 1.,
+                track_volume: 1.,
+                tracks: vec![Track {
+                    volume: 0.5,
+                    ..Track::new(BEEP)
+                }],
+            },
+        ];
+        let output = render_commands(library(), commands, Duration::from_millis(250));
+
+        // The beep peaks at 0.5 by itself
+        assert!(
+            (peak(&output) - 0.125).abs() < 0.01,
+            "peak {}",
+            peak(&output)
+        );
+    }
+
+    #[test]
+    fn tracks_are_silent_after_fading_out() {
+        let fade = Duration::from_secs(1);
+        let commands = vec![
+            MixerCommand::State {
+                sound_volume: 1.,
+                track_volume: 1.,
+                tracks: vec![Track::new(BEEP)],
+            },
+            MixerCommand::Fade {
+                index: 0,
+                level: 0.,
+                duration: fade,
+                curve: None,
+            },
+        ];
+        let output = render_commands(library(), commands, Duration::from_millis(1500));
+
+        // The fade is linear by default, so it's at half volume halfway
+        let halfway = sample_index(fade / 2);
+        let around_halfway = &output[halfway - 1000..halfway + 1000];
+        assert!(
+            (peak(around_halfway) - 0.25).abs() < 0.02,
+            "peak halfway {}",
+            peak(around_halfway)
+        );
+
+        let settled = fade + Duration::from_millis(20);
+        assert_eq!(peak(&output[sample_index(settled)..]), 0.);
+    }
+
+    /// Two full-scale sines in phase, which add up to twice full scale.
+    fn render_stacked_sines(options: MixerOptions) -> Vec<f32> {
+        let commands = vec![
+            MixerCommand::PlayTone(sine(1.)),
+            MixerCommand::PlayTone(sine(1.)),
+        ];
+        render_commands_with(library(), commands, Duration::from_millis(250), options)
+    }
+
+    #[test]
+    fn stacked_sounds_clip_without_a_limiter() {
+        let output = render_stacked_sines(MixerOptions::default());
+        assert!(peak(&output) > 1.5, "peak {}", peak(&output));
+    }
+
+    #[test]
+    fn limiter_keeps_stacked_sounds_under_full_scale() {
+        let options = MixerOptions {
+            limiter: Some(Limiter::default()),
+            ..Default::default()
+        };
+        let output = render_stacked_sines(options);
+        assert!(peak(&output) < 1., "peak {}", peak(&output));
+        assert!(peak(&output) > 0.9, "peak {}", peak(&output));
+    }
+
+    /// How many times the left channel crosses zero, which is twice the
+    /// frequency of a tone over one second.
+    fn zero_crossings(samples: &[f32]) -> usize {
+        let left = samples.iter().step_by(CHANNELS as usize).map(|&s| s > 0.);
+        left.clone()
+            .zip(left.skip(1))
+            .filter(|(a, b)| a != b)
+            .count()
+    }
+
+    #[test]
+    fn switching_outputs_rebuilds_tracks_and_drops_sounds() {
+        const BLIP: &str = "blip";
+
+        let mut library = library();
+        let blip = Tone {
+            freq: 1323.,
+            ..square(0.25)
+        };
+        library.insert(BLIP, blip.to_audio_bytes());
+
+        let configs = [(22050, 1), (48000, 2), (11025, 1), (32000, 6)];
+        let before = Duration::from_millis(100);
+        let after = Duration::from_millis(150);
+
+        let mut commands = vec![MixerCommand::State {
+            sound_volume: 1.,
+            track_volume: 1.,
+            tracks: vec![Track::new(BEEP)],
+        }];
+        for &(sample_rate, channels) in &configs {
+            commands.push(MixerCommand::PlaySound(Sound::new(BLIP)));
+            commands.push(MixerCommand::Wait(before));
+            commands.push(MixerCommand::SwitchOutput(OutputConfig {
+                sample_rate,
+                channels,
+                buffer_size: None,
+            }));
+            commands.push(MixerCommand::Wait(after));
+        }
+
+        let duration = (before + after) * configs.len() as u32;
+        let output = render_commands(library, commands, duration);
+        assert_eq!(output.len(), sample_index(duration));
+
+        for (i, config) in configs.iter().enumerate() {
+            let start = (before + after) * i as u32;
+            let switch = start + before;
+            let settled = switch + Duration::from_millis(20);
+            let end = switch + after;
+
+            // The blip started before the switch is dropped by it, leaving
+            // just the track, which is rebuilt at its own pitch
+            let with_blip = peak(&output[sample_index(start)..sample_index(switch)]);
+            assert!(with_blip > 0.6, "{:?}: peak before {}", config, with_blip);
+
+            let rest = &output[sample_index(settled)..sample_index(end)];
+            assert!(
+                (peak(rest) - 0.5).abs() < 0.05,
+                "{:?}: peak after {}",
+                config,
+                peak(rest)
+            );
+
+            let expected = 441. * 2. * (end - settled).as_secs_f32();
+            let crossings = zero_crossings(rest) as f32;
+            assert!(
+                (crossings - expected).abs() < expected * 0.05,
+                "{:?}: {} zero crossings, expected {}",
+                config,
+                crossings,
+                expected
+            );
+        }
+    }
+}
@@ -0,0 +1,222 @@
+//! Simple synthesized sound effects, for when there are no real ones yet.
+
+use std::{borrow::Cow, time::Duration};
+
+use rodio::Source;
+
+use super::AudioBytes;
+
+pub const TONE_SAMPLE_RATE: u32 = 44100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Wave {
+    Square,
+    Saw,
+    Sine,
+    /// White noise, resampled at the tone's frequency.
+    Noise,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Adsr {
+    pub attack: Duration,
+    pub decay: Duration,
+    /// The level held between the decay and release.
+    pub sustain: f32,
+    /// Taken from the end of the tone's duration.
+    pub release: Duration,
+}
+
+impl Default for Adsr {
+    fn default() -> Self {
+        Adsr {
+            attack: Duration::from_millis(5),
+            decay: Duration::from_millis(50),
+            sustain: 0.6,
+            release: Duration::from_millis(50),
+        }
+    }
+}
+
+impl Adsr {
+    fn level(&self, t: f32, duration: f32) -> f32 {
+        let attack = self.attack.as_secs_f32();
+        let decay = self.decay.as_secs_f32();
+        let release = self.release.as_secs_f32();
+        let release_start = (duration - release).max(0.);
+
+        let level = if t < attack {
+            t / attack
+        } else if t < attack + decay {
+            1. - (1. - self.sustain) * (t - attack) / decay
+        } else {
+            self.sustain
+        };
+
+        match t >= release_start && release > 0. {
+            true => level * (1. - (t - release_start) / release).max(0.),
+            false => level,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tone {
+    pub wave: Wave,
+    pub freq: f32,
+    pub duration: Duration,
+    pub envelope: Adsr,
+    /// Change in frequency, in Hz per second.
+    pub sweep: f32,
+    pub volume: f32,
+    /// Seeds the `Noise` wave, so the same tone always sounds the same.
+    pub seed: u32,
+}
+
+impl Default for Tone {
+    fn default() -> Self {
+        Tone {
+            wave: Wave::Square,
+            freq: 440.,
+            duration: Duration::from_millis(200),
+            envelope: Adsr::default(),
+            sweep: 0.,
+            volume: 0.5,
+            seed: 1,
+        }
+    }
+}
+
+impl Tone {
+    pub fn source(&self) -> ToneSource {
+        ToneSource::new(*self)
+    }
+
+    pub fn render(&self) -> Vec<f32> {
+        self.source().collect()
+    }
+
+    /// Render to an in-memory WAV, to be added to an `AudioLibrary`.
+    pub fn to_audio_bytes(&self) -> AudioBytes {
+        let samples = self.render();
+        let data_len = samples.len() as u32 * 2;
+
+        let mut wav = Vec::with_capacity(44 + data_len as usize);
+        wav.extend_from_slice(b"RIFF");
+        wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+        wav.extend_from_slice(b"WAVEfmt ");
+        wav.extend_from_slice(&16_u32.to_le_bytes());
+        wav.extend_from_slice(&1_u16.to_le_bytes()); // PCM
+        wav.extend_from_slice(&1_u16.to_le_bytes()); // Mono
+        wav.extend_from_slice(&TONE_SAMPLE_RATE.to_le_bytes());
+        wav.extend_from_slice(&(TONE_SAMPLE_RATE * 2).to_le_bytes());
+        wav.extend_from_slice(&2_u16.to_le_bytes());
+        wav.extend_from_slice(&16_u16.to_le_bytes());
+        wav.extend_from_slice(b"data");
+        wav.extend_from_slice(&data_len.to_le_bytes());
+
+        for sample in samples {
+            let sample = (sample.clamp(-1., 1.) * i16::MAX as f32) as i16;
+            wav.extend_from_slice(&sample.to_le_bytes());
+        }
+
+        AudioBytes::new(Cow::Owned(wav))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ToneSource {
+    tone: Tone,
+    index: u32,
+    len: u32,
+    phase: f32,
+    noise_state: u32,
+    noise_sample: f32,
+}
+
+impl ToneSource {
+    pub fn new(tone: Tone) -> Self {
+        let len = (tone.duration.as_secs_f64() * TONE_SAMPLE_RATE as f64) as u32;
+
+        // Xorshift gets stuck on zero
+        let noise_state = tone.seed.max(1);
+
+        let mut source = ToneSource {
+            tone,
+            index: 0,
+            len,
+            phase: 0.,
+            noise_state,
+            noise_sample: 0.,
+        };
+        source.noise_sample = source.next_noise();
+        source
+    }
+
+    fn next_noise(&mut self) -> f32 {
+        let mut x = self.noise_state;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.noise_state = x;
+        (x as f32 / u32::MAX as f32) * 2. - 1.
+    }
+}
+
+impl Iterator for ToneSource {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.index >= self.len {
+            return None;
+        }
+
+        let tone = &self.tone;
+        let t = self.index as f32 / TONE_SAMPLE_RATE as f32;
+        let duration = tone.duration.as_secs_f32();
+        let freq = (tone.freq + tone.sweep * t).max(1.);
+
+        let wave = match tone.wave {
+            Wave::Square if self.phase < 0.5 => 1.,
+            Wave::Square => -1.,
+            Wave::Saw => self.phase * 2. - 1.,
+            Wave::Sine => (self.phase * std::f32::consts::TAU).sin(),
+            Wave::Noise => self.noise_sample,
+        };
+        let sample = wave * tone.envelope.level(t, duration) * tone.volume;
+
+        self.phase += freq / TONE_SAMPLE_RATE as f32;
+        if self.phase >= 1. {
+            self.phase -= 1.;
+            if tone.wave == Wave::Noise {
+                self.noise_sample = self.next_noise();
+            }
+        }
+
+        self.index += 1;
+        Some(sample)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = (self.len - self.index) as usize;
+        (remaining, Some(remaining))
+    }
+}
+
+impl Source for ToneSource {
+    fn current_frame_len(&self) -> Option<usize> {
+        Some((self.len - self.index) as usize)
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        TONE_SAMPLE_RATE
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        Some(self.tone.duration)
+    }
+}
@@ -0,0 +1,47 @@
+/// Details of the build, as set by `jamjar package`. See `build_info!`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BuildInfo {
+    pub version: &'static str,
+    pub git_hash: &'static str,
+    pub dirty: bool,
+    /// Seconds since the Unix epoch.
+    pub timestamp: &'static str,
+    pub profile: &'static str,
+}
+
+impl std::fmt::Display for BuildInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "v{} ({}", self.version, self.git_hash)?;
+        if self.dirty {
+            write!(f, "-dirty")?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// Get the `BuildInfo` for the calling crate. Outside of `jamjar package`,
+/// the version comes from Cargo.toml and everything else is `"unknown"`.
+#[macro_export]
+macro_rules! build_info {
+    () => {
+        $crate::build_info::BuildInfo {
+            version: match option_env!("JAMJAR_BUILD_VERSION") {
+                Some(version) => version,
+                None => env!("CARGO_PKG_VERSION"),
+            },
+            git_hash: match option_env!("JAMJAR_BUILD_GIT_HASH") {
+                Some(hash) => hash,
+                None => "unknown",
+            },
+            dirty: matches!(option_env!("JAMJAR_BUILD_DIRTY"), Some("true")),
+            timestamp: match option_env!("JAMJAR_BUILD_TIMESTAMP") {
+                Some(timestamp) => timestamp,
+                None => "unknown",
+            },
+            profile: match option_env!("JAMJAR_BUILD_PROFILE") {
+                Some(profile) => profile,
+                None => "unknown",
+            },
+        }
+    };
+}
@@ -54,6 +54,91 @@ pub fn create_files_enums<'a, I: IntoIterator<Item = &'a SrcModuleType<'a>>>(
     Ok(())
 }
 
+/// Why `check_files_keys` or `verify_files_keys` failed.
+#[derive(Debug)]
+pub enum FilesKeysError {
+    /// The directory couldn't be read.
+    Io {
+        dir: String,
+        error: std::io::Error,
+    },
+    Collision(crate::resource_keys::KeyCollision),
+    Mismatches(crate::resource_keys::KeyMismatches),
+}
+
+impl std::fmt::Display for FilesKeysError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            FilesKeysError::Io { dir, error } => write!(f, "Could not read {}: {}", dir, error),
+            FilesKeysError::Collision(collision) => collision.fmt(f),
+            FilesKeysError::Mismatches(mismatches) => mismatches.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for FilesKeysError {}
+
+/// The names of the files directly inside `dir`, in sorted order.
+fn file_names(dir: &str) -> Result<Vec<String>, FilesKeysError> {
+    let io_error = |error| FilesKeysError::Io {
+        dir: dir.to_owned(),
+        error,
+    };
+
+    let mut names = vec![];
+    for entry in std::fs::read_dir(dir).map_err(io_error)? {
+        let entry = entry.map_err(io_error)?;
+        if entry.path().is_file() {
+            names.push(entry.file_name().to_string_lossy().into_owned());
+        }
+    }
+    names.sort();
+    Ok(names)
+}
+
+/// Fail the build if, in `Strict` mode, two files in any of the directories
+/// have names that normalize to the same key. Call this alongside
+/// `create_files_enums`, with the mode passed to `map_resources_with` and
+/// friends.
+pub fn check_files_keys<'a, I: IntoIterator<Item = &'a SrcModuleType<'a>>>(
+    mode: crate::resource_keys::KeyMode,
+    dir_mod_types: I,
+) -> Result<(), FilesKeysError> {
+    for &(dir, _, _) in dir_mod_types {
+        let stems = file_names(dir)?.into_iter().map(|name| {
+            std::path::Path::new(&name)
+                .file_stem()
+                .unwrap()
+                .to_string_lossy()
+                .into_owned()
+        });
+
+        crate::resource_keys::check_keys(mode, stems).map_err(FilesKeysError::Collision)?;
+    }
+
+    Ok(())
+}
+
+/// Fail the build unless every key is paired with exactly one file in
+/// `dir`, and every file has a key or matches an `ignore` pattern. The
+/// pairing is the same as `map_resources_with` and friends in `mode`.
+///
+/// The keys are usually the ones code refers to, e.g. listed in a data
+/// file, so a renamed file is caught before it's missing at runtime.
+pub fn verify_files_keys<K: AsRef<str>, P: AsRef<str>>(
+    mode: crate::resource_keys::KeyMode,
+    dir: &str,
+    keys: &[K],
+    ignore: &[P],
+) -> Result<(), FilesKeysError> {
+    if is_build_script() {
+        println!("cargo:rerun-if-changed={}", dir);
+    }
+
+    crate::resource_keys::verify_keys(mode, keys, &file_names(dir)?, ignore)
+        .map_err(FilesKeysError::Mismatches)
+}
+
 pub fn create_data_enums<'a, I: IntoIterator<Item = &'a SrcModuleType<'a>>>(
     file_mod_types: I,
 ) -> Result<(), edres::Error> {
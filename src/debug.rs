@@ -0,0 +1,284 @@
+use std::fmt::{self, Write};
+
+use winit::event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent};
+
+use crate::{
+    draw::{
+        groove::{DrawStats, Renderer},
+        shapes::gradient_rect,
+        text::TextPainter,
+        Region, Space,
+    },
+    font::Font,
+    gfx::SupportedBackend,
+};
+
+/// How many frame times the graph shows.
+const FRAME_HISTORY: usize = 120;
+
+const BAR_WIDTH: f32 = 2.;
+const GRAPH_HEIGHT: f32 = 32.;
+const PADDING: f32 = 4.;
+
+/// Something that adds lines to an `Overlay`, like the game's own state.
+/// Each line written is shown on its own line of the panel.
+///
+/// Closures taking a `&mut dyn fmt::Write` work too:
+///
+/// ```ignore
+/// let enemies = |out: &mut dyn std::fmt::Write| writeln!(out, "enemies: {}", state.enemies.len());
+/// overlay.draw(&mut renderer, &mut text_painter, &font, white, &[&stats, &enemies]);
+/// ```
+pub trait OverlayLines {
+    fn write_lines(&self, out: &mut dyn Write) -> fmt::Result;
+}
+
+impl<F> OverlayLines for F
+where
+    F: Fn(&mut dyn Write) -> fmt::Result,
+{
+    fn write_lines(&self, out: &mut dyn Write) -> fmt::Result {
+        self(out)
+    }
+}
+
+impl OverlayLines for DrawStats {
+    fn write_lines(&self, out: &mut dyn Write) -> fmt::Result {
+        writeln!(
+            out,
+            "draw: {} sprites, {} batches, {} page switches",
+            self.sprites, self.batches, self.page_switches
+        )
+    }
+}
+
+#[cfg(feature = "audio")]
+impl<K: fmt::Debug> OverlayLines for crate::audio::MixerSnapshot<K> {
+    fn write_lines(&self, out: &mut dyn Write) -> fmt::Result {
+        use crate::audio::TrackStatus;
+
+        let live_sounds = self
+            .live_sounds
+            .iter()
+            .map(|(_, count)| count)
+            .sum::<usize>();
+        writeln!(
+            out,
+            "audio: sound {:.2}, track {:.2}, {} live sounds",
+            self.sound_volume, self.track_volume, live_sounds
+        )?;
+
+        for track in &self.tracks {
+            if track.status == TrackStatus::Playing {
+                writeln!(
+                    out,
+                    "  [{}] {:?}: {:.2}",
+                    track.index, track.key, track.effective_volume
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The files that are failing to load, from `reloading::current_errors`.
+#[cfg(feature = "reloading")]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ReloadErrors;
+
+#[cfg(feature = "reloading")]
+impl OverlayLines for ReloadErrors {
+    fn write_lines(&self, out: &mut dyn Write) -> fmt::Result {
+        for error in crate::reloading::current_errors() {
+            let first_line = error.message.lines().next().unwrap_or("");
+            writeln!(out, "{}: {}", error.file, first_line)?;
+        }
+        Ok(())
+    }
+}
+
+/// A panel in the corner of the screen with a graph of recent frame times
+/// and lines of stats from each `OverlayLines`. It's hidden until
+/// `toggle_key` is pressed.
+///
+/// While it's hidden, recording frames and calling `draw` cost next to
+/// nothing, so they can be left in release builds. Gathering the stats
+/// (like `Mixer::debug_snapshot`) is up to the caller, so check `enabled`
+/// first for any that aren't free.
+#[derive(Debug, Clone)]
+pub struct Overlay {
+    pub enabled: bool,
+    pub toggle_key: VirtualKeyCode,
+    /// The top-left of the panel, in screen space.
+    pub pos: [f32; 2],
+    pub scale: f32,
+    /// The frame time that's drawn in green. Slower frames are yellow, and
+    /// frames over twice as slow are red.
+    pub target_frame_time: f32,
+    frame_times: [f32; FRAME_HISTORY],
+    next_frame: usize,
+    text: String,
+}
+
+impl Default for Overlay {
+    fn default() -> Self {
+        Overlay {
+            enabled: false,
+            toggle_key: VirtualKeyCode::F3,
+            pos: [4., 4.],
+            scale: 11.,
+            target_frame_time: 1. / 60.,
+            frame_times: [0.; FRAME_HISTORY],
+            next_frame: 0,
+            text: String::new(),
+        }
+    }
+}
+
+impl Overlay {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Show or hide the overlay when `toggle_key` is pressed.
+    pub fn handle_event<T>(&mut self, event: &Event<T>) {
+        if let Event::WindowEvent {
+            event:
+                WindowEvent::KeyboardInput {
+                    input:
+                        KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(key),
+                            ..
+                        },
+                    is_synthetic: false,
+                    ..
+                },
+            ..
+        } = event
+        {
+            if *key == self.toggle_key {
+                self.enabled = !self.enabled;
+            }
+        }
+    }
+
+    /// Add how long the last frame took, in seconds, to the graph. This is
+    /// recorded while the overlay is hidden too, so the graph is full as
+    /// soon as it's shown.
+    pub fn record_frame(&mut self, frame_time: f64) {
+        self.frame_times[self.next_frame] = frame_time as f32;
+        self.next_frame = (self.next_frame + 1) % FRAME_HISTORY;
+    }
+
+    /// Queue the panel, over everything else drawn so far. `white` should
+    /// be a region of solid white pixels in the atlas.
+    pub fn draw<B: SupportedBackend>(
+        &mut self,
+        renderer: &mut Renderer<B>,
+        text_painter: &mut TextPainter,
+        font: &Font,
+        white: Region,
+        providers: &[&dyn OverlayLines],
+    ) {
+        if !self.enabled {
+            return;
+        }
+
+        self.text.clear();
+        self.write_frame_summary();
+        for provider in providers {
+            // Writing to a String can't fail, so an error is the
+            // provider's own, and its partial lines are still worth showing
+            let _ = provider.write_lines(&mut self.text);
+        }
+
+        let line_height = font.metrics(self.scale).line_height;
+        let text_width = self
+            .text
+            .lines()
+            .map(|line| line.chars().map(|c| font.char_advance(c, self.scale)).sum())
+            .fold(0., f32::max);
+        let graph_width = FRAME_HISTORY as f32 * BAR_WIDTH;
+        let width = text_width.max(graph_width) + PADDING * 2.;
+        let height = GRAPH_HEIGHT + self.text.lines().count() as f32 * line_height + PADDING * 3.;
+
+        let previous_space = renderer.space();
+        renderer.set_space(Space::Screen);
+
+        let [x, y] = self.pos;
+        renderer.sprite(gradient_rect(
+            white,
+            ([x, y], [width, height]),
+            [0., 0., 0., 0.7],
+            [0., 0., 0., 0.5],
+        ));
+
+        let graph_bottom = y + PADDING + GRAPH_HEIGHT;
+        for i in 0..FRAME_HISTORY {
+            let frame_time = self.frame_times[(self.next_frame + i) % FRAME_HISTORY];
+            let proportion = frame_time / (self.target_frame_time * 2.);
+            let bar_height = (proportion * GRAPH_HEIGHT).min(GRAPH_HEIGHT).max(1.);
+            let color = self.frame_color(frame_time);
+            renderer.sprite(gradient_rect(
+                white,
+                (
+                    [
+                        x + PADDING + i as f32 * BAR_WIDTH,
+                        graph_bottom - bar_height,
+                    ],
+                    [BAR_WIDTH, bar_height],
+                ),
+                color,
+                color,
+            ));
+        }
+
+        renderer.set_space(previous_space);
+
+        let previous_space = text_painter.space();
+        text_painter.set_space(Space::Screen);
+        for (i, line) in self.text.lines().enumerate() {
+            text_painter.draw_text(
+                font,
+                line,
+                [x + PADDING, graph_bottom + PADDING + i as f32 * line_height],
+                self.scale,
+                [1., 1., 1., 1.],
+            );
+        }
+        text_painter.set_space(previous_space);
+    }
+
+    fn write_frame_summary(&mut self) {
+        let recorded = self.frame_times.iter().filter(|&&time| time > 0.);
+        let (count, total, worst) = recorded
+            .fold((0, 0., 0_f32), |(count, total, worst), &time| {
+                (count + 1, total + time, worst.max(time))
+            });
+
+        if count == 0 {
+            let _ = writeln!(self.text, "no frames recorded");
+            return;
+        }
+
+        let average = total / count as f32;
+        let _ = writeln!(
+            self.text,
+            "{:.0} fps, {:.1} ms (worst {:.1} ms)",
+            1. / average,
+            average * 1000.,
+            worst * 1000.
+        );
+    }
+
+    fn frame_color(&self, frame_time: f32) -> [f32; 4] {
+        if frame_time <= self.target_frame_time * 1.05 {
+            [0.3, 0.9, 0.4, 0.9]
+        } else if frame_time <= self.target_frame_time * 2. {
+            [1., 0.8, 0.2, 0.9]
+        } else {
+            [1., 0.3, 0.3, 0.9]
+        }
+    }
+}
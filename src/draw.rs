@@ -1,9 +1,39 @@
+pub mod animation;
+pub mod ease;
+pub mod effects;
+pub mod palette;
+
 #[cfg(feature = "draw_groove")]
 pub mod groove;
 
+#[cfg(feature = "draw_groove")]
+pub mod particles;
+
+#[cfg(feature = "draw_groove")]
+pub mod ribbon;
+
+#[cfg(feature = "draw_groove")]
+pub mod shapes;
+
 #[cfg(feature = "draw_sloth")]
 pub mod sloth;
 
+#[cfg(all(feature = "draw_groove", feature = "font"))]
+pub mod text;
+
+#[cfg(all(feature = "draw_groove", not(target_arch = "wasm32")))]
+pub mod testing;
+
+pub use self::animation::{Animation, AnimationEvent, AnimationMode, AnimationPlayer};
+pub use self::ease::Ease;
+pub use self::effects::ScreenEffects;
+pub use self::palette::{Dither, Palette};
+
+#[cfg(feature = "draw_groove")]
+pub use self::particles::Particles;
+#[cfg(feature = "draw_groove")]
+pub use self::ribbon::Ribbon;
+
 pub mod backend {
     #[cfg(feature = "opengl")]
     pub type OpenGL = gfx_backend_gl::Backend;
@@ -19,12 +49,139 @@ pub mod backend {
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Region {
+    /// Where the region is in the atlas, as stored. For regions turned on
+    /// their side, this is the image's size with the sides swapped.
     pub pixels: ([u32; 2], [u32; 2]),
     pub uv: ([f32; 2], [f32; 2]),
+    /// Which texture of a multi-page atlas the region is on. Always 0 for
+    /// a single page.
+    pub page: usize,
+    pub orientation: Orientation,
+}
+
+impl Region {
+    /// An upright region on the first page of the atlas. Set any other
+    /// fields with struct update syntax, e.g.
+    /// `Region { page: 1, ..Region::new(pixels, uv) }`.
+    pub fn new(pixels: ([u32; 2], [u32; 2]), uv: ([f32; 2], [f32; 2])) -> Self {
+        Region {
+            pixels,
+            uv,
+            page: 0,
+            orientation: Orientation::Upright,
+        }
+    }
+
+    /// The size of the image, the right way up.
+    pub fn size(&self) -> [u32; 2] {
+        let (_, [w, h]) = self.pixels;
+        match self.orientation.swaps_sides() {
+            true => [h, w],
+            false => [w, h],
+        }
+    }
+}
+
+/// How a region's image is stored in the atlas, compared to how it's drawn.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Orientation {
+    #[default]
+    Upright,
+    /// Turned 90° clockwise, as TexturePacker stores `rotated` frames.
+    Rot90,
+    Rot180,
+    /// Turned 90° anticlockwise.
+    Rot270,
+    /// Mirrored left to right.
+    FlipX,
+    /// Mirrored top to bottom.
+    FlipY,
+    /// Mirrored across the diagonal through the top-left corner.
+    Transpose,
+    /// Mirrored across the diagonal through the top-right corner.
+    Transverse,
+}
+
+impl Orientation {
+    /// Whether the stored image is as wide as the upright one is tall.
+    pub fn swaps_sides(self) -> bool {
+        use Orientation::*;
+
+        matches!(self, Rot90 | Rot270 | Transpose | Transverse)
+    }
+
+    /// The orientation that undoes this one.
+    pub fn inverse(self) -> Self {
+        match self {
+            Orientation::Rot90 => Orientation::Rot270,
+            Orientation::Rot270 => Orientation::Rot90,
+            other => other,
+        }
+    }
+
+    /// Where the point `[x, y]` of an upright image of the given size ends
+    /// up once it's stored this way.
+    pub fn map_point(self, point: [f32; 2], size: [f32; 2]) -> [f32; 2] {
+        let [x, y] = point;
+        let [w, h] = size;
+        match self {
+            Orientation::Upright => [x, y],
+            Orientation::Rot90 => [h - y, x],
+            Orientation::Rot180 => [w - x, h - y],
+            Orientation::Rot270 => [y, w - x],
+            Orientation::FlipX => [w - x, y],
+            Orientation::FlipY => [x, h - y],
+            Orientation::Transpose => [y, x],
+            Orientation::Transverse => [h - y, w - x],
+        }
+    }
+
+    /// The uv of each corner of the upright image, given the `(topleft,
+    /// size)` uv of the stored one: top-left, top-right, bottom-left, then
+    /// bottom-right.
+    pub fn corner_uvs(self, uv: ([f32; 2], [f32; 2])) -> [[f32; 2]; 4] {
+        let ([u0, v0], [uw, vh]) = uv;
+        let corner = |point| {
+            let [a, b] = self.map_point(point, [1., 1.]);
+            [u0 + a * uw, v0 + b * vh]
+        };
+        [
+            corner([0., 0.]),
+            corner([1., 0.]),
+            corner([0., 1.]),
+            corner([1., 1.]),
+        ]
+    }
+
+    /// The uv of the top-left `fraction` of the upright image, given the uv
+    /// of the stored one. Draw it with the same orientation.
+    pub fn crop_uv(self, uv: ([f32; 2], [f32; 2]), fraction: [f32; 2]) -> ([f32; 2], [f32; 2]) {
+        self.sub_uv(uv, [0., 0.], fraction)
+    }
+
+    /// The uv of the part of the upright image between the fractions `from`
+    /// and `to` of its size, given the uv of the stored one.
+    pub fn sub_uv(
+        self,
+        uv: ([f32; 2], [f32; 2]),
+        from: [f32; 2],
+        to: [f32; 2],
+    ) -> ([f32; 2], [f32; 2]) {
+        let ([u0, v0], [uw, vh]) = uv;
+        let [a0, b0] = self.map_point(from, [1., 1.]);
+        let [a1, b1] = self.map_point(to, [1., 1.]);
+        (
+            [u0 + a0.min(a1) * uw, v0 + b0.min(b1) * vh],
+            [(a1 - a0).abs() * uw, (b1 - b0).abs() * vh],
+        )
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct GlyphRegion {
+    /// The top-left of the glyph's bitmap, relative to the top of its line
+    /// rather than its baseline. The baseline is `Metrics::ascent` below
+    /// the `pos` the glyph was laid out at.
     pub pos: [f32; 2],
     pub size: [f32; 2],
     pub uv: ([f32; 2], [f32; 2]),
@@ -42,6 +199,30 @@ impl Default for CanvasMode {
     }
 }
 
+/// How finished frames are handed to the display. Not every mode is
+/// available everywhere, so the context reports the one it got.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum PresentMode {
+    /// Wait for the display to refresh, so frames never tear.
+    #[default]
+    Vsync,
+    /// Show each frame as soon as it's ready, which can tear.
+    NoVsync,
+    /// Wait for the refresh, unless the frame is late for it, in which
+    /// case it's shown straight away.
+    Adaptive,
+}
+
+/// What a sprite's position is relative to. `World` sprites move with the
+/// camera (and any screen shake), while `Screen` sprites stay put on the
+/// canvas, for HUDs and menus.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Space {
+    #[default]
+    World,
+    Screen,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ResizeMode {
     Free,
@@ -60,6 +241,9 @@ impl Default for ResizeMode {
 pub enum ScaleMode {
     Set(f64),
     Max,
+    /// Scale up by the largest whole number that fits. With
+    /// `ResizeMode::SetLogical`, the DPI scale is also rounded to a whole
+    /// number, so a 1.5x display renders the canvas at 2x.
     MaxInt,
 }
 
@@ -114,7 +298,6 @@ impl CanvasConfig {
         physical_window_size: [u32; 2],
         scale_factor: f64,
     ) -> CanvasProperties {
-        let s = scale_factor;
         let [pw, ph] = physical_window_size;
         let logical_window_size = [
             (pw as f64 / scale_factor) as u32,
@@ -129,6 +312,14 @@ impl CanvasConfig {
             [scaled_width, scaled_height]
         }
 
+        // With integer scaling, each logical pixel has to cover a whole
+        // number of physical ones too, so that texels land on pixel edges.
+        // Rounding up from halves keeps the detail of 1.5x displays.
+        let s = match self.scale_mode {
+            ScaleMode::MaxInt => scale_factor.round().max(1.),
+            _ => scale_factor,
+        };
+
         let [cw, ch] = match self.resize_mode {
             ResizeMode::Free => physical_window_size,
             ResizeMode::SetLogical([w, h]) => [(w as f64 * s) as u32, (h as f64 * s) as u32],
@@ -167,3 +358,149 @@ impl CanvasConfig {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{imageops, Rgba, RgbaImage};
+
+    const ORIENTATIONS: [Orientation; 8] = [
+        Orientation::Upright,
+        Orientation::Rot90,
+        Orientation::Rot180,
+        Orientation::Rot270,
+        Orientation::FlipX,
+        Orientation::FlipY,
+        Orientation::Transpose,
+        Orientation::Transverse,
+    ];
+
+    /// A 3x2 image with a different color in every pixel.
+    fn upright() -> RgbaImage {
+        RgbaImage::from_fn(3, 2, |x, y| Rgba([x as u8 * 80, y as u8 * 200, 0, 255]))
+    }
+
+    /// The image as it would be stored in an atlas with `orientation`.
+    fn stored(image: &RgbaImage, orientation: Orientation) -> RgbaImage {
+        match orientation {
+            Orientation::Upright => image.clone(),
+            Orientation::Rot90 => imageops::rotate90(image),
+            Orientation::Rot180 => imageops::rotate180(image),
+            Orientation::Rot270 => imageops::rotate270(image),
+            Orientation::FlipX => imageops::flip_horizontal(image),
+            Orientation::FlipY => imageops::flip_vertical(image),
+            Orientation::Transpose => imageops::flip_horizontal(&imageops::rotate90(image)),
+            Orientation::Transverse => imageops::flip_horizontal(&imageops::rotate270(image)),
+        }
+    }
+
+    /// The pixel of `image` under the point `[u, v]`, from 0 to 1 across it.
+    fn sample(image: &RgbaImage, [u, v]: [f32; 2]) -> Rgba<u8> {
+        let x = (u * image.width() as f32) as u32;
+        let y = (v * image.height() as f32) as u32;
+        *image.get_pixel(x, y)
+    }
+
+    #[test]
+    fn orientations_map_pixels_to_where_they_are_stored() {
+        let upright = upright();
+        for &orientation in &ORIENTATIONS {
+            let stored = stored(&upright, orientation);
+            assert_eq!(
+                stored.width() != upright.width(),
+                orientation.swaps_sides(),
+                "{:?}",
+                orientation
+            );
+
+            for (x, y, pixel) in upright.enumerate_pixels() {
+                let center = [x as f32 + 0.5, y as f32 + 0.5];
+                let [sx, sy] = orientation.map_point(center, [3., 2.]);
+                assert_eq!(
+                    stored.get_pixel(sx as u32, sy as u32),
+                    pixel,
+                    "{:?} at {:?}",
+                    orientation,
+                    [x, y]
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn corner_uvs_sample_the_upright_corners() {
+        let upright = upright();
+        let upright_corners = [[0, 0], [2, 0], [0, 1], [2, 1]];
+        for &orientation in &ORIENTATIONS {
+            let stored = stored(&upright, orientation);
+            let corners = orientation.corner_uvs(([0., 0.], [1., 1.]));
+
+            for (&[u, v], &[x, y]) in corners.iter().zip(&upright_corners) {
+                // Step in from the corner, to sample the pixel inside it
+                let inside = [u + (0.5 - u) * 0.1, v + (0.5 - v) * 0.1];
+                assert_eq!(
+                    sample(&stored, inside),
+                    *upright.get_pixel(x, y),
+                    "{:?} at {:?}",
+                    orientation,
+                    [x, y]
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn inverse_orientations_map_points_back() {
+        let size = [3., 2.];
+        for &orientation in &ORIENTATIONS {
+            let stored_size = match orientation.swaps_sides() {
+                true => [2., 3.],
+                false => size,
+            };
+            let point = [0.5, 1.5];
+            let stored = orientation.map_point(point, size);
+            assert_eq!(
+                orientation.inverse().map_point(stored, stored_size),
+                point,
+                "{:?}",
+                orientation
+            );
+        }
+    }
+
+    #[test]
+    fn sub_uvs_cover_the_same_pixels_in_every_orientation() {
+        let upright = upright();
+        for &orientation in &ORIENTATIONS {
+            let stored = stored(&upright, orientation);
+            // The right-hand column of the upright image
+            let (topleft, size) = orientation.sub_uv(([0., 0.], [1., 1.]), [2. / 3., 0.], [1., 1.]);
+            let [cx, cy] = [topleft[0] + size[0] / 2., topleft[1] + size[1] / 2.];
+            let mut covered = [
+                sample(&stored, [cx - size[0] / 4., cy - size[1] / 4.]),
+                sample(&stored, [cx + size[0] / 4., cy + size[1] / 4.]),
+            ];
+            let mut expected = [*upright.get_pixel(2, 0), *upright.get_pixel(2, 1)];
+            covered.sort_by_key(|pixel| pixel.0);
+            expected.sort_by_key(|pixel| pixel.0);
+            assert_eq!(covered, expected, "{:?}", orientation);
+        }
+    }
+
+    #[test]
+    fn max_int_keeps_fractional_dpi_scales_sharp() {
+        let config = CanvasConfig {
+            canvas_mode: CanvasMode::Intermediate,
+            resize_mode: ResizeMode::SetLogical([320, 180]),
+            scale_mode: ScaleMode::MaxInt,
+        };
+
+        let props = config.canvas_properties([1920, 1080], 1.5);
+        assert_eq!(props.physical_canvas_size, [640, 360]);
+        assert_eq!(props.viewport_scissor_rect, ([0, 0], [1920, 1080]));
+
+        let props = config.canvas_properties([1280, 720], 1.);
+        assert_eq!(props.physical_canvas_size, [320, 180]);
+        assert_eq!(props.viewport_scissor_rect, ([0, 0], [1280, 720]));
+    }
+}
@@ -0,0 +1,185 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Deserializer};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AnimationMode {
+    #[default]
+    Loop,
+    /// Play forwards then backwards, forever.
+    PingPong,
+    /// Play once, then hold the last frame.
+    Once,
+}
+
+/// A sequence of atlas region keys, each shown for its own duration.
+///
+/// Can be deserialized (e.g. with `static_data_mod!`) from TOML like:
+///
+/// ```toml
+/// [walk]
+/// mode = "pingpong"
+/// frames = [["walk_0", 0.1], ["walk_1", 0.1], ["walk_2", 0.2]]
+/// ```
+///
+/// where frame durations are in seconds, and can't be negative.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(bound(deserialize = "K: Deserialize<'de>"))]
+pub struct Animation<K> {
+    #[serde(deserialize_with = "frames_in_secs")]
+    pub frames: Vec<(K, Duration)>,
+
+    #[serde(default)]
+    pub mode: AnimationMode,
+}
+
+impl<K> Animation<K> {
+    /// Build an animation where every frame lasts `frame_duration`, for
+    /// example from the keys returned by `ImageAtlas::insert_grid`.
+    pub fn uniform<I>(keys: I, frame_duration: Duration, mode: AnimationMode) -> Self
+    where
+        I: IntoIterator<Item = K>,
+    {
+        Animation {
+            frames: keys.into_iter().map(|key| (key, frame_duration)).collect(),
+            mode,
+        }
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.frames.iter().map(|(_, duration)| *duration).sum()
+    }
+
+    /// The index of the frame showing `secs` into the animation.
+    pub fn frame_at(&self, secs: f64) -> usize {
+        let last = self.frames.len().saturating_sub(1);
+        let total = self.duration().as_secs_f64();
+        if total <= 0. {
+            return 0;
+        }
+
+        match self.mode {
+            AnimationMode::Loop => self.forward_frame_at(secs.rem_euclid(total)),
+            AnimationMode::Once if secs >= total => last,
+            AnimationMode::Once => self.forward_frame_at(secs.max(0.)),
+            AnimationMode::PingPong => {
+                // The first and last frames aren't repeated on the way back
+                let inner = &self.frames[1.min(last)..last];
+                let returning: f64 = inner.iter().map(|(_, d)| d.as_secs_f64()).sum();
+                let t = secs.rem_euclid(total + returning);
+
+                if t < total {
+                    return self.forward_frame_at(t);
+                }
+
+                let mut t = t - total;
+                for (i, (_, duration)) in inner.iter().enumerate().rev() {
+                    t -= duration.as_secs_f64();
+                    if t < 0. {
+                        return i + 1;
+                    }
+                }
+                0
+            }
+        }
+    }
+
+    fn forward_frame_at(&self, mut t: f64) -> usize {
+        for (i, (_, duration)) in self.frames.iter().enumerate() {
+            t -= duration.as_secs_f64();
+            if t < 0. {
+                return i;
+            }
+        }
+        self.frames.len().saturating_sub(1)
+    }
+}
+
+const MAX_FRAME_SECS: f64 = u32::MAX as f64;
+
+fn frames_in_secs<'de, D, K>(deserializer: D) -> Result<Vec<(K, Duration)>, D::Error>
+where
+    D: Deserializer<'de>,
+    K: Deserialize<'de>,
+{
+    use serde::de::Error;
+
+    let frames = Vec::<(K, f64)>::deserialize(deserializer)?;
+    frames
+        .into_iter()
+        .map(|(key, secs)| match secs {
+            // The upper limit keeps the animation's total duration from
+            // overflowing, however many frames there are
+            secs if (0. ..MAX_FRAME_SECS).contains(&secs) => {
+                Ok((key, Duration::from_secs_f64(secs)))
+            }
+            secs => Err(D::Error::custom(format!(
+                "invalid frame duration {}, expected a number of seconds >= 0",
+                secs
+            ))),
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnimationEvent {
+    /// A `Once` animation reached its last frame.
+    Completed,
+}
+
+/// Playback state for an `Animation`.
+///
+/// This doesn't hold the animation itself, so that the animation can live
+/// in static data and be hot-reloaded while it's playing.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct AnimationPlayer {
+    elapsed: f64,
+    completed: bool,
+}
+
+impl AnimationPlayer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn restart(&mut self) {
+        *self = Self::default();
+    }
+
+    pub fn elapsed(&self) -> f64 {
+        self.elapsed
+    }
+
+    pub fn completed(&self) -> bool {
+        self.completed
+    }
+
+    /// Advance by `delta` seconds, as returned by `Clock::update`.
+    pub fn advance<K>(&mut self, animation: &Animation<K>, delta: f64) -> Option<AnimationEvent> {
+        self.elapsed += delta;
+
+        let finished = animation.mode == AnimationMode::Once
+            && self.elapsed >= animation.duration().as_secs_f64();
+
+        if finished && !self.completed {
+            self.completed = true;
+            return Some(AnimationEvent::Completed);
+        }
+
+        None
+    }
+
+    pub fn frame<K>(&self, animation: &Animation<K>) -> usize {
+        animation.frame_at(self.elapsed)
+    }
+
+    /// The region key to fetch from the atlas for the current frame, or
+    /// `None` if the animation has no frames.
+    pub fn current<'a, K>(&self, animation: &'a Animation<K>) -> Option<&'a K> {
+        animation
+            .frames
+            .get(self.frame(animation))
+            .map(|(key, _)| key)
+    }
+}
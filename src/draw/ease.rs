@@ -0,0 +1,51 @@
+/// Easing curves, mapping progress `t` from 0 to 1 onto a value from 0 to 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Ease {
+    #[default]
+    Linear,
+    QuadIn,
+    QuadOut,
+    QuadInOut,
+    CubicIn,
+    CubicOut,
+    CubicInOut,
+    /// Eases out, briefly overshooting 1 before settling.
+    BackOut,
+}
+
+impl Ease {
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.clamp(0., 1.);
+        match self {
+            Ease::Linear => t,
+            Ease::QuadIn => t * t,
+            Ease::QuadOut => 1. - (1. - t) * (1. - t),
+            Ease::QuadInOut if t < 0.5 => 2. * t * t,
+            Ease::QuadInOut => 1. - 2. * (1. - t) * (1. - t),
+            Ease::CubicIn => t * t * t,
+            Ease::CubicOut => 1. - (1. - t).powi(3),
+            Ease::CubicInOut if t < 0.5 => 4. * t * t * t,
+            Ease::CubicInOut => 1. - 4. * (1. - t).powi(3),
+            Ease::BackOut => {
+                const OVERSHOOT: f32 = 1.70158;
+                let u = t - 1.;
+                1. + u * u * ((OVERSHOOT + 1.) * u + OVERSHOOT)
+            }
+        }
+    }
+
+    /// Interpolate from `from` to `to` at progress `t`.
+    pub fn between(self, from: f32, to: f32, t: f32) -> f32 {
+        from + (to - from) * self.apply(t)
+    }
+
+    /// Interpolate each channel of a color from `from` to `to`.
+    pub fn between_colors(self, from: [f32; 4], to: [f32; 4], t: f32) -> [f32; 4] {
+        let t = self.apply(t);
+        let mut color = [0.; 4];
+        for (i, channel) in color.iter_mut().enumerate() {
+            *channel = from[i] + (to[i] - from[i]) * t;
+        }
+        color
+    }
+}
@@ -0,0 +1,231 @@
+use crate::draw::ease::Ease;
+
+const TAU: f32 = std::f32::consts::PI * 2.;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Shake {
+    amplitude: f32,
+    frequency: f32,
+    duration: f32,
+    elapsed: f32,
+    phase: [f32; 2],
+}
+
+impl Shake {
+    fn offset(&self, decay: Ease) -> [f32; 2] {
+        let strength = self.amplitude * (1. - decay.apply(self.elapsed / self.duration));
+        let angle = TAU * self.frequency * self.elapsed;
+
+        // Slightly different rates on each axis keep it from moving in a line
+        [
+            strength * (angle + self.phase[0]).sin(),
+            strength * (angle * 1.13 + self.phase[1]).sin(),
+        ]
+    }
+}
+
+/// A color moving from one value to another over time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ColorTween {
+    from: [f32; 4],
+    to: [f32; 4],
+    duration: f32,
+    elapsed: f32,
+    ease: Ease,
+}
+
+impl ColorTween {
+    fn steady(color: [f32; 4]) -> Self {
+        ColorTween {
+            from: color,
+            to: color,
+            duration: 0.,
+            elapsed: 0.,
+            ease: Ease::Linear,
+        }
+    }
+
+    fn done(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+
+    fn color(&self) -> [f32; 4] {
+        match self.done() {
+            true => self.to,
+            false => self
+                .ease
+                .between_colors(self.from, self.to, self.elapsed / self.duration),
+        }
+    }
+}
+
+fn transparent([r, g, b, _]: [f32; 4]) -> [f32; 4] {
+    [r, g, b, 0.]
+}
+
+/// Screen shake, flashes, and fades, for a bit of juice.
+///
+/// Call `update` each frame with the time since the last one (e.g. from a
+/// `timing::RealClock`). Then add `camera_offset` to everything drawn in
+/// world space (but not the HUD), and draw `overlay` as a full-screen quad
+/// on top. With `draw_groove`, the `DrawContext` can do both automatically
+/// with `install_screen_effects`.
+///
+/// Effects combine: every shake is added together, a flash is drawn over
+/// the fade, and a new fade takes over from where the last one got to.
+#[derive(Debug, Clone)]
+pub struct ScreenEffects {
+    /// How shakes die down over their duration.
+    pub shake_decay: Ease,
+    /// How flashes disappear over their duration.
+    pub flash_ease: Ease,
+    /// The curve used by `fade_to` and `fade_from`.
+    pub fade_ease: Ease,
+    shakes: Vec<Shake>,
+    flash: Option<ColorTween>,
+    fade: ColorTween,
+    rng_state: u32,
+}
+
+impl Default for ScreenEffects {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ScreenEffects {
+    pub fn new() -> Self {
+        ScreenEffects {
+            shake_decay: Ease::QuadOut,
+            flash_ease: Ease::QuadOut,
+            fade_ease: Ease::QuadInOut,
+            shakes: vec![],
+            flash: None,
+            fade: ColorTween::steady([0., 0., 0., 0.]),
+            rng_state: 1,
+        }
+    }
+
+    /// Shake the screen up to `amplitude` pixels, `frequency` times per
+    /// second, dying down over `duration` seconds.
+    pub fn shake(&mut self, amplitude: f32, duration: f32, frequency: f32) {
+        if duration <= 0. {
+            return;
+        }
+
+        let phase = [self.random_angle(), self.random_angle()];
+        self.shakes.push(Shake {
+            amplitude,
+            frequency,
+            duration,
+            elapsed: 0.,
+            phase,
+        });
+    }
+
+    /// Flash the screen with `color`, fading away over `duration` seconds.
+    /// This replaces any flash already in progress.
+    pub fn flash(&mut self, color: [f32; 4], duration: f32) {
+        self.flash = Some(ColorTween {
+            from: color,
+            to: transparent(color),
+            duration,
+            elapsed: 0.,
+            ease: self.flash_ease,
+        });
+    }
+
+    /// Fade the screen to `color` over `duration` seconds, and stay there.
+    pub fn fade_to(&mut self, color: [f32; 4], duration: f32) {
+        let current = self.fade.color();
+
+        // Fading from nothing shouldn't pass through the old color
+        let from = match current[3] {
+            a if a <= 0. => transparent(color),
+            _ => current,
+        };
+
+        self.fade = ColorTween {
+            from,
+            to: color,
+            duration,
+            elapsed: 0.,
+            ease: self.fade_ease,
+        };
+    }
+
+    /// Start the screen covered in `color`, and fade it away over
+    /// `duration` seconds.
+    pub fn fade_from(&mut self, color: [f32; 4], duration: f32) {
+        self.fade = ColorTween {
+            from: color,
+            to: transparent(color),
+            duration,
+            elapsed: 0.,
+            ease: self.fade_ease,
+        };
+    }
+
+    /// Stop every effect immediately.
+    pub fn clear(&mut self) {
+        self.shakes.clear();
+        self.flash = None;
+        self.fade = ColorTween::steady([0., 0., 0., 0.]);
+    }
+
+    /// Advance every effect by `dt` seconds.
+    pub fn update(&mut self, dt: f32) {
+        for shake in &mut self.shakes {
+            shake.elapsed += dt;
+        }
+        self.shakes.retain(|shake| shake.elapsed < shake.duration);
+
+        if let Some(flash) = &mut self.flash {
+            flash.elapsed += dt;
+            if flash.done() {
+                self.flash = None;
+            }
+        }
+
+        self.fade.elapsed = (self.fade.elapsed + dt).min(self.fade.duration);
+    }
+
+    /// Whether anything is still moving, e.g. to keep requesting frames
+    /// with a `RedrawScheduler`.
+    pub fn is_animating(&self) -> bool {
+        !self.shakes.is_empty() || self.flash.is_some() || !self.fade.done()
+    }
+
+    /// How far to move everything drawn, in pixels.
+    pub fn camera_offset(&self) -> [f32; 2] {
+        self.shakes.iter().fold([0., 0.], |[x, y], shake| {
+            let [dx, dy] = shake.offset(self.shake_decay);
+            [x + dx, y + dy]
+        })
+    }
+
+    /// The color to draw over the whole screen, if any.
+    pub fn overlay(&self) -> Option<[f32; 4]> {
+        let [br, bg, bb, ba] = self.fade.color();
+        let [fr, fg, fb, fa] = self.flash.map(|flash| flash.color()).unwrap_or_default();
+
+        // The flash is blended over the fade
+        let alpha = fa + ba * (1. - fa);
+        if alpha <= 0. {
+            return None;
+        }
+
+        let blend = |f: f32, b: f32| (f * fa + b * ba * (1. - fa)) / alpha;
+        Some([blend(fr, br), blend(fg, bg), blend(fb, bb), alpha])
+    }
+
+    fn random_angle(&mut self) -> f32 {
+        let mut x = self.rng_state;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.rng_state = x;
+
+        (x >> 8) as f32 / (1 << 24) as f32 * TAU
+    }
+}
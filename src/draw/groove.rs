@@ -5,7 +5,11 @@ use std::mem::ManuallyDrop;
 use image::RgbaImage;
 
 use crate::{
-    draw::{CanvasConfig, CanvasMode, GlyphRegion, Region},
+    draw::{
+        effects::ScreenEffects,
+        palette::{Dither, MAX_PALETTE_COLORS},
+        CanvasConfig, CanvasMode, GlyphRegion, Orientation, PresentMode, Region, Space,
+    },
     gfx::{self, easy, prelude::*, SupportedBackend},
     utils::over,
     windowing::{
@@ -15,7 +19,10 @@ use crate::{
 };
 
 #[cfg(feature = "font")]
-use crate::{atlas::Atlas, font::Glyph};
+use crate::{
+    atlas::{font::OutlineAtlas, Atlas},
+    font::Glyph,
+};
 
 #[cfg(all(target_arch = "wasm32", not(feature = "opengl")))]
 compile_error!("Web builds (wasm32) require the `opengl` feature to be enabled.");
@@ -32,6 +39,51 @@ const SHADER_SOURCES: (&'static [u8], &'static [u8]) = (
     include_bytes!("../../assets/shaders/compiled/groove.es.frag"),
 );
 
+/// The fragment shader for the final blit when dithering. It shares the
+/// vertex shader with the other pipelines.
+#[cfg(not(all(target_arch = "wasm32", feature = "bypass_spirv_cross")))]
+const DITHER_SHADER_SOURCE: &'static [u8] =
+    include_bytes!("../../assets/shaders/compiled/dither.frag.spv");
+
+#[cfg(all(target_arch = "wasm32", feature = "bypass_spirv_cross"))]
+const DITHER_SHADER_SOURCE: &'static [u8] =
+    include_bytes!("../../assets/shaders/compiled/dither.es.frag");
+
+/// In dev builds with the `reloading` feature, shaders are loaded through
+/// the resource system and reloaded when they change on disk.
+#[cfg(all(feature = "reloading", debug_assertions, not(target_arch = "wasm32")))]
+mod shader_dev {
+    use resource::{resource, Resource};
+
+    /// The name shader errors are reported under, with `reloading::report_error`.
+    pub const SHADER_NAME: &str = "assets/shaders/compiled/groove.{vert,frag}.spv";
+
+    pub struct ShaderWatch {
+        vert: Resource<[u8]>,
+        frag: Resource<[u8]>,
+    }
+
+    impl ShaderWatch {
+        pub fn new() -> Self {
+            ShaderWatch {
+                vert: resource!("assets/shaders/compiled/groove.vert.spv"),
+                frag: resource!("assets/shaders/compiled/groove.frag.spv"),
+            }
+        }
+
+        /// Reload whichever shaders have changed, returning whether any did.
+        pub fn reload_if_changed(&mut self) -> bool {
+            let vert_changed = self.vert.reload_if_changed();
+            let frag_changed = self.frag.reload_if_changed();
+            vert_changed || frag_changed
+        }
+
+        pub fn shaders(&self) -> (&[u8], &[u8]) {
+            (&self.vert, &self.frag)
+        }
+    }
+}
+
 pub const MAX_SPRITES: usize = 10000;
 const VERTEX_BUFFER_LEN: usize = MAX_SPRITES * 6;
 
@@ -40,11 +92,31 @@ pub struct Sprite {
     pub pos: [f32; 2],
     pub size: [f32; 2],
     pub tint: [f32; 4],
+    /// Multiplied with `tint` at each corner: top-left, top-right,
+    /// bottom-left, then bottom-right.
+    pub corner_tints: [[f32; 4]; 4],
     pub atlas_uv: ([f32; 2], [f32; 2]),
+    /// How the image at `atlas_uv` is stored.
+    pub orientation: Orientation,
     pub angle: f32,
+    /// Which page of the atlas `atlas_uv` is on.
+    pub page: usize,
+    /// Round the sprite's position to a whole pixel of the canvas, after
+    /// the camera has moved it.
+    pub pixel_snap: bool,
+    /// Where to draw each corner, in the same order as `corner_tints`, for
+    /// shapes that aren't rectangles. When set, `pos`, `size`, and `angle`
+    /// are ignored.
+    pub corners: Option<[[f32; 2]; 4]>,
 }
 
+const WHITE_CORNERS: [[f32; 4]; 4] = [[1., 1., 1., 1.]; 4];
+
 impl Sprite {
+    /// The region, untinted and unrotated, with its top-left corner at `pos`.
+    /// Other fields like `corners` are best set on top of this, as in
+    /// `Sprite { corners: Some(corners), ..Sprite::new(region, pos) }`, so
+    /// that fields added later get their defaults.
     pub fn new(region: Region, pos: [f32; 2]) -> Self {
         Self::tinted(region, pos, [1., 1., 1., 1.])
     }
@@ -55,15 +127,20 @@ impl Sprite {
 
     pub fn scaled(region: Region, pos: [f32; 2], tint: [f32; 4], scale: [f32; 2]) -> Self {
         let [x, y] = pos;
-        let (_, [w, h]) = region.pixels;
+        let [w, h] = region.size();
         let [sx, sy] = scale;
 
         Sprite {
             pos: [x as f32, y as f32],
             size: [w as f32 * sx, h as f32 * sy],
             tint,
+            corner_tints: WHITE_CORNERS,
             atlas_uv: region.uv,
+            orientation: region.orientation,
             angle: 0.,
+            page: region.page,
+            pixel_snap: false,
+            corners: None,
         }
     }
 
@@ -75,28 +152,65 @@ impl Sprite {
             pos: [x as f32, y as f32],
             size: [sx, sy],
             tint,
+            corner_tints: WHITE_CORNERS,
             atlas_uv: region.uv,
+            orientation: region.orientation,
             angle: 0.,
+            page: region.page,
+            pixel_snap: false,
+            corners: None,
+        }
+    }
+
+    /// A quad with its corners at the given points: top-left, top-right,
+    /// bottom-left, then bottom-right, as the region's corners are mapped.
+    pub fn quad(region: Region, corners: [[f32; 2]; 4], tint: [f32; 4]) -> Self {
+        Sprite {
+            corners: Some(corners),
+            ..Self::sized(region, corners[0], tint, [0., 0.])
         }
     }
 
+    pub fn with_corner_tints(self, corner_tints: [[f32; 4]; 4]) -> Self {
+        Sprite {
+            corner_tints,
+            ..self
+        }
+    }
+
+    pub fn pixel_snapped(self) -> Self {
+        Sprite {
+            pixel_snap: true,
+            ..self
+        }
+    }
+
+    /// Shade from `top` to `bottom` down the sprite.
+    pub fn with_vertical_gradient(self, top: [f32; 4], bottom: [f32; 4]) -> Self {
+        self.with_corner_tints([top, top, bottom, bottom])
+    }
+
     pub fn glyph(region: GlyphRegion, tint: [f32; 4]) -> Self {
         Sprite {
             pos: region.pos,
             size: region.size,
             tint,
+            corner_tints: WHITE_CORNERS,
             atlas_uv: region.uv,
+            orientation: Orientation::Upright,
             angle: 0.,
+            page: 0,
+            pixel_snap: false,
+            corners: None,
         }
     }
 
     pub fn gauge(region: Region, pos: [f32; 2], proportion: f32, brightness: f32) -> Self {
         let [x, y] = pos;
-        let (_, [w, h]) = region.pixels;
+        let [w, h] = region.size();
         let (x, y, w, h) = (x as f32, y as f32, w as f32, h as f32);
 
-        let mut uv = region.uv;
-        uv.1[0] *= proportion;
+        let uv = region.orientation.crop_uv(region.uv, [proportion, 1.]);
 
         let scaled_w = w * proportion;
         let b = brightness;
@@ -104,8 +218,13 @@ impl Sprite {
             pos: [x - w / 2. + scaled_w / 2., y],
             size: [scaled_w, h],
             tint: [b, b, b, 1.],
+            corner_tints: WHITE_CORNERS,
             atlas_uv: uv,
+            orientation: region.orientation,
             angle: 0.,
+            page: region.page,
+            pixel_snap: false,
+            corners: None,
         }
     }
 }
@@ -118,6 +237,14 @@ struct Vertex {
     pub offset: [f32; 3],
 }
 
+/// Matches `DitherParams` in `dither.frag`.
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+struct DitherParams {
+    strength: f32,
+    palette_size: f32,
+}
+
 fn wiperr<T>(_: T) -> () {}
 
 fn is_srgb(surface_format: Format) -> bool {
@@ -137,12 +264,12 @@ struct Resources<B: SupportedBackend> {
     surface: B::Surface,
     command_pool: B::CommandPool,
     vertex_buffer: (B::Memory, B::Buffer),
-    atlas_image: (B::Memory, B::Image, B::ImageView),
+    atlas_pages: Vec<(B::Memory, B::Image, B::ImageView)>,
     sampler: B::Sampler,
     render_pass_to_canvas: B::RenderPass,
     desc_set_layout: B::DescriptorSetLayout,
     desc_set_pool: B::DescriptorPool,
-    desc_set: B::DescriptorSet,
+    page_desc_sets: Vec<B::DescriptorSet>,
     blit_desc_set: B::DescriptorSet,
     pipeline_layout_to_canvas: B::PipelineLayout,
     pipeline_to_canvas: B::GraphicsPipeline,
@@ -153,6 +280,23 @@ struct Resources<B: SupportedBackend> {
     render_pass_to_surface: B::RenderPass,
     pipeline_layout_to_surface: B::PipelineLayout,
     pipeline_to_surface: B::GraphicsPipeline,
+    dither_palette: (B::Memory, B::Image, B::ImageView),
+    dither_desc_set_layout: B::DescriptorSetLayout,
+    dither_desc_set_pool: B::DescriptorPool,
+    dither_desc_set: B::DescriptorSet,
+    pipeline_layout_dither: B::PipelineLayout,
+    pipeline_dither: B::GraphicsPipeline,
+}
+
+/// What it took to draw a frame.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DrawStats {
+    pub sprites: usize,
+    /// How many draw calls the sprites were split into. Sprites are drawn
+    /// in order, so there's a new batch every time the atlas page changes.
+    pub batches: usize,
+    /// How many times a different atlas page had to be bound mid-frame.
+    pub page_switches: usize,
 }
 
 pub struct DrawContext<B: SupportedBackend> {
@@ -166,9 +310,18 @@ pub struct DrawContext<B: SupportedBackend> {
     scale_factor: f64,
     framebuffer_attachment: Option<FramebufferAttachment>,
     swapchain_invalidated: Option<()>,
+    present_mode: PresentMode,
+    achieved_present_mode: Option<PresentMode>,
     texture_atlas: RgbaImage,
     vertex_cache: Vec<Vertex>,
     canvas_config: CanvasConfig,
+    camera: [f32; 2],
+    screen_effects: Option<(ScreenEffects, Region)>,
+    dither: Option<Dither>,
+    stats: DrawStats,
+
+    #[cfg(all(feature = "reloading", debug_assertions, not(target_arch = "wasm32")))]
+    shader_watch: shader_dev::ShaderWatch,
 }
 
 impl<B: SupportedBackend> DrawContext<B> {
@@ -177,6 +330,19 @@ impl<B: SupportedBackend> DrawContext<B> {
         canvas_config: CanvasConfig,
         texture_atlas: RgbaImage,
     ) -> Result<Self, ()> {
+        Self::with_pages(window, canvas_config, vec![texture_atlas])
+    }
+
+    /// Create a context for a multi-page atlas, like the images from
+    /// `ImageAtlas::compile_pages`. Sprites are drawn from the page in
+    /// their `page`. More pages can be added with `Renderer::update_pages`.
+    pub fn with_pages(
+        window: &Window,
+        canvas_config: CanvasConfig,
+        mut pages: Vec<RgbaImage>,
+    ) -> Result<Self, ()> {
+        assert!(!pages.is_empty(), "The atlas needs at least one page");
+
         let (
             instance,
             surface,
@@ -208,20 +374,22 @@ impl<B: SupportedBackend> DrawContext<B> {
             )
         };
 
-        let atlas_image_size = texture_atlas.dimensions();
-        let atlas_image = unsafe {
-            use hal::format::{Aspects, Format};
-            use hal::image::Usage;
+        let atlas_pages = pages
+            .iter()
+            .map(|page| unsafe {
+                use hal::format::{Aspects, Format};
+                use hal::image::Usage;
 
-            gfx::make_image::<B>(
-                &device,
-                &adapter.physical_device,
-                atlas_image_size,
-                texture_format(surface_color_format),
-                Usage::SAMPLED | Usage::TRANSFER_DST,
-                Aspects::COLOR,
-            )
-        };
+                gfx::make_image::<B>(
+                    &device,
+                    &adapter.physical_device,
+                    page.dimensions(),
+                    texture_format(surface_color_format),
+                    Usage::SAMPLED | Usage::TRANSFER_DST,
+                    Aspects::COLOR,
+                )
+            })
+            .collect::<Vec<_>>();
 
         let (intermediate_canvas, intermediate_canvas_size) = unsafe {
             use gfx_hal::format::{Aspects, Format};
@@ -238,13 +406,27 @@ impl<B: SupportedBackend> DrawContext<B> {
                     &adapter.physical_device,
                     (intermediate_canvas_size[0], intermediate_canvas_size[1]),
                     Format::Rgba8Srgb,
-                    Usage::COLOR_ATTACHMENT | Usage::SAMPLED,
+                    Usage::COLOR_ATTACHMENT | Usage::SAMPLED | Usage::TRANSFER_SRC,
                     Aspects::COLOR,
                 ),
                 intermediate_canvas_size,
             )
         };
 
+        let dither_palette = unsafe {
+            use gfx_hal::format::{Aspects, Format};
+            use gfx_hal::image::Usage;
+
+            gfx::make_image::<B>(
+                &device,
+                &adapter.physical_device,
+                (MAX_PALETTE_COLORS as u32, 1),
+                Format::Rgba8Srgb,
+                Usage::SAMPLED | Usage::TRANSFER_DST,
+                Aspects::COLOR,
+            )
+        };
+
         let sampler = unsafe {
             use hal::image::{Filter, SamplerDesc, Usage, WrapMode};
 
@@ -253,32 +435,45 @@ impl<B: SupportedBackend> DrawContext<B> {
                 .expect("TODO")
         };
 
-        unsafe {
-            gfx::upload_image::<B>(
-                &device,
-                &adapter.physical_device,
-                &mut command_pool,
-                &mut queue_group.queues[0],
-                &atlas_image.1,
-                atlas_image_size,
-                &texture_atlas,
-            );
+        for (page, atlas_image) in pages.iter().zip(&atlas_pages) {
+            unsafe {
+                gfx::upload_image::<B>(
+                    &device,
+                    &adapter.physical_device,
+                    &mut command_pool,
+                    &mut queue_group.queues[0],
+                    &atlas_image.1,
+                    page.dimensions(),
+                    page,
+                );
+            }
         }
 
         let render_pass_to_canvas = easy::render_pass::<B>(&device, Format::Rgba8Srgb, None, true);
         let render_pass_to_surface =
             easy::render_pass::<B>(&device, surface_color_format, None, false);
 
-        let (desc_set_layout, mut desc_set_pool, mut desc_sets) = easy::desc_sets::<B>(
-            &device,
-            vec![
-                (vec![], vec![&atlas_image.2], vec![&sampler]),
-                (vec![], vec![&intermediate_canvas.2], vec![&sampler]),
-            ],
-        );
+        let mut desc_set_values = atlas_pages
+            .iter()
+            .map(|atlas_image| (vec![], vec![&atlas_image.2], vec![&sampler]))
+            .collect::<Vec<_>>();
+        desc_set_values.push((vec![], vec![&intermediate_canvas.2], vec![&sampler]));
+
+        let (desc_set_layout, mut desc_set_pool, mut page_desc_sets) =
+            easy::desc_sets::<B>(&device, desc_set_values);
 
-        let mut desc_set = desc_sets.remove(0);
-        let mut blit_desc_set = desc_sets.remove(0);
+        let mut blit_desc_set = page_desc_sets.pop().unwrap();
+
+        let (dither_desc_set_layout, dither_desc_set_pool, mut dither_desc_sets) =
+            easy::desc_sets::<B>(
+                &device,
+                vec![(
+                    vec![],
+                    vec![&intermediate_canvas.2, &dither_palette.2],
+                    vec![&sampler],
+                )],
+            );
+        let dither_desc_set = dither_desc_sets.pop().unwrap();
 
         let (pipeline_to_canvas, pipeline_layout_to_canvas) = easy::pipeline::<B>(
             &device,
@@ -302,6 +497,17 @@ impl<B: SupportedBackend> DrawContext<B> {
             &[4, 2, 3],
         );
 
+        let (pipeline_dither, pipeline_layout_dither) = easy::pipeline::<B>(
+            &device,
+            Some(&dither_desc_set_layout),
+            std::mem::size_of::<DitherParams>() as u32,
+            SHADER_SOURCES.0,
+            DITHER_SHADER_SOURCE,
+            &render_pass_to_surface,
+            None,
+            &[4, 2, 3],
+        );
+
         let submission_complete_fence = device.create_fence(true).expect("Out of memory");
         let rendering_complete_semaphore = device.create_semaphore().expect("Out of memory");
 
@@ -311,12 +517,12 @@ impl<B: SupportedBackend> DrawContext<B> {
                 surface,
                 command_pool,
                 vertex_buffer,
-                atlas_image,
+                atlas_pages,
                 sampler,
                 render_pass_to_canvas,
                 desc_set_layout,
                 desc_set_pool,
-                desc_set,
+                page_desc_sets,
                 blit_desc_set,
                 pipeline_layout_to_canvas,
                 pipeline_to_canvas,
@@ -327,6 +533,12 @@ impl<B: SupportedBackend> DrawContext<B> {
                 render_pass_to_surface,
                 pipeline_layout_to_surface,
                 pipeline_to_surface,
+                dither_palette,
+                dither_desc_set_layout,
+                dither_desc_set_pool,
+                dither_desc_set,
+                pipeline_layout_dither,
+                pipeline_dither,
             }),
             adapter,
             device,
@@ -337,12 +549,89 @@ impl<B: SupportedBackend> DrawContext<B> {
             scale_factor: dpi,
             framebuffer_attachment: None,
             swapchain_invalidated: Some(()),
-            texture_atlas,
+            present_mode: PresentMode::default(),
+            achieved_present_mode: None,
+            texture_atlas: pages.swap_remove(0),
             vertex_cache: Vec::with_capacity(VERTEX_BUFFER_LEN),
             canvas_config,
+            camera: [0., 0.],
+            screen_effects: None,
+            dither: None,
+            stats: DrawStats::default(),
+
+            #[cfg(all(feature = "reloading", debug_assertions, not(target_arch = "wasm32")))]
+            shader_watch: shader_dev::ShaderWatch::new(),
         })
     }
 
+    /// Rebuild the pipelines from the shaders on disk. If they fail to
+    /// compile, the error is reported with `reloading::report_error` and the
+    /// previous pipelines are kept.
+    ///
+    /// This happens automatically when the shader files change. It does
+    /// nothing without the `reloading` feature, or in release or wasm
+    /// builds, where the shaders are embedded.
+    pub fn reload_shaders(&mut self) -> Result<(), ()> {
+        #[cfg(all(feature = "reloading", debug_assertions, not(target_arch = "wasm32")))]
+        {
+            use crate::reloading::{clear_error, report_error};
+
+            let (vs_bytes, fs_bytes) = self.shader_watch.shaders();
+            let report = |message: String| {
+                report_error(shader_dev::SHADER_NAME, &(vs_bytes, fs_bytes), message)
+            };
+
+            let device = &self.device;
+            let resources = &mut *self.resources;
+
+            let make_pipeline = |render_pass| {
+                easy::try_pipeline::<B>(
+                    device,
+                    Some(&resources.desc_set_layout),
+                    0,
+                    vs_bytes,
+                    fs_bytes,
+                    render_pass,
+                    None,
+                    &[4, 2, 3],
+                )
+                .map_err(|e| report(e.to_string()))
+            };
+
+            let to_canvas = make_pipeline(&resources.render_pass_to_canvas)?;
+            let to_surface = match make_pipeline(&resources.render_pass_to_surface) {
+                Ok(pipeline) => pipeline,
+                Err(()) => unsafe {
+                    device.destroy_graphics_pipeline(to_canvas.0);
+                    device.destroy_pipeline_layout(to_canvas.1);
+                    return Err(());
+                },
+            };
+
+            unsafe {
+                device.wait_idle().map_err(|e| report(format!("{:?}", e)))?;
+
+                let old_pipeline =
+                    std::mem::replace(&mut resources.pipeline_to_canvas, to_canvas.0);
+                let old_layout =
+                    std::mem::replace(&mut resources.pipeline_layout_to_canvas, to_canvas.1);
+                device.destroy_graphics_pipeline(old_pipeline);
+                device.destroy_pipeline_layout(old_layout);
+
+                let old_pipeline =
+                    std::mem::replace(&mut resources.pipeline_to_surface, to_surface.0);
+                let old_layout =
+                    std::mem::replace(&mut resources.pipeline_layout_to_surface, to_surface.1);
+                device.destroy_graphics_pipeline(old_pipeline);
+                device.destroy_pipeline_layout(old_layout);
+            }
+
+            clear_error(shader_dev::SHADER_NAME);
+        }
+
+        Ok(())
+    }
+
     pub fn resolution_changed(&mut self, resolution: (u32, u32)) {
         self.surface_extent = hal::window::Extent2D {
             width: resolution.0,
@@ -360,7 +649,164 @@ impl<B: SupportedBackend> DrawContext<B> {
         self.canvas_config = canvas_config;
     }
 
+    /// Ask for a different present mode. It takes effect from the next
+    /// frame.
+    pub fn set_present_mode(&mut self, present_mode: PresentMode) {
+        if present_mode != self.present_mode {
+            self.present_mode = present_mode;
+            self.swapchain_invalidated = Some(());
+        }
+    }
+
+    /// The present mode in use, which is the closest the device supports
+    /// to the one asked for. `None` until the first frame is started.
+    pub fn present_mode(&self) -> Option<PresentMode> {
+        self.achieved_present_mode
+    }
+
+    /// Set the world-space position shown at the top-left of the canvas.
+    /// Only `Space::World` sprites are moved by it.
+    pub fn set_camera(&mut self, camera: [f32; 2]) {
+        self.camera = camera;
+    }
+
+    pub fn camera(&self) -> [f32; 2] {
+        self.camera
+    }
+
+    /// Apply `effects` to every frame: world-space sprites are moved by its
+    /// camera offset, and its overlay is drawn on top of everything using
+    /// `white`, a plain white region of the atlas.
+    pub fn install_screen_effects(&mut self, effects: ScreenEffects, white: Region) {
+        self.screen_effects = Some((effects, white));
+    }
+
+    /// The installed screen effects, to trigger or update.
+    pub fn screen_effects(&mut self) -> Option<&mut ScreenEffects> {
+        self.screen_effects.as_mut().map(|(effects, _)| effects)
+    }
+
+    pub fn uninstall_screen_effects(&mut self) -> Option<ScreenEffects> {
+        self.screen_effects.take().map(|(effects, _)| effects)
+    }
+
+    /// Quantize the canvas to a palette as it's drawn to the window, or
+    /// stop with `None`. Changing the palette uploads it, but changing just
+    /// the strength is free, so it can be animated.
+    ///
+    /// This happens in the final blit from the intermediate canvas, so
+    /// while a dither is set the canvas is drawn through one even in
+    /// `CanvasMode::Direct`, and on the web. `capture_canvas` still returns
+    /// the frame from before it's dithered.
+    pub fn set_dither(&mut self, dither: Option<Dither>) {
+        let new_palette = match (&self.dither, &dither) {
+            (_, None) => None,
+            (Some(old), Some(new)) if old.palette == new.palette => None,
+            (_, Some(new)) => Some(&new.palette),
+        };
+
+        if let Some(palette) = new_palette {
+            let Resources {
+                submission_complete_fence,
+                command_pool,
+                dither_palette,
+                ..
+            } = &mut *self.resources;
+
+            let colors = palette.colors();
+            let mut bytes = Vec::with_capacity(MAX_PALETTE_COLORS * 4);
+            for i in 0..MAX_PALETTE_COLORS {
+                let color = colors[i.min(colors.len() - 1)];
+                bytes.extend(
+                    color
+                        .iter()
+                        .map(|&c| (c.clamp(0., 1.) * 255.).round() as u8),
+                );
+            }
+
+            unsafe {
+                // We refuse to wait more than a second, to avoid hanging.
+                let render_timeout_ns = 1_000_000_000;
+
+                self.device
+                    .wait_for_fence(&submission_complete_fence, render_timeout_ns)
+                    .expect("Out of memory or device lost");
+
+                gfx::upload_image::<B>(
+                    &self.device,
+                    &self.adapter.physical_device,
+                    command_pool,
+                    &mut self.queue_group.queues[0],
+                    &dither_palette.1,
+                    (MAX_PALETTE_COLORS as u32, 1),
+                    &bytes,
+                );
+            }
+        }
+
+        self.dither = dither;
+    }
+
+    pub fn dither(&self) -> Option<&Dither> {
+        self.dither.as_ref()
+    }
+
+    /// How the last frame was drawn.
+    pub fn stats(&self) -> DrawStats {
+        self.stats
+    }
+
+    pub fn page_count(&self) -> usize {
+        self.resources.atlas_pages.len()
+    }
+
+    /// Read back the last frame drawn to the intermediate canvas, at its
+    /// physical size. Returns `None` in `Direct` mode (or on the web),
+    /// where there's no canvas image to read.
+    pub fn capture_canvas(&mut self) -> Option<RgbaImage> {
+        if cfg!(target_arch = "wasm32")
+            || self.canvas_config.canvas_mode != CanvasMode::Intermediate
+        {
+            return None;
+        }
+
+        let Resources {
+            submission_complete_fence,
+            command_pool,
+            intermediate_canvas,
+            intermediate_canvas_size,
+            ..
+        } = &mut *self.resources;
+
+        let [width, height] = *intermediate_canvas_size;
+
+        let bytes = unsafe {
+            // We refuse to wait more than a second, to avoid hanging.
+            let render_timeout_ns = 1_000_000_000;
+
+            self.device
+                .wait_for_fence(&submission_complete_fence, render_timeout_ns)
+                .expect("Out of memory or device lost");
+
+            gfx::download_image::<B>(
+                &self.device,
+                &self.adapter.physical_device,
+                command_pool,
+                &mut self.queue_group.queues[0],
+                &intermediate_canvas.1,
+                (width, height),
+            )
+        };
+
+        RgbaImage::from_raw(width, height, bytes)
+    }
+
     pub fn start_rendering(&mut self, clear_color: Color) -> Renderer<B> {
+        #[cfg(all(feature = "reloading", debug_assertions, not(target_arch = "wasm32")))]
+        if self.shader_watch.reload_if_changed() {
+            let _ = self.reload_shaders();
+        }
+
         let Resources {
             surface,
             submission_complete_fence,
@@ -371,6 +817,8 @@ impl<B: SupportedBackend> DrawContext<B> {
             intermediate_canvas_size,
             sampler,
             blit_desc_set,
+            dither_palette,
+            dither_desc_set,
             ..
         } = &mut *self.resources;
 
@@ -392,13 +840,16 @@ impl<B: SupportedBackend> DrawContext<B> {
         }
 
         if self.swapchain_invalidated.take().is_some() {
-            self.framebuffer_attachment = Some(easy::reconfigure_swapchain::<B>(
+            let (framebuffer_attachment, achieved) = easy::reconfigure_swapchain::<B>(
                 surface,
                 &self.adapter,
                 &self.device,
                 self.surface_color_format,
                 &mut self.surface_extent,
-            ));
+                self.present_mode,
+            );
+            self.framebuffer_attachment = Some(framebuffer_attachment);
+            self.achieved_present_mode = Some(achieved);
         }
 
         let canvas_properties = self.canvas_config.canvas_properties(
@@ -418,7 +869,7 @@ impl<B: SupportedBackend> DrawContext<B> {
                     &self.adapter.physical_device,
                     (intermediate_canvas_size[0], intermediate_canvas_size[1]),
                     Format::Rgba8Srgb,
-                    Usage::COLOR_ATTACHMENT | Usage::SAMPLED,
+                    Usage::COLOR_ATTACHMENT | Usage::SAMPLED | Usage::TRANSFER_SRC,
                     Aspects::COLOR,
                 );
 
@@ -435,6 +886,15 @@ impl<B: SupportedBackend> DrawContext<B> {
                     vec![blit_desc_set],
                     vec![(vec![], vec![&intermediate_canvas.2], vec![&sampler])],
                 );
+                easy::write_desc_sets::<B>(
+                    &self.device,
+                    vec![dither_desc_set],
+                    vec![(
+                        vec![],
+                        vec![&intermediate_canvas.2, &dither_palette.2],
+                        vec![&sampler],
+                    )],
+                );
             }
         }
 
@@ -476,15 +936,23 @@ impl<B: SupportedBackend> DrawContext<B> {
             clear_color,
             framebuffer_to_canvas,
             framebuffer_to_surface,
-            sprites: vec![
+            sprites: vec![(
                 Sprite {
                     pos: [0., 0.],
                     size: [0., 0.],
                     tint: [0., 0., 0., 0.],
+                    corner_tints: WHITE_CORNERS,
                     atlas_uv: ([0., 0.], [0., 0.]),
+                    orientation: Orientation::Upright,
                     angle: 0.,
-                }, // Note: Dummy sprite for fullscreen quad
-            ],
+                    page: 0,
+                    pixel_snap: false,
+                    corners: None,
+                },
+                Space::Screen,
+            )], // Note: Dummy sprite for fullscreen quad
+            space: Space::World,
+            interleave_spaces: false,
 
             #[cfg(feature = "font")]
             glyphs: vec![],
@@ -501,7 +969,7 @@ impl<B: SupportedBackend> Drop for DrawContext<B> {
                 mut surface,
                 command_pool,
                 vertex_buffer,
-                atlas_image,
+                atlas_pages,
                 sampler,
                 render_pass_to_canvas,
                 desc_set_layout,
@@ -515,10 +983,27 @@ impl<B: SupportedBackend> Drop for DrawContext<B> {
                 render_pass_to_surface,
                 pipeline_layout_to_surface,
                 pipeline_to_surface,
-                desc_set,
+                page_desc_sets,
                 blit_desc_set,
+                dither_palette,
+                dither_desc_set_layout,
+                dither_desc_set_pool,
+                dither_desc_set,
+                pipeline_layout_dither,
+                pipeline_dither,
             } = ManuallyDrop::take(&mut self.resources);
 
+            self.device.destroy_graphics_pipeline(pipeline_dither);
+            self.device.destroy_pipeline_layout(pipeline_layout_dither);
+            self.device.destroy_descriptor_pool(dither_desc_set_pool);
+            self.device
+                .destroy_descriptor_set_layout(dither_desc_set_layout);
+            {
+                let (mem, img, view) = dither_palette;
+                self.device.destroy_image_view(view);
+                self.device.destroy_image(img);
+                self.device.free_memory(mem);
+            }
             self.device.destroy_semaphore(rendering_complete_semaphore);
             self.device.destroy_fence(submission_complete_fence);
             self.device.destroy_graphics_pipeline(pipeline_to_canvas);
@@ -528,8 +1013,7 @@ impl<B: SupportedBackend> Drop for DrawContext<B> {
             self.device.destroy_descriptor_set_layout(desc_set_layout);
             self.device.destroy_render_pass(render_pass_to_canvas);
             self.device.destroy_sampler(sampler);
-            {
-                let (mem, img, view) = atlas_image;
+            for (mem, img, view) in atlas_pages {
                 self.device.destroy_image_view(view);
                 self.device.destroy_image(img);
                 self.device.free_memory(mem);
@@ -557,15 +1041,43 @@ pub struct Renderer<'a, B: SupportedBackend> {
         <B::Surface as PresentationSurface<B>>::SwapchainImage,
         Viewport,
     )>,
-    sprites: Vec<Sprite>,
+    sprites: Vec<(Sprite, Space)>,
+    space: Space,
+    interleave_spaces: bool,
 
     #[cfg(feature = "font")]
-    glyphs: Vec<(Glyph, [f32; 4])>,
+    glyphs: Vec<(Glyph, [f32; 4], Option<[f32; 4]>, Space)>,
 }
 
 impl<'a, B: SupportedBackend> Renderer<'a, B> {
+    /// Set the space for the sprites and glyphs submitted after this. Each
+    /// frame starts in `Space::World`.
+    pub fn set_space(&mut self, space: Space) {
+        self.space = space;
+    }
+
+    pub fn space(&self) -> Space {
+        self.space
+    }
+
+    /// By default, screen-space sprites are drawn over all world-space
+    /// ones. Interleaving draws them all in the order they were submitted
+    /// instead, e.g. for a screen-space background.
+    pub fn interleave_spaces(&mut self, interleave: bool) {
+        self.interleave_spaces = interleave;
+    }
+
     pub fn sprite(&mut self, sprite: Sprite) {
-        self.sprites.push(sprite);
+        self.sprites.push((sprite, self.space));
+    }
+
+    pub fn sprites<I>(&mut self, sprites: I)
+    where
+        I: IntoIterator<Item = Sprite>,
+    {
+        let space = self.space;
+        self.sprites
+            .extend(sprites.into_iter().map(|sprite| (sprite, space)));
     }
 
     // TODO: Can we maybe just _borrow_ Glyphs instead of
@@ -576,16 +1088,36 @@ impl<'a, B: SupportedBackend> Renderer<'a, B> {
         I: IntoIterator<Item = Glyph>,
     {
         for glyph in glyphs {
-            self.glyphs.push((glyph, tint));
+            self.glyphs.push((glyph, tint, None, self.space));
+        }
+    }
+
+    #[cfg(feature = "font")]
+    pub fn outlined_glyphs<I>(
+        &mut self,
+        glyphs: I,
+        tint: [f32; 4],
+        outline_tint: [f32; 4],
+        thickness: u32,
+    ) where
+        I: IntoIterator<Item = Glyph>,
+    {
+        for glyph in glyphs {
+            self.glyphs.push((
+                glyph.with_outline(thickness),
+                tint,
+                Some(outline_tint),
+                self.space,
+            ));
         }
     }
 
     #[cfg(feature = "font")]
     pub fn finish_with_text<A>(mut self, font_atlas: &mut A, atlas_image: Option<&mut RgbaImage>)
     where
-        A: Atlas<Glyph, Glyph, Option<GlyphRegion>, RgbaImage>,
+        A: Atlas<Glyph, Glyph, Option<GlyphRegion>, RgbaImage> + OutlineAtlas,
     {
-        for (glyph, _) in &self.glyphs {
+        for (glyph, _, _, _) in &self.glyphs {
             font_atlas.insert(glyph.clone());
         }
 
@@ -603,11 +1135,27 @@ impl<'a, B: SupportedBackend> Renderer<'a, B> {
             self.update_atlas(atlas_image);
         }
 
-        for (glyph, tint) in self.glyphs.drain(..) {
+        // Outlines go first so that they're drawn beneath all of the fills
+        for (glyph, _, outline_tint, space) in &self.glyphs {
+            if let Some(outline_tint) = outline_tint {
+                if let Some(outline_region) = font_atlas.fetch_outline(glyph) {
+                    let outline_sprite = Sprite {
+                        pixel_snap: glyph.pixel_snap,
+                        ..Sprite::glyph(outline_region, *outline_tint)
+                    };
+                    self.sprites.push((outline_sprite, *space));
+                }
+            }
+        }
+
+        for (glyph, tint, _, space) in self.glyphs.drain(..) {
             let glyph_region = font_atlas.fetch(&glyph);
             if let Some(glyph_region) = glyph_region {
-                let glyph_sprite = Sprite::glyph(glyph_region, tint);
-                self.sprites.push(glyph_sprite);
+                let glyph_sprite = Sprite {
+                    pixel_snap: glyph.pixel_snap,
+                    ..Sprite::glyph(glyph_region, tint)
+                };
+                self.sprites.push((glyph_sprite, space));
             }
         }
     }
@@ -618,7 +1166,7 @@ impl<'a, B: SupportedBackend> Renderer<'a, B> {
 
         let Resources {
             command_pool,
-            atlas_image,
+            atlas_pages,
             ..
         } = &mut *self.context.resources;
 
@@ -628,12 +1176,109 @@ impl<'a, B: SupportedBackend> Renderer<'a, B> {
                 &self.context.adapter.physical_device,
                 command_pool,
                 &mut self.context.queue_group.queues[0],
-                &atlas_image.1,
+                &atlas_pages[0].1,
                 dimensions,
                 &self.context.texture_atlas,
             );
         }
     }
+
+    /// Replace every page of the atlas, e.g. with the images from
+    /// `ImageAtlas::compile_pages` after it's changed. Pages past the ones
+    /// the context has are added, so the atlas can grow.
+    pub fn update_pages(&mut self, pages: Vec<RgbaImage>) {
+        let page_count = self.context.page_count();
+        if pages.len() > page_count {
+            self.add_pages(&pages[page_count..]);
+        }
+
+        for (page, new_page) in pages.into_iter().enumerate() {
+            self.update_page(page, new_page);
+        }
+    }
+
+    /// Make room for `pages` after the existing ones. They still need
+    /// uploading.
+    fn add_pages(&mut self, pages: &[RgbaImage]) {
+        let Resources {
+            atlas_pages,
+            sampler,
+            desc_set_layout,
+            desc_set_pool,
+            page_desc_sets,
+            blit_desc_set,
+            intermediate_canvas,
+            ..
+        } = &mut *self.context.resources;
+
+        for page in pages {
+            use hal::format::Aspects;
+            use hal::image::Usage;
+
+            atlas_pages.push(unsafe {
+                gfx::make_image::<B>(
+                    &self.context.device,
+                    &self.context.adapter.physical_device,
+                    page.dimensions(),
+                    texture_format(self.context.surface_color_format),
+                    Usage::SAMPLED | Usage::TRANSFER_DST,
+                    Aspects::COLOR,
+                )
+            });
+        }
+
+        // The pool can't grow, so every set is made again in a bigger one.
+        // The last frame has finished by now, so the old sets aren't in use.
+        let mut desc_set_values = atlas_pages
+            .iter()
+            .map(|atlas_image| (vec![], vec![&atlas_image.2], vec![&*sampler]))
+            .collect::<Vec<_>>();
+        desc_set_values.push((vec![], vec![&intermediate_canvas.2], vec![&*sampler]));
+
+        let (new_pool, mut new_desc_sets) =
+            easy::desc_pool::<B>(&self.context.device, desc_set_layout, desc_set_values);
+
+        *blit_desc_set = new_desc_sets.pop().unwrap();
+        *page_desc_sets = new_desc_sets;
+        let old_pool = std::mem::replace(desc_set_pool, new_pool);
+        unsafe {
+            self.context.device.destroy_descriptor_pool(old_pool);
+        }
+    }
+
+    /// Replace one page of a multi-page atlas. Page 0 is the same as
+    /// `update_atlas`.
+    pub fn update_page(&mut self, page: usize, new_page: RgbaImage) {
+        if page == 0 {
+            return self.update_atlas(new_page);
+        }
+
+        let Resources {
+            command_pool,
+            atlas_pages,
+            ..
+        } = &mut *self.context.resources;
+
+        let atlas_image = atlas_pages.get(page).unwrap_or_else(|| {
+            panic!(
+                "Page {} is out of range: the context has {} pages",
+                page,
+                atlas_pages.len()
+            )
+        });
+
+        unsafe {
+            gfx::upload_image::<B>(
+                &self.context.device,
+                &self.context.adapter.physical_device,
+                command_pool,
+                &mut self.context.queue_group.queues[0],
+                &atlas_image.1,
+                new_page.dimensions(),
+                &new_page,
+            );
+        }
+    }
 }
 
 impl<'a, B: SupportedBackend> Drop for Renderer<'a, B> {
@@ -656,8 +1301,11 @@ impl<'a, B: SupportedBackend> Drop for Renderer<'a, B> {
             pipeline_layout_to_surface,
             pipeline_to_surface,
             render_pass_to_surface,
-            desc_set,
+            page_desc_sets,
             blit_desc_set,
+            dither_desc_set,
+            pipeline_layout_dither,
+            pipeline_dither,
             ..
         } = &mut *self.context.resources;
 
@@ -669,14 +1317,85 @@ impl<'a, B: SupportedBackend> Drop for Renderer<'a, B> {
             self.context.scale_factor,
         );
 
+        // The sort is stable, so each space keeps its own order. The first
+        // sprite is the fullscreen quad, which has to stay first.
+        if !self.interleave_spaces {
+            self.sprites[1..].sort_by_key(|&(_, space)| space);
+        }
+
+        let [camera_x, camera_y] = self.context.camera;
+        let world_offset = match &self.context.screen_effects {
+            Some((effects, white)) => {
+                let [sx, sy] = effects.camera_offset();
+
+                if let Some(color) = effects.overlay() {
+                    let [w, h] = canvas_properties.logical_canvas_size;
+                    self.sprites.push((
+                        Sprite::sized(*white, [0., 0.], color, [w as f32, h as f32]),
+                        Space::Screen,
+                    ));
+                }
+                [sx - camera_x, sy - camera_y]
+            }
+            None => [-camera_x, -camera_y],
+        };
+
         // TODO: Dynamically grow vertex buffer?
         assert!(self.sprites.len() <= MAX_SPRITES);
 
+        // Consecutive sprites on the same page are drawn together. The
+        // first sprite is the fullscreen quad, which isn't drawn here.
+        // Sprites on pages that haven't been uploaded are skipped.
+        let mut batches: Vec<(usize, std::ops::Range<u32>)> = vec![];
+        for (i, (sprite, _)) in self.sprites.iter().enumerate().skip(1) {
+            if sprite.page >= page_desc_sets.len() {
+                continue;
+            }
+
+            let [start, end] = [i as u32 * 6, i as u32 * 6 + 6];
+            match batches.last_mut() {
+                Some((page, range)) if *page == sprite.page && range.end == start => {
+                    range.end = end
+                }
+                _ => batches.push((sprite.page, start..end)),
+            }
+        }
+
+        self.context.stats = DrawStats {
+            sprites: self.sprites.len() - 1,
+            batches: batches.len(),
+            page_switches: batches.len().saturating_sub(1),
+        };
+
         let verts = &mut self.context.vertex_cache;
         verts.clear(); // TODO: Maybe actually cache?
 
         let [canvas_width, canvas_height] = canvas_properties.logical_canvas_size;
 
+        // Dithering happens in the blit from the intermediate canvas, so it
+        // needs one whatever the mode
+        let intermediate_mode = match self.context.canvas_config.canvas_mode {
+            _ if self.context.dither.is_some() => true,
+            CanvasMode::Intermediate if cfg!(target_arch = "wasm32") => false,
+            CanvasMode::Intermediate => true,
+            CanvasMode::Direct => false,
+        };
+
+        // Snapped sprites are rounded to the pixels that the canvas is
+        // drawn onto: the intermediate canvas, or the viewport otherwise
+        let [pixels_x, pixels_y] = match intermediate_mode {
+            true => canvas_properties.physical_canvas_size,
+            false => {
+                let (_, [w, h]) = canvas_properties.viewport_scissor_rect;
+                [w as u32, h as u32]
+            }
+        };
+        let pixel_ratio = [
+            pixels_x as f32 / canvas_width as f32,
+            pixels_y as f32 / canvas_height as f32,
+        ];
+        let snap = |value: f32, ratio: f32| (value * ratio).round() / ratio;
+
         let scale_x = (2.0 / canvas_width as f64) as f32;
         let scale_y = (2.0 / canvas_height as f64) as f32;
 
@@ -695,37 +1414,57 @@ impl<'a, B: SupportedBackend> Drop for Renderer<'a, B> {
             }
         };
 
-        for sprite in &self.sprites {
-            let tint = if is_srgb(self.context.surface_color_format) {
-                gfx::srgb_to_linear(sprite.tint)
-            } else {
-                sprite.tint
-            };
-            let [x, y] = sprite.pos;
-            let [w, h] = sprite.size;
-            let [cx, cy] = [x + w / 2., y + h / 2.];
-            let ([u0, v0], [uw, vh]) = sprite.atlas_uv;
-            let (s, c) = sprite.angle.sin_cos();
-            let p0 = Vertex {
-                offset: project(x, y, cx, cy, c, s),
-                tint: tint,
-                uv: [u0, v0],
+        let srgb = is_srgb(self.context.surface_color_format);
+
+        for (sprite, space) in &self.sprites {
+            let [ox, oy] = match space {
+                Space::World => world_offset,
+                Space::Screen => [0., 0.],
             };
-            let p1 = Vertex {
-                offset: project(x, y + h, cx, cy, c, s),
-                tint: tint,
-                uv: [u0, v0 + vh],
+            let corner_tint = |corner: usize| {
+                let [r, g, b, a] = sprite.tint;
+                let [cr, cg, cb, ca] = sprite.corner_tints[corner];
+                let tint = [r * cr, g * cg, b * cb, a * ca];
+                if srgb {
+                    gfx::srgb_to_linear(tint)
+                } else {
+                    tint
+                }
             };
-            let p2 = Vertex {
-                offset: project(x + w, y + h, cx, cy, c, s),
-                tint: tint,
-                uv: [u0 + uw, v0 + vh],
+            // Top-left, top-right, bottom-left, then bottom-right
+            let offsets = match sprite.corners {
+                Some(corners) => corners.map(|[x, y]| {
+                    let [mut x, mut y] = [x + ox, y + oy];
+                    if sprite.pixel_snap {
+                        x = snap(x, pixel_ratio[0]);
+                        y = snap(y, pixel_ratio[1]);
+                    }
+                    project(x, y, x, y, 1., 0.)
+                }),
+                None => {
+                    let [mut x, mut y] = [sprite.pos[0] + ox, sprite.pos[1] + oy];
+                    if sprite.pixel_snap {
+                        x = snap(x, pixel_ratio[0]);
+                        y = snap(y, pixel_ratio[1]);
+                    }
+                    let [w, h] = sprite.size;
+                    let [cx, cy] = [x + w / 2., y + h / 2.];
+                    let (s, c) = sprite.angle.sin_cos();
+                    [
+                        project(x, y, cx, cy, c, s),
+                        project(x + w, y, cx, cy, c, s),
+                        project(x, y + h, cx, cy, c, s),
+                        project(x + w, y + h, cx, cy, c, s),
+                    ]
+                }
             };
-            let p3 = Vertex {
-                offset: project(x + w, y, cx, cy, c, s),
-                tint: tint,
-                uv: [u0 + uw, v0],
+            let uvs = sprite.orientation.corner_uvs(sprite.atlas_uv);
+            let corner = |i: usize| Vertex {
+                offset: offsets[i],
+                tint: corner_tint(i),
+                uv: uvs[i],
             };
+            let [p0, p1, p2, p3] = [corner(0), corner(2), corner(3), corner(1)];
             verts.push(p0);
             verts.push(p1);
             verts.push(p2);
@@ -819,12 +1558,6 @@ impl<'a, B: SupportedBackend> Drop for Renderer<'a, B> {
             depth: 0.0..1.0,
         };
 
-        let intermediate_mode = match self.context.canvas_config.canvas_mode {
-            CanvasMode::Intermediate if cfg!(target_arch = "wasm32") => false,
-            CanvasMode::Intermediate => true,
-            CanvasMode::Direct => false,
-        };
-
         if let Some((framebuffer, surface_image, _)) = self.framebuffer_to_surface.take() {
             use std::borrow::Borrow;
 
@@ -898,13 +1631,6 @@ impl<'a, B: SupportedBackend> Drop for Renderer<'a, B> {
                         SubpassContents::Inline,
                     );
 
-                    self.context.command_buffer.bind_graphics_descriptor_sets(
-                        pipeline_layout,
-                        0,
-                        over([&*desc_set]),
-                        over([]),
-                    );
-
                     self.context.command_buffer.bind_vertex_buffers(
                         0,
                         over([(
@@ -918,8 +1644,16 @@ impl<'a, B: SupportedBackend> Drop for Renderer<'a, B> {
 
                     self.context.command_buffer.bind_graphics_pipeline(pipeline);
 
-                    let num_verts = verts.len() as u32;
-                    self.context.command_buffer.draw(6..num_verts, 0..1);
+                    for (page, range) in &batches {
+                        self.context.command_buffer.bind_graphics_descriptor_sets(
+                            pipeline_layout,
+                            0,
+                            over([&page_desc_sets[*page]]),
+                            over([]),
+                        );
+
+                        self.context.command_buffer.draw(range.clone(), 0..1);
+                    }
 
                     self.context.command_buffer.end_render_pass();
                 }
@@ -963,14 +1697,41 @@ impl<'a, B: SupportedBackend> Drop for Renderer<'a, B> {
                         SubpassContents::Inline,
                     );
 
-                    self.context.command_buffer.bind_graphics_pipeline(pipeline);
-
-                    self.context.command_buffer.bind_graphics_descriptor_sets(
-                        pipeline_layout,
-                        0,
-                        over([&*blit_desc_set]),
-                        over([]),
-                    );
+                    match &self.context.dither {
+                        Some(dither) => {
+                            use gfx_hal::pso::ShaderStageFlags;
+
+                            let params = DitherParams {
+                                strength: dither.strength,
+                                palette_size: dither.palette.colors().len() as f32,
+                            };
+
+                            self.context
+                                .command_buffer
+                                .bind_graphics_pipeline(pipeline_dither);
+                            self.context.command_buffer.bind_graphics_descriptor_sets(
+                                pipeline_layout_dither,
+                                0,
+                                over([&*dither_desc_set]),
+                                over([]),
+                            );
+                            self.context.command_buffer.push_graphics_constants(
+                                pipeline_layout_dither,
+                                ShaderStageFlags::VERTEX | ShaderStageFlags::FRAGMENT,
+                                0,
+                                gfx::push_constant_bytes(&params),
+                            );
+                        }
+                        None => {
+                            self.context.command_buffer.bind_graphics_pipeline(pipeline);
+                            self.context.command_buffer.bind_graphics_descriptor_sets(
+                                pipeline_layout,
+                                0,
+                                over([&*blit_desc_set]),
+                                over([]),
+                            );
+                        }
+                    }
 
                     self.context.command_buffer.draw(0..6, 0..1);
 
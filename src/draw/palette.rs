@@ -0,0 +1,77 @@
+/// The most colors a `Palette` can have.
+pub const MAX_PALETTE_COLORS: usize = 256;
+
+/// A fixed set of colors, e.g. to quantize a frame to with `Dither`.
+/// Colors are in sRGB, like sprite tints.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Palette {
+    colors: Vec<[f32; 4]>,
+}
+
+impl Palette {
+    pub fn new(colors: Vec<[f32; 4]>) -> Self {
+        assert!(
+            !colors.is_empty() && colors.len() <= MAX_PALETTE_COLORS,
+            "A palette needs between 1 and {} colors, not {}",
+            MAX_PALETTE_COLORS,
+            colors.len()
+        );
+        Palette { colors }
+    }
+
+    /// A palette of opaque colors written like `0x9bbc0f`.
+    pub fn from_hex(colors: &[u32]) -> Self {
+        let channel = |color: u32, shift: u32| ((color >> shift) & 0xff) as f32 / 255.;
+        Self::new(
+            colors
+                .iter()
+                .map(|&color| [channel(color, 16), channel(color, 8), channel(color, 0), 1.])
+                .collect(),
+        )
+    }
+
+    pub fn colors(&self) -> &[[f32; 4]] {
+        &self.colors
+    }
+
+    /// Black and white.
+    pub fn one_bit() -> Self {
+        Self::from_hex(&[0x000000, 0xffffff])
+    }
+
+    /// The four greens of the original Game Boy.
+    pub fn game_boy() -> Self {
+        Self::from_hex(&[0x0f380f, 0x306230, 0x8bac0f, 0x9bbc0f])
+    }
+
+    /// CGA's high-intensity palette 1: black, cyan, magenta and white.
+    pub fn cga() -> Self {
+        Self::from_hex(&[0x000000, 0x55ffff, 0xff55ff, 0xffffff])
+    }
+
+    /// The PICO-8 fantasy console's sixteen colors.
+    pub fn pico8() -> Self {
+        Self::from_hex(&[
+            0x000000, 0x1d2b53, 0x7e2553, 0x008751, 0xab5236, 0x5f574f, 0xc2c3c7, 0xfff1e8,
+            0xff004d, 0xffa300, 0xffec27, 0x00e436, 0x29adff, 0x83769c, 0xff77a8, 0xffccaa,
+        ])
+    }
+}
+
+/// Quantize each frame to a palette, using ordered (Bayer) dithering to
+/// fake the colors in between.
+///
+/// `strength` is how far colors are nudged before picking the nearest
+/// palette entry: `0.0` just picks the nearest, and `1.0` suits a palette
+/// with only black and white. Palettes with more colors need less.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dither {
+    pub palette: Palette,
+    pub strength: f32,
+}
+
+impl Dither {
+    pub fn new(palette: Palette, strength: f32) -> Self {
+        Dither { palette, strength }
+    }
+}
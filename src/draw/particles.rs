@@ -0,0 +1,246 @@
+use crate::{
+    draw::{
+        groove::{Renderer, Sprite},
+        Region,
+    },
+    gfx::SupportedBackend,
+};
+
+/// A value that changes over a particle's life, from `t = 0` at spawn to
+/// `t = 1` when it dies.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Curve {
+    Constant(f32),
+    Linear(f32, f32),
+    /// `(t, value)` pairs in order of `t`, linearly interpolated.
+    Keys(Vec<(f32, f32)>),
+}
+
+impl Curve {
+    pub fn at(&self, t: f32) -> f32 {
+        match self {
+            Curve::Constant(value) => *value,
+            Curve::Linear(start, end) => start + (end - start) * t,
+            Curve::Keys(keys) => {
+                let next = keys.iter().position(|&(kt, _)| kt > t);
+                match next {
+                    Some(0) => keys[0].1,
+                    Some(i) => {
+                        let (t0, v0) = keys[i - 1];
+                        let (t1, v1) = keys[i];
+                        match t1 > t0 {
+                            true => v0 + (v1 - v0) * (t - t0) / (t1 - t0),
+                            false => v1,
+                        }
+                    }
+                    None => keys.last().map(|&(_, v)| v).unwrap_or(1.),
+                }
+            }
+        }
+    }
+}
+
+/// How a `Particles` emitter spawns and moves its particles. Ranges are
+/// `(min, max)`, and each particle picks a value in between.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EmitterConfig {
+    /// Particles spawned per second while emitting.
+    pub rate: f32,
+    /// How long each particle lives, in seconds.
+    pub lifetime: (f32, f32),
+    /// Initial speed, in pixels per second.
+    pub speed: (f32, f32),
+    /// The middle of the cone particles are launched in, in radians.
+    /// Zero is to the right, and positive angles go down the screen.
+    pub direction: f32,
+    /// How far either side of `direction` particles can be launched.
+    pub spread: f32,
+    /// Acceleration, in pixels per second squared.
+    pub gravity: [f32; 2],
+    /// Multiplies the size of the region.
+    pub size: Curve,
+    /// Multiplies the alpha of `tint`.
+    pub alpha: Curve,
+    pub tint: [f32; 4],
+    /// The region each particle is drawn with. With more than one, they're
+    /// played in order over the particle's life, like an animation.
+    pub frames: Vec<Region>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Particle {
+    pos: [f32; 2],
+    vel: [f32; 2],
+    age: f32,
+    lifetime: f32,
+}
+
+/// A particle emitter. Particles are stored in a pool allocated up front,
+/// so spawning them never allocates.
+///
+/// NOTE: Particles are drawn as ordinary sprites, so together with
+/// everything else, a frame is still limited to `groove::MAX_SPRITES`.
+#[derive(Debug, Clone)]
+pub struct Particles {
+    pub config: EmitterConfig,
+    /// Where new particles are spawned.
+    pub position: [f32; 2],
+    /// Whether particles are spawned at `config.rate`. Bursts are spawned
+    /// either way.
+    pub emitting: bool,
+    pool: Vec<Particle>,
+    capacity: usize,
+    spawn_debt: f32,
+    rng_state: u32,
+}
+
+impl Particles {
+    /// An emitter that never has more than `capacity` particles alive.
+    /// Any spawned beyond that are dropped.
+    pub fn new(config: EmitterConfig, position: [f32; 2], capacity: usize) -> Self {
+        Particles {
+            config,
+            position,
+            emitting: true,
+            pool: Vec::with_capacity(capacity),
+            capacity,
+            spawn_debt: 0.,
+            rng_state: 1,
+        }
+    }
+
+    /// Use a different seed for the random numbers. Emitters with the same
+    /// seed, config, and updates produce the same particles.
+    pub fn with_seed(self, seed: u32) -> Self {
+        Particles {
+            // Xorshift gets stuck on zero
+            rng_state: seed.max(1),
+            ..self
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.pool.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pool.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.pool.clear();
+    }
+
+    /// Spawn `count` particles at once.
+    pub fn burst(&mut self, count: usize) {
+        for _ in 0..count {
+            self.spawn();
+        }
+    }
+
+    /// Advance every particle by `dt` seconds, remove any that have died,
+    /// and spawn new ones.
+    pub fn update(&mut self, dt: f32) {
+        let [gx, gy] = self.config.gravity;
+
+        for particle in &mut self.pool {
+            particle.age += dt;
+            particle.vel[0] += gx * dt;
+            particle.vel[1] += gy * dt;
+            particle.pos[0] += particle.vel[0] * dt;
+            particle.pos[1] += particle.vel[1] * dt;
+        }
+
+        // Particles stay in spawn order, so the draw order doesn't flicker
+        self.pool
+            .retain(|particle| particle.age < particle.lifetime);
+
+        if self.emitting {
+            self.spawn_debt += self.config.rate * dt;
+            while self.spawn_debt >= 1. {
+                self.spawn_debt -= 1.;
+                self.spawn();
+            }
+        }
+    }
+
+    fn spawn(&mut self) {
+        if self.pool.len() >= self.capacity {
+            return;
+        }
+
+        let lifetime = self.random_in(self.config.lifetime);
+        let speed = self.random_in(self.config.speed);
+        let spread = self.config.spread;
+        let angle = self.config.direction + self.random_in((-spread, spread));
+        let (s, c) = angle.sin_cos();
+
+        self.pool.push(Particle {
+            pos: self.position,
+            vel: [c * speed, s * speed],
+            age: 0.,
+            lifetime,
+        });
+    }
+
+    fn random_in(&mut self, (min, max): (f32, f32)) -> f32 {
+        let mut x = self.rng_state;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.rng_state = x;
+
+        let t = (x >> 8) as f32 / (1 << 24) as f32;
+        min + (max - min) * t
+    }
+
+    /// The sprites for every live particle, centered on their positions.
+    pub fn sprites(&self) -> impl Iterator<Item = Sprite> + '_ {
+        let config = &self.config;
+        let [r, g, b, a] = config.tint;
+
+        self.pool.iter().filter_map(move |particle| {
+            let t = particle.age / particle.lifetime;
+            let frame = (t * config.frames.len() as f32) as usize;
+            let region = *config.frames.get(frame).or_else(|| config.frames.last())?;
+
+            let [w, h] = region.size();
+            let scale = config.size.at(t);
+            let [w, h] = [w as f32 * scale, h as f32 * scale];
+            let [x, y] = particle.pos;
+            let alpha = a * config.alpha.at(t);
+
+            Some(Sprite::sized(
+                region,
+                [x - w / 2., y - h / 2.],
+                [r, g, b, alpha],
+                [w, h],
+            ))
+        })
+    }
+
+    pub fn draw<B: SupportedBackend>(&self, renderer: &mut Renderer<B>) {
+        renderer.sprites(self.sprites());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_interpolate_between_neighbours() {
+        let curve = Curve::Keys(vec![(0., 0.), (0.5, 1.), (1., 0.)]);
+        assert_eq!(curve.at(0.25), 0.5);
+        assert_eq!(curve.at(0.75), 0.5);
+        assert_eq!(curve.at(1.), 0.);
+    }
+
+    #[test]
+    fn keys_at_the_same_time_make_a_step() {
+        let curve = Curve::Keys(vec![(0., 0.), (0.5, 0.), (0.5, 1.), (1., 1.)]);
+        assert_eq!(curve.at(0.25), 0.);
+        assert_eq!(curve.at(0.5), 1.);
+        assert_eq!(curve.at(0.75), 1.);
+    }
+}
@@ -0,0 +1,426 @@
+use std::collections::VecDeque;
+use std::f32::consts::PI;
+
+use crate::{
+    draw::{
+        groove::{Renderer, Sprite},
+        particles::Curve,
+        Region,
+    },
+    gfx::SupportedBackend,
+};
+
+/// Points closer than this to the head are dropped, so that segments
+/// always have a direction.
+const MIN_SEGMENT: f32 = 0.01;
+
+/// How far a miter join can stick out, as a multiple of half the width,
+/// before it's cut off flat.
+const MITER_LIMIT: f32 = 4.;
+
+/// Round joins are only rounded for turns sharper than this, in radians.
+/// Gentler ones are mitered, which looks the same.
+const ROUND_ABOVE: f32 = PI / 9.;
+
+/// The largest angle covered by one triangle of a round join.
+const ROUND_STEP: f32 = PI / 8.;
+
+/// How corners are drawn where a ribbon turns.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Join {
+    /// The edges are extended until they meet. Turns too sharp for that
+    /// are cut off flat.
+    #[default]
+    Miter,
+    /// Sharp turns are rounded off.
+    Round,
+}
+
+/// How a `Ribbon` looks, and how long its trail is kept.
+///
+/// `width` and `alpha` are curves over `t`, which is 0 at the head and 1
+/// where the point will be dropped: whichever of `max_age` and
+/// `max_length` it reaches first. With neither, `t` goes from 0 to 1 along
+/// the ribbon.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RibbonConfig {
+    /// Stretched along the ribbon, with its left edge at the head. For a
+    /// solid color, use a region of solid white pixels.
+    pub region: Region,
+    pub tint: [f32; 4],
+    /// The full width, in pixels.
+    pub width: Curve,
+    /// Multiplies the alpha of `tint`.
+    pub alpha: Curve,
+    /// How long points last, in seconds.
+    pub max_age: Option<f32>,
+    /// How long the ribbon can be, in pixels. The tail is trimmed to fit.
+    pub max_length: Option<f32>,
+    pub join: Join,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Point {
+    pos: [f32; 2],
+    age: f32,
+}
+
+/// A strip through the recent positions of something, for trails behind
+/// projectiles and sword swipes. Push a point each frame and the ribbon is
+/// drawn through them, newest first, thinning and fading toward the tail
+/// as `RibbonConfig` says.
+///
+/// NOTE: Each segment of the ribbon (and each triangle of a round join) is
+/// drawn as a sprite, so they count toward `groove::MAX_SPRITES`.
+#[derive(Debug, Clone)]
+pub struct Ribbon {
+    pub config: RibbonConfig,
+    /// Newest first.
+    points: VecDeque<Point>,
+    max_points: usize,
+}
+
+impl Ribbon {
+    /// A ribbon through at most `max_points` points. Pushing more drops
+    /// the oldest.
+    pub fn new(config: RibbonConfig, max_points: usize) -> Self {
+        Ribbon {
+            config,
+            points: VecDeque::with_capacity(max_points),
+            max_points,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.points.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.points.clear();
+    }
+
+    /// Add a new head to the ribbon. It's ignored if it's in the same place
+    /// as the current head.
+    pub fn push(&mut self, pos: [f32; 2]) {
+        if let Some(head) = self.points.front() {
+            if distance(head.pos, pos) < MIN_SEGMENT {
+                return;
+            }
+        }
+
+        self.points.push_front(Point { pos, age: 0. });
+        self.points.truncate(self.max_points);
+        self.trim_length();
+    }
+
+    /// Age every point by `dt` seconds, and drop any past `max_age`.
+    pub fn update(&mut self, dt: f32) {
+        for point in &mut self.points {
+            point.age += dt;
+        }
+
+        let max_age = match self.config.max_age {
+            Some(max_age) => max_age,
+            None => return,
+        };
+        let expired = match self.points.iter().position(|p| p.age >= max_age) {
+            Some(0) => return self.clear(),
+            Some(i) => i,
+            None => return,
+        };
+
+        // Move the first expired point back to where it would be exactly
+        // `max_age` old, so the tail shrinks smoothly
+        let (young, old) = (self.points[expired - 1], self.points[expired]);
+        let f = (max_age - young.age) / (old.age - young.age);
+        self.shorten_tail(expired, lerp(young.pos, old.pos, f), max_age);
+    }
+
+    fn trim_length(&mut self) {
+        let max_length = match self.config.max_length {
+            Some(max_length) => max_length,
+            None => return,
+        };
+
+        let mut length = 0.;
+        for i in 1..self.points.len() {
+            let (prev, point) = (self.points[i - 1], self.points[i]);
+            let segment = distance(prev.pos, point.pos);
+            if length + segment > max_length {
+                let f = (max_length - length) / segment;
+                let age = prev.age + (point.age - prev.age) * f;
+                self.shorten_tail(i, lerp(prev.pos, point.pos, f), age);
+                return;
+            }
+            length += segment;
+        }
+    }
+
+    /// Make point `i` the tail, moved to `pos`, or drop it if that's on
+    /// top of the point before.
+    fn shorten_tail(&mut self, i: usize, pos: [f32; 2], age: f32) {
+        if distance(self.points[i - 1].pos, pos) < MIN_SEGMENT {
+            self.points.truncate(i);
+        } else {
+            self.points[i] = Point { pos, age };
+            self.points.truncate(i + 1);
+        }
+    }
+
+    /// `t` for the point `dist` pixels from the head.
+    fn t(&self, point: &Point, dist: f32, total_length: f32) -> f32 {
+        let by_age = self.config.max_age.map(|max_age| point.age / max_age);
+        let by_length = self.config.max_length.map(|max_length| dist / max_length);
+        let t = match (by_age, by_length) {
+            (None, None) if total_length > 0. => dist / total_length,
+            (None, None) => 0.,
+            (by_age, by_length) => by_age.unwrap_or(0.).max(by_length.unwrap_or(0.)),
+        };
+        t.min(1.)
+    }
+
+    /// The edges of the ribbon at point `i`, which is `dist` from the head.
+    fn joint(&self, i: usize, dist: f32, total_length: f32) -> Joint {
+        let point = &self.points[i];
+        let t = self.t(point, dist, total_length);
+        let half_width = self.config.width.at(t) / 2.;
+        let alpha = self.config.alpha.at(t);
+        let u = match total_length > 0. {
+            true => dist / total_length,
+            false => 0.,
+        };
+
+        let dir_in = i
+            .checked_sub(1)
+            .map(|prev| direction(self.points[prev].pos, point.pos));
+        let dir_out = self
+            .points
+            .get(i + 1)
+            .map(|next| direction(point.pos, next.pos));
+
+        let edges = |normal: [f32; 2], half_width: f32| {
+            let offset = scale(normal, half_width);
+            [add(point.pos, offset), sub(point.pos, offset)]
+        };
+        let straight = |edges| Joint {
+            end: edges,
+            start: edges,
+            fan: None,
+            u,
+            alpha,
+        };
+
+        let (dir_in, dir_out) = match (dir_in, dir_out) {
+            (Some(dir_in), Some(dir_out)) => (dir_in, dir_out),
+            (Some(dir), None) | (None, Some(dir)) => {
+                return straight(edges(normal(dir), half_width))
+            }
+            (None, None) => return straight([point.pos; 2]),
+        };
+
+        // The cosine of half the angle turned
+        let cos_half = ((1. + dot(dir_in, dir_out)) / 2.).max(0.).sqrt();
+        let miter_above = match self.config.join {
+            Join::Miter => 1. / MITER_LIMIT,
+            Join::Round => (ROUND_ABOVE / 2.).cos(),
+        };
+        if cos_half >= miter_above {
+            let miter = normal(normalize(add(dir_in, dir_out)));
+            return straight(edges(miter, half_width / cos_half));
+        }
+
+        // The segments end square, and the gap on the outside of the turn
+        // is filled with triangles around the point
+        let (normal_in, normal_out) = (normal(dir_in), normal(dir_out));
+        let outer_top = dot(dir_out, normal_in) < 0.;
+        let side = if outer_top { half_width } else { -half_width };
+        let from = scale(normal_in, side);
+        let to = scale(normal_out, side);
+        let (angle, steps) = match self.config.join {
+            Join::Miter => (cross(from, to).atan2(dot(from, to)), 1),
+            Join::Round => {
+                let angle = cross(from, to).atan2(dot(from, to));
+                (angle, (angle.abs() / ROUND_STEP).ceil().max(1.) as usize)
+            }
+        };
+
+        Joint {
+            end: edges(normal_in, half_width),
+            start: edges(normal_out, half_width),
+            fan: Some(Fan {
+                center: point.pos,
+                from,
+                angle,
+                steps,
+                outer_top,
+            }),
+            u,
+            alpha,
+        }
+    }
+
+    /// The sprites for the ribbon, from the head to the tail.
+    pub fn sprites(&self) -> impl Iterator<Item = Sprite> + '_ {
+        let config = &self.config;
+        let total_length = self
+            .points
+            .iter()
+            .zip(self.points.iter().skip(1))
+            .map(|(a, b)| distance(a.pos, b.pos))
+            .sum::<f32>();
+
+        let mut head = None;
+        (1..self.points.len()).flat_map(move |i| {
+            let (prev, prev_dist) = head
+                .take()
+                .unwrap_or_else(|| (self.joint(0, 0., total_length), 0.));
+            let dist = prev_dist + distance(self.points[i - 1].pos, self.points[i].pos);
+            let joint = self.joint(i, dist, total_length);
+
+            let segment = segment_sprite(config, &prev, &joint);
+            let fan = joint.fan.unwrap_or(Fan::NONE);
+            let fan_sprites = (0..fan.steps)
+                .map(move |step| fan_sprite(config, &fan, step, joint.u, joint.alpha));
+            head = Some((joint, dist));
+
+            std::iter::once(segment).chain(fan_sprites)
+        })
+    }
+
+    pub fn draw<B: SupportedBackend>(&self, renderer: &mut Renderer<B>) {
+        renderer.sprites(self.sprites());
+    }
+}
+
+/// The ribbon's edges at a point, as `[top, bottom]`: where the segment
+/// before it ends, and where the one after starts.
+#[derive(Debug, Clone, Copy)]
+struct Joint {
+    end: [[f32; 2]; 2],
+    start: [[f32; 2]; 2],
+    fan: Option<Fan>,
+    u: f32,
+    alpha: f32,
+}
+
+/// Triangles around a point, filling the outside of a sharp turn.
+#[derive(Debug, Clone, Copy)]
+struct Fan {
+    center: [f32; 2],
+    /// From `center` to the outer edge of the segment before.
+    from: [f32; 2],
+    angle: f32,
+    steps: usize,
+    outer_top: bool,
+}
+
+impl Fan {
+    /// No triangles at all.
+    const NONE: Fan = Fan {
+        center: [0., 0.],
+        from: [0., 0.],
+        angle: 0.,
+        steps: 0,
+        outer_top: false,
+    };
+}
+
+fn segment_sprite(config: &RibbonConfig, from: &Joint, to: &Joint) -> Sprite {
+    let region = config.region;
+    let [top_0, bottom_0] = from.start;
+    let [top_1, bottom_1] = to.end;
+    let alpha_0 = [1., 1., 1., from.alpha];
+    let alpha_1 = [1., 1., 1., to.alpha];
+
+    Sprite {
+        atlas_uv: region
+            .orientation
+            .sub_uv(region.uv, [from.u, 0.], [to.u, 1.]),
+        ..Sprite::quad(region, [top_0, top_1, bottom_0, bottom_1], config.tint)
+            .with_corner_tints([alpha_0, alpha_1, alpha_0, alpha_1])
+    }
+}
+
+/// One triangle of a fan, as a quad with two corners in the same place.
+/// The center takes the middle of the region, and the outside its edge.
+fn fan_sprite(config: &RibbonConfig, fan: &Fan, step: usize, u: f32, alpha: f32) -> Sprite {
+    let region = config.region;
+    let angle = |step| fan.angle * step as f32 / fan.steps as f32;
+    let mut a = add(fan.center, rotate(fan.from, angle(step)));
+    let mut b = add(fan.center, rotate(fan.from, angle(step + 1)));
+    let c = fan.center;
+
+    // Only one of the quad's triangles is drawn, and it has to wind the
+    // same way as the rest, or it'll be culled
+    let (corners, from, to) = match fan.outer_top {
+        true => {
+            if cross(sub(c, a), sub(b, a)) > 0. {
+                std::mem::swap(&mut a, &mut b);
+            }
+            ([a, b, c, c], [u, 0.], [u, 0.5])
+        }
+        false => {
+            if cross(sub(a, c), sub(b, c)) > 0. {
+                std::mem::swap(&mut a, &mut b);
+            }
+            ([c, c, a, b], [u, 0.5], [u, 1.])
+        }
+    };
+
+    Sprite {
+        atlas_uv: region.orientation.sub_uv(region.uv, from, to),
+        ..Sprite::quad(region, corners, config.tint).with_corner_tints([[1., 1., 1., alpha]; 4])
+    }
+}
+
+fn add([ax, ay]: [f32; 2], [bx, by]: [f32; 2]) -> [f32; 2] {
+    [ax + bx, ay + by]
+}
+
+fn sub([ax, ay]: [f32; 2], [bx, by]: [f32; 2]) -> [f32; 2] {
+    [ax - bx, ay - by]
+}
+
+fn scale([x, y]: [f32; 2], s: f32) -> [f32; 2] {
+    [x * s, y * s]
+}
+
+fn dot([ax, ay]: [f32; 2], [bx, by]: [f32; 2]) -> f32 {
+    ax * bx + ay * by
+}
+
+fn cross([ax, ay]: [f32; 2], [bx, by]: [f32; 2]) -> f32 {
+    ax * by - ay * bx
+}
+
+fn distance(a: [f32; 2], b: [f32; 2]) -> f32 {
+    let d = sub(b, a);
+    dot(d, d).sqrt()
+}
+
+fn normalize(v: [f32; 2]) -> [f32; 2] {
+    scale(v, 1. / dot(v, v).sqrt())
+}
+
+fn direction(from: [f32; 2], to: [f32; 2]) -> [f32; 2] {
+    normalize(sub(to, from))
+}
+
+/// The side of a direction that's drawn at the top of the region. Sprites
+/// are culled if they're wound the wrong way, so this can't be flipped.
+fn normal([x, y]: [f32; 2]) -> [f32; 2] {
+    [y, -x]
+}
+
+fn rotate([x, y]: [f32; 2], angle: f32) -> [f32; 2] {
+    let (s, c) = angle.sin_cos();
+    [c * x - s * y, s * x + c * y]
+}
+
+fn lerp(a: [f32; 2], b: [f32; 2], f: f32) -> [f32; 2] {
+    add(a, scale(sub(b, a), f))
+}
@@ -0,0 +1,13 @@
+use crate::draw::{groove::Sprite, Region};
+
+/// A rectangle shaded from `top_color` to `bottom_color`. `white` should be
+/// a region of solid white pixels in the atlas.
+pub fn gradient_rect(
+    white: Region,
+    (pos, size): ([f32; 2], [f32; 2]),
+    top_color: [f32; 4],
+    bottom_color: [f32; 4],
+) -> Sprite {
+    Sprite::sized(white, pos, [1., 1., 1., 1.], size)
+        .with_vertical_gradient(top_color, bottom_color)
+}
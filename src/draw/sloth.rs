@@ -3,7 +3,7 @@
 use std::mem::ManuallyDrop;
 
 use crate::{
-    draw::CanvasConfig,
+    draw::{CanvasConfig, PresentMode},
     gfx::{self, easy, prelude::*, SupportedBackend},
     utils::over,
     windowing::{
@@ -49,6 +49,8 @@ pub struct DrawContext<B: SupportedBackend> {
     scale_factor: f64,
     framebuffer_attachment: Option<FramebufferAttachment>,
     swapchain_invalidated: Option<()>,
+    present_mode: PresentMode,
+    achieved_present_mode: Option<PresentMode>,
     canvas_image_size: (u32, u32),
     canvas_config: CanvasConfig,
 }
@@ -146,6 +148,8 @@ impl<B: SupportedBackend> DrawContext<B> {
             desc_set,
             framebuffer_attachment: None,
             swapchain_invalidated: Some(()),
+            present_mode: PresentMode::default(),
+            achieved_present_mode: None,
             canvas_image_size,
             canvas_config,
         })
@@ -168,6 +172,21 @@ impl<B: SupportedBackend> DrawContext<B> {
         self.canvas_config = canvas_config;
     }
 
+    /// Ask for a different present mode. It takes effect from the next
+    /// frame.
+    pub fn set_present_mode(&mut self, present_mode: PresentMode) {
+        if present_mode != self.present_mode {
+            self.present_mode = present_mode;
+            self.swapchain_invalidated = Some(());
+        }
+    }
+
+    /// The present mode in use, which is the closest the device supports
+    /// to the one asked for. `None` until the first frame is started.
+    pub fn present_mode(&self) -> Option<PresentMode> {
+        self.achieved_present_mode
+    }
+
     pub fn start_rendering(&mut self, clear_color: Color) -> Renderer<B> {
         let Resources {
             surface,
@@ -195,13 +214,16 @@ impl<B: SupportedBackend> DrawContext<B> {
         }
 
         if self.swapchain_invalidated.take().is_some() {
-            self.framebuffer_attachment = Some(easy::reconfigure_swapchain::<B>(
+            let (framebuffer_attachment, achieved) = easy::reconfigure_swapchain::<B>(
                 surface,
                 &self.adapter,
                 &self.device,
                 self.surface_color_format,
                 &mut self.surface_extent,
-            ));
+                self.present_mode,
+            );
+            self.framebuffer_attachment = Some(framebuffer_attachment);
+            self.achieved_present_mode = Some(achieved);
         }
 
         let framebuffer = easy::acquire_framebuffer::<B>(
@@ -0,0 +1,232 @@
+//! Helpers for golden-image tests of groove rendering.
+//!
+//! Render a scene with `render_offscreen`, then compare it against a stored
+//! PNG with `assert_image_matches`. Run the tests with `JAMJAR_UPDATE_GOLDEN`
+//! set to write the golden images instead of comparing against them.
+
+use std::path::{Path, PathBuf};
+
+use image::{Rgba, RgbaImage};
+
+use crate::{
+    draw::{backend, groove::DrawContext, CanvasConfig, CanvasMode, ResizeMode, ScaleMode},
+    windowing::{dpi::LogicalSize, event_loop::EventLoop, window::WindowBuilder},
+};
+
+pub const UPDATE_GOLDEN_VAR: &str = "JAMJAR_UPDATE_GOLDEN";
+
+/// Render a frame at exactly `size` pixels and read it back.
+///
+/// The draw context still needs a window, so a hidden one is created. If
+/// there's no display or GPU to render with (e.g. on CI), a message is
+/// printed and `None` is returned, so the test can skip itself:
+///
+/// ```ignore
+/// let image = match render_offscreen([64, 64], atlas, |ctx| {
+///     let mut ren = ctx.start_rendering([0., 0., 0., 1.]);
+///     ren.sprite(...);
+/// }) {
+///     Some(image) => image,
+///     None => return,
+/// };
+/// ```
+///
+/// NOTE: On macOS, windows can only be created on the main thread, so tests
+/// using this need to be run with `--test-threads=1`.
+pub fn render_offscreen<F>(size: [u32; 2], texture_atlas: RgbaImage, draw: F) -> Option<RgbaImage>
+where
+    F: FnOnce(&mut DrawContext<backend::Whatever>),
+{
+    let skip = |reason: &str| {
+        eprintln!("Skipping render test: {}", reason);
+        None
+    };
+
+    let event_loop = match std::panic::catch_unwind(event_loop) {
+        Ok(event_loop) => event_loop,
+        Err(_) => return skip("no display available"),
+    };
+
+    let [width, height] = size;
+    let window = match WindowBuilder::new()
+        .with_title("jamjar render test")
+        .with_inner_size(LogicalSize::new(width, height))
+        .with_visible(false)
+        .build(&event_loop)
+    {
+        Ok(window) => window,
+        Err(_) => return skip("couldn't create a window"),
+    };
+
+    let canvas_config = CanvasConfig {
+        canvas_mode: CanvasMode::Intermediate,
+        resize_mode: ResizeMode::SetPhysical(size),
+        scale_mode: ScaleMode::Set(1.),
+    };
+
+    let mut context = match DrawContext::new(&window, canvas_config, texture_atlas) {
+        Ok(context) => context,
+        Err(()) => return skip("no GPU available"),
+    };
+
+    draw(&mut context);
+
+    context.capture_canvas()
+}
+
+#[cfg(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+))]
+fn event_loop() -> EventLoop<()> {
+    use winit::platform::unix::EventLoopExtUnix;
+
+    EventLoop::new_any_thread()
+}
+
+#[cfg(windows)]
+fn event_loop() -> EventLoop<()> {
+    use winit::platform::windows::EventLoopExtWindows;
+
+    EventLoop::new_any_thread()
+}
+
+#[cfg(not(any(
+    windows,
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd"
+)))]
+fn event_loop() -> EventLoop<()> {
+    EventLoop::new()
+}
+
+/// Panic unless every channel of every pixel is within `tolerance` of the
+/// golden image.
+///
+/// On failure, `name.actual.png` and `name.diff.png` are written next to
+/// the golden image. In the diff, mismatched pixels are red.
+pub fn assert_image_matches<P: AsRef<Path>>(actual: &RgbaImage, golden_path: P, tolerance: u8) {
+    let golden_path = golden_path.as_ref();
+
+    if std::env::var_os(UPDATE_GOLDEN_VAR).is_some() {
+        if let Some(parent) = golden_path.parent() {
+            std::fs::create_dir_all(parent).unwrap();
+        }
+        actual
+            .save(golden_path)
+            .unwrap_or_else(|e| panic!("Failed to write {}: {}", golden_path.display(), e));
+        return;
+    }
+
+    let golden = match image::open(golden_path) {
+        Ok(golden) => golden.to_rgba8(),
+        Err(e) => panic!(
+            "Failed to load golden image {}: {}. Run with {} set to create it.",
+            golden_path.display(),
+            e,
+            UPDATE_GOLDEN_VAR
+        ),
+    };
+
+    let actual_path = sibling_path(golden_path, "actual");
+
+    if golden.dimensions() != actual.dimensions() {
+        actual.save(&actual_path).unwrap();
+        panic!(
+            "Image is {:?} but golden image {} is {:?}. Actual image written to {}",
+            actual.dimensions(),
+            golden_path.display(),
+            golden.dimensions(),
+            actual_path.display()
+        );
+    }
+
+    let (width, height) = actual.dimensions();
+    let mut diff = RgbaImage::new(width, height);
+    let mut mismatched = 0;
+    let mut worst = 0;
+
+    for ((x, y, &Rgba(a)), &Rgba(g)) in actual.enumerate_pixels().zip(golden.pixels()) {
+        let distance = (0..4)
+            .map(|i| (a[i] as i16 - g[i] as i16).abs() as u8)
+            .max()
+            .unwrap();
+        worst = worst.max(distance);
+
+        let diff_pixel = if distance > tolerance {
+            mismatched += 1;
+            Rgba([255, 0, 0, 255])
+        } else {
+            Rgba([a[0] / 4, a[1] / 4, a[2] / 4, 255])
+        };
+        diff.put_pixel(x, y, diff_pixel);
+    }
+
+    if mismatched > 0 {
+        let diff_path = sibling_path(golden_path, "diff");
+        actual.save(&actual_path).unwrap();
+        diff.save(&diff_path).unwrap();
+        panic!(
+            "{} pixels differ from golden image {} (by up to {}, tolerance {}). Actual image written to {}, diff to {}",
+            mismatched,
+            golden_path.display(),
+            worst,
+            tolerance,
+            actual_path.display(),
+            diff_path.display()
+        );
+    }
+}
+
+fn sibling_path(path: &Path, suffix: &str) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!("{}.{}.png", stem, suffix))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::draw::{groove::Sprite, Region};
+
+    fn golden_path(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("src/draw/golden")
+            .join(name)
+    }
+
+    /// Red, green, blue, and white texels in the top-left corner.
+    fn checker_atlas() -> RgbaImage {
+        let mut atlas = RgbaImage::new(4, 4);
+        atlas.put_pixel(0, 0, Rgba([255, 0, 0, 255]));
+        atlas.put_pixel(1, 0, Rgba([0, 255, 0, 255]));
+        atlas.put_pixel(0, 1, Rgba([0, 0, 255, 255]));
+        atlas.put_pixel(1, 1, Rgba([255, 255, 255, 255]));
+        atlas
+    }
+
+    #[test]
+    fn pixel_snapped_sprites_cover_whole_pixels_at_3x() {
+        let draw = |context: &mut DrawContext<backend::Whatever>| {
+            context.set_camera([0.3, -0.2]);
+            let mut renderer = context.start_rendering([0., 0., 0., 1.]);
+            let region = Region::new(([0, 0], [2, 2]), ([0., 0.], [0.5, 0.5]));
+            let tint = [1., 1., 1., 1.];
+            renderer.sprite(Sprite::scaled(region, [4.4, 5.6], tint, [3., 3.]).pixel_snapped());
+        };
+
+        let first = match render_offscreen([16, 16], checker_atlas(), &draw) {
+            Some(image) => image,
+            None => return,
+        };
+        let second = render_offscreen([16, 16], checker_atlas(), &draw).unwrap();
+
+        assert!(first == second, "Rendering twice gave different images");
+        assert_image_matches(&first, golden_path("pixel_snap_3x.png"), 0);
+    }
+}
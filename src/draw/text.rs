@@ -0,0 +1,126 @@
+use image::RgbaImage;
+
+use crate::{
+    atlas::{font::OutlineAtlas, Atlas},
+    draw::{groove::Renderer, GlyphRegion, Space},
+    font::{Font, Glyph},
+    gfx::SupportedBackend,
+};
+
+/// How to draw a piece of text with `TextPainter::draw_styled`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextStyle {
+    pub scale: f32,
+    pub color: [f32; 4],
+    /// The color and thickness of an outline around the text.
+    pub outline: Option<([f32; 4], u32)>,
+    /// Keep every glyph on whole pixels of the canvas, for pixel fonts. Use
+    /// a `FontAtlas::with_pixel_snap` atlas too, so that glyphs are only
+    /// rasterized once, at a whole-pixel offset.
+    pub pixel_snap: bool,
+}
+
+impl TextStyle {
+    pub fn new(scale: f32, color: [f32; 4]) -> Self {
+        TextStyle {
+            scale,
+            color,
+            outline: None,
+            pixel_snap: false,
+        }
+    }
+
+    pub fn with_outline(self, color: [f32; 4], thickness: u32) -> Self {
+        TextStyle {
+            outline: Some((color, thickness)),
+            ..self
+        }
+    }
+
+    pub fn pixel_snapped(self) -> Self {
+        TextStyle {
+            pixel_snap: true,
+            ..self
+        }
+    }
+}
+
+/// Records text to draw during a frame, and handles queueing, compiling,
+/// and fetching glyphs from the font atlas in the right order at the end.
+#[derive(Debug, Default)]
+pub struct TextPainter {
+    queued: Vec<(Glyph, [f32; 4], Option<([f32; 4], u32)>, Space)>,
+    space: Space,
+}
+
+impl TextPainter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the space for the text drawn after this, like
+    /// `Renderer::set_space`. This lasts until it's changed again.
+    pub fn set_space(&mut self, space: Space) {
+        self.space = space;
+    }
+
+    pub fn space(&self) -> Space {
+        self.space
+    }
+
+    pub fn draw_text(
+        &mut self,
+        font: &Font,
+        text: &str,
+        pos: [f32; 2],
+        scale: f32,
+        color: [f32; 4],
+    ) {
+        self.draw_styled(font, text, pos, TextStyle::new(scale, color));
+    }
+
+    /// Like `draw_text`, with an outline of the given color and thickness.
+    pub fn draw_outlined_text(
+        &mut self,
+        font: &Font,
+        text: &str,
+        pos: [f32; 2],
+        scale: f32,
+        color: [f32; 4],
+        outline: ([f32; 4], u32),
+    ) {
+        let (outline_color, thickness) = outline;
+        let style = TextStyle::new(scale, color).with_outline(outline_color, thickness);
+        self.draw_styled(font, text, pos, style);
+    }
+
+    pub fn draw_styled(&mut self, font: &Font, text: &str, pos: [f32; 2], style: TextStyle) {
+        for glyph in font.layout(text, pos, style.scale) {
+            let glyph = match style.pixel_snap {
+                true => glyph.pixel_snapped(),
+                false => glyph,
+            };
+            self.queued
+                .push((glyph, style.color, style.outline, self.space));
+        }
+    }
+
+    /// Draw all of this frame's text and finish the frame.
+    pub fn finish<B, A>(&mut self, mut renderer: Renderer<B>, font_atlas: &mut A)
+    where
+        B: SupportedBackend,
+        A: Atlas<Glyph, Glyph, Option<GlyphRegion>, RgbaImage> + OutlineAtlas,
+    {
+        for (glyph, color, outline, space) in self.queued.drain(..) {
+            renderer.set_space(space);
+            match outline {
+                Some((outline_color, thickness)) => {
+                    renderer.outlined_glyphs(Some(glyph), color, outline_color, thickness)
+                }
+                None => renderer.glyphs(Some(glyph), color),
+            }
+        }
+
+        renderer.finish_with_text(font_atlas, None);
+    }
+}
@@ -1,30 +1,228 @@
+use std::collections::hash_map::DefaultHasher;
+use std::fmt;
+use std::hash::{Hash, Hasher};
 use std::sync::atomic::{AtomicUsize, Ordering};
 
-use rusttype::{Font as RTFont, PositionedGlyph};
+use rusttype::{Font as RTFont, PositionedGlyph, Scale};
 
 static mut FONT_COUNT: AtomicUsize = AtomicUsize::new(0);
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FontError {
+    /// There's no resource with this path.
+    NotFound(String),
+    /// The data isn't a TrueType or OpenType font.
+    UnknownFormat,
+    /// The data looks like a font, but couldn't be parsed.
+    Invalid,
+}
+
+impl fmt::Display for FontError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FontError::NotFound(path) => write!(f, "There is no font resource `{}`", path),
+            FontError::UnknownFormat => write!(f, "Font is not in TTF or OTF format"),
+            FontError::Invalid => write!(f, "Failed to parse font"),
+        }
+    }
+}
+
+impl std::error::Error for FontError {}
+
+fn parse_font(bytes: Vec<u8>) -> Result<RTFont<'static>, FontError> {
+    // TrueType, OpenType (CFF), Apple TrueType, and font collections
+    const MAGIC: &[&[u8]] = &[b"\x00\x01\x00\x00", b"OTTO", b"true", b"ttcf"];
+
+    if !MAGIC.iter().any(|magic| bytes.starts_with(magic)) {
+        return Err(FontError::UnknownFormat);
+    }
+
+    RTFont::try_from_vec(bytes).ok_or(FontError::Invalid)
+}
+
+/// A quick hash of the font data, to tell when a reload hasn't changed
+/// anything. It's not collision-proof, but a collision only means a
+/// changed font isn't picked up until it changes again.
+fn weak_hash(bytes: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    bytes.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Vertical metrics for a font at a particular scale, in pixels.
+///
+/// `ascent` is the distance from the baseline up to the top of the line,
+/// and `descent` is negative, from the baseline down to the bottom.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Metrics {
+    pub ascent: f32,
+    pub descent: f32,
+    pub line_gap: f32,
+    /// The distance between the baselines of consecutive lines.
+    pub line_height: f32,
+}
+
+impl Metrics {
+    fn of(font: &RTFont, scale: f32) -> Self {
+        let v_metrics = font.v_metrics(Scale::uniform(scale));
+        Metrics {
+            ascent: v_metrics.ascent,
+            descent: v_metrics.descent,
+            line_gap: v_metrics.line_gap,
+            line_height: v_metrics.ascent - v_metrics.descent + v_metrics.line_gap,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Glyph {
     pub(crate) font_id: usize,
+    /// The generation of the font the glyph was laid out with.
+    pub(crate) generation: u32,
     pub(crate) glyph: PositionedGlyph<'static>,
+    pub(crate) outline: Option<u32>,
+    pub(crate) pixel_snap: bool,
+}
+
+impl Glyph {
+    pub fn with_outline(mut self, thickness: u32) -> Self {
+        self.outline = Some(thickness);
+        self
+    }
+
+    /// Move the glyph to the nearest whole pixel, and keep it on a whole
+    /// pixel of the canvas when it's drawn, after the camera has moved it.
+    /// This keeps pixel fonts from blurring across pixels.
+    pub fn pixel_snapped(mut self) -> Self {
+        use rusttype::Point;
+
+        let Point { x, y } = self.glyph.position();
+        self.glyph = self.glyph.unpositioned().clone().positioned(Point {
+            x: x.round(),
+            y: y.round(),
+        });
+        self.pixel_snap = true;
+        self
+    }
+
+    /// The metrics of this glyph's font at the scale it was laid out at.
+    pub fn metrics(&self) -> Metrics {
+        Metrics::of(self.glyph.font(), self.glyph.scale().y)
+    }
 }
 
 pub struct Font {
     font_id: usize,
+    generation: u32,
+    hash: u64,
     font: RTFont<'static>,
 }
 
 impl Font {
+    /// Load a TTF or OTF font, panicking if it's invalid.
     pub fn new(bytes: Vec<u8>) -> Self {
+        Self::from_bytes(bytes).unwrap()
+    }
+
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Self, FontError> {
+        let hash = weak_hash(&bytes);
+        let font = parse_font(bytes)?;
         let font_id = unsafe { FONT_COUNT.fetch_add(1, Ordering::Relaxed) };
-        let font = RTFont::try_from_vec(bytes).unwrap();
 
-        Font { font_id, font }
+        Ok(Font {
+            font_id,
+            generation: 0,
+            hash,
+            font,
+        })
+    }
+
+    /// The id that glyphs from this font are cached under. It's unique to
+    /// this font, and stays the same when it's reloaded.
+    pub fn id(&self) -> usize {
+        self.font_id
+    }
+
+    /// How many times the font has been replaced by `reload`.
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+
+    /// Replace the font with new data, keeping its id. The next time its
+    /// glyphs reach a `FontAtlas`, the atlas throws away what it cached
+    /// for the old version.
+    ///
+    /// Returns `false` if the data hasn't changed. If it's invalid, the
+    /// old font is kept.
+    pub fn reload(&mut self, bytes: Vec<u8>) -> Result<bool, FontError> {
+        let hash = weak_hash(&bytes);
+        if hash == self.hash {
+            return Ok(false);
+        }
+
+        self.font = parse_font(bytes)?;
+        self.hash = hash;
+        self.generation += 1;
+        Ok(true)
+    }
+
+    pub fn metrics(&self, scale: f32) -> Metrics {
+        Metrics::of(&self.font, scale)
+    }
+
+    /// How far to move along the line after drawing `c`.
+    pub fn char_advance(&self, c: char, scale: f32) -> f32 {
+        self.font
+            .glyph(c)
+            .scaled(Scale::uniform(scale))
+            .h_metrics()
+            .advance_width
+    }
+
+    /// The adjustment to the advance between `a` and `b` when `b` follows
+    /// `a`. This is usually zero or negative.
+    pub fn kerning(&self, a: char, b: char, scale: f32) -> f32 {
+        self.font.pair_kerning(Scale::uniform(scale), a, b)
+    }
+
+    /// Lay out a single line of text. The glyph regions fetched for the
+    /// glyphs are positioned with `pos` as the top-left of the line, so the
+    /// baseline is at `pos[1] + metrics(scale).ascent`.
+    pub fn layout(&self, text: &str, pos: [f32; 2], scale: f32) -> Vec<Glyph> {
+        use rusttype::Point;
+
+        let [x, y] = pos;
+        let mut caret = x;
+        let mut last = None;
+
+        text.chars()
+            .map(|c| {
+                if let Some(last) = last {
+                    caret += self.kerning(last, c, scale);
+                }
+
+                let glyph = self
+                    .font
+                    .glyph(c)
+                    .scaled(Scale::uniform(scale))
+                    .positioned(Point { x: caret, y });
+
+                caret += self.char_advance(c, scale);
+                last = Some(c);
+
+                Glyph {
+                    font_id: self.font_id,
+                    generation: self.generation,
+                    glyph,
+                    outline: None,
+                    pixel_snap: false,
+                }
+            })
+            .collect()
     }
 
     pub fn test_glyph(&self, c: char, pos: [f32; 2]) -> Glyph {
-        use rusttype::{Point, Scale};
+        use rusttype::Point;
 
         let g = self.font.glyph(c);
         let g = g.scaled(Scale { x: 11., y: 11. });
@@ -34,7 +232,116 @@ impl Font {
 
         Glyph {
             font_id: self.font_id,
+            generation: self.generation,
             glyph: g,
+            outline: None,
+            pixel_snap: false,
         }
     }
 }
+
+/// A font loaded from the resource system, which remembers its path so it
+/// can be reloaded from there.
+#[cfg(feature = "resources")]
+pub struct FontHandle {
+    path: String,
+    font: Font,
+}
+
+#[cfg(feature = "resources")]
+impl FontHandle {
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Reload the font from the same path in `resources`, e.g. after
+    /// `resource_list!` has picked up a change. If the new version is
+    /// invalid, the old one is kept.
+    pub fn reload<'a, I>(&mut self, resources: I) -> Result<bool, FontError>
+    where
+        I: IntoIterator<Item = &'a (&'static str, resource::Resource<[u8]>)>,
+    {
+        let bytes = find_resource(resources, &self.path)?;
+        self.font.reload(bytes)
+    }
+}
+
+#[cfg(feature = "resources")]
+impl std::ops::Deref for FontHandle {
+    type Target = Font;
+
+    fn deref(&self) -> &Font {
+        &self.font
+    }
+}
+
+/// Load the font at `path` from a `resource_list!`. The path can be the
+/// resource's full filename, or any number of trailing path components of
+/// it, like `"fonts/pixel.ttf"`.
+#[cfg(feature = "resources")]
+pub fn load_font<'a, I>(resources: I, path: &str) -> Result<FontHandle, FontError>
+where
+    I: IntoIterator<Item = &'a (&'static str, resource::Resource<[u8]>)>,
+{
+    let bytes = find_resource(resources, path)?;
+
+    Ok(FontHandle {
+        path: path.to_owned(),
+        font: Font::from_bytes(bytes)?,
+    })
+}
+
+#[cfg(feature = "resources")]
+fn find_resource<'a, I>(resources: I, path: &str) -> Result<Vec<u8>, FontError>
+where
+    I: IntoIterator<Item = &'a (&'static str, resource::Resource<[u8]>)>,
+{
+    let path = path.replace('\\', "/");
+    let suffix = format!("/{}", path);
+
+    resources
+        .into_iter()
+        .find(|(filename, _)| {
+            let filename = filename.replace('\\', "/");
+            filename == path || filename.ends_with(&suffix)
+        })
+        .map(|(_, resource)| resource.to_vec())
+        .ok_or(FontError::NotFound(path))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    pub(crate) fn test_font() -> Font {
+        let bytes = include_bytes!("../jamjar_examples/assets/fonts/chocolate_11.ttf");
+        Font::new(bytes.to_vec())
+    }
+
+    #[test]
+    fn line_height_is_the_sum_of_the_metrics() {
+        let font = test_font();
+        let metrics = font.metrics(22.);
+
+        assert!(metrics.ascent > 0.);
+        assert!(metrics.descent <= 0.);
+        assert_eq!(
+            metrics.line_height,
+            metrics.ascent - metrics.descent + metrics.line_gap
+        );
+
+        let doubled = font.metrics(44.);
+        assert!((doubled.line_height - 2. * metrics.line_height).abs() < 0.01);
+    }
+
+    #[test]
+    fn layout_advances_by_char_advance_and_kerning() {
+        let font = test_font();
+        let glyphs = font.layout("AV", [5., 7.], 22.);
+
+        let expected_x = 5. + font.char_advance('A', 22.) + font.kerning('A', 'V', 22.);
+        let position = glyphs[1].glyph.position();
+        assert_eq!([position.x, position.y], [expected_x, 7.]);
+        assert_eq!(glyphs[1].metrics(), font.metrics(22.));
+    }
+}
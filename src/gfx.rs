@@ -26,14 +26,28 @@ pub trait SupportedBackend: Backend {
     unsafe fn make_shader_module(
         device: &<Self as Backend>::Device,
         source: &[u8],
-        _is_fragment: bool,
+        is_fragment: bool,
     ) -> <Self as Backend>::ShaderModule {
-        debug_assert!(source.len() % 4 == 0, "SPIRV not aligned");
-        let spirv = {
-            let p = source.as_ptr() as *const u32;
-            std::slice::from_raw_parts(p, source.len() / 4)
-        };
-        device.create_shader_module(spirv).unwrap()
+        Self::try_make_shader_module(device, source, is_fragment).unwrap()
+    }
+
+    unsafe fn try_make_shader_module(
+        device: &<Self as Backend>::Device,
+        source: &[u8],
+        _is_fragment: bool,
+    ) -> Result<<Self as Backend>::ShaderModule, String> {
+        if source.len() % 4 != 0 {
+            return Err("SPIRV not aligned".into());
+        }
+
+        let spirv = source
+            .chunks_exact(4)
+            .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+            .collect::<Vec<_>>();
+
+        device
+            .create_shader_module(&spirv)
+            .map_err(|e| format!("{:?}", e))
     }
 }
 
@@ -43,11 +57,11 @@ impl SupportedBackend for backend::Metal {}
 #[cfg(feature = "opengl")]
 impl SupportedBackend for backend::OpenGL {
     #[cfg(all(target_arch = "wasm32", feature = "bypass_spirv_cross"))]
-    unsafe fn make_shader_module(
+    unsafe fn try_make_shader_module(
         device: &<Self as Backend>::Device,
         source: &[u8],
         is_fragment: bool,
-    ) -> <Self as Backend>::ShaderModule {
+    ) -> Result<<Self as Backend>::ShaderModule, String> {
         let source = std::str::from_utf8_unchecked(source);
         let stage = if is_fragment {
             gfx_auxil::ShaderStage::Fragment
@@ -56,7 +70,7 @@ impl SupportedBackend for backend::OpenGL {
         };
         device
             .create_shader_module_from_source(source, stage)
-            .expect("Failed to create shader module")
+            .map_err(|e| format!("Failed to create shader module: {:?}", e))
     }
 }
 
@@ -303,6 +317,152 @@ pub unsafe fn upload_image<B: Backend>(
     device.destroy_fence(texture_fence);
 }
 
+/// Copy an RGBA8 image back from the GPU. The image must have been created
+/// with `Usage::TRANSFER_SRC`, and be in `ShaderReadOnlyOptimal` layout,
+/// which it is left in afterwards.
+pub unsafe fn download_image<B: Backend>(
+    device: &B::Device,
+    physical_device: &B::PhysicalDevice,
+    command_pool: &mut B::CommandPool,
+    queue: &mut B::CommandQueue,
+    image_resource: &B::Image,
+    image_size: (u32, u32),
+) -> Vec<u8> {
+    use gfx_hal::format::Aspects;
+    use gfx_hal::image::SubresourceRange;
+    use gfx_hal::memory::{Properties, Segment};
+
+    let (image_width, image_height) = image_size;
+
+    let mut texture_fence = device.create_fence(false).expect("TODO");
+
+    let limits = physical_device.limits();
+    let non_coherent_alignment = limits.non_coherent_atom_size as u64;
+    let row_alignment_mask = limits.optimal_buffer_copy_pitch_alignment as u32 - 1;
+
+    let image_stride = 4usize;
+    let row_pitch = (image_width * image_stride as u32 + row_alignment_mask) & !row_alignment_mask;
+    let download_size = (image_height * row_pitch) as u64;
+    let padded_download_size = ((download_size + non_coherent_alignment - 1)
+        / non_coherent_alignment)
+        * non_coherent_alignment;
+
+    let (mut buffer_memory, buffer) = make_buffer::<B>(
+        device,
+        physical_device,
+        padded_download_size as usize,
+        gfx_hal::buffer::Usage::TRANSFER_DST,
+        Properties::CPU_VISIBLE,
+    );
+
+    let command_buffer = {
+        use gfx_hal::command::{BufferImageCopy, CommandBufferFlags, Level};
+        use gfx_hal::image::{Access, Extent, Layout, Offset, SubresourceLayers};
+        use gfx_hal::memory::{Barrier, Dependencies};
+        use gfx_hal::pso::PipelineStage;
+
+        let mut command_buffer = command_pool.allocate_one(Level::Primary);
+
+        command_buffer.begin_primary(CommandBufferFlags::ONE_TIME_SUBMIT);
+
+        let image_barrier = Barrier::Image {
+            states: (Access::SHADER_READ, Layout::ShaderReadOnlyOptimal)
+                ..(Access::TRANSFER_READ, Layout::TransferSrcOptimal),
+            target: image_resource,
+            families: None,
+            range: SubresourceRange {
+                aspects: Aspects::COLOR,
+                ..Default::default()
+            },
+        };
+
+        command_buffer.pipeline_barrier(
+            PipelineStage::FRAGMENT_SHADER..PipelineStage::TRANSFER,
+            Dependencies::empty(),
+            over([image_barrier]),
+        );
+
+        command_buffer.copy_image_to_buffer(
+            image_resource,
+            Layout::TransferSrcOptimal,
+            &buffer,
+            over([BufferImageCopy {
+                buffer_offset: 0,
+                buffer_width: row_pitch / (image_stride as u32),
+                buffer_height: image_height as u32,
+                image_layers: SubresourceLayers {
+                    aspects: Aspects::COLOR,
+                    level: 0,
+                    layers: 0..1,
+                },
+                image_offset: Offset { x: 0, y: 0, z: 0 },
+                image_extent: Extent {
+                    width: image_width,
+                    height: image_height,
+                    depth: 1,
+                },
+            }]),
+        );
+
+        let image_barrier = Barrier::Image {
+            states: (Access::TRANSFER_READ, Layout::TransferSrcOptimal)
+                ..(Access::SHADER_READ, Layout::ShaderReadOnlyOptimal),
+            target: image_resource,
+            families: None,
+            range: SubresourceRange {
+                aspects: Aspects::COLOR,
+                ..Default::default()
+            },
+        };
+
+        command_buffer.pipeline_barrier(
+            PipelineStage::TRANSFER..PipelineStage::FRAGMENT_SHADER,
+            Dependencies::empty(),
+            over([image_barrier]),
+        );
+
+        command_buffer.finish();
+        command_buffer
+    };
+
+    queue.submit(
+        over([&command_buffer]),
+        over([]),
+        over([]),
+        Some(&mut texture_fence),
+    );
+
+    // TODO: Don't wait forever
+    device.wait_for_fence(&texture_fence, !0).expect("TODO");
+
+    let mapped_memory = device
+        .map_memory(&mut buffer_memory, Segment::ALL)
+        .expect("TODO");
+
+    device
+        .invalidate_mapped_memory_ranges(over([(&buffer_memory, Segment::ALL)]))
+        .expect("TODO");
+
+    let row_len = image_width as usize * image_stride;
+    let mut image_bytes = vec![0; row_len * image_height as usize];
+    for y in 0..image_height as usize {
+        std::ptr::copy_nonoverlapping(
+            mapped_memory.offset(y as isize * row_pitch as isize),
+            image_bytes[y * row_len..].as_mut_ptr(),
+            row_len,
+        );
+    }
+
+    device.unmap_memory(&mut buffer_memory);
+
+    // Cleanup staging resources
+    device.destroy_buffer(buffer);
+    device.free_memory(buffer_memory);
+    device.destroy_fence(texture_fence);
+
+    image_bytes
+}
+
 pub unsafe fn push_constant_bytes<T>(push_constants: &T) -> &[u32] {
     let size_in_bytes = std::mem::size_of::<T>();
     let push_constant_size = std::mem::size_of::<u32>();
@@ -1,6 +1,6 @@
 use super::*;
 
-use crate::utils::over;
+use crate::{draw::PresentMode, utils::over};
 
 pub fn init<B: Backend>(
     window: &crate::windowing::window::Window,
@@ -89,99 +89,106 @@ pub fn desc_sets<B: Backend>(
 ) {
     use gfx_hal::pso::*;
 
-    let sets = values.len();
-    let ubos = values.get(0).map(|set| set.0.len()).unwrap_or(0);
-    let images = values.get(0).map(|set| set.1.len()).unwrap_or(0);
-    let samplers = values.get(0).map(|set| set.2.len()).unwrap_or(0);
-
-    assert!(
-        values
-            .iter()
-            .all(|set| set.0.len() == ubos && set.1.len() == images && set.2.len() == samplers),
-        "All desc_sets must have the same layout of values"
-    );
+    let (ubos, images, samplers) = desc_set_shape::<B>(&values);
 
-    let mut binding_number = 0;
-    let mut bindings = vec![];
-    let mut ranges = vec![];
-    for _ in 0..ubos {
-        bindings.push(DescriptorSetLayoutBinding {
-            binding: binding_number,
-            ty: DescriptorType::Buffer {
-                ty: BufferDescriptorType::Uniform,
-                format: BufferDescriptorFormat::Structured {
-                    dynamic_offset: false,
-                },
-            },
-            count: 1,
-            stage_flags: ShaderStageFlags::FRAGMENT,
-            immutable_samplers: false,
-        });
-        ranges.push(DescriptorRangeDesc {
-            ty: DescriptorType::Buffer {
-                ty: BufferDescriptorType::Uniform,
-                format: BufferDescriptorFormat::Structured {
-                    dynamic_offset: false,
-                },
-            },
-            count: sets,
-        });
-        binding_number += 1;
-    }
-    for _ in 0..images {
-        bindings.push(DescriptorSetLayoutBinding {
-            binding: binding_number,
-            ty: DescriptorType::Image {
-                ty: gfx_hal::pso::ImageDescriptorType::Sampled {
-                    with_sampler: false,
-                },
-            },
-            count: 1,
-            stage_flags: ShaderStageFlags::FRAGMENT,
-            immutable_samplers: false,
-        });
-        ranges.push(DescriptorRangeDesc {
-            ty: DescriptorType::Image {
-                ty: gfx_hal::pso::ImageDescriptorType::Sampled {
-                    with_sampler: false,
-                },
-            },
-            count: sets,
-        });
-        binding_number += 1;
-    }
-    for _ in 0..samplers {
-        bindings.push(DescriptorSetLayoutBinding {
-            binding: binding_number,
-            ty: DescriptorType::Sampler,
+    let bindings = desc_types(ubos, images, samplers)
+        .enumerate()
+        .map(|(binding, ty)| DescriptorSetLayoutBinding {
+            binding: binding as u32,
+            ty,
             count: 1,
             stage_flags: ShaderStageFlags::FRAGMENT,
             immutable_samplers: false,
-        });
-        ranges.push(DescriptorRangeDesc {
-            ty: DescriptorType::Sampler,
-            count: sets,
-        });
-        binding_number += 1;
-    }
+        })
+        .collect::<Vec<_>>();
 
-    let (layout, pool, mut desc_sets) = unsafe {
-        let layout = device
+    let layout = unsafe {
+        device
             .create_descriptor_set_layout(bindings.into_iter(), over([]))
-            .unwrap();
+            .unwrap()
+    };
+
+    let (pool, desc_sets) = desc_pool::<B>(device, &layout, values);
+
+    (layout, pool, desc_sets)
+}
+
+/// Make a pool with a set for each of `values`, using a `layout` from
+/// `desc_sets`, and write the values to them. Pools can't grow, so this is
+/// how to make room for more sets with the same layout.
+pub fn desc_pool<B: Backend>(
+    device: &B::Device,
+    layout: &B::DescriptorSetLayout,
+    values: Vec<(Vec<&B::Buffer>, Vec<&B::ImageView>, Vec<&B::Sampler>)>,
+) -> (B::DescriptorPool, Vec<B::DescriptorSet>) {
+    use gfx_hal::pso::*;
+
+    let sets = values.len();
+    let (ubos, images, samplers) = desc_set_shape::<B>(&values);
+
+    let ranges = desc_types(ubos, images, samplers)
+        .map(|ty| DescriptorRangeDesc { ty, count: sets })
+        .collect::<Vec<_>>();
+
+    let (pool, mut desc_sets) = unsafe {
         let mut pool = device
             .create_descriptor_pool(sets, ranges.into_iter(), DescriptorPoolCreateFlags::empty())
             .unwrap();
         let mut desc_sets = Vec::with_capacity(sets);
         for _ in 0..sets {
-            desc_sets.push(pool.allocate_one(&layout).unwrap());
+            desc_sets.push(pool.allocate_one(layout).unwrap());
         }
-        (layout, pool, desc_sets)
+        (pool, desc_sets)
     };
 
     write_desc_sets::<B>(device, desc_sets.iter_mut().collect(), values);
 
-    (layout, pool, desc_sets)
+    (pool, desc_sets)
+}
+
+/// How many buffers, images, and samplers each set has.
+fn desc_set_shape<B: Backend>(
+    values: &[(Vec<&B::Buffer>, Vec<&B::ImageView>, Vec<&B::Sampler>)],
+) -> (usize, usize, usize) {
+    let ubos = values.get(0).map(|set| set.0.len()).unwrap_or(0);
+    let images = values.get(0).map(|set| set.1.len()).unwrap_or(0);
+    let samplers = values.get(0).map(|set| set.2.len()).unwrap_or(0);
+
+    assert!(
+        values
+            .iter()
+            .all(|set| set.0.len() == ubos && set.1.len() == images && set.2.len() == samplers),
+        "All desc_sets must have the same layout of values"
+    );
+
+    (ubos, images, samplers)
+}
+
+/// The type of each binding in a set, in binding order.
+fn desc_types(
+    ubos: usize,
+    images: usize,
+    samplers: usize,
+) -> impl Iterator<Item = gfx_hal::pso::DescriptorType> {
+    use gfx_hal::pso::*;
+    use std::iter::repeat;
+
+    let ubo = DescriptorType::Buffer {
+        ty: BufferDescriptorType::Uniform,
+        format: BufferDescriptorFormat::Structured {
+            dynamic_offset: false,
+        },
+    };
+    let image = DescriptorType::Image {
+        ty: ImageDescriptorType::Sampled {
+            with_sampler: false,
+        },
+    };
+
+    repeat(ubo)
+        .take(ubos)
+        .chain(repeat(image).take(images))
+        .chain(repeat(DescriptorType::Sampler).take(samplers))
 }
 
 pub fn write_desc_sets<B: Backend>(
@@ -196,16 +203,7 @@ pub fn write_desc_sets<B: Backend>(
         "Must supply a matching, non-zero number of desc_sets and values"
     );
 
-    let ubos = values.get(0).map(|set| set.0.len()).unwrap_or(0);
-    let images = values.get(0).map(|set| set.1.len()).unwrap_or(0);
-    let samplers = values.get(0).map(|set| set.2.len()).unwrap_or(0);
-
-    assert!(
-        values
-            .iter()
-            .all(|set| set.0.len() == ubos && set.1.len() == images && set.2.len() == samplers),
-        "All desc_sets must have the same layout of values"
-    );
+    let (ubos, images, samplers) = desc_set_shape::<B>(&values);
 
     for (set_values, desc_set) in values.into_iter().zip(desc_sets.into_iter()) {
         use gfx_hal::buffer::SubRange;
@@ -297,6 +295,29 @@ pub fn pipeline<B: SupportedBackend>(
     depth_format: Option<Format>,
     attribute_sizes: &[u32],
 ) -> (B::GraphicsPipeline, B::PipelineLayout) {
+    try_pipeline::<B>(
+        device,
+        desc_layout,
+        push_constant_size,
+        vs_bytes,
+        fs_bytes,
+        render_pass,
+        depth_format,
+        attribute_sizes,
+    )
+    .expect("failed to create graphics pipeline")
+}
+
+pub fn try_pipeline<B: SupportedBackend>(
+    device: &B::Device,
+    desc_layout: Option<&B::DescriptorSetLayout>,
+    push_constant_size: u32,
+    vs_bytes: &[u8],
+    fs_bytes: &[u8],
+    render_pass: &B::RenderPass,
+    depth_format: Option<Format>,
+    attribute_sizes: &[u32],
+) -> Result<(B::GraphicsPipeline, B::PipelineLayout), String> {
     use gfx_hal::pso::*;
 
     let push = vec![(
@@ -313,7 +334,24 @@ pub fn pipeline<B: SupportedBackend>(
 
     let shader_modules = [(vs_bytes, false), (fs_bytes, true)]
         .iter()
-        .map(|&(bytes, is_frag)| unsafe { B::make_shader_module(device, bytes, is_frag) })
+        .map(|&(bytes, is_frag)| unsafe { B::try_make_shader_module(device, bytes, is_frag) })
+        .collect::<Vec<_>>();
+
+    if shader_modules.iter().any(Result::is_err) {
+        let mut errors = vec![];
+        for module in shader_modules {
+            match module {
+                Ok(module) => unsafe { device.destroy_shader_module(module) },
+                Err(e) => errors.push(e),
+            }
+        }
+        unsafe { device.destroy_pipeline_layout(pipeline_layout) };
+        return Err(errors.join("\n"));
+    }
+
+    let shader_modules = shader_modules
+        .into_iter()
+        .map(Result::unwrap)
         .collect::<Vec<_>>();
     let mut entries = shader_modules.iter().map(|module| EntryPoint::<B> {
         entry: "main",
@@ -392,9 +430,7 @@ pub fn pipeline<B: SupportedBackend>(
     }
 
     let pipeline = unsafe {
-        let pipeline = device
-            .create_graphics_pipeline(&pipeline_desc, None)
-            .expect("failed to create graphics pipeline");
+        let pipeline = device.create_graphics_pipeline(&pipeline_desc, None);
 
         for module in shader_modules {
             device.destroy_shader_module(module);
@@ -402,22 +438,63 @@ pub fn pipeline<B: SupportedBackend>(
         pipeline
     };
 
-    (pipeline, pipeline_layout)
+    match pipeline {
+        Ok(pipeline) => Ok((pipeline, pipeline_layout)),
+        Err(e) => {
+            unsafe { device.destroy_pipeline_layout(pipeline_layout) };
+            Err(format!("{:?}", e))
+        }
+    }
+}
+
+/// The closest supported mode to `requested`, and what it amounts to.
+/// FIFO is always supported.
+fn choose_present_mode(
+    supported: gfx_hal::window::PresentMode,
+    requested: PresentMode,
+) -> (gfx_hal::window::PresentMode, PresentMode) {
+    use gfx_hal::window::PresentMode as Hal;
+
+    let preferences: &[Hal] = match requested {
+        // Mailbox never blocks, so it doesn't pace frames like vsync should
+        PresentMode::Vsync => &[Hal::FIFO],
+        PresentMode::NoVsync => &[Hal::IMMEDIATE, Hal::MAILBOX, Hal::FIFO],
+        PresentMode::Adaptive => &[Hal::RELAXED, Hal::FIFO],
+    };
+
+    let mode = preferences
+        .iter()
+        .copied()
+        .find(|&mode| supported.contains(mode))
+        .unwrap_or(Hal::FIFO);
+
+    let achieved = match mode {
+        Hal::IMMEDIATE | Hal::MAILBOX => PresentMode::NoVsync,
+        Hal::RELAXED => PresentMode::Adaptive,
+        _ => PresentMode::Vsync,
+    };
+
+    (mode, achieved)
 }
 
+/// Returns the attachment for the new framebuffers, and the present mode
+/// that was actually used.
 pub fn reconfigure_swapchain<B: Backend>(
     surface: &mut B::Surface,
     adapter: &Adapter<B>,
     device: &B::Device,
     surface_color_format: Format,
     surface_extent: &mut gfx_hal::window::Extent2D,
-) -> FramebufferAttachment {
+    present_mode: PresentMode,
+) -> (FramebufferAttachment, PresentMode) {
     use gfx_hal::window::SwapchainConfig;
 
     let caps = surface.capabilities(&adapter.physical_device);
 
+    let (mode, achieved) = choose_present_mode(caps.present_modes, present_mode);
     let mut swapchain_config =
-        SwapchainConfig::from_caps(&caps, surface_color_format, *surface_extent);
+        SwapchainConfig::from_caps(&caps, surface_color_format, *surface_extent)
+            .with_present_mode(mode);
 
     let framebuffer_attachment = swapchain_config.framebuffer_attachment();
 
@@ -434,7 +511,7 @@ pub fn reconfigure_swapchain<B: Backend>(
             .expect("failed to configure swapchain");
     };
 
-    framebuffer_attachment
+    (framebuffer_attachment, achieved)
 }
 
 // TODO: Remove viewport pls
@@ -3,3 +3,252 @@ pub use buttons::winit_support::*;
 
 pub use winit::event::MouseButton;
 pub use winit::event::VirtualKeyCode as Key;
+
+pub mod actions;
+#[cfg(feature = "gamepad")]
+pub mod gamepad;
+pub mod pointer;
+pub mod text;
+
+pub use self::actions::{ActionMap, Binding};
+#[cfg(feature = "gamepad")]
+pub use self::gamepad::{AxisDirection, GamepadAxis, GamepadButton};
+pub use self::pointer::{Pointer, PointerId, PointerPhase};
+pub use self::text::TextInputEvent;
+
+use std::collections::HashSet;
+
+use winit::{
+    event::{ElementState, Event, KeyboardInput, WindowEvent},
+    window::Window,
+};
+
+use crate::draw::CanvasConfig;
+
+/// Tracks which keys and buttons are held for `ActionMap`, as well as text
+/// input, the mouse, touches, and, with the `gamepad` feature, gamepads.
+///
+/// The first finger down stands in for the left mouse button, and its
+/// position for the mouse's, so code written for the mouse mostly works on
+/// touch screens too.
+#[derive(Default)]
+pub struct InputState {
+    text: text::TextInput,
+    pointers: pointer::Pointers,
+    held: HashSet<Binding>,
+    pressed: HashSet<Binding>,
+    released: HashSet<Binding>,
+    capturing: bool,
+    captured: Option<Binding>,
+    #[cfg(feature = "gamepad")]
+    gamepads: gamepad::Gamepads,
+}
+
+impl InputState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Map pointer positions onto a canvas, the same way it's drawn into
+    /// the window. Until this is called, they're in logical window
+    /// coordinates. On wasm, this also starts listening for touches on the
+    /// canvas, which winit doesn't report there.
+    pub fn set_canvas(&mut self, window: &Window, canvas_config: CanvasConfig) {
+        self.pointers.set_canvas(window, canvas_config);
+    }
+
+    pub fn handle_event<T>(&mut self, event: &Event<T>) {
+        #[cfg(target_arch = "wasm32")]
+        for state in self.pointers.web_touches() {
+            self.button_changed(Binding::Mouse(MouseButton::Left), state);
+        }
+
+        #[cfg(feature = "gamepad")]
+        if let Event::NewEvents(_) = event {
+            for (binding, state) in self.gamepads.poll() {
+                self.button_changed(binding, state);
+            }
+        }
+
+        let event = match event {
+            Event::WindowEvent { event, .. } => event,
+            _ => return,
+        };
+
+        match event {
+            WindowEvent::ReceivedCharacter(ch) => self.text.received_character(*ch),
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        virtual_keycode: Some(key),
+                        state,
+                        ..
+                    },
+                ..
+            } => self.button_changed(Binding::Key(*key), *state),
+            WindowEvent::MouseInput { button, state, .. } => {
+                if *button == MouseButton::Left {
+                    self.pointers.mouse_button(*state);
+                }
+                self.button_changed(Binding::Mouse(*button), *state)
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                self.pointers.mouse_moved([position.x, position.y])
+            }
+            WindowEvent::CursorLeft { .. } => self.pointers.mouse_left(),
+            WindowEvent::Touch(touch) => {
+                let position = [touch.location.x, touch.location.y];
+                if let Some(state) = self.pointers.touch(touch.id, touch.phase, position) {
+                    self.button_changed(Binding::Mouse(MouseButton::Left), state);
+                }
+            }
+            WindowEvent::Resized(size) => self.pointers.resized([size.width, size.height]),
+            WindowEvent::ScaleFactorChanged {
+                scale_factor,
+                new_inner_size,
+            } => self
+                .pointers
+                .scale_factor_changed(*scale_factor, [new_inner_size.width, new_inner_size.height]),
+            WindowEvent::Focused(false) => {
+                self.pointers.cancel_all();
+                self.released.extend(self.held.drain());
+            }
+            _ => (),
+        }
+    }
+
+    fn button_changed(&mut self, binding: Binding, state: ElementState) {
+        match state {
+            ElementState::Pressed => {
+                // Ignore key repeats
+                if self.held.insert(binding) {
+                    self.pressed.insert(binding);
+
+                    if self.capturing {
+                        self.capturing = false;
+                        self.captured = Some(binding);
+                    }
+                }
+            }
+            ElementState::Released => {
+                if self.held.remove(&binding) {
+                    self.released.insert(binding);
+                }
+            }
+        }
+    }
+
+    /// Call at the end of each frame, after handling input, to reset which
+    /// buttons count as pressed or released this frame.
+    pub fn finish_frame(&mut self) {
+        self.pressed.clear();
+        self.released.clear();
+        self.pointers.finish_frame();
+    }
+
+    pub fn binding_held(&self, binding: Binding) -> bool {
+        self.held.contains(&binding)
+    }
+
+    pub fn binding_pressed(&self, binding: Binding) -> bool {
+        self.pressed.contains(&binding)
+    }
+
+    pub fn binding_released(&self, binding: Binding) -> bool {
+        self.released.contains(&binding)
+    }
+
+    /// How far the binding is held, from 0.0 to 1.0. Keys and buttons are
+    /// either 0.0 or 1.0.
+    pub fn binding_value(&self, binding: Binding) -> f32 {
+        match binding {
+            #[cfg(feature = "gamepad")]
+            Binding::GamepadAxis(axis, direction) => self.gamepads.axis_value(axis, direction),
+            _ => match self.binding_held(binding) {
+                true => 1.,
+                false => 0.,
+            },
+        }
+    }
+
+    /// How far a gamepad axis has to be pushed, from 0.0 to 1.0, to count
+    /// as held. The default is 0.5.
+    #[cfg(feature = "gamepad")]
+    pub fn set_axis_threshold(&mut self, threshold: f32) {
+        self.gamepads.set_threshold(threshold);
+    }
+
+    /// Every finger on the screen, plus the mouse while its left button is
+    /// held. Pointers that were lifted this frame are included, with a
+    /// phase of `Ended` or `Cancelled`.
+    pub fn pointers(&self) -> &[Pointer] {
+        self.pointers.pointers()
+    }
+
+    /// The mouse position, or the first finger's while a touch is held.
+    pub fn mouse_position(&self) -> Option<[f32; 2]> {
+        self.pointers.mouse_position()
+    }
+
+    /// The distance between the first two fingers down.
+    pub fn pinch_distance(&self) -> Option<f32> {
+        self.pointers.pinch_distance()
+    }
+
+    /// How much the first two fingers have moved apart this frame. This is
+    /// negative when they're pinched together.
+    pub fn pinch_delta(&self) -> Option<f32> {
+        self.pointers.pinch_delta()
+    }
+
+    /// How far the midpoint of the first two fingers has moved this frame.
+    pub fn two_finger_pan(&self) -> Option<[f32; 2]> {
+        self.pointers.two_finger_pan()
+    }
+
+    /// Wait for the next key, mouse button, or gamepad press, for the
+    /// player to pick a new binding. Collect it with `take_captured`.
+    pub fn start_capture(&mut self) {
+        self.capturing = true;
+        self.captured = None;
+    }
+
+    pub fn cancel_capture(&mut self) {
+        self.capturing = false;
+    }
+
+    pub fn capturing(&self) -> bool {
+        self.capturing
+    }
+
+    /// The press captured since `start_capture`, if there's been one.
+    pub fn take_captured(&mut self) -> Option<Binding> {
+        self.captured.take()
+    }
+
+    /// Start collecting text input. On wasm, this also pops up the soft
+    /// keyboard on mobile browsers, so it should be called in response to
+    /// a tap or click.
+    pub fn start_text_input(&mut self) {
+        self.text.start();
+    }
+
+    pub fn stop_text_input(&mut self) {
+        self.text.stop();
+    }
+
+    pub fn text_input_active(&self) -> bool {
+        self.text.active()
+    }
+
+    /// All text input events since this was last called.
+    pub fn text_input(&mut self) -> impl Iterator<Item = TextInputEvent> {
+        self.text.take_events().into_iter()
+    }
+
+    /// The characters committed since this was last called, excluding any
+    /// that are still being composed.
+    pub fn take_text(&mut self) -> String {
+        self.text.take_committed()
+    }
+}
@@ -0,0 +1,118 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use serde::{Deserialize, Serialize};
+
+use super::{InputState, Key, MouseButton};
+
+#[cfg(feature = "gamepad")]
+use super::gamepad::{AxisDirection, GamepadAxis, GamepadButton};
+
+/// A raw input that can be bound to an action.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Binding {
+    Key(Key),
+    Mouse(MouseButton),
+    /// A button on any gamepad.
+    #[cfg(feature = "gamepad")]
+    GamepadButton(GamepadButton),
+    /// An axis on any gamepad, pushed in one direction past the threshold
+    /// set with `InputState::set_axis_threshold`.
+    #[cfg(feature = "gamepad")]
+    GamepadAxis(GamepadAxis, AxisDirection),
+}
+
+/// Maps the game's actions (`A`, usually an enum) to the inputs that
+/// trigger them. Each action can have any number of bindings.
+///
+/// Serializes as a map from action to bindings, so it can be saved with
+/// the player's other settings.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ActionMap<A: Eq + Hash> {
+    bindings: HashMap<A, Vec<Binding>>,
+}
+
+impl<A: Eq + Hash> Default for ActionMap<A> {
+    fn default() -> Self {
+        ActionMap {
+            bindings: HashMap::new(),
+        }
+    }
+}
+
+impl<A: Eq + Hash> ActionMap<A> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_bindings<I>(bindings: I) -> Self
+    where
+        I: IntoIterator<Item = (A, Binding)>,
+    {
+        let mut map = Self::new();
+        for (action, binding) in bindings {
+            map.bind(action, binding);
+        }
+        map
+    }
+
+    /// Add a binding for the action, alongside any it already has.
+    pub fn bind(&mut self, action: A, binding: Binding) {
+        let bindings = self.bindings.entry(action).or_insert_with(Vec::new);
+        if !bindings.contains(&binding) {
+            bindings.push(binding);
+        }
+    }
+
+    /// Replace all of the action's bindings with this one.
+    pub fn rebind(&mut self, action: A, binding: Binding) {
+        self.bindings.insert(action, vec![binding]);
+    }
+
+    pub fn unbind(&mut self, action: &A, binding: Binding) {
+        if let Some(bindings) = self.bindings.get_mut(action) {
+            bindings.retain(|&b| b != binding);
+        }
+    }
+
+    pub fn bindings(&self, action: &A) -> &[Binding] {
+        self.bindings
+            .get(action)
+            .map(|bindings| bindings.as_slice())
+            .unwrap_or(&[])
+    }
+
+    /// Whether any of the action's bindings were pressed this frame.
+    pub fn pressed(&self, input: &InputState, action: &A) -> bool {
+        self.bindings(action)
+            .iter()
+            .any(|&binding| input.binding_pressed(binding))
+    }
+
+    /// Whether any of the action's bindings are held.
+    pub fn held(&self, input: &InputState, action: &A) -> bool {
+        self.bindings(action)
+            .iter()
+            .any(|&binding| input.binding_held(binding))
+    }
+
+    /// Whether any of the action's bindings were released this frame.
+    pub fn released(&self, input: &InputState, action: &A) -> bool {
+        self.bindings(action)
+            .iter()
+            .any(|&binding| input.binding_released(binding))
+    }
+
+    /// From -1.0 to 1.0, depending on how far each of the two actions is
+    /// held. Keys and buttons count as held all the way, and gamepad axes
+    /// by how far they're pushed.
+    pub fn axis(&self, input: &InputState, negative: &A, positive: &A) -> f32 {
+        let value = |action| {
+            self.bindings(action)
+                .iter()
+                .map(|&binding| input.binding_value(binding))
+                .fold(0., f32::max)
+        };
+        value(positive) - value(negative)
+    }
+}
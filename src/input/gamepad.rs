@@ -0,0 +1,110 @@
+use std::collections::{HashMap, HashSet};
+
+use gilrs::{EventType, GamepadId, Gilrs};
+use serde::{Deserialize, Serialize};
+use winit::event::ElementState;
+
+use super::Binding;
+
+pub use gilrs::{Axis as GamepadAxis, Button as GamepadButton};
+
+/// Which way a gamepad axis has to be pushed to trigger a binding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum AxisDirection {
+    Negative,
+    Positive,
+}
+
+impl AxisDirection {
+    fn sign(self) -> f32 {
+        match self {
+            AxisDirection::Negative => -1.,
+            AxisDirection::Positive => 1.,
+        }
+    }
+}
+
+/// Polls every connected gamepad. Buttons on any gamepad count as the same
+/// binding, and each axis takes the value of whichever gamepad pushes it
+/// furthest.
+pub(crate) struct Gamepads {
+    /// `None` if gamepads aren't supported here, e.g. without udev on Linux.
+    gilrs: Option<Gilrs>,
+    axes: HashMap<(GamepadId, GamepadAxis), f32>,
+    threshold: f32,
+}
+
+impl Default for Gamepads {
+    fn default() -> Self {
+        Gamepads {
+            gilrs: Gilrs::new().ok(),
+            axes: HashMap::new(),
+            threshold: 0.5,
+        }
+    }
+}
+
+impl Gamepads {
+    pub fn set_threshold(&mut self, threshold: f32) {
+        self.threshold = threshold;
+    }
+
+    /// Handle the gamepad events since the last poll, returning which
+    /// bindings changed. An axis binding is held while it's pushed past the
+    /// threshold.
+    pub fn poll(&mut self) -> Vec<(Binding, ElementState)> {
+        let gilrs = match &mut self.gilrs {
+            Some(gilrs) => gilrs,
+            None => return vec![],
+        };
+
+        let mut changes = vec![];
+        while let Some(event) = gilrs.next_event() {
+            match event.event {
+                EventType::ButtonPressed(button, _) => {
+                    changes.push((Binding::GamepadButton(button), ElementState::Pressed))
+                }
+                EventType::ButtonReleased(button, _) => {
+                    changes.push((Binding::GamepadButton(button), ElementState::Released))
+                }
+                EventType::AxisChanged(axis, value, _) => {
+                    self.axes.insert((event.id, axis), value);
+                }
+                EventType::Disconnected => self.axes.retain(|(id, _), _| *id != event.id),
+                _ => (),
+            }
+        }
+
+        let axes = self
+            .axes
+            .keys()
+            .map(|&(_, axis)| axis)
+            .collect::<HashSet<_>>();
+        for axis in axes {
+            for &direction in &[AxisDirection::Negative, AxisDirection::Positive] {
+                let state = match self.axis_value(axis, direction) > 0. {
+                    true => ElementState::Pressed,
+                    false => ElementState::Released,
+                };
+                changes.push((Binding::GamepadAxis(axis, direction), state));
+            }
+        }
+        changes
+    }
+
+    /// How far the axis is pushed in `direction`, from 0.0 to 1.0, or 0.0
+    /// if it's not past the threshold.
+    pub fn axis_value(&self, axis: GamepadAxis, direction: AxisDirection) -> f32 {
+        let value = self
+            .axes
+            .iter()
+            .filter(|((_, a), _)| *a == axis)
+            .map(|(_, value)| value * direction.sign())
+            .fold(0., f32::max);
+
+        match value >= self.threshold {
+            true => value.min(1.),
+            false => 0.,
+        }
+    }
+}
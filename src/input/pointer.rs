@@ -0,0 +1,419 @@
+use winit::{
+    event::{ElementState, TouchPhase},
+    window::Window,
+};
+
+use crate::draw::CanvasConfig;
+
+/// Which mouse or finger a `Pointer` is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PointerId {
+    Mouse,
+    Touch(u64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PointerPhase {
+    /// Pressed this frame.
+    Started,
+    Moved,
+    /// Held without moving this frame.
+    Stationary,
+    /// Lifted this frame. It's gone after `InputState::finish_frame`.
+    Ended,
+    /// The platform took the touch away, e.g. for a system gesture. It's
+    /// gone after `InputState::finish_frame`.
+    Cancelled,
+}
+
+/// A finger on the screen, or the mouse while its left button is held.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pointer {
+    pub id: PointerId,
+    /// In canvas coordinates, once `InputState::set_canvas` has been
+    /// called, or logical window coordinates before then.
+    pub position: [f32; 2],
+    /// The position at the start of the frame, or where it was pressed.
+    pub previous: [f32; 2],
+    pub phase: PointerPhase,
+}
+
+impl Pointer {
+    pub fn active(&self) -> bool {
+        !matches!(self.phase, PointerPhase::Ended | PointerPhase::Cancelled)
+    }
+
+    fn moved_to(&mut self, position: [f32; 2]) {
+        self.position = position;
+        if self.phase != PointerPhase::Started {
+            self.phase = PointerPhase::Moved;
+        }
+    }
+}
+
+/// Maps physical window positions onto the canvas, the same way the canvas
+/// is drawn into the window.
+#[derive(Debug, Clone, Copy)]
+struct CanvasMapping {
+    canvas_config: Option<CanvasConfig>,
+    window_size: [u32; 2],
+    scale_factor: f64,
+}
+
+impl Default for CanvasMapping {
+    fn default() -> Self {
+        CanvasMapping {
+            canvas_config: None,
+            window_size: [0, 0],
+            scale_factor: 1.,
+        }
+    }
+}
+
+impl CanvasMapping {
+    fn map(&self, [x, y]: [f64; 2]) -> [f32; 2] {
+        let logical = [
+            (x / self.scale_factor) as f32,
+            (y / self.scale_factor) as f32,
+        ];
+
+        let canvas_config = match self.canvas_config {
+            Some(canvas_config) if self.window_size[0] > 0 && self.window_size[1] > 0 => {
+                canvas_config
+            }
+            _ => return logical,
+        };
+
+        let properties = canvas_config.canvas_properties(self.window_size, self.scale_factor);
+        let ([vx, vy], [vw, vh]) = properties.viewport_scissor_rect;
+        let [cw, ch] = properties.logical_canvas_size;
+        if vw <= 0 || vh <= 0 {
+            return logical;
+        }
+
+        [
+            ((x - vx as f64) / vw as f64 * cw as f64) as f32,
+            ((y - vy as f64) / vh as f64 * ch as f64) as f32,
+        ]
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct Pointers {
+    pointers: Vec<Pointer>,
+    mapping: CanvasMapping,
+    /// The last physical position of the mouse, if it's over the window.
+    mouse: Option<[f64; 2]>,
+    /// The first finger down, which acts as the left mouse button.
+    primary_touch: Option<u64>,
+
+    #[cfg(target_arch = "wasm32")]
+    touch_listener: Option<web::TouchListener>,
+}
+
+impl Pointers {
+    pub fn pointers(&self) -> &[Pointer] {
+        &self.pointers
+    }
+
+    pub fn set_canvas(&mut self, window: &Window, canvas_config: CanvasConfig) {
+        let size = window.inner_size();
+        self.mapping = CanvasMapping {
+            canvas_config: Some(canvas_config),
+            window_size: [size.width, size.height],
+            scale_factor: window.scale_factor(),
+        };
+
+        #[cfg(target_arch = "wasm32")]
+        if self.touch_listener.is_none() {
+            let canvas = winit::platform::web::WindowExtWebSys::canvas(window);
+            self.touch_listener = Some(web::TouchListener::new(canvas));
+        }
+    }
+
+    /// Handle the touches the canvas has received since the last call,
+    /// returning the presses and releases of the left mouse button they
+    /// stand in for.
+    #[cfg(target_arch = "wasm32")]
+    pub fn web_touches(&mut self) -> Vec<ElementState> {
+        let touches = match &self.touch_listener {
+            Some(listener) => listener.take_touches(),
+            None => return vec![],
+        };
+
+        touches
+            .into_iter()
+            .filter_map(|(id, phase, position)| self.touch(id, phase, position))
+            .collect()
+    }
+
+    pub fn resized(&mut self, window_size: [u32; 2]) {
+        self.mapping.window_size = window_size;
+    }
+
+    pub fn scale_factor_changed(&mut self, scale_factor: f64, window_size: [u32; 2]) {
+        self.mapping.scale_factor = scale_factor;
+        self.mapping.window_size = window_size;
+    }
+
+    /// The mouse position, or the first finger's while a touch is held.
+    pub fn mouse_position(&self) -> Option<[f32; 2]> {
+        let primary_touch = self
+            .primary_touch
+            .and_then(|id| self.get(PointerId::Touch(id)));
+        match primary_touch {
+            Some(pointer) => Some(pointer.position),
+            None => self.mouse.map(|position| self.mapping.map(position)),
+        }
+    }
+
+    pub fn mouse_moved(&mut self, position: [f64; 2]) {
+        self.mouse = Some(position);
+
+        let position = self.mapping.map(position);
+        if let Some(pointer) = self.get_mut(PointerId::Mouse) {
+            pointer.moved_to(position);
+        }
+    }
+
+    pub fn mouse_left(&mut self) {
+        self.mouse = None;
+    }
+
+    pub fn mouse_button(&mut self, state: ElementState) {
+        let position = match self.mouse {
+            Some(position) => self.mapping.map(position),
+            None => return,
+        };
+
+        // Browsers also report touches as the mouse, so ignore it while a
+        // finger is down
+        if cfg!(target_arch = "wasm32") && self.primary_touch.is_some() {
+            return;
+        }
+
+        match state {
+            ElementState::Pressed => self.start(PointerId::Mouse, position),
+            ElementState::Released => self.end(PointerId::Mouse, PointerPhase::Ended),
+        }
+    }
+
+    /// Track a touch, returning a press or release if it's the one standing
+    /// in for the left mouse button.
+    pub fn touch(
+        &mut self,
+        id: u64,
+        phase: TouchPhase,
+        position: [f64; 2],
+    ) -> Option<ElementState> {
+        let position = self.mapping.map(position);
+        let pointer_id = PointerId::Touch(id);
+
+        match phase {
+            TouchPhase::Started => {
+                if cfg!(target_arch = "wasm32") {
+                    self.pointers
+                        .retain(|pointer| pointer.id != PointerId::Mouse);
+                }
+                self.start(pointer_id, position);
+
+                let first = !self.pointers.iter().any(|pointer| {
+                    pointer.active() && pointer.id != pointer_id && pointer.id != PointerId::Mouse
+                });
+                if first && self.primary_touch.is_none() {
+                    self.primary_touch = Some(id);
+                    return Some(ElementState::Pressed);
+                }
+            }
+            TouchPhase::Moved => {
+                if let Some(pointer) = self.get_mut(pointer_id) {
+                    pointer.moved_to(position);
+                }
+            }
+            TouchPhase::Ended | TouchPhase::Cancelled => {
+                if let Some(pointer) = self.get_mut(pointer_id) {
+                    pointer.position = position;
+                }
+
+                let phase = match phase {
+                    TouchPhase::Ended => PointerPhase::Ended,
+                    _ => PointerPhase::Cancelled,
+                };
+                self.end(pointer_id, phase);
+
+                if self.primary_touch == Some(id) {
+                    self.primary_touch = None;
+                    return Some(ElementState::Released);
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Forget every pointer, e.g. when the window loses focus.
+    pub fn cancel_all(&mut self) {
+        for pointer in &mut self.pointers {
+            if pointer.active() {
+                pointer.phase = PointerPhase::Cancelled;
+            }
+        }
+        self.primary_touch = None;
+    }
+
+    pub fn finish_frame(&mut self) {
+        self.pointers.retain(Pointer::active);
+        for pointer in &mut self.pointers {
+            pointer.previous = pointer.position;
+            pointer.phase = PointerPhase::Stationary;
+        }
+    }
+
+    /// The first two fingers down, for gestures.
+    pub fn two_touches(&self) -> Option<(&Pointer, &Pointer)> {
+        let mut touches = self
+            .pointers
+            .iter()
+            .filter(|pointer| pointer.active() && pointer.id != PointerId::Mouse);
+        Some((touches.next()?, touches.next()?))
+    }
+
+    fn start(&mut self, id: PointerId, position: [f32; 2]) {
+        // A pointer that ended this frame can start again straight away
+        self.pointers.retain(|pointer| pointer.id != id);
+        self.pointers.push(Pointer {
+            id,
+            position,
+            previous: position,
+            phase: PointerPhase::Started,
+        });
+    }
+
+    fn end(&mut self, id: PointerId, phase: PointerPhase) {
+        if let Some(pointer) = self.get_mut(id) {
+            pointer.phase = phase;
+        }
+    }
+
+    fn get(&self, id: PointerId) -> Option<&Pointer> {
+        self.pointers
+            .iter()
+            .find(|pointer| pointer.id == id && pointer.active())
+    }
+
+    fn get_mut(&mut self, id: PointerId) -> Option<&mut Pointer> {
+        self.pointers
+            .iter_mut()
+            .find(|pointer| pointer.id == id && pointer.active())
+    }
+}
+
+fn distance([ax, ay]: [f32; 2], [bx, by]: [f32; 2]) -> f32 {
+    ((bx - ax).powi(2) + (by - ay).powi(2)).sqrt()
+}
+
+fn midpoint([ax, ay]: [f32; 2], [bx, by]: [f32; 2]) -> [f32; 2] {
+    [(ax + bx) / 2., (ay + by) / 2.]
+}
+
+impl Pointers {
+    pub fn pinch_distance(&self) -> Option<f32> {
+        let (a, b) = self.two_touches()?;
+        Some(distance(a.position, b.position))
+    }
+
+    pub fn pinch_delta(&self) -> Option<f32> {
+        let (a, b) = self.two_touches()?;
+        Some(distance(a.position, b.position) - distance(a.previous, b.previous))
+    }
+
+    pub fn two_finger_pan(&self) -> Option<[f32; 2]> {
+        let (a, b) = self.two_touches()?;
+        let [x, y] = midpoint(a.position, b.position);
+        let [px, py] = midpoint(a.previous, b.previous);
+        Some([x - px, y - py])
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+pub(crate) mod web {
+    use std::{cell::RefCell, rc::Rc};
+
+    use wasm_bindgen::{closure::Closure, JsCast};
+    use web_sys::{Event, HtmlCanvasElement, TouchEvent};
+    use winit::event::TouchPhase;
+
+    /// Listens for touches on the canvas, which winit doesn't report on the
+    /// web. Positions are in physical pixels, like winit's.
+    pub struct TouchListener {
+        canvas: HtmlCanvasElement,
+        touches: Rc<RefCell<Vec<(u64, TouchPhase, [f64; 2])>>>,
+        _listeners: Vec<(&'static str, Closure<dyn FnMut(Event)>)>,
+    }
+
+    impl TouchListener {
+        pub fn new(canvas: HtmlCanvasElement) -> Self {
+            let touches = Rc::new(RefCell::new(vec![]));
+
+            let listener = |phase: TouchPhase| {
+                let touches = Rc::clone(&touches);
+                let canvas = canvas.clone();
+                Closure::wrap(Box::new(move |event: Event| {
+                    let event = match event.dyn_ref::<TouchEvent>() {
+                        Some(event) => event,
+                        None => return,
+                    };
+
+                    // Stop the page from scrolling or zooming instead
+                    event.prevent_default();
+
+                    let rect = canvas.get_bounding_client_rect();
+                    let ratio = web_sys::window().unwrap().device_pixel_ratio();
+                    let changed = event.changed_touches();
+                    let mut touches = touches.borrow_mut();
+
+                    for i in 0..changed.length() {
+                        if let Some(touch) = changed.get(i) {
+                            let x = (touch.client_x() as f64 - rect.left()) * ratio;
+                            let y = (touch.client_y() as f64 - rect.top()) * ratio;
+                            touches.push((touch.identifier() as u64, phase, [x, y]));
+                        }
+                    }
+                }) as Box<dyn FnMut(Event)>)
+            };
+
+            let listeners = vec![
+                ("touchstart", listener(TouchPhase::Started)),
+                ("touchmove", listener(TouchPhase::Moved)),
+                ("touchend", listener(TouchPhase::Ended)),
+                ("touchcancel", listener(TouchPhase::Cancelled)),
+            ];
+
+            for (name, listener) in &listeners {
+                canvas
+                    .add_event_listener_with_callback(name, listener.as_ref().unchecked_ref())
+                    .unwrap();
+            }
+
+            TouchListener {
+                canvas,
+                touches,
+                _listeners: listeners,
+            }
+        }
+
+        pub fn take_touches(&self) -> Vec<(u64, TouchPhase, [f64; 2])> {
+            std::mem::take(&mut *self.touches.borrow_mut())
+        }
+    }
+
+    impl Drop for TouchListener {
+        fn drop(&mut self) {
+            for (name, listener) in &self._listeners {
+                let _ = self
+                    .canvas
+                    .remove_event_listener_with_callback(name, listener.as_ref().unchecked_ref());
+            }
+        }
+    }
+}
@@ -0,0 +1,228 @@
+/// A change to the text being entered.
+///
+/// NOTE: winit doesn't report IME composition on native platforms yet, so
+/// there composed text arrives as plain `Char`s once it's committed. The
+/// `Composition*` markers only appear on wasm.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TextInputEvent {
+    Char(char),
+    Backspace,
+    CompositionStart,
+    CompositionEnd,
+}
+
+#[derive(Default)]
+pub(crate) struct TextInput {
+    active: bool,
+    events: Vec<TextInputEvent>,
+    committed: String,
+
+    #[cfg(target_arch = "wasm32")]
+    hidden_input: Option<web::HiddenInput>,
+}
+
+impl TextInput {
+    pub fn active(&self) -> bool {
+        self.active
+    }
+
+    pub fn start(&mut self) {
+        self.active = true;
+
+        #[cfg(target_arch = "wasm32")]
+        self.hidden_input
+            .get_or_insert_with(web::HiddenInput::new)
+            .focus();
+    }
+
+    pub fn stop(&mut self) {
+        self.active = false;
+
+        #[cfg(target_arch = "wasm32")]
+        if let Some(hidden_input) = &self.hidden_input {
+            hidden_input.blur();
+        }
+    }
+
+    pub fn received_character(&mut self, ch: char) {
+        // On wasm, the hidden input element receives the text instead
+        if cfg!(target_arch = "wasm32") || !self.active {
+            return;
+        }
+
+        match ch {
+            '\u{8}' | '\u{7f}' => self.push(TextInputEvent::Backspace),
+            ch if ch.is_control() => (),
+            ch => self.push(TextInputEvent::Char(ch)),
+        }
+    }
+
+    pub fn take_events(&mut self) -> Vec<TextInputEvent> {
+        self.sync();
+        std::mem::replace(&mut self.events, Vec::new())
+    }
+
+    pub fn take_committed(&mut self) -> String {
+        self.sync();
+        std::mem::replace(&mut self.committed, String::new())
+    }
+
+    fn push(&mut self, event: TextInputEvent) {
+        if let TextInputEvent::Char(ch) = event {
+            self.committed.push(ch);
+        }
+        self.events.push(event);
+    }
+
+    fn sync(&mut self) {
+        #[cfg(target_arch = "wasm32")]
+        if let Some(hidden_input) = &self.hidden_input {
+            for event in hidden_input.take_events() {
+                if self.active {
+                    self.push(event);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+mod web {
+    use std::{cell::RefCell, rc::Rc};
+
+    use wasm_bindgen::{closure::Closure, JsCast};
+    use web_sys::{CompositionEvent, Event, HtmlInputElement, KeyboardEvent};
+
+    use super::TextInputEvent;
+
+    #[derive(Default)]
+    struct Shared {
+        composing: bool,
+        events: Vec<TextInputEvent>,
+    }
+
+    /// An invisible `<input>` element. Focusing it is the only way to get
+    /// mobile browsers to show their soft keyboard.
+    pub struct HiddenInput {
+        element: HtmlInputElement,
+        shared: Rc<RefCell<Shared>>,
+        _listeners: Vec<(&'static str, Closure<dyn FnMut(Event)>)>,
+    }
+
+    impl HiddenInput {
+        pub fn new() -> Self {
+            let document = web_sys::window().unwrap().document().unwrap();
+            let element = document
+                .create_element("input")
+                .unwrap()
+                .dyn_into::<HtmlInputElement>()
+                .unwrap();
+
+            element.set_type("text");
+            element
+                .set_attribute(
+                    "style",
+                    "position: fixed; left: 0; bottom: 0; width: 1px; height: 1px; \
+                     opacity: 0; border: none; padding: 0;",
+                )
+                .unwrap();
+            element.set_attribute("autocomplete", "off").unwrap();
+            element.set_attribute("autocapitalize", "off").unwrap();
+            document.body().unwrap().append_child(&element).unwrap();
+
+            let shared = Rc::new(RefCell::new(Shared::default()));
+
+            let listeners = vec![
+                ("compositionstart", {
+                    let shared = Rc::clone(&shared);
+                    Closure::wrap(Box::new(move |_: Event| {
+                        let mut shared = shared.borrow_mut();
+                        shared.composing = true;
+                        shared.events.push(TextInputEvent::CompositionStart);
+                    }) as Box<dyn FnMut(Event)>)
+                }),
+                ("compositionend", {
+                    let shared = Rc::clone(&shared);
+                    let element = element.clone();
+                    Closure::wrap(Box::new(move |event: Event| {
+                        let mut shared = shared.borrow_mut();
+                        shared.composing = false;
+                        shared.events.push(TextInputEvent::CompositionEnd);
+
+                        let data = event
+                            .dyn_ref::<CompositionEvent>()
+                            .and_then(CompositionEvent::data)
+                            .unwrap_or_default();
+                        shared.events.extend(data.chars().map(TextInputEvent::Char));
+                        element.set_value("");
+                    }) as Box<dyn FnMut(Event)>)
+                }),
+                ("input", {
+                    let shared = Rc::clone(&shared);
+                    let element = element.clone();
+                    Closure::wrap(Box::new(move |_: Event| {
+                        let mut shared = shared.borrow_mut();
+                        if !shared.composing {
+                            let value = element.value();
+                            shared
+                                .events
+                                .extend(value.chars().map(TextInputEvent::Char));
+                            element.set_value("");
+                        }
+                    }) as Box<dyn FnMut(Event)>)
+                }),
+                ("keydown", {
+                    let shared = Rc::clone(&shared);
+                    Closure::wrap(Box::new(move |event: Event| {
+                        let mut shared = shared.borrow_mut();
+                        let backspace = event
+                            .dyn_ref::<KeyboardEvent>()
+                            .map(|event| event.key() == "Backspace")
+                            .unwrap_or(false);
+
+                        // The element is always empty, so backspace never
+                        // causes an `input` event
+                        if backspace && !shared.composing {
+                            shared.events.push(TextInputEvent::Backspace);
+                        }
+                    }) as Box<dyn FnMut(Event)>)
+                }),
+            ];
+
+            for (name, listener) in &listeners {
+                element
+                    .add_event_listener_with_callback(name, listener.as_ref().unchecked_ref())
+                    .unwrap();
+            }
+
+            HiddenInput {
+                element,
+                shared,
+                _listeners: listeners,
+            }
+        }
+
+        pub fn focus(&self) {
+            self.element.focus().unwrap();
+        }
+
+        pub fn blur(&self) {
+            self.element.blur().unwrap();
+        }
+
+        pub fn take_events(&self) -> Vec<TextInputEvent> {
+            std::mem::replace(&mut self.shared.borrow_mut().events, Vec::new())
+        }
+    }
+
+    impl Drop for HiddenInput {
+        fn drop(&mut self) {
+            for (name, listener) in &self._listeners {
+                let _ = self
+                    .element
+                    .remove_event_listener_with_callback(name, listener.as_ref().unchecked_ref());
+            }
+            self.element.remove();
+        }
+    }
+}
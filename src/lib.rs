@@ -3,9 +3,14 @@ pub mod codegen;
 
 pub mod atlas;
 
+pub mod build_info;
+
 #[cfg(feature = "audio")]
 pub mod audio;
 
+#[cfg(all(feature = "draw_groove", feature = "font"))]
+pub mod debug;
+
 pub mod draw;
 
 #[cfg(feature = "font")]
@@ -32,6 +37,9 @@ pub use lazy_static::lazy_static;
 #[cfg(any(feature = "reloading", feature = "resources"))]
 pub use resource::*;
 
+#[cfg(feature = "resource_keys")]
+pub mod resource_keys;
+
 #[cfg(feature = "resources")]
 pub mod resources;
 
@@ -8,6 +8,14 @@ pub fn init_logging() {
     }
 }
 
+/// Called by the page `jamjar web --debug` generates, so panics are logged
+/// to the console even if the app doesn't call `init_logging`.
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen::prelude::wasm_bindgen]
+pub fn jamjar_set_panic_hook() {
+    std::panic::set_hook(Box::new(console_error_panic_hook::hook));
+}
+
 #[macro_export]
 macro_rules! jprintln {
     () => {
@@ -1,7 +1,163 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+    sync::Mutex,
+    time::SystemTime,
+};
+
 pub use dirty_static::DirtyStatic;
 pub use serde_yaml::from_str as parse_yaml;
 pub use toml::from_str as parse_toml;
 
+/// How many errors are kept for `take_recent_errors` before the oldest are
+/// dropped.
+const MAX_RECENT_ERRORS: usize = 32;
+
+/// A data file that failed to load.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReloadError {
+    pub file: String,
+    pub message: String,
+    pub time: SystemTime,
+}
+
+#[derive(Default)]
+struct ErrorSink {
+    /// The error for each file that's currently failing, and a hash of the
+    /// contents that caused it.
+    failing: HashMap<String, (u64, ReloadError)>,
+    recent: Vec<ReloadError>,
+}
+
+lazy_static::lazy_static! {
+    static ref ERRORS: Mutex<ErrorSink> = Mutex::new(ErrorSink::default());
+}
+
+fn now() -> SystemTime {
+    // NOTE: There's no clock on wasm without the timing feature, so errors
+    // there all have the same time.
+    #[cfg(target_arch = "wasm32")]
+    {
+        SystemTime::UNIX_EPOCH
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        SystemTime::now()
+    }
+}
+
+/// Record that `file` failed to load, and print the error. The same error
+/// isn't recorded or printed again until the file's `contents` change.
+///
+/// This is called by `static_data_mod!` and for shaders, but can also be
+/// used for other reloadable files.
+pub fn report_error<C: Hash + ?Sized>(file: &str, contents: &C, message: String) {
+    let mut hasher = DefaultHasher::new();
+    contents.hash(&mut hasher);
+    let hash = hasher.finish();
+
+    let mut sink = ERRORS.lock().unwrap();
+    if let Some((failed_hash, _)) = sink.failing.get(file) {
+        if *failed_hash == hash {
+            return;
+        }
+    }
+
+    eprintln!("Failed to load {}: {}", file, message);
+
+    let error = ReloadError {
+        file: file.to_owned(),
+        message,
+        time: now(),
+    };
+
+    sink.failing.insert(file.to_owned(), (hash, error.clone()));
+    sink.recent.push(error);
+    if sink.recent.len() > MAX_RECENT_ERRORS {
+        sink.recent.remove(0);
+    }
+}
+
+/// Record that `file` loaded successfully, so its next error is reported.
+pub fn clear_error(file: &str) {
+    ERRORS.lock().unwrap().failing.remove(file);
+}
+
+/// Errors reported since this was last called, oldest first.
+pub fn take_recent_errors() -> Vec<ReloadError> {
+    std::mem::take(&mut ERRORS.lock().unwrap().recent)
+}
+
+/// The latest error for each file that's still failing to load, oldest
+/// first.
+pub fn current_errors() -> Vec<ReloadError> {
+    let sink = ERRORS.lock().unwrap();
+    let mut errors = sink
+        .failing
+        .values()
+        .map(|(_, error)| error.clone())
+        .collect::<Vec<_>>();
+    errors.sort_by_key(|error| error.time);
+    errors
+}
+
+/// Draws the errors for files that are still failing to load over the game.
+#[cfg(all(feature = "draw_groove", feature = "font"))]
+#[derive(Debug, Clone)]
+pub struct ErrorOverlay {
+    pub enabled: bool,
+    /// The most errors shown at once. The newest are shown.
+    pub max_errors: usize,
+    pub scale: f32,
+}
+
+#[cfg(all(feature = "draw_groove", feature = "font"))]
+impl Default for ErrorOverlay {
+    fn default() -> Self {
+        ErrorOverlay {
+            enabled: cfg!(debug_assertions),
+            max_errors: 4,
+            scale: 11.,
+        }
+    }
+}
+
+#[cfg(all(feature = "draw_groove", feature = "font"))]
+impl ErrorOverlay {
+    /// Queue the errors as text, one line each, starting at `pos`.
+    pub fn draw(
+        &self,
+        text_painter: &mut crate::draw::text::TextPainter,
+        font: &crate::font::Font,
+        pos: [f32; 2],
+    ) {
+        if !self.enabled {
+            return;
+        }
+
+        let errors = current_errors();
+        let skip = errors.len().saturating_sub(self.max_errors);
+        let lines = errors.iter().skip(skip).flat_map(|error| {
+            let mut lines = error.message.lines();
+            let first = format!("{}: {}", error.file, lines.next().unwrap_or(""));
+            std::iter::once(first).chain(lines.map(|line| format!("  {}", line)))
+        });
+
+        let [x, y] = pos;
+        for (i, line) in lines.enumerate() {
+            text_painter.draw_outlined_text(
+                font,
+                &line,
+                [x, y + i as f32 * self.scale * 1.2],
+                self.scale,
+                [1., 0.3, 0.3, 1.],
+                ([0., 0., 0., 1.], 1),
+            );
+        }
+    }
+}
+
 #[macro_export]
 macro_rules! static_data_mod {
     ($visibility:vis mod $modname:ident { $(static $constname:ident : $datatype:ty = $fnname:ident ( $path:literal ) ;)* }) => {
@@ -11,10 +167,22 @@ macro_rules! static_data_mod {
 
             $(
                 fn $fnname() -> Result<$datatype, ()> {
-                    if $path.ends_with(".toml") {
-                        jamjar::reloading::parse_toml(&jamjar::resource_str!($path)).map_err(|e| eprintln!("Failed to load {}: {}", stringify!($constname), e))
+                    let contents = jamjar::resource_str!($path);
+                    let result = if $path.ends_with(".toml") {
+                        jamjar::reloading::parse_toml(&contents).map_err(|e| e.to_string())
                     } else {
-                        jamjar::reloading::parse_yaml(&jamjar::resource_str!($path)).map_err(|e| eprintln!("Failed to load {}: {}", stringify!($constname), e))
+                        jamjar::reloading::parse_yaml(&contents).map_err(|e| e.to_string())
+                    };
+
+                    match result {
+                        Ok(data) => {
+                            jamjar::reloading::clear_error($path);
+                            Ok(data)
+                        }
+                        Err(message) => {
+                            jamjar::reloading::report_error($path, &*contents, message);
+                            Err(())
+                        }
                     }
                 }
             )*
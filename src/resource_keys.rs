@@ -0,0 +1,362 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+use unicode_normalization::UnicodeNormalization;
+
+/// How resource keys (file paths or names) are compared.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyMode {
+    /// Keys must match exactly.
+    #[default]
+    Exact,
+    /// Keys are normalized with `normalize_key` before comparing, so they
+    /// agree between case-sensitive and case-insensitive file systems.
+    Normalized,
+    /// Like `Normalized`, but it's an error for two keys to be the same
+    /// once normalized and stripped down to their `variant_key`, i.e. for
+    /// two files to match the same generated enum variant.
+    Strict,
+}
+
+impl KeyMode {
+    pub fn key(self, key: &str) -> String {
+        match self {
+            KeyMode::Exact => key.to_owned(),
+            KeyMode::Normalized | KeyMode::Strict => normalize_key(key),
+        }
+    }
+}
+
+/// Lowercase, use forward slashes, and use NFC unicode.
+pub fn normalize_key(key: &str) -> String {
+    key.replace('\\', "/")
+        .nfc()
+        .collect::<String>()
+        .to_lowercase()
+}
+
+/// The normalized key, with anything that can't appear in an identifier
+/// removed. This is used to match generated enum variants (`DuellingTheme`)
+/// to the files they came from (`duelling_theme.ogg`).
+pub(crate) fn variant_key(key: &str) -> String {
+    normalize_key(key)
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .collect()
+}
+
+/// The `variant_key` for a resource file: its file name without the
+/// extension or directory.
+pub(crate) fn file_key(path: &str) -> String {
+    let path = path.replace('\\', "/");
+    let name = path.rsplit('/').next().unwrap_or(&path);
+    let stem = match name.rfind('.') {
+        Some(0) | None => name,
+        Some(dot) => &name[..dot],
+    };
+    variant_key(stem)
+}
+
+/// For each key, the indices of the files it's paired with. In `Exact`
+/// mode, keys and files are paired in order. Otherwise, a key matches every
+/// file whose name is the same once normalized.
+///
+/// This is how `resources::map_resources_with` and friends pair keys with
+/// resources, and what `verify_keys` checks.
+pub fn match_keys<K, F>(mode: KeyMode, keys: &[K], files: &[F]) -> Vec<Vec<usize>>
+where
+    K: AsRef<str>,
+    F: AsRef<str>,
+{
+    if mode == KeyMode::Exact {
+        return (0..keys.len())
+            .map(|i| if i < files.len() { vec![i] } else { vec![] })
+            .collect();
+    }
+
+    let mut by_key: HashMap<String, Vec<usize>> = HashMap::new();
+    for (i, file) in files.iter().enumerate() {
+        by_key
+            .entry(file_key(file.as_ref()))
+            .or_insert_with(Vec::new)
+            .push(i);
+    }
+
+    keys.iter()
+        .map(|key| {
+            by_key
+                .get(&variant_key(key.as_ref()))
+                .cloned()
+                .unwrap_or_default()
+        })
+        .collect()
+}
+
+/// Whether `path` matches an ignore pattern, where `*` and `?` match any
+/// characters. Patterns without a `/` are matched against the file name.
+pub fn is_ignored<P: AsRef<str>>(path: &str, patterns: &[P]) -> bool {
+    fn matches(pattern: &[char], text: &[char]) -> bool {
+        match pattern.split_first() {
+            None => text.is_empty(),
+            Some(('*', rest)) => (0..=text.len()).any(|i| matches(rest, &text[i..])),
+            Some(('?', rest)) => !text.is_empty() && matches(rest, &text[1..]),
+            Some((c, rest)) => text.first() == Some(c) && matches(rest, &text[1..]),
+        }
+    }
+
+    let path = path.replace('\\', "/");
+    let name = path.rsplit('/').next().unwrap_or(&path);
+    patterns.iter().any(|pattern| {
+        let pattern = pattern.as_ref().replace('\\', "/");
+        let text = match pattern.contains('/') {
+            true => path.as_str(),
+            false => name,
+        };
+        matches(
+            &pattern.chars().collect::<Vec<_>>(),
+            &text.chars().collect::<Vec<_>>(),
+        )
+    })
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeyMismatch {
+    /// No file matches the key.
+    Missing { key: String },
+    /// More than one file matches the key.
+    Ambiguous { key: String, paths: Vec<String> },
+    /// No key matches the file, and it isn't ignored.
+    Unmapped { path: String },
+    /// In `Exact` mode, the file paired with the key has a different name,
+    /// so the files aren't in the same order as the keys.
+    Misordered { key: String, path: String },
+    /// In `Strict` mode, these files would all match the same key.
+    Collision { key: String, paths: Vec<String> },
+}
+
+impl std::fmt::Display for KeyMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            KeyMismatch::Missing { key } => write!(f, "`{}` has no file", key),
+            KeyMismatch::Ambiguous { key, paths } => {
+                write!(
+                    f,
+                    "`{}` matches more than one file: {}",
+                    key,
+                    paths.join(", ")
+                )
+            }
+            KeyMismatch::Unmapped { path } => write!(f, "{} has no key", path),
+            KeyMismatch::Misordered { key, path } => {
+                write!(f, "`{}` is paired with {}", key, path)
+            }
+            KeyMismatch::Collision { key, paths } => {
+                write!(f, "{} all have the key `{}`", paths.join(", "), key)
+            }
+        }
+    }
+}
+
+/// Every mismatch found by `verify_keys`, in the order they were found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyMismatches(pub Vec<KeyMismatch>);
+
+impl std::fmt::Display for KeyMismatches {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Resource keys don't match the files:")?;
+        for mismatch in &self.0 {
+            write!(f, "\n  {}", mismatch)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for KeyMismatches {}
+
+/// Check that every key is paired with exactly one file, and every file
+/// with a key, unless it matches one of the `ignore` patterns (see
+/// `is_ignored`). In `Strict` mode, files that would match the same key are
+/// reported too, whether or not there's a key for them.
+pub fn verify_keys<K, F, P>(
+    mode: KeyMode,
+    keys: &[K],
+    files: &[F],
+    ignore: &[P],
+) -> Result<(), KeyMismatches>
+where
+    K: AsRef<str>,
+    F: AsRef<str>,
+    P: AsRef<str>,
+{
+    let files = files
+        .iter()
+        .map(AsRef::as_ref)
+        .filter(|file| !is_ignored(file, ignore))
+        .collect::<Vec<_>>();
+
+    let matches = match_keys(mode, keys, &files);
+    let mut mapped = vec![false; files.len()];
+    let mut mismatches = vec![];
+
+    for (key, indices) in keys.iter().zip(&matches) {
+        let key = key.as_ref();
+        for &i in indices {
+            mapped[i] = true;
+        }
+
+        match indices.as_slice() {
+            [] => mismatches.push(KeyMismatch::Missing {
+                key: key.to_owned(),
+            }),
+            &[i] if mode == KeyMode::Exact && file_key(files[i]) != variant_key(key) => mismatches
+                .push(KeyMismatch::Misordered {
+                    key: key.to_owned(),
+                    path: files[i].to_owned(),
+                }),
+            [_] => (),
+            // These files are all reported as a collision below
+            _ if mode == KeyMode::Strict => (),
+            _ => mismatches.push(KeyMismatch::Ambiguous {
+                key: key.to_owned(),
+                paths: indices.iter().map(|&i| files[i].to_owned()).collect(),
+            }),
+        }
+    }
+
+    for (file, mapped) in files.iter().zip(mapped) {
+        if !mapped {
+            mismatches.push(KeyMismatch::Unmapped {
+                path: (*file).to_owned(),
+            });
+        }
+    }
+
+    if mode == KeyMode::Strict {
+        for (key, paths) in collisions(files.iter().map(|file| (file_key(file), *file))) {
+            mismatches.push(KeyMismatch::Collision { key, paths });
+        }
+    }
+
+    match mismatches.is_empty() {
+        true => Ok(()),
+        false => Err(KeyMismatches(mismatches)),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyCollision {
+    pub key: String,
+    pub paths: Vec<String>,
+}
+
+impl std::fmt::Display for KeyCollision {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "Resources {} all have the key `{}`",
+            self.paths.join(", "),
+            self.key
+        )
+    }
+}
+
+impl std::error::Error for KeyCollision {}
+
+/// Check that no two keys are the same once reduced to their
+/// `variant_key`, the way keys are matched to files. Only `Strict` mode
+/// reports collisions; in the other modes, later keys replace earlier ones.
+pub fn check_keys<I, S>(mode: KeyMode, keys: I) -> Result<(), KeyCollision>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    if mode != KeyMode::Strict {
+        return Ok(());
+    }
+
+    let keys = keys.into_iter().collect::<Vec<_>>();
+    let keyed = keys
+        .iter()
+        .map(|key| (variant_key(key.as_ref()), key.as_ref()));
+
+    match collisions(keyed).into_iter().next() {
+        Some((key, paths)) => Err(KeyCollision { key, paths }),
+        None => Ok(()),
+    }
+}
+
+/// Each key shared by more than one of the names, with those names, sorted.
+fn collisions<'a, I>(keyed: I) -> Vec<(String, Vec<String>)>
+where
+    I: IntoIterator<Item = (String, &'a str)>,
+{
+    let mut seen: HashMap<String, Vec<String>> = HashMap::new();
+    for (key, name) in keyed {
+        seen.entry(key)
+            .or_insert_with(Vec::new)
+            .push(name.to_owned());
+    }
+
+    let mut collisions = seen
+        .into_iter()
+        .filter(|(_, names)| names.len() > 1)
+        .collect::<Vec<_>>();
+    collisions.sort();
+    collisions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strict_keys_collide_like_enum_variants() {
+        let collision = check_keys(
+            KeyMode::Strict,
+            &["duelling_theme", "jump", "Duelling-Theme"],
+        );
+        assert_eq!(
+            collision,
+            Err(KeyCollision {
+                key: "duellingtheme".to_owned(),
+                paths: vec!["duelling_theme".to_owned(), "Duelling-Theme".to_owned()],
+            })
+        );
+
+        assert!(check_keys(KeyMode::Normalized, &["duelling_theme", "Duelling-Theme"]).is_ok());
+    }
+
+    #[test]
+    fn strict_verification_reports_colliding_files() {
+        let files = ["duelling_theme.ogg", "duelling-theme.wav", "jump.wav"];
+        let ignore: &[&str] = &[];
+
+        assert_eq!(
+            verify_keys(KeyMode::Strict, &["DuellingTheme", "Jump"], &files, ignore),
+            Err(KeyMismatches(vec![KeyMismatch::Collision {
+                key: "duellingtheme".to_owned(),
+                paths: vec![
+                    "duelling_theme.ogg".to_owned(),
+                    "duelling-theme.wav".to_owned()
+                ],
+            }]))
+        );
+
+        assert_eq!(
+            verify_keys(
+                KeyMode::Normalized,
+                &["DuellingTheme", "Jump"],
+                &files,
+                ignore
+            ),
+            Err(KeyMismatches(vec![KeyMismatch::Ambiguous {
+                key: "DuellingTheme".to_owned(),
+                paths: vec![
+                    "duelling_theme.ogg".to_owned(),
+                    "duelling-theme.wav".to_owned()
+                ],
+            }]))
+        );
+    }
+}
@@ -2,6 +2,171 @@ use std::collections::HashMap;
 
 use resource::Resource;
 
+pub use crate::resource_keys::{
+    check_keys, match_keys, normalize_key, verify_keys, KeyCollision, KeyMismatch, KeyMismatches,
+    KeyMode,
+};
+
+/// Resources by name, where names are normalized on insertion and lookup
+/// according to the map's `KeyMode`.
+#[derive(Debug, Clone)]
+pub struct ResourceMap<R> {
+    mode: KeyMode,
+    resources: HashMap<String, R>,
+}
+
+impl<R> ResourceMap<R> {
+    pub fn new<I, S>(mode: KeyMode, resources: I) -> Result<Self, KeyCollision>
+    where
+        I: IntoIterator<Item = (S, R)>,
+        S: AsRef<str>,
+    {
+        let resources = resources.into_iter().collect::<Vec<_>>();
+        check_keys(mode, resources.iter().map(|(key, _)| key.as_ref()))?;
+
+        Ok(ResourceMap {
+            mode,
+            resources: resources
+                .into_iter()
+                .map(|(key, res)| (mode.key(key.as_ref()), res))
+                .collect(),
+        })
+    }
+
+    pub fn mode(&self) -> KeyMode {
+        self.mode
+    }
+
+    pub fn get(&self, key: &str) -> Option<&R> {
+        self.resources.get(&self.mode.key(key))
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.resources.contains_key(&self.mode.key(key))
+    }
+
+    pub fn insert(&mut self, key: &str, resource: R) -> Option<R> {
+        self.resources.insert(self.mode.key(key), resource)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &R)> {
+        self.resources.iter().map(|(key, res)| (key.as_str(), res))
+    }
+
+    pub fn len(&self) -> usize {
+        self.resources.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.resources.is_empty()
+    }
+}
+
+/// Pair up generated keys with resources, using `resource_keys::match_keys`.
+/// In `Exact` mode, they're paired in order. Otherwise, each key is matched
+/// to the file with the same normalized name, so the order files are listed
+/// in doesn't matter.
+fn pair_keys<'a, T, X, I, F, R>(
+    mode: KeyMode,
+    keys: &[T],
+    resources: I,
+    convert: F,
+) -> Result<HashMap<T, R>, KeyCollision>
+where
+    T: std::hash::Hash + std::cmp::Eq + Copy + std::fmt::Display,
+    X: 'a,
+    I: IntoIterator<Item = &'a (&'static str, X)>,
+    F: Fn(&X) -> R,
+{
+    let resources = resources.into_iter().collect::<Vec<_>>();
+    check_keys(
+        mode,
+        resources.iter().map(|(filename, _)| stem_name(filename)),
+    )?;
+
+    let names = keys.iter().map(ToString::to_string).collect::<Vec<_>>();
+    let filenames = resources
+        .iter()
+        .map(|(filename, _)| *filename)
+        .collect::<Vec<_>>();
+    let matches = match_keys(mode, &names, &filenames);
+
+    // If more than one file matches, the last one wins
+    Ok(keys
+        .iter()
+        .zip(matches)
+        .filter_map(|(&key, indices)| {
+            let &i = indices.last()?;
+            Some((key, convert(&resources[i].1)))
+        })
+        .collect())
+}
+
+/// Check that every key is paired with exactly one of the resources by the
+/// `_with` mapping functions in the same `mode`, and that every resource
+/// has a key unless it matches an `ignore` pattern.
+///
+/// Call this (e.g. with `T::ALL` and `resource_list!`) to find renamed or
+/// missing files at startup, rather than when they're first played.
+pub fn verify_resources<'a, T, X, I, P>(
+    mode: KeyMode,
+    keys: &[T],
+    resources: I,
+    ignore: &[P],
+) -> Result<(), KeyMismatches>
+where
+    T: std::fmt::Display,
+    X: 'a,
+    I: IntoIterator<Item = &'a (&'static str, X)>,
+    P: AsRef<str>,
+{
+    let names = keys.iter().map(ToString::to_string).collect::<Vec<_>>();
+    let filenames = resources
+        .into_iter()
+        .map(|(filename, _)| *filename)
+        .collect::<Vec<_>>();
+    verify_keys(mode, &names, &filenames, ignore)
+}
+
+pub fn map_resources_with<'a, T, I>(
+    mode: KeyMode,
+    keys: &[T],
+    resources: I,
+) -> Result<HashMap<T, Resource<[u8]>>, KeyCollision>
+where
+    T: std::hash::Hash + std::cmp::Eq + Copy + std::fmt::Display,
+    I: IntoIterator<Item = &'a (&'static str, Resource<[u8]>)>,
+{
+    pair_keys(mode, keys, resources, |res| res.clone())
+}
+
+pub fn map_str_resources_with<'a, T, I>(
+    mode: KeyMode,
+    keys: &[T],
+    resources: I,
+) -> Result<HashMap<T, Resource<str>>, KeyCollision>
+where
+    T: std::hash::Hash + std::cmp::Eq + Copy + std::fmt::Display,
+    I: IntoIterator<Item = &'a (&'static str, Resource<str>)>,
+{
+    pair_keys(mode, keys, resources, |res| res.clone())
+}
+
+#[cfg(feature = "audio")]
+pub fn map_audio_resources_with<'a, T, I>(
+    mode: KeyMode,
+    keys: &[T],
+    resources: I,
+) -> Result<HashMap<T, crate::audio::AudioBytes>, KeyCollision>
+where
+    T: std::hash::Hash + std::cmp::Eq + Copy + std::fmt::Display,
+    I: IntoIterator<Item = &'a (&'static str, Resource<[u8]>)>,
+{
+    pair_keys(mode, keys, resources, |res| {
+        crate::audio::AudioBytes::new(res.clone().into())
+    })
+}
+
 pub fn map_resources<'a, T, I>(keys: &[T], resources: I) -> HashMap<T, Resource<[u8]>>
 where
     T: std::hash::Hash + std::cmp::Eq + Copy,
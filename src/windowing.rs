@@ -1,3 +1,16 @@
+pub mod background;
+pub mod canvas;
+pub mod cursor;
+pub mod monitor;
+pub mod multi;
+pub mod redraw;
+
+pub use self::background::{BackgroundPolicy, Visibility};
+pub use self::canvas::WebCanvasOptions;
+pub use self::cursor::{CursorController, CursorError, CursorGrab};
+pub use self::monitor::{current_monitor, monitors, refresh_rate, MonitorInfo};
+pub use self::multi::WindowSet;
+pub use self::redraw::{RedrawPolicy, RedrawScheduler};
 pub use winit::*;
 use winit::{
     event_loop::{EventLoop, EventLoopWindowTarget},
@@ -7,16 +20,35 @@ use winit::{
 pub fn window_and_event_loop(
     title: &str,
     logical_size: [u32; 2],
+) -> Result<(Window, EventLoop<()>), winit::error::OsError> {
+    window_and_event_loop_with(title, logical_size, WebCanvasOptions::default())
+}
+
+/// Like `window_and_event_loop`, but with control over how the canvas
+/// handles browser input on the web.
+pub fn window_and_event_loop_with(
+    title: &str,
+    logical_size: [u32; 2],
+    web_options: WebCanvasOptions,
 ) -> Result<(Window, EventLoop<()>), winit::error::OsError> {
     let event_loop = EventLoop::<()>::new();
 
-    window(&event_loop, title, logical_size).map(|w| (w, event_loop))
+    window_with(&event_loop, title, logical_size, web_options).map(|w| (w, event_loop))
 }
 
 pub fn window(
     event_loop: &EventLoopWindowTarget<()>,
     title: &str,
     logical_size: [u32; 2],
+) -> Result<Window, winit::error::OsError> {
+    window_with(event_loop, title, logical_size, WebCanvasOptions::default())
+}
+
+pub fn window_with(
+    event_loop: &EventLoopWindowTarget<()>,
+    title: &str,
+    logical_size: [u32; 2],
+    web_options: WebCanvasOptions,
 ) -> Result<Window, winit::error::OsError> {
     let logical_window_size = {
         use winit::dpi::LogicalSize;
@@ -32,15 +64,20 @@ pub fn window(
 
     #[cfg(target_arch = "wasm32")]
     if let Ok(window) = window.as_ref() {
+        let canvas = winit::platform::web::WindowExtWebSys::canvas(window);
         web_sys::window()
             .unwrap()
             .document()
             .unwrap()
             .body()
             .unwrap()
-            .append_child(&winit::platform::web::WindowExtWebSys::canvas(window))
+            .append_child(&canvas)
             .unwrap();
+        self::canvas::set_up_canvas(&canvas, &web_options);
     }
 
+    #[cfg(not(target_arch = "wasm32"))]
+    let _ = web_options;
+
     window
 }
@@ -0,0 +1,147 @@
+use winit::event::{Event, WindowEvent};
+
+/// Whether the player can see the window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Visibility {
+    #[default]
+    Visible,
+    /// Covered up, or in a background browser tab.
+    ///
+    /// Native windows can't tell when they're covered with this version of
+    /// winit, so this only happens on the web.
+    Occluded,
+    /// Only reported on platforms that resize minimized windows to nothing,
+    /// like Windows.
+    Minimized,
+}
+
+/// What a `RedrawScheduler` does while the window isn't `Visible`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BackgroundPolicy {
+    /// Carry on as if the window were visible.
+    Continue,
+    /// Stop redrawing, but wake up this many times per second for a
+    /// background tick, to keep things like audio and networking going.
+    Tick(f32),
+    /// Stop redrawing and sleep until the window is visible again.
+    Sleep,
+}
+
+impl Default for BackgroundPolicy {
+    fn default() -> Self {
+        BackgroundPolicy::Tick(10.)
+    }
+}
+
+/// Works out the window's `Visibility` from its events, and on the web,
+/// from the page's `visibilitychange` events.
+#[derive(Debug, Default)]
+pub(crate) struct VisibilityTracker {
+    minimized: bool,
+    reported: Visibility,
+
+    #[cfg(target_arch = "wasm32")]
+    page: web::PageVisibility,
+}
+
+impl VisibilityTracker {
+    pub fn handle_event<T>(&mut self, event: &Event<T>) {
+        if let Event::WindowEvent {
+            event: WindowEvent::Resized(size),
+            ..
+        } = event
+        {
+            self.minimized = size.width == 0 || size.height == 0;
+        }
+    }
+
+    pub fn visibility(&self) -> Visibility {
+        #[cfg(target_arch = "wasm32")]
+        if self.page.hidden() {
+            return Visibility::Occluded;
+        }
+
+        match self.minimized {
+            true => Visibility::Minimized,
+            false => Visibility::Visible,
+        }
+    }
+
+    /// The new visibility, if it's changed since this was last called.
+    pub fn poll_change(&mut self) -> Option<Visibility> {
+        let visibility = self.visibility();
+        if visibility == self.reported {
+            return None;
+        }
+
+        self.reported = visibility;
+        Some(visibility)
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+mod web {
+    use std::{cell::Cell, rc::Rc};
+
+    use wasm_bindgen::{closure::Closure, JsCast};
+    use web_sys::{Document, Event};
+
+    /// Whether the page is hidden, kept up to date by a `visibilitychange`
+    /// listener.
+    pub struct PageVisibility {
+        document: Document,
+        hidden: Rc<Cell<bool>>,
+        listener: Closure<dyn FnMut(Event)>,
+    }
+
+    impl PageVisibility {
+        pub fn hidden(&self) -> bool {
+            self.hidden.get()
+        }
+    }
+
+    impl Default for PageVisibility {
+        fn default() -> Self {
+            let document = web_sys::window().unwrap().document().unwrap();
+            let hidden = Rc::new(Cell::new(document.hidden()));
+
+            let listener = {
+                let document = document.clone();
+                let hidden = Rc::clone(&hidden);
+                Closure::wrap(Box::new(move |_: Event| {
+                    hidden.set(document.hidden());
+                }) as Box<dyn FnMut(Event)>)
+            };
+
+            document
+                .add_event_listener_with_callback(
+                    "visibilitychange",
+                    listener.as_ref().unchecked_ref(),
+                )
+                .unwrap();
+
+            PageVisibility {
+                document,
+                hidden,
+                listener,
+            }
+        }
+    }
+
+    impl Drop for PageVisibility {
+        fn drop(&mut self) {
+            let _ = self.document.remove_event_listener_with_callback(
+                "visibilitychange",
+                self.listener.as_ref().unchecked_ref(),
+            );
+        }
+    }
+
+    impl std::fmt::Debug for PageVisibility {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            f.debug_struct("PageVisibility")
+                .field("hidden", &self.hidden.get())
+                .finish()
+        }
+    }
+}
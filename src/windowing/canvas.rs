@@ -0,0 +1,103 @@
+/// Keys that scroll the page, as `KeyboardEvent.code` values.
+pub const SCROLL_KEYS: &[&str] = &[
+    "ArrowUp",
+    "ArrowDown",
+    "ArrowLeft",
+    "ArrowRight",
+    "Space",
+    "PageUp",
+    "PageDown",
+    "Home",
+    "End",
+];
+
+/// Stops the browser's own handling of input getting in the way of the
+/// game, on the web. Native windows ignore this.
+///
+/// The mouse wheel is left out, since winit already stops it scrolling
+/// the page while over the canvas.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WebCanvasOptions {
+    /// Stop right-clicks on the canvas opening the context menu.
+    pub prevent_context_menu: bool,
+    /// Focus the canvas when it's clicked, so key presses reach the game
+    /// straight away.
+    pub focus_on_click: bool,
+    /// Keys whose default action is stopped anywhere on the page, as
+    /// `KeyboardEvent.code` values like `"Space"` or `"KeyW"`. Keys typed
+    /// into text fields are left alone.
+    pub captured_keys: Vec<String>,
+}
+
+impl Default for WebCanvasOptions {
+    fn default() -> Self {
+        WebCanvasOptions {
+            prevent_context_menu: true,
+            focus_on_click: true,
+            captured_keys: SCROLL_KEYS.iter().map(|&key| key.to_owned()).collect(),
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+pub(crate) use self::web::set_up_canvas;
+
+#[cfg(target_arch = "wasm32")]
+mod web {
+    use wasm_bindgen::{closure::Closure, JsCast};
+    use web_sys::{Element, Event, EventTarget, HtmlCanvasElement, HtmlElement, KeyboardEvent};
+
+    use super::WebCanvasOptions;
+
+    /// Add listeners for `options`. They're never removed, since the canvas
+    /// lasts as long as the page.
+    pub fn set_up_canvas(canvas: &HtmlCanvasElement, options: &WebCanvasOptions) {
+        if options.prevent_context_menu {
+            listen(canvas, "contextmenu", |event| event.prevent_default());
+        }
+
+        if options.focus_on_click {
+            let focused = canvas.clone();
+            listen(canvas, "mousedown", move |_| {
+                let _ = focused.focus();
+            });
+        }
+
+        if !options.captured_keys.is_empty() {
+            let keys = options.captured_keys.clone();
+            let document = web_sys::window().unwrap().document().unwrap();
+            listen(&document, "keydown", move |event| {
+                let code = match event.dyn_ref::<KeyboardEvent>() {
+                    Some(event) => event.code(),
+                    None => return,
+                };
+                if keys.contains(&code) && !typing_into(event.target()) {
+                    event.prevent_default();
+                }
+            });
+        }
+    }
+
+    fn listen<F: 'static + FnMut(Event)>(target: &EventTarget, name: &str, listener: F) {
+        let listener = Closure::wrap(Box::new(listener) as Box<dyn FnMut(Event)>);
+        target
+            .add_event_listener_with_callback(name, listener.as_ref().unchecked_ref())
+            .unwrap();
+        listener.forget();
+    }
+
+    /// Whether `target` is somewhere text can be typed, like the hidden
+    /// input used for text entry.
+    fn typing_into(target: Option<EventTarget>) -> bool {
+        let element = match target.and_then(|target| target.dyn_into::<Element>().ok()) {
+            Some(element) => element,
+            None => return false,
+        };
+
+        let editable = element
+            .dyn_ref::<HtmlElement>()
+            .map(HtmlElement::is_content_editable)
+            .unwrap_or(false);
+        editable || matches!(element.tag_name().as_str(), "INPUT" | "TEXTAREA" | "SELECT")
+    }
+}
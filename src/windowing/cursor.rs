@@ -0,0 +1,227 @@
+use winit::{
+    event::{Event, WindowEvent},
+    window::Window,
+};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum CursorGrab {
+    #[default]
+    None,
+    /// The cursor can move, but can't leave the window.
+    Confined,
+    /// The cursor stays where it is. Use `DeviceEvent::MouseMotion` for
+    /// movement.
+    Locked,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CursorError {
+    /// Custom cursor images aren't supported by winit on native platforms
+    /// yet. Hide the cursor and draw a sprite at its position instead.
+    NotSupported,
+}
+
+/// Tracks the requested cursor state, so that it can be released when the
+/// window loses focus and restored when it regains it.
+#[derive(Debug)]
+pub struct CursorController {
+    visible: bool,
+    grab: CursorGrab,
+    applied_grab: CursorGrab,
+    focused: bool,
+
+    #[cfg(target_arch = "wasm32")]
+    custom_cursor_css: Option<String>,
+}
+
+impl Default for CursorController {
+    fn default() -> Self {
+        CursorController {
+            visible: true,
+            grab: CursorGrab::None,
+            applied_grab: CursorGrab::None,
+            focused: true,
+
+            #[cfg(target_arch = "wasm32")]
+            custom_cursor_css: None,
+        }
+    }
+}
+
+impl CursorController {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn visible(&self) -> bool {
+        self.visible
+    }
+
+    pub fn set_visible(&mut self, window: &Window, visible: bool) {
+        self.visible = visible;
+        window.set_cursor_visible(visible);
+
+        #[cfg(target_arch = "wasm32")]
+        if visible {
+            self.apply_custom_cursor(window);
+        }
+    }
+
+    /// The grab mode currently in effect, which may differ from the one
+    /// requested if it's unsupported or the window is unfocused.
+    pub fn grab(&self) -> CursorGrab {
+        self.applied_grab
+    }
+
+    /// Request a grab mode, and return the mode actually applied. Where a
+    /// mode is unsupported, the closest supported one is used:
+    ///
+    /// - `Confined` isn't available on macOS or web, so there it falls back
+    ///   to `None`.
+    /// - `Locked` falls back to `Confined` on Windows and Linux.
+    /// - On web, `Locked` uses the pointer lock API, which browsers only
+    ///   allow in response to a click or key press.
+    pub fn set_grab(&mut self, window: &Window, grab: CursorGrab) -> CursorGrab {
+        self.grab = grab;
+        if self.focused {
+            self.applied_grab = apply_grab(window, grab);
+        }
+        self.applied_grab
+    }
+
+    /// Use an image as the cursor, with `hotspot` being the pixel that
+    /// points. On web, browsers may refuse images over 128x128.
+    #[cfg(feature = "image")]
+    pub fn set_custom_cursor(
+        &mut self,
+        window: &Window,
+        image: &image::RgbaImage,
+        hotspot: [u32; 2],
+    ) -> Result<(), CursorError> {
+        #[cfg(target_arch = "wasm32")]
+        {
+            use image::{png::PngEncoder, ColorType};
+
+            let mut png = Vec::new();
+            PngEncoder::new(&mut png)
+                .encode(image, image.width(), image.height(), ColorType::Rgba8)
+                .unwrap();
+
+            self.custom_cursor_css = Some(format!(
+                "url(data:image/png;base64,{}) {} {}, auto",
+                base64(&png),
+                hotspot[0],
+                hotspot[1]
+            ));
+            self.apply_custom_cursor(window);
+            Ok(())
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let _ = (window, image, hotspot);
+            Err(CursorError::NotSupported)
+        }
+    }
+
+    pub fn clear_custom_cursor(&mut self, window: &Window) {
+        #[cfg(target_arch = "wasm32")]
+        {
+            self.custom_cursor_css = None;
+            self.apply_custom_cursor(window);
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        let _ = window;
+    }
+
+    pub fn handle_event<T>(&mut self, window: &Window, event: &Event<T>) {
+        if let Event::WindowEvent {
+            event: WindowEvent::Focused(focused),
+            ..
+        } = event
+        {
+            self.focused = *focused;
+            self.applied_grab = match focused {
+                true => apply_grab(window, self.grab),
+                false => apply_grab(window, CursorGrab::None),
+            };
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn apply_custom_cursor(&self, window: &Window) {
+        use winit::platform::web::WindowExtWebSys;
+
+        if !self.visible {
+            return;
+        }
+
+        let css = self.custom_cursor_css.as_deref().unwrap_or("auto");
+        window.canvas().style().set_property("cursor", css).unwrap();
+    }
+}
+
+fn apply_grab(window: &Window, grab: CursorGrab) -> CursorGrab {
+    #[cfg(target_arch = "wasm32")]
+    {
+        use winit::platform::web::WindowExtWebSys;
+
+        let document = web_sys::window().unwrap().document().unwrap();
+        match grab {
+            CursorGrab::Locked => {
+                window.canvas().request_pointer_lock();
+                CursorGrab::Locked
+            }
+            CursorGrab::None | CursorGrab::Confined => {
+                document.exit_pointer_lock();
+                CursorGrab::None
+            }
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        // winit only offers a single grab mode, which locks on macOS and
+        // confines elsewhere.
+        let native_grab = match cfg!(target_os = "macos") {
+            true => CursorGrab::Locked,
+            false => CursorGrab::Confined,
+        };
+
+        let applied = match grab {
+            CursorGrab::None => CursorGrab::None,
+            CursorGrab::Confined if native_grab == CursorGrab::Locked => CursorGrab::None,
+            _ => native_grab,
+        };
+
+        match window.set_cursor_grab(applied != CursorGrab::None) {
+            Ok(()) => applied,
+            Err(_) => CursorGrab::None,
+        }
+    }
+}
+
+#[cfg(all(target_arch = "wasm32", feature = "image"))]
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut output = String::with_capacity((bytes.len() + 2) / 3 * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                output.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                output.push('=');
+            }
+        }
+    }
+    output
+}
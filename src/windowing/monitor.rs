@@ -0,0 +1,69 @@
+use winit::{event_loop::EventLoopWindowTarget, monitor::MonitorHandle, window::Window};
+
+/// A display, as seen when it was looked up.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MonitorInfo {
+    pub name: Option<String>,
+    /// The resolution, in physical pixels.
+    pub size: [u32; 2],
+    pub scale: f64,
+    /// Every refresh rate the monitor supports at any resolution, in Hz,
+    /// from highest to lowest.
+    pub refresh_rates: Vec<u16>,
+    /// The refresh rate it's probably running at: the highest one it
+    /// supports at its current resolution. winit can't tell which mode is
+    /// actually in use, and browsers don't say at all, so this is `None`
+    /// on the web.
+    pub refresh_rate: Option<u16>,
+}
+
+impl MonitorInfo {
+    pub fn of(monitor: &MonitorHandle) -> Self {
+        let size = monitor.size();
+        let size = [size.width, size.height];
+
+        let mut refresh_rate = None;
+        let mut refresh_rates = vec![];
+        for mode in monitor.video_modes() {
+            let rate = mode.refresh_rate();
+            let mode_size = mode.size();
+            if [mode_size.width, mode_size.height] == size {
+                refresh_rate = refresh_rate.max(Some(rate));
+            }
+            refresh_rates.push(rate);
+        }
+        refresh_rates.sort_unstable_by(|a, b| b.cmp(a));
+        refresh_rates.dedup();
+
+        MonitorInfo {
+            name: monitor.name(),
+            size,
+            scale: monitor.scale_factor(),
+            refresh_rates,
+            refresh_rate,
+        }
+    }
+}
+
+/// Every monitor connected to the system.
+pub fn monitors<T>(event_loop: &EventLoopWindowTarget<T>) -> Vec<MonitorInfo> {
+    event_loop
+        .available_monitors()
+        .map(|monitor| MonitorInfo::of(&monitor))
+        .collect()
+}
+
+/// The monitor the window is mostly on.
+pub fn current_monitor(window: &Window) -> Option<MonitorInfo> {
+    window
+        .current_monitor()
+        .map(|monitor| MonitorInfo::of(&monitor))
+}
+
+/// The refresh rate of the window's monitor, in Hz, if it can be found.
+/// This is a guess from the monitor's modes, not the mode it's actually in
+/// (see `MonitorInfo::refresh_rate`), so a 144Hz monitor set to 60Hz is
+/// reported as 144Hz.
+pub fn refresh_rate(window: &Window) -> Option<u16> {
+    current_monitor(window)?.refresh_rate
+}
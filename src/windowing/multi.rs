@@ -0,0 +1,114 @@
+use std::collections::HashMap;
+
+use winit::{
+    error::OsError,
+    event::{Event, WindowEvent},
+    event_loop::{ControlFlow, EventLoopWindowTarget},
+    window::{Window, WindowId},
+};
+
+/// Owns a primary window and any number of secondary ones, e.g. for
+/// editor panels alongside the game view.
+///
+/// Events already carry the `WindowId` they're for. Pass them all to
+/// `handle_event` so closing a window is handled consistently: closing a
+/// secondary window just closes it, and closing the primary window exits
+/// the app unless `set_exit_on_primary_close(false)` was called.
+///
+/// NOTE: Each window needs its own `DrawContext`, which for now also means
+/// its own GPU device and copy of the atlas texture.
+pub struct WindowSet {
+    primary: WindowId,
+    windows: HashMap<WindowId, Window>,
+    exit_on_primary_close: bool,
+}
+
+impl WindowSet {
+    pub fn new(primary: Window) -> Self {
+        let primary_id = primary.id();
+        let mut windows = HashMap::new();
+        windows.insert(primary_id, primary);
+
+        WindowSet {
+            primary: primary_id,
+            windows,
+            exit_on_primary_close: true,
+        }
+    }
+
+    pub fn set_exit_on_primary_close(&mut self, exit: bool) {
+        self.exit_on_primary_close = exit;
+    }
+
+    /// Open another window. This can be done at any point while the event
+    /// loop is running.
+    pub fn create(
+        &mut self,
+        event_loop: &EventLoopWindowTarget<()>,
+        title: &str,
+        logical_size: [u32; 2],
+    ) -> Result<WindowId, OsError> {
+        let window = super::window(event_loop, title, logical_size)?;
+        let id = window.id();
+        self.windows.insert(id, window);
+        Ok(id)
+    }
+
+    pub fn primary_id(&self) -> WindowId {
+        self.primary
+    }
+
+    /// The primary window, unless it's been closed.
+    pub fn primary(&self) -> Option<&Window> {
+        self.windows.get(&self.primary)
+    }
+
+    pub fn is_primary(&self, id: WindowId) -> bool {
+        id == self.primary
+    }
+
+    pub fn get(&self, id: WindowId) -> Option<&Window> {
+        self.windows.get(&id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (WindowId, &Window)> {
+        self.windows.iter().map(|(&id, window)| (id, window))
+    }
+
+    pub fn request_redraw_all(&self) {
+        for window in self.windows.values() {
+            window.request_redraw();
+        }
+    }
+
+    /// Close windows when requested, returning the window that was closed.
+    ///
+    /// The window is handed back rather than dropped, because its draw
+    /// context's surface has to be dropped first. Drop the context, then
+    /// the window.
+    pub fn handle_event<T>(
+        &mut self,
+        event: &Event<T>,
+        control_flow: &mut ControlFlow,
+    ) -> Option<(WindowId, Window)> {
+        if let Event::WindowEvent {
+            window_id,
+            event: WindowEvent::CloseRequested,
+        } = event
+        {
+            if *window_id == self.primary && self.exit_on_primary_close {
+                *control_flow = ControlFlow::Exit;
+            }
+
+            let window = self.windows.remove(window_id)?;
+
+            if self.windows.is_empty() {
+                *control_flow = ControlFlow::Exit;
+            }
+
+            return Some((*window_id, window));
+        }
+
+        None
+    }
+}
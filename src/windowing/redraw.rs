@@ -0,0 +1,273 @@
+use std::time::Duration;
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+
+use winit::{
+    event::{Event, WindowEvent},
+    event_loop::ControlFlow,
+    window::Window,
+};
+
+use crate::windowing::background::{BackgroundPolicy, Visibility, VisibilityTracker};
+
+/// When a `RedrawScheduler` redraws the window.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum RedrawPolicy {
+    /// Redraw as often as possible (usually at vsync).
+    #[default]
+    Continuous,
+    /// Only redraw after input, or when the game asks with `request_frame`.
+    /// The event loop sleeps in between, which saves a lot of power on
+    /// static screens like menus.
+    OnEvent,
+    /// Redraw continuously, but no more than this many times per second.
+    AtMost(f32),
+}
+
+/// Decides when to redraw the window, and how long the event loop can
+/// sleep, according to a `RedrawPolicy`.
+///
+/// Pass every event to `handle_event`, then call `update` on
+/// `Event::MainEventsCleared` instead of calling `window.request_redraw()`.
+///
+/// On wasm, the browser drives the event loop with animation frames, so
+/// rather than sleeping, frames are skipped until a redraw is due.
+///
+/// While the window is minimized or hidden, the `BackgroundPolicy` takes
+/// over: by default, redraws stop and `take_background_tick` returns true
+/// 10 times a second, so the game can keep sending `update_state` to its
+/// audio without spinning the CPU. Browsers slow timers in background tabs
+/// down to around once a second.
+#[derive(Debug)]
+pub struct RedrawScheduler {
+    policy: RedrawPolicy,
+    background: BackgroundPolicy,
+    visibility: VisibilityTracker,
+    in_background: bool,
+    frame_requested: bool,
+    wake_at: Option<f64>,
+    last_redraw: Option<f64>,
+    last_tick: Option<f64>,
+    tick_due: bool,
+
+    #[cfg(not(target_arch = "wasm32"))]
+    epoch: Instant,
+}
+
+impl RedrawScheduler {
+    pub fn new(policy: RedrawPolicy) -> Self {
+        RedrawScheduler {
+            policy,
+            background: BackgroundPolicy::default(),
+            visibility: VisibilityTracker::default(),
+            in_background: false,
+            frame_requested: true,
+            wake_at: None,
+            last_redraw: None,
+            last_tick: None,
+            tick_due: false,
+
+            #[cfg(not(target_arch = "wasm32"))]
+            epoch: Instant::now(),
+        }
+    }
+
+    pub fn policy(&self) -> RedrawPolicy {
+        self.policy
+    }
+
+    /// Change the policy, e.g. when moving between a menu and gameplay. The
+    /// next frame is drawn straight away.
+    pub fn set_policy(&mut self, policy: RedrawPolicy) {
+        self.policy = policy;
+        self.frame_requested = true;
+    }
+
+    pub fn background_policy(&self) -> BackgroundPolicy {
+        self.background
+    }
+
+    pub fn set_background_policy(&mut self, background: BackgroundPolicy) {
+        self.background = background;
+    }
+
+    pub fn visibility(&self) -> Visibility {
+        self.visibility.visibility()
+    }
+
+    /// The window's new visibility, if it's changed since this was last
+    /// called, e.g. to pause the game when it's minimized.
+    pub fn take_visibility_change(&mut self) -> Option<Visibility> {
+        self.visibility.poll_change()
+    }
+
+    /// Whether a background tick is due. This only happens while the
+    /// window isn't visible, with `BackgroundPolicy::Tick`. Check it after
+    /// `update`.
+    pub fn take_background_tick(&mut self) -> bool {
+        std::mem::take(&mut self.tick_due)
+    }
+
+    /// Ask for another frame, e.g. while something is animating. This only
+    /// matters with `RedrawPolicy::OnEvent`.
+    pub fn request_frame(&mut self) {
+        self.frame_requested = true;
+    }
+
+    /// Ask for a frame after `delay`, e.g. for a timer or blinking cursor.
+    pub fn request_frame_after(&mut self, delay: Duration) {
+        let time = self.now() + delay.as_secs_f64();
+        self.wake_at = Some(self.wake_at.map_or(time, |wake_at| wake_at.min(time)));
+    }
+
+    /// Request a frame for input, and anything else that changes what's
+    /// on screen, like resizing.
+    pub fn handle_event<T>(&mut self, event: &Event<T>) {
+        self.visibility.handle_event(event);
+
+        let event = match event {
+            Event::WindowEvent { event, .. } => event,
+            _ => return,
+        };
+
+        match event {
+            WindowEvent::Resized(_)
+            | WindowEvent::ScaleFactorChanged { .. }
+            | WindowEvent::Focused(_)
+            | WindowEvent::ReceivedCharacter(_)
+            | WindowEvent::KeyboardInput { .. }
+            | WindowEvent::ModifiersChanged(_)
+            | WindowEvent::CursorMoved { .. }
+            | WindowEvent::CursorEntered { .. }
+            | WindowEvent::CursorLeft { .. }
+            | WindowEvent::MouseWheel { .. }
+            | WindowEvent::MouseInput { .. }
+            | WindowEvent::Touch(_) => self.frame_requested = true,
+            _ => (),
+        }
+    }
+
+    /// Request a redraw of `window` if one is due, and set how long the
+    /// event loop should wait for the next one.
+    ///
+    /// Doesn't change `control_flow` if it's `ControlFlow::Exit`.
+    pub fn update(&mut self, window: &Window, control_flow: &mut ControlFlow) {
+        let now = self.now();
+
+        let visible = self.visibility.visibility() == Visibility::Visible;
+        match (visible, self.background) {
+            (true, _) | (false, BackgroundPolicy::Continue) => {
+                // Draw the first frame back straight away
+                if std::mem::take(&mut self.in_background) {
+                    self.frame_requested = true;
+                    self.last_tick = None;
+                }
+            }
+            (false, background) => {
+                self.in_background = true;
+
+                let next = match background {
+                    BackgroundPolicy::Tick(rate) => {
+                        let interval = 1. / rate.max(0.001) as f64;
+                        match self.last_tick {
+                            Some(last) if now - last < interval => Some(last + interval),
+                            _ => {
+                                self.tick_due = true;
+                                self.last_tick = Some(now);
+                                Some(now + interval)
+                            }
+                        }
+                    }
+                    _ => None,
+                };
+
+                if *control_flow != ControlFlow::Exit {
+                    *control_flow = self.background_control_flow(now, next);
+                }
+                return;
+            }
+        }
+
+        if let Some(wake_at) = self.wake_at {
+            if now >= wake_at {
+                self.wake_at = None;
+                self.frame_requested = true;
+            }
+        }
+
+        let (redraw, next) = match self.policy {
+            RedrawPolicy::Continuous => (true, Some(now)),
+            // The loop wakes straight after drawing, so that a `request_frame`
+            // made while handling `RedrawRequested` gets the next frame
+            RedrawPolicy::OnEvent => match self.frame_requested {
+                true => (true, Some(now)),
+                false => (false, self.wake_at),
+            },
+            RedrawPolicy::AtMost(fps) => {
+                let interval = 1. / fps.max(0.001) as f64;
+                match self.last_redraw {
+                    Some(last) if now - last < interval => (false, Some(last + interval)),
+                    _ => (true, Some(now + interval)),
+                }
+            }
+        };
+
+        if redraw {
+            window.request_redraw();
+            self.frame_requested = false;
+            self.last_redraw = Some(now);
+        }
+
+        if *control_flow != ControlFlow::Exit {
+            *control_flow = self.control_flow(now, next);
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn now(&self) -> f64 {
+        self.epoch.elapsed().as_secs_f64()
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn now(&self) -> f64 {
+        web_sys::window()
+            .and_then(|window| window.performance())
+            .map(|performance| performance.now() / 1000.)
+            .unwrap_or(0.)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn control_flow(&self, now: f64, next: Option<f64>) -> ControlFlow {
+        match next {
+            Some(next) if next <= now => ControlFlow::Poll,
+            Some(next) => ControlFlow::WaitUntil(self.epoch + Duration::from_secs_f64(next)),
+            None => ControlFlow::Wait,
+        }
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn control_flow(&self, _now: f64, next: Option<f64>) -> ControlFlow {
+        match next {
+            Some(_) => ControlFlow::Poll,
+            None => ControlFlow::Wait,
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn background_control_flow(&self, now: f64, next: Option<f64>) -> ControlFlow {
+        self.control_flow(now, next)
+    }
+
+    /// Animation frames stop in background tabs, so this has to use a
+    /// timer instead.
+    #[cfg(target_arch = "wasm32")]
+    fn background_control_flow(&self, now: f64, next: Option<f64>) -> ControlFlow {
+        match next {
+            Some(next) => ControlFlow::WaitUntil(
+                instant::Instant::now() + Duration::from_secs_f64((next - now).max(0.)),
+            ),
+            None => ControlFlow::Wait,
+        }
+    }
+}